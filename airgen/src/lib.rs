@@ -5,7 +5,10 @@
 use std::collections::BTreeMap;
 
 use powdr_ast::{
-    asm_analysis::{AnalysisASMFile, Item, LinkDefinitionStatement, SubmachineDeclaration},
+    asm_analysis::{
+        AnalysisASMFile, Item, LinkDefinitionStatement, MachineParamDeclaration,
+        SubmachineDeclaration,
+    },
     object::{Link, LinkFrom, LinkTo, Location, Object, Operation, PILGraph, TypeOrExpression},
     parsed::{
         asm::{parse_absolute_path, AbsoluteSymbolPath, CallableRef},
@@ -38,39 +41,63 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
         }
     };
 
+    assert!(
+        input.items[&main_ty]
+            .try_to_machine()
+            .unwrap()
+            .params
+            .is_empty(),
+        "the main machine cannot have parameters"
+    );
+
     // get a list of all machines to instantiate. The order does not matter.
-    let mut queue = vec![(main_location.clone(), main_ty.clone())];
+    let mut queue = vec![(main_location.clone(), main_ty.clone(), BTreeMap::new())];
 
     let mut instances = vec![];
 
-    while let Some((location, ty)) = queue.pop() {
+    while let Some((location, ty, params)) = queue.pop() {
         let machine = input.items.get(&ty).unwrap().try_to_machine().unwrap();
 
         queue.extend(machine.submachines.iter().map(|def| {
+            let submachine = input.items.get(&def.ty).unwrap().try_to_machine().unwrap();
+            // the parameters of the submachine refer to instances which already exist
+            let submachine_params = submachine
+                .params
+                .iter()
+                .zip(&def.args)
+                .map(|(param, arg)| {
+                    (
+                        param.name.clone(),
+                        instance_location(&location, &params, arg),
+                    )
+                })
+                .collect();
             (
                 // get the absolute name for this submachine
                 location.clone().join(def.name.clone()),
                 // get its type
                 def.ty.clone(),
+                submachine_params,
             )
         }));
 
-        instances.push((location, ty));
+        instances.push((location, ty, params));
     }
 
     // count incoming permutations for each machine.
     let mut incoming_permutations = instances
         .iter()
-        .map(|(location, _)| (location.clone(), 0))
+        .map(|(location, _, _)| (location.clone(), 0))
         .collect();
 
     // visit the tree compiling the machines
     let mut objects: BTreeMap<_, _> = instances
         .into_iter()
-        .map(|(location, ty)| {
+        .map(|(location, ty, params)| {
             let object = ASMPILConverter::convert_machine(
                 &location,
                 &ty,
+                &params,
                 &input,
                 &mut incoming_permutations,
             );
@@ -125,6 +152,12 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
         .filter_map(|(n, v)| match v {
             Item::Expression(e) => Some((n, TypeOrExpression::Expression(e))),
             Item::TypeDeclaration(type_decl) => Some((n, TypeOrExpression::Type(type_decl))),
+            Item::TraitDeclaration(trait_decl) => {
+                Some((n, TypeOrExpression::TraitDeclaration(trait_decl)))
+            }
+            Item::TraitImplementation(trait_impl) => {
+                Some((n, TypeOrExpression::TraitImplementation(trait_impl)))
+            }
             _ => None,
         })
         .collect();
@@ -137,13 +170,29 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
     }
 }
 
+/// Returns the location of the instance `name` referenced in the machine at `location`,
+/// where `params` are the locations of the instances passed to its parameters.
+fn instance_location(
+    location: &Location,
+    params: &BTreeMap<String, Location>,
+    name: &str,
+) -> Location {
+    params
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| location.clone().join(name))
+}
+
 struct ASMPILConverter<'a> {
     /// Location in the machine tree
     location: &'a Location,
+    /// Locations of the instances passed to the machine parameters
+    params: &'a BTreeMap<String, Location>,
     /// Input definitions and machines.
     items: &'a BTreeMap<AbsoluteSymbolPath, Item>,
     pil: Vec<PilStatement>,
     submachines: Vec<SubmachineDeclaration>,
+    machine_params: Vec<MachineParamDeclaration>,
     /// keeps track of the total count of incoming permutations for a given machine.
     incoming_permutations: &'a mut BTreeMap<Location, u64>,
 }
@@ -151,14 +200,17 @@ struct ASMPILConverter<'a> {
 impl<'a> ASMPILConverter<'a> {
     fn new(
         location: &'a Location,
+        params: &'a BTreeMap<String, Location>,
         input: &'a AnalysisASMFile,
         incoming_permutations: &'a mut BTreeMap<Location, u64>,
     ) -> Self {
         Self {
             location,
+            params,
             items: &input.items,
             pil: Default::default(),
             submachines: Default::default(),
+            machine_params: Default::default(),
             incoming_permutations,
         }
     }
@@ -170,10 +222,11 @@ impl<'a> ASMPILConverter<'a> {
     fn convert_machine(
         location: &'a Location,
         ty: &'a AbsoluteSymbolPath,
+        params: &'a BTreeMap<String, Location>,
         input: &'a AnalysisASMFile,
         incoming_permutations: &'a mut BTreeMap<Location, u64>,
     ) -> Object {
-        Self::new(location, input, incoming_permutations).convert_machine_inner(ty)
+        Self::new(location, params, input, incoming_permutations).convert_machine_inner(ty)
     }
 
     fn convert_machine_inner(mut self, ty: &AbsoluteSymbolPath) -> Object {
@@ -185,6 +238,7 @@ impl<'a> ASMPILConverter<'a> {
         let degree = input.degree.map(|s| s.degree);
 
         self.submachines = input.submachines;
+        self.machine_params = input.params;
 
         // machines should only have constraints, operations and links at this point
         assert!(input.instructions.is_empty());
@@ -232,20 +286,23 @@ impl<'a> ASMPILConverter<'a> {
             flag: flag.clone(),
         };
 
-        // get the machine type name for this submachine from the submachine declarations
+        // get the machine type name for this instance from the submachine or parameter declarations
         let instance_ty_name = self
             .submachines
             .iter()
-            .find(|s| s.name == instance)
+            .map(|s| (&s.name, &s.ty))
+            .chain(self.machine_params.iter().map(|p| (&p.name, &p.ty)))
+            .find(|(name, _)| **name == instance)
             .unwrap()
-            .ty
+            .1
             .clone();
         // get the machine type from the machine map
         let Item::Machine(instance_ty) = self.items.get(&instance_ty_name).unwrap() else {
             panic!();
         };
-        // get the instance location from the current location joined with the instance name
-        let instance_location = self.location.clone().join(instance);
+        // get the instance location from the current location joined with the instance name,
+        // or from the location of the instance passed to the parameter
+        let instance_location = instance_location(self.location, self.params, &instance);
 
         let mut selector_idx = None;
 
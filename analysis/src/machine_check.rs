@@ -1,14 +1,14 @@
 #![deny(clippy::print_stdout)]
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use powdr_ast::{
     asm_analysis::{
         AnalysisASMFile, AssignmentStatement, CallableSymbolDefinitions, DebugDirective,
         DegreeStatement, FunctionBody, FunctionStatements, FunctionSymbol, Instruction,
         InstructionDefinitionStatement, InstructionStatement, Item, LabelStatement,
-        LinkDefinitionStatement, Machine, OperationSymbol, RegisterDeclarationStatement,
-        RegisterTy, Return, SubmachineDeclaration,
+        LinkDefinitionStatement, Machine, MachineParamDeclaration, OperationSymbol,
+        PcJumpStatement, RegisterDeclarationStatement, RegisterTy, Return, SubmachineDeclaration,
     },
     parsed::{
         self,
@@ -25,9 +25,55 @@ use powdr_ast::{
 pub fn check(file: ASMProgram) -> Result<AnalysisASMFile, Vec<String>> {
     let ctx = AbsoluteSymbolPath::default();
     let machines = TypeChecker::default().check_module(file.main, &ctx)?;
-    Ok(AnalysisASMFile {
+    let file = AnalysisASMFile {
         items: machines.into_iter().collect(),
-    })
+    };
+    check_submachine_arguments(&file)?;
+    Ok(file)
+}
+
+/// Checks that the submachines of each machine are instantiated with
+/// arguments matching the parameters of their types.
+fn check_submachine_arguments(file: &AnalysisASMFile) -> Result<(), Vec<String>> {
+    let mut errors = vec![];
+    for (ctx, machine) in file.machines() {
+        let instance_type = |name: &String| {
+            machine
+                .params
+                .iter()
+                .map(|p| (&p.name, &p.ty))
+                .chain(machine.submachines.iter().map(|s| (&s.name, &s.ty)))
+                .find_map(|(n, ty)| (n == name).then_some(ty))
+        };
+        for s in &machine.submachines {
+            let Some(Item::Machine(ty)) = file.items.get(&s.ty) else {
+                continue;
+            };
+            if s.args.len() != ty.params.len() {
+                errors.push(format!(
+                    "Submachine `{}` in machine {ctx} expects {} argument(s), but got {}",
+                    s.name,
+                    ty.params.len(),
+                    s.args.len()
+                ));
+                continue;
+            }
+            for (arg, param) in s.args.iter().zip(&ty.params) {
+                match instance_type(arg) {
+                    Some(arg_ty) if arg_ty != &param.ty => errors.push(format!(
+                        "Argument `{arg}` of submachine `{}` in machine {ctx} has type {arg_ty}, but parameter `{}` expects {}",
+                        s.name, param.name, param.ty
+                    )),
+                    _ => {}
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
 #[derive(Default)]
@@ -43,12 +89,21 @@ impl TypeChecker {
 
         let mut degree = None;
         let mut call_selectors = None;
+        let mut pc_jump = None;
         let mut registers = vec![];
         let mut pil = vec![];
         let mut instructions = vec![];
         let mut links = vec![];
         let mut callable = CallableSymbolDefinitions::default();
         let mut submachines = vec![];
+        let params = machine
+            .params
+            .into_iter()
+            .map(|param| MachineParamDeclaration {
+                name: param.name,
+                ty: AbsoluteSymbolPath::default().join(param.ty),
+            })
+            .collect::<Vec<_>>();
 
         for s in machine.statements {
             match s {
@@ -66,6 +121,13 @@ impl TypeChecker {
                         call_selectors = Some(sel);
                     }
                 }
+                MachineStatement::PcJump(_, flag, target) => {
+                    if pc_jump.is_some() {
+                        errors.push(format!("Machine {ctx} already has a pc_jump"));
+                    } else {
+                        pc_jump = Some(PcJumpStatement { flag, target });
+                    }
+                }
                 MachineStatement::RegisterDeclaration(source, name, flag) => {
                     let ty = match flag {
                         Some(RegisterFlag::IsAssignment) => RegisterTy::Assignment,
@@ -103,10 +165,11 @@ impl TypeChecker {
                 MachineStatement::Pil(_source, statement) => {
                     pil.push(statement);
                 }
-                MachineStatement::Submachine(_, ty, name) => {
+                MachineStatement::Submachine(_, ty, name, args) => {
                     submachines.push(SubmachineDeclaration {
                         name,
                         ty: AbsoluteSymbolPath::default().join(ty),
+                        args,
                     });
                 }
                 MachineStatement::FunctionDeclaration(source, name, params, statements) => {
@@ -228,6 +291,13 @@ impl TypeChecker {
                 ));
             }
 
+            if pc_jump.is_some() {
+                errors.push(format!(
+                    "Machine {} should not have a pc_jump as it does not have a pc",
+                    ctx
+                ));
+            }
+
             for f in callable.function_definitions() {
                 errors.push(format!(
                     "Machine {} should not have functions as it does not have a pc, found `{}`",
@@ -278,6 +348,27 @@ impl TypeChecker {
             errors.push(format!("Machine {} cannot have more than one pc", ctx));
         }
 
+        let mut instance_names = BTreeSet::new();
+        for name in params
+            .iter()
+            .map(|p| &p.name)
+            .chain(submachines.iter().map(|s| &s.name))
+        {
+            if !instance_names.insert(name) {
+                errors.push(format!("Duplicate instance `{name}` in machine {ctx}"));
+            }
+        }
+        for s in &submachines {
+            for arg in &s.args {
+                if !instance_names.contains(arg) {
+                    errors.push(format!(
+                        "Argument `{arg}` of submachine `{}` in machine {ctx} is neither a submachine nor a parameter",
+                        s.name
+                    ));
+                }
+            }
+        }
+
         let machine = Machine {
             degree,
             latch,
@@ -287,12 +378,14 @@ impl TypeChecker {
                 .iter()
                 .enumerate()
                 .find_map(|(i, r)| (r.ty.is_pc()).then_some(i)),
+            pc_jump,
             registers,
             links,
             instructions,
             pil,
             callable,
             submachines,
+            params,
         };
 
         if !errors.is_empty() {
@@ -310,6 +403,7 @@ impl TypeChecker {
         let mut errors = vec![];
 
         let mut res: BTreeMap<AbsoluteSymbolPath, Item> = BTreeMap::default();
+        let mut trait_impl_count = 0;
 
         for m in module.statements {
             match m {
@@ -355,8 +449,22 @@ impl TypeChecker {
                                 Item::TypeDeclaration(enum_decl),
                             );
                         }
+                        asm::SymbolValue::TraitDeclaration(trait_decl) => {
+                            res.insert(
+                                ctx.clone().with_part(&name),
+                                Item::TraitDeclaration(trait_decl),
+                            );
+                        }
                     }
                 }
+                ModuleStatement::TraitImplementation(trait_impl) => {
+                    // `#` cannot appear in identifiers, so this does not clash with any symbol.
+                    res.insert(
+                        ctx.clone().with_part(&format!("impl#{trait_impl_count}")),
+                        Item::TraitImplementation(trait_impl),
+                    );
+                    trait_impl_count += 1;
+                }
             }
         }
 
@@ -519,4 +627,100 @@ machine Main {
             ]),
         );
     }
+
+    #[test]
+    fn constrained_machine_has_no_pc_jump() {
+        let src = r#"
+machine Arith(latch, _) {
+   pc_jump flag, 2;
+}
+"#;
+        expect_check_str(
+            src,
+            Err(vec![
+                "Machine ::Arith should not have a pc_jump as it does not have a pc",
+            ]),
+        );
+    }
+
+    #[test]
+    fn submachine_argument_count() {
+        let src = r#"
+machine Inc(latch, _) {
+   operation inc x -> y;
+}
+
+machine Wrapper(inc: Inc)(latch, _) {
+   operation wrap x -> y;
+}
+
+machine Main {
+   reg pc[@pc];
+
+   Inc inc;
+   Wrapper wrapper;
+}
+"#;
+        expect_check_str(
+            src,
+            Err(vec![
+                "Submachine `wrapper` in machine ::Main expects 1 argument(s), but got 0",
+            ]),
+        );
+    }
+
+    #[test]
+    fn submachine_argument_type() {
+        let src = r#"
+machine Inc(latch, _) {
+   operation inc x -> y;
+}
+
+machine Dec(latch, _) {
+   operation dec x -> y;
+}
+
+machine Wrapper(inc: Inc)(latch, _) {
+   operation wrap x -> y;
+}
+
+machine Main {
+   reg pc[@pc];
+
+   Dec dec;
+   Wrapper wrapper(dec);
+}
+"#;
+        expect_check_str(
+            src,
+            Err(vec![
+                "Argument `dec` of submachine `wrapper` in machine ::Main has type ::Dec, but parameter `inc` expects ::Inc",
+            ]),
+        );
+    }
+
+    #[test]
+    fn submachine_argument_unknown() {
+        let src = r#"
+machine Inc(latch, _) {
+   operation inc x -> y;
+}
+
+machine Wrapper(inc: Inc)(latch, _) {
+   operation wrap x -> y;
+}
+
+machine Main {
+   reg pc[@pc];
+
+   Wrapper wrapper(inc);
+}
+"#;
+        expect_check_str(
+            src,
+            Err(vec![
+                "Argument `inc` of submachine `wrapper` in machine ::Main is neither a submachine nor a parameter",
+            ]),
+        );
+    }
 }
@@ -134,7 +134,10 @@ impl RomBatcher {
     pub fn batch(&mut self, mut asm_file: AnalysisASMFile) -> AnalysisASMFile {
         for (name, machine) in asm_file.items.iter_mut().filter_map(|(n, m)| match m {
             Item::Machine(m) => Some((n, m)),
-            Item::Expression(_) | Item::TypeDeclaration(_) => None,
+            Item::Expression(_)
+            | Item::TypeDeclaration(_)
+            | Item::TraitDeclaration(_)
+            | Item::TraitImplementation(_) => None,
         }) {
             self.extract_batches(name, machine);
         }
@@ -220,7 +220,7 @@ pub fn generate_machine_rom<T: FieldElement>(mut machine: Machine) -> (Machine,
         machine.pil.extend([
             // inject the operation_id
             parse_pil_statement(&format!(
-                "col witness {operation_id}(i) query std::prover::Query::Hint({sink_id});"
+                "col witness {operation_id}(i) query std::prover::hint({sink_id});"
             )),
             // inject last step
             parse_pil_statement(&format!("col constant {last_step} = [0]* + [1];")),
@@ -267,7 +267,10 @@ mod tests {
             .into_iter()
             .filter_map(|(name, m)| match m {
                 Item::Machine(m) => Some((name, generate_machine_rom::<T>(m))),
-                Item::Expression(_) | Item::TypeDeclaration(_) => None,
+                Item::Expression(_)
+                | Item::TypeDeclaration(_)
+                | Item::TraitDeclaration(_)
+                | Item::TraitImplementation(_) => None,
             })
             .collect()
     }
@@ -6,7 +6,8 @@ use powdr_ast::{
     asm_analysis::{
         AssignmentStatement, Batch, DebugDirective, FunctionStatement,
         InstructionDefinitionStatement, InstructionStatement, LabelStatement,
-        LinkDefinitionStatement, Machine, RegisterDeclarationStatement, RegisterTy, Rom,
+        LinkDefinitionStatement, Machine, PcJumpStatement, RegisterDeclarationStatement,
+        RegisterTy, Rom,
     },
     parsed::{
         asm::{CallableRef, InstructionBody, InstructionParams},
@@ -120,6 +121,8 @@ impl<T: FieldElement> VMConverter<T> {
             ),
         ));
 
+        let pc_jump = input.pc_jump.take();
+
         self.pil.extend(
             self.registers
                 .iter()
@@ -134,21 +137,38 @@ impl<T: FieldElement> VMConverter<T> {
                                 // this may not be optimal for backends which support higher degree constraints
                                 let pc_update_name = format!("{}_update", name);
 
-                                vec![
-                                    PilStatement::PolynomialDefinition(
-                                        SourceRef::unknown(),
-                                        pc_update_name.to_string(),
-                                        rhs,
-                                    ),
-                                    PilStatement::Expression(
-                                        SourceRef::unknown(),
-                                        build::identity(
-                                            lhs,
-                                            (Expression::from(1) - next_reference("first_step"))
-                                                * direct_reference(pc_update_name),
-                                        ),
+                                let mut statements = vec![PilStatement::PolynomialDefinition(
+                                    SourceRef::unknown(),
+                                    pc_update_name.to_string(),
+                                    rhs,
+                                )];
+
+                                // If the machine declares a `pc_jump`, the pc is set to the target
+                                // instead of being updated on rows where the flag is set.
+                                let pc_next_name =
+                                    if let Some(PcJumpStatement { flag, target }) = &pc_jump {
+                                        let pc_next_name = format!("{}_next", name);
+                                        statements.push(PilStatement::PolynomialDefinition(
+                                            SourceRef::unknown(),
+                                            pc_next_name.clone(),
+                                            (Expression::from(1) - direct_reference(flag))
+                                                * direct_reference(pc_update_name)
+                                                + direct_reference(flag) * target.clone(),
+                                        ));
+                                        pc_next_name
+                                    } else {
+                                        pc_update_name
+                                    };
+
+                                statements.push(PilStatement::Expression(
+                                    SourceRef::unknown(),
+                                    build::identity(
+                                        lhs,
+                                        (Expression::from(1) - next_reference("first_step"))
+                                            * direct_reference(pc_next_name),
                                     ),
-                                ]
+                                ));
+                                statements
                             }
                             // Un-constrain read-only registers when calling `_reset`
                             ReadOnly => {
@@ -735,6 +755,8 @@ impl<T: FieldElement> VMConverter<T> {
             Expression::PublicReference(_) => panic!(),
             Expression::IndexAccess(_) => panic!(),
             Expression::FunctionCall(_) => panic!(),
+            Expression::StructExpression(_) => panic!(),
+            Expression::FieldAccess(_) => panic!(),
             Expression::Reference(reference) => {
                 // TODO check it actually is a register
                 let name = reference.try_to_identifier().unwrap();
@@ -933,7 +955,12 @@ impl<T: FieldElement> VMConverter<T> {
                                 .unwrap()
                                 .push(MatchArm {
                                     pattern: Pattern::Number(i.into()),
-                                    value: expr.clone(),
+                                    value: Expression::FunctionCall(FunctionCall {
+                                        function: Box::new(absolute_reference(
+                                            "::std::utils::Option::Some",
+                                        )),
+                                        arguments: vec![expr.clone()],
+                                    }),
                                 });
                         }
                     }
@@ -975,8 +1002,13 @@ impl<T: FieldElement> VMConverter<T> {
             .assignment_register_names()
             .map(|reg| {
                 let free_value = format!("{reg}_free_value");
-                let prover_query_arms = free_value_query_arms.remove(reg).unwrap();
-                let prover_query = (!prover_query_arms.is_empty()).then_some({
+                let mut prover_query_arms = free_value_query_arms.remove(reg).unwrap();
+                let prover_query = (!prover_query_arms.is_empty()).then(|| {
+                    // On all other lines, the query does not provide a value.
+                    prover_query_arms.push(MatchArm {
+                        pattern: Pattern::CatchAll,
+                        value: absolute_reference("::std::utils::Option::None"),
+                    });
                     FunctionDefinition::Expression(Expression::LambdaExpression(LambdaExpression {
                         kind: FunctionKind::Query,
                         params: vec![Pattern::Variable("__i".to_string())],
@@ -1299,4 +1331,45 @@ machine Main {
 ";
         parse_analyze_and_compile::<GoldilocksField>(asm);
     }
+
+    #[test]
+    fn pc_jump() {
+        let asm = r"
+machine Main {
+  degree 8;
+  reg pc[@pc];
+  reg X[<=];
+  reg A;
+
+  pol commit jump;
+  pc_jump jump, 2;
+
+  function main {
+    A <=X= 1;
+  }
+}
+";
+        let compiled = parse_analyze_and_compile::<GoldilocksField>(asm).to_string();
+        assert!(compiled.contains("pol pc_next = (((1 - jump) * pc_update) + (jump * 2));"));
+        assert!(compiled.contains("(pc' = ((1 - first_step') * pc_next));"));
+    }
+
+    #[test]
+    fn no_pc_jump() {
+        let asm = r"
+machine Main {
+  degree 8;
+  reg pc[@pc];
+  reg X[<=];
+  reg A;
+
+  function main {
+    A <=X= 1;
+  }
+}
+";
+        let compiled = parse_analyze_and_compile::<GoldilocksField>(asm).to_string();
+        assert!(!compiled.contains("pc_next"));
+        assert!(compiled.contains("(pc' = ((1 - first_step') * pc_update));"));
+    }
 }
@@ -21,7 +21,20 @@ use super::*;
 
 impl<T: Display> Display for Analyzed<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let degree = self.degree.unwrap_or_default();
+        let default_degree = self.degree.unwrap_or_default();
+        // Namespaces without columns are printed with the maximum degree.
+        let namespace_degrees = self
+            .definitions
+            .values()
+            .map(|(symbol, _)| symbol)
+            .chain(self.intermediate_columns.values().map(|(symbol, _)| symbol))
+            .filter_map(|symbol| {
+                let mut namespace = AbsoluteSymbolPath::default()
+                    .join(SymbolPath::from_str(&symbol.absolute_name).unwrap());
+                namespace.pop();
+                Some((namespace, symbol.degree?))
+            })
+            .collect::<BTreeMap<_, _>>();
         let mut current_namespace = AbsoluteSymbolPath::default();
         let mut update_namespace = |name: &str, f: &mut Formatter<'_>| {
             let mut namespace =
@@ -29,6 +42,9 @@ impl<T: Display> Display for Analyzed<T> {
             let name = namespace.pop().unwrap();
             if namespace != current_namespace {
                 current_namespace = namespace;
+                let degree = namespace_degrees
+                    .get(&current_namespace)
+                    .unwrap_or(&default_degree);
                 writeln!(
                     f,
                     "namespace {}({degree});",
@@ -45,8 +61,9 @@ impl<T: Display> Display for Analyzed<T> {
                         if matches!(
                             definition,
                             Some(FunctionValueDefinition::TypeConstructor(_, _))
+                                | Some(FunctionValueDefinition::TraitFunction(_, _))
                         ) {
-                            // These are printed as part of the enum.
+                            // These are printed as part of the enum / trait.
                             continue;
                         }
                         let (name, is_local) = update_namespace(name, f)?;
@@ -91,9 +108,18 @@ impl<T: Display> Display for Analyzed<T> {
                                         )?;
                                     }
                                     Some(FunctionValueDefinition::TypeDeclaration(
-                                        enum_declaration,
+                                        type_declaration,
                                     )) => {
-                                        writeln_indented(f, enum_declaration)?;
+                                        writeln_indented(f, type_declaration)?;
+                                    }
+                                    Some(FunctionValueDefinition::TraitDeclaration(
+                                        trait_declaration,
+                                        implementations,
+                                    )) => {
+                                        writeln_indented(f, trait_declaration)?;
+                                        for implementation in implementations {
+                                            writeln_indented(f, implementation)?;
+                                        }
                                     }
                                     _ => {
                                         unreachable!("Invalid definition for symbol: {}", name)
@@ -102,7 +128,8 @@ impl<T: Display> Display for Analyzed<T> {
                             }
                         }
                     } else if let Some((symbol, definition)) = self.intermediate_columns.get(name) {
-                        assert!(symbol.stage.is_none());
+                        // The stage of an intermediate column follows from its definition,
+                        // so it is not printed.
                         let (name, _) = update_namespace(name, f)?;
                         assert_eq!(symbol.kind, SymbolKind::Poly(PolynomialType::Intermediate));
                         if let Some(length) = symbol.length {
@@ -216,7 +243,9 @@ impl Display for FunctionValueDefinition {
                 write!(f, ": {} = {e}", ts.ty)
             }
             FunctionValueDefinition::TypeDeclaration(_)
-            | FunctionValueDefinition::TypeConstructor(_, _) => {
+            | FunctionValueDefinition::TypeConstructor(_, _)
+            | FunctionValueDefinition::TraitDeclaration(_, _)
+            | FunctionValueDefinition::TraitFunction(_, _) => {
                 panic!("Should not use this formatting function.")
             }
         }
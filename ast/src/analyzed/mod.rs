@@ -8,17 +8,22 @@ use std::hash::{Hash, Hasher};
 use std::iter;
 use std::ops::{self, ControlFlow};
 use std::str::FromStr;
+use std::sync::Arc;
 
+use itertools::Itertools;
 use powdr_number::{DegreeType, FieldElement};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::parsed::asm::SymbolPath;
-use crate::parsed::types::{ArrayType, Type, TypeScheme};
+use crate::parsed::types::{ArrayType, Type, TypeBounds, TypeScheme};
 use crate::parsed::visitor::{Children, ExpressionVisitable};
 pub use crate::parsed::BinaryOperator;
 pub use crate::parsed::UnaryOperator;
-use crate::parsed::{self, EnumDeclaration, EnumVariant, SelectedExpressions};
+use crate::parsed::{
+    self, EnumDeclaration, EnumVariant, SelectedExpressions, TraitDeclaration, TraitFunction,
+    TraitImplementation, TypeDeclaration,
+};
 use crate::SourceRef;
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
@@ -32,7 +37,8 @@ pub enum StatementIdentifier {
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct Analyzed<T> {
-    /// The degree of all namespaces, which must match if provided. If no degrees are given, then `None`.
+    /// The maximum degree of all namespaces. If no degrees are given, then `None`.
+    /// The degree of each column is stored in its symbol.
     pub degree: Option<DegreeType>,
     pub definitions: HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
     pub public_declarations: HashMap<String, PublicDeclaration>,
@@ -44,9 +50,32 @@ pub struct Analyzed<T> {
 }
 
 impl<T> Analyzed<T> {
-    /// @returns the degree if any. Panics if there is none.
+    /// @returns the degree if any. Panics if there is none or if the columns
+    /// do not all have the same degree.
     pub fn degree(&self) -> DegreeType {
-        self.degree.unwrap()
+        self.unique_degree().unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// @returns the degree if there is one and all columns have the same degree,
+    /// and an error otherwise.
+    pub fn unique_degree(&self) -> Result<DegreeType, String> {
+        let degrees = self.degrees();
+        if degrees.len() > 1 {
+            return Err(format!(
+                "Expected all namespaces to have the same degree, but found degrees {}",
+                degrees.iter().format(", ")
+            ));
+        }
+        self.degree
+            .ok_or_else(|| "No degree was given.".to_string())
+    }
+    /// @returns the set of distinct degrees of all columns.
+    pub fn degrees(&self) -> BTreeSet<DegreeType> {
+        self.definitions
+            .values()
+            .map(|(symbol, _)| symbol)
+            .chain(self.intermediate_columns.values().map(|(symbol, _)| symbol))
+            .filter_map(|symbol| symbol.degree)
+            .collect()
     }
     /// @returns the number of committed polynomials (with multiplicities for arrays)
     pub fn commitment_count(&self) -> usize {
@@ -408,11 +437,15 @@ pub fn type_from_definition(
             FunctionValueDefinition::TypeDeclaration(_) => {
                 panic!("Requested type of type declaration.")
             }
-            FunctionValueDefinition::TypeConstructor(type_name, variant) => Some(
-                variant
-                    .constructor_type(SymbolPath::from_str(type_name).unwrap())
-                    .into(),
+            FunctionValueDefinition::TypeConstructor(enum_decl, variant) => Some(
+                variant.constructor_type(enum_decl, SymbolPath::from_str(&enum_decl.name).unwrap()),
             ),
+            FunctionValueDefinition::TraitDeclaration(_, _) => {
+                panic!("Requested type of trait declaration.")
+            }
+            FunctionValueDefinition::TraitFunction(trait_name, function) => {
+                Some(trait_function_type_scheme(trait_name, function))
+            }
         }
     } else {
         assert!(
@@ -433,6 +466,22 @@ pub fn type_from_definition(
     }
 }
 
+/// Returns the type scheme of a function declared in a trait: The type variable of the
+/// trait is quantified and bounded by the trait itself, so that every use of the function
+/// requires an implementation of the trait for the type it is instantiated to.
+pub fn trait_function_type_scheme(trait_name: &str, function: &TraitFunction) -> TypeScheme {
+    TypeScheme {
+        vars: TypeBounds::new(
+            function
+                .ty
+                .contained_type_vars()
+                .unique()
+                .map(|v| (v.clone(), BTreeSet::from([trait_name.to_string()]))),
+        ),
+        ty: function.ty.clone(),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Symbol {
     pub id: u64,
@@ -441,6 +490,8 @@ pub struct Symbol {
     pub stage: Option<u32>,
     pub kind: SymbolKind,
     pub length: Option<DegreeType>,
+    /// The degree of the namespace the symbol is declared in, for columns only.
+    pub degree: Option<DegreeType>,
 }
 
 impl Symbol {
@@ -508,8 +559,14 @@ pub enum SymbolKind {
 pub enum FunctionValueDefinition {
     Array(Vec<RepeatedArray>),
     Expression(TypedExpression),
-    TypeDeclaration(EnumDeclaration),
-    TypeConstructor(String, EnumVariant),
+    TypeDeclaration(TypeDeclaration),
+    /// A constructor of an enum variant, together with the enum declaration.
+    /// The name of the enum declaration is the absolute name of the enum.
+    TypeConstructor(Arc<EnumDeclaration>, EnumVariant),
+    /// A trait declaration together with all implementations of the trait.
+    TraitDeclaration(TraitDeclaration, Vec<TraitImplementation<Expression>>),
+    /// A function declared in a trait, with the absolute name of the trait.
+    TraitFunction(String, TraitFunction),
 }
 
 impl Children<Expression> for FunctionValueDefinition {
@@ -521,10 +578,16 @@ impl Children<Expression> for FunctionValueDefinition {
             FunctionValueDefinition::Array(array) => {
                 Box::new(array.iter().flat_map(|i| i.children()))
             }
-            FunctionValueDefinition::TypeDeclaration(enum_declaration) => {
-                enum_declaration.children()
+            FunctionValueDefinition::TypeDeclaration(type_declaration) => {
+                type_declaration.children()
             }
             FunctionValueDefinition::TypeConstructor(_, variant) => variant.children(),
+            FunctionValueDefinition::TraitDeclaration(trait_decl, impls) => Box::new(
+                trait_decl
+                    .children()
+                    .chain(impls.iter().flat_map(|i| i.children())),
+            ),
+            FunctionValueDefinition::TraitFunction(_, _) => Box::new(iter::empty()),
         }
     }
 
@@ -536,10 +599,16 @@ impl Children<Expression> for FunctionValueDefinition {
             FunctionValueDefinition::Array(array) => {
                 Box::new(array.iter_mut().flat_map(|i| i.children_mut()))
             }
-            FunctionValueDefinition::TypeDeclaration(enum_declaration) => {
-                enum_declaration.children_mut()
+            FunctionValueDefinition::TypeDeclaration(type_declaration) => {
+                type_declaration.children_mut()
             }
             FunctionValueDefinition::TypeConstructor(_, variant) => variant.children_mut(),
+            FunctionValueDefinition::TraitDeclaration(trait_decl, impls) => Box::new(
+                trait_decl
+                    .children_mut()
+                    .chain(impls.iter_mut().flat_map(|i| i.children_mut())),
+            ),
+            FunctionValueDefinition::TraitFunction(_, _) => Box::new(iter::empty()),
         }
     }
 }
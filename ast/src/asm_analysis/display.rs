@@ -20,7 +20,8 @@ use super::{
     DebugDirective, DegreeStatement, FunctionBody, FunctionStatement, FunctionStatements,
     Incompatible, IncompatibleSet, Instruction, InstructionDefinitionStatement,
     InstructionStatement, Item, LabelStatement, LinkDefinitionStatement, Machine,
-    RegisterDeclarationStatement, RegisterTy, Return, Rom, SubmachineDeclaration,
+    MachineParamDeclaration, PcJumpStatement, RegisterDeclarationStatement, RegisterTy, Return,
+    Rom, SubmachineDeclaration,
 };
 
 impl Display for AnalysisASMFile {
@@ -56,8 +57,14 @@ impl Display for AnalysisASMFile {
                     ),
                     current_path.len(),
                 )?,
-                Item::TypeDeclaration(enum_decl) => {
-                    write_indented_by(f, enum_decl, current_path.len())?
+                Item::TypeDeclaration(type_decl) => {
+                    write_indented_by(f, type_decl, current_path.len())?
+                }
+                Item::TraitDeclaration(trait_decl) => {
+                    write_indented_by(f, trait_decl, current_path.len())?
+                }
+                Item::TraitImplementation(trait_impl) => {
+                    write_indented_by(f, trait_impl, current_path.len())?
                 }
             }
         }
@@ -71,6 +78,9 @@ impl Display for AnalysisASMFile {
 
 impl Display for Machine {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if !self.params.is_empty() {
+            write!(f, "({})", self.params.iter().format(", "))?;
+        }
         match (&self.latch, &self.operation_id) {
             (Some(latch), Some(operation_id)) => write!(f, "({latch}, {operation_id})"),
             (None, None) => write!(f, ""),
@@ -83,6 +93,7 @@ impl Display for Machine {
         write_items_indented(f, &self.degree)?;
         write_items_indented(f, &self.submachines)?;
         write_items_indented(f, &self.registers)?;
+        write_items_indented(f, &self.pc_jump)?;
         write_items_indented(f, &self.instructions)?;
         write_items_indented(f, &self.callable)?;
         write_items_indented(f, &self.pil)?;
@@ -106,7 +117,17 @@ impl Display for LinkDefinitionStatement {
 
 impl Display for SubmachineDeclaration {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{} {}", self.ty, self.name)
+        write!(f, "{} {}", self.ty, self.name)?;
+        if !self.args.is_empty() {
+            write!(f, "({})", self.args.iter().format(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for MachineParamDeclaration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}: {}", self.name, self.ty)
     }
 }
 
@@ -124,6 +145,12 @@ impl Display for DegreeStatement {
     }
 }
 
+impl Display for PcJumpStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "pc_jump {}, {};", self.flag, self.target)
+    }
+}
+
 impl Display for FunctionStatement {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
@@ -17,7 +17,8 @@ use crate::parsed::{
         InstructionParams, OperationId, OperationParams,
     },
     visitor::{ExpressionVisitable, VisitOrder},
-    EnumDeclaration, NamespacedPolynomialReference, PilStatement, TypedExpression,
+    NamespacedPolynomialReference, PilStatement, TraitDeclaration, TraitImplementation,
+    TypeDeclaration, TypedExpression,
 };
 use crate::SourceRef;
 
@@ -531,6 +532,14 @@ pub struct DegreeStatement {
     pub degree: Expression,
 }
 
+/// An override of the pc update: on rows where `flag` is 1, the pc is set to `target`
+/// instead of being updated by the instructions.
+#[derive(Clone, Debug)]
+pub struct PcJumpStatement {
+    pub flag: String,
+    pub target: Expression,
+}
+
 #[derive(Clone, Debug)]
 pub enum FunctionStatement {
     Assignment(AssignmentStatement),
@@ -664,6 +673,16 @@ pub struct SubmachineDeclaration {
     pub name: String,
     /// the type of the submachine
     pub ty: AbsoluteSymbolPath,
+    /// the names of the instances passed to the parameters of the submachine
+    pub args: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MachineParamDeclaration {
+    /// the name of the parameter
+    pub name: String,
+    /// the type of the instance passed to this parameter
+    pub ty: AbsoluteSymbolPath,
 }
 
 /// An item that is part of the module tree after all modules,
@@ -672,14 +691,21 @@ pub struct SubmachineDeclaration {
 pub enum Item {
     Machine(Machine),
     Expression(TypedExpression),
-    TypeDeclaration(EnumDeclaration<Expression>),
+    TypeDeclaration(TypeDeclaration<Expression>),
+    TraitDeclaration(TraitDeclaration<Expression>),
+    /// Trait implementations do not define a symbol, they are stored
+    /// under a synthetic name that is unique inside their module.
+    TraitImplementation(TraitImplementation<Expression, Expression>),
 }
 
 impl Item {
     pub fn try_to_machine(&self) -> Option<&Machine> {
         match self {
             Item::Machine(m) => Some(m),
-            Item::Expression(_) | Item::TypeDeclaration(_) => None,
+            Item::Expression(_)
+            | Item::TypeDeclaration(_)
+            | Item::TraitDeclaration(_)
+            | Item::TraitImplementation(_) => None,
         }
     }
 }
@@ -698,6 +724,8 @@ pub struct Machine {
     pub registers: Vec<RegisterDeclarationStatement>,
     /// The index of the program counter in the registers, if any
     pub pc: Option<usize>,
+    /// The override of the pc update, if any
+    pub pc_jump: Option<PcJumpStatement>,
     /// The set of pil statements
     pub pil: Vec<PilStatement>,
     /// The set of instructions which can be invoked in functions
@@ -708,6 +736,8 @@ pub struct Machine {
     pub callable: CallableSymbolDefinitions,
     /// The set of submachines
     pub submachines: Vec<SubmachineDeclaration>,
+    /// The machine parameters, i.e. the instances passed in by the parent machine
+    pub params: Vec<MachineParamDeclaration>,
 }
 
 impl Machine {
@@ -800,13 +830,19 @@ impl AnalysisASMFile {
     pub fn machines(&self) -> impl Iterator<Item = (&AbsoluteSymbolPath, &Machine)> {
         self.items.iter().filter_map(|(n, m)| match m {
             Item::Machine(m) => Some((n, m)),
-            Item::Expression(_) | Item::TypeDeclaration(_) => None,
+            Item::Expression(_)
+            | Item::TypeDeclaration(_)
+            | Item::TraitDeclaration(_)
+            | Item::TraitImplementation(_) => None,
         })
     }
     pub fn machines_mut(&mut self) -> impl Iterator<Item = (&AbsoluteSymbolPath, &mut Machine)> {
         self.items.iter_mut().filter_map(|(n, m)| match m {
             Item::Machine(m) => Some((n, m)),
-            Item::Expression(_) | Item::TypeDeclaration(_) => None,
+            Item::Expression(_)
+            | Item::TypeDeclaration(_)
+            | Item::TraitDeclaration(_)
+            | Item::TraitImplementation(_) => None,
         })
     }
 }
@@ -32,6 +32,16 @@ impl SourceRef {
     }
 }
 
+impl Display for SourceRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result {
+        match &self.file {
+            Some(file) => write!(f, "{file}:{}:{}", self.line, self.col + 1),
+            None if self.line == 0 => write!(f, "<unknown location>"),
+            None => write!(f, "{}:{}", self.line, self.col + 1),
+        }
+    }
+}
+
 /// quick and dirty String to String indentation
 pub fn indent<S: ToString>(s: S, indentation: usize) -> String {
     s.to_string()
@@ -24,8 +24,14 @@ impl Display for PILGraph {
                         format_type_scheme_around_name(&name.to_string(), type_scheme)
                     )?;
                 }
-                TypeOrExpression::Type(enum_decl) => {
-                    writeln!(f, "{enum_decl}",)?;
+                TypeOrExpression::Type(type_decl) => {
+                    writeln!(f, "{type_decl}",)?;
+                }
+                TypeOrExpression::TraitDeclaration(trait_decl) => {
+                    writeln!(f, "{trait_decl}")?;
+                }
+                TypeOrExpression::TraitImplementation(trait_impl) => {
+                    writeln!(f, "{trait_impl}")?;
                 }
             }
         }
@@ -4,7 +4,8 @@ use powdr_number::BigUint;
 
 use crate::parsed::{
     asm::{AbsoluteSymbolPath, CallableParams, OperationParams},
-    EnumDeclaration, Expression, PilStatement, TypedExpression,
+    Expression, PilStatement, TraitDeclaration, TraitImplementation, TypeDeclaration,
+    TypedExpression,
 };
 
 mod display;
@@ -37,8 +38,10 @@ pub struct PILGraph {
 
 #[derive(Clone)]
 pub enum TypeOrExpression {
-    Type(EnumDeclaration<Expression>),
+    Type(TypeDeclaration<Expression>),
     Expression(TypedExpression),
+    TraitDeclaration(TraitDeclaration<Expression>),
+    TraitImplementation(TraitImplementation<Expression, Expression>),
 }
 
 #[derive(Default, Clone)]
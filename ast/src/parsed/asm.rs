@@ -14,7 +14,8 @@ use serde::{Deserialize, Serialize};
 use crate::SourceRef;
 
 use super::{
-    visitor::Children, EnumDeclaration, EnumVariant, Expression, PilStatement, TypedExpression,
+    visitor::Children, EnumVariant, Expression, PilStatement, TraitDeclaration, TraitFunction,
+    TraitImplementation, TypeDeclaration, TypedExpression,
 };
 
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
@@ -29,8 +30,18 @@ pub struct ASMModule {
 
 impl ASMModule {
     pub fn symbol_definitions(&self) -> impl Iterator<Item = &SymbolDefinition> {
-        self.statements.iter().map(|s| match s {
-            ModuleStatement::SymbolDefinition(d) => d,
+        self.statements.iter().filter_map(|s| match s {
+            ModuleStatement::SymbolDefinition(d) => Some(d),
+            ModuleStatement::TraitImplementation(_) => None,
+        })
+    }
+
+    pub fn trait_implementations(
+        &self,
+    ) -> impl Iterator<Item = &TraitImplementation<Expression, Expression>> {
+        self.statements.iter().filter_map(|s| match s {
+            ModuleStatement::SymbolDefinition(_) => None,
+            ModuleStatement::TraitImplementation(i) => Some(i),
         })
     }
 }
@@ -38,6 +49,8 @@ impl ASMModule {
 #[derive(Debug, Clone, PartialEq, Eq, From)]
 pub enum ModuleStatement {
     SymbolDefinition(SymbolDefinition),
+    /// Trait implementations do not define a symbol.
+    TraitImplementation(TraitImplementation<Expression, Expression>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -56,8 +69,10 @@ pub enum SymbolValue {
     Module(Module),
     /// A generic symbol / function.
     Expression(TypedExpression),
-    /// A type declaration (currently only enums)
-    TypeDeclaration(EnumDeclaration<Expression>),
+    /// A type declaration (an enum or a struct)
+    TypeDeclaration(TypeDeclaration<Expression>),
+    /// A trait declaration
+    TraitDeclaration(TraitDeclaration<Expression>),
 }
 
 impl SymbolValue {
@@ -68,6 +83,7 @@ impl SymbolValue {
             SymbolValue::Module(m) => SymbolValueRef::Module(m.as_ref()),
             SymbolValue::Expression(e) => SymbolValueRef::Expression(e),
             SymbolValue::TypeDeclaration(t) => SymbolValueRef::TypeDeclaration(t),
+            SymbolValue::TraitDeclaration(t) => SymbolValueRef::TraitDeclaration(t),
         }
     }
 }
@@ -82,10 +98,14 @@ pub enum SymbolValueRef<'a> {
     Module(ModuleRef<'a>),
     /// A generic symbol / function.
     Expression(&'a TypedExpression),
-    /// A type declaration (currently only enums)
-    TypeDeclaration(&'a EnumDeclaration<Expression>),
+    /// A type declaration (an enum or a struct)
+    TypeDeclaration(&'a TypeDeclaration<Expression>),
     /// A type constructor of an enum.
     TypeConstructor(&'a EnumVariant<Expression>),
+    /// A trait declaration
+    TraitDeclaration(&'a TraitDeclaration<Expression>),
+    /// A function declared inside a trait.
+    TraitFunction(&'a TraitFunction<Expression>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, From)]
@@ -383,6 +403,7 @@ impl Display for Part {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Machine {
+    pub params: Vec<MachineParam>,
     pub arguments: MachineArguments,
     pub statements: Vec<MachineStatement>,
 }
@@ -401,7 +422,8 @@ impl Machine {
                         }
                         MachineStatement::CallSelectors(_, name) => Box::new(once(name)),
                         MachineStatement::Degree(_, _)
-                        | MachineStatement::Submachine(_, _, _)
+                        | MachineStatement::PcJump(_, _, _)
+                        | MachineStatement::Submachine(_, _, _, _)
                         | MachineStatement::InstructionDeclaration(_, _, _)
                         | MachineStatement::LinkDeclaration(_, _)
                         | MachineStatement::FunctionDeclaration(_, _, _, _)
@@ -412,6 +434,14 @@ impl Machine {
     }
 }
 
+/// A machine parameter, i.e. an instance of another machine which is
+/// passed in when the machine is instantiated.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct MachineParam {
+    pub name: String,
+    pub ty: SymbolPath,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone)]
 pub struct MachineArguments {
     pub latch: Option<String>,
@@ -477,8 +507,12 @@ pub struct Instruction {
 pub enum MachineStatement {
     CallSelectors(SourceRef, String),
     Degree(SourceRef, Expression),
+    /// An override of the pc update: on rows where the flag column is 1,
+    /// the pc is set to the target expression instead.
+    PcJump(SourceRef, String, Expression),
     Pil(SourceRef, PilStatement),
-    Submachine(SourceRef, SymbolPath, String),
+    /// A submachine instance declaration: type, name and the instances passed as arguments.
+    Submachine(SourceRef, SymbolPath, String, Vec<String>),
     RegisterDeclaration(SourceRef, String, Option<RegisterFlag>),
     InstructionDeclaration(SourceRef, String, Instruction),
     LinkDeclaration(SourceRef, LinkDeclaration),
@@ -1,4 +1,7 @@
-use std::fmt::{Display, Formatter, Result};
+use std::{
+    fmt::{Display, Formatter, Result},
+    str::FromStr,
+};
 
 use itertools::Itertools;
 
@@ -35,6 +38,7 @@ impl Display for ModuleStatement {
             ModuleStatement::SymbolDefinition(SymbolDefinition { name, value }) => match value {
                 SymbolValue::Machine(
                     m @ Machine {
+                        params,
                         arguments:
                             MachineArguments {
                                 latch,
@@ -43,12 +47,16 @@ impl Display for ModuleStatement {
                         ..
                     },
                 ) => {
+                    write!(f, "machine {name}")?;
+                    if !params.is_empty() {
+                        write!(f, "({})", params.iter().format(", "))?;
+                    }
                     if let (None, None) = (latch, operation_id) {
-                        write!(f, "machine {name} {m}")
+                        write!(f, " {m}")
                     } else {
                         write!(
                             f,
-                            "machine {name}({}, {}) {m}",
+                            "({}, {}) {m}",
                             latch.as_deref().unwrap_or("_"),
                             operation_id.as_deref().unwrap_or("_"),
                         )
@@ -71,7 +79,9 @@ impl Display for ModuleStatement {
                     )
                 }
                 SymbolValue::TypeDeclaration(ty) => write!(f, "{ty}"),
+                SymbolValue::TraitDeclaration(trait_decl) => write!(f, "{trait_decl}"),
             },
+            ModuleStatement::TraitImplementation(trait_impl) => write!(f, "{trait_impl}"),
         }
     }
 }
@@ -95,6 +105,12 @@ impl Display for Import {
     }
 }
 
+impl Display for MachineParam {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}: {}", self.name, self.ty)
+    }
+}
+
 impl Display for Machine {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         writeln!(f, "{{")?;
@@ -169,8 +185,15 @@ impl Display for MachineStatement {
         match self {
             MachineStatement::Degree(_, degree) => write!(f, "degree {};", degree),
             MachineStatement::CallSelectors(_, sel) => write!(f, "call_selectors {};", sel),
+            MachineStatement::PcJump(_, flag, target) => write!(f, "pc_jump {flag}, {target};"),
             MachineStatement::Pil(_, statement) => write!(f, "{statement}"),
-            MachineStatement::Submachine(_, ty, name) => write!(f, "{ty} {name};"),
+            MachineStatement::Submachine(_, ty, name, args) => {
+                write!(f, "{ty} {name}")?;
+                if !args.is_empty() {
+                    write!(f, "({})", args.iter().format(", "))?;
+                }
+                write!(f, ";")
+            }
             MachineStatement::RegisterDeclaration(_, name, flag) => write!(
                 f,
                 "reg {}{};",
@@ -311,6 +334,26 @@ impl<E: Display> Display for IndexAccess<E> {
     }
 }
 
+impl<E: Display> Display for StructExpression<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{} {{ {} }}",
+            self.name,
+            self.fields
+                .iter()
+                .map(|field| format!("{}: {}", field.name, field.body))
+                .format(", ")
+        )
+    }
+}
+
+impl<E: Display> Display for FieldAccess<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}.{}", self.object, self.field)
+    }
+}
+
 impl<E: Display> Display for FunctionCall<E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{}({})", self.function, format_list(&self.arguments))
@@ -333,6 +376,22 @@ impl Display for Pattern {
             Pattern::Tuple(t) => write!(f, "({})", t.iter().format(", ")),
             Pattern::Array(a) => write!(f, "[{}]", a.iter().format(", ")),
             Pattern::Variable(v) => write!(f, "{v}"),
+            Pattern::Struct(name, fields) => write!(
+                f,
+                "{name} {{ {} }}",
+                fields
+                    .iter()
+                    .map(|(field, p)| format!("{field}: {p}"))
+                    .format(", ")
+            ),
+            Pattern::Enum(name, fields) => write!(
+                f,
+                "{name}{}",
+                fields
+                    .as_ref()
+                    .map(|fields| format!("({})", fields.iter().format(", ")))
+                    .unwrap_or_default()
+            ),
         }
     }
 }
@@ -467,6 +526,9 @@ impl Display for PilStatement {
             }
             PilStatement::Expression(_, e) => write_indented_by(f, format!("{e};"), 1),
             PilStatement::EnumDeclaration(_, enum_decl) => write_indented_by(f, enum_decl, 1),
+            PilStatement::StructDeclaration(_, struct_decl) => write_indented_by(f, struct_decl, 1),
+            PilStatement::TraitDeclaration(_, trait_decl) => write_indented_by(f, trait_decl, 1),
+            PilStatement::TraitImplementation(_, trait_impl) => write_indented_by(f, trait_impl, 1),
         }
     }
 }
@@ -513,9 +575,42 @@ impl Display for FunctionDefinition {
     }
 }
 
+impl<E: Display> Display for TypeDeclaration<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            TypeDeclaration::Enum(enum_decl) => write!(f, "{enum_decl}"),
+            TypeDeclaration::Struct(struct_decl) => write!(f, "{struct_decl}"),
+        }
+    }
+}
+
+impl<E: Display> Display for StructDeclaration<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "struct {} {{", self.name)?;
+        write_items_indented(f, self.fields.iter())?;
+        write!(f, "}}")
+    }
+}
+
+impl<E: Display> Display for StructField<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{}: {},",
+            self.name,
+            format_type_with_parentheses(&self.ty)
+        )
+    }
+}
+
 impl<E: Display> Display for EnumDeclaration<E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        writeln!(f, "enum {} {{", self.name)?;
+        let type_vars = if self.type_vars.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", self.type_vars)
+        };
+        writeln!(f, "enum {}{type_vars} {{", self.name)?;
         write_items_indented(f, self.variants.iter())?;
         write!(f, "}}")
     }
@@ -535,6 +630,49 @@ impl<E: Display> Display for EnumVariant<E> {
     }
 }
 
+impl<E: Display> Display for TraitDeclaration<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "trait {}<{}> {{",
+            self.name,
+            self.type_vars.iter().format(", ")
+        )?;
+        write_items_indented(f, self.functions.iter())?;
+        write!(f, "}}")
+    }
+}
+
+impl<E: Display> Display for TraitFunction<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}: {},", self.name, self.ty)
+    }
+}
+
+impl<Expr: Display, E: Display> Display for TraitImplementation<Expr, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let type_vars = if self.type_vars.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", self.type_vars)
+        };
+        writeln!(
+            f,
+            "impl{type_vars} {}<{}> {{",
+            self.name,
+            self.type_args.iter().format(", ")
+        )?;
+        write_items_indented(f, self.functions.iter())?;
+        write!(f, "}}")
+    }
+}
+
+impl<Expr: Display> Display for NamedExpression<Expr> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}: {},", self.name, self.body)
+    }
+}
+
 fn format_list<L: IntoIterator<Item = I>, I: Display>(list: L) -> String {
     format!("{}", list.into_iter().format(", "))
 }
@@ -559,6 +697,8 @@ impl<Ref: Display> Display for Expression<Ref> {
             }
             Expression::IndexAccess(index_access) => write!(f, "{index_access}"),
             Expression::FunctionCall(fun_call) => write!(f, "{fun_call}"),
+            Expression::StructExpression(struct_expr) => write!(f, "{struct_expr}"),
+            Expression::FieldAccess(field_access) => write!(f, "{field_access}"),
             Expression::FreeInput(input) => write!(f, "${{ {input} }}"),
             Expression::MatchExpression(scrutinee, arms) => {
                 writeln!(f, "match {scrutinee} {{")?;
@@ -695,7 +835,10 @@ impl<E: Display> Display for Type<E> {
             Type::Tuple(tuple) => write!(f, "{tuple}"),
             Type::Function(fun) => write!(f, "{fun}"),
             Type::TypeVar(name) => write!(f, "{name}"),
-            Type::NamedType(name) => write!(f, "{name}"),
+            Type::NamedType(name, Some(args)) => {
+                write!(f, "{name}<{}>", format_list_of_types(args))
+            }
+            Type::NamedType(name, None) => write!(f, "{name}"),
         }
     }
 }
@@ -768,7 +911,13 @@ impl Display for TypeBounds {
                 if bounds.is_empty() {
                     String::new()
                 } else {
-                    format!(": {}", bounds.iter().join(" + "))
+                    format!(
+                        ": {}",
+                        bounds
+                            .iter()
+                            .map(|b| SymbolPath::from_str(b).unwrap())
+                            .join(" + ")
+                    )
                 }
             )
         }
@@ -3,7 +3,7 @@ use super::{
         ASMModule, ASMProgram, Import, Machine, Module, ModuleStatement, SymbolDefinition,
         SymbolValue,
     },
-    EnumDeclaration, Expression,
+    Expression, TraitDeclaration, TraitImplementation, TypeDeclaration,
 };
 
 pub trait Folder {
@@ -28,8 +28,14 @@ pub trait Folder {
                     SymbolValue::TypeDeclaration(ty) => {
                         self.fold_type_declaration(ty).map(From::from)
                     }
+                    SymbolValue::TraitDeclaration(trait_decl) => {
+                        self.fold_trait_declaration(trait_decl).map(From::from)
+                    }
                 }
                 .map(|value| ModuleStatement::SymbolDefinition(SymbolDefinition { value, ..d })),
+                ModuleStatement::TraitImplementation(trait_impl) => {
+                    self.fold_trait_implementation(trait_impl).map(From::from)
+                }
             })
             .collect::<Result<Vec<_>, _>>()?;
 
@@ -53,8 +59,22 @@ pub trait Folder {
 
     fn fold_type_declaration(
         &mut self,
-        ty: EnumDeclaration<Expression>,
-    ) -> Result<EnumDeclaration<Expression>, Self::Error> {
+        ty: TypeDeclaration<Expression>,
+    ) -> Result<TypeDeclaration<Expression>, Self::Error> {
         Ok(ty)
     }
+
+    fn fold_trait_declaration(
+        &mut self,
+        trait_decl: TraitDeclaration<Expression>,
+    ) -> Result<TraitDeclaration<Expression>, Self::Error> {
+        Ok(trait_decl)
+    }
+
+    fn fold_trait_implementation(
+        &mut self,
+        trait_impl: TraitImplementation<Expression, Expression>,
+    ) -> Result<TraitImplementation<Expression, Expression>, Self::Error> {
+        Ok(trait_impl)
+    }
 }
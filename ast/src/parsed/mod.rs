@@ -11,13 +11,14 @@ use std::{
     ops,
 };
 
+use derive_more::From;
 use powdr_number::{BigInt, BigUint, DegreeType};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use self::{
     asm::{Part, SymbolPath},
-    types::{FunctionType, Type, TypeScheme},
+    types::{FunctionType, Type, TypeBounds, TypeScheme},
     visitor::Children,
 };
 use crate::SourceRef;
@@ -73,6 +74,9 @@ pub enum PilStatement {
     ConnectIdentity(SourceRef, Vec<Expression>, Vec<Expression>),
     ConstantDefinition(SourceRef, String, Expression),
     EnumDeclaration(SourceRef, EnumDeclaration<Expression>),
+    StructDeclaration(SourceRef, StructDeclaration<Expression>),
+    TraitDeclaration(SourceRef, TraitDeclaration<Expression>),
+    TraitImplementation(SourceRef, TraitImplementation<Expression, Expression>),
     Expression(SourceRef, Expression),
 }
 
@@ -87,7 +91,9 @@ impl PilStatement {
             | PilStatement::ConstantDefinition(_, name, _)
             | PilStatement::PublicDeclaration(_, name, _, _, _)
             | PilStatement::LetStatement(_, name, _, _) => Box::new(once((name, false))),
-            PilStatement::EnumDeclaration(_, EnumDeclaration { name, variants: _ }) => {
+            PilStatement::EnumDeclaration(_, EnumDeclaration { name, .. })
+            | PilStatement::StructDeclaration(_, StructDeclaration { name, fields: _ })
+            | PilStatement::TraitDeclaration(_, TraitDeclaration { name, .. }) => {
                 Box::new(once((name, true)))
             }
             PilStatement::PolynomialConstantDeclaration(_, polynomials)
@@ -100,6 +106,7 @@ impl PilStatement {
             | PilStatement::PlookupIdentity(_, _, _)
             | PilStatement::PermutationIdentity(_, _, _)
             | PilStatement::ConnectIdentity(_, _, _)
+            | PilStatement::TraitImplementation(_, _)
             | PilStatement::Expression(_, _) => Box::new(empty()),
         }
     }
@@ -111,9 +118,15 @@ impl PilStatement {
         &self,
     ) -> Box<dyn Iterator<Item = (&String, &String, bool)> + '_> {
         match self {
-            PilStatement::EnumDeclaration(_, EnumDeclaration { name, variants }) => {
+            PilStatement::EnumDeclaration(_, EnumDeclaration { name, variants, .. }) => {
                 Box::new(variants.iter().map(move |v| (name, &v.name, false)))
             }
+            PilStatement::TraitDeclaration(
+                _,
+                TraitDeclaration {
+                    name, functions, ..
+                },
+            ) => Box::new(functions.iter().map(move |f| (name, &f.name, false))),
             _ => Box::new(empty()),
         }
     }
@@ -136,6 +149,9 @@ impl Children<Expression> for PilStatement {
             | PilStatement::ConstantDefinition(_, _, e) => Box::new(once(e)),
 
             PilStatement::EnumDeclaration(_, enum_decl) => enum_decl.children(),
+            PilStatement::StructDeclaration(_, struct_decl) => struct_decl.children(),
+            PilStatement::TraitDeclaration(_, trait_decl) => trait_decl.children(),
+            PilStatement::TraitImplementation(_, trait_impl) => trait_impl.children(),
 
             PilStatement::LetStatement(_, _, type_scheme, value) => Box::new(
                 type_scheme
@@ -171,6 +187,9 @@ impl Children<Expression> for PilStatement {
             | PilStatement::ConstantDefinition(_, _, e) => Box::new(once(e)),
 
             PilStatement::EnumDeclaration(_, enum_decl) => enum_decl.children_mut(),
+            PilStatement::StructDeclaration(_, struct_decl) => struct_decl.children_mut(),
+            PilStatement::TraitDeclaration(_, trait_decl) => trait_decl.children_mut(),
+            PilStatement::TraitImplementation(_, trait_impl) => trait_impl.children_mut(),
 
             PilStatement::LetStatement(_, _, ty, value) => {
                 Box::new(ty.iter_mut().flat_map(|t| t.ty.children_mut()).chain(value))
@@ -188,12 +207,194 @@ impl Children<Expression> for PilStatement {
     }
 }
 
+/// The declaration of a trait: A set of functions whose types depend on
+/// the type variables of the trait and which are provided by implementations.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TraitDeclaration<E = u64> {
+    pub name: String,
+    pub type_vars: Vec<String>,
+    pub functions: Vec<TraitFunction<E>>,
+}
+
+impl<E> TraitDeclaration<E> {
+    pub fn function_by_name(&self, name: &str) -> Option<&TraitFunction<E>> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+}
+
+impl<R> Children<Expression<R>> for TraitDeclaration<u64> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(empty())
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(empty())
+    }
+}
+
+impl<R> Children<Expression<R>> for TraitDeclaration<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(self.functions.iter().flat_map(|f| f.ty.children()))
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(self.functions.iter_mut().flat_map(|f| f.ty.children_mut()))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TraitFunction<E = u64> {
+    pub name: String,
+    pub ty: Type<E>,
+}
+
+/// An implementation of a trait for specific types, which can be generic.
+/// `Expr` is the type of the function bodies, `E` the type of the array
+/// lengths inside the types.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TraitImplementation<Expr, E = u64> {
+    /// The name of the implemented trait.
+    pub name: SymbolPath,
+    /// The generic type variables of the implementation.
+    pub type_vars: TypeBounds,
+    /// The types the trait variables are instantiated to.
+    pub type_args: Vec<Type<E>>,
+    pub functions: Vec<NamedExpression<Expr>>,
+}
+
+impl<Expr, E> TraitImplementation<Expr, E> {
+    pub fn function_by_name(&self, name: &str) -> Option<&NamedExpression<Expr>> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+}
+
+impl<R> Children<Expression<R>> for TraitImplementation<Expression<R>, u64> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(self.functions.iter().map(|f| f.body.as_ref()))
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(self.functions.iter_mut().map(|f| f.body.as_mut()))
+    }
+}
+
+impl<R> Children<Expression<R>> for TraitImplementation<Expression<R>, Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(
+            self.type_args
+                .iter()
+                .flat_map(|t| t.children())
+                .chain(self.functions.iter().map(|f| f.body.as_ref())),
+        )
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(
+            self.type_args
+                .iter_mut()
+                .flat_map(|t| t.children_mut())
+                .chain(self.functions.iter_mut().map(|f| f.body.as_mut())),
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NamedExpression<Expr> {
+    pub name: String,
+    pub body: Box<Expr>,
+}
+
+/// A user-defined type: an enum or a struct.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema, From,
+)]
+pub enum TypeDeclaration<E = u64> {
+    Enum(EnumDeclaration<E>),
+    Struct(StructDeclaration<E>),
+}
+
+impl<E> TypeDeclaration<E> {
+    pub fn name(&self) -> &String {
+        match self {
+            TypeDeclaration::Enum(EnumDeclaration { name, .. })
+            | TypeDeclaration::Struct(StructDeclaration { name, .. }) => name,
+        }
+    }
+}
+
+impl<R> Children<Expression<R>> for TypeDeclaration<u64> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(empty())
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(empty())
+    }
+}
+
+impl<R> Children<Expression<R>> for TypeDeclaration<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        match self {
+            TypeDeclaration::Enum(enum_decl) => enum_decl.children(),
+            TypeDeclaration::Struct(struct_decl) => struct_decl.children(),
+        }
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        match self {
+            TypeDeclaration::Enum(enum_decl) => enum_decl.children_mut(),
+            TypeDeclaration::Struct(struct_decl) => struct_decl.children_mut(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StructDeclaration<E = u64> {
+    pub name: String,
+    pub fields: Vec<StructField<E>>,
+}
+
+impl<E> StructDeclaration<E> {
+    pub fn field_by_name(&self, name: &str) -> Option<&StructField<E>> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+impl<R> Children<Expression<R>> for StructDeclaration<u64> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(empty())
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(empty())
+    }
+}
+
+impl<R> Children<Expression<R>> for StructDeclaration<Expression<R>> {
+    fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
+        Box::new(self.fields.iter().flat_map(|f| f.ty.children()))
+    }
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
+        Box::new(self.fields.iter_mut().flat_map(|f| f.ty.children_mut()))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StructField<E = u64> {
+    pub name: String,
+    pub ty: Type<E>,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct EnumDeclaration<E = u64> {
     pub name: String,
+    /// The type variables of a generic enum like `Option<T>`.
+    pub type_vars: TypeBounds,
     pub variants: Vec<EnumVariant<E>>,
 }
 
+impl<E> EnumDeclaration<E> {
+    /// Returns the type of values of this enum, using its type variables as type arguments.
+    pub fn ty(&self, type_name: SymbolPath) -> Type<E> {
+        let type_args = (!self.type_vars.is_empty())
+            .then(|| self.type_vars.vars().cloned().map(Type::TypeVar).collect());
+        Type::NamedType(type_name, type_args)
+    }
+}
+
 impl<R> Children<Expression<R>> for EnumDeclaration<u64> {
     fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
         Box::new(empty())
@@ -219,15 +420,23 @@ pub struct EnumVariant<E = u64> {
 }
 
 impl<E: Clone> EnumVariant<E> {
-    /// Returns the type of the constructor function for this variant
-    /// given the name of the enum type.
-    pub fn constructor_type(&self, type_name: SymbolPath) -> Type<E> {
-        match &self.fields {
-            None => Type::NamedType(type_name),
-            Some(fields) => Type::Function(FunctionType {
-                params: (*fields).clone(),
-                value: Type::NamedType(type_name).into(),
-            }),
+    /// Returns the type scheme of the constructor function for this variant
+    /// given the enum it is declared in and the name of the enum type.
+    pub fn constructor_type(
+        &self,
+        enum_decl: &EnumDeclaration<E>,
+        type_name: SymbolPath,
+    ) -> TypeScheme<E> {
+        let enum_type = enum_decl.ty(type_name);
+        TypeScheme {
+            vars: enum_decl.type_vars.clone(),
+            ty: match &self.fields {
+                None => enum_type,
+                Some(fields) => Type::Function(FunctionType {
+                    params: (*fields).clone(),
+                    value: enum_type.into(),
+                }),
+            },
         }
     }
 }
@@ -301,6 +510,8 @@ pub enum Expression<Ref = NamespacedPolynomialReference> {
     UnaryOperation(UnaryOperator, Box<Self>),
     IndexAccess(IndexAccess<Self>),
     FunctionCall(FunctionCall<Self>),
+    StructExpression(StructExpression<Self>),
+    FieldAccess(FieldAccess<Self>),
     FreeInput(Box<Self>),
     MatchExpression(Box<Self>, Vec<MatchArm<Self>>),
     IfExpression(IfExpression<Self>),
@@ -403,6 +614,10 @@ impl<R> Expression<R> {
                 function,
                 arguments,
             }) => Box::new(once(function.as_ref()).chain(arguments.iter())),
+            Expression::StructExpression(struct_expr) => struct_expr.children(),
+            Expression::FieldAccess(FieldAccess { object, field: _ }) => {
+                Box::new(once(object.as_ref()))
+            }
             Expression::FreeInput(e) => Box::new(once(e.as_ref())),
             Expression::MatchExpression(e, arms) => {
                 Box::new(once(e.as_ref()).chain(arms.iter().flat_map(|arm| arm.children())))
@@ -447,6 +662,10 @@ impl<R> Expression<R> {
                 function,
                 arguments,
             }) => Box::new(once(function.as_mut()).chain(arguments.iter_mut())),
+            Expression::StructExpression(struct_expr) => struct_expr.children_mut(),
+            Expression::FieldAccess(FieldAccess { object, field: _ }) => {
+                Box::new(once(object.as_mut()))
+            }
             Expression::FreeInput(e) => Box::new(once(e.as_mut())),
             Expression::MatchExpression(e, arms) => {
                 Box::new(once(e.as_mut()).chain(arms.iter_mut().flat_map(|arm| arm.children_mut())))
@@ -621,6 +840,41 @@ impl<E> Children<E> for FunctionCall<E> {
     }
 }
 
+/// A struct literal like `Point { x: 1, y: 2 }`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StructExpression<E = Expression<NamespacedPolynomialReference>> {
+    /// The name of the struct type.
+    pub name: SymbolPath,
+    pub fields: Vec<NamedExpression<E>>,
+}
+
+impl<E> Children<E> for StructExpression<E> {
+    fn children(&self) -> Box<dyn Iterator<Item = &E> + '_> {
+        Box::new(self.fields.iter().map(|f| f.body.as_ref()))
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut E> + '_> {
+        Box::new(self.fields.iter_mut().map(|f| f.body.as_mut()))
+    }
+}
+
+/// An access to a field of a struct value like `p.x`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FieldAccess<E = Expression<NamespacedPolynomialReference>> {
+    pub object: Box<E>,
+    pub field: String,
+}
+
+impl<E> Children<E> for FieldAccess<E> {
+    fn children(&self) -> Box<dyn Iterator<Item = &E> + '_> {
+        Box::new(once(self.object.as_ref()))
+    }
+
+    fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut E> + '_> {
+        Box::new(once(self.object.as_mut()))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MatchArm<E = Expression<NamespacedPolynomialReference>> {
     pub pattern: Pattern,
@@ -710,7 +964,7 @@ pub enum FunctionDefinition {
     /// Generic expression
     Expression(Expression),
     /// A type declaration.
-    TypeDeclaration(EnumDeclaration<Expression>),
+    TypeDeclaration(TypeDeclaration<Expression>),
 }
 
 impl Children<Expression> for FunctionDefinition {
@@ -718,7 +972,7 @@ impl Children<Expression> for FunctionDefinition {
         match self {
             FunctionDefinition::Array(ae) => ae.children(),
             FunctionDefinition::Expression(e) => Box::new(once(e)),
-            FunctionDefinition::TypeDeclaration(_enum_declaration) => todo!(),
+            FunctionDefinition::TypeDeclaration(_type_declaration) => todo!(),
         }
     }
 
@@ -726,7 +980,7 @@ impl Children<Expression> for FunctionDefinition {
         match self {
             FunctionDefinition::Array(ae) => ae.children_mut(),
             FunctionDefinition::Expression(e) => Box::new(once(e)),
-            FunctionDefinition::TypeDeclaration(_enum_declaration) => todo!(),
+            FunctionDefinition::TypeDeclaration(_type_declaration) => todo!(),
         }
     }
 }
@@ -842,6 +1096,11 @@ pub enum Pattern {
     Tuple(Vec<Pattern>),
     Array(Vec<Pattern>),
     Variable(String),
+    /// A struct pattern like `Point { x: 0, y }`, fields that are
+    /// not mentioned are not matched.
+    Struct(SymbolPath, Vec<(String, Pattern)>),
+    /// An enum variant pattern like `Option::Some(x)` or `Option::None`.
+    Enum(SymbolPath, Option<Vec<Pattern>>),
 }
 
 impl Pattern {
@@ -858,12 +1117,14 @@ impl Pattern {
         match self {
             Pattern::Ellipsis => unreachable!(),
             Pattern::CatchAll | Pattern::Variable(_) => true,
-            Pattern::Number(_) | Pattern::String(_) => false,
+            // We do not know if the enum has other variants.
+            Pattern::Number(_) | Pattern::String(_) | Pattern::Enum(_, _) => false,
             Pattern::Array(items) => {
                 // Only "[..]"" is irrefutable
                 items == &vec![Pattern::Ellipsis]
             }
             Pattern::Tuple(p) => p.iter().all(|p| p.is_irrefutable()),
+            Pattern::Struct(_, fields) => fields.iter().all(|(_, p)| p.is_irrefutable()),
         }
     }
 }
@@ -877,6 +1138,8 @@ impl Children<Pattern> for Pattern {
             | Pattern::String(_)
             | Pattern::Variable(_) => Box::new(empty()),
             Pattern::Tuple(p) | Pattern::Array(p) => Box::new(p.iter()),
+            Pattern::Struct(_, fields) => Box::new(fields.iter().map(|(_, p)| p)),
+            Pattern::Enum(_, fields) => Box::new(fields.iter().flatten()),
         }
     }
 
@@ -888,6 +1151,8 @@ impl Children<Pattern> for Pattern {
             | Pattern::String(_)
             | Pattern::Variable(_) => Box::new(empty()),
             Pattern::Tuple(p) | Pattern::Array(p) => Box::new(p.iter_mut()),
+            Pattern::Struct(_, fields) => Box::new(fields.iter_mut().map(|(_, p)| p)),
+            Pattern::Enum(_, fields) => Box::new(fields.iter_mut().flatten()),
         }
     }
 }
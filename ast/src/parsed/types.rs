@@ -33,9 +33,10 @@ pub enum Type<E = u64> {
     Tuple(TupleType<E>),
     Function(FunctionType<E>),
     TypeVar(String),
-    /// A named type like an enum. Directly after parsing, type variables are also
+    /// A named type like an enum, optionally with type arguments.
+    /// Directly after parsing, type variables are also
     /// represented as NamedTypes, because the parser cannot distinguish.
-    NamedType(SymbolPath),
+    NamedType(SymbolPath, Option<Vec<Type<E>>>),
 }
 
 impl<E> Type<E> {
@@ -55,7 +56,7 @@ impl<E> Type<E> {
             | Type::Tuple(_)
             | Type::Function(_)
             | Type::TypeVar(_)
-            | Type::NamedType(_) => false,
+            | Type::NamedType(_, _) => false,
         }
     }
     /// Returns true if the type name needs parentheses during formatting
@@ -63,7 +64,7 @@ impl<E> Type<E> {
     pub fn needs_parentheses(&self) -> bool {
         match self {
             _ if self.is_elementary() => false,
-            Type::Array(_) | Type::Tuple(_) | Type::TypeVar(_) | Type::NamedType(_) => false,
+            Type::Array(_) | Type::Tuple(_) | Type::TypeVar(_) | Type::NamedType(_, _) => false,
             Type::Function(_) => true,
             _ => unreachable!(),
         }
@@ -73,7 +74,7 @@ impl<E> Type<E> {
     /// to TypeVars.
     pub fn map_to_type_vars(&mut self, type_vars: &HashSet<&String>) {
         match self {
-            Type::NamedType(n) => {
+            Type::NamedType(n, None) => {
                 if let Some(identifier) = n.try_to_identifier() {
                     if type_vars.contains(identifier) {
                         *self = Type::TypeVar(identifier.clone());
@@ -88,14 +89,22 @@ impl<E> Type<E> {
 
     pub fn contained_named_types(&self) -> Box<dyn Iterator<Item = &SymbolPath> + '_> {
         match self {
-            Type::NamedType(n) => Box::new(std::iter::once(n)),
+            Type::NamedType(n, _) => Box::new(
+                std::iter::once(n).chain(self.children().flat_map(|t| t.contained_named_types())),
+            ),
             _ => Box::new(self.children().flat_map(|t| t.contained_named_types())),
         }
     }
 
     pub fn contained_named_types_mut(&mut self) -> Box<dyn Iterator<Item = &mut SymbolPath> + '_> {
         match self {
-            Type::NamedType(n) => Box::new(std::iter::once(n)),
+            Type::NamedType(n, args) => Box::new(
+                std::iter::once(n).chain(
+                    args.iter_mut()
+                        .flatten()
+                        .flat_map(|t| t.contained_named_types_mut()),
+                ),
+            ),
             _ => Box::new(
                 self.children_mut()
                     .flat_map(|t| t.contained_named_types_mut()),
@@ -158,7 +167,8 @@ impl<E> Children<Type<E>> for Type<E> {
             Type::Array(ar) => Box::new(std::iter::once(&*ar.base)),
             Type::Tuple(tu) => Box::new(tu.items.iter()),
             Type::Function(fun) => Box::new(fun.params.iter().chain(std::iter::once(&*fun.value))),
-            Type::NamedType(_) | Type::TypeVar(_) => Box::new(std::iter::empty()),
+            Type::NamedType(_, args) => Box::new(args.iter().flatten()),
+            Type::TypeVar(_) => Box::new(std::iter::empty()),
             _ => {
                 assert!(self.is_elementary());
                 Box::new(std::iter::empty())
@@ -175,7 +185,8 @@ impl<E> Children<Type<E>> for Type<E> {
                     .iter_mut()
                     .chain(std::iter::once(&mut *fun.value)),
             ),
-            Type::NamedType(_) | Type::TypeVar(_) => Box::new(std::iter::empty()),
+            Type::NamedType(_, args) => Box::new(args.iter_mut().flatten()),
+            Type::TypeVar(_) => Box::new(std::iter::empty()),
             _ => {
                 assert!(self.is_elementary());
                 Box::new(std::iter::empty())
@@ -189,7 +200,8 @@ impl<R> Children<Expression<R>> for Type<Expression<R>> {
     fn children(&self) -> Box<dyn Iterator<Item = &Expression<R>> + '_> {
         match self {
             _ if self.is_elementary() => Box::new(empty()),
-            Type::TypeVar(_) | Type::NamedType(_) => Box::new(empty()),
+            Type::TypeVar(_) => Box::new(empty()),
+            Type::NamedType(_, args) => Box::new(args.iter().flatten().flat_map(|t| t.children())),
             Type::Array(a) => a.children(),
             Type::Tuple(t) => t.children(),
             Type::Function(f) => f.children(),
@@ -200,7 +212,10 @@ impl<R> Children<Expression<R>> for Type<Expression<R>> {
     fn children_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression<R>> + '_> {
         match self {
             _ if self.is_elementary() => Box::new(empty()),
-            Type::TypeVar(_) | Type::NamedType(_) => Box::new(empty()),
+            Type::TypeVar(_) => Box::new(empty()),
+            Type::NamedType(_, args) => {
+                Box::new(args.iter_mut().flatten().flat_map(|t| t.children_mut()))
+            }
             Type::Array(a) => a.children_mut(),
             Type::Tuple(t) => t.children_mut(),
             Type::Function(f) => f.children_mut(),
@@ -224,7 +239,10 @@ impl<R: Display> From<Type<Expression<R>>> for Type<u64> {
             Type::Tuple(t) => Type::Tuple(t.into()),
             Type::Function(f) => Type::Function(f.into()),
             Type::TypeVar(n) => Type::TypeVar(n),
-            Type::NamedType(n) => Type::NamedType(n),
+            Type::NamedType(n, args) => Type::NamedType(
+                n,
+                args.map(|args| args.into_iter().map(|t| t.into()).collect()),
+            ),
         }
     }
 }
@@ -383,10 +401,29 @@ impl From<Type> for TypeScheme {
     }
 }
 
+/// Returns true if the given name is a trait that is implemented by the compiler
+/// for the elementary types.
+pub fn is_builtin_trait(name: &str) -> bool {
+    [
+        "ToString",
+        "FromLiteral",
+        "Add",
+        "Sub",
+        "Neg",
+        "Mul",
+        "Mod",
+        "Pow",
+        "Ord",
+        "Eq",
+    ]
+    .contains(&name)
+}
+
 #[derive(
     Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default, Serialize, Deserialize, JsonSchema,
 )]
-// TODO bounds should be SymbolPaths in the future.
+/// Type variables together with their trait bounds. A bound is either the name of
+/// a built-in trait (like `Add`) or the path of a user-defined trait.
 pub struct TypeBounds(Vec<(String, BTreeSet<String>)>);
 
 impl TypeBounds {
@@ -409,4 +446,8 @@ impl TypeBounds {
     pub fn bounds(&self) -> impl Iterator<Item = (&String, &BTreeSet<String>)> {
         self.0.iter().map(|(n, x)| (n, x))
     }
+
+    pub fn bounds_mut(&mut self) -> impl Iterator<Item = (&String, &mut BTreeSet<String>)> {
+        self.0.iter_mut().map(|(n, x)| (&*n, x))
+    }
 }
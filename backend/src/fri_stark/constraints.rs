@@ -0,0 +1,489 @@
+//! The constraints of a PIL file, arranged for evaluation by the prover and verifier.
+//!
+//! Lookups and permutations are proven with the LogUp argument. For a lookup
+//! `sel_l { l_1, ..., l_k } in sel_r { r_1, ..., r_k }`, the tuples are compressed to
+//! `f = l_1 * alpha^(k-1) + ... + l_k` and `t = r_1 * alpha^(k-1) + ... + r_k` using a
+//! challenge `alpha`. Each side has an accumulator column in its own domain, which sums up
+//! `sel_l / (beta - f)` on the left and `m * sel_r / (beta - t)` on the right, using a second
+//! challenge `beta` and the multiplicities `m` of the rows of the right side. The prover
+//! claims the sum `s` of each side and the accumulator is constrained by
+//! `(acc' - acc + s / n) * (beta - f) = sel_l` (and accordingly on the right side), which
+//! wraps around from the last row of the `n` rows to the first one, so it holds if and only
+//! if `s` is the sum. The verifier checks that the claimed sums of both sides are equal,
+//! which is the case (with high probability) if and only if every selected tuple on the
+//! left side occurs on the right side. Since the sides are only connected through their sums,
+//! they can have different degrees. Permutations use the same argument with all
+//! multiplicities equal to one.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicUnaryOperator, Analyzed,
+    IdentityKind, PolyID, SymbolKind,
+};
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_number::{DegreeType, FieldElement, LargeInt};
+
+/// The location of a column within its domain: The index of the committed matrix it is
+/// part of (0 for fixed columns, `s + 1` for witness columns of stage `s`) and its
+/// index within that matrix.
+pub type ColumnLocation = (usize, usize);
+
+pub struct ConstraintSystem<T> {
+    /// The constraints of each degree, in ascending order of degree.
+    domains: Vec<DomainConstraints<T>>,
+    /// The number of witness stages.
+    stage_count: usize,
+    /// The names of the public values, in source order.
+    publics: Vec<String>,
+    /// The IDs of the challenges drawn after each stage.
+    challenges: Vec<Vec<u64>>,
+    /// The lookups and permutations, in source order.
+    lookups: Vec<Lookup>,
+}
+
+/// A lookup or permutation, given by the locations of its sides: The index of the domain
+/// and the index of the side within the lookup sides of that domain.
+pub struct Lookup {
+    pub left: (usize, usize),
+    pub right: (usize, usize),
+}
+
+/// One side of a lookup or permutation.
+pub struct LookupSide<T> {
+    pub selector: Option<Expression<T>>,
+    pub expressions: Vec<Expression<T>>,
+    /// The location of the multiplicity column, for the right side of a lookup.
+    pub multiplicity: Option<ColumnLocation>,
+}
+
+/// The challenges of the LogUp argument and the claimed sums of the lookup sides of a domain.
+pub struct LookupValues<'b, T> {
+    /// The challenge the tuples are compressed with.
+    pub alpha: T,
+    /// The challenge the compressed tuples are subtracted from.
+    pub beta: T,
+    pub sums: &'b [T],
+}
+
+/// The columns of all namespaces of the same degree and the constraints that reference them.
+pub struct DomainConstraints<T> {
+    /// The number of rows of the columns.
+    degree: DegreeType,
+    /// The polynomial identities, as expressions that have to evaluate to zero.
+    constraints: Vec<Expression<T>>,
+    /// The names of the columns in each committed matrix.
+    matrices: Vec<Vec<String>>,
+    locations: HashMap<PolyID, ColumnLocation>,
+    /// The sides of lookups and permutations over columns of this degree, whose
+    /// accumulators are the columns of the lookup matrix, which follows the stages.
+    lookup_sides: Vec<LookupSide<T>>,
+}
+
+impl<T: FieldElement> ConstraintSystem<T> {
+    pub fn new(pil: &Analyzed<T>) -> Result<Self, String> {
+        let identities = pil.identities_with_inlined_intermediate_polynomials();
+        if let Some(identity) = identities.iter().find(|identity| {
+            !matches!(
+                identity.kind,
+                IdentityKind::Polynomial | IdentityKind::Plookup | IdentityKind::Permutation
+            )
+        }) {
+            return Err(format!(
+                "The FRI STARK backend only supports polynomial identities, lookups and permutations, found: {identity}"
+            ));
+        }
+        let default_degree = pil
+            .degree
+            .ok_or_else(|| "No degree was given.".to_string())?;
+
+        let mut domains = BTreeMap::new();
+        for (symbol, _) in pil.constant_polys_in_source_order() {
+            let domain = domains
+                .entry(symbol.degree.unwrap_or(default_degree))
+                .or_insert_with_key(|degree| DomainConstraints::new(*degree));
+            for (name, id) in symbol.array_elements() {
+                domain.locations.insert(id, (0, domain.matrices[0].len()));
+                domain.matrices[0].push(name);
+            }
+        }
+        let mut stages = HashMap::new();
+        for (symbol, _) in pil.committed_polys_in_source_order() {
+            let domain = domains
+                .entry(symbol.degree.unwrap_or(default_degree))
+                .or_insert_with_key(|degree| DomainConstraints::new(*degree));
+            let stage = symbol.stage.unwrap_or_default() as usize;
+            if domain.matrices.len() < stage + 2 {
+                domain.matrices.resize(stage + 2, vec![]);
+            }
+            for (name, id) in symbol.array_elements() {
+                domain
+                    .locations
+                    .insert(id, (stage + 1, domain.matrices[stage + 1].len()));
+                domain.matrices[stage + 1].push(name.clone());
+                stages.insert(name, stage);
+            }
+        }
+        // Constraints that do not reference any column are checked on the largest domain.
+        domains
+            .entry(default_degree)
+            .or_insert_with_key(|degree| DomainConstraints::new(*degree));
+        // The multiplicities of lookups are witness columns of the first stage.
+        let stage_count = domains
+            .values()
+            .map(|domain| domain.matrices.len() - 1)
+            .max()
+            .unwrap()
+            .max(1);
+        for domain in domains.values_mut() {
+            domain.matrices.resize(stage_count + 1, vec![]);
+        }
+
+        let degrees = column_degrees(pil, default_degree);
+        let domain_indices = domains
+            .keys()
+            .enumerate()
+            .map(|(index, degree)| (*degree, index))
+            .collect::<HashMap<_, _>>();
+        let mut lookups = vec![];
+        for identity in &identities {
+            if identity.kind == IdentityKind::Polynomial {
+                let constraint = identity.expression_for_poly_id();
+                let degree = referenced_degree(constraint.all_children(), &degrees)
+                    .map_err(|e| format!("{e}: {identity}"))?
+                    .unwrap_or(default_degree);
+                domains
+                    .get_mut(&degree)
+                    .unwrap()
+                    .constraints
+                    .push(constraint.clone());
+                continue;
+            }
+
+            let mut sides = [&identity.left, &identity.right].into_iter().map(|side| {
+                let degree = referenced_degree(side.all_children(), &degrees)
+                    .map_err(|e| format!("{e}: {identity}"))?
+                    .ok_or_else(|| {
+                        format!("Lookup sides have to reference a column: {identity}")
+                    })?;
+                if side.all_children().any(|e| match e {
+                    Expression::Reference(r) => domains[&degree].locations[&r.poly_id].0 > 1,
+                    Expression::Challenge(_) => true,
+                    _ => false,
+                }) {
+                    return Err(format!(
+                        "The FRI STARK backend only supports lookups over columns of the first stage: {identity}"
+                    ));
+                }
+                Ok((degree, side))
+            });
+            let (left_degree, left) = sides.next().unwrap()?;
+            let (right_degree, right) = sides.next().unwrap()?;
+            let left_index = domains[&left_degree].lookup_sides.len();
+            let right_index = domains[&right_degree].lookup_sides.len()
+                + usize::from(left_degree == right_degree);
+            let lookup = Lookup {
+                left: (domain_indices[&left_degree], left_index),
+                right: (domain_indices[&right_degree], right_index),
+            };
+
+            domains
+                .get_mut(&left_degree)
+                .unwrap()
+                .lookup_sides
+                .push(LookupSide {
+                    selector: left.selector.clone(),
+                    expressions: left.expressions.clone(),
+                    multiplicity: None,
+                });
+            let right_domain = domains.get_mut(&right_degree).unwrap();
+            let multiplicity = (identity.kind == IdentityKind::Plookup).then(|| {
+                right_domain.matrices[1]
+                    .push(format!("multiplicities of identity {}", identity.id));
+                (1, right_domain.matrices[1].len() - 1)
+            });
+            right_domain.lookup_sides.push(LookupSide {
+                selector: right.selector.clone(),
+                expressions: right.expressions.clone(),
+                multiplicity,
+            });
+            lookups.push(lookup);
+        }
+        let domains = domains.into_values().collect::<Vec<_>>();
+
+        let publics = pil
+            .public_declarations_in_source_order()
+            .into_iter()
+            .map(
+                |(name, declaration)| match stages.get(&declaration.referenced_poly_name()) {
+                    Some(0) => Ok(name.clone()),
+                    _ => Err(format!(
+                        "Public {name} has to reference a witness column of the first stage."
+                    )),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut challenges = vec![vec![]; stage_count];
+        for expr in identities
+            .iter()
+            .flat_map(|identity| identity.all_children())
+        {
+            if let Expression::Challenge(challenge) = expr {
+                let stage = challenge.stage as usize;
+                if stage + 1 >= stage_count {
+                    return Err(format!(
+                        "Challenge {} of stage {stage} is never used by a witness column of a later stage.",
+                        challenge.id
+                    ));
+                }
+                if !challenges[stage].contains(&challenge.id) {
+                    challenges[stage].push(challenge.id);
+                }
+            }
+        }
+        challenges.iter_mut().for_each(|ids| ids.sort());
+
+        Ok(ConstraintSystem {
+            domains,
+            stage_count,
+            publics,
+            challenges,
+            lookups,
+        })
+    }
+
+    /// The lookups and permutations, in source order.
+    pub fn lookups(&self) -> &[Lookup] {
+        &self.lookups
+    }
+
+    /// The constraints of each degree, in ascending order of degree.
+    pub fn domains(&self) -> &[DomainConstraints<T>] {
+        &self.domains
+    }
+
+    /// The number of witness stages.
+    pub fn stage_count(&self) -> usize {
+        self.stage_count
+    }
+
+    pub fn publics(&self) -> &[String] {
+        &self.publics
+    }
+
+    /// The IDs of the challenges drawn after committing to the given stage, in ascending order.
+    pub fn challenges(&self, stage: usize) -> &[u64] {
+        &self.challenges[stage]
+    }
+}
+
+impl<T: FieldElement> DomainConstraints<T> {
+    fn new(degree: DegreeType) -> Self {
+        DomainConstraints {
+            degree,
+            constraints: vec![],
+            matrices: vec![vec![]],
+            locations: HashMap::new(),
+            lookup_sides: vec![],
+        }
+    }
+
+    /// The number of rows of the columns.
+    pub fn degree(&self) -> DegreeType {
+        self.degree
+    }
+
+    /// The polynomial identities, as expressions that have to evaluate to zero.
+    pub fn constraints(&self) -> &[Expression<T>] {
+        &self.constraints
+    }
+
+    pub fn location(&self, id: &PolyID) -> ColumnLocation {
+        self.locations[id]
+    }
+
+    /// The names of the columns of each committed matrix, starting with the fixed columns.
+    /// The lookup matrix is not included.
+    pub fn matrices(&self) -> &[Vec<String>] {
+        &self.matrices
+    }
+
+    pub fn lookup_sides(&self) -> &[LookupSide<T>] {
+        &self.lookup_sides
+    }
+
+    /// The maximum degree of all constraints, at least 1.
+    pub fn constraint_degree(&self) -> usize {
+        let lookup_degrees = self.lookup_sides.iter().map(|side| {
+            let denominator = side.expressions.iter().map(expression_degree).max();
+            let numerator = side.selector.as_ref().map(expression_degree).unwrap_or(0)
+                + usize::from(side.multiplicity.is_some());
+            (1 + denominator.unwrap_or_default()).max(numerator)
+        });
+        self.constraints
+            .iter()
+            .map(expression_degree)
+            .chain(lookup_degrees)
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+
+    /// Evaluates the selector and the tuple of the given lookup side, where `column`
+    /// returns the value of the column at the given location, on the current or the next row.
+    pub fn evaluate_lookup_side(
+        &self,
+        side: &LookupSide<T>,
+        column: &impl Fn(ColumnLocation, bool) -> T,
+        publics: &BTreeMap<&str, T>,
+    ) -> (T, Vec<T>) {
+        let challenges = BTreeMap::new();
+        let selector = side.selector.as_ref().map_or(T::one(), |selector| {
+            self.evaluate(selector, column, publics, &challenges)
+        });
+        let tuple = side
+            .expressions
+            .iter()
+            .map(|e| self.evaluate(e, column, publics, &challenges))
+            .collect();
+        (selector, tuple)
+    }
+
+    /// Evaluates the numerator and the denominator of the summand of the LogUp argument
+    /// of the given lookup side.
+    pub fn lookup_term(
+        &self,
+        side: &LookupSide<T>,
+        column: &impl Fn(ColumnLocation, bool) -> T,
+        publics: &BTreeMap<&str, T>,
+        (alpha, beta): (T, T),
+    ) -> (T, T) {
+        let (selector, tuple) = self.evaluate_lookup_side(side, column, publics);
+        let multiplicity = side
+            .multiplicity
+            .map_or(T::one(), |location| column(location, false));
+        let compressed = tuple.into_iter().fold(T::zero(), |acc, v| acc * alpha + v);
+        (selector * multiplicity, beta - compressed)
+    }
+
+    /// Evaluates the random linear combination `sum_i alpha^i * c_i` of all constraints,
+    /// where `column` returns the value of the column at the given location, on the
+    /// current or the next row.
+    /// The constraints of the lookup sides follow the polynomial identities.
+    pub fn combine(
+        &self,
+        alpha: T,
+        column: &impl Fn(ColumnLocation, bool) -> T,
+        publics: &BTreeMap<&str, T>,
+        challenges: &BTreeMap<u64, T>,
+        lookup_values: &LookupValues<T>,
+    ) -> T {
+        let combined = self.constraints.iter().fold(T::zero(), |acc, constraint| {
+            acc * alpha + self.evaluate(constraint, column, publics, challenges)
+        });
+        let degree_inverse = T::one() / T::from(self.degree);
+        self.lookup_sides
+            .iter()
+            .enumerate()
+            .fold(combined, |acc, (index, side)| {
+                let (numerator, denominator) = self.lookup_term(
+                    side,
+                    column,
+                    publics,
+                    (lookup_values.alpha, lookup_values.beta),
+                );
+                let accumulator = (self.matrices.len(), index);
+                let step = column(accumulator, true) - column(accumulator, false)
+                    + lookup_values.sums[index] * degree_inverse;
+                acc * alpha + step * denominator - numerator
+            })
+    }
+
+    fn evaluate(
+        &self,
+        expr: &Expression<T>,
+        column: &impl Fn(ColumnLocation, bool) -> T,
+        publics: &BTreeMap<&str, T>,
+        challenges: &BTreeMap<u64, T>,
+    ) -> T {
+        let eval = |e| self.evaluate(e, column, publics, challenges);
+        match expr {
+            Expression::Reference(r) => column(self.locations[&r.poly_id], r.next),
+            Expression::PublicReference(name) => publics[name.as_str()],
+            Expression::Challenge(challenge) => challenges[&challenge.id],
+            Expression::Number(n) => *n,
+            Expression::BinaryOperation(left, op, right) => match op {
+                AlgebraicBinaryOperator::Add => eval(left) + eval(right),
+                AlgebraicBinaryOperator::Sub => eval(left) - eval(right),
+                AlgebraicBinaryOperator::Mul => eval(left) * eval(right),
+                AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                    Expression::Number(exponent) => eval(left).pow(exponent.to_integer()),
+                    _ => unreachable!("Exponent has to be a number."),
+                },
+            },
+            Expression::UnaryOperation(op, inner) => match op {
+                AlgebraicUnaryOperator::Minus => -eval(inner),
+            },
+        }
+    }
+}
+
+/// @returns the degree of the columns referenced in the given expressions, if any,
+/// or an error if they have different degrees.
+fn referenced_degree<'b, T: 'b>(
+    expressions: impl Iterator<Item = &'b Expression<T>>,
+    degrees: &HashMap<PolyID, DegreeType>,
+) -> Result<Option<DegreeType>, String> {
+    let referenced_degrees = expressions
+        .filter_map(|e| match e {
+            Expression::Reference(r) => Some(degrees[&r.poly_id]),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    if referenced_degrees.len() > 1 {
+        let degrees = referenced_degrees
+            .iter()
+            .map(|degree| degree.to_string())
+            .collect::<Vec<_>>();
+        return Err(format!(
+            "Identity references columns of different degrees ({})",
+            degrees.join(", ")
+        ));
+    }
+    Ok(referenced_degrees.into_iter().next())
+}
+
+/// @returns the degree of each column, where columns of namespaces without a degree
+/// have the given default degree.
+fn column_degrees<T>(pil: &Analyzed<T>, default_degree: DegreeType) -> HashMap<PolyID, DegreeType> {
+    pil.definitions
+        .values()
+        .map(|(symbol, _)| symbol)
+        .filter(|symbol| matches!(symbol.kind, SymbolKind::Poly(_)))
+        .flat_map(|symbol| {
+            let degree = symbol.degree.unwrap_or(default_degree);
+            symbol.array_elements().map(move |(_, id)| (id, degree))
+        })
+        .collect()
+}
+
+fn expression_degree<T: FieldElement>(expr: &Expression<T>) -> usize {
+    match expr {
+        Expression::Reference(_) => 1,
+        Expression::PublicReference(_) | Expression::Challenge(_) | Expression::Number(_) => 0,
+        Expression::BinaryOperation(left, op, right) => match op {
+            AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                expression_degree(left).max(expression_degree(right))
+            }
+            AlgebraicBinaryOperator::Mul => expression_degree(left) + expression_degree(right),
+            AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                Expression::Number(exponent) => {
+                    expression_degree(left) * exponent.to_integer().try_into_u64().unwrap() as usize
+                }
+                _ => unreachable!("Exponent has to be a number."),
+            },
+        },
+        Expression::UnaryOperation(_, inner) => expression_degree(inner),
+    }
+}
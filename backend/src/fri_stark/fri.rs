@@ -0,0 +1,144 @@
+//! The FRI low-degree test, folding by a factor of two in each round.
+//!
+//! Layer `r` consists of the evaluations of a polynomial on the coset `g^(2^r) * <w>`
+//! of size `M / 2^r`, where `M` is the size of the evaluation domain. The evaluations
+//! at `x` and `-x`, which are half a layer apart, are committed to in the same leaf.
+//! The first layer is never committed to, because the verifier can compute it from
+//! the openings of the trace.
+
+use powdr_number::FieldElement;
+
+use super::merkle::{CommittedMatrix, Hash, MerkleOpening};
+use super::polynomial::{coset_shift, powers, root_of_unity};
+use super::transcript::Transcript;
+
+/// Computes the value of the folded polynomial at `x^2`, given the values at `x` and `-x`.
+fn fold<T: FieldElement>(value: T, negated_value: T, x: T, gamma: T) -> T {
+    let two = T::from(2);
+    (value + negated_value) / two + gamma * (value - negated_value) / (two * x)
+}
+
+pub struct FriLayers<T> {
+    committed: Vec<CommittedMatrix<T>>,
+    pub final_value: T,
+}
+
+impl<T: FieldElement> FriLayers<T> {
+    /// Runs the commit phase on the evaluations of a polynomial of degree
+    /// less than `2^rounds` on the evaluation domain.
+    pub fn commit(
+        mut values: Vec<T>,
+        rounds: usize,
+        transcript: &mut Transcript<T>,
+    ) -> Result<Self, String> {
+        let mut shift = coset_shift::<T>();
+        let mut committed = vec![];
+        for round in 0..rounds {
+            let gamma = transcript.challenge();
+            let half = values.len() / 2;
+            values = powers(root_of_unity::<T>(values.len()), half)
+                .into_iter()
+                .enumerate()
+                .map(|(i, w)| fold(values[i], values[i + half], shift * w, gamma))
+                .collect();
+            shift = shift * shift;
+            if round + 1 < rounds {
+                let half = values.len() / 2;
+                let layer = CommittedMatrix::new(
+                    vec![values[..half].to_vec(), values[half..].to_vec()],
+                    half,
+                );
+                transcript.absorb_hash(&layer.root());
+                committed.push(layer);
+            }
+        }
+        let final_value = values[0];
+        if values.iter().any(|v| *v != final_value) {
+            return Err("The FRI protocol did not end in a constant polynomial.".to_string());
+        }
+        transcript.absorb_values(&[final_value]);
+        Ok(FriLayers {
+            committed,
+            final_value,
+        })
+    }
+
+    pub fn roots(&self) -> Vec<Hash> {
+        self.committed.iter().map(|layer| layer.root()).collect()
+    }
+
+    /// Opens all committed layers for the query with the given index,
+    /// which has to be less than half the size of the evaluation domain.
+    pub fn open(&self, index: usize) -> Vec<MerkleOpening<T>> {
+        self.committed
+            .iter()
+            .map(|layer| layer.open(index % (layer.columns()[0].len())))
+            .collect()
+    }
+}
+
+/// Replays the commit phase on the transcript and returns the folding challenges.
+pub fn replay_commit_phase<T: FieldElement>(
+    roots: &[Hash],
+    final_value: T,
+    rounds: usize,
+    transcript: &mut Transcript<T>,
+) -> Result<Vec<T>, String> {
+    if roots.len() + 1 != rounds {
+        return Err(format!(
+            "Expected {} FRI layers, got {}.",
+            rounds - 1,
+            roots.len()
+        ));
+    }
+    let gammas = (0..rounds)
+        .map(|round| {
+            let gamma = transcript.challenge();
+            if let Some(root) = roots.get(round) {
+                transcript.absorb_hash(root);
+            }
+            gamma
+        })
+        .collect();
+    transcript.absorb_values(&[final_value]);
+    Ok(gammas)
+}
+
+/// Checks the query with the given index, starting with the values of the first
+/// layer at the index and the index shifted by half the domain size.
+pub fn verify_query<T: FieldElement>(
+    index: usize,
+    first_layer: (T, T),
+    domain_size: usize,
+    roots: &[Hash],
+    openings: &[MerkleOpening<T>],
+    gammas: &[T],
+    final_value: T,
+) -> Result<(), String> {
+    if openings.len() != roots.len() {
+        return Err("Wrong number of FRI layer openings.".to_string());
+    }
+    let mut shift = coset_shift::<T>();
+    let mut size = domain_size;
+    let x = shift * root_of_unity::<T>(size).pow((index as u64).into());
+    let mut value = fold(first_layer.0, first_layer.1, x, gammas[0]);
+    for ((root, opening), gamma) in roots.iter().zip(openings).zip(&gammas[1..]) {
+        shift = shift * shift;
+        size /= 2;
+        let half = size / 2;
+        let leaf = index % half;
+        if opening.values.len() != 2 || !opening.verify(root, leaf) {
+            return Err("Invalid FRI layer opening.".to_string());
+        }
+        let position = index % size;
+        if value != opening.values[position / half] {
+            return Err("FRI folding is inconsistent.".to_string());
+        }
+        let x = shift * root_of_unity::<T>(size).pow((leaf as u64).into());
+        value = fold(opening.values[0], opening.values[1], x, *gamma);
+    }
+    if value != final_value {
+        return Err("FRI folding does not end in the final value.".to_string());
+    }
+    Ok(())
+}
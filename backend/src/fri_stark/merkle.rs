@@ -0,0 +1,190 @@
+//! Merkle trees over rows of field elements, using the Poseidon permutation
+//! over the Goldilocks field so that they can be verified efficiently in a circuit.
+
+use powdr_hash::poseidon_gl::poseidon_gl;
+use powdr_number::{FieldElement, GoldilocksField, LargeInt};
+use serde::{Deserialize, Serialize};
+
+/// Four field elements, stored as their canonical integer values.
+pub type Hash = [u64; 4];
+
+/// The number of field elements absorbed by one application of the permutation.
+pub const RATE: usize = 8;
+
+pub fn hash_to_values<T: FieldElement>(hash: &Hash) -> [T; 4] {
+    hash.map(T::from)
+}
+
+fn values_to_hash<T: FieldElement>(values: [T; 4]) -> Hash {
+    values.map(|v| v.to_integer().try_into_u64().unwrap())
+}
+
+/// Applies the permutation to `rate` (padded with zeros) and `capacity`
+/// and returns the first four elements of the resulting state.
+pub fn permute<T: FieldElement>(rate: &[T], capacity: [T; 4]) -> [T; 4] {
+    assert!(rate.len() <= RATE);
+    let mut inputs = rate.to_vec();
+    inputs.resize(RATE, T::zero());
+    inputs.extend(capacity);
+    poseidon_gl(&inputs)
+}
+
+/// Hashes a sequence of field elements by absorbing them in chunks of `RATE` elements,
+/// where each chunk is permuted together with the output of the previous chunk.
+/// Since the number of values is always known, padding with zeros is unambiguous.
+pub fn hash_values<T: FieldElement>(values: &[T]) -> Hash {
+    values_to_hash(
+        values
+            .chunks(RATE)
+            .fold([T::zero(); 4], |state, chunk| permute(chunk, state)),
+    )
+}
+
+/// Hashes two nodes of a tree, with a zero capacity.
+pub fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    let inputs = left
+        .iter()
+        .chain(right)
+        .map(|v| GoldilocksField::from(*v))
+        .collect::<Vec<_>>();
+    values_to_hash(permute(&inputs, [0.into(); 4]))
+}
+
+pub struct MerkleTree {
+    /// The layers of the tree, starting with the leaves and ending with the root.
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Creates a tree from its leaves, the number of which has to be a power of two.
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        assert!(leaves.len().is_power_of_two());
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], &pair[1]))
+                .collect();
+            layers.push(next);
+        }
+        MerkleTree { layers }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.layers.last().unwrap()[0]
+    }
+
+    /// Returns the authentication path of the leaf with the given index.
+    pub fn path(&self, mut index: usize) -> Vec<Hash> {
+        self.layers[..self.layers.len() - 1]
+            .iter()
+            .map(|layer| {
+                let sibling = layer[index ^ 1];
+                index >>= 1;
+                sibling
+            })
+            .collect()
+    }
+}
+
+/// Checks that `leaf` is the leaf with the given index in the tree with the given root.
+pub fn verify_path(root: &Hash, leaf: Hash, mut index: usize, path: &[Hash]) -> bool {
+    let computed = path.iter().fold(leaf, |node, sibling| {
+        let parent = if index & 1 == 0 {
+            hash_pair(&node, sibling)
+        } else {
+            hash_pair(sibling, &node)
+        };
+        index >>= 1;
+        parent
+    });
+    index == 0 && &computed == root
+}
+
+/// The values of a row of a committed matrix, together with their authentication path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerkleOpening<T> {
+    pub values: Vec<T>,
+    pub path: Vec<Hash>,
+}
+
+impl<T: FieldElement> MerkleOpening<T> {
+    pub fn verify(&self, root: &Hash, index: usize) -> bool {
+        verify_path(root, hash_values(&self.values), index, &self.path)
+    }
+}
+
+/// A matrix of field elements, committed to row by row.
+pub struct CommittedMatrix<T> {
+    /// The columns of the matrix.
+    columns: Vec<Vec<T>>,
+    tree: MerkleTree,
+}
+
+impl<T: FieldElement> CommittedMatrix<T> {
+    /// Commits to the given columns, all of which need to have `height` elements.
+    pub fn new(columns: Vec<Vec<T>>, height: usize) -> Self {
+        assert!(columns.iter().all(|c| c.len() == height));
+        let leaves = (0..height)
+            .map(|row| hash_values(&Self::row_of(&columns, row)))
+            .collect();
+        CommittedMatrix {
+            columns,
+            tree: MerkleTree::new(leaves),
+        }
+    }
+
+    fn row_of(columns: &[Vec<T>], row: usize) -> Vec<T> {
+        columns.iter().map(|c| c[row]).collect()
+    }
+
+    pub fn root(&self) -> Hash {
+        self.tree.root()
+    }
+
+    pub fn columns(&self) -> &[Vec<T>] {
+        &self.columns
+    }
+
+    pub fn open(&self, row: usize) -> MerkleOpening<T> {
+        MerkleOpening {
+            values: Self::row_of(&self.columns, row),
+            path: self.tree.path(row),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn open_and_verify() {
+        let columns = vec![
+            (0..8).map(GoldilocksField::from).collect::<Vec<_>>(),
+            (10..18).map(GoldilocksField::from).collect::<Vec<_>>(),
+        ];
+        let matrix = CommittedMatrix::new(columns, 8);
+        let root = matrix.root();
+        for row in 0..8 {
+            let opening = matrix.open(row);
+            assert_eq!(
+                opening.values,
+                vec![
+                    GoldilocksField::from(row as u32),
+                    GoldilocksField::from(row as u32 + 10)
+                ]
+            );
+            assert!(opening.verify(&root, row));
+            assert!(!opening.verify(&root, row ^ 1));
+        }
+        let mut opening = matrix.open(3);
+        opening.values[1] += GoldilocksField::from(1);
+        assert!(!opening.verify(&root, 3));
+    }
+}
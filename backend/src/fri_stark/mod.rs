@@ -0,0 +1,632 @@
+//! A native STARK prover and verifier over the Goldilocks field, based on FRI.
+//!
+//! The witness columns of each stage, the fixed columns and the chunks of the
+//! quotient polynomial are committed to through Merkle trees over their low-degree
+//! extensions. The constraints are checked at a random out-of-domain point and the
+//! openings there are proven with FRI on the DEEP composition polynomial.
+//! Polynomial identities, lookups and permutations are supported, where lookups
+//! and permutations are proven with the LogUp argument.
+//!
+//! Namespaces of different degrees are proven on different trace domains: The columns
+//! of each degree are committed to separately and each domain has its own quotient
+//! polynomial and FRI proof. All domains share the transcript, the challenges and the
+//! out-of-domain point. Lookups and permutations can connect namespaces of different
+//! degrees, since the LogUp accumulators of both sides are only compared through their sums.
+
+mod constraints;
+mod fri;
+pub(crate) mod merkle;
+mod polynomial;
+mod proof;
+mod prover;
+mod recursion;
+pub(crate) mod transcript;
+mod verifier;
+
+use std::io;
+use std::path::Path;
+
+use powdr_ast::analyzed::Analyzed;
+use powdr_executor::column_store::ColumnStore;
+use powdr_executor::witgen::WitgenCallback;
+use powdr_number::{DegreeType, FieldElement, KnownField};
+
+use crate::{Backend, BackendFactory, Error};
+
+use self::constraints::ConstraintSystem;
+use self::merkle::Hash;
+use self::proof::{DomainKey, VerificationKey};
+use self::prover::CommittedColumns;
+use self::transcript::Transcript;
+
+/// The number of bits of security the number of FRI queries is chosen for.
+const SECURITY_BITS: usize = 100;
+
+pub struct FriStarkFactory;
+
+impl<F: FieldElement> BackendFactory<F> for FriStarkFactory {
+    fn create<'a>(
+        &self,
+        pil: &'a Analyzed<F>,
+        fixed: &'a [(String, Vec<F>)],
+        _output_dir: Option<&'a Path>,
+        setup: Option<&mut dyn io::Read>,
+        verification_key: Option<&mut dyn io::Read>,
+    ) -> Result<Box<dyn Backend<'a, F> + 'a>, Error> {
+        if F::known_field() != Some(KnownField::GoldilocksField) {
+            return Err(Error::BackendError(
+                "The FRI STARK backend only supports the Goldilocks field.".to_string(),
+            ));
+        }
+        if setup.is_some() {
+            return Err(Error::NoSetupAvailable);
+        }
+        let stark = FriStark::new(pil, fixed)?;
+        if let Some(verification_key) = verification_key {
+            let verification_key: VerificationKey = serde_cbor::from_reader(verification_key)
+                .map_err(|e| format!("Could not read verification key: {e}"))?;
+            if verification_key != stark.verification_key {
+                return Err(Error::BackendError(
+                    "The verification key does not match the PIL file and fixed columns."
+                        .to_string(),
+                ));
+            }
+        }
+        Ok(Box::new(stark))
+    }
+}
+
+struct Parameters {
+    /// The number of rows of the trace.
+    degree: usize,
+    /// The factor by which the trace domain is extended for commitments.
+    blowup: usize,
+    /// The number of chunks of size `degree` the quotient polynomial is split into.
+    quotient_chunks: usize,
+    /// The number of FRI queries.
+    query_count: usize,
+}
+
+impl Parameters {
+    fn new(degree: DegreeType, constraint_degree: usize) -> Result<Self, String> {
+        if degree < 2 || !degree.is_power_of_two() {
+            return Err(format!(
+                "The FRI STARK backend requires the degree to be a power of two and at least 2, got {degree}."
+            ));
+        }
+        let blowup = constraint_degree.max(2).next_power_of_two();
+        Ok(Parameters {
+            degree: degree as usize,
+            blowup,
+            quotient_chunks: (constraint_degree - 1).max(1),
+            query_count: SECURITY_BITS.div_ceil(blowup.trailing_zeros() as usize),
+        })
+    }
+
+    /// The size of the domain the low-degree extensions are evaluated on.
+    fn domain_size(&self) -> usize {
+        self.degree * self.blowup
+    }
+
+    /// The number of FRI folding rounds until the polynomial is constant.
+    fn fri_rounds(&self) -> usize {
+        self.degree.trailing_zeros() as usize
+    }
+}
+
+/// The columns of all namespaces of the same degree, committed to on the same trace domain.
+struct Domain<F> {
+    params: Parameters,
+    fixed: CommittedColumns<F>,
+}
+
+pub struct FriStark<'a, F> {
+    pil: &'a Analyzed<F>,
+    /// The values of the fixed columns, which are needed to compute the multiplicities of lookups.
+    fixed: &'a [(String, Vec<F>)],
+    constraints: ConstraintSystem<F>,
+    /// The domains, in the order of `constraints.domains()`.
+    domains: Vec<Domain<F>>,
+    verification_key: VerificationKey,
+}
+
+impl<'a, F: FieldElement> FriStark<'a, F> {
+    fn new(pil: &'a Analyzed<F>, fixed: &'a [(String, Vec<F>)]) -> Result<Self, String> {
+        let constraints = ConstraintSystem::new(pil)?;
+        let domains = constraints
+            .domains()
+            .iter()
+            .map(|domain| {
+                let params = Parameters::new(domain.degree(), domain.constraint_degree())?;
+                let fixed =
+                    CommittedColumns::from_named_values(&domain.matrices()[0], fixed, &params)?;
+                Ok(Domain { params, fixed })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let verification_key = VerificationKey {
+            domains: constraints
+                .domains()
+                .iter()
+                .zip(&domains)
+                .map(|(constraints, domain)| DomainKey {
+                    degree: constraints.degree(),
+                    fixed_root: domain.fixed.root(),
+                })
+                .collect(),
+        };
+        Ok(FriStark {
+            pil,
+            fixed,
+            constraints,
+            domains,
+            verification_key,
+        })
+    }
+
+    /// Creates the transcript that is shared by prover and verifier,
+    /// which is bound to the fixed columns and the public values.
+    fn initial_transcript(&self, publics: &[F]) -> Transcript<F> {
+        let mut transcript = Transcript::new(b"powdr-fri-stark");
+        for key in &self.verification_key.domains {
+            transcript.absorb_hash(&key.fixed_root);
+            transcript.absorb_values(&[F::from(key.degree)]);
+        }
+        transcript.absorb_values(publics);
+        transcript
+    }
+
+    /// The number of columns of each committed matrix of the given domain: the fixed
+    /// columns, the witness columns of each stage, the LogUp accumulators (if the domain
+    /// has lookup sides) and the quotient chunks.
+    fn column_counts(&self, domain: usize) -> Vec<usize> {
+        let constraints = &self.constraints.domains()[domain];
+        let lookup_sides = constraints.lookup_sides().len();
+        constraints
+            .matrices()
+            .iter()
+            .map(|names| names.len())
+            .chain((lookup_sides > 0).then_some(lookup_sides))
+            .chain(std::iter::once(self.domains[domain].params.quotient_chunks))
+            .collect()
+    }
+
+    /// The roots of the committed matrices of the given domain: the fixed columns,
+    /// the witness columns of each stage, the LogUp accumulators and the quotient chunks.
+    fn matrix_roots(
+        &self,
+        domain: usize,
+        stage_roots: &[Hash],
+        lookup_root: Option<Hash>,
+        quotient_root: Hash,
+    ) -> Vec<Hash> {
+        std::iter::once(self.verification_key.domains[domain].fixed_root)
+            .chain(stage_roots.iter().cloned())
+            .chain(lookup_root)
+            .chain(std::iter::once(quotient_root))
+            .collect()
+    }
+}
+
+/// Draws the out-of-domain point, which must neither be in the trace domain
+/// nor in the evaluation domain of any domain. Since all sizes are powers of two,
+/// it suffices to check the largest ones.
+fn draw_out_of_domain_point<F: FieldElement>(
+    transcript: &mut Transcript<F>,
+    domains: &[Domain<F>],
+) -> F {
+    let degree = domains.iter().map(|d| d.params.degree).max().unwrap();
+    let domain_size = domains
+        .iter()
+        .map(|d| d.params.domain_size())
+        .max()
+        .unwrap();
+    let shift = polynomial::coset_shift::<F>();
+    loop {
+        let zeta: F = transcript.challenge();
+        if zeta.pow((degree as u64).into()) != F::one()
+            && (zeta / shift).pow((domain_size as u64).into()) != F::one()
+        {
+            return zeta;
+        }
+    }
+}
+
+/// Evaluates the DEEP composition polynomial at `x`, given the values of the columns
+/// of all committed matrices at `x` and their openings at `zeta` and `zeta_next`.
+/// The next-row openings exist for all matrices except for the quotient chunks.
+fn deep_composition<F: FieldElement>(
+    rows: &[&[F]],
+    openings: &[Vec<F>],
+    next_openings: &[Vec<F>],
+    x: F,
+    (zeta, zeta_next): (F, F),
+    beta: F,
+) -> F {
+    let combine = |acc: F, opened: &[Vec<F>], point: F| {
+        let inverse = F::one() / (x - point);
+        rows.iter()
+            .zip(opened)
+            .flat_map(|(row, opened)| row.iter().zip(opened))
+            .fold(acc, |acc, (value, opened)| {
+                acc * beta + (*value - *opened) * inverse
+            })
+    };
+    let acc = combine(F::zero(), openings, zeta);
+    combine(acc, next_openings, zeta_next)
+}
+
+impl<'a, F: FieldElement> Backend<'a, F> for FriStark<'a, F> {
+    fn prove(
+        &self,
+        witness: &[(String, Vec<F>)],
+        prev_proof: Option<crate::Proof>,
+        witgen_callback: WitgenCallback<F>,
+    ) -> Result<crate::Proof, Error> {
+        if witness.is_empty() {
+            return Err(Error::EmptyWitness);
+        }
+        if prev_proof.is_some() {
+            // Proofs are aggregated by proving the program from `verifier_program`.
+            return Err(Error::NoAggregationAvailable);
+        }
+
+        log::info!("Creating FRI STARK proof.");
+        let start = std::time::Instant::now();
+        let proof = self.prove_stages(witness, Some((witness, witgen_callback)))?;
+        log::info!("Proof done in: {:?}", start.elapsed());
+
+        Ok(serde_cbor::to_vec(&proof).unwrap())
+    }
+
+    /// Reads the witness columns one at a time while committing to them, unless witness
+    /// generation for later stages needs the whole witness of the first stage.
+    fn prove_from_store(
+        &self,
+        witness: &ColumnStore<F>,
+        prev_proof: Option<crate::Proof>,
+        witgen_callback: WitgenCallback<F>,
+    ) -> Result<crate::Proof, Error> {
+        if self.constraints.stage_count() > 1 {
+            let names = witness.column_names();
+            let witness = witness
+                .read_columns(names.iter().map(|name| name.as_str()))
+                .collect::<Vec<_>>();
+            return self.prove(&witness, prev_proof, witgen_callback);
+        }
+        if witness.column_names().is_empty() {
+            return Err(Error::EmptyWitness);
+        }
+        if prev_proof.is_some() {
+            return Err(Error::NoAggregationAvailable);
+        }
+
+        log::info!("Creating FRI STARK proof from the witness store.");
+        let start = std::time::Instant::now();
+        let proof = self.prove_stages(witness, None)?;
+        log::info!("Proof done in: {:?}", start.elapsed());
+
+        Ok(serde_cbor::to_vec(&proof).unwrap())
+    }
+
+    fn verify(&self, proof: &[u8], instances: &[Vec<F>]) -> Result<(), Error> {
+        let proof: proof::Proof<F> = serde_cbor::from_slice(proof)
+            .map_err(|e| format!("Could not deserialize proof: {e}"))?;
+        let publics = instances.first().map(Vec::as_slice).unwrap_or_default();
+        Ok(self.verify_proof(&proof, publics)?)
+    }
+
+    fn verifier_program(&self, publics: &[Vec<F>]) -> Result<String, Error> {
+        Ok(FriStark::verifier_program(self, publics)?)
+    }
+
+    fn verifier_inputs(&self, proofs: &[(crate::Proof, Vec<F>)]) -> Result<Vec<F>, Error> {
+        let proofs = proofs
+            .iter()
+            .map(|(proof, publics)| {
+                serde_cbor::from_slice(proof)
+                    .map(|proof| (proof, publics.clone()))
+                    .map_err(|e| format!("Could not deserialize proof: {e}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(FriStark::verifier_inputs(self, &proofs)?)
+    }
+
+    fn export_verification_key(&self, output: &mut dyn io::Write) -> Result<(), Error> {
+        serde_cbor::to_writer(output, &self.verification_key)
+            .map_err(|e| Error::BackendError(format!("Could not write verification key: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use powdr_executor::witgen::WitgenCallback;
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+    use test_log::test;
+
+    use super::*;
+
+    type Columns = Vec<(String, Vec<GoldilocksField>)>;
+
+    fn column(name: &str, values: impl IntoIterator<Item = u64>) -> (String, Vec<GoldilocksField>) {
+        (
+            name.to_string(),
+            values.into_iter().map(GoldilocksField::from).collect(),
+        )
+    }
+
+    fn fibonacci() -> (Analyzed<GoldilocksField>, Columns, Columns) {
+        let pil = analyze_string::<GoldilocksField>(
+            r"
+namespace F(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness x, y;
+    public out = y(7);
+    (1 - FIRST') * (x' - y) = 0;
+    (1 - FIRST') * (y' - (x + y)) = 0;
+    FIRST * (x - 1) = 0;
+    FIRST * (y - 1) = 0;
+",
+        );
+        let fixed = vec![column("F.FIRST", [1, 0, 0, 0, 0, 0, 0, 0])];
+        let witness = vec![
+            column("F.x", [1, 1, 2, 3, 5, 8, 13, 21]),
+            column("F.y", [1, 2, 3, 5, 8, 13, 21, 34]),
+        ];
+        (pil, fixed, witness)
+    }
+
+    fn callback(
+        pil: &Analyzed<GoldilocksField>,
+        fixed: &Columns,
+    ) -> WitgenCallback<GoldilocksField> {
+        WitgenCallback::new(Rc::new(pil.clone()), Rc::new(fixed.clone()), None)
+    }
+
+    #[test]
+    fn prove_and_verify() {
+        let (pil, fixed, witness) = fibonacci();
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        let proof = backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .unwrap();
+        backend
+            .verify(&proof, &[vec![GoldilocksField::from(34)]])
+            .unwrap();
+        assert!(backend
+            .verify(&proof, &[vec![GoldilocksField::from(35)]])
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_witness() {
+        let (pil, fixed, mut witness) = fibonacci();
+        witness[1].1[3] += GoldilocksField::from(1);
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        assert!(backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .is_err());
+    }
+
+    #[test]
+    fn tampered_proof() {
+        let (pil, fixed, witness) = fibonacci();
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        let proof = backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .unwrap();
+        let mut proof: proof::Proof<GoldilocksField> = serde_cbor::from_slice(&proof).unwrap();
+        proof.domains[0].openings[1][0] += GoldilocksField::from(1);
+        let proof = serde_cbor::to_vec(&proof).unwrap();
+        assert!(backend
+            .verify(&proof, &[vec![GoldilocksField::from(34)]])
+            .is_err());
+    }
+
+    #[test]
+    fn verification_key() {
+        let (pil, fixed, witness) = fibonacci();
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        let proof = backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .unwrap();
+        let mut verification_key = vec![];
+        backend
+            .export_verification_key(&mut verification_key)
+            .unwrap();
+
+        let verifier = FriStarkFactory
+            .create(
+                &pil,
+                &fixed,
+                None,
+                None,
+                Some(&mut verification_key.as_slice()),
+            )
+            .unwrap();
+        verifier
+            .verify(&proof, &[vec![GoldilocksField::from(34)]])
+            .unwrap();
+
+        let other_fixed = vec![column("F.FIRST", [0, 1, 0, 0, 0, 0, 0, 0])];
+        assert!(FriStarkFactory
+            .create(
+                &pil,
+                &other_fixed,
+                None,
+                None,
+                Some(&mut verification_key.as_slice())
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn multi_stage() {
+        let pil = analyze_string::<GoldilocksField>(
+            r"
+namespace std::prover(4);
+    let challenge = [];
+namespace main(4);
+    col fixed first = [1] + [0]*;
+    col witness a, b;
+    col witness stage(1) z;
+    let beta: expr = std::prover::challenge(0, 1);
+    first * (z - 1) = 0;
+    (beta - b) * z' = z * (beta - a);
+",
+        );
+        let fixed = vec![column("main.first", [1, 0, 0, 0])];
+        let witness = vec![
+            column("main.a", [1, 2, 3, 4]),
+            column("main.b", [4, 1, 3, 2]),
+        ];
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        let proof = backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .unwrap();
+        backend.verify(&proof, &[]).unwrap();
+    }
+
+    #[test]
+    fn different_degrees() {
+        let pil = analyze_string::<GoldilocksField>(
+            r"
+namespace A(4);
+    col fixed FIRST = [1] + [0]*;
+    col witness x;
+    (1 - FIRST') * (x' - x - 1) = 0;
+    FIRST * x = 0;
+namespace B(8);
+    col fixed FIRST = [1] + [0]*;
+    col witness y;
+    public out = y(7);
+    (1 - FIRST') * (y' - 2 * y) = 0;
+    FIRST * (y - 1) = 0;
+",
+        );
+        let fixed = vec![
+            column("A.FIRST", [1, 0, 0, 0]),
+            column("B.FIRST", [1, 0, 0, 0, 0, 0, 0, 0]),
+        ];
+        let witness = vec![
+            column("A.x", [0, 1, 2, 3]),
+            column("B.y", [1, 2, 4, 8, 16, 32, 64, 128]),
+        ];
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        let proof = backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .unwrap();
+        backend
+            .verify(&proof, &[vec![GoldilocksField::from(128)]])
+            .unwrap();
+        assert!(backend
+            .verify(&proof, &[vec![GoldilocksField::from(64)]])
+            .is_err());
+        assert!(backend.verifier_program(&[vec![]]).is_err());
+
+        let mut invalid_witness = witness;
+        invalid_witness[0].1[2] += GoldilocksField::from(1);
+        assert!(backend
+            .prove(&invalid_witness, None, callback(&pil, &fixed))
+            .is_err());
+    }
+
+    #[test]
+    fn lookups_between_different_degrees() {
+        let pil = analyze_string::<GoldilocksField>(
+            r"
+namespace A(4);
+    col witness x, sel;
+    { x } in { B.BYTE };
+    sel { x } is B.SEL { B.y };
+namespace B(8);
+    col fixed BYTE = [0, 1, 2, 3, 4, 5, 6, 7];
+    col fixed SEL = [1, 1] + [0]*;
+    col witness y;
+",
+        );
+        let fixed = vec![
+            column("B.BYTE", [0, 1, 2, 3, 4, 5, 6, 7]),
+            column("B.SEL", [1, 1, 0, 0, 0, 0, 0, 0]),
+        ];
+        let witness = vec![
+            column("A.x", [3, 5, 5, 0]),
+            column("A.sel", [1, 0, 1, 0]),
+            column("B.y", [5, 3, 9, 9, 9, 9, 9, 9]),
+        ];
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        let proof = backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .unwrap();
+        backend.verify(&proof, &[]).unwrap();
+        assert!(backend.verifier_program(&[vec![]]).is_err());
+
+        // The sums of both sides of the lookup are changed by the same amount,
+        // which violates the constraints of the accumulators.
+        let mut tampered: proof::Proof<GoldilocksField> = serde_cbor::from_slice(&proof).unwrap();
+        tampered.domains[0].lookup_sums[0] += GoldilocksField::from(1);
+        tampered.domains[1].lookup_sums[0] += GoldilocksField::from(1);
+        let tampered = serde_cbor::to_vec(&tampered).unwrap();
+        assert!(backend.verify(&tampered, &[]).is_err());
+
+        let mut invalid_witness = witness.clone();
+        invalid_witness[0].1[3] = GoldilocksField::from(9);
+        assert!(backend
+            .prove(&invalid_witness, None, callback(&pil, &fixed))
+            .is_err());
+
+        let mut invalid_witness = witness;
+        invalid_witness[2].1[0] = GoldilocksField::from(4);
+        assert!(backend
+            .prove(&invalid_witness, None, callback(&pil, &fixed))
+            .is_err());
+    }
+
+    #[test]
+    fn aggregation_of_invalid_proof() {
+        let (pil, fixed, witness) = fibonacci();
+        let backend = FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .unwrap();
+        let proof = backend
+            .prove(&witness, None, callback(&pil, &fixed))
+            .unwrap();
+        backend
+            .verifier_inputs(&[(proof.clone(), vec![GoldilocksField::from(34)])])
+            .unwrap();
+        assert!(backend
+            .verifier_inputs(&[(proof, vec![GoldilocksField::from(35)])])
+            .is_err());
+    }
+
+    #[test]
+    fn unsupported_identity() {
+        let pil = analyze_string::<GoldilocksField>(
+            r"
+namespace main(4);
+    col witness x, y;
+    { x } connect { y };
+",
+        );
+        let fixed = vec![];
+        assert!(FriStarkFactory
+            .create(&pil, &fixed, None, None, None)
+            .is_err());
+    }
+}
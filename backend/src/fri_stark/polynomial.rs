@@ -0,0 +1,156 @@
+//! Univariate polynomials over two-adic domains of the Goldilocks field.
+
+use powdr_executor::constraint_checker::GOLDILOCKS_ROOT_OF_UNITY_2_32;
+use powdr_number::FieldElement;
+
+/// A generator of the multiplicative group of the Goldilocks field.
+/// Used to shift the evaluation domain off the trace domain.
+const MULTIPLICATIVE_GENERATOR: u64 = 7;
+
+/// Returns a primitive root of unity of order `size`, which has to be a power of two.
+pub fn root_of_unity<T: FieldElement>(size: usize) -> T {
+    assert!(size.is_power_of_two() && size <= 1 << 32);
+    T::from(GOLDILOCKS_ROOT_OF_UNITY_2_32).pow(((1u64 << 32) / size as u64).into())
+}
+
+/// Returns the shift of the coset the low-degree extension is evaluated on.
+pub fn coset_shift<T: FieldElement>() -> T {
+    T::from(MULTIPLICATIVE_GENERATOR)
+}
+
+/// Returns the powers `1, x, x^2, ...` of `x`, `count` of them.
+pub fn powers<T: FieldElement>(x: T, count: usize) -> Vec<T> {
+    std::iter::successors(Some(T::one()), |p| Some(*p * x))
+        .take(count)
+        .collect()
+}
+
+/// Evaluates the polynomial given by its coefficients (lowest degree first)
+/// at all powers of a root of unity of order `values.len()`, in place.
+pub fn ntt<T: FieldElement>(values: &mut [T]) {
+    let root = root_of_unity(values.len());
+    ntt_with_root(values, root);
+}
+
+/// The inverse of `ntt`: Computes the coefficients from the evaluations, in place.
+pub fn intt<T: FieldElement>(values: &mut [T]) {
+    let root = root_of_unity::<T>(values.len());
+    ntt_with_root(values, T::one() / root);
+    let size_inv = T::one() / T::from(values.len() as u64);
+    values.iter_mut().for_each(|v| *v = *v * size_inv);
+}
+
+fn ntt_with_root<T: FieldElement>(values: &mut [T], root: T) {
+    let n = values.len();
+    assert!(n.is_power_of_two());
+    if n == 1 {
+        return;
+    }
+    let log_n = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - log_n);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let twiddles = powers(root.pow(((n / len) as u64).into()), len / 2);
+        for chunk in values.chunks_mut(len) {
+            let (low, high) = chunk.split_at_mut(len / 2);
+            for ((l, h), w) in low.iter_mut().zip(high.iter_mut()).zip(&twiddles) {
+                let u = *l;
+                let v = *h * *w;
+                *l = u + v;
+                *h = u - v;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Computes the coefficients of the polynomial of degree less than `evaluations.len()`
+/// that takes the given values on the subgroup of that size.
+pub fn interpolate<T: FieldElement>(evaluations: &[T]) -> Vec<T> {
+    let mut coefficients = evaluations.to_vec();
+    intt(&mut coefficients);
+    coefficients
+}
+
+/// Evaluates the polynomial on the coset `shift * <w>` of size `size`.
+pub fn coset_evaluate<T: FieldElement>(coefficients: &[T], size: usize, shift: T) -> Vec<T> {
+    assert!(coefficients.len() <= size);
+    let mut values = coefficients
+        .iter()
+        .zip(powers(shift, coefficients.len()))
+        .map(|(c, s)| *c * s)
+        .collect::<Vec<_>>();
+    values.resize(size, T::zero());
+    ntt(&mut values);
+    values
+}
+
+/// The inverse of `coset_evaluate`: Computes the coefficients from the evaluations on the coset.
+pub fn coset_interpolate<T: FieldElement>(evaluations: &[T], shift: T) -> Vec<T> {
+    let mut coefficients = interpolate(evaluations);
+    let shift_inv = T::one() / shift;
+    coefficients
+        .iter_mut()
+        .zip(powers(shift_inv, evaluations.len()))
+        .for_each(|(c, s)| *c = *c * s);
+    coefficients
+}
+
+/// Evaluates the polynomial at a single point.
+pub fn evaluate<T: FieldElement>(coefficients: &[T], x: T) -> T {
+    coefficients
+        .iter()
+        .rev()
+        .fold(T::zero(), |acc, c| acc * x + *c)
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use test_log::test;
+
+    use super::*;
+
+    fn convert(values: Vec<u64>) -> Vec<GoldilocksField> {
+        values.into_iter().map(GoldilocksField::from).collect()
+    }
+
+    #[test]
+    fn root_of_unity_order() {
+        let root = root_of_unity::<GoldilocksField>(1 << 32);
+        assert_eq!(root.pow((1u64 << 31).into()), -GoldilocksField::from(1));
+    }
+
+    #[test]
+    fn ntt_matches_evaluation() {
+        let coefficients = convert(vec![3, 1, 4, 1, 5, 9, 2, 6]);
+        let mut values = coefficients.clone();
+        ntt(&mut values);
+        let root = root_of_unity::<GoldilocksField>(8);
+        for (i, v) in values.iter().enumerate() {
+            assert_eq!(*v, evaluate(&coefficients, root.pow((i as u64).into())));
+        }
+        intt(&mut values);
+        assert_eq!(values, coefficients);
+    }
+
+    #[test]
+    fn coset_roundtrip() {
+        let coefficients = convert(vec![7, 0, 2, 11]);
+        let shift = coset_shift::<GoldilocksField>();
+        let values = coset_evaluate(&coefficients, 16, shift);
+        let root = root_of_unity::<GoldilocksField>(16);
+        assert_eq!(
+            values[5],
+            evaluate(&coefficients, shift * root.pow(5u64.into()))
+        );
+        let mut expected = coefficients.clone();
+        expected.resize(16, 0.into());
+        assert_eq!(coset_interpolate(&values, shift), expected);
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use super::merkle::{Hash, MerkleOpening};
+
+/// Everything the verifier needs to know about the fixed columns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationKey {
+    /// The keys of the domains, in ascending order of degree.
+    pub domains: Vec<DomainKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainKey {
+    pub degree: u64,
+    /// The root of the commitment to the low-degree extension of the fixed columns.
+    pub fixed_root: Hash,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proof<T> {
+    /// The proofs of the domains, in ascending order of degree.
+    pub domains: Vec<DomainProof<T>>,
+}
+
+/// The commitments, openings and FRI proof of the columns of one degree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainProof<T> {
+    /// The roots of the commitments to the witness columns, one per stage.
+    pub stage_roots: Vec<Hash>,
+    /// The root of the commitment to the LogUp accumulators of the lookup sides,
+    /// if the domain has any.
+    pub lookup_root: Option<Hash>,
+    /// The claimed sums of the LogUp accumulators, one per lookup side.
+    pub lookup_sums: Vec<T>,
+    /// The root of the commitment to the chunks of the quotient polynomial.
+    pub quotient_root: Hash,
+    /// The values of all columns at the out-of-domain point, per committed matrix:
+    /// the fixed columns, the witness columns of each stage, the LogUp accumulators
+    /// and the quotient chunks.
+    pub openings: Vec<Vec<T>>,
+    /// The values of all columns except for the quotient chunks at the out-of-domain
+    /// point shifted by one row.
+    pub next_openings: Vec<Vec<T>>,
+    /// The roots of the commitments to the FRI layers, except for the first and the last one.
+    pub fri_roots: Vec<Hash>,
+    /// The value of the constant polynomial the FRI protocol ends in.
+    pub fri_final_value: T,
+    pub queries: Vec<QueryProof<T>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryProof<T> {
+    /// The rows `j` and `j + M/2` of each committed matrix, where `M` is the size
+    /// of the evaluation domain.
+    pub matrices: Vec<[MerkleOpening<T>; 2]>,
+    /// The opened pair of each committed FRI layer.
+    pub fri_layers: Vec<MerkleOpening<T>>,
+}
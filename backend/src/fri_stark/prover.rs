@@ -0,0 +1,565 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::iter::once;
+
+use powdr_ast::analyzed::AlgebraicExpression as Expression;
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_executor::column_store::ColumnStore;
+use powdr_executor::witgen::WitgenCallback;
+use powdr_number::FieldElement;
+
+use super::constraints::{ColumnLocation, LookupValues};
+use super::fri::FriLayers;
+use super::merkle::{CommittedMatrix, Hash, MerkleOpening};
+use super::polynomial::{
+    coset_evaluate, coset_interpolate, coset_shift, evaluate, interpolate, root_of_unity,
+};
+use super::proof::{DomainProof, Proof, QueryProof};
+use super::{deep_composition, draw_out_of_domain_point, Domain, FriStark, Parameters};
+
+/// Named columns that can be read one at a time, either from memory or from a [ColumnStore].
+pub trait ColumnSource<T: Clone> {
+    /// @returns the values of the column with the given name, if it exists.
+    fn column(&self, name: &str) -> Option<Cow<'_, [T]>>;
+
+    /// @returns the value of the column with the given name in the given row, if it exists.
+    fn value(&self, name: &str, row: usize) -> Option<T> {
+        self.column(name)?.get(row).cloned()
+    }
+}
+
+impl<T: Clone> ColumnSource<T> for [(String, Vec<T>)] {
+    fn column(&self, name: &str) -> Option<Cow<'_, [T]>> {
+        self.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, values)| Cow::Borrowed(values.as_slice()))
+    }
+}
+
+impl<T: FieldElement> ColumnSource<T> for ColumnStore<T> {
+    fn column(&self, name: &str) -> Option<Cow<'_, [T]>> {
+        (self.column_len(name) > 0).then(|| Cow::Owned(self.read_column(name)))
+    }
+
+    fn value(&self, name: &str, row: usize) -> Option<T> {
+        let column = (self.column_len(name) > 0).then(|| self.column_reader(name))?;
+        (row < column.len()).then(|| column.get(row))
+    }
+}
+
+/// The columns of the first source, followed by the columns of the second one.
+impl<T: Clone, A: ColumnSource<T> + ?Sized, B: ColumnSource<T> + ?Sized> ColumnSource<T>
+    for (&A, &B)
+{
+    fn column(&self, name: &str) -> Option<Cow<'_, [T]>> {
+        self.0.column(name).or_else(|| self.1.column(name))
+    }
+
+    fn value(&self, name: &str, row: usize) -> Option<T> {
+        self.0.value(name, row).or_else(|| self.1.value(name, row))
+    }
+}
+
+/// Columns given by their coefficients, committed to through their low-degree extension.
+pub struct CommittedColumns<T> {
+    coefficients: Vec<Vec<T>>,
+    matrix: CommittedMatrix<T>,
+}
+
+impl<T: FieldElement> CommittedColumns<T> {
+    /// Commits to the columns with the given names, looking up their values on the trace domain.
+    /// The values are read one column at a time.
+    pub fn from_named_values(
+        names: &[String],
+        values: &(impl ColumnSource<T> + ?Sized),
+        params: &Parameters,
+    ) -> Result<Self, String> {
+        let coefficients = names
+            .iter()
+            .map(|name| match values.column(name) {
+                Some(values) if values.len() == params.degree => Ok(interpolate(&values)),
+                Some(values) => Err(format!(
+                    "Column {name} has {} rows, expected {}.",
+                    values.len(),
+                    params.degree
+                )),
+                None => Err(format!("Column {name} is missing.")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_coefficients(coefficients, params))
+    }
+
+    pub fn from_coefficients(coefficients: Vec<Vec<T>>, params: &Parameters) -> Self {
+        let evaluations = coefficients
+            .iter()
+            .map(|c| coset_evaluate(c, params.domain_size(), coset_shift()))
+            .collect();
+        CommittedColumns {
+            coefficients,
+            matrix: CommittedMatrix::new(evaluations, params.domain_size()),
+        }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.matrix.root()
+    }
+
+    /// The values of the low-degree extensions.
+    fn evaluations(&self) -> &[Vec<T>] {
+        self.matrix.columns()
+    }
+
+    fn row(&self, index: usize) -> Vec<T> {
+        self.evaluations().iter().map(|c| c[index]).collect()
+    }
+
+    fn open(&self, index: usize) -> MerkleOpening<T> {
+        self.matrix.open(index)
+    }
+
+    fn evaluate_at(&self, x: T) -> Vec<T> {
+        self.coefficients.iter().map(|c| evaluate(c, x)).collect()
+    }
+}
+
+/// The values of the LogUp accumulators of a domain on the trace domain, if it has
+/// lookup sides, and their sums.
+type LookupAccumulators<T> = (Option<Vec<Vec<T>>>, Vec<T>);
+
+/// The witness of the first stage in memory and the callback to generate the witness
+/// of the later stages from it.
+type LaterStages<'b, T> = (&'b [(String, Vec<T>)], WitgenCallback<T>);
+
+impl<'a, F: FieldElement> FriStark<'a, F> {
+    /// Proves the given witness of the first stage, whose columns are read one at a time.
+    /// The witness of the later stages is generated with the callback, which needs the
+    /// witness of the first stage in memory, so it can only be omitted with a single stage.
+    pub(super) fn prove_stages(
+        &self,
+        witness: &(impl ColumnSource<F> + ?Sized),
+        later_stages: Option<LaterStages<F>>,
+    ) -> Result<Proof<F>, String> {
+        if self.constraints.stage_count() > 1 && later_stages.is_none() {
+            return Err(
+                "The witness of later stages cannot be generated without the witness of the first stage in memory."
+                    .to_string(),
+            );
+        }
+        let publics = self
+            .pil
+            .public_declarations_in_source_order()
+            .into_iter()
+            .map(|(name, public_declaration)| {
+                let column = public_declaration.referenced_poly_name();
+                witness
+                    .value(&column, public_declaration.index as usize)
+                    .map(|value| (name.as_str(), value))
+                    .ok_or_else(|| format!("Column {column} is missing."))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        let public_values = self
+            .constraints
+            .publics()
+            .iter()
+            .map(|name| publics[name.as_str()])
+            .collect::<Vec<_>>();
+        let mut transcript = self.initial_transcript(&public_values);
+
+        let multiplicities = self.multiplicities(witness, &publics)?;
+        let mut later_witness: Option<Vec<(String, Vec<F>)>> = None;
+        let mut challenges = BTreeMap::new();
+        let mut stages = self.domains.iter().map(|_| vec![]).collect::<Vec<_>>();
+        for stage in 0..self.constraints.stage_count() {
+            for ((domain, constraints), stages) in self
+                .domains
+                .iter()
+                .zip(self.constraints.domains())
+                .zip(&mut stages)
+            {
+                let names = &constraints.matrices()[stage + 1];
+                let columns = match &later_witness {
+                    // The multiplicities of lookups are committed to in the first stage.
+                    None => CommittedColumns::from_named_values(
+                        names,
+                        &(witness, multiplicities.as_slice()),
+                        &domain.params,
+                    )?,
+                    Some(later_witness) => CommittedColumns::from_named_values(
+                        names,
+                        later_witness.as_slice(),
+                        &domain.params,
+                    )?,
+                };
+                transcript.absorb_hash(&columns.root());
+                stages.push(columns);
+            }
+
+            for id in self.constraints.challenges(stage) {
+                challenges.insert(*id, transcript.challenge());
+            }
+            if stage + 1 < self.constraints.stage_count() {
+                let (first_stage, witgen_callback) = later_stages.as_ref().unwrap();
+                log::info!("Running witness generation for stage {}.", stage + 1);
+                later_witness = Some(witgen_callback.next_stage_witness(
+                    later_witness.as_deref().unwrap_or(first_stage),
+                    challenges.clone(),
+                    (stage + 1) as u8,
+                ));
+            }
+        }
+
+        let (lookup_alpha, lookup_beta) = if self.constraints.lookups().is_empty() {
+            (F::zero(), F::zero())
+        } else {
+            (transcript.challenge(), transcript.challenge())
+        };
+        let accumulators = (0..self.domains.len())
+            .map(|domain| {
+                let (columns, sums) = self.lookup_accumulators(
+                    domain,
+                    &stages[domain],
+                    &publics,
+                    (lookup_alpha, lookup_beta),
+                )?;
+                let columns = columns.map(|columns| {
+                    let columns = CommittedColumns::from_coefficients(
+                        columns.iter().map(|c| interpolate(c)).collect(),
+                        &self.domains[domain].params,
+                    );
+                    transcript.absorb_hash(&columns.root());
+                    columns
+                });
+                Ok((columns, sums))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        for lookup in self.constraints.lookups() {
+            let sum = |(domain, side): (usize, usize)| accumulators[domain].1[side];
+            if sum(lookup.left) != sum(lookup.right) {
+                return Err("The witness does not satisfy the constraints.".to_string());
+            }
+        }
+        for (_, sums) in &accumulators {
+            transcript.absorb_values(sums);
+        }
+
+        let traces = self
+            .domains
+            .iter()
+            .zip(&stages)
+            .zip(&accumulators)
+            .map(|((domain, stages), (accumulators, _))| {
+                once(&domain.fixed)
+                    .chain(stages)
+                    .chain(accumulators)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let alpha = transcript.challenge();
+        let quotients = (0..self.domains.len())
+            .map(|domain| {
+                let lookup_values = LookupValues {
+                    alpha: lookup_alpha,
+                    beta: lookup_beta,
+                    sums: &accumulators[domain].1,
+                };
+                let quotient = self.quotient(
+                    domain,
+                    &traces[domain],
+                    alpha,
+                    &publics,
+                    &challenges,
+                    &lookup_values,
+                )?;
+                transcript.absorb_hash(&quotient.root());
+                Ok(quotient)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let zeta = draw_out_of_domain_point(&mut transcript, &self.domains);
+        let matrices = traces
+            .iter()
+            .zip(&quotients)
+            .map(|(trace, quotient)| trace.iter().cloned().chain(once(quotient)).collect())
+            .collect::<Vec<Vec<_>>>();
+        let (openings, next_openings): (Vec<_>, Vec<_>) = self
+            .domains
+            .iter()
+            .zip(&matrices)
+            .map(|(domain, matrices)| {
+                let zeta_next = zeta * root_of_unity::<F>(domain.params.degree);
+                let openings = matrices
+                    .iter()
+                    .map(|m| m.evaluate_at(zeta))
+                    .collect::<Vec<_>>();
+                let next_openings = matrices[..matrices.len() - 1]
+                    .iter()
+                    .map(|m| m.evaluate_at(zeta_next))
+                    .collect::<Vec<_>>();
+                openings
+                    .iter()
+                    .chain(&next_openings)
+                    .for_each(|values| transcript.absorb_values(values));
+                (openings, next_openings)
+            })
+            .unzip();
+
+        let beta = transcript.challenge();
+        let fri = self
+            .domains
+            .iter()
+            .enumerate()
+            .map(|(domain, Domain { params, .. })| {
+                let domain_size = params.domain_size();
+                let zeta_next = zeta * root_of_unity::<F>(params.degree);
+                let deep_values = (0..domain_size)
+                    .map(|i| {
+                        let rows = matrices[domain]
+                            .iter()
+                            .map(|m| m.row(i))
+                            .collect::<Vec<_>>();
+                        let rows = rows.iter().map(Vec::as_slice).collect::<Vec<_>>();
+                        let x = coset_shift::<F>()
+                            * root_of_unity::<F>(domain_size).pow((i as u64).into());
+                        deep_composition(
+                            &rows,
+                            &openings[domain],
+                            &next_openings[domain],
+                            x,
+                            (zeta, zeta_next),
+                            beta,
+                        )
+                    })
+                    .collect();
+                FriLayers::commit(deep_values, params.fri_rounds(), &mut transcript)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let domains = (0..self.domains.len())
+            .map(|domain| {
+                let params = &self.domains[domain].params;
+                let domain_size = params.domain_size();
+                let queries = (0..params.query_count)
+                    .map(|_| {
+                        let index = transcript.challenge_index(domain_size / 2);
+                        QueryProof {
+                            matrices: matrices[domain]
+                                .iter()
+                                .map(|m| [m.open(index), m.open(index + domain_size / 2)])
+                                .collect(),
+                            fri_layers: fri[domain].open(index),
+                        }
+                    })
+                    .collect();
+                DomainProof {
+                    stage_roots: stages[domain].iter().map(|s| s.root()).collect(),
+                    lookup_root: accumulators[domain].0.as_ref().map(|c| c.root()),
+                    lookup_sums: accumulators[domain].1.clone(),
+                    quotient_root: quotients[domain].root(),
+                    openings: openings[domain].clone(),
+                    next_openings: next_openings[domain].clone(),
+                    fri_roots: fri[domain].roots(),
+                    fri_final_value: fri[domain].final_value,
+                    queries,
+                }
+            })
+            .collect();
+
+        Ok(Proof { domains })
+    }
+
+    /// Computes and commits to the quotient of the combined constraints of the given domain
+    /// by the vanishing polynomial of its trace domain, given its committed matrices.
+    fn quotient(
+        &self,
+        domain: usize,
+        trace: &[&CommittedColumns<F>],
+        alpha: F,
+        publics: &BTreeMap<&str, F>,
+        challenges: &BTreeMap<u64, F>,
+        lookup_values: &LookupValues<F>,
+    ) -> Result<CommittedColumns<F>, String> {
+        let params = &self.domains[domain].params;
+        let domain_size = params.domain_size();
+
+        // Evaluate the quotient on the evaluation domain. The vanishing polynomial
+        // `x^n - 1` only takes `blowup` different values there.
+        let shift = coset_shift::<F>();
+        let vanishing_inverses = (0..params.blowup)
+            .map(|i| {
+                let x = shift * root_of_unity::<F>(domain_size).pow((i as u64).into());
+                F::one() / (x.pow((params.degree as u64).into()) - F::one())
+            })
+            .collect::<Vec<_>>();
+        let quotient_values = (0..domain_size)
+            .map(|i| {
+                let column = |(matrix, column): ColumnLocation, next: bool| {
+                    let row = if next { i + params.blowup } else { i };
+                    trace[matrix].evaluations()[column][row % domain_size]
+                };
+                self.constraints.domains()[domain].combine(
+                    alpha,
+                    &column,
+                    publics,
+                    challenges,
+                    lookup_values,
+                ) * vanishing_inverses[i % params.blowup]
+            })
+            .collect::<Vec<_>>();
+        let mut quotient = coset_interpolate(&quotient_values, shift);
+        let quotient_size = params.quotient_chunks * params.degree;
+        if quotient[quotient_size..].iter().any(|c| !c.is_zero()) {
+            return Err("The witness does not satisfy the constraints.".to_string());
+        }
+        quotient.truncate(quotient_size);
+        Ok(CommittedColumns::from_coefficients(
+            quotient.chunks(params.degree).map(|c| c.to_vec()).collect(),
+            params,
+        ))
+    }
+
+    /// Computes the multiplicities of the right sides of all lookups, i.e. how often each
+    /// of their rows is looked up by the left side, as named columns.
+    fn multiplicities(
+        &self,
+        witness: &(impl ColumnSource<F> + ?Sized),
+        publics: &BTreeMap<&str, F>,
+    ) -> Result<Vec<(String, Vec<F>)>, String> {
+        let values = (self.fixed, witness);
+        let mut multiplicities = vec![];
+        for lookup in self.constraints.lookups() {
+            let (domain, side) = lookup.right;
+            let constraints = &self.constraints.domains()[domain];
+            let Some((matrix, column)) = constraints.lookup_sides()[side].multiplicity else {
+                continue;
+            };
+            // If a tuple occurs in several rows, all lookups of it are attributed to the first one.
+            let mut rows = HashMap::new();
+            for (row, (selector, tuple)) in self
+                .lookup_side_rows(lookup.right, &values, publics)?
+                .into_iter()
+                .enumerate()
+                .rev()
+            {
+                if selector == F::one() {
+                    rows.insert(tuple, row);
+                }
+            }
+            let mut counts = vec![F::zero(); self.domains[domain].params.degree];
+            for (selector, tuple) in self.lookup_side_rows(lookup.left, &values, publics)? {
+                if selector.is_zero() {
+                    continue;
+                }
+                let row = rows.get(&tuple).ok_or_else(|| {
+                    "The witness does not satisfy the constraints: A looked up tuple does not exist."
+                        .to_string()
+                })?;
+                counts[*row] += selector;
+            }
+            multiplicities.push((constraints.matrices()[matrix][column].clone(), counts));
+        }
+        Ok(multiplicities)
+    }
+
+    /// Evaluates the selector and the tuple of the given lookup side on each row of the trace.
+    /// Only the columns referenced by the lookup side are read.
+    fn lookup_side_rows(
+        &self,
+        (domain, side): (usize, usize),
+        values: &(impl ColumnSource<F> + ?Sized),
+        publics: &BTreeMap<&str, F>,
+    ) -> Result<Vec<(F, Vec<F>)>, String> {
+        let constraints = &self.constraints.domains()[domain];
+        let degree = self.domains[domain].params.degree;
+        let side = &constraints.lookup_sides()[side];
+        // Lookups only reference fixed columns and witness columns of the first stage,
+        // but not the multiplicities, which are not computed yet.
+        let mut columns = HashMap::new();
+        let references = side
+            .selector
+            .iter()
+            .chain(&side.expressions)
+            .flat_map(|e| e.all_children())
+            .filter_map(|e| match e {
+                Expression::Reference(r) => Some(r),
+                _ => None,
+            });
+        for reference in references {
+            let (matrix, column) = constraints.location(&reference.poly_id);
+            if columns.contains_key(&(matrix, column)) {
+                continue;
+            }
+            let name = &constraints.matrices()[matrix][column];
+            let values = values
+                .column(name)
+                .filter(|values| values.len() == degree)
+                .ok_or_else(|| {
+                    format!("Column {name} is missing or does not have {degree} rows.")
+                })?;
+            columns.insert((matrix, column), values);
+        }
+        Ok((0..degree)
+            .map(|row| {
+                let column = |location: ColumnLocation, next: bool| {
+                    columns[&location][(row + usize::from(next)) % degree]
+                };
+                constraints.evaluate_lookup_side(side, &column, publics)
+            })
+            .collect())
+    }
+
+    /// Computes the LogUp accumulators of the lookup sides of the given domain on the trace
+    /// domain and their sums, given the committed matrices of the stages.
+    /// Returns no columns if the domain has no lookup sides.
+    fn lookup_accumulators(
+        &self,
+        domain: usize,
+        stages: &[CommittedColumns<F>],
+        publics: &BTreeMap<&str, F>,
+        challenges: (F, F),
+    ) -> Result<LookupAccumulators<F>, String> {
+        let constraints = &self.constraints.domains()[domain];
+        if constraints.lookup_sides().is_empty() {
+            return Ok((None, vec![]));
+        }
+        let degree = self.domains[domain].params.degree;
+        let trace_values = once(&self.domains[domain].fixed)
+            .chain(stages)
+            .take(2)
+            .map(|matrix| {
+                matrix
+                    .coefficients
+                    .iter()
+                    .map(|c| coset_evaluate(c, degree, F::one()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let degree_inverse = F::one() / F::from(degree as u64);
+        let mut columns = vec![];
+        let mut sums = vec![];
+        for side in constraints.lookup_sides() {
+            let terms = (0..degree)
+                .map(|row| {
+                    let column = |(matrix, column): ColumnLocation, next: bool| {
+                        trace_values[matrix][column][(row + usize::from(next)) % degree]
+                    };
+                    let (numerator, denominator) =
+                        constraints.lookup_term(side, &column, publics, challenges);
+                    if denominator.is_zero() {
+                        return Err("The LogUp challenge is a looked up value.".to_string());
+                    }
+                    Ok(numerator / denominator)
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let sum = terms.iter().fold(F::zero(), |acc, term| acc + *term);
+            let mut accumulator = Vec::with_capacity(degree);
+            terms.iter().fold(F::zero(), |acc, term| {
+                accumulator.push(acc);
+                acc + *term - sum * degree_inverse
+            });
+            columns.push(accumulator);
+            sums.push(sum);
+        }
+        Ok((Some(columns), sums))
+    }
+}
@@ -0,0 +1,776 @@
+//! A verifier for FRI STARK proofs written in powdr-asm, used for proof aggregation.
+//!
+//! The verifier is a straight-line program that performs all checks of the native
+//! verifier for a fixed list of proofs. Merkle trees and the transcript are based on
+//! the Poseidon permutation, which the program computes with the
+//! `std::hash::poseidon_gl::PoseidonGL` machine. The proofs are read from the prover
+//! inputs, while the verification key and the public values of the aggregated proofs
+//! are constants of the program.
+//!
+//! Since the program only depends on the verification key and the public values, a proof
+//! of it is checked by generating the same program from the public values and verifying
+//! the proof against it. Compiling and proving the program is left to the pipeline.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter, Write};
+use std::iter::once;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicUnaryOperator,
+};
+use powdr_number::{FieldElement, LargeInt};
+
+use super::merkle::{hash_to_values, Hash, MerkleOpening, RATE};
+use super::polynomial::{coset_shift, root_of_unity};
+use super::proof::{DomainProof, Proof, QueryProof};
+use super::transcript::Transcript;
+use super::FriStark;
+
+/// The number of rows of the Poseidon machine per permutation.
+const ROWS_PER_PERMUTATION: usize = 31;
+
+impl<'a, F: FieldElement> FriStark<'a, F> {
+    /// Generates the verifier program for proofs with the given public values.
+    pub(super) fn verifier_program(&self, publics: &[Vec<F>]) -> Result<String, String> {
+        let placeholder = self.placeholder_proof();
+        let proofs = publics
+            .iter()
+            .map(|publics| (placeholder.clone(), publics.clone()))
+            .collect::<Vec<_>>();
+        let program = self.program(&proofs)?;
+        log::info!(
+            "Generated verifier program with {} rows and {} permutations.",
+            program.statements.len(),
+            program.permutations
+        );
+        Ok(program.to_asm())
+    }
+
+    /// Verifies the given proofs, each with its public values, and returns the prover
+    /// inputs of the verifier program for their public values.
+    pub(super) fn verifier_inputs(&self, proofs: &[(Proof<F>, Vec<F>)]) -> Result<Vec<F>, String> {
+        for (proof, publics) in proofs {
+            self.verify_proof(proof, publics)
+                .map_err(|e| format!("Cannot aggregate an invalid proof: {e}"))?;
+        }
+        Ok(self.program(proofs)?.inputs)
+    }
+
+    /// A proof consisting of zeros, with the shape of a valid proof.
+    /// The verifier program only depends on the shape of the proofs.
+    fn placeholder_proof(&self) -> Proof<F> {
+        let params = &self.domains[0].params;
+        let column_counts = self.column_counts(0);
+        let stage_count = self.constraints.stage_count();
+        let depth = params.domain_size().trailing_zeros() as usize;
+        let opening = |count: usize, depth: usize| MerkleOpening {
+            values: vec![F::zero(); count],
+            path: vec![[0; 4]; depth],
+        };
+        let domain = DomainProof {
+            stage_roots: vec![[0; 4]; stage_count],
+            lookup_root: None,
+            lookup_sums: vec![],
+            quotient_root: [0; 4],
+            openings: column_counts.iter().map(|c| vec![F::zero(); *c]).collect(),
+            next_openings: column_counts[..=stage_count]
+                .iter()
+                .map(|c| vec![F::zero(); *c])
+                .collect(),
+            fri_roots: vec![[0; 4]; params.fri_rounds() - 1],
+            fri_final_value: F::zero(),
+            queries: (0..params.query_count)
+                .map(|_| QueryProof {
+                    matrices: column_counts
+                        .iter()
+                        .map(|c| [opening(*c, depth), opening(*c, depth)])
+                        .collect(),
+                    fri_layers: (1..params.fri_rounds())
+                        .map(|round| opening(2, depth - 1 - round))
+                        .collect(),
+                })
+                .collect(),
+        };
+        Proof {
+            domains: vec![domain],
+        }
+    }
+
+    /// Generates the program that verifies the given proofs, which need to have the shape
+    /// of valid proofs.
+    fn program(&self, proofs: &[(Proof<F>, Vec<F>)]) -> Result<Program<F>, String> {
+        if self.domains.len() > 1 {
+            return Err(
+                "The verifier program does not support PIL files with different degrees."
+                    .to_string(),
+            );
+        }
+        if !self.constraints.lookups().is_empty() {
+            return Err(
+                "The verifier program does not support lookups and permutations.".to_string(),
+            );
+        }
+        let mut program = Program::default();
+        for (proof, publics) in proofs {
+            if publics.len() != self.constraints.publics().len() {
+                return Err(format!(
+                    "Expected {} public values, got {}.",
+                    self.constraints.publics().len(),
+                    publics.len()
+                ));
+            }
+            self.verify_in_program(&mut program, &proof.domains[0], publics);
+        }
+        Ok(program)
+    }
+
+    /// Adds the checks of `verify_proof` for the given proof to the program.
+    fn verify_in_program(&self, p: &mut Program<F>, proof: &DomainProof<F>, publics: &[F]) {
+        let params = &self.domains[0].params;
+        let domain_size = params.domain_size();
+        let stage_count = self.constraints.stage_count();
+        let column_counts = self.column_counts(0);
+        let index_bits = (domain_size / 2).trailing_zeros() as usize;
+
+        p.init_transcript(&self.initial_transcript(publics));
+        let mut roots = vec![
+            hash_to_values::<F>(&self.verification_key.domains[0].fixed_root).map(Affine::from),
+        ];
+        let mut challenges = BTreeMap::new();
+        for (stage, root) in proof.stage_roots.iter().enumerate() {
+            let root = p.read_hash(&format!("root_{}", stage + 1), root);
+            p.absorb(&root);
+            roots.push(root);
+            for id in self.constraints.challenges(stage) {
+                challenges.insert(*id, p.squeeze(&format!("challenge_{id}")));
+            }
+        }
+        let alpha = p.squeeze("alpha");
+        let quotient_root = p.read_hash(&format!("root_{}", stage_count + 1), &proof.quotient_root);
+        p.absorb(&quotient_root);
+        roots.push(quotient_root);
+
+        // The native verifier draws a new point if the out-of-domain point is in the
+        // trace domain or in the evaluation domain, which happens with negligible
+        // probability. Here, these cases are rejected.
+        let zeta = p.squeeze("zeta");
+        let zeta_to_degree = p.power("zeta_to_degree", &zeta, params.degree);
+        p.inverse("tmp", &(zeta_to_degree.clone() - F::one().into()));
+        let zeta_to_domain_size = p.power("zeta_to_domain_size", &zeta_to_degree, params.blowup);
+        let shift_to_domain_size = coset_shift::<F>().pow((domain_size as u64).into());
+        p.inverse("tmp", &(zeta_to_domain_size - shift_to_domain_size.into()));
+        let zeta_next = p.assign(
+            "zeta_next",
+            zeta.clone() * root_of_unity::<F>(params.degree),
+        );
+
+        let openings = read_openings(p, "opening", &proof.openings);
+        let next_openings = read_openings(p, "next_opening", &proof.next_openings);
+        for values in openings.iter().chain(&next_openings) {
+            p.absorb(values);
+        }
+        let beta = p.squeeze("beta");
+        let mut gammas = vec![];
+        let mut fri_roots = vec![];
+        for round in 0..params.fri_rounds() {
+            gammas.push(p.squeeze(&format!("gamma_{round}")));
+            if let Some(root) = proof.fri_roots.get(round) {
+                let root = p.read_hash(&format!("fri_root_{round}"), root);
+                p.absorb(&root);
+                fri_roots.push(root);
+            }
+        }
+        let final_value = p.read("fri_final_value", proof.fri_final_value);
+        p.absorb(std::slice::from_ref(&final_value));
+
+        // Check the constraints at the out-of-domain point.
+        let publics = self
+            .constraints
+            .publics()
+            .iter()
+            .map(String::as_str)
+            .zip(publics.iter().cloned())
+            .collect();
+        let columns = [&openings[..], &next_openings[..]];
+        let mut combined = Affine::from(F::zero());
+        for constraint in self.constraints.domains()[0].constraints() {
+            p.temporaries = 0;
+            let value = self.evaluate_in_program(p, constraint, columns, &publics, &challenges);
+            combined = p.mul("combined", &combined, &alpha, &value);
+        }
+        let mut quotient = Affine::from(F::zero());
+        for chunk in openings.last().unwrap().iter().rev() {
+            quotient = p.mul("quotient", &quotient, &zeta_to_degree, chunk);
+        }
+        let vanishing = zeta_to_degree - F::one().into();
+        let expected = p.mul("tmp", &vanishing, &quotient, &F::zero().into());
+        p.assert_eq(&combined, &expected);
+
+        // Check that the openings are consistent with the commitments.
+        let value_count = column_counts[..=stage_count].iter().sum();
+        let beta_power = p.power("beta_power", &beta, value_count);
+        for query in &proof.queries {
+            p.squeeze("index");
+            let bits = p.decompose("index", index_bits);
+            let x = p.exponentiate_bits("x", coset_shift(), root_of_unity(domain_size), &bits);
+            let points = [x.clone(), -x];
+            let inverses = [0, 1].map(|half| {
+                [("zeta", &zeta), ("zeta_next", &zeta_next)].map(|(name, z)| {
+                    p.inverse(
+                        &format!("inverse_{half}_{name}"),
+                        &(points[half].clone() - z.clone()),
+                    )
+                })
+            });
+            let mut accumulators = [0, 1].map(|half| {
+                ["zeta", "zeta_next"]
+                    .map(|name| p.assign(&format!("deep_{half}_{name}"), F::zero().into()))
+            });
+            for (m, (openings_pair, root)) in query.matrices.iter().zip(&roots).enumerate() {
+                for (half, opening) in openings_pair.iter().enumerate() {
+                    let mut node = [F::zero(); 4].map(Affine::from);
+                    for (chunk_index, chunk) in opening.values.chunks(RATE).enumerate() {
+                        let values = chunk
+                            .iter()
+                            .enumerate()
+                            .map(|(j, v)| p.read(&format!("value_{j}"), *v))
+                            .collect::<Vec<_>>();
+                        for (j, value) in values.iter().enumerate() {
+                            let column = chunk_index * RATE + j;
+                            let [current, next] = &mut accumulators[half];
+                            p.accumulate(
+                                current,
+                                &(value.clone() - openings[m][column].clone()),
+                                &inverses[half][0],
+                                &beta,
+                            );
+                            if m <= stage_count {
+                                p.accumulate(
+                                    next,
+                                    &(value.clone() - next_openings[m][column].clone()),
+                                    &inverses[half][1],
+                                    &beta,
+                                );
+                            }
+                        }
+                        node = p.permute("node", &values, &node);
+                    }
+                    let path_bits = bits
+                        .iter()
+                        .cloned()
+                        .map(PathBit::Variable)
+                        .chain(once(PathBit::Constant(half == 1)));
+                    p.verify_path(node, &opening.path, path_bits, root);
+                }
+            }
+            let [low, high] = [0, 1].map(|half| {
+                let [current, next] = &accumulators[half];
+                p.mul(&format!("deep_{half}"), current, &beta_power, next)
+            });
+
+            // Check the query against the FRI layers, see `verify_query`.
+            let mut value = p.fold(&low, &high, &points[0], &gammas[0]);
+            let mut shift = coset_shift::<F>();
+            let mut size = domain_size;
+            for (round, (opening, root)) in query.fri_layers.iter().zip(&fri_roots).enumerate() {
+                shift = shift * shift;
+                size /= 2;
+                let leaf_bits = &bits[..index_bits - round - 1];
+                let values = [0, 1].map(|i| p.read(&format!("fri_value_{i}"), opening.values[i]));
+                let leaf = p.permute("node", &values, &[F::zero(); 4].map(Affine::from));
+                let path_bits = leaf_bits.iter().cloned().map(PathBit::Variable);
+                p.verify_path(leaf, &opening.path, path_bits, root);
+                let position_bit = &bits[leaf_bits.len()];
+                let selected = p.mul(
+                    "tmp",
+                    position_bit,
+                    &(values[1].clone() - values[0].clone()),
+                    &values[0],
+                );
+                p.assert_eq(&value, &selected);
+                let x = p.exponentiate_bits("x", shift, root_of_unity(size), leaf_bits);
+                value = p.fold(&values[0], &values[1], &x, &gammas[round + 1]);
+            }
+            p.assert_eq(&value, &final_value);
+        }
+    }
+
+    /// Evaluates a constraint at the out-of-domain point in the program, given the
+    /// registers of the openings at the current and the next row.
+    fn evaluate_in_program(
+        &self,
+        p: &mut Program<F>,
+        expr: &Expression<F>,
+        columns: [&[Vec<Affine<F>>]; 2],
+        publics: &BTreeMap<&str, F>,
+        challenges: &BTreeMap<u64, Affine<F>>,
+    ) -> Affine<F> {
+        let eval =
+            |p: &mut Program<F>, e| self.evaluate_in_program(p, e, columns, publics, challenges);
+        match expr {
+            Expression::Reference(r) => {
+                let (matrix, column) = self.constraints.domains()[0].location(&r.poly_id);
+                let openings = columns[r.next as usize];
+                openings[matrix][column].clone()
+            }
+            Expression::PublicReference(name) => publics[name.as_str()].into(),
+            Expression::Challenge(challenge) => challenges[&challenge.id].clone(),
+            Expression::Number(n) => (*n).into(),
+            Expression::BinaryOperation(left, op, right) => {
+                let left = eval(p, left);
+                match op {
+                    AlgebraicBinaryOperator::Add => left + eval(p, right),
+                    AlgebraicBinaryOperator::Sub => left - eval(p, right),
+                    AlgebraicBinaryOperator::Mul => {
+                        let right = eval(p, right);
+                        p.product(&left, &right)
+                    }
+                    AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                        Expression::Number(exponent) => {
+                            let exponent = exponent.to_integer().try_into_u64().unwrap();
+                            (0..exponent).fold(F::one().into(), |acc, _| p.product(&acc, &left))
+                        }
+                        _ => unreachable!("Exponent has to be a number."),
+                    },
+                }
+            }
+            Expression::UnaryOperation(op, inner) => match op {
+                AlgebraicUnaryOperator::Minus => -eval(p, inner),
+            },
+        }
+    }
+}
+
+fn read_openings<T: FieldElement>(
+    p: &mut Program<T>,
+    name: &str,
+    openings: &[Vec<T>],
+) -> Vec<Vec<Affine<T>>> {
+    openings
+        .iter()
+        .enumerate()
+        .map(|(matrix, values)| {
+            values
+                .iter()
+                .enumerate()
+                .map(|(column, v)| p.read(&format!("{name}_{matrix}_{column}"), *v))
+                .collect()
+        })
+        .collect()
+}
+
+/// A bit of the index of a leaf in a Merkle tree, which decides the order of
+/// a node and its sibling.
+enum PathBit<T> {
+    Variable(Affine<T>),
+    Constant(bool),
+}
+
+/// A straight-line program for the verifier machine.
+#[derive(Default)]
+struct Program<T> {
+    registers: BTreeSet<String>,
+    statements: Vec<String>,
+    /// The values of the prover inputs read by the program.
+    inputs: Vec<T>,
+    permutations: usize,
+    /// The counter of the transcript, which is known when the program is generated.
+    transcript_counter: u64,
+    /// The number of temporary registers used by the current constraint.
+    temporaries: usize,
+}
+
+impl<T: FieldElement> Program<T> {
+    fn register(&mut self, name: &str) -> Affine<T> {
+        self.registers.insert(name.to_string());
+        Affine::register(name)
+    }
+
+    /// Reads the next prover input. The inputs are read through a single column
+    /// instead of one free input per statement, whose prover query would have an
+    /// arm for each of them.
+    fn read(&mut self, name: &str, value: T) -> Affine<T> {
+        self.statements.push(format!("{name} <== read_input();"));
+        self.inputs.push(value);
+        self.register(name)
+    }
+
+    fn read_hash(&mut self, name: &str, hash: &Hash) -> [Affine<T>; 4] {
+        let values = hash_to_values::<T>(hash);
+        [0, 1, 2, 3].map(|i| self.read(&format!("{name}_{i}"), values[i]))
+    }
+
+    fn assign(&mut self, name: &str, value: Affine<T>) -> Affine<T> {
+        self.statements.push(format!("{name} <=X= {value};"));
+        self.register(name)
+    }
+
+    /// Computes `x * y + z`.
+    fn mul(&mut self, name: &str, x: &Affine<T>, y: &Affine<T>, z: &Affine<T>) -> Affine<T> {
+        self.statements
+            .push(format!("{name} <== mul({x}, {y}, {z});"));
+        self.register(name)
+    }
+
+    /// Computes `x * y`, using a new temporary register if neither of them is constant.
+    fn product(&mut self, x: &Affine<T>, y: &Affine<T>) -> Affine<T> {
+        match (x.as_constant(), y.as_constant()) {
+            (Some(c), _) => y.clone() * c,
+            (_, Some(c)) => x.clone() * c,
+            _ => {
+                let name = format!("e_{}", self.temporaries);
+                self.temporaries += 1;
+                self.mul(&name, x, y, &T::zero().into())
+            }
+        }
+    }
+
+    /// Computes the inverse of `x`, which fails if `x` is zero.
+    fn inverse(&mut self, name: &str, x: &Affine<T>) -> Affine<T> {
+        self.statements.push(format!("{name} <== inverse({x});"));
+        self.register(name)
+    }
+
+    fn assert_eq(&mut self, x: &Affine<T>, y: &Affine<T>) {
+        self.statements.push(format!("assert_eq {x}, {y};"));
+    }
+
+    fn power(&mut self, name: &str, base: &Affine<T>, exponent: usize) -> Affine<T> {
+        let mut result = Affine::from(T::one());
+        for bit in (0..usize::BITS - exponent.leading_zeros()).rev() {
+            result = self.mul(name, &result, &result, &T::zero().into());
+            if (exponent >> bit) & 1 == 1 {
+                result = self.mul(name, &result, base, &T::zero().into());
+            }
+        }
+        result
+    }
+
+    /// Computes `shift * root^i`, where `i` is given by its bits, lowest first.
+    fn exponentiate_bits(
+        &mut self,
+        name: &str,
+        shift: T,
+        root: T,
+        bits: &[Affine<T>],
+    ) -> Affine<T> {
+        let mut result = self.assign(name, shift.into());
+        let mut power = root;
+        for bit in bits {
+            let factor = bit.clone() * (power - T::one()) + T::one().into();
+            result = self.mul(name, &result, &factor, &T::zero().into());
+            power = power * power;
+        }
+        result
+    }
+
+    /// Decomposes the value of the given register into 64 bits and returns the
+    /// lowest `count` of them. Each `low_bit` removes the lowest bit from `remaining`
+    /// and halves it, so it is zero in the end exactly if the bits sum up to the value.
+    /// Since the bits are not checked to be the canonical representation, values
+    /// smaller than `2^64 - p` have a second decomposition, which only happens with
+    /// negligible probability for random values.
+    fn decompose(&mut self, register: &str, count: usize) -> Vec<Affine<T>> {
+        self.statements.push(format!("remaining <=X= {register};"));
+        let mut bits = vec![];
+        for i in 0..64 {
+            let name = if i < count {
+                format!("bit_{i}")
+            } else {
+                "high_bit".to_string()
+            };
+            self.statements.push(format!("{name} <== low_bit();"));
+            let bit = self.register(&name);
+            if i < count {
+                bits.push(bit);
+            }
+        }
+        self.assert_eq(&Affine::register("remaining"), &T::zero().into());
+        bits
+    }
+
+    /// Updates `accumulator` to `accumulator * beta + value * inverse`.
+    fn accumulate(
+        &mut self,
+        accumulator: &mut Affine<T>,
+        value: &Affine<T>,
+        inverse: &Affine<T>,
+        beta: &Affine<T>,
+    ) {
+        let term = self.mul("tmp", value, inverse, &T::zero().into());
+        let name = accumulator.to_string();
+        *accumulator = self.mul(&name, accumulator, beta, &term);
+    }
+
+    /// Computes the value of the folded polynomial, see `fri::fold`.
+    fn fold(
+        &mut self,
+        value: &Affine<T>,
+        negated_value: &Affine<T>,
+        x: &Affine<T>,
+        gamma: &Affine<T>,
+    ) -> Affine<T> {
+        let x_inverse = self.inverse("fold_x_inverse", x);
+        let difference = value.clone() - negated_value.clone();
+        let term = self.mul("tmp", gamma, &difference, &T::zero().into());
+        let term = self.mul("tmp", &term, &x_inverse, &T::zero().into());
+        let half = T::one() / T::from(2);
+        self.assign(
+            "folded",
+            (value.clone() + negated_value.clone() + term) * half,
+        )
+    }
+
+    /// Applies the Poseidon permutation to the rate (padded with zeros) and the capacity
+    /// and stores the first four elements of the result in the registers `{name}_i`.
+    fn permute(
+        &mut self,
+        name: &str,
+        rate: &[Affine<T>],
+        capacity: &[Affine<T>; 4],
+    ) -> [Affine<T>; 4] {
+        assert!(rate.len() <= RATE);
+        let mut inputs = rate.to_vec();
+        inputs.resize(RATE, T::zero().into());
+        for (i, input) in inputs.iter().chain(capacity).enumerate() {
+            self.statements.push(format!("P{i} <=X= {input};"));
+        }
+        self.statements.push("poseidon;".to_string());
+        self.permutations += 1;
+        [0, 1, 2, 3]
+            .map(|i| self.assign(&format!("{name}_{i}"), Affine::register(&format!("P{i}"))))
+    }
+
+    /// Checks that `leaf` is the leaf at the position given by `bits` (lowest first)
+    /// in the tree with the given root.
+    fn verify_path(
+        &mut self,
+        leaf: [Affine<T>; 4],
+        path: &[Hash],
+        bits: impl Iterator<Item = PathBit<T>>,
+        root: &[Affine<T>; 4],
+    ) {
+        let mut node = leaf;
+        for (sibling, bit) in path.iter().zip(bits) {
+            let sibling = self.read_hash("sibling", sibling);
+            let (left, right) = match bit {
+                PathBit::Constant(false) => (node, sibling),
+                PathBit::Constant(true) => (sibling, node),
+                PathBit::Variable(bit) => {
+                    let left = [0, 1, 2, 3].map(|i| {
+                        let difference = sibling[i].clone() - node[i].clone();
+                        self.mul(&format!("left_{i}"), &bit, &difference, &node[i])
+                    });
+                    let right = [0, 1, 2, 3]
+                        .map(|i| node[i].clone() + sibling[i].clone() - left[i].clone());
+                    (left, right)
+                }
+            };
+            let children = left.into_iter().chain(right).collect::<Vec<_>>();
+            node = self.permute("node", &children, &[T::zero(); 4].map(Affine::from));
+        }
+        for (node, root) in node.iter().zip(root) {
+            self.assert_eq(node, root);
+        }
+    }
+
+    fn transcript_state(&mut self) -> [Affine<T>; 4] {
+        [0, 1, 2, 3].map(|i| self.register(&format!("transcript_{i}")))
+    }
+
+    /// Sets the state of the transcript, which is known when the program is generated.
+    fn init_transcript(&mut self, transcript: &Transcript<T>) {
+        let (state, counter) = transcript.state();
+        for (i, value) in state.into_iter().enumerate() {
+            self.assign(&format!("transcript_{i}"), value.into());
+        }
+        self.transcript_counter = counter;
+    }
+
+    /// Absorbs the values into the transcript, see `Transcript::absorb_values`.
+    fn absorb(&mut self, values: &[Affine<T>]) {
+        for chunk in values.chunks(RATE) {
+            let state = self.transcript_state();
+            self.permute("transcript", chunk, &state);
+        }
+        self.transcript_counter = 0;
+    }
+
+    /// Draws a challenge from the transcript into the given register, see `Transcript::challenge`.
+    fn squeeze(&mut self, name: &str) -> Affine<T> {
+        let inputs = self
+            .transcript_state()
+            .into_iter()
+            .chain(once(T::from(self.transcript_counter).into()))
+            .collect::<Vec<_>>();
+        self.transcript_counter += 1;
+        let [output, ..] = self.permute("squeeze", &inputs, &[T::zero(); 4].map(Affine::from));
+        self.assign(name, output)
+    }
+
+    fn to_asm(&self) -> String {
+        let degree = (self.statements.len() + 8)
+            .max((self.permutations + 1) * ROWS_PER_PERMUTATION)
+            .next_power_of_two();
+        let indent = |lines: Vec<String>, depth: usize| {
+            lines.into_iter().fold(String::new(), |mut result, line| {
+                writeln!(result, "{}{line}", " ".repeat(depth)).unwrap();
+                result
+            })
+        };
+        let registers = once("reg pc[@pc];".to_string())
+            .chain(["X", "Y", "Z", "W"].map(|name| format!("reg {name}[<=];")))
+            .chain((0..12).map(|i| format!("reg P{i};")))
+            .chain(["input_index", "remaining"].map(|name| format!("reg {name};")))
+            .chain(self.registers.iter().map(|name| format!("reg {name};")))
+            .collect();
+        let input_count = self.inputs.len();
+        let half = T::one() / T::from(2);
+        format!(
+            r#"use std::hash::poseidon_gl::PoseidonGL;
+
+machine Main {{
+    degree {degree};
+
+{}
+    PoseidonGL poseidon_gl;
+
+    col witness input_value(i) query if std::convert::int(std::prover::eval(input_index)) < {input_count} {{
+        std::utils::Option::Some(std::prover::Query::Input(std::convert::int(std::prover::eval(input_index))))
+    }} else {{
+        std::utils::Option::None
+    }};
+    col witness low_bit_value(i) query std::prover::hint(std::convert::fe(std::convert::int(std::prover::eval(remaining)) % 2));
+
+    instr poseidon ~ poseidon_gl.poseidon_permutation P0, P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11 -> P0', P1', P2', P3';
+    instr read_input -> X {{ X = input_value, input_index' = input_index + 1 }}
+    instr low_bit -> X {{ X = low_bit_value, X * (1 - X) = 0, remaining' = (remaining - X) * {half} }}
+    instr mul X, Y, Z -> W {{ W = X * Y + Z }}
+    instr inverse X -> Y {{ X * Y = 1 }}
+    instr assert_eq X, Y {{ X = Y }}
+
+    function main {{
+{}        return;
+    }}
+}}
+"#,
+            indent(registers, 4),
+            indent(self.statements.clone(), 8)
+        )
+    }
+}
+
+/// A linear combination of registers plus a constant, which can be assigned
+/// to an assignment register.
+#[derive(Debug, Clone, PartialEq)]
+struct Affine<T> {
+    terms: BTreeMap<String, T>,
+    constant: T,
+}
+
+impl<T: FieldElement> Affine<T> {
+    fn register(name: &str) -> Self {
+        Affine {
+            terms: [(name.to_string(), T::one())].into(),
+            constant: T::zero(),
+        }
+    }
+
+    fn as_constant(&self) -> Option<T> {
+        self.terms.is_empty().then_some(self.constant)
+    }
+}
+
+impl<T: FieldElement> From<T> for Affine<T> {
+    fn from(constant: T) -> Self {
+        Affine {
+            terms: BTreeMap::new(),
+            constant,
+        }
+    }
+}
+
+impl<T: FieldElement> Add for Affine<T> {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        for (name, coefficient) in other.terms {
+            let sum = *self.terms.get(&name).unwrap_or(&T::zero()) + coefficient;
+            if sum.is_zero() {
+                self.terms.remove(&name);
+            } else {
+                self.terms.insert(name, sum);
+            }
+        }
+        self.constant += other.constant;
+        self
+    }
+}
+
+impl<T: FieldElement> Neg for Affine<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -T::one()
+    }
+}
+
+impl<T: FieldElement> Sub for Affine<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl<T: FieldElement> Mul<T> for Affine<T> {
+    type Output = Self;
+
+    fn mul(self, factor: T) -> Self {
+        if factor.is_zero() {
+            return T::zero().into();
+        }
+        Affine {
+            terms: self
+                .terms
+                .into_iter()
+                .map(|(name, coefficient)| (name, coefficient * factor))
+                .collect(),
+            constant: self.constant * factor,
+        }
+    }
+}
+
+impl<T: FieldElement> Display for Affine<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let terms = self
+            .terms
+            .iter()
+            .map(|(name, coefficient)| (*coefficient, Some(name)))
+            .chain(once((self.constant, None)))
+            .filter(|(coefficient, _)| !coefficient.is_zero())
+            .collect::<Vec<_>>();
+        if terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (i, (coefficient, name)) in terms.into_iter().enumerate() {
+            // Print coefficients in the upper half of the field as negative numbers.
+            let negative =
+                (-coefficient).to_arbitrary_integer() < coefficient.to_arbitrary_integer();
+            let magnitude = if negative { -coefficient } else { coefficient };
+            match (i, negative) {
+                (0, false) => {}
+                (0, true) => write!(f, "0 - ")?,
+                (_, false) => write!(f, " + ")?,
+                (_, true) => write!(f, " - ")?,
+            }
+            match name {
+                Some(name) if magnitude.is_one() => write!(f, "{name}")?,
+                Some(name) => write!(f, "{} * {name}", magnitude.to_arbitrary_integer())?,
+                None => write!(f, "{}", magnitude.to_arbitrary_integer())?,
+            }
+        }
+        Ok(())
+    }
+}
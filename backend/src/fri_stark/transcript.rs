@@ -0,0 +1,58 @@
+//! A Fiat-Shamir transcript based on the Poseidon permutation over the Goldilocks field,
+//! so that it can be replayed efficiently by the recursive verifier.
+
+use powdr_number::{FieldElement, LargeInt};
+
+use super::merkle::{hash_to_values, hash_values, permute, Hash, RATE};
+
+pub struct Transcript<T> {
+    state: [T; 4],
+    /// A counter to derive several challenges from the same state.
+    counter: u64,
+}
+
+impl<T: FieldElement> Transcript<T> {
+    pub fn new(label: &[u8]) -> Self {
+        let label = label.iter().map(|b| T::from(*b as u64)).collect::<Vec<_>>();
+        Transcript {
+            state: hash_to_values(&hash_values(&label)),
+            counter: 0,
+        }
+    }
+
+    /// The current state and counter.
+    pub fn state(&self) -> ([T; 4], u64) {
+        (self.state, self.counter)
+    }
+
+    pub fn absorb_hash(&mut self, hash: &Hash) {
+        self.absorb_values(&hash_to_values::<T>(hash));
+    }
+
+    /// Absorbs the values in chunks of `RATE` elements, using the state as capacity.
+    pub fn absorb_values(&mut self, values: &[T]) {
+        for chunk in values.chunks(RATE) {
+            self.state = permute(chunk, self.state);
+        }
+        self.counter = 0;
+    }
+
+    fn squeeze(&mut self) -> T {
+        let mut inputs = self.state.to_vec();
+        inputs.push(T::from(self.counter));
+        self.counter += 1;
+        permute(&inputs, [T::zero(); 4])[0]
+    }
+
+    /// Draws a field element.
+    pub fn challenge(&mut self) -> T {
+        self.squeeze()
+    }
+
+    /// Draws an index in `0..bound`, where `bound` has to be a power of two,
+    /// by taking the lowest bits of a field element.
+    pub fn challenge_index(&mut self, bound: usize) -> usize {
+        assert!(bound.is_power_of_two());
+        (self.squeeze().to_integer().try_into_u64().unwrap() as usize) & (bound - 1)
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+
+use powdr_number::FieldElement;
+
+use super::constraints::{ColumnLocation, LookupValues};
+use super::fri::{replay_commit_phase, verify_query};
+use super::polynomial::{coset_shift, root_of_unity};
+use super::proof::{DomainProof, Proof};
+use super::transcript::Transcript;
+use super::{deep_composition, draw_out_of_domain_point, FriStark};
+
+impl<'a, F: FieldElement> FriStark<'a, F> {
+    pub(super) fn verify_proof(&self, proof: &Proof<F>, publics: &[F]) -> Result<(), String> {
+        let stage_count = self.constraints.stage_count();
+
+        if publics.len() != self.constraints.publics().len() {
+            return Err(format!(
+                "Expected {} public values, got {}.",
+                self.constraints.publics().len(),
+                publics.len()
+            ));
+        }
+        if proof.domains.len() != self.domains.len() {
+            return Err(format!(
+                "Expected proofs of {} domains, got {}.",
+                self.domains.len(),
+                proof.domains.len()
+            ));
+        }
+        for (domain, domain_proof) in proof.domains.iter().enumerate() {
+            if domain_proof.stage_roots.len() != stage_count {
+                return Err(format!(
+                    "Expected {stage_count} stage commitments, got {}.",
+                    domain_proof.stage_roots.len()
+                ));
+            }
+            let column_counts = self.column_counts(domain);
+            if domain_proof
+                .openings
+                .iter()
+                .map(Vec::len)
+                .ne(column_counts.iter().cloned())
+                || domain_proof
+                    .next_openings
+                    .iter()
+                    .map(Vec::len)
+                    .ne(column_counts[..column_counts.len() - 1].iter().cloned())
+            {
+                return Err("The openings do not match the committed columns.".to_string());
+            }
+            let lookup_sides = self.constraints.domains()[domain].lookup_sides().len();
+            if domain_proof.lookup_sums.len() != lookup_sides
+                || domain_proof.lookup_root.is_some() != (lookup_sides > 0)
+            {
+                return Err("The LogUp accumulators do not match the lookups.".to_string());
+            }
+        }
+
+        let mut transcript = self.initial_transcript(publics);
+        let mut challenges = BTreeMap::new();
+        for stage in 0..stage_count {
+            for domain_proof in &proof.domains {
+                transcript.absorb_hash(&domain_proof.stage_roots[stage]);
+            }
+            for id in self.constraints.challenges(stage) {
+                challenges.insert(*id, transcript.challenge());
+            }
+        }
+        let (lookup_alpha, lookup_beta) = if self.constraints.lookups().is_empty() {
+            (F::zero(), F::zero())
+        } else {
+            (transcript.challenge(), transcript.challenge())
+        };
+        for root in proof.domains.iter().filter_map(|d| d.lookup_root.as_ref()) {
+            transcript.absorb_hash(root);
+        }
+        for domain_proof in &proof.domains {
+            transcript.absorb_values(&domain_proof.lookup_sums);
+        }
+        let alpha = transcript.challenge();
+        for domain_proof in &proof.domains {
+            transcript.absorb_hash(&domain_proof.quotient_root);
+        }
+        let zeta: F = draw_out_of_domain_point(&mut transcript, &self.domains);
+        for domain_proof in &proof.domains {
+            domain_proof
+                .openings
+                .iter()
+                .chain(&domain_proof.next_openings)
+                .for_each(|values| transcript.absorb_values(values));
+        }
+        let beta = transcript.challenge();
+        let gammas = self
+            .domains
+            .iter()
+            .zip(&proof.domains)
+            .map(|(domain, domain_proof)| {
+                replay_commit_phase(
+                    &domain_proof.fri_roots,
+                    domain_proof.fri_final_value,
+                    domain.params.fri_rounds(),
+                    &mut transcript,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for lookup in self.constraints.lookups() {
+            let sum = |(domain, side): (usize, usize)| proof.domains[domain].lookup_sums[side];
+            if sum(lookup.left) != sum(lookup.right) {
+                return Err(
+                    "The sums of the LogUp accumulators of a lookup do not match.".to_string(),
+                );
+            }
+        }
+
+        // Check the constraints at the out-of-domain point.
+        let publics = self
+            .constraints
+            .publics()
+            .iter()
+            .map(String::as_str)
+            .zip(publics.iter().cloned())
+            .collect();
+        for (domain, domain_proof) in proof.domains.iter().enumerate() {
+            let column = |(matrix, column): ColumnLocation, next: bool| {
+                if next {
+                    domain_proof.next_openings[matrix][column]
+                } else {
+                    domain_proof.openings[matrix][column]
+                }
+            };
+            let lookup_values = LookupValues {
+                alpha: lookup_alpha,
+                beta: lookup_beta,
+                sums: &domain_proof.lookup_sums,
+            };
+            let combined = self.constraints.domains()[domain].combine(
+                alpha,
+                &column,
+                &publics,
+                &challenges,
+                &lookup_values,
+            );
+            let zeta_to_degree = zeta.pow((self.domains[domain].params.degree as u64).into());
+            let quotient = domain_proof
+                .openings
+                .last()
+                .unwrap()
+                .iter()
+                .rev()
+                .fold(F::zero(), |acc, chunk| acc * zeta_to_degree + *chunk);
+            if combined != (zeta_to_degree - F::one()) * quotient {
+                return Err(
+                    "The constraints are not satisfied at the out-of-domain point.".to_string(),
+                );
+            }
+        }
+
+        // Check that the openings are consistent with the commitments.
+        for (domain, domain_proof) in proof.domains.iter().enumerate() {
+            self.verify_queries(
+                domain,
+                domain_proof,
+                &mut transcript,
+                (zeta, beta),
+                &gammas[domain],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Checks the queries of the given domain, drawing their indices from the transcript.
+    fn verify_queries(
+        &self,
+        domain: usize,
+        proof: &DomainProof<F>,
+        transcript: &mut Transcript<F>,
+        (zeta, beta): (F, F),
+        gammas: &[F],
+    ) -> Result<(), String> {
+        let params = &self.domains[domain].params;
+        let domain_size = params.domain_size();
+        let zeta_next = zeta * root_of_unity::<F>(params.degree);
+        if proof.queries.len() != params.query_count {
+            return Err(format!(
+                "Expected {} queries, got {}.",
+                params.query_count,
+                proof.queries.len()
+            ));
+        }
+        let column_counts = self.column_counts(domain);
+        let roots = self.matrix_roots(
+            domain,
+            &proof.stage_roots,
+            proof.lookup_root,
+            proof.quotient_root,
+        );
+        for query in &proof.queries {
+            let index = transcript.challenge_index(domain_size / 2);
+            if query.matrices.len() != roots.len() {
+                return Err("Wrong number of matrix openings.".to_string());
+            }
+            for ((root, openings), count) in roots.iter().zip(&query.matrices).zip(&column_counts) {
+                // Each matrix is opened at the query row and its negation.
+                if openings.len() != 2 {
+                    return Err("Expected two openings per matrix.".to_string());
+                }
+                for (opening, row) in openings.iter().zip([index, index + domain_size / 2]) {
+                    if opening.values.len() != *count || !opening.verify(root, row) {
+                        return Err("Invalid matrix opening.".to_string());
+                    }
+                }
+            }
+            let x = coset_shift::<F>() * root_of_unity::<F>(domain_size).pow((index as u64).into());
+            let [low, high] = [0, 1].map(|half| {
+                let rows = query
+                    .matrices
+                    .iter()
+                    .map(|openings| openings[half].values.as_slice())
+                    .collect::<Vec<_>>();
+                let x = if half == 0 { x } else { -x };
+                deep_composition(
+                    &rows,
+                    &proof.openings,
+                    &proof.next_openings,
+                    x,
+                    (zeta, zeta_next),
+                    beta,
+                )
+            });
+            verify_query(
+                index,
+                (low, high),
+                domain_size,
+                &proof.fri_roots,
+                &query.fri_layers,
+                gammas,
+                proof.fri_final_value,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -17,6 +17,9 @@ impl<F: FieldElement> BackendFactory<F> for Halo2ProverFactory {
         setup: Option<&mut dyn io::Read>,
         verification_key: Option<&mut dyn io::Read>,
     ) -> Result<Box<dyn crate::Backend<'a, F> + 'a>, Error> {
+        if pil.degrees().len() > 1 {
+            return Err(Error::NoVariableDegreeAvailable);
+        }
         let mut halo2 = Box::new(Halo2Prover::new(pil, fixed, setup)?);
         if let Some(vk) = verification_key {
             halo2.add_verification_key(vk);
@@ -76,6 +79,9 @@ impl<F: FieldElement> BackendFactory<F> for Halo2MockFactory {
         setup: Option<&mut dyn io::Read>,
         verification_key: Option<&mut dyn io::Read>,
     ) -> Result<Box<dyn crate::Backend<'a, F> + 'a>, Error> {
+        if pil.degrees().len() > 1 {
+            return Err(Error::NoVariableDegreeAvailable);
+        }
         if setup.is_some() {
             return Err(Error::NoSetupAvailable);
         }
@@ -1,10 +1,12 @@
 #![deny(clippy::print_stdout)]
 
+mod fri_stark;
 #[cfg(feature = "halo2")]
 mod halo2_impl;
 mod pilstark;
 
 use powdr_ast::analyzed::Analyzed;
+use powdr_executor::column_store::ColumnStore;
 use powdr_executor::witgen::WitgenCallback;
 use powdr_number::{DegreeType, FieldElement};
 use std::{io, path::Path};
@@ -22,6 +24,8 @@ pub enum BackendType {
     EStark,
     #[strum(serialize = "pil-stark-cli")]
     PilStarkCli,
+    #[strum(serialize = "fri-stark")]
+    FriStark,
 }
 
 impl BackendType {
@@ -32,6 +36,7 @@ impl BackendType {
         const HALO2_MOCK_FACTORY: halo2_impl::Halo2MockFactory = halo2_impl::Halo2MockFactory;
         const ESTARK_FACTORY: pilstark::estark::EStarkFactory = pilstark::estark::EStarkFactory;
         const PIL_STARK_CLI_FACTORY: pilstark::PilStarkCliFactory = pilstark::PilStarkCliFactory;
+        const FRI_STARK_FACTORY: fri_stark::FriStarkFactory = fri_stark::FriStarkFactory;
 
         match self {
             #[cfg(feature = "halo2")]
@@ -40,6 +45,7 @@ impl BackendType {
             BackendType::Halo2Mock => &HALO2_MOCK_FACTORY,
             BackendType::EStark => &ESTARK_FACTORY,
             BackendType::PilStarkCli => &PIL_STARK_CLI_FACTORY,
+            BackendType::FriStark => &FRI_STARK_FACTORY,
         }
     }
 }
@@ -56,6 +62,8 @@ pub enum Error {
     NoVerificationAvailable,
     #[error("the backend does not support proof aggregation")]
     NoAggregationAvailable,
+    #[error("the backend does not support machines of different degrees")]
+    NoVariableDegreeAvailable,
     #[error("internal backend error")]
     BackendError(String),
 }
@@ -95,7 +103,10 @@ pub trait BackendFactory<F: FieldElement> {
 pub trait Backend<'a, F: FieldElement> {
     /// Perform the proving.
     ///
-    /// If prev_proof is provided, proof aggregation is performed.
+    /// If prev_proof is provided, proof aggregation is performed. Backends that aggregate
+    /// proofs with a program from [Backend::verifier_program] return
+    /// [Error::NoAggregationAvailable] instead, since proving that program requires
+    /// compiling it. The pipeline proves the program in that case.
     ///
     /// Returns the generated proof.
     fn prove(
@@ -105,11 +116,44 @@ pub trait Backend<'a, F: FieldElement> {
         witgen_callback: WitgenCallback<F>,
     ) -> Result<Proof, Error>;
 
+    /// Perform the proving on a witness that has been spilled to disk, e.g. by
+    /// [powdr_executor::witgen::WitnessGenerator::generate_into].
+    ///
+    /// The default implementation reads all columns into memory and calls [Backend::prove],
+    /// so it only saves memory during witness generation. Backends that can consume the
+    /// columns one at a time should override it.
+    fn prove_from_store(
+        &self,
+        witness: &ColumnStore<F>,
+        prev_proof: Option<Proof>,
+        witgen_callback: WitgenCallback<F>,
+    ) -> Result<Proof, Error> {
+        let names = witness.column_names();
+        let witness = witness
+            .read_columns(names.iter().map(|name| name.as_str()))
+            .collect::<Vec<_>>();
+        self.prove(&witness, prev_proof, witgen_callback)
+    }
+
     /// Verifies a proof.
     fn verify(&self, _proof: &[u8], _instances: &[Vec<F>]) -> Result<(), Error> {
         Err(Error::NoVerificationAvailable)
     }
 
+    /// Generates a program in powdr-asm that verifies proofs of this backend with the
+    /// given public values, one list of values per proof. The program only depends on
+    /// the verification key and the public values, so a proof of the program with the
+    /// inputs from [Backend::verifier_inputs] aggregates the verified proofs.
+    fn verifier_program(&self, _publics: &[Vec<F>]) -> Result<String, Error> {
+        Err(Error::NoAggregationAvailable)
+    }
+
+    /// Verifies the given proofs, each together with its public values, and returns
+    /// the prover inputs of the program generated by [Backend::verifier_program].
+    fn verifier_inputs(&self, _proofs: &[(Proof, Vec<F>)]) -> Result<Vec<F>, Error> {
+        Err(Error::NoAggregationAvailable)
+    }
+
     /// Exports the setup in a backend specific format. Can be used to create a
     /// new backend object of the same kind.
     fn export_setup(&self, _output: &mut dyn io::Write) -> Result<(), Error> {
@@ -1,11 +1,17 @@
+use std::collections::BTreeMap;
 use std::io;
 use std::iter::{once, repeat};
 use std::time::Instant;
 
+use crate::fri_stark::merkle::{hash_values, Hash, MerkleTree};
+use crate::fri_stark::transcript::Transcript;
 use crate::{pilstark, Backend, BackendFactory, Error};
-use powdr_ast::analyzed::Analyzed;
+use powdr_ast::analyzed::{AlgebraicExpression, Analyzed};
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_executor::column_store::ColumnStore;
 use powdr_executor::witgen::WitgenCallback;
 use powdr_number::{DegreeType, FieldElement, GoldilocksField, LargeInt};
+use serde::{Deserialize, Serialize};
 
 use starky::{
     merklehash::MerkleTreeGL,
@@ -18,6 +24,10 @@ use starky::{
     types::{StarkStruct, Step, PIL},
 };
 
+/// The proofs of starky cannot be verified in powdr-asm, only those of the FRI STARK.
+const NO_VERIFIER_PROGRAM: &str =
+    "eSTARK proofs cannot be aggregated. Use the FRI STARK backend to aggregate proofs.";
+
 pub struct EStarkFactory;
 
 impl<F: FieldElement> BackendFactory<F> for EStarkFactory {
@@ -25,7 +35,7 @@ impl<F: FieldElement> BackendFactory<F> for EStarkFactory {
         &self,
         pil: &'a Analyzed<F>,
         fixed: &'a [(String, Vec<F>)],
-        _output_dir: Option<&std::path::Path>,
+        _output_dir: Option<&'a std::path::Path>,
         setup: Option<&mut dyn std::io::Read>,
         verification_key: Option<&mut dyn std::io::Read>,
     ) -> Result<Box<dyn crate::Backend<'a, F> + 'a>, Error> {
@@ -37,8 +47,9 @@ impl<F: FieldElement> BackendFactory<F> for EStarkFactory {
         if setup.is_some() {
             return Err(Error::NoSetupAvailable);
         }
-
-        let degree = pil.degree();
+        let degree = pil
+            .unique_degree()
+            .map_err(|_| Error::NoVariableDegreeAvailable)?;
         assert!(degree > 1);
         let n_bits = (DegreeType::BITS - (degree - 1).leading_zeros()) as usize;
         let n_bits_ext = n_bits + 1;
@@ -57,13 +68,21 @@ impl<F: FieldElement> BackendFactory<F> for EStarkFactory {
             steps,
         };
 
-        let (pil_json, fixed) = pil_json(pil, fixed);
+        let (pil_json, fixed) = pil_json(pil, degree, fixed);
         let const_pols = to_starky_pols_array(&fixed, &pil_json, PolKind::Constant);
+        let stages = Stages::new(pil);
 
         let setup = if let Some(vkey) = verification_key {
             serde_json::from_reader(vkey).unwrap()
         } else {
-            create_stark_setup(pil_json.clone(), &const_pols, &params)
+            // For PILs with later stages, the setup depends on the values of the challenges,
+            // so this one is only used for the commitment to the fixed columns.
+            let mut pil_json = pil_json.clone();
+            pilstark::json_exporter::substitute_challenges(
+                &mut pil_json,
+                &stages.placeholder_challenges::<F>(),
+            );
+            create_stark_setup(pil_json, &const_pols, &params)
         };
 
         Ok(Box::new(EStark {
@@ -71,16 +90,16 @@ impl<F: FieldElement> BackendFactory<F> for EStarkFactory {
             pil_json,
             params,
             setup,
+            stages,
         }))
     }
 }
 
 fn pil_json<'a, F: FieldElement>(
     pil: &'a Analyzed<F>,
+    degree: DegreeType,
     fixed: &'a [(String, Vec<F>)],
 ) -> (PIL, Vec<(String, Vec<F>)>) {
-    let degree = pil.degree();
-
     let mut pil: PIL = pilstark::json_exporter::export(pil);
 
     // TODO starky requires a fixed column with the equivalent
@@ -132,6 +151,104 @@ fn create_stark_setup(
     .unwrap()
 }
 
+/// The witness columns and challenges of the stages of a PIL.
+struct Stages {
+    /// The names of the witness columns of each stage.
+    columns: Vec<Vec<String>>,
+    /// The IDs of the challenges drawn after committing to each stage, in ascending order.
+    challenges: Vec<Vec<u64>>,
+}
+
+impl Stages {
+    fn new<F: FieldElement>(pil: &Analyzed<F>) -> Self {
+        let mut columns = vec![vec![]];
+        for (symbol, _) in pil.committed_polys_in_source_order() {
+            let stage = symbol.stage.unwrap_or_default() as usize;
+            if columns.len() <= stage {
+                columns.resize(stage + 1, vec![]);
+            }
+            columns[stage].extend(symbol.array_elements().map(|(name, _)| name));
+        }
+
+        let mut challenges = vec![vec![]; columns.len()];
+        for identity in pil.identities_with_inlined_intermediate_polynomials() {
+            for expr in identity.all_children() {
+                if let AlgebraicExpression::Challenge(challenge) = expr {
+                    let ids = &mut challenges[challenge.stage as usize];
+                    if !ids.contains(&challenge.id) {
+                        ids.push(challenge.id);
+                    }
+                }
+            }
+        }
+        challenges.iter_mut().for_each(|ids| ids.sort());
+
+        Stages {
+            columns,
+            challenges,
+        }
+    }
+
+    fn is_multi_stage(&self) -> bool {
+        self.columns.len() > 1
+    }
+
+    /// Zero values for all challenges, which are enough to derive the commitment to the
+    /// fixed columns.
+    fn placeholder_challenges<F: FieldElement>(&self) -> BTreeMap<u64, F> {
+        self.challenges
+            .iter()
+            .flatten()
+            .map(|id| (*id, F::zero()))
+            .collect()
+    }
+
+    /// Creates the transcript the challenges are drawn from, starting with the
+    /// commitment to the fixed columns.
+    fn initial_transcript<F: FieldElement>(fixed: &[(String, Vec<F>)]) -> Transcript<F> {
+        let mut transcript = Transcript::new(b"powdr-estark-challenges");
+        transcript.absorb_hash(&commit_columns(fixed.iter().map(|(_, values)| values)));
+        transcript
+    }
+
+    /// Absorbs the commitment to the witness columns of a stage and draws the
+    /// challenges of that stage.
+    fn draw_challenges<F: FieldElement>(
+        &self,
+        transcript: &mut Transcript<F>,
+        stage: usize,
+        stage_root: &Hash,
+        challenges: &mut BTreeMap<u64, F>,
+    ) {
+        transcript.absorb_hash(stage_root);
+        for id in &self.challenges[stage] {
+            challenges.insert(*id, transcript.challenge());
+        }
+    }
+}
+
+/// Commits to a set of columns through a Merkle tree over their rows.
+fn commit_columns<'b, F: FieldElement>(columns: impl Iterator<Item = &'b Vec<F>>) -> Hash {
+    let columns = columns.collect::<Vec<_>>();
+    let height = columns.first().map_or(1, |c| c.len());
+    let leaves = (0..height)
+        .map(|row| hash_values(&columns.iter().map(|c| c[row]).collect::<Vec<_>>()))
+        .collect();
+    MerkleTree::new(leaves).root()
+}
+
+/// The proof of a PIL with later stages: the commitments to the witness columns of all
+/// but the last stage, from which the challenges are derived, and the eSTARK proof
+/// of the constraints with the values of the challenges substituted.
+#[derive(Serialize, Deserialize)]
+struct MultiStageProof {
+    stage_roots: Vec<Hash>,
+    proof: StarkProof<MerkleTreeGL>,
+}
+
+/// The full witness, the commitments to all but the last stage and the challenges.
+type LaterStages<F> = (Vec<(String, Vec<F>)>, Vec<Hash>, BTreeMap<u64, F>);
+
 pub struct EStark<F: FieldElement> {
     fixed: Vec<(String, Vec<F>)>,
     pil_json: PIL,
@@ -139,12 +256,14 @@ pub struct EStark<F: FieldElement> {
     // eSTARK calls it setup, but it works similarly to a verification key and depends only on the
     // constants and circuit.
     setup: StarkSetup<MerkleTreeGL>,
+    stages: Stages,
 }
 
 impl<F: FieldElement> EStark<F> {
     fn verify_stark_with_publics(
         &self,
         proof: &StarkProof<MerkleTreeGL>,
+        setup: &StarkSetup<MerkleTreeGL>,
         instances: &[Vec<F>],
     ) -> Result<(), Error> {
         assert_eq!(instances.len(), 1);
@@ -155,60 +274,111 @@ impl<F: FieldElement> EStark<F> {
             .collect::<Vec<_>>();
         assert_eq!(instances[0], proof_publics);
 
-        self.verify_stark(proof)
+        self.verify_stark(proof, setup)
     }
 
-    fn verify_stark(&self, proof: &StarkProof<MerkleTreeGL>) -> Result<(), Error> {
+    fn verify_stark(
+        &self,
+        proof: &StarkProof<MerkleTreeGL>,
+        setup: &StarkSetup<MerkleTreeGL>,
+    ) -> Result<(), Error> {
+        // The commitment to the fixed columns is always taken from our own setup,
+        // which might come from a verification key.
         match stark_verify::<MerkleTreeGL, TranscriptGL>(
             proof,
             &self.setup.const_root,
-            &self.setup.starkinfo,
+            &setup.starkinfo,
             &self.params,
-            &self.setup.program,
+            &setup.program,
         ) {
             Ok(true) => Ok(()),
             Ok(false) => Err(Error::BackendError("Proof is invalid".to_string())),
             Err(e) => Err(Error::BackendError(e.to_string())),
         }
     }
-}
 
-impl<'a, F: FieldElement> Backend<'a, F> for EStark<F> {
-    fn verify(&self, proof: &[u8], instances: &[Vec<F>]) -> Result<(), Error> {
-        let proof: StarkProof<MerkleTreeGL> =
-            serde_json::from_str(&String::from_utf8(proof.to_vec()).unwrap()).unwrap();
-        self.verify_stark_with_publics(&proof, instances)
+    /// Derives the challenges from the commitments to the witness columns of all
+    /// but the last stage.
+    fn challenges(&self, stage_roots: &[Hash]) -> BTreeMap<u64, F> {
+        let mut transcript = Stages::initial_transcript(&self.fixed);
+        let mut challenges = BTreeMap::new();
+        for (stage, root) in stage_roots.iter().enumerate() {
+            self.stages
+                .draw_challenges(&mut transcript, stage, root, &mut challenges);
+        }
+        challenges
     }
 
-    fn prove(
+    /// Creates the PIL and setup with the given values of the challenges substituted.
+    fn setup_with_challenges(
+        &self,
+        challenges: &BTreeMap<u64, F>,
+    ) -> (PIL, StarkSetup<MerkleTreeGL>) {
+        let mut pil_json = self.pil_json.clone();
+        pilstark::json_exporter::substitute_challenges(&mut pil_json, challenges);
+        let const_pols = to_starky_pols_array(&self.fixed, &pil_json, PolKind::Constant);
+        let setup = create_stark_setup(pil_json.clone(), &const_pols, &self.params);
+        (pil_json, setup)
+    }
+
+    /// Runs witness generation for the later stages. Each stage is committed to and its
+    /// challenges are drawn from the commitment before the next stage is computed.
+    fn compute_later_stages(
         &self,
         witness: &[(String, Vec<F>)],
-        prev_proof: Option<crate::Proof>,
-        // TODO: Implement challenges
-        _witgen_callback: WitgenCallback<F>,
-    ) -> Result<crate::Proof, Error> {
-        if prev_proof.is_some() {
-            return Err(Error::NoAggregationAvailable);
-        }
-        if witness.is_empty() {
-            return Err(Error::EmptyWitness);
+        witgen_callback: &WitgenCallback<F>,
+    ) -> Result<LaterStages<F>, Error> {
+        let mut transcript = Stages::initial_transcript(&self.fixed);
+        let mut witness = witness.to_vec();
+        let mut stage_roots = vec![];
+        let mut challenges = BTreeMap::new();
+        for stage in 0..self.stages.columns.len() - 1 {
+            let values = witness
+                .iter()
+                .map(|(name, values)| (name.as_str(), values))
+                .collect::<BTreeMap<_, _>>();
+            let columns = self.stages.columns[stage]
+                .iter()
+                .map(|name| {
+                    values.get(name.as_str()).copied().ok_or_else(|| {
+                        Error::BackendError(format!("Witness column {name} is missing."))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let root = commit_columns(columns.into_iter());
+            self.stages
+                .draw_challenges(&mut transcript, stage, &root, &mut challenges);
+            stage_roots.push(root);
+
+            log::info!("Running witness generation for stage {}.", stage + 1);
+            witness =
+                witgen_callback.next_stage_witness(&witness, challenges.clone(), (stage + 1) as u8);
         }
+        Ok((witness, stage_roots, challenges))
+    }
 
+    /// Creates the eSTARK proof for the given witness columns with the given PIL and
+    /// setup and checks it.
+    fn prove_pols(
+        &self,
+        cm_pols: PolsArray,
+        pil_json: &PIL,
+        setup: &StarkSetup<MerkleTreeGL>,
+    ) -> Result<StarkProof<MerkleTreeGL>, Error> {
         log::info!("Creating eSTARK proof.");
 
-        let cm_pols = to_starky_pols_array(witness, &self.pil_json, PolKind::Commit);
         let start = Instant::now();
 
         // TODO it would be good not to recompute this here
-        let const_pols = to_starky_pols_array(&self.fixed, &self.pil_json, PolKind::Constant);
+        let const_pols = to_starky_pols_array(&self.fixed, pil_json, PolKind::Constant);
 
         let starkproof = StarkProof::<MerkleTreeGL>::stark_gen::<TranscriptGL>(
             cm_pols,
             const_pols,
-            &self.setup.const_tree,
-            &self.setup.starkinfo,
-            &self.setup.program,
-            &self.pil_json,
+            &setup.const_tree,
+            &setup.starkinfo,
+            &setup.program,
+            pil_json,
             &self.params,
             "",
         );
@@ -222,10 +392,97 @@ impl<'a, F: FieldElement> Backend<'a, F> for EStark<F> {
 
         log::info!("Proof done in: {:?}", duration);
 
-        match self.verify_stark(&starkproof) {
-            Ok(_) => Ok(serde_json::to_string(&starkproof).unwrap().into_bytes()),
-            Err(e) => Err(e),
+        self.verify_stark(&starkproof, setup)?;
+        Ok(starkproof)
+    }
+}
+
+impl<'a, F: FieldElement> Backend<'a, F> for EStark<F> {
+    fn verify(&self, proof: &[u8], instances: &[Vec<F>]) -> Result<(), Error> {
+        let proof = String::from_utf8(proof.to_vec()).unwrap();
+        if self.stages.is_multi_stage() {
+            let proof: MultiStageProof = serde_json::from_str(&proof).unwrap();
+            if proof.stage_roots.len() + 1 != self.stages.columns.len() {
+                return Err(Error::BackendError(
+                    "Wrong number of stage commitments".to_string(),
+                ));
+            }
+            let (_, setup) = self.setup_with_challenges(&self.challenges(&proof.stage_roots));
+            self.verify_stark_with_publics(&proof.proof, &setup, instances)
+        } else {
+            let proof: StarkProof<MerkleTreeGL> = serde_json::from_str(&proof).unwrap();
+            self.verify_stark_with_publics(&proof, &self.setup, instances)
+        }
+    }
+
+    fn prove(
+        &self,
+        witness: &[(String, Vec<F>)],
+        prev_proof: Option<crate::Proof>,
+        witgen_callback: WitgenCallback<F>,
+    ) -> Result<crate::Proof, Error> {
+        if prev_proof.is_some() {
+            return Err(Error::NoAggregationAvailable);
+        }
+        if witness.is_empty() {
+            return Err(Error::EmptyWitness);
+        }
+
+        if !self.stages.is_multi_stage() {
+            let cm_pols = to_starky_pols_array(witness, &self.pil_json, PolKind::Commit);
+            let proof = self.prove_pols(cm_pols, &self.pil_json, &self.setup)?;
+            return Ok(serde_json::to_string(&proof).unwrap().into_bytes());
+        }
+
+        let (witness, stage_roots, challenges) =
+            self.compute_later_stages(witness, &witgen_callback)?;
+        let (pil_json, setup) = self.setup_with_challenges(&challenges);
+        let cm_pols = to_starky_pols_array(&witness, &pil_json, PolKind::Commit);
+        let proof = MultiStageProof {
+            stage_roots,
+            proof: self.prove_pols(cm_pols, &pil_json, &setup)?,
+        };
+        Ok(serde_json::to_string(&proof).unwrap().into_bytes())
+    }
+
+    fn prove_from_store(
+        &self,
+        witness: &ColumnStore<F>,
+        prev_proof: Option<crate::Proof>,
+        witgen_callback: WitgenCallback<F>,
+    ) -> Result<crate::Proof, Error> {
+        // Witness generation for the later stages needs the whole witness in memory.
+        if self.stages.is_multi_stage() {
+            let names = witness.column_names();
+            let witness = witness
+                .read_columns(names.iter().map(|name| name.as_str()))
+                .collect::<Vec<_>>();
+            return self.prove(&witness, prev_proof, witgen_callback);
         }
+        if prev_proof.is_some() {
+            return Err(Error::NoAggregationAvailable);
+        }
+        let names = witness.column_names();
+        if names.is_empty() {
+            return Err(Error::EmptyWitness);
+        }
+
+        // The columns are read one at a time and converted directly, so that
+        // the witness is only kept in memory in the representation of starky.
+        let columns = names
+            .iter()
+            .map(|name| witness.column_reader(name).into_values());
+        let cm_pols = columns_to_starky_pols_array(columns, &self.pil_json, PolKind::Commit);
+        let proof = self.prove_pols(cm_pols, &self.pil_json, &self.setup)?;
+        Ok(serde_json::to_string(&proof).unwrap().into_bytes())
+    }
+
+    fn verifier_program(&self, _publics: &[Vec<F>]) -> Result<String, Error> {
+        Err(Error::BackendError(NO_VERIFIER_PROGRAM.to_string()))
+    }
+
+    fn verifier_inputs(&self, _proofs: &[(crate::Proof, Vec<F>)]) -> Result<Vec<F>, Error> {
+        Err(Error::BackendError(NO_VERIFIER_PROGRAM.to_string()))
     }
 
     fn export_verification_key(&self, output: &mut dyn io::Write) -> Result<(), Error> {
@@ -243,17 +500,35 @@ fn to_starky_pols_array<F: FieldElement>(
     pil: &PIL,
     kind: PolKind,
 ) -> PolsArray {
-    let mut output = PolsArray::new(pil, kind);
-    assert_eq!(output.array.len(), array.len());
-    for ((_, from), to) in array.iter().zip(output.array.iter_mut()) {
-        assert_eq!(from.len(), to.len());
+    columns_to_starky_pols_array(
+        array.iter().map(|(_, values)| values.iter().cloned()),
+        pil,
+        kind,
+    )
+}
 
-        for (f, t) in from.iter().zip(to.iter_mut()) {
+/// Converts the given columns (in the order of their IDs) one after the other,
+/// so that they can be read lazily.
+fn columns_to_starky_pols_array<F: FieldElement>(
+    columns: impl IntoIterator<Item = impl IntoIterator<Item = F>>,
+    pil: &PIL,
+    kind: PolKind,
+) -> PolsArray {
+    let mut output = PolsArray::new(pil, kind);
+    let mut count = 0;
+    for from in columns {
+        let mut from = from.into_iter();
+        let to = &mut output.array[count];
+        for t in to.iter_mut() {
+            let f = from.next().expect("Column is shorter than expected.");
             *t = TryInto::<u64>::try_into(f.to_integer().to_arbitrary_integer())
                 .unwrap()
                 .into();
         }
+        assert!(from.next().is_none(), "Column is longer than expected.");
+        count += 1;
     }
+    assert_eq!(output.array.len(), count);
 
     output
 }
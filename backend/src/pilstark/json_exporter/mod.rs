@@ -1,10 +1,10 @@
 use powdr_number::FieldElement;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::{cmp, path::PathBuf};
 
 use powdr_ast::analyzed::{
     AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicUnaryOperator, Analyzed,
-    IdentityKind, PolyID, PolynomialType, StatementIdentifier, SymbolKind,
+    IdentityKind, PolyID, PolynomialType, StatementIdentifier, Symbol, SymbolKind,
 };
 use starky::types::{
     ConnectionIdentity, Expression as StarkyExpr, PermutationIdentity, PlookupIdentity,
@@ -157,6 +157,28 @@ pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
     }
 }
 
+/// Replaces all references to challenges in the exported PIL by their values.
+/// starky does not know about challenges, so they have to be turned into numbers
+/// once they have been drawn.
+pub fn substitute_challenges<T: FieldElement>(pil: &mut PIL, challenges: &BTreeMap<u64, T>) {
+    fn substitute<T: FieldElement>(expr: &mut StarkyExpr, challenges: &BTreeMap<u64, T>) {
+        if expr.op == "challenge" {
+            let id = expr.id.take().unwrap() as u64;
+            let value = challenges
+                .get(&id)
+                .unwrap_or_else(|| panic!("Value of challenge {id} is missing."));
+            expr.op = "number".to_string();
+            expr.value = Some(format!("{value}"));
+        }
+        for child in expr.values.iter_mut().flatten() {
+            substitute(child, challenges);
+        }
+    }
+    for expr in &mut pil.expressions {
+        substitute(expr, challenges);
+    }
+}
+
 fn symbol_kind_to_json_string(k: SymbolKind) -> &'static str {
     match k {
         SymbolKind::Poly(poly_type) => polynomial_type_to_json_string(poly_type),
@@ -225,7 +247,7 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                     polType: None,
                     type_: symbol_kind_to_json_string(symbol.kind).to_string(),
                     id: id as usize,
-                    polDeg: self.analyzed.degree() as usize,
+                    polDeg: self.column_degree(symbol),
                     isArray: symbol.is_array(),
                     elementType: None,
                     len: symbol.length.map(|l| l as usize),
@@ -244,7 +266,7 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                             polType: None,
                             type_: symbol_kind_to_json_string(symbol.kind).to_string(),
                             id: id as usize,
-                            polDeg: self.analyzed.degree() as usize,
+                            polDeg: self.column_degree(symbol),
                             isArray: symbol.is_array(),
                             elementType: None,
                             len: symbol.length.map(|l| l as usize),
@@ -255,6 +277,12 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
             .collect::<HashMap<String, Reference>>()
     }
 
+    /// @returns the degree of the given column, falling back to the maximum degree
+    /// for columns that do not have their own degree.
+    fn column_degree(&self, symbol: &Symbol) -> usize {
+        symbol.degree.or(self.analyzed.degree).unwrap() as usize
+    }
+
     /// Processes the given expression
     /// @returns the expression ID
     fn extract_expression(&mut self, expr: &Expression<T>, max_degree: u32) -> usize {
@@ -561,4 +589,22 @@ mod test {
         compare_export_file_ignore_idq_hex("rom.pil");
         compare_export_file_ignore_idq_hex("main.pil");
     }
+
+    #[test]
+    fn substitute_challenge_values() {
+        let file = std::path::PathBuf::from(format!(
+            "{}/../test_data/pil/permutation_via_challenges.pil",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+        let analyzed = analyze_file::<GoldilocksField>(&file);
+        let mut pil = export(&analyzed);
+        let count =
+            |pil: &PIL, pattern: &str| serde_json::to_string(pil).unwrap().matches(pattern).count();
+        assert_eq!(count(&pil, "\"challenge\""), 2);
+
+        let challenges = [(12345, GoldilocksField::from(77))].into_iter().collect();
+        substitute_challenges(&mut pil, &challenges);
+        assert_eq!(count(&pil, "\"challenge\""), 0);
+        assert_eq!(count(&pil, "\"value\":\"77\""), 2);
+    }
 }
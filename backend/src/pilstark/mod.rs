@@ -23,6 +23,9 @@ impl<F: FieldElement> BackendFactory<F> for PilStarkCliFactory {
         setup: Option<&mut dyn std::io::Read>,
         verification_key: Option<&mut dyn std::io::Read>,
     ) -> Result<Box<dyn crate::Backend<'a, F> + 'a>, Error> {
+        if analyzed.degrees().len() > 1 {
+            return Err(Error::NoVariableDegreeAvailable);
+        }
         if setup.is_some() {
             return Err(Error::NoSetupAvailable);
         }
@@ -46,7 +49,8 @@ impl<'a, F: FieldElement> Backend<'a, F> for PilStarkCli<'a, F> {
         &self,
         _witness: &[(String, Vec<F>)],
         prev_proof: Option<Proof>,
-        // TODO: Implement challenges
+        // Later stages are left to the external prover, which finds the
+        // challenges as `challenge` expressions in `constraints.json`.
         _witgen_callback: WitgenCallback<F>,
     ) -> Result<Proof, Error> {
         if prev_proof.is_some() {
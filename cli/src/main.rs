@@ -2,17 +2,20 @@
 
 mod util;
 
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use env_logger::fmt::Color;
 use env_logger::{Builder, Target};
 use log::LevelFilter;
 use powdr_backend::BackendType;
 use powdr_number::{read_polys_csv_file, CsvRenderMode};
 use powdr_number::{Bn254Field, FieldElement, GoldilocksField};
-use powdr_pipeline::util::write_or_panic;
+use powdr_pipeline::util::{write_or_panic, FIXED_COLS_CACHE_DIR};
 use powdr_pipeline::Pipeline;
-use powdr_riscv::continuations::{rust_continuations, rust_continuations_dry_run};
+use powdr_riscv::continuations::{
+    rust_continuations, rust_continuations_dry_run, verify_chunk_publics, ChunkPublics,
+};
 use powdr_riscv::{compile_riscv_asm, compile_rust};
+use powdr_riscv_executor::debugger::{DebugProgram, Debugger};
 use std::io::{self, BufWriter};
 use std::path::PathBuf;
 use std::{borrow::Cow, fs, io::Write, path::Path};
@@ -30,6 +33,7 @@ fn bind_cli_args<F: FieldElement>(
     witness_values: Option<String>,
     export_csv: bool,
     csv_mode: CsvRenderModeCLI,
+    fixed_cols_cache: FixedColsCacheArgs,
 ) -> Pipeline<F> {
     let witness_values = witness_values
         .map(|csv_path| {
@@ -44,7 +48,8 @@ fn bind_cli_args<F: FieldElement>(
         CsvRenderModeCLI::Hex => CsvRenderMode::Hex,
     };
 
-    let pipeline = pipeline
+    let pipeline = fixed_cols_cache
+        .bind(pipeline)
         .with_output(output_dir.clone(), force_overwrite)
         .add_external_witness_values(witness_values.clone())
         .with_witness_csv_settings(export_csv, csv_mode)
@@ -75,6 +80,34 @@ pub enum CsvRenderModeCLI {
     Hex,
 }
 
+/// Where to cache fixed columns across runs.
+#[derive(Args, Clone)]
+struct FixedColsCacheArgs {
+    /// Directory in which fixed columns are cached across runs.
+    /// Defaults to `fixed_cols_cache` in the output directory.
+    #[arg(long)]
+    fixed_cols_cache_dir: Option<String>,
+
+    /// Do not cache fixed columns.
+    #[arg(long)]
+    #[arg(default_value_t = false)]
+    #[arg(conflicts_with = "fixed_cols_cache_dir")]
+    no_fixed_cols_cache: bool,
+}
+
+impl FixedColsCacheArgs {
+    /// Binds the arguments to the pipeline, which keeps its default location otherwise.
+    fn bind<F: FieldElement>(self, pipeline: Pipeline<F>) -> Pipeline<F> {
+        if self.no_fixed_cols_cache {
+            pipeline.with_fixed_cols_cache(None)
+        } else if let Some(dir) = self.fixed_cols_cache_dir {
+            pipeline.with_fixed_cols_cache(Some(PathBuf::from(dir)))
+        } else {
+            pipeline
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "powdr", author, version, about, long_about = None)]
 struct Cli {
@@ -150,10 +183,13 @@ enum Commands {
         #[arg(default_value_t = false)]
         just_execute: bool,
 
-        /// Run a long execution in chunks (Experimental and not sound!)
+        /// Run a long execution in chunks (Experimental!)
         #[arg(short, long)]
         #[arg(default_value_t = false)]
         continuations: bool,
+
+        #[command(flatten)]
+        fixed_cols_cache: FixedColsCacheArgs,
     },
     /// Compiles (no-std) rust code to riscv assembly, then to powdr assembly
     /// and finally to PIL and generates fixed and witness columns.
@@ -213,10 +249,13 @@ enum Commands {
         #[arg(default_value_t = false)]
         just_execute: bool,
 
-        /// Run a long execution in chunks (Experimental and not sound!)
+        /// Run a long execution in chunks (Experimental!)
         #[arg(short, long)]
         #[arg(default_value_t = false)]
         continuations: bool,
+
+        #[command(flatten)]
+        fixed_cols_cache: FixedColsCacheArgs,
     },
 
     /// Compiles riscv assembly to powdr assembly and then to PIL
@@ -277,10 +316,51 @@ enum Commands {
         #[arg(default_value_t = false)]
         just_execute: bool,
 
-        /// Run a long execution in chunks (Experimental and not sound!)
+        /// Run a long execution in chunks (Experimental!)
         #[arg(short, long)]
         #[arg(default_value_t = false)]
         continuations: bool,
+
+        #[command(flatten)]
+        fixed_cols_cache: FixedColsCacheArgs,
+    },
+
+    /// Runs a powdr-asm file, RISC-V assembly file or Rust crate in an
+    /// interactive step debugger.
+    Debug {
+        /// Input file: a powdr-asm file (.asm), a RISC-V assembly file (.s, .S)
+        /// or a rust crate dir or its Cargo.toml file
+        file: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
+        field: FieldArgument,
+
+        /// Comma-separated list of free inputs (numbers).
+        #[arg(short, long)]
+        #[arg(default_value_t = String::new())]
+        inputs: String,
+
+        /// Directory for output files.
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        output_directory: String,
+
+        /// Force overwriting of files in output directory.
+        #[arg(short, long)]
+        #[arg(default_value_t = false)]
+        force: bool,
+
+        /// Comma-separated list of coprocessors.
+        #[arg(long)]
+        coprocessors: Option<String>,
+
+        /// Comma-separated list of labels or PCs to set breakpoints on.
+        #[arg(short, long)]
+        #[arg(default_value_t = String::new())]
+        breakpoints: String,
     },
 
     Prove {
@@ -314,6 +394,9 @@ enum Commands {
         /// File containing previously generated setup parameters.
         #[arg(long)]
         params: Option<String>,
+
+        #[command(flatten)]
+        fixed_cols_cache: FixedColsCacheArgs,
     },
 
     Verify {
@@ -345,6 +428,11 @@ enum Commands {
         #[arg(default_value_t = String::new())]
         publics: String,
 
+        /// File containing the public inputs, as exported next to the proof.
+        /// Takes precedence over --publics.
+        #[arg(long)]
+        publics_file: Option<String>,
+
         /// File containing the verification ley.
         #[arg(long)]
         vkey: String,
@@ -352,6 +440,46 @@ enum Commands {
         /// File containing the params.
         #[arg(long)]
         params: Option<String>,
+
+        /// Whether the proof aggregates a proof with the given public inputs,
+        /// i.e. it was created with --proof.
+        #[arg(long)]
+        #[arg(default_value_t = false)]
+        aggregated: bool,
+
+        #[command(flatten)]
+        fixed_cols_cache: FixedColsCacheArgs,
+    },
+
+    /// Verifies the chunk proofs of an execution with continuations and
+    /// checks that they link up to a single execution of the program.
+    VerifyContinuations {
+        /// The powdr-asm file the chunks were proven for
+        file: String,
+
+        /// Directory to find the fixed values and the chunk proofs and public values
+        #[arg(short, long)]
+        #[arg(default_value_t = String::from("."))]
+        dir: String,
+
+        /// The field to use
+        #[arg(long)]
+        #[arg(default_value_t = FieldArgument::Gl)]
+        #[arg(value_parser = clap_enum_variants!(FieldArgument))]
+        field: FieldArgument,
+
+        /// The backend the chunks were proven with.
+        #[arg(short, long)]
+        #[arg(value_parser = clap_enum_variants!(BackendType))]
+        backend: BackendType,
+
+        /// File containing the verification key.
+        #[arg(long)]
+        vkey: Option<String>,
+
+        /// File containing the params.
+        #[arg(long)]
+        params: Option<String>,
     },
 
     VerificationKey {
@@ -485,6 +613,7 @@ fn run_command(command: Commands) {
             coprocessors,
             just_execute,
             continuations,
+            fixed_cols_cache,
         } => {
             call_with_field!(run_rust::<field>(
                 &file,
@@ -497,7 +626,8 @@ fn run_command(command: Commands) {
                 csv_mode,
                 coprocessors,
                 just_execute,
-                continuations
+                continuations,
+                fixed_cols_cache
             ))
         }
         Commands::RiscvAsm {
@@ -513,6 +643,7 @@ fn run_command(command: Commands) {
             coprocessors,
             just_execute,
             continuations,
+            fixed_cols_cache,
         } => {
             assert!(!files.is_empty());
             let name = if files.len() == 1 {
@@ -533,7 +664,26 @@ fn run_command(command: Commands) {
                 csv_mode,
                 coprocessors,
                 just_execute,
-                continuations
+                continuations,
+                fixed_cols_cache
+            ))
+        }
+        Commands::Debug {
+            file,
+            field,
+            inputs,
+            output_directory,
+            force,
+            coprocessors,
+            breakpoints,
+        } => {
+            call_with_field!(run_debug::<field>(
+                &file,
+                split_inputs(&inputs),
+                Path::new(&output_directory),
+                force,
+                coprocessors,
+                &breakpoints
             ))
         }
         Commands::Reformat { file } => {
@@ -561,6 +711,7 @@ fn run_command(command: Commands) {
             csv_mode,
             just_execute,
             continuations,
+            fixed_cols_cache,
         } => {
             call_with_field!(run_pil::<field>(
                 file,
@@ -573,7 +724,8 @@ fn run_command(command: Commands) {
                 export_csv,
                 csv_mode,
                 just_execute,
-                continuations
+                continuations,
+                fixed_cols_cache
             ))
         }
         Commands::Prove {
@@ -584,11 +736,18 @@ fn run_command(command: Commands) {
             proof,
             vkey,
             params,
+            fixed_cols_cache,
         } => {
             let pil = Path::new(&file);
             let dir = Path::new(&dir);
             call_with_field!(read_and_prove::<field>(
-                pil, dir, &backend, proof, vkey, params
+                pil,
+                dir,
+                &backend,
+                proof,
+                vkey,
+                params,
+                fixed_cols_cache
             ))
         }
         Commands::Verify {
@@ -598,13 +757,39 @@ fn run_command(command: Commands) {
             backend,
             proof,
             publics,
+            publics_file,
             params,
             vkey,
+            aggregated,
+            fixed_cols_cache,
         } => {
             let pil = Path::new(&file);
             let dir = Path::new(&dir);
             call_with_field!(read_and_verify::<field>(
-                pil, dir, &backend, proof, publics, params, vkey
+                pil,
+                dir,
+                &backend,
+                proof,
+                publics,
+                publics_file,
+                params,
+                vkey,
+                aggregated,
+                fixed_cols_cache
+            ))
+        }
+        Commands::VerifyContinuations {
+            file,
+            dir,
+            field,
+            backend,
+            vkey,
+            params,
+        } => {
+            let file = Path::new(&file);
+            let dir = Path::new(&dir);
+            call_with_field!(verify_continuations::<field>(
+                file, dir, &backend, params, vkey
             ))
         }
         Commands::VerificationKey {
@@ -681,6 +866,7 @@ fn run_rust<F: FieldElement>(
     coprocessors: Option<String>,
     just_execute: bool,
     continuations: bool,
+    fixed_cols_cache: FixedColsCacheArgs,
 ) -> Result<(), Vec<String>> {
     let runtime = match coprocessors {
         Some(list) => {
@@ -712,6 +898,7 @@ fn run_rust<F: FieldElement>(
         None,
         export_csv,
         csv_mode,
+        fixed_cols_cache,
     );
     run(pipeline, inputs, prove_with, just_execute, continuations)?;
     Ok(())
@@ -731,6 +918,7 @@ fn run_riscv_asm<F: FieldElement>(
     coprocessors: Option<String>,
     just_execute: bool,
     continuations: bool,
+    fixed_cols_cache: FixedColsCacheArgs,
 ) -> Result<(), Vec<String>> {
     let runtime = match coprocessors {
         Some(list) => {
@@ -763,11 +951,79 @@ fn run_riscv_asm<F: FieldElement>(
         None,
         export_csv,
         csv_mode,
+        fixed_cols_cache,
     );
     run(pipeline, inputs, prove_with, just_execute, continuations)?;
     Ok(())
 }
 
+fn run_debug<F: FieldElement>(
+    file_name: &str,
+    inputs: Vec<F>,
+    output_dir: &Path,
+    force_overwrite: bool,
+    coprocessors: Option<String>,
+    breakpoints: &str,
+) -> Result<(), Vec<String>> {
+    let runtime = match coprocessors {
+        Some(list) => {
+            powdr_riscv::Runtime::try_from(list.split(',').collect::<Vec<_>>().as_ref()).unwrap()
+        }
+        None => powdr_riscv::Runtime::base(),
+    };
+
+    let (asm_file_path, asm_contents) = match Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+    {
+        Some("asm") => (
+            PathBuf::from(file_name),
+            fs::read_to_string(file_name).unwrap(),
+        ),
+        Some("s" | "S") => compile_riscv_asm::<F>(
+            file_name,
+            std::iter::once(file_name.to_string()),
+            output_dir,
+            force_overwrite,
+            &runtime,
+            false,
+        )
+        .ok_or_else(|| vec!["could not compile RISC-V assembly".to_string()])?,
+        _ => compile_rust::<F>(file_name, output_dir, force_overwrite, &runtime, false)
+            .ok_or_else(|| vec!["could not compile rust".to_string()])?,
+    };
+
+    let mut pipeline = Pipeline::<F>::default()
+        .from_asm_string(asm_contents, Some(asm_file_path))
+        .with_prover_inputs(inputs);
+    let analyzed = pipeline.compute_analyzed_asm()?.clone();
+
+    let program = DebugProgram::<F>::new(&analyzed);
+    let mut debugger = Debugger::new(
+        &program,
+        powdr_riscv_executor::MemoryState::new(),
+        pipeline.data_callback().unwrap(),
+        &[],
+        usize::MAX,
+    )
+    .map_err(|_| vec!["the program is empty".to_string()])?;
+
+    for target in breakpoints
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        let pc = program
+            .resolve_pc(target)
+            .ok_or_else(|| vec![format!("unknown label or PC: {target}")])?;
+        debugger.add_breakpoint(pc);
+    }
+
+    debugger
+        .repl(io::stdin().lock(), io::stdout())
+        .map_err(|e| vec![e.to_string()])
+}
+
 #[allow(clippy::too_many_arguments)]
 fn run_pil<F: FieldElement>(
     file: String,
@@ -781,6 +1037,7 @@ fn run_pil<F: FieldElement>(
     csv_mode: CsvRenderModeCLI,
     just_execute: bool,
     continuations: bool,
+    fixed_cols_cache: FixedColsCacheArgs,
 ) -> Result<(), Vec<String>> {
     let inputs = split_inputs::<F>(&inputs);
 
@@ -793,6 +1050,7 @@ fn run_pil<F: FieldElement>(
         witness_values,
         export_csv,
         csv_mode,
+        fixed_cols_cache,
     );
     run(pipeline, inputs, prove_with, just_execute, continuations)?;
     Ok(())
@@ -856,8 +1114,10 @@ fn read_and_prove<T: FieldElement>(
     proof_path: Option<String>,
     vkey: Option<String>,
     params: Option<String>,
+    fixed_cols_cache: FixedColsCacheArgs,
 ) -> Result<(), Vec<String>> {
-    Pipeline::<T>::default()
+    fixed_cols_cache
+        .bind(Pipeline::<T>::default())
         .from_maybe_pil_object(file.to_path_buf())?
         .with_output(dir.to_path_buf(), true)
         .read_witness(dir)
@@ -869,37 +1129,108 @@ fn read_and_prove<T: FieldElement>(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn read_and_verify<T: FieldElement>(
     file: &Path,
     dir: &Path,
     backend_type: &BackendType,
     proof: String,
     publics: String,
+    publics_file: Option<String>,
     params: Option<String>,
     vkey: String,
+    aggregated: bool,
+    fixed_cols_cache: FixedColsCacheArgs,
 ) -> Result<(), Vec<String>> {
     let proof = Path::new(&proof);
     let vkey = Path::new(&vkey).to_path_buf();
 
     let proof = fs::read(proof).unwrap();
-    let publics = split_inputs(publics.as_str());
+    let publics = match publics_file {
+        Some(publics_file) => read_publics_file(Path::new(&publics_file))?,
+        None => split_inputs(publics.as_str()),
+    };
 
-    let mut pipeline = Pipeline::<T>::default()
+    // There is no output directory, so the fixed columns of the verifier program of
+    // aggregated proofs are cached next to the fixed values by default.
+    let pipeline =
+        Pipeline::<T>::default().with_fixed_cols_cache(Some(dir.join(FIXED_COLS_CACHE_DIR)));
+    let mut pipeline = fixed_cols_cache
+        .bind(pipeline)
         .from_file(file.to_path_buf())
         .read_constants(dir)
         .with_setup_file(params.map(PathBuf::from))
         .with_vkey_file(Some(vkey))
         .with_backend(*backend_type);
 
-    pipeline.verify(&proof, &[publics])?;
-    println!("Proof is valid!");
+    if aggregated {
+        pipeline.verify_aggregated(&proof, &[publics])?;
+    } else {
+        pipeline.verify(&proof, &[publics])?;
+    }
+    log::info!("Proof is valid!");
 
     Ok(())
 }
 
-#[allow(clippy::print_stdout)]
+fn verify_continuations<T: FieldElement>(
+    file: &Path,
+    dir: &Path,
+    backend_type: &BackendType,
+    params: Option<String>,
+    vkey: Option<String>,
+) -> Result<(), Vec<String>> {
+    let mut pipeline = Pipeline::<T>::default().from_file(file.to_path_buf());
+    let program = pipeline.compute_analyzed_asm()?.clone();
+    let name = pipeline.name().to_string();
+    let mut pipeline = pipeline
+        .read_constants(dir)
+        .with_setup_file(params.map(PathBuf::from))
+        .with_vkey_file(vkey.map(PathBuf::from))
+        .with_backend(*backend_type);
+
+    // Chunk proofs and public values are written as `<name>_chunk_<i>_proof.bin` and
+    // `<name>_chunk_<i>_publics.csv` by `rust_continuations`.
+    let mut chunks = vec![];
+    loop {
+        let chunk_name = format!("{name}_chunk_{}", chunks.len());
+        let proof_file = dir.join(format!("{chunk_name}_proof.bin"));
+        if !proof_file.exists() {
+            break;
+        }
+        let proof = fs::read(&proof_file).unwrap();
+        let publics = read_publics_file(&dir.join(format!("{chunk_name}_publics.csv")))?;
+        pipeline.verify(&proof, &[publics.clone()])?;
+        log::info!("Proof of chunk {} is valid.", chunks.len());
+        chunks.push(ChunkPublics::from_values(&publics).map_err(|e| vec![e])?);
+    }
+
+    verify_chunk_publics(&program, &chunks).map_err(|e| vec![e])?;
+    log::info!(
+        "All {} chunk proofs are valid and form a single execution!",
+        chunks.len()
+    );
+
+    Ok(())
+}
+
+/// Reads the `name,value` lines of a publics file written next to a proof.
+fn read_publics_file<T: FieldElement>(path: &Path) -> Result<Vec<T>, Vec<String>> {
+    fs::read_to_string(path)
+        .map_err(|e| vec![format!("could not read {}: {e}", path.display())])?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (_name, value) = line
+                .rsplit_once(',')
+                .ok_or_else(|| vec![format!("invalid line in publics file: {line}")])?;
+            T::from_str_radix(value.trim(), 10).map_err(|e| vec![e])
+        })
+        .collect()
+}
+
 fn optimize_and_output<T: FieldElement>(file: &str) {
-    println!(
+    log::info!(
         "{}",
         Pipeline::<T>::default()
             .from_file(PathBuf::from(file))
@@ -910,7 +1241,7 @@ fn optimize_and_output<T: FieldElement>(file: &str) {
 
 #[cfg(test)]
 mod test {
-    use crate::{run_command, Commands, CsvRenderModeCLI, FieldArgument};
+    use crate::{run_command, Commands, CsvRenderModeCLI, FieldArgument, FixedColsCacheArgs};
     use powdr_backend::BackendType;
 
     #[test]
@@ -935,8 +1266,16 @@ mod test {
             csv_mode: CsvRenderModeCLI::Hex,
             just_execute: false,
             continuations: false,
+            fixed_cols_cache: FixedColsCacheArgs {
+                fixed_cols_cache_dir: None,
+                no_fixed_cols_cache: false,
+            },
         };
         run_command(pil_command);
+        assert!(output_dir
+            .path()
+            .join(powdr_pipeline::util::FIXED_COLS_CACHE_DIR)
+            .exists());
 
         #[cfg(feature = "halo2")]
         {
@@ -953,6 +1292,10 @@ mod test {
                 proof: None,
                 vkey: None,
                 params: None,
+                fixed_cols_cache: FixedColsCacheArgs {
+                    fixed_cols_cache_dir: None,
+                    no_fixed_cols_cache: true,
+                },
             };
             run_command(prove_command);
         }
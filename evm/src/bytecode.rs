@@ -0,0 +1,152 @@
+//! Decoding of EVM bytecode into a sequence of instructions.
+
+use std::collections::BTreeSet;
+
+use powdr_number::BigUint;
+
+/// The subset of EVM instructions supported by this frontend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Stop,
+    Add,
+    Mul,
+    Sub,
+    Div,
+    Mod,
+    Lt,
+    Gt,
+    Eq,
+    IsZero,
+    And,
+    Or,
+    Xor,
+    Not,
+    CallDataLoad,
+    Pop,
+    MLoad,
+    MStore,
+    SLoad,
+    SStore,
+    Jump,
+    JumpI,
+    Pc,
+    JumpDest,
+    /// PUSH0 to PUSH32, with the pushed value.
+    Push(BigUint),
+    /// DUP1 to DUP16, with the depth of the duplicated stack item (1-based).
+    Dup(usize),
+    /// SWAP1 to SWAP16, with the depth of the item swapped with the top of the stack.
+    Swap(usize),
+    Return,
+    Revert,
+    Invalid,
+}
+
+/// Decodes a hex string (with or without `0x` prefix) into bytecode.
+pub fn from_hex(code: &str) -> Result<Vec<u8>, String> {
+    let code = code.trim();
+    hex::decode(code.strip_prefix("0x").unwrap_or(code))
+        .map_err(|e| format!("Invalid hex bytecode: {e}"))
+}
+
+/// Decodes bytecode into a list of instructions together with their program counters.
+/// Push data that extends beyond the end of the code is padded with zeros.
+pub fn decode(code: &[u8]) -> Result<Vec<(usize, Instruction)>, String> {
+    let mut instructions = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        let instruction = match opcode {
+            0x00 => Instruction::Stop,
+            0x01 => Instruction::Add,
+            0x02 => Instruction::Mul,
+            0x03 => Instruction::Sub,
+            0x04 => Instruction::Div,
+            0x06 => Instruction::Mod,
+            0x10 => Instruction::Lt,
+            0x11 => Instruction::Gt,
+            0x14 => Instruction::Eq,
+            0x15 => Instruction::IsZero,
+            0x16 => Instruction::And,
+            0x17 => Instruction::Or,
+            0x18 => Instruction::Xor,
+            0x19 => Instruction::Not,
+            0x35 => Instruction::CallDataLoad,
+            0x50 => Instruction::Pop,
+            0x51 => Instruction::MLoad,
+            0x52 => Instruction::MStore,
+            0x54 => Instruction::SLoad,
+            0x55 => Instruction::SStore,
+            0x56 => Instruction::Jump,
+            0x57 => Instruction::JumpI,
+            0x58 => Instruction::Pc,
+            0x5b => Instruction::JumpDest,
+            0x5f..=0x7f => {
+                let len = (opcode - 0x5f) as usize;
+                let mut data =
+                    code[(pc + 1).min(code.len())..(pc + 1 + len).min(code.len())].to_vec();
+                data.resize(len, 0);
+                Instruction::Push(BigUint::from_be_bytes(&data))
+            }
+            0x80..=0x8f => Instruction::Dup((opcode - 0x7f) as usize),
+            0x90..=0x9f => Instruction::Swap((opcode - 0x8f) as usize),
+            0xf3 => Instruction::Return,
+            0xfd => Instruction::Revert,
+            0xfe => Instruction::Invalid,
+            _ => {
+                return Err(format!(
+                    "Unsupported opcode 0x{opcode:02x} at position {pc}"
+                ))
+            }
+        };
+        instructions.push((pc, instruction));
+        pc += 1 + match opcode {
+            0x5f..=0x7f => (opcode - 0x5f) as usize,
+            _ => 0,
+        };
+    }
+    Ok(instructions)
+}
+
+/// Returns the program counters of all JUMPDEST instructions, i.e. the valid jump targets.
+pub fn jump_destinations(instructions: &[(usize, Instruction)]) -> BTreeSet<usize> {
+    instructions
+        .iter()
+        .filter(|(_, instr)| *instr == Instruction::JumpDest)
+        .map(|(pc, _)| *pc)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_push_and_jumpdest() {
+        let code = from_hex("0x6001600a5b61ffff5f").unwrap();
+        let instructions = decode(&code).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                (0, Instruction::Push(1u32.into())),
+                (2, Instruction::Push(10u32.into())),
+                (4, Instruction::JumpDest),
+                (5, Instruction::Push(0xffffu32.into())),
+                (8, Instruction::Push(0u32.into())),
+            ]
+        );
+        assert_eq!(jump_destinations(&instructions), [4].into_iter().collect());
+    }
+
+    #[test]
+    fn truncated_push() {
+        let instructions = decode(&from_hex("61ff").unwrap()).unwrap();
+        assert_eq!(instructions, vec![(0, Instruction::Push(0xff00u32.into()))]);
+    }
+
+    #[test]
+    #[should_panic = "Unsupported opcode 0x20 at position 2"]
+    fn unsupported_opcode() {
+        decode(&from_hex("600020").unwrap()).unwrap();
+    }
+}
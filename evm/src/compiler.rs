@@ -0,0 +1,649 @@
+//! Compiles EVM bytecode to a powdr-asm virtual machine.
+//!
+//! Stack words are represented as eight little-endian 32-bit limbs. The stack, the
+//! memory and the storage of the contract all live in the `std::memory::Memory`
+//! machine, which requires addresses to be multiples of 4: limb `i` of stack slot `k`
+//! is stored at address `32 * k + 4 * i`, memory and storage words are stored in the
+//! same way starting at `MEMORY_BASE` and `STORAGE_BASE`.
+//! 256-bit arithmetic is performed by the `std::arith::Arith` machine and bitwise
+//! operations by the `std::binary::Binary` machine.
+//!
+//! The prover inputs are the number of words returned by the contract, the returned
+//! words and then the calldata words, all split into limbs (see [`crate::prover_inputs`]).
+//! `RETURN` and `STOP` assert that the contract returns exactly the claimed data.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use itertools::Itertools;
+use powdr_number::{BigUint, DegreeType};
+
+use crate::{
+    bytecode::{decode, jump_destinations, Instruction},
+    word_to_limbs, WORD_LIMBS,
+};
+
+/// The address of the first EVM memory word in the powdr memory.
+const MEMORY_BASE: u32 = 0x2000_0000;
+/// The address of the first storage word in the powdr memory.
+const STORAGE_BASE: u32 = 0x4000_0000;
+
+/// The lowest supported degree, given by the 16-bit tables of the RangeCheck machine.
+pub const MIN_DEGREE: DegreeType = 1 << 16;
+/// The lowest supported degree of contracts with bitwise operations,
+/// given by the lookup table of the Binary machine.
+pub const BINARY_MIN_DEGREE: DegreeType = 1 << 18;
+
+/// Compiles EVM bytecode to a powdr-asm program with the given degree, which has to be
+/// a power of two of at least [MIN_DEGREE], or [BINARY_MIN_DEGREE] if the contract
+/// uses bitwise operations.
+///
+/// Only memory accesses and calldata loads at offsets that are multiples of 32
+/// and storage keys smaller than 2**24 are supported.
+pub fn compile(code: &[u8], degree: DegreeType) -> Result<String, String> {
+    let instructions = decode(code)?;
+    let jump_destinations = jump_destinations(&instructions);
+
+    let uses_binary = instructions.iter().any(|(_, instruction)| {
+        matches!(
+            instruction,
+            Instruction::And | Instruction::Or | Instruction::Xor
+        )
+    });
+    let min_degree = if uses_binary {
+        BINARY_MIN_DEGREE
+    } else {
+        MIN_DEGREE
+    };
+    if degree < min_degree || !degree.is_power_of_two() {
+        return Err(format!(
+            "The degree has to be a power of two of at least {min_degree}, but is {degree}."
+        ));
+    }
+
+    let mut program = vec![
+        "cd <=X= ${ std::prover::Query::Input(0) };".to_string(),
+        "cd <=X= cd * 8 + 1;".to_string(),
+    ];
+    let mut previous = None;
+    for (pc, instruction) in &instructions {
+        program.extend(process_instruction(
+            *pc,
+            instruction,
+            previous,
+            &jump_destinations,
+        ));
+        previous = Some(instruction);
+    }
+    // Running past the end of the code is the same as STOP.
+    program.extend(process_instruction(
+        code.len(),
+        &Instruction::Stop,
+        previous,
+        &jump_destinations,
+    ));
+    program.extend(jump_table(&jump_destinations));
+    program.extend(return_routine());
+
+    Ok(evm_machine(&preamble(degree), program))
+}
+
+fn evm_machine(preamble: &str, program: Vec<String>) -> String {
+    format!(
+        r#"
+use std::arith::Arith;
+use std::binary::Binary;
+use std::memory::Memory;
+use std::range_check::RangeCheck;
+
+machine Main {{
+{}
+
+    function main {{
+{}
+    }}
+}}
+"#,
+        preamble,
+        program
+            .into_iter()
+            .format_with("\n", |line, f| f(&format_args!("\t\t{line}"))),
+    )
+}
+
+fn preamble(degree: DegreeType) -> String {
+    format!("    degree {degree};\n")
+        + r#"
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+"# + &["A", "B", "C", "D", "E"]
+        .iter()
+        .flat_map(|name| (0..WORD_LIMBS).map(move |i| format!("{name}{i}[<=]")))
+        .chain(WORD_REGISTERS.iter().flat_map(|name| limbs(name)))
+        .fold(String::new(), |mut registers, register| {
+            writeln!(registers, "    reg {register};").unwrap();
+            registers
+        })
+        + r#"    // Stack pointer, in words.
+    reg sp;
+    // Index of the first calldata limb in the prover inputs.
+    reg cd;
+    reg tmp;
+    reg n;
+    reg j;
+
+    RangeCheck range;
+    Arith arith(range);
+    Binary binary;
+    Memory memory(range);
+
+    // ============== iszero check for X =======================
+    let XIsZero = std::utils::is_zero(X);
+
+    // ============== control-flow instructions ==============
+
+    instr jump l: label { pc' = l }
+    instr branch_if_nonzero X, l: label { pc' = (1 - XIsZero) * l + XIsZero * (pc + 1) }
+    instr branch_if_zero X, l: label { pc' = XIsZero * l + (1 - XIsZero) * (pc + 1) }
+    instr fail { 1 = 0 }
+
+    // ================= logical instructions =================
+
+    instr assert_eq X, Y { X = Y }
+    instr is_equal_zero X -> Y { Y = XIsZero }
+
+    // ================= range checks =================
+
+    col fixed bytes(i) { i & 0xff };
+    col witness X_b1;
+    col witness X_b2;
+    col witness X_b3;
+    col witness X_b4;
+    { X_b1 } in { bytes };
+    { X_b2 } in { bytes };
+    { X_b3 } in { bytes };
+    { X_b4 } in { bytes };
+
+    instr check_u32 X { X = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000 }
+    // Requires X < 2**24.
+    instr check_index X { X = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 }
+    // Converts a byte offset to a word index, requires the offset to be a multiple of 32.
+    instr word_index X -> Y { X = 32 * Y, Y = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 }
+
+    // ================= submachine instructions =================
+
+    col fixed STEP(i) { i };
+    instr mload X -> Y ~ memory.mload X, STEP -> Y;
+    instr mstore X, Y -> ~ memory.mstore X, STEP, Y ->;
+
+    instr and X, Y -> Z ~ binary.and;
+    instr or X, Y -> Z ~ binary.or;
+    instr xor X, Y -> Z ~ binary.xor;
+
+"# + &format!(
+        "    instr affine_256 {} -> {} ~ arith.affine_256;\n",
+        ["A", "B", "C"]
+            .iter()
+            .flat_map(|r| limbs_of(r, ""))
+            .join(", "),
+        ["D", "E"].iter().flat_map(|r| limbs_of(r, "")).join(", ")
+    ) + r#"
+    // ================= prover functions for 256-bit words =================
+
+    let word_value: expr[] -> int = query |limbs| std::array::sum(std::array::map_enumerated(limbs, |i, l| std::convert::int(std::prover::eval(l)) << (i * 32)));
+    let word_limb: int, int -> fe = |w, i| std::convert::fe((w >> (i * 32)) & 0xffffffff);
+    let sub_limb: expr[], expr[], int -> fe = query |x, y, i| word_limb((word_value(x) + (1 << 256) - word_value(y)) % (1 << 256), i);
+    let div_limb: expr[], expr[], int -> fe = query |x, y, i| word_limb(word_value(x) / word_value(y), i);
+    let mod_limb: expr[], expr[], int -> fe = query |x, y, i| word_limb(word_value(x) % word_value(y), i);
+"#
+}
+
+/// The names of the registers holding a full 256-bit word.
+/// `h` and `l` receive the high and low words of the results of `affine_256`.
+const WORD_REGISTERS: [&str; 6] = ["a", "b", "c", "d", "h", "l"];
+
+fn limbs_of(name: &str, separator: &str) -> Vec<String> {
+    (0..WORD_LIMBS)
+        .map(|i| format!("{name}{separator}{i}"))
+        .collect()
+}
+
+/// The limb registers of the word register `name`.
+fn limbs(name: &str) -> Vec<String> {
+    limbs_of(name, "_")
+}
+
+fn sum(limbs: &[String]) -> String {
+    limbs.join(" + ")
+}
+
+fn constant_limbs(value: &BigUint) -> Vec<String> {
+    word_to_limbs(value).iter().map(|l| l.to_string()).collect()
+}
+
+fn stack_address(offset: &str, i: usize) -> String {
+    format!("({offset}) * 32 + {}", 4 * i)
+}
+
+fn pop(word: &str) -> Vec<String> {
+    std::iter::once("sp <=X= sp - 1;".to_string())
+        .chain(
+            limbs(word)
+                .iter()
+                .enumerate()
+                .map(|(i, l)| format!("{l} <== mload({});", stack_address("sp", i))),
+        )
+        .collect()
+}
+
+fn push(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("mstore {}, {v};", stack_address("sp", i)))
+        .chain(std::iter::once("sp <=X= sp + 1;".to_string()))
+        .collect()
+}
+
+/// Pushes a word whose value is given by a single limb.
+fn push_small(value: &str) -> Vec<String> {
+    let mut limbs = vec!["0".to_string(); WORD_LIMBS];
+    limbs[0] = value.to_string();
+    push(&limbs)
+}
+
+/// Computes `x * y + z` into the word registers `h` (high word) and `l` (low word).
+fn affine_256(x: &[String], y: &[String], z: &[String]) -> String {
+    format!(
+        "{}, {} <== affine_256({}, {}, {});",
+        limbs("h").join(", "),
+        limbs("l").join(", "),
+        x.join(", "),
+        y.join(", "),
+        z.join(", ")
+    )
+}
+
+/// Computes `x - y` modulo 2**256 into `out` and sets `h_0` to 1 if `x < y` and to 0 otherwise.
+fn sub_words(x: &str, y: &str, out: &str) -> Vec<String> {
+    let (x, y, out) = (limbs(x), limbs(y), limbs(out));
+    // The difference is provided by the prover and checked via y + out = h * 2**256 + x.
+    out.iter()
+        .enumerate()
+        .map(|(i, l)| {
+            format!(
+                "{l} <=X= ${{ std::prover::Query::Hint(sub_limb([{}], [{}], {i})) }};",
+                x.join(", "),
+                y.join(", ")
+            )
+        })
+        .chain(std::iter::once(affine_256(
+            &y,
+            &constant_limbs(&1u32.into()),
+            &out,
+        )))
+        .chain(
+            limbs("l")
+                .iter()
+                .zip(&x)
+                .map(|(l, x)| format!("assert_eq {l}, {x};")),
+        )
+        .collect()
+}
+
+/// Requires the word in `word` to be smaller than 2**32 and returns its lowest limb.
+fn small_word(word: &str) -> (String, Vec<String>) {
+    let limbs = limbs(word);
+    (
+        limbs[0].clone(),
+        vec![format!("assert_eq {}, 0;", sum(&limbs[1..]))],
+    )
+}
+
+/// Converts the byte offset in `word` to a word index in `out`.
+fn word_index(word: &str, out: &str) -> Vec<String> {
+    let (offset, mut code) = small_word(word);
+    code.push(format!("{out} <== word_index({offset});"));
+    code
+}
+
+fn escape_label(pc: usize) -> String {
+    format!("evm_{pc}")
+}
+
+fn process_instruction(
+    pc: usize,
+    instruction: &Instruction,
+    previous: Option<&Instruction>,
+    jump_destinations: &BTreeSet<usize>,
+) -> Vec<String> {
+    // The destination of a jump, if it is known at compile time.
+    let static_destination = match previous {
+        Some(Instruction::Push(destination)) => usize::try_from(destination)
+            .ok()
+            .filter(|d| jump_destinations.contains(d)),
+        _ => None,
+    };
+    match instruction {
+        Instruction::Stop => vec!["assert_eq cd, 1;".to_string(), "return;".to_string()],
+        Instruction::Add => [
+            pop("a"),
+            pop("b"),
+            vec![affine_256(
+                &limbs("a"),
+                &constant_limbs(&1u32.into()),
+                &limbs("b"),
+            )],
+            push(&limbs("l")),
+        ]
+        .concat(),
+        Instruction::Mul => [
+            pop("a"),
+            pop("b"),
+            vec![affine_256(
+                &limbs("a"),
+                &limbs("b"),
+                &constant_limbs(&0u32.into()),
+            )],
+            push(&limbs("l")),
+        ]
+        .concat(),
+        Instruction::Sub => [pop("a"), pop("b"), sub_words("a", "b", "c"), push(&limbs("c"))].concat(),
+        Instruction::Div | Instruction::Mod => {
+            let (quotient, remainder) = (limbs("c"), limbs("d"));
+            let result = if *instruction == Instruction::Div {
+                &quotient
+            } else {
+                &remainder
+            };
+            let done = format!("{}_done", escape_label(pc));
+            let hints = |name: &str, out: &[String]| {
+                out.iter()
+                    .enumerate()
+                    .map(|(i, l)| {
+                        format!(
+                            "{l} <=X= ${{ std::prover::Query::Hint({name}([{}], [{}], {i})) }};",
+                            limbs("a").join(", "),
+                            limbs("b").join(", ")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            };
+            [
+                pop("a"),
+                pop("b"),
+                // The result is zero if the divisor is zero.
+                result.iter().map(|l| format!("{l} <=X= 0;")).collect(),
+                vec![format!("branch_if_zero {}, {done};", sum(&limbs("b")))],
+                hints("div_limb", &quotient),
+                hints("mod_limb", &remainder),
+                // a = quotient * b + remainder
+                vec![
+                    affine_256(&quotient, &limbs("b"), &remainder),
+                    format!("assert_eq {}, 0;", sum(&limbs("h"))),
+                ],
+                limbs("l")
+                    .iter()
+                    .zip(limbs("a"))
+                    .map(|(l, a)| format!("assert_eq {l}, {a};"))
+                    .collect(),
+                // remainder < b
+                sub_words("d", "b", "a"),
+                vec!["assert_eq h_0, 1;".to_string(), format!("{done}:")],
+                push(result),
+            ]
+            .concat()
+        }
+        Instruction::Lt => [pop("a"), pop("b"), sub_words("a", "b", "c"), push_small("h_0")].concat(),
+        Instruction::Gt => [pop("a"), pop("b"), sub_words("b", "a", "c"), push_small("h_0")].concat(),
+        Instruction::Eq => [
+            pop("a"),
+            pop("b"),
+            sub_words("a", "b", "c"),
+            vec![format!("tmp <== is_equal_zero({});", sum(&limbs("c")))],
+            push_small("tmp"),
+        ]
+        .concat(),
+        Instruction::IsZero => [
+            pop("a"),
+            vec![format!("tmp <== is_equal_zero({});", sum(&limbs("a")))],
+            push_small("tmp"),
+        ]
+        .concat(),
+        Instruction::And | Instruction::Or | Instruction::Xor => {
+            let op = match instruction {
+                Instruction::And => "and",
+                Instruction::Or => "or",
+                _ => "xor",
+            };
+            [
+                pop("a"),
+                pop("b"),
+                limbs("c")
+                    .iter()
+                    .zip(limbs("a").iter().zip(limbs("b")))
+                    .map(|(c, (a, b))| format!("{c} <== {op}({a}, {b});"))
+                    .collect(),
+                push(&limbs("c")),
+            ]
+            .concat()
+        }
+        Instruction::Not => [
+            pop("a"),
+            limbs("c")
+                .iter()
+                .zip(limbs("a"))
+                .map(|(c, a)| format!("{c} <=X= 0xffffffff - {a};"))
+                .collect(),
+            push(&limbs("c")),
+        ]
+        .concat(),
+        Instruction::CallDataLoad => [
+            pop("a"),
+            word_index("a", "tmp"),
+            limbs("c")
+                .iter()
+                .enumerate()
+                .flat_map(|(i, c)| {
+                    [
+                        format!("{c} <=X= ${{ std::prover::Query::Input(std::convert::int(std::prover::eval(cd)) + std::convert::int(std::prover::eval(tmp)) * 8 + {i}) }};"),
+                        format!("check_u32 {c};"),
+                    ]
+                })
+                .collect(),
+            push(&limbs("c")),
+        ]
+        .concat(),
+        Instruction::Pop => vec!["sp <=X= sp - 1;".to_string()],
+        Instruction::MLoad => [
+            pop("a"),
+            word_index("a", "tmp"),
+            limbs("c")
+                .iter()
+                .enumerate()
+                .map(|(i, c)| format!("{c} <== mload({MEMORY_BASE} + tmp * 32 + {});", 4 * i))
+                .collect(),
+            push(&limbs("c")),
+        ]
+        .concat(),
+        Instruction::MStore => [
+            pop("a"),
+            pop("b"),
+            word_index("a", "tmp"),
+            limbs("b")
+                .iter()
+                .enumerate()
+                .map(|(i, b)| format!("mstore {MEMORY_BASE} + tmp * 32 + {}, {b};", 4 * i))
+                .collect(),
+        ]
+        .concat(),
+        Instruction::SLoad => {
+            let (key, check) = small_word("a");
+            [
+                pop("a"),
+                check,
+                vec![format!("check_index {key};")],
+                limbs("c")
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| format!("{c} <== mload({STORAGE_BASE} + {key} * 32 + {});", 4 * i))
+                    .collect(),
+                push(&limbs("c")),
+            ]
+            .concat()
+        }
+        Instruction::SStore => {
+            let (key, check) = small_word("a");
+            [
+                pop("a"),
+                pop("b"),
+                check,
+                vec![format!("check_index {key};")],
+                limbs("b")
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| format!("mstore {STORAGE_BASE} + {key} * 32 + {}, {b};", 4 * i))
+                    .collect(),
+            ]
+            .concat()
+        }
+        Instruction::Jump => match static_destination {
+            Some(destination) => vec![
+                "sp <=X= sp - 1;".to_string(),
+                format!("jump {};", escape_label(destination)),
+            ],
+            None => [pop("a"), dynamic_jump("a")].concat(),
+        },
+        Instruction::JumpI => match static_destination {
+            Some(destination) => [
+                vec!["sp <=X= sp - 1;".to_string()],
+                pop("b"),
+                vec![format!(
+                    "branch_if_nonzero {}, {};",
+                    sum(&limbs("b")),
+                    escape_label(destination)
+                )],
+            ]
+            .concat(),
+            None => {
+                let skip = format!("{}_skip", escape_label(pc));
+                [
+                    pop("a"),
+                    pop("b"),
+                    vec![format!("branch_if_zero {}, {skip};", sum(&limbs("b")))],
+                    dynamic_jump("a"),
+                    vec![format!("{skip}:")],
+                ]
+                .concat()
+            }
+        },
+        Instruction::Pc => push(&constant_limbs(&pc.into())),
+        Instruction::JumpDest => vec![format!("{}:", escape_label(pc))],
+        Instruction::Push(value) => push(&constant_limbs(value)),
+        Instruction::Dup(depth) => [
+            limbs("c")
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    format!(
+                        "{c} <== mload({});",
+                        stack_address(&format!("sp - {depth}"), i)
+                    )
+                })
+                .collect(),
+            push(&limbs("c")),
+        ]
+        .concat(),
+        Instruction::Swap(depth) => {
+            let top = "sp - 1".to_string();
+            let other = format!("sp - {}", depth + 1);
+            let load = |word: &str, offset: &str| {
+                limbs(word)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, l)| format!("{l} <== mload({});", stack_address(offset, i)))
+                    .collect::<Vec<_>>()
+            };
+            let store = |word: &str, offset: &str| {
+                limbs(word)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, l)| format!("mstore {}, {l};", stack_address(offset, i)))
+                    .collect::<Vec<_>>()
+            };
+            [
+                load("a", &top),
+                load("b", &other),
+                store("a", &other),
+                store("b", &top),
+            ]
+            .concat()
+        }
+        Instruction::Return => [
+            pop("a"),
+            pop("b"),
+            word_index("a", "tmp"),
+            word_index("b", "n"),
+            vec!["jump __evm_return;".to_string()],
+        ]
+        .concat(),
+        // Reverting executions cannot be proven.
+        Instruction::Revert | Instruction::Invalid => vec!["fail;".to_string()],
+    }
+}
+
+/// Jumps to the code location stored in `word`. Fails if it is not a valid jump destination.
+fn dynamic_jump(word: &str) -> Vec<String> {
+    let (destination, mut code) = small_word(word);
+    code.extend([
+        format!("tmp <=X= {destination};"),
+        "jump __evm_jump_table;".to_string(),
+    ]);
+    code
+}
+
+fn jump_table(jump_destinations: &BTreeSet<usize>) -> Vec<String> {
+    std::iter::once("__evm_jump_table:".to_string())
+        .chain(
+            jump_destinations
+                .iter()
+                .map(|d| format!("branch_if_zero tmp - {d}, {};", escape_label(*d))),
+        )
+        .chain(std::iter::once("fail;".to_string()))
+        .collect()
+}
+
+/// Compares `n` memory words starting at word index `tmp` to the return data claimed
+/// in the prover inputs and terminates the program.
+fn return_routine() -> Vec<String> {
+    [
+        vec![
+            "__evm_return:".to_string(),
+            "assert_eq cd, n * 8 + 1;".to_string(),
+            "j <=X= 0;".to_string(),
+            "__evm_return_loop:".to_string(),
+            "branch_if_zero n - j, __evm_return_end;".to_string(),
+        ],
+        limbs("c")
+            .iter()
+            .zip(limbs("d"))
+            .enumerate()
+            .flat_map(|(i, (c, d))| {
+                [
+                    format!("{c} <== mload({MEMORY_BASE} + (tmp + j) * 32 + {});", 4 * i),
+                    format!("{d} <=X= ${{ std::prover::Query::Input(std::convert::int(std::prover::eval(j)) * 8 + {}) }};", i + 1),
+                    format!("assert_eq {c}, {d};"),
+                ]
+            })
+            .collect(),
+        vec![
+            "j <=X= j + 1;".to_string(),
+            "jump __evm_return_loop;".to_string(),
+            "__evm_return_end:".to_string(),
+            "return;".to_string(),
+        ],
+    ]
+    .concat()
+}
@@ -0,0 +1,244 @@
+//! A simple reference interpreter for the supported subset of the EVM.
+//! It is used to compute the expected results of contracts compiled to powdr-asm.
+
+use std::collections::BTreeMap;
+
+use powdr_number::BigUint;
+
+use crate::bytecode::{decode, jump_destinations, Instruction};
+
+/// The result of a successful execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Stop,
+    Return(Vec<u8>),
+    Revert(Vec<u8>),
+}
+
+/// Executes `code` with the given calldata.
+/// Returns an error for exceptional halts, like stack underflows or invalid jumps.
+pub fn execute(code: &[u8], calldata: &[u8]) -> Result<Outcome, String> {
+    let instructions = decode(code)?;
+    let jump_destinations = jump_destinations(&instructions);
+    let index_of_pc = instructions
+        .iter()
+        .enumerate()
+        .map(|(i, (pc, _))| (*pc, i))
+        .collect::<BTreeMap<_, _>>();
+
+    let modulus = BigUint::from(1u32) << 256;
+    let mut stack: Vec<BigUint> = vec![];
+    let mut memory: Vec<u8> = vec![];
+    let mut storage: BTreeMap<BigUint, BigUint> = BTreeMap::new();
+
+    let mut i = 0;
+    while let Some((pc, instruction)) = instructions.get(i) {
+        i += 1;
+        let mut pop = || {
+            stack
+                .pop()
+                .ok_or(format!("Stack underflow at position {pc}"))
+        };
+        match instruction {
+            Instruction::Stop => return Ok(Outcome::Stop),
+            Instruction::Add => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push((a + b) % &modulus);
+            }
+            Instruction::Mul => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push((a * b) % &modulus);
+            }
+            Instruction::Sub => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push((a + &modulus - b) % &modulus);
+            }
+            Instruction::Div | Instruction::Mod => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push(if b == BigUint::from(0u32) {
+                    b
+                } else if *instruction == Instruction::Div {
+                    a / b
+                } else {
+                    a % b
+                });
+            }
+            Instruction::Lt => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push(BigUint::from((a < b) as u32));
+            }
+            Instruction::Gt => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push(BigUint::from((a > b) as u32));
+            }
+            Instruction::Eq => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push(BigUint::from((a == b) as u32));
+            }
+            Instruction::IsZero => {
+                let a = pop()?;
+                stack.push(BigUint::from((a == BigUint::from(0u32)) as u32));
+            }
+            Instruction::And => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push(a & b);
+            }
+            Instruction::Or => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push(a | b);
+            }
+            Instruction::Xor => {
+                let (a, b) = (pop()?, pop()?);
+                stack.push(a ^ b);
+            }
+            Instruction::Not => {
+                let a = pop()?;
+                stack.push(&modulus - BigUint::from(1u32) - a);
+            }
+            Instruction::CallDataLoad => {
+                let offset = to_usize(&pop()?);
+                let mut word = [0u8; 32];
+                for (j, byte) in word.iter_mut().enumerate() {
+                    if let Some(b) = offset.checked_add(j).and_then(|k| calldata.get(k)) {
+                        *byte = *b;
+                    }
+                }
+                stack.push(BigUint::from_be_bytes(&word));
+            }
+            Instruction::Pop => {
+                pop()?;
+            }
+            Instruction::MLoad => {
+                let offset = to_usize(&pop()?);
+                let word = read_memory(&mut memory, offset, 32);
+                stack.push(BigUint::from_be_bytes(&word));
+            }
+            Instruction::MStore => {
+                let (offset, value) = (to_usize(&pop()?), pop()?);
+                write_memory(&mut memory, offset, &to_word_bytes(&value));
+            }
+            Instruction::SLoad => {
+                let key = pop()?;
+                stack.push(storage.get(&key).cloned().unwrap_or_default());
+            }
+            Instruction::SStore => {
+                let (key, value) = (pop()?, pop()?);
+                storage.insert(key, value);
+            }
+            Instruction::Jump | Instruction::JumpI => {
+                let destination = pop()?;
+                if *instruction == Instruction::JumpI && pop()? == BigUint::from(0u32) {
+                    continue;
+                }
+                let destination = to_usize(&destination);
+                if !jump_destinations.contains(&destination) {
+                    return Err(format!(
+                        "Invalid jump destination {destination} at position {pc}"
+                    ));
+                }
+                i = index_of_pc[&destination];
+            }
+            Instruction::Pc => stack.push(BigUint::from(*pc)),
+            Instruction::JumpDest => {}
+            Instruction::Push(value) => stack.push(value.clone()),
+            Instruction::Dup(n) => {
+                let value = stack
+                    .len()
+                    .checked_sub(*n)
+                    .map(|k| stack[k].clone())
+                    .ok_or(format!("Stack underflow at position {pc}"))?;
+                stack.push(value);
+            }
+            Instruction::Swap(n) => {
+                let top = stack.len().checked_sub(1);
+                let other = stack.len().checked_sub(n + 1);
+                match (top, other) {
+                    (Some(top), Some(other)) => stack.swap(top, other),
+                    _ => return Err(format!("Stack underflow at position {pc}")),
+                }
+            }
+            Instruction::Return | Instruction::Revert => {
+                let (offset, size) = (to_usize(&pop()?), to_usize(&pop()?));
+                let data = read_memory(&mut memory, offset, size);
+                return Ok(if *instruction == Instruction::Return {
+                    Outcome::Return(data)
+                } else {
+                    Outcome::Revert(data)
+                });
+            }
+            Instruction::Invalid => return Err(format!("Invalid instruction at position {pc}")),
+        }
+    }
+    Ok(Outcome::Stop)
+}
+
+/// Converts a stack word to an offset. Offsets that do not fit into
+/// memory are clamped, they would run out of gas on a real EVM.
+fn to_usize(value: &BigUint) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX).min(1 << 32)
+}
+
+fn to_word_bytes(value: &BigUint) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut word = vec![0u8; 32 - bytes.len()];
+    word.extend(bytes);
+    word
+}
+
+fn read_memory(memory: &mut Vec<u8>, offset: usize, size: usize) -> Vec<u8> {
+    if size > 0 && memory.len() < offset + size {
+        memory.resize(offset + size, 0);
+    }
+    memory[offset.min(memory.len())..(offset + size).min(memory.len())].to_vec()
+}
+
+fn write_memory(memory: &mut Vec<u8>, offset: usize, data: &[u8]) {
+    if memory.len() < offset + data.len() {
+        memory.resize(offset + data.len(), 0);
+    }
+    memory[offset..offset + data.len()].copy_from_slice(data);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bytecode::from_hex;
+
+    use super::*;
+
+    fn word(value: u32) -> Vec<u8> {
+        to_word_bytes(&BigUint::from(value))
+    }
+
+    #[test]
+    fn arithmetic() {
+        // (7 - 10) mod 2**256, returned from memory offset 0.
+        let code = from_hex("600a6007035f5260205ff3").unwrap();
+        let Outcome::Return(data) = execute(&code, &[]).unwrap() else {
+            panic!()
+        };
+        assert_eq!(data, [vec![0xff; 31], vec![0xfd]].concat());
+    }
+
+    #[test]
+    fn loop_with_calldata() {
+        // Sums the numbers from 1 to calldata[0].
+        //  0: PUSH0 CALLDATALOAD PUSH0
+        //  3: JUMPDEST DUP2 ISZERO PUSH1 20 JUMPI
+        //  9: DUP2 ADD SWAP1 PUSH1 1 SWAP1 SUB SWAP1 PUSH1 3 JUMP
+        // 20: JUMPDEST PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        let code = from_hex("5f355f5b811560145781019060019003906003565b5f5260205ff3").unwrap();
+        assert_eq!(
+            execute(&code, &word(10)).unwrap(),
+            Outcome::Return(word(55))
+        );
+    }
+
+    #[test]
+    fn invalid_jump() {
+        let code = from_hex("600356").unwrap();
+        assert_eq!(
+            execute(&code, &[]),
+            Err("Invalid jump destination 3 at position 2".to_string())
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! An EVM frontend for powdr
+//!
+//! Compiles the bytecode of a contract to a powdr-asm virtual machine (see [compiler]),
+//! whose execution on the given calldata proves that the contract returns the claimed data.
+//!
+//! Only a subset of the EVM is supported:
+//! - Memory accesses (`MLOAD`, `MSTORE`, `RETURN`) and `CALLDATALOAD` need offsets that
+//!   are multiples of 32.
+//! - Storage keys (`SLOAD`, `SSTORE`) need to be smaller than 2**24.
+//! - Executions that revert cannot be proven.
+#![deny(clippy::print_stdout)]
+
+use powdr_number::{BigUint, FieldElement};
+
+pub mod bytecode;
+pub mod compiler;
+pub mod interpreter;
+
+/// The number of 32-bit limbs of a 256-bit EVM word.
+pub const WORD_LIMBS: usize = 8;
+
+/// Splits a 256-bit word into little-endian 32-bit limbs.
+pub fn word_to_limbs(word: &BigUint) -> [u32; WORD_LIMBS] {
+    let mask = BigUint::from(u32::MAX);
+    let mut limbs = [0; WORD_LIMBS];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        *limb = u32::try_from((word >> (i * 32)) & &mask).unwrap();
+    }
+    limbs
+}
+
+/// Splits big-endian bytes into words (padding the last word with zeros) and
+/// returns the limbs of all words.
+fn bytes_to_limbs(data: &[u8]) -> Vec<u32> {
+    data.chunks(32)
+        .flat_map(|chunk| {
+            let mut word = chunk.to_vec();
+            word.resize(32, 0);
+            word_to_limbs(&BigUint::from_be_bytes(&word))
+        })
+        .collect()
+}
+
+/// Returns the prover inputs for running a compiled contract with the given
+/// calldata and claimed return data.
+/// The length of the return data has to be a multiple of 32.
+pub fn prover_inputs<T: FieldElement>(calldata: &[u8], return_data: &[u8]) -> Vec<T> {
+    assert!(
+        return_data.len() % 32 == 0,
+        "Return data has to consist of full words."
+    );
+    std::iter::once((return_data.len() / 32) as u32)
+        .chain(bytes_to_limbs(return_data))
+        .chain(bytes_to_limbs(calldata))
+        .map(T::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+
+    use super::*;
+
+    #[test]
+    fn limbs() {
+        let word = (BigUint::from(1u32) << 255) + BigUint::from(0x1_0000_0002u64);
+        assert_eq!(word_to_limbs(&word), [2, 1, 0, 0, 0, 0, 0, 0x8000_0000]);
+    }
+
+    #[test]
+    fn inputs() {
+        let mut return_data = vec![0; 32];
+        return_data[31] = 7;
+        let inputs = prover_inputs::<GoldilocksField>(&[1], &return_data);
+        let expected = [1, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x0100_0000];
+        assert_eq!(
+            inputs,
+            expected
+                .into_iter()
+                .map(GoldilocksField::from)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use std::path::PathBuf;
+
+use powdr_evm::{
+    bytecode::from_hex,
+    compiler::{compile, BINARY_MIN_DEGREE, MIN_DEGREE},
+    interpreter::{execute, Outcome},
+    prover_inputs,
+};
+use powdr_number::{BigUint, GoldilocksField};
+use powdr_pipeline::{test_util::verify_pipeline, Pipeline};
+use test_log::test;
+
+/// Sums the numbers from 1 to the first calldata word:
+///  0: PUSH0 CALLDATALOAD PUSH0
+///  3: JUMPDEST DUP2 ISZERO PUSH1 20 JUMPI
+///  9: DUP2 ADD SWAP1 PUSH1 1 SWAP1 SUB SWAP1 PUSH1 3 JUMP
+/// 20: JUMPDEST PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+const SUM: &str = "5f355f5b811560145781019060019003906003565b5f5260205ff3";
+
+/// Loads x and y from calldata and returns
+/// [x * y, x / y, x % y, x < y, x > y, x == y, x + y, !x],
+/// where x + y goes through the storage and a dynamic jump.
+const ARITHMETIC: &str = "6020355f35026000526020355f35046020526020355f35066040526020355f35106060526020355f35116080526020355f351460a0526020355f350160055560455f0156fe5b60055460c0525f351960e0526101005ff3";
+
+/// Returns [x & y, x | y, x ^ y, !x] for the calldata words x and y.
+const BITWISE: &str = "6020355f35165f526020355f35176020526020355f35186040525f351960605260805ff3";
+
+/// Returns [x * y, x / y, x % y, x / 0, x % 0] for the calldata words x and y:
+///  0: PUSH1 32 CALLDATALOAD PUSH0 CALLDATALOAD MUL PUSH0 MSTORE
+///  8: PUSH1 32 CALLDATALOAD PUSH0 CALLDATALOAD DIV PUSH1 32 MSTORE
+/// 17: PUSH1 32 CALLDATALOAD PUSH0 CALLDATALOAD MOD PUSH1 64 MSTORE
+/// 26: PUSH0 PUSH0 CALLDATALOAD DIV PUSH1 96 MSTORE
+/// 33: PUSH0 PUSH0 CALLDATALOAD MOD PUSH1 128 MSTORE
+/// 40: PUSH1 160 PUSH0 RETURN
+const MUL_DIV_MOD: &str =
+    "6020355f35025f526020355f35046020526020355f35066040525f5f35046060525f5f350660805260a05ff3";
+
+/// Returns [x < y, x > y, x == y, x == x, x == 0, 0 == 0] for the calldata words x and y:
+///  0: PUSH1 32 CALLDATALOAD PUSH0 CALLDATALOAD LT PUSH0 MSTORE
+///  8: PUSH1 32 CALLDATALOAD PUSH0 CALLDATALOAD GT PUSH1 32 MSTORE
+/// 17: PUSH1 32 CALLDATALOAD PUSH0 CALLDATALOAD EQ PUSH1 64 MSTORE
+/// 26: PUSH0 CALLDATALOAD DUP1 EQ PUSH1 96 MSTORE
+/// 33: PUSH0 CALLDATALOAD ISZERO PUSH1 128 MSTORE
+/// 39: PUSH0 ISZERO PUSH1 160 MSTORE
+/// 44: PUSH1 192 PUSH0 RETURN
+const COMPARISONS: &str =
+    "6020355f35105f526020355f35116020526020355f35146040525f3580146060525f35156080525f1560a05260c05ff3";
+
+/// Stores x at key 1 and y at key 2**24 - 1, overwrites key 1 with x + y and returns
+/// the values at the keys 1, 2**24 - 1 and 2 for the calldata words x and y:
+///  0: PUSH0 CALLDATALOAD PUSH1 1 SSTORE
+///  5: PUSH1 32 CALLDATALOAD PUSH3 0xffffff SSTORE
+/// 13: PUSH1 32 CALLDATALOAD PUSH0 CALLDATALOAD ADD PUSH1 1 SSTORE
+/// 22: PUSH1 1 SLOAD PUSH0 MSTORE
+/// 27: PUSH3 0xffffff SLOAD PUSH1 32 MSTORE
+/// 35: PUSH1 2 SLOAD PUSH1 64 MSTORE
+/// 41: PUSH1 96 PUSH0 RETURN
+const STORAGE: &str =
+    "5f3560015560203562ffffff556020355f35016001556001545f5262ffffff5460205260025460405260605ff3";
+
+/// Returns x + 1 for a non-zero calldata word x and 42 otherwise, using jumps to
+/// destinations computed from the program counter:
+///  0: PUSH0 CALLDATALOAD DUP1
+///  3: PC PUSH1 13 ADD JUMPI
+///  8: POP PUSH1 42
+/// 11: PC PUSH1 9 ADD JUMP
+/// 16: JUMPDEST PUSH1 1 ADD
+/// 20: JUMPDEST PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+const DYNAMIC_JUMPS: &str = "5f358058600d015750602a58600901565b6001015b5f5260205ff3";
+
+fn word(value: BigUint) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    [vec![0; 32 - bytes.len()], bytes].concat()
+}
+
+/// Runs the contract in the reference interpreter and then proves that the contract,
+/// compiled with the given degree, returns `return_data` (or the interpreter result if `None`).
+fn verify_contract(
+    name: &str,
+    code: &str,
+    degree: u64,
+    calldata: &[u8],
+    return_data: Option<Vec<u8>>,
+) {
+    let code = from_hex(code).unwrap();
+    let expected = match execute(&code, calldata).unwrap() {
+        Outcome::Stop => vec![],
+        Outcome::Return(data) => data,
+        Outcome::Revert(_) => panic!("Contract reverted"),
+    };
+    let inputs = prover_inputs::<GoldilocksField>(calldata, &return_data.unwrap_or(expected));
+
+    let pipeline = Pipeline::default()
+        .from_asm_string(compile(&code, degree).unwrap(), Some(PathBuf::from(name)))
+        .with_prover_inputs(inputs);
+    verify_pipeline(pipeline).unwrap();
+}
+
+/// Checks that the contract returns the expected words for each pair of calldata words and
+/// expected words, both in the reference interpreter and in the compiled program (via the
+/// constraint checker). The program is compiled and its fixed columns are evaluated only once.
+fn verify_opcodes(name: &str, code: &str, degree: u64, cases: &[(Vec<BigUint>, Vec<BigUint>)]) {
+    let code = from_hex(code).unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .from_asm_string(compile(&code, degree).unwrap(), Some(PathBuf::from(name)));
+    pipeline.compute_fixed_cols().unwrap();
+    for (calldata, expected) in cases {
+        let calldata = calldata.iter().cloned().flat_map(word).collect::<Vec<_>>();
+        let return_data = expected.iter().cloned().flat_map(word).collect::<Vec<_>>();
+        assert_eq!(
+            execute(&code, &calldata).unwrap(),
+            Outcome::Return(return_data.clone())
+        );
+        let inputs = prover_inputs(&calldata, &return_data);
+        verify_pipeline(pipeline.clone().with_prover_inputs(inputs)).unwrap();
+    }
+}
+
+/// Compiles the contract down to optimized PIL, which checks that the generated
+/// program is well-formed without the slow witness generation of the tests below.
+fn compile_contract(name: &str, code: &str, degree: u64) {
+    Pipeline::<GoldilocksField>::default()
+        .from_asm_string(
+            compile(&from_hex(code).unwrap(), degree).unwrap(),
+            Some(PathBuf::from(name)),
+        )
+        .compute_optimized_pil()
+        .unwrap();
+}
+
+#[test]
+fn compile_contracts() {
+    compile_contract("sum", SUM, MIN_DEGREE);
+    compile_contract("arithmetic", ARITHMETIC, MIN_DEGREE);
+    compile_contract("bitwise", BITWISE, BINARY_MIN_DEGREE);
+}
+
+#[test]
+fn degree_too_small() {
+    let code = from_hex(BITWISE).unwrap();
+    assert_eq!(
+        compile(&code, MIN_DEGREE),
+        Err("The degree has to be a power of two of at least 262144, but is 65536.".to_string())
+    );
+    assert!(compile(&from_hex(SUM).unwrap(), MIN_DEGREE / 2).is_err());
+}
+
+#[test]
+fn sum() {
+    verify_contract("sum", SUM, MIN_DEGREE, &word(3u32.into()), None);
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn sum_large_degree() {
+    verify_contract("sum", SUM, BINARY_MIN_DEGREE, &word(10u32.into()), None);
+}
+
+#[test]
+#[should_panic = "Witness generation failed."]
+fn sum_wrong_result() {
+    verify_contract(
+        "sum",
+        SUM,
+        MIN_DEGREE,
+        &word(3u32.into()),
+        Some(word(7u32.into())),
+    );
+}
+
+#[test]
+fn mul_div_mod() {
+    let x = (BigUint::from(1u32) << 200) + BigUint::from(12345u32);
+    let y = BigUint::from(1000u32);
+    let zero = BigUint::from(0u32);
+    verify_opcodes(
+        "mul_div_mod",
+        MUL_DIV_MOD,
+        MIN_DEGREE,
+        &[
+            (
+                vec![x.clone(), y.clone()],
+                vec![
+                    // The product wraps around.
+                    (&x * &y) % (BigUint::from(1u32) << 256),
+                    &x / &y,
+                    &x % &y,
+                    zero.clone(),
+                    zero.clone(),
+                ],
+            ),
+            // Division by zero results in zero.
+            (vec![y.clone(), zero.clone()], vec![zero.clone(); 5]),
+        ],
+    );
+}
+
+#[test]
+fn comparisons() {
+    let x = (BigUint::from(1u32) << 200) + BigUint::from(5u32);
+    let y = (BigUint::from(1u32) << 200) + BigUint::from(7u32);
+    let [zero, one] = [0u32, 1u32].map(BigUint::from);
+    verify_opcodes(
+        "comparisons",
+        COMPARISONS,
+        MIN_DEGREE,
+        &[
+            (
+                vec![x.clone(), y.clone()],
+                vec![
+                    one.clone(),
+                    zero.clone(),
+                    zero.clone(),
+                    one.clone(),
+                    zero.clone(),
+                    one.clone(),
+                ],
+            ),
+            (
+                vec![y.clone(), x.clone()],
+                vec![
+                    zero.clone(),
+                    one.clone(),
+                    zero.clone(),
+                    one.clone(),
+                    zero.clone(),
+                    one.clone(),
+                ],
+            ),
+            (
+                vec![zero.clone(), zero.clone()],
+                vec![
+                    zero.clone(),
+                    zero.clone(),
+                    one.clone(),
+                    one.clone(),
+                    one.clone(),
+                    one.clone(),
+                ],
+            ),
+        ],
+    );
+}
+
+#[test]
+fn storage() {
+    let x = (BigUint::from(1u32) << 255) + BigUint::from(3u32);
+    let y = BigUint::from(0xffff_ffffu32) << 100;
+    verify_opcodes(
+        "storage",
+        STORAGE,
+        MIN_DEGREE,
+        &[(
+            vec![x.clone(), y.clone()],
+            vec![
+                (&x + &y) % (BigUint::from(1u32) << 256),
+                y,
+                BigUint::from(0u32),
+            ],
+        )],
+    );
+}
+
+#[test]
+fn dynamic_jumps() {
+    verify_opcodes(
+        "dynamic_jumps",
+        DYNAMIC_JUMPS,
+        MIN_DEGREE,
+        &[
+            (vec![BigUint::from(5u32)], vec![BigUint::from(6u32)]),
+            (vec![BigUint::from(0u32)], vec![BigUint::from(42u32)]),
+        ],
+    );
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn arithmetic() {
+    let x = (BigUint::from(1u32) << 200) + BigUint::from(12345u32);
+    let y = BigUint::from(1000u32);
+    verify_contract(
+        "arithmetic",
+        ARITHMETIC,
+        MIN_DEGREE,
+        &[word(x.clone()), word(y)].concat(),
+        None,
+    );
+    // Division by zero results in zero.
+    verify_contract(
+        "arithmetic",
+        ARITHMETIC,
+        MIN_DEGREE,
+        &[word(x), word(0u32.into())].concat(),
+        None,
+    );
+}
+
+/// Bitwise operations need the larger degree of the Binary machine.
+#[test]
+fn bitwise() {
+    let x = (BigUint::from(0xf0f0u32) << 240) + BigUint::from(0xff00ff00u32);
+    let y = (BigUint::from(0x0ff0u32) << 240) + BigUint::from(0x0ff00ff0u32);
+    let ones = (BigUint::from(1u32) << 256) - BigUint::from(1u32);
+    verify_opcodes(
+        "bitwise",
+        BITWISE,
+        BINARY_MIN_DEGREE,
+        &[(
+            vec![x.clone(), y.clone()],
+            vec![
+                (BigUint::from(0x00f0u32) << 240) + BigUint::from(0x0f000f00u32),
+                (BigUint::from(0xfff0u32) << 240) + BigUint::from(0xfff0fff0u32),
+                (BigUint::from(0xff00u32) << 240) + BigUint::from(0xf0f0f0f0u32),
+                ones - x,
+            ],
+        )],
+    );
+}
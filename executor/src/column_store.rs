@@ -0,0 +1,305 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use powdr_number::{write_polys_file, DegreeType, FieldElement};
+
+/// A set of columns stored on disk, so that the values of huge traces do not have
+/// to be kept in memory.
+///
+/// Columns are written in chunks of consecutive rows, each chunk in its own file.
+/// Inside a chunk file, the columns are stored one after the other, each in the format
+/// of [write_polys_file]. This way, a single column can be read without reading the values
+/// of the other columns of the chunk.
+///
+/// Columns are read through a [ColumnReader] (see [ColumnStore::column_reader]),
+/// which only loads the accessed values from disk.
+pub struct ColumnStore<T> {
+    dir: PathBuf,
+    /// The names of the columns declared via [ColumnStore::declare_columns].
+    declared_columns: Vec<String>,
+    chunks: Vec<Chunk>,
+    _marker: PhantomData<T>,
+}
+
+/// A chunk of consecutive rows of some columns.
+struct Chunk {
+    file: PathBuf,
+    /// The index of the first row of the chunk.
+    start: DegreeType,
+    /// The number of rows in the chunk.
+    len: DegreeType,
+    columns: Vec<String>,
+}
+
+impl<T: FieldElement> ColumnStore<T> {
+    /// Creates an empty store, writing its chunks to the given directory.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            declared_columns: vec![],
+            chunks: vec![],
+            _marker: PhantomData,
+        })
+    }
+
+    /// Declares the columns of the store, which determines the order of [ColumnStore::column_names].
+    /// This is useful if the columns are not written in order.
+    pub fn declare_columns(&mut self, names: impl IntoIterator<Item = String>) {
+        self.declared_columns = names.into_iter().collect();
+    }
+
+    /// Writes the values of the given columns, starting at row `start`, to a new chunk file.
+    /// All columns need to have the same number of values.
+    pub fn write_chunk(&mut self, start: DegreeType, columns: &[(String, Vec<T>)]) {
+        let Some((_, first)) = columns.first() else {
+            return;
+        };
+        let len = first.len() as DegreeType;
+        if len == 0 {
+            return;
+        }
+        let file = self.dir.join(format!("chunk_{}.bin", self.chunks.len()));
+        let mut writer = BufWriter::new(File::create(&file).unwrap());
+        for column in columns {
+            assert_eq!(
+                column.1.len() as DegreeType,
+                len,
+                "All columns of a chunk need to have the same length."
+            );
+            write_polys_file(&mut writer, std::slice::from_ref(column));
+        }
+        writer.flush().unwrap();
+        self.chunks.push(Chunk {
+            file,
+            start,
+            len,
+            columns: columns.iter().map(|(name, _)| name.clone()).collect(),
+        });
+    }
+
+    /// @returns the names of all declared columns, followed by the names of all other stored
+    /// columns in the order they were first written.
+    pub fn column_names(&self) -> Vec<String> {
+        let mut names = self.declared_columns.clone();
+        for name in self.chunks.iter().flat_map(|chunk| &chunk.columns) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+
+    /// @returns the number of rows of the given column.
+    pub fn column_len(&self, name: &str) -> DegreeType {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.columns.iter().any(|c| c == name))
+            .map(|chunk| chunk.len)
+            .sum()
+    }
+
+    /// Reads the values of a single column from disk.
+    /// Panics if the column does not exist or if its chunks do not cover consecutive rows.
+    pub fn read_column(&self, name: &str) -> Vec<T> {
+        self.column_reader(name).into_values().collect()
+    }
+
+    /// Opens the chunk files of a single column, so that its values can be
+    /// accessed without reading the whole column.
+    /// Panics if the column does not exist or if its chunks do not cover consecutive rows.
+    pub fn column_reader(&self, name: &str) -> ColumnReader<T> {
+        let width = value_width::<T>();
+        let mut chunks = self
+            .chunks
+            .iter()
+            .filter_map(|chunk| {
+                let index = chunk.columns.iter().position(|c| c == name)?;
+                Some((chunk, index))
+            })
+            .collect::<Vec<_>>();
+        assert!(!chunks.is_empty(), "Column {name} not found in store.");
+        chunks.sort_by_key(|(chunk, _)| chunk.start);
+
+        let mut column = ColumnReader {
+            chunks: vec![],
+            _marker: PhantomData,
+        };
+        for (chunk, index) in chunks {
+            assert_eq!(
+                chunk.start,
+                column.len() as DegreeType,
+                "Rows {}..{} of column {name} are missing.",
+                column.len(),
+                chunk.start
+            );
+            column.chunks.push(ColumnChunk {
+                start: chunk.start as usize,
+                len: chunk.len as usize,
+                offset: (index * chunk.len as usize * width) as u64,
+                path: chunk.file.clone(),
+                file: File::open(&chunk.file).unwrap(),
+            });
+        }
+        column
+    }
+
+    /// Reads the given columns from disk, one column at a time.
+    pub fn read_columns<'a>(
+        &'a self,
+        names: impl IntoIterator<Item = &'a str> + 'a,
+    ) -> impl Iterator<Item = (String, Vec<T>)> + 'a {
+        names
+            .into_iter()
+            .map(|name| (name.to_string(), self.read_column(name)))
+    }
+}
+
+/// The number of bytes of a value in the files of [write_polys_file].
+fn value_width<T: FieldElement>() -> usize {
+    (T::BITS as usize).div_ceil(64) * 8
+}
+
+/// A column of a [ColumnStore], whose values are read from its chunk files on demand.
+pub struct ColumnReader<T> {
+    /// The chunks of the column, in the order of their rows.
+    chunks: Vec<ColumnChunk>,
+    _marker: PhantomData<T>,
+}
+
+struct ColumnChunk {
+    /// The index of the first row of the chunk.
+    start: usize,
+    /// The number of rows in the chunk.
+    len: usize,
+    /// The position of the values of the column in the chunk file.
+    offset: u64,
+    path: PathBuf,
+    /// The chunk file, opened for random accesses.
+    file: File,
+}
+
+impl ColumnChunk {
+    /// @returns a reader of the values of the column in this chunk.
+    /// It opens the chunk file again, so that it does not interfere with random accesses.
+    fn values(&self) -> impl Read {
+        let mut file = File::open(&self.path).unwrap();
+        file.seek(SeekFrom::Start(self.offset)).unwrap();
+        BufReader::new(file)
+    }
+}
+
+impl<T: FieldElement> ColumnReader<T> {
+    /// @returns the number of rows of the column.
+    pub fn len(&self) -> usize {
+        self.chunks
+            .last()
+            .map_or(0, |chunk| chunk.start + chunk.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// @returns the value of the column in the given row.
+    pub fn get(&self, row: usize) -> T {
+        assert!(row < self.len(), "Row {row} is out of range.");
+        let chunk = &self.chunks[self.chunks.partition_point(|chunk| chunk.start <= row) - 1];
+        let width = value_width::<T>();
+        let mut file = &chunk.file;
+        let mut bytes = vec![0; width];
+        file.seek(SeekFrom::Start(
+            chunk.offset + ((row - chunk.start) * width) as u64,
+        ))
+        .unwrap();
+        file.read_exact(&mut bytes).unwrap();
+        T::from_bytes_le(&bytes)
+    }
+
+    /// @returns the values of the column, in the order of their rows.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| read_values(chunk.values(), chunk.len))
+    }
+
+    /// @returns the values of the column, in the order of their rows, without borrowing it.
+    pub fn into_values(self) -> impl Iterator<Item = T> {
+        self.chunks
+            .into_iter()
+            .flat_map(|chunk| read_values(chunk.values(), chunk.len))
+    }
+}
+
+/// Reads `len` values from the given reader.
+fn read_values<T: FieldElement>(mut reader: impl Read, len: usize) -> impl Iterator<Item = T> {
+    let mut bytes = vec![0; value_width::<T>()];
+    (0..len).map(move |_| {
+        reader.read_exact(&mut bytes).unwrap();
+        T::from_bytes_le(&bytes)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+
+    use super::*;
+    use test_log::test;
+
+    fn column(name: &str, values: impl IntoIterator<Item = u64>) -> (String, Vec<GoldilocksField>) {
+        (
+            name.to_string(),
+            values.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    #[test]
+    fn write_read() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let mut store = ColumnStore::<GoldilocksField>::new(&dir).unwrap();
+        // Chunks can be written in any order.
+        store.write_chunk(2, &[column("a", [2, 3, 4]), column("b", [12, 13, 14])]);
+        store.write_chunk(0, &[column("a", [0, 1]), column("b", [10, 11])]);
+        store.write_chunk(0, &[column("c", [7, 8])]);
+
+        assert_eq!(store.column_names(), vec!["a", "b", "c"]);
+        store.declare_columns(["c".to_string(), "a".to_string()]);
+        assert_eq!(store.column_names(), vec!["c", "a", "b"]);
+        assert_eq!(store.column_len("a"), 5);
+        assert_eq!(
+            store.read_columns(["b", "c", "a"]).collect::<Vec<_>>(),
+            vec![column("b", 10..15), column("c", [7, 8]), column("a", 0..5)]
+        );
+    }
+
+    #[test]
+    fn column_reader() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let mut store = ColumnStore::<GoldilocksField>::new(&dir).unwrap();
+        store.write_chunk(3, &[column("b", [13, 14]), column("a", [3, 4])]);
+        store.write_chunk(0, &[column("a", [0, 1, 2]), column("b", [10, 11, 12])]);
+
+        let a = store.column_reader("a");
+        assert_eq!(a.len(), 5);
+        assert_eq!(a.get(2), 2.into());
+        assert_eq!(a.get(3), 3.into());
+        assert_eq!(store.column_reader("b").get(4), 14.into());
+        // Random accesses do not interfere with iteration.
+        let values = a.iter().map(|value| (value, a.get(4))).collect::<Vec<_>>();
+        assert_eq!(values[1], (1.into(), 4.into()));
+        assert_eq!(values.len(), 5);
+        assert_eq!(a.into_values().collect::<Vec<_>>(), column("a", 0..5).1);
+    }
+
+    #[test]
+    #[should_panic = "Rows 0..2 of column a are missing."]
+    fn missing_rows() {
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let mut store = ColumnStore::<GoldilocksField>::new(&dir).unwrap();
+        store.write_chunk(2, &[column("a", [2, 3])]);
+        store.read_column("a");
+    }
+}
@@ -12,7 +12,10 @@ use powdr_ast::{
     },
 };
 use powdr_number::{BigInt, DegreeType, FieldElement};
-use powdr_pil_analyzer::evaluator::{self, Definitions, SymbolLookup, Value};
+use powdr_pil_analyzer::{
+    compiled_evaluator,
+    evaluator::{self, Definitions, SymbolLookup, Value},
+};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
 /// Generates the fixed column values for all fixed columns that are defined
@@ -20,15 +23,35 @@ use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 /// @returns the names (in source order) and the values for the columns.
 /// Arrays of columns are flattened, the name of the `i`th array element
 /// is `name[i]`.
+/// Each column has the degree of the namespace it is declared in.
 pub fn generate<T: FieldElement>(analyzed: &Analyzed<T>) -> Vec<(String, Vec<T>)> {
+    generate_with_known(analyzed, &[])
+}
+
+/// Like [generate], but takes the values of the columns in `known` instead of
+/// evaluating them again, as long as they have the degree of their namespace.
+/// The known columns need to have the same definitions in `analyzed` as in the
+/// PIL they were generated from, e.g. because `analyzed` only adds columns to it.
+pub fn generate_with_known<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    known: &[(String, Vec<T>)],
+) -> Vec<(String, Vec<T>)> {
+    let known = known
+        .iter()
+        .map(|(name, values)| (name.as_str(), values))
+        .collect::<HashMap<_, _>>();
     let mut fixed_cols = HashMap::new();
     for (poly, value) in analyzed.constant_polys_in_source_order() {
         if let Some(value) = value {
+            let degree = poly.degree.unwrap_or_else(|| analyzed.degree.unwrap());
             // For arrays, generate values for each index,
             // for non-arrays, set index to None.
             for (index, (name, id)) in poly.array_elements().enumerate() {
                 let index = poly.is_array().then_some(index as u64);
-                let values = generate_values(analyzed, analyzed.degree(), &name, value, index);
+                let values = match known.get(name.as_str()) {
+                    Some(values) if values.len() as DegreeType == degree => (*values).clone(),
+                    _ => generate_values(analyzed, degree, &name, value, index),
+                };
                 assert!(fixed_cols.insert(name, (id, values)).is_none());
             }
         }
@@ -76,16 +99,24 @@ fn generate_values<T: FieldElement>(
             } else {
                 e
             };
+            // Compile the function once instead of interpreting it on every row.
+            let compiled = compiled_evaluator::try_compile(e, &analyzed.definitions);
+            // Otherwise, at least evaluate the expression to a function only once, so that
+            // for example arrays of columns are not constructed again for every row.
+            let fun = compiled
+                .is_none()
+                .then(|| evaluator::evaluate(e, &mut symbols.clone()).unwrap());
             (0..degree)
                 .into_par_iter()
                 .map(|i| {
                     let mut symbols = symbols.clone();
-                    let fun = evaluator::evaluate(e, &mut symbols).unwrap();
-                    evaluator::evaluate_function_call(
-                        fun,
-                        vec![Arc::new(Value::Integer(BigInt::from(i)))],
-                        &mut symbols,
-                    )
+                    let arguments = vec![Arc::new(Value::Integer(BigInt::from(i)))];
+                    if let Some(compiled) = &compiled {
+                        compiled.call(arguments, &mut symbols)
+                    } else {
+                        let fun = fun.clone().unwrap();
+                        evaluator::evaluate_function_call(fun, arguments, &mut symbols)
+                    }
                     .and_then(|v| v.try_to_field_element())
                 })
                 .collect::<Result<Vec<_>, _>>()
@@ -119,7 +150,9 @@ fn generate_values<T: FieldElement>(
                 })
         }
         FunctionValueDefinition::TypeDeclaration(_)
-        | FunctionValueDefinition::TypeConstructor(_, _) => panic!(),
+        | FunctionValueDefinition::TypeConstructor(_, _)
+        | FunctionValueDefinition::TraitDeclaration(_, _)
+        | FunctionValueDefinition::TraitFunction(_, _) => panic!(),
     };
     match result {
         Err(err) => {
@@ -581,6 +614,30 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn known_columns() {
+        let src = r#"
+            namespace F(4);
+            col fixed A(i) { i };
+            col fixed B(i) { i * i };
+            col fixed C(i) { i + 1 };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        // Known columns are not evaluated again, unless they have the wrong length.
+        let known = vec![
+            ("F.A".to_string(), convert(vec![7, 7, 7, 7])),
+            ("F.C".to_string(), convert(vec![7, 7])),
+        ];
+        assert_eq!(
+            generate_with_known(&analyzed, &known),
+            vec![
+                ("F.A".to_string(), convert(vec![7, 7, 7, 7])),
+                ("F.B".to_string(), convert(vec![0, 1, 4, 9])),
+                ("F.C".to_string(), convert(vec![1, 2, 3, 4])),
+            ]
+        );
+    }
+
     #[test]
     pub fn generic_cache() {
         // Tests that the evaluation cache stores symbols with their
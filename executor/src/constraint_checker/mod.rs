@@ -0,0 +1,956 @@
+//! A native checker for PIL constraints: Given the values of all fixed and witness columns,
+//! it evaluates every identity of an [Analyzed] PIL file and reports the rows
+//! in which they are violated.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicUnaryOperator, Analyzed,
+    Identity, IdentityKind, PolyID, PolynomialType, SymbolKind,
+};
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_ast::parsed::SelectedExpressions;
+use powdr_ast::SourceRef;
+use powdr_number::{DegreeType, FieldElement, KnownField};
+
+use crate::witgen::extract_publics;
+
+/// The number of violations after which the checker stops by default.
+pub const DEFAULT_MAX_VIOLATIONS: usize = 10;
+
+/// A violation of an identity in a specific row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation<T> {
+    /// The ID of the violated identity.
+    pub identity_id: u64,
+    /// The location of the identity in the source.
+    pub source: SourceRef,
+    /// The violated identity, rendered as PIL.
+    pub identity: String,
+    /// The row in which the violation was found.
+    pub row: DegreeType,
+    pub kind: ViolationKind<T>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind<T> {
+    /// A polynomial identity evaluates to the given non-zero value.
+    NonZero(T),
+    /// The tuple on the left-hand side of a lookup does not occur on the right-hand side.
+    NotInLookup(Vec<T>),
+    /// The tuple occurs a different number of times on both sides of a permutation.
+    PermutationMismatch {
+        values: Vec<T>,
+        left_count: usize,
+        right_count: usize,
+    },
+    /// A cell of a connect identity differs from the cell it is connected to.
+    ConnectMismatch {
+        column: usize,
+        value: T,
+        connected_column: usize,
+        connected_row: DegreeType,
+        connected_value: T,
+    },
+    /// The connection column contains a value that does not encode any cell.
+    InvalidConnection { column: usize, label: T },
+}
+
+/// The reason why a witness was rejected by [ConstraintChecker::check].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError<T> {
+    /// An identity references a column or challenge without a value, so it cannot be checked.
+    MissingInput { identity: String, missing: String },
+    /// Values were provided for a column that is not declared in the PIL file.
+    UnknownColumn { column: String },
+    /// The number of values of a column differs from the degree of its namespace.
+    WrongLength {
+        column: String,
+        length: usize,
+        degree: DegreeType,
+    },
+    /// The namespace of a column does not have a degree, and there is no global degree.
+    MissingDegree { column: String },
+    /// An identity cannot be checked, e.g. a connect identity in an unsupported field.
+    Unsupported { identity: String, reason: String },
+    /// The first violated identities (at most `max_violations`).
+    Violations(Vec<Violation<T>>),
+}
+
+impl<T: Display> Display for CheckError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::MissingInput { identity, missing } => {
+                write!(
+                    f,
+                    "Cannot check identity that references {missing}: {identity}"
+                )
+            }
+            CheckError::UnknownColumn { column } => {
+                write!(f, "Values provided for unknown column {column}")
+            }
+            CheckError::WrongLength {
+                column,
+                length,
+                degree,
+            } => write!(
+                f,
+                "Column {column} has {length} values, but the degree is {degree}"
+            ),
+            CheckError::MissingDegree { column } => {
+                write!(f, "The degree of column {column} is unknown")
+            }
+            CheckError::Unsupported { identity, reason } => {
+                write!(f, "Cannot check identity: {reason}: {identity}")
+            }
+            CheckError::Violations(violations) => write!(
+                f,
+                "Found {} violation(s), the first one is:\n{}",
+                violations.len(),
+                violations[0]
+            ),
+        }
+    }
+}
+
+impl<T: Display> Display for Violation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: identity is not satisfied in row {}: {}\n    {}",
+            self.source, self.row, self.kind, self.identity
+        )
+    }
+}
+
+impl<T: Display> Display for ViolationKind<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::NonZero(value) => write!(f, "evaluates to {value} instead of zero"),
+            ViolationKind::NotInLookup(values) => write!(
+                f,
+                "({}) does not occur on the right-hand side",
+                values.iter().format(", ")
+            ),
+            ViolationKind::PermutationMismatch {
+                values,
+                left_count,
+                right_count,
+            } => write!(
+                f,
+                "({}) occurs {left_count} times on the left-hand side but {right_count} times on the right-hand side",
+                values.iter().format(", ")
+            ),
+            ViolationKind::ConnectMismatch {
+                column,
+                value,
+                connected_column,
+                connected_row,
+                connected_value,
+            } => write!(
+                f,
+                "value {value} in column {column} differs from value {connected_value} in column {connected_column}, row {connected_row}"
+            ),
+            ViolationKind::InvalidConnection { column, label } => write!(
+                f,
+                "connection value {label} in column {column} does not refer to any cell"
+            ),
+        }
+    }
+}
+
+/// Checks fixed and witness column values against the identities of a PIL file.
+pub struct ConstraintChecker<'a, T> {
+    analyzed: &'a Analyzed<T>,
+    fixed_col_values: &'a [(String, Vec<T>)],
+    witness: &'a [(String, Vec<T>)],
+    challenges: BTreeMap<u64, T>,
+    max_violations: usize,
+}
+
+impl<'a, T: FieldElement> ConstraintChecker<'a, T> {
+    pub fn new(
+        analyzed: &'a Analyzed<T>,
+        fixed_col_values: &'a [(String, Vec<T>)],
+        witness: &'a [(String, Vec<T>)],
+    ) -> Self {
+        ConstraintChecker {
+            analyzed,
+            fixed_col_values,
+            witness,
+            challenges: BTreeMap::new(),
+            max_violations: DEFAULT_MAX_VIOLATIONS,
+        }
+    }
+
+    /// Sets the values of the challenges. Checking fails if an identity references
+    /// a challenge without a value.
+    pub fn with_challenges(self, challenges: BTreeMap<u64, T>) -> Self {
+        ConstraintChecker { challenges, ..self }
+    }
+
+    /// Sets the number of violations after which checking stops.
+    pub fn with_max_violations(self, max_violations: usize) -> Self {
+        ConstraintChecker {
+            max_violations,
+            ..self
+        }
+    }
+
+    /// Checks all identities in source order.
+    /// @returns the first violations found (at most `max_violations`), if any, or an error
+    /// if the values do not fit the PIL file or an identity cannot be checked.
+    pub fn check(self) -> Result<(), CheckError<T>> {
+        let evaluator = self.evaluator()?;
+
+        let mut violations = vec![];
+        for identity in self
+            .analyzed
+            .identities_with_inlined_intermediate_polynomials()
+        {
+            let remaining = self.max_violations.saturating_sub(violations.len());
+            if remaining == 0 {
+                break;
+            }
+            if let Some(missing) = evaluator.missing_input(&identity) {
+                return Err(CheckError::MissingInput {
+                    identity: identity.to_string(),
+                    missing,
+                });
+            }
+            let failures = match identity.kind {
+                IdentityKind::Polynomial => evaluator.check_polynomial_identity(&identity),
+                IdentityKind::Plookup => evaluator.check_lookup(&identity),
+                IdentityKind::Permutation => evaluator.check_permutation(&identity),
+                IdentityKind::Connect => evaluator.check_connect(&identity).map_err(|reason| {
+                    CheckError::Unsupported {
+                        identity: identity.to_string(),
+                        reason,
+                    }
+                })?,
+            };
+            violations.extend(failures.take(remaining).map(|(row, kind)| Violation {
+                identity_id: identity.id,
+                source: identity.source.clone(),
+                identity: identity.to_string(),
+                row,
+                kind,
+            }));
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(CheckError::Violations(violations))
+        }
+    }
+
+    /// Computes the values of the multiplicity column of the plookup identity with the given ID,
+    /// i.e. how often the tuple in each row of the right side is looked up by the left side.
+    /// If a tuple occurs in several rows of the right side, only the first one is counted.
+    /// Fails if a tuple of the left side does not occur on the right side.
+    pub fn lookup_multiplicities(&self, identity_id: u64) -> Result<Vec<T>, String> {
+        let identity = self
+            .analyzed
+            .identities_with_inlined_intermediate_polynomials()
+            .into_iter()
+            .find(|identity| identity.id == identity_id && identity.kind == IdentityKind::Plookup)
+            .ok_or_else(|| format!("No plookup identity with ID {identity_id}"))?;
+        let evaluator = self.evaluator().map_err(|e| e.to_string())?;
+
+        let right = &identity.right;
+        let degree = evaluator.degree_of(right.selector.iter().chain(&right.expressions));
+        let mut rows = HashMap::new();
+        for (row, values) in evaluator.selected_tuples(right) {
+            rows.entry(values).or_insert(row);
+        }
+        let mut multiplicities = vec![T::zero(); degree as usize];
+        for (row, values) in evaluator.selected_tuples(&identity.left) {
+            let Some(right_row) = rows.get(&values) else {
+                return Err(format!(
+                    "Values ({}) of row {row} not found on the right side of {identity}",
+                    values.iter().format(", ")
+                ));
+            };
+            multiplicities[*right_row as usize] += T::one();
+        }
+        Ok(multiplicities)
+    }
+
+    fn evaluator(&self) -> Result<RowEvaluator<'a, '_, T>, CheckError<T>> {
+        Ok(RowEvaluator {
+            // Expressions that do not reference any column have the same value in every
+            // row, so it is enough to check a single row without a global degree.
+            degree: self.analyzed.degree.unwrap_or(1),
+            columns: self.columns_by_id()?,
+            publics: extract_publics(self.witness, self.analyzed)
+                .into_iter()
+                .collect(),
+            challenges: &self.challenges,
+        })
+    }
+
+    /// @returns the values of all columns by their ID. Columns of namespaces
+    /// without a degree have the global degree, if any.
+    fn columns_by_id(&self) -> Result<HashMap<PolyID, &'a [T]>, CheckError<T>> {
+        let ids_by_name = self
+            .analyzed
+            .definitions
+            .values()
+            .filter(|(symbol, _)| {
+                matches!(
+                    symbol.kind,
+                    SymbolKind::Poly(PolynomialType::Committed | PolynomialType::Constant)
+                )
+            })
+            .flat_map(|(symbol, _)| {
+                let degree = symbol.degree.or(self.analyzed.degree);
+                symbol
+                    .array_elements()
+                    .map(move |(name, id)| (name, (id, degree)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.fixed_col_values
+            .iter()
+            .chain(self.witness)
+            .map(|(name, values)| {
+                let (id, degree) =
+                    ids_by_name
+                        .get(name)
+                        .ok_or_else(|| CheckError::UnknownColumn {
+                            column: name.clone(),
+                        })?;
+                let degree = degree.ok_or_else(|| CheckError::MissingDegree {
+                    column: name.clone(),
+                })?;
+                if values.len() as DegreeType != degree {
+                    return Err(CheckError::WrongLength {
+                        column: name.clone(),
+                        length: values.len(),
+                        degree,
+                    });
+                }
+                Ok((*id, values.as_slice()))
+            })
+            .collect()
+    }
+}
+
+struct RowEvaluator<'a, 'b, T> {
+    /// The degree of expressions that do not reference any column.
+    degree: DegreeType,
+    columns: HashMap<PolyID, &'a [T]>,
+    publics: HashMap<String, T>,
+    challenges: &'b BTreeMap<u64, T>,
+}
+
+type Failures<'c, T> = Box<dyn Iterator<Item = (DegreeType, ViolationKind<T>)> + 'c>;
+
+impl<'a, 'b, T: FieldElement> RowEvaluator<'a, 'b, T> {
+    /// @returns a description of the first column or challenge referenced by the identity
+    /// for which no value is available.
+    fn missing_input(&self, identity: &Identity<Expression<T>>) -> Option<String> {
+        identity.all_children().find_map(|e| match e {
+            Expression::Reference(r) if !self.columns.contains_key(&r.poly_id) => {
+                Some(format!("column {} without values", r.name))
+            }
+            Expression::Challenge(c) if !self.challenges.contains_key(&c.id) => {
+                Some(format!("challenge {} without value", c.id))
+            }
+            _ => None,
+        })
+    }
+
+    /// @returns the degree of the columns referenced by the given expressions.
+    /// The analyzer makes sure that they all have the same degree.
+    fn degree_of<'c>(&self, expressions: impl IntoIterator<Item = &'c Expression<T>>) -> DegreeType
+    where
+        T: 'c,
+    {
+        expressions
+            .into_iter()
+            .flat_map(|e| e.all_children())
+            .find_map(|e| match e {
+                Expression::Reference(r) => Some(self.columns[&r.poly_id].len() as DegreeType),
+                _ => None,
+            })
+            .unwrap_or(self.degree)
+    }
+
+    fn evaluate(&self, expr: &Expression<T>, row: DegreeType) -> T {
+        match expr {
+            Expression::Reference(r) => {
+                let column = self.columns[&r.poly_id];
+                let row = if r.next {
+                    (row as usize + 1) % column.len()
+                } else {
+                    row as usize
+                };
+                column[row]
+            }
+            Expression::PublicReference(name) => self.publics[name],
+            Expression::Challenge(c) => self.challenges[&c.id],
+            Expression::Number(n) => *n,
+            Expression::BinaryOperation(left, op, right) => {
+                let left = self.evaluate(left, row);
+                let right = self.evaluate(right, row);
+                match op {
+                    AlgebraicBinaryOperator::Add => left + right,
+                    AlgebraicBinaryOperator::Sub => left - right,
+                    AlgebraicBinaryOperator::Mul => left * right,
+                    AlgebraicBinaryOperator::Pow => left.pow(right.to_integer()),
+                }
+            }
+            Expression::UnaryOperation(AlgebraicUnaryOperator::Minus, e) => -self.evaluate(e, row),
+        }
+    }
+
+    /// Evaluates the selected expressions in all rows where the selector is non-zero.
+    fn selected_tuples<'c>(
+        &'c self,
+        selected: &'c SelectedExpressions<Expression<T>>,
+    ) -> impl Iterator<Item = (DegreeType, Vec<T>)> + 'c {
+        let degree = self.degree_of(selected.selector.iter().chain(&selected.expressions));
+        (0..degree).filter_map(move |row| {
+            let active = selected
+                .selector
+                .as_ref()
+                .map(|s| !self.evaluate(s, row).is_zero())
+                .unwrap_or(true);
+            active.then(|| {
+                let values = selected
+                    .expressions
+                    .iter()
+                    .map(|e| self.evaluate(e, row))
+                    .collect();
+                (row, values)
+            })
+        })
+    }
+
+    fn check_polynomial_identity<'c>(
+        &'c self,
+        identity: &'c Identity<Expression<T>>,
+    ) -> Failures<'c, T> {
+        let expr = identity.expression_for_poly_id();
+        Box::new((0..self.degree_of([expr])).filter_map(move |row| {
+            let value = self.evaluate(expr, row);
+            (!value.is_zero()).then_some((row, ViolationKind::NonZero(value)))
+        }))
+    }
+
+    fn check_lookup<'c>(&'c self, identity: &'c Identity<Expression<T>>) -> Failures<'c, T> {
+        let right = self
+            .selected_tuples(&identity.right)
+            .map(|(_, values)| values)
+            .collect::<HashSet<_>>();
+        Box::new(
+            self.selected_tuples(&identity.left)
+                .filter(move |(_, values)| !right.contains(values))
+                .map(|(row, values)| (row, ViolationKind::NotInLookup(values))),
+        )
+    }
+
+    fn check_permutation<'c>(&'c self, identity: &'c Identity<Expression<T>>) -> Failures<'c, T> {
+        // Maps each tuple to its number of occurrences on each side and the first row it occurs in.
+        let mut counts: HashMap<Vec<T>, (usize, usize, DegreeType)> = HashMap::new();
+        for (row, values) in self.selected_tuples(&identity.left) {
+            counts.entry(values).or_insert((0, 0, row)).0 += 1;
+        }
+        for (row, values) in self.selected_tuples(&identity.right) {
+            counts.entry(values).or_insert((0, 0, row)).1 += 1;
+        }
+        Box::new(
+            counts
+                .into_iter()
+                .filter(|(_, (left_count, right_count, _))| left_count != right_count)
+                .sorted_by_key(|(_, (_, _, row))| *row)
+                .map(|(values, (left_count, right_count, row))| {
+                    (
+                        row,
+                        ViolationKind::PermutationMismatch {
+                            values,
+                            left_count,
+                            right_count,
+                        },
+                    )
+                }),
+        )
+    }
+
+    /// Fails if the cells of the connect identity cannot be labeled.
+    fn check_connect<'c>(
+        &'c self,
+        identity: &'c Identity<Expression<T>>,
+    ) -> Result<Failures<'c, T>, String> {
+        let columns = &identity.left.expressions;
+        let connections = &identity.right.expressions;
+        assert_eq!(columns.len(), connections.len());
+        let degree = self.degree_of(columns);
+        let labels = connect_cell_labels::<T>(columns.len(), degree)?;
+        let cells_by_label = labels
+            .iter()
+            .enumerate()
+            .flat_map(|(column, labels)| {
+                labels
+                    .iter()
+                    .enumerate()
+                    .map(move |(row, label)| (*label, (column, row as DegreeType)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        Ok(Box::new((0..degree).flat_map(move |row| {
+            (0..columns.len())
+                .filter_map(|column| {
+                    let value = self.evaluate(&columns[column], row);
+                    let label = self.evaluate(&connections[column], row);
+                    let Some((connected_column, connected_row)) = cells_by_label.get(&label) else {
+                        return Some((row, ViolationKind::InvalidConnection { column, label }));
+                    };
+                    let connected_value =
+                        self.evaluate(&columns[*connected_column], *connected_row);
+                    (value != connected_value).then_some((
+                        row,
+                        ViolationKind::ConnectMismatch {
+                            column,
+                            value,
+                            connected_column: *connected_column,
+                            connected_row: *connected_row,
+                            connected_value,
+                        },
+                    ))
+                })
+                .collect::<Vec<_>>()
+        })))
+    }
+}
+
+/// A generator of the multiplicative subgroup of order 2^32 of the Goldilocks field.
+pub const GOLDILOCKS_ROOT_OF_UNITY_2_32: u64 = 1753635133440165772;
+/// The coset shift pil-stark uses to separate the columns of a connect identity.
+const GOLDILOCKS_CONNECT_COSET_SHIFT: u64 = 12275445934081160404;
+/// A generator of the multiplicative subgroup of order 2^28 of the Bn254 scalar field.
+const BN254_ROOT_OF_UNITY_2_28: &str =
+    "19103219067921713944291392827692070036145651957329286315305642004821462161904";
+/// The coset shift used to separate the columns of a connect identity over Bn254:
+/// the multiplicative generator of the field.
+const BN254_CONNECT_COSET_SHIFT: u64 = 7;
+
+/// Returns, for each of the `column_count` columns of a connect identity, the labels
+/// of its cells as expected in the connection (fixed) columns.
+/// We use the same encoding as pil-stark: Cell `(column, row)` is labeled `k^column * w^row`,
+/// where `w` is a root of unity of order `degree` and `k` is a coset shift.
+/// Fails for fields other than Goldilocks and Bn254 and for unsupported degrees.
+pub fn connect_cell_labels<T: FieldElement>(
+    column_count: usize,
+    degree: DegreeType,
+) -> Result<Vec<Vec<T>>, String> {
+    let (max_root_of_unity, two_adicity, shift) = match T::known_field() {
+        Some(KnownField::GoldilocksField) => (
+            T::from(GOLDILOCKS_ROOT_OF_UNITY_2_32),
+            32,
+            T::from(GOLDILOCKS_CONNECT_COSET_SHIFT),
+        ),
+        Some(KnownField::Bn254Field) => (
+            T::from_str_radix(BN254_ROOT_OF_UNITY_2_28, 10).unwrap(),
+            28,
+            T::from(BN254_CONNECT_COSET_SHIFT),
+        ),
+        None => return Err("Connect identities are not supported for this field".to_string()),
+    };
+    if !(degree.is_power_of_two() && degree <= 1 << two_adicity) {
+        return Err(format!(
+            "Degree {degree} is not a power of two up to 2^{two_adicity}"
+        ));
+    }
+    let root_of_unity = max_root_of_unity.pow(((1u64 << two_adicity) / degree).into());
+    Ok((0..column_count)
+        .scan(T::one(), |column_shift, _| {
+            let labels = (0..degree)
+                .scan(*column_shift, |label, _| {
+                    let current = *label;
+                    *label = current * root_of_unity;
+                    Some(current)
+                })
+                .collect::<Vec<_>>();
+            *column_shift = *column_shift * shift;
+            Some(labels)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::{Bn254Field, GoldilocksField};
+    use powdr_pil_analyzer::analyze_string;
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+
+    use super::*;
+
+    fn convert(input: Vec<i32>) -> Vec<GoldilocksField> {
+        input.into_iter().map(|x| x.into()).collect()
+    }
+
+    fn check(
+        src: &str,
+        fixed: Vec<(&str, Vec<GoldilocksField>)>,
+        witness: Vec<(&str, Vec<GoldilocksField>)>,
+    ) -> Result<(), Vec<Violation<GoldilocksField>>> {
+        let analyzed = analyze_string(src);
+        let fixed = fixed
+            .into_iter()
+            .map(|(n, v)| (n.to_string(), v))
+            .collect::<Vec<_>>();
+        let witness = witness
+            .into_iter()
+            .map(|(n, v)| (n.to_string(), v))
+            .collect::<Vec<_>>();
+        ConstraintChecker::new(&analyzed, &fixed, &witness)
+            .check()
+            .map_err(|e| match e {
+                CheckError::Violations(violations) => violations,
+                e => panic!("{e}"),
+            })
+    }
+
+    #[test]
+    fn root_of_unity() {
+        let root = GoldilocksField::from(GOLDILOCKS_ROOT_OF_UNITY_2_32);
+        assert_eq!(root.pow((1u64 << 31).into()), -GoldilocksField::from(1));
+    }
+
+    #[test]
+    fn fibonacci() {
+        let src = r#"
+            namespace F(4);
+            col fixed ISLAST = [0, 0, 0, 1];
+            col witness x, y;
+            ISLAST * (y' - 1) = 0;
+            ISLAST * (x' - 1) = 0;
+            (1 - ISLAST) * (x' - y) = 0;
+            (1 - ISLAST) * (y' - (x + y)) = 0;
+        "#;
+        let fixed = vec![("F.ISLAST", convert(vec![0, 0, 0, 1]))];
+        let witness = |y: Vec<i32>| vec![("F.x", convert(vec![1, 1, 2, 3])), ("F.y", convert(y))];
+        assert_eq!(check(src, fixed.clone(), witness(vec![1, 2, 3, 5])), Ok(()));
+
+        let violations = check(src, fixed, witness(vec![1, 2, 4, 5])).unwrap_err();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (v.source.line, v.row, v.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (7, 2, ViolationKind::NonZero((-1).into())),
+                (8, 1, ViolationKind::NonZero(1.into())),
+                (8, 2, ViolationKind::NonZero((-1).into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn wraparound() {
+        let src = r#"
+            namespace F(4);
+            col witness x;
+            x' = x + 1;
+        "#;
+        let violations = check(src, vec![], vec![("F.x", convert(vec![0, 1, 2, 3]))]).unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].row, 3);
+        assert_eq!(violations[0].kind, ViolationKind::NonZero((-4).into()));
+    }
+
+    #[test]
+    fn max_violations() {
+        let src = r#"
+            namespace F(8);
+            col witness x;
+            x = 0;
+            x = 1;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let witness = vec![("F.x".to_string(), convert(vec![1; 8]))];
+        let Err(CheckError::Violations(violations)) =
+            ConstraintChecker::new(&analyzed, &[], &witness)
+                .with_max_violations(3)
+                .check()
+        else {
+            panic!("Expected violations");
+        };
+        assert_eq!(violations.len(), 3);
+        assert!(violations
+            .iter()
+            .all(|v| v.identity_id == violations[0].identity_id));
+    }
+
+    #[test]
+    fn lookup() {
+        let src = r#"
+            namespace F(4);
+            col fixed BYTE(i) { i };
+            col witness sel, x;
+            sel { x } in { BYTE };
+        "#;
+        let fixed = vec![("F.BYTE", convert(vec![0, 1, 2, 3]))];
+        let witness = |x: Vec<i32>| vec![("F.sel", convert(vec![1, 1, 0, 1])), ("F.x", convert(x))];
+        assert_eq!(check(src, fixed.clone(), witness(vec![3, 2, 7, 0])), Ok(()));
+        let violations = check(src, fixed, witness(vec![3, 8, 7, 0])).unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].row, 1);
+        assert_eq!(
+            violations[0].kind,
+            ViolationKind::NotInLookup(convert(vec![8]))
+        );
+    }
+
+    #[test]
+    fn lookup_multiplicities() {
+        let src = r#"
+            namespace F(4);
+            col fixed BYTE(i) { i % 2 };
+            col witness sel, x;
+            sel { x } in { BYTE };
+        "#;
+        let analyzed = analyze_string(src);
+        let fixed = vec![("F.BYTE".to_string(), convert(vec![0, 1, 0, 1]))];
+        let witness = vec![
+            ("F.sel".to_string(), convert(vec![1, 1, 0, 1])),
+            ("F.x".to_string(), convert(vec![1, 0, 7, 1])),
+        ];
+        assert_eq!(
+            ConstraintChecker::new(&analyzed, &fixed, &witness).lookup_multiplicities(0),
+            Ok(convert(vec![1, 2, 0, 0]))
+        );
+
+        let witness = vec![
+            ("F.sel".to_string(), convert(vec![1, 1, 1, 1])),
+            ("F.x".to_string(), convert(vec![1, 0, 7, 1])),
+        ];
+        assert_eq!(
+            ConstraintChecker::new(&analyzed, &fixed, &witness).lookup_multiplicities(0),
+            Err(
+                "Values (7) of row 2 not found on the right side of F.sel { F.x } in { F.BYTE };"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn lookup_between_different_degrees() {
+        let src = r#"
+            namespace Byte(4);
+            col fixed BYTE(i) { i };
+            namespace F(8);
+            col witness x;
+            x' = x + 1;
+            { x } in { Byte.BYTE };
+        "#;
+        let fixed = vec![("Byte.BYTE", convert(vec![0, 1, 2, 3]))];
+        let violations = check(
+            src,
+            fixed,
+            vec![("F.x", convert(vec![0, 1, 2, 3, 4, 5, 6, 7]))],
+        )
+        .unwrap_err();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (v.row, v.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (7, ViolationKind::NonZero((-8).into())),
+                (4, ViolationKind::NotInLookup(convert(vec![4]))),
+                (5, ViolationKind::NotInLookup(convert(vec![5]))),
+                (6, ViolationKind::NotInLookup(convert(vec![6]))),
+                (7, ViolationKind::NotInLookup(convert(vec![7]))),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_challenge() {
+        let src = r#"
+            namespace std::prover(4);
+            let challenge = [];
+            namespace F(4);
+            col witness x;
+            x * std::prover::challenge(0, 1) = 0;
+        "#;
+        let analyzed = analyze_string(src);
+        let witness = vec![("F.x".to_string(), convert(vec![0, 0, 0, 0]))];
+        assert_eq!(
+            ConstraintChecker::new(&analyzed, &[], &witness).check(),
+            Err(CheckError::MissingInput {
+                identity: "(F.x * std::prover::challenge(0, 1)) = 0;".to_string(),
+                missing: "challenge 1 without value".to_string()
+            })
+        );
+        assert_eq!(
+            ConstraintChecker::new(&analyzed, &[], &witness)
+                .with_challenges([(1, 5.into())].into())
+                .check(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let src = r#"
+            namespace F(4);
+            col witness x;
+            x = 0;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let check = |witness: Vec<(String, Vec<GoldilocksField>)>| {
+            ConstraintChecker::new(&analyzed, &[], &witness).check()
+        };
+        assert_eq!(
+            check(vec![("F.y".to_string(), convert(vec![0; 4]))]),
+            Err(CheckError::UnknownColumn {
+                column: "F.y".to_string()
+            })
+        );
+        assert_eq!(
+            check(vec![("F.x".to_string(), convert(vec![0; 3]))]),
+            Err(CheckError::WrongLength {
+                column: "F.x".to_string(),
+                length: 3,
+                degree: 4
+            })
+        );
+
+        let src = r#"
+            namespace F;
+            col witness x;
+            x = 0;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let witness = vec![("F.x".to_string(), convert(vec![0; 4]))];
+        assert_eq!(
+            ConstraintChecker::new(&analyzed, &[], &witness).check(),
+            Err(CheckError::MissingDegree {
+                column: "F.x".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn unsupported_connect() {
+        let src = r#"
+            namespace F(3);
+            col fixed C;
+            col witness a;
+            { a } connect { C };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let fixed = vec![("F.C".to_string(), convert(vec![0; 3]))];
+        let witness = vec![("F.a".to_string(), convert(vec![0; 3]))];
+        assert_eq!(
+            ConstraintChecker::new(&analyzed, &fixed, &witness).check(),
+            Err(CheckError::Unsupported {
+                identity: "{ F.a } connect { F.C };".to_string(),
+                reason: "Degree 3 is not a power of two up to 2^32".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn permutation() {
+        let src = r#"
+            namespace F(4);
+            col witness a, b, sel;
+            { a } is sel { b };
+        "#;
+        let witness = |b: Vec<i32>| {
+            vec![
+                ("F.a", convert(vec![1, 2, 3, 4])),
+                ("F.b", convert(b)),
+                ("F.sel", convert(vec![1; 4])),
+            ]
+        };
+        assert_eq!(check(src, vec![], witness(vec![4, 3, 2, 1])), Ok(()));
+        let violations = check(src, vec![], witness(vec![4, 3, 1, 1])).unwrap_err();
+        assert_eq!(
+            violations.iter().map(|v| &v.kind).collect::<Vec<_>>(),
+            vec![
+                &ViolationKind::PermutationMismatch {
+                    values: convert(vec![1]),
+                    left_count: 1,
+                    right_count: 2
+                },
+                &ViolationKind::PermutationMismatch {
+                    values: convert(vec![2]),
+                    left_count: 1,
+                    right_count: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn connect() {
+        let src = r#"
+            namespace F(4);
+            col fixed C1, C2;
+            col witness a, b;
+            { a, b } connect { C1, C2 };
+        "#;
+        // Connects (a, 0) with (b, 2) and (a, 3) with (b, 1), all other cells are
+        // connected to themselves.
+        let labels = connect_cell_labels::<GoldilocksField>(2, 4).unwrap();
+        let (a, b) = (&labels[0], &labels[1]);
+        let fixed = vec![
+            ("F.C1", vec![b[2], a[1], a[2], b[1]]),
+            ("F.C2", vec![b[0], a[3], a[0], b[3]]),
+        ];
+        let witness = |a: Vec<i32>, b: Vec<i32>| vec![("F.a", convert(a)), ("F.b", convert(b))];
+        assert_eq!(
+            check(
+                src,
+                fixed.clone(),
+                witness(vec![5, 1, 2, 6], vec![0, 6, 5, 3])
+            ),
+            Ok(())
+        );
+        let violations =
+            check(src, fixed, witness(vec![5, 1, 2, 6], vec![0, 7, 5, 3])).unwrap_err();
+        assert_eq!(
+            violations.iter().map(|v| v.row).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(
+            violations[0].kind,
+            ViolationKind::ConnectMismatch {
+                column: 1,
+                value: 7.into(),
+                connected_column: 0,
+                connected_row: 3,
+                connected_value: 6.into()
+            }
+        );
+    }
+
+    #[test]
+    fn connect_cell_labels_bn254() {
+        let labels = connect_cell_labels::<Bn254Field>(3, 8).unwrap();
+        assert_eq!(labels[0][0], Bn254Field::from(1));
+        assert_eq!(labels[1][0], 7.into());
+        // The labels are a root of unity of order 8 times distinct coset shifts.
+        let root_of_unity = labels[0][1];
+        assert_eq!(root_of_unity.pow(8u64.into()), Bn254Field::from(1));
+        assert_ne!(root_of_unity.pow(4u64.into()), Bn254Field::from(1));
+        assert_eq!(
+            labels
+                .iter()
+                .flatten()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            24
+        );
+    }
+}
@@ -2,5 +2,7 @@
 
 #![deny(clippy::print_stdout)]
 
+pub mod column_store;
 pub mod constant_evaluator;
+pub mod constraint_checker;
 pub mod witgen;
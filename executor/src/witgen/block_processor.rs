@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use powdr_ast::analyzed::{
-    AlgebraicExpression as Expression, AlgebraicReference, Identity, PolyID,
+    AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
 };
 use powdr_number::FieldElement;
 
@@ -20,13 +20,18 @@ use super::{
 /// - `'b`: The duration of this machine's call (e.g. the mutable references of the other machines)
 /// - `'c`: The duration of this Processor's lifetime (e.g. the reference to the identity processor)
 pub struct BlockProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
+    /// The name of the machine, used in failure reports
+    name: &'c str,
     processor: Processor<'a, 'b, 'c, T, Q>,
     /// The list of identities
     identities: &'c [&'a Identity<Expression<T>>],
+    /// The (row index, identity index) pairs of the lookups that are complete.
+    completed_lookups: HashSet<(usize, usize)>,
 }
 
 impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c, T, Q> {
     pub fn new(
+        name: &'c str,
         row_offset: RowIndex,
         data: FinalizableData<'a, T>,
         mutable_state: &'c mut MutableState<'a, 'b, T, Q>,
@@ -36,18 +41,23 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
     ) -> Self {
         let processor = Processor::new(row_offset, data, mutable_state, fixed_data, witness_cols);
         Self {
+            name,
             processor,
             identities,
+            completed_lookups: HashSet::new(),
         }
     }
 
     pub fn from_processor(
+        name: &'c str,
         processor: Processor<'a, 'b, 'c, T, Q>,
         identities: &'c [&'a Identity<Expression<T>>],
     ) -> Self {
         Self {
+            name,
             processor,
             identities,
+            completed_lookups: HashSet::new(),
         }
     }
 
@@ -61,6 +71,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
 
     /// Figures out unknown values.
     /// Returns the assignments to outer query columns.
+    /// In diagnostic mode, errors are returned as an [EvalError::FailureReport].
     pub fn solve(
         &mut self,
         sequence_iterator: &mut ProcessingSequenceIterator,
@@ -71,21 +82,40 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
             let row_index = (1 + row_delta) as usize;
             let progress = match action {
                 Action::InternalIdentity(identity_index) => {
-                    self.processor
-                        .process_identity(
-                            row_index,
-                            self.identities[identity_index],
-                            UnknownStrategy::Unknown,
-                        )?
-                        .progress
+                    let identity = self.identities[identity_index];
+                    let is_lookup = identity.kind != IdentityKind::Polynomial;
+                    if is_lookup
+                        && self
+                            .completed_lookups
+                            .contains(&(row_index, identity_index))
+                    {
+                        false
+                    } else {
+                        let result = self
+                            .processor
+                            .process_identity(row_index, identity, UnknownStrategy::Unknown)
+                            .map_err(|e| self.failure_report(row_index, Some(identity), e))?;
+                        // Lookups into other machines can have side effects (e.g. a memory
+                        // write) without updating any value, so completing them is progress
+                        // and they are only processed once.
+                        let completed = is_lookup
+                            && result.is_complete
+                            && self.completed_lookups.insert((row_index, identity_index));
+                        result.progress || completed
+                    }
                 }
                 Action::OuterQuery => {
-                    let (progress, new_outer_assignments) =
-                        self.processor.process_outer_query(row_index)?;
+                    let (progress, new_outer_assignments) = self
+                        .processor
+                        .process_outer_query(row_index)
+                        .map_err(|e| self.failure_report(row_index, None, e))?;
                     outer_assignments.extend(new_outer_assignments);
                     progress
                 }
-                Action::ProverQueries => self.processor.process_queries(row_index)?,
+                Action::ProverQueries => self
+                    .processor
+                    .process_queries(row_index)
+                    .map_err(|e| self.failure_report(row_index, None, e))?,
             };
             sequence_iterator.report_progress(progress);
         }
@@ -102,6 +132,32 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
     pub fn finish(self) -> FinalizableData<'a, T> {
         self.processor.finish()
     }
+
+    /// In diagnostic mode, turns an error in the given row into a [FailureReport]
+    /// listing the failed identity (if any) and the identities with unknown cells.
+    /// Otherwise, returns the error unchanged.
+    fn failure_report(
+        &self,
+        row_index: usize,
+        failed_identity: Option<&'a Identity<Expression<T>>>,
+        error: EvalError<T>,
+    ) -> EvalError<T> {
+        if !self.processor.diagnostics() {
+            return error;
+        }
+        let mut report = self.processor.failure_report(
+            self.name,
+            row_index,
+            self.identities.iter().copied(),
+            &[error],
+        );
+        report.blocked_identities.retain(|blocked| {
+            !blocked.unknown_references.is_empty()
+                || failed_identity.map(|identity| identity.id) == Some(blocked.identity_id)
+        });
+        log::error!("\n{report}\n");
+        EvalError::FailureReport(Box::new(report))
+    }
 }
 
 #[cfg(test)]
@@ -184,6 +240,7 @@ mod tests {
         let witness_cols = fixed_data.witness_cols.keys().collect();
 
         let processor = BlockProcessor::new(
+            "Block Machine",
             row_offset,
             data,
             &mut mutable_state,
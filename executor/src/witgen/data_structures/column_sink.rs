@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use powdr_number::{DegreeType, FieldElement};
+
+use crate::column_store::ColumnStore;
+
+/// Receives the witness columns of a machine row by row (or in chunks of rows).
+/// If a witness store is given, the rows are written to it in chunks of the given number
+/// of rows, so that the columns of the machine never have to be kept in memory as a whole.
+/// Otherwise, the columns are collected in memory.
+pub struct ColumnSink<'a, T> {
+    store: Option<(&'a Mutex<ColumnStore<T>>, usize)>,
+    names: Vec<String>,
+    /// The rows that have not been written to the store yet.
+    columns: Vec<Vec<T>>,
+    /// The index of the first row in `columns`.
+    start: DegreeType,
+}
+
+impl<'a, T: FieldElement> ColumnSink<'a, T> {
+    pub fn new(names: Vec<String>, store: Option<(&'a Mutex<ColumnStore<T>>, usize)>) -> Self {
+        let columns = names.iter().map(|_| vec![]).collect();
+        Self {
+            store,
+            names,
+            columns,
+            start: 0,
+        }
+    }
+
+    /// Writes the given columns to the store (if any) in chunks and returns the columns
+    /// that are still in memory, i.e. all columns without a store and none with a store.
+    pub fn write_all(
+        columns: HashMap<String, Vec<T>>,
+        store: Option<(&'a Mutex<ColumnStore<T>>, usize)>,
+    ) -> HashMap<String, Vec<T>> {
+        if store.is_none() {
+            return columns;
+        }
+        let (names, columns): (Vec<_>, Vec<_>) = columns.into_iter().unzip();
+        let mut sink = Self::new(names, store);
+        sink.extend(columns);
+        sink.finish()
+    }
+
+    /// Appends a row, given by the values of the columns in the order of their names.
+    pub fn push_row(&mut self, row: impl IntoIterator<Item = T>) {
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+        self.write_full_chunks();
+    }
+
+    /// Appends the given rows, given as columns in the order of their names.
+    pub fn extend(&mut self, columns: Vec<Vec<T>>) {
+        for (column, values) in self.columns.iter_mut().zip(columns) {
+            if column.is_empty() {
+                *column = values;
+            } else {
+                column.extend(values);
+            }
+        }
+        self.write_full_chunks();
+    }
+
+    /// Writes the remaining rows to the store (if any) and returns the columns that are
+    /// still in memory, i.e. all columns without a store and none with a store.
+    pub fn finish(mut self) -> HashMap<String, Vec<T>> {
+        match self.store {
+            Some((store, _)) => {
+                let columns = self.take_rows(self.len());
+                store.lock().unwrap().write_chunk(self.start, &columns);
+                HashMap::new()
+            }
+            None => self.names.into_iter().zip(self.columns).collect(),
+        }
+    }
+
+    /// The number of rows that have not been written to the store yet.
+    fn len(&self) -> usize {
+        self.columns.first().map_or(0, Vec::len)
+    }
+
+    fn write_full_chunks(&mut self) {
+        let Some((store, chunk_size)) = self.store else {
+            return;
+        };
+        while self.len() >= chunk_size {
+            let columns = self.take_rows(chunk_size);
+            store.lock().unwrap().write_chunk(self.start, &columns);
+            self.start += chunk_size as DegreeType;
+        }
+    }
+
+    /// Removes the first `count` rows and returns them as named columns.
+    fn take_rows(&mut self, count: usize) -> Vec<(String, Vec<T>)> {
+        self.names
+            .iter()
+            .cloned()
+            .zip(self.columns.iter_mut().map(|c| c.drain(..count).collect()))
+            .collect()
+    }
+}
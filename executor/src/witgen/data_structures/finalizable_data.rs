@@ -1,6 +1,6 @@
 use std::{
     collections::HashSet,
-    ops::{Index, IndexMut},
+    ops::{Index, IndexMut, Range},
 };
 
 use bit_vec::BitVec;
@@ -25,12 +25,16 @@ enum Entry<'a, T: FieldElement> {
 /// constraints is freed. The information which cells are known is preserved, though.
 /// Once a row has been finalized, any operation trying to access it again will fail at runtime.
 /// [FinalizableData::take_transposed] can be used to access the final cells.
+/// A range of finalized rows can also be taken out early using
+/// [FinalizableData::take_finalized_rows], which frees their memory entirely.
 #[derive(Clone)]
 pub struct FinalizableData<'a, T: FieldElement> {
-    /// The list of rows (either in progress or finalized)
+    /// The list of rows (either in progress or finalized), except for the taken rows.
     data: Vec<Entry<'a, T>>,
     /// The list of column IDs (in sorted order), used to index finalized rows.
     column_ids: Vec<PolyID>,
+    /// The range of rows that have been taken out by [FinalizableData::take_finalized_rows].
+    taken: Range<usize>,
 }
 
 impl<'a, T: FieldElement> FinalizableData<'a, T> {
@@ -45,11 +49,26 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         let mut column_ids = column_ids.iter().cloned().collect::<Vec<_>>();
         column_ids.sort();
         let data = rows.map(Entry::InProgress).collect::<Vec<_>>();
-        Self { data, column_ids }
+        Self {
+            data,
+            column_ids,
+            taken: 0..0,
+        }
+    }
+
+    /// Returns the index in [FinalizableData::data] of the row with the given index.
+    fn position(&self, i: usize) -> usize {
+        if i < self.taken.start {
+            i
+        } else {
+            assert!(i >= self.taken.end, "Row {i} already taken.");
+            i - self.taken.len()
+        }
     }
 
+    /// Returns the number of rows, including the taken ones.
     pub fn len(&self) -> usize {
-        self.data.len()
+        self.data.len() + self.taken.len()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -69,10 +88,12 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
     }
 
     pub fn extend(&mut self, other: Self) {
+        assert!(other.taken.is_empty());
         self.data.extend(other.data);
     }
 
     pub fn remove(&mut self, i: usize) -> Row<'a, T> {
+        assert!(self.taken.is_empty());
         match self.data.remove(i) {
             Entry::InProgress(row) => row,
             Entry::Finalized(_, _) => panic!("Row {} already finalized.", i),
@@ -80,11 +101,13 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
     }
 
     pub fn truncate(&mut self, len: usize) {
-        self.data.truncate(len);
+        assert!(len >= self.taken.end);
+        self.data.truncate(len - self.taken.len());
     }
 
     pub fn get_mut(&mut self, i: usize) -> Option<&mut Row<'a, T>> {
-        match &mut self.data[i] {
+        let position = self.position(i);
+        match &mut self.data[position] {
             Entry::InProgress(row) => Some(row),
             Entry::Finalized(_, _) => panic!("Row {} already finalized.", i),
         }
@@ -99,7 +122,9 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
     }
 
     pub fn mutable_row_pair(&mut self, i: usize) -> (&mut Row<'a, T>, &mut Row<'a, T>) {
-        let (before, after) = self.data.split_at_mut(i + 1);
+        let position = self.position(i);
+        assert_eq!(self.position(i + 1), position + 1);
+        let (before, after) = self.data.split_at_mut(position + 1);
         let current = before.last_mut().unwrap();
         let next = after.first_mut().unwrap();
         match (current, next) {
@@ -109,13 +134,14 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
     }
 
     pub fn finalize(&mut self, i: usize) -> bool {
-        if let Entry::InProgress(row) = &self.data[i] {
+        let position = self.position(i);
+        if let Entry::InProgress(row) = &self.data[position] {
             let (values, known_cells) = self
                 .column_ids
                 .iter()
                 .map(|c| (row[c].value.unwrap_or_default(), row[c].value.is_known()))
                 .unzip();
-            self.data[i] = Entry::Finalized(values, known_cells);
+            self.data[position] = Entry::Finalized(values, known_cells);
             true
         } else {
             false
@@ -128,11 +154,81 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         }
     }
 
+    /// Returns the number of known cells in all rows that have not been taken.
+    pub fn known_cells_count(&self) -> usize {
+        self.data
+            .iter()
+            .map(|entry| match entry {
+                Entry::InProgress(row) => self
+                    .column_ids
+                    .iter()
+                    .filter(|c| row[c].value.is_known())
+                    .count(),
+                Entry::Finalized(_, known_cells) => known_cells.iter().filter(|k| *k).count(),
+            })
+            .sum()
+    }
+
+    /// Returns the range of rows taken out by [FinalizableData::take_finalized_rows].
+    pub fn taken_rows(&self) -> Range<usize> {
+        self.taken.clone()
+    }
+
+    /// Takes a range of finalized rows out of the [FinalizableData] and returns their values
+    /// as a list of columns. Only one contiguous range of rows can be taken, so the range
+    /// has to start where the previously taken range ended.
+    /// The rows keep their indices, but accessing them again will fail at runtime.
+    pub fn take_finalized_rows(&mut self, range: Range<usize>) -> Vec<(PolyID, Vec<T>)> {
+        if self.taken.is_empty() {
+            self.taken = range.start..range.start;
+        }
+        assert_eq!(range.start, self.taken.end);
+        let position = self.position(range.start);
+        let mut columns = vec![Vec::with_capacity(range.len()); self.column_ids.len()];
+        for (i, row) in self
+            .data
+            .drain(position..position + range.len())
+            .enumerate()
+        {
+            let Entry::Finalized(row, _) = row else {
+                panic!("Row {} not finalized.", range.start + i);
+            };
+            for (column, value) in columns.iter_mut().zip(row) {
+                column.push(value);
+            }
+        }
+        self.taken.end = range.end;
+        self.column_ids.iter().cloned().zip(columns).collect()
+    }
+
     /// Takes all data out of the [FinalizableData] and returns it as a list of columns.
+    /// Taken rows are not included, i.e. the columns continue with the row after the
+    /// taken range.
     /// Columns are represented as a tuple of:
     /// - A list of values
     /// - A bit vector indicating which cells are known. Values of unknown cells should be ignored.
     pub fn take_transposed(&mut self) -> impl Iterator<Item = (PolyID, (Vec<T>, BitVec))> {
+        let chunk_size = self.data.len().max(1);
+        self.take_transposed_chunks(chunk_size)
+            .next()
+            .unwrap_or_else(|| {
+                // Without rows, there is one empty column per column ID.
+                let column_ids = std::mem::take(&mut self.column_ids);
+                column_ids
+                    .into_iter()
+                    .map(|id| (id, (vec![], BitVec::new())))
+                    .collect()
+            })
+            .into_iter()
+    }
+
+    /// Like [FinalizableData::take_transposed], but transposes the rows in chunks of
+    /// `chunk_size` consecutive rows (the last chunk might be shorter), so that the columns
+    /// of all rows never have to be kept in memory at the same time.
+    pub fn take_transposed_chunks(
+        &mut self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Vec<(PolyID, (Vec<T>, BitVec))>> + 'a {
         log::debug!(
             "Transposing {} rows with {} columns...",
             self.data.len(),
@@ -140,40 +236,47 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         );
         log::debug!("Finalizing remaining rows...");
         let mut counter = 0;
-        for i in 0..self.data.len() {
+        for i in (0..self.taken.start).chain(self.taken.end..self.len()) {
             if self.finalize(i) {
                 counter += 1;
             }
         }
         log::debug!("Needed to finalize {} / {} rows.", counter, self.data.len());
 
-        // Store transposed columns in vectors for performance reasons
-        let mut columns = vec![Vec::with_capacity(self.data.len()); self.column_ids.len()];
-        let mut known_cells_col =
-            vec![BitVec::with_capacity(self.data.len()); self.column_ids.len()];
-        for row in std::mem::take(&mut self.data) {
-            match row {
-                Entry::InProgress(_) => unreachable!(),
-                Entry::Finalized(row, known_cells) => {
-                    for (col_index, (value, is_known)) in
-                        row.into_iter().zip(known_cells).enumerate()
-                    {
-                        known_cells_col[col_index].push(is_known);
-                        columns[col_index].push(value);
+        let column_ids = std::mem::take(&mut self.column_ids);
+        let mut rows = std::mem::take(&mut self.data).into_iter().peekable();
+        std::iter::from_fn(move || {
+            rows.peek()?;
+            // Store transposed columns in vectors for performance reasons
+            let capacity = chunk_size.min(rows.len());
+            let mut columns = vec![Vec::with_capacity(capacity); column_ids.len()];
+            let mut known_cells_col = vec![BitVec::with_capacity(capacity); column_ids.len()];
+            for row in rows.by_ref().take(chunk_size) {
+                match row {
+                    Entry::InProgress(_) => unreachable!(),
+                    Entry::Finalized(row, known_cells) => {
+                        for (col_index, (value, is_known)) in
+                            row.into_iter().zip(known_cells).enumerate()
+                        {
+                            known_cells_col[col_index].push(is_known);
+                            columns[col_index].push(value);
+                        }
                     }
                 }
             }
-        }
-
-        log::debug!("Done transposing.");
+            if rows.peek().is_none() {
+                log::debug!("Done transposing.");
+            }
 
-        // Pair columns with their IDs
-        let column_ids = std::mem::take(&mut self.column_ids);
-        columns.into_iter().zip(known_cells_col).enumerate().map(
-            move |(col_index, (column, known_cells))| {
-                (column_ids[col_index], (column, known_cells))
-            },
-        )
+            // Pair columns with their IDs
+            Some(
+                column_ids
+                    .iter()
+                    .cloned()
+                    .zip(columns.into_iter().zip(known_cells_col))
+                    .collect(),
+            )
+        })
     }
 }
 
@@ -181,7 +284,7 @@ impl<'a, T: FieldElement> Index<usize> for FinalizableData<'a, T> {
     type Output = Row<'a, T>;
 
     fn index(&self, index: usize) -> &Self::Output {
-        match &self.data[index] {
+        match &self.data[self.position(index)] {
             Entry::InProgress(row) => row,
             Entry::Finalized(_, _) => panic!("Row {} already finalized.", index),
         }
@@ -190,7 +293,8 @@ impl<'a, T: FieldElement> Index<usize> for FinalizableData<'a, T> {
 
 impl<'a, T: FieldElement> IndexMut<usize> for FinalizableData<'a, T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        match &mut self.data[index] {
+        let position = self.position(index);
+        match &mut self.data[position] {
             Entry::InProgress(row) => row,
             Entry::Finalized(_, _) => panic!("Row {} already finalized.", index),
         }
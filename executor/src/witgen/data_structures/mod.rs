@@ -1,2 +1,3 @@
 pub mod column_map;
+pub mod column_sink;
 pub mod finalizable_data;
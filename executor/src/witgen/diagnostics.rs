@@ -0,0 +1,215 @@
+//! Structured reports for rows in which witness generation got stuck.
+
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+use powdr_ast::analyzed::{AlgebraicExpression as Expression, Identity, IdentityKind, PolyID};
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_ast::SourceRef;
+use powdr_number::{DegreeType, FieldElement};
+use powdr_parser_util::lines::indent;
+
+use super::rows::{CellValue, Row};
+use super::EvalError;
+
+/// A report of a row in which witness generation failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureReport<T> {
+    /// The name of the machine the row belongs to.
+    pub machine: String,
+    /// The (global) index of the row.
+    pub row: DegreeType,
+    /// The known values of the machine's witness columns in the row.
+    pub known_values: Vec<(String, T)>,
+    /// The known values of the machine's witness columns in the next row.
+    pub known_next_values: Vec<(String, T)>,
+    /// The identities that could not be solved, in source order.
+    pub blocked_identities: Vec<BlockedIdentity>,
+    /// The errors reported while solving the row.
+    pub errors: Vec<String>,
+}
+
+/// An identity that could not be solved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedIdentity {
+    pub identity_id: u64,
+    /// The location of the identity in the source.
+    pub source: SourceRef,
+    /// The identity, rendered as PIL.
+    pub identity: String,
+    /// The references to witness columns of the machine whose values are not known,
+    /// with a trailing `'` for references to the next row.
+    pub unknown_references: Vec<String>,
+}
+
+impl<T: FieldElement> FailureReport<T> {
+    pub fn new<'a>(
+        machine: &str,
+        row: DegreeType,
+        (current, next): (&Row<T>, &Row<T>),
+        witnesses: &HashSet<PolyID>,
+        identities: impl IntoIterator<Item = &'a Identity<Expression<T>>>,
+        errors: &[EvalError<T>],
+    ) -> Self {
+        let known_values = |row: &Row<T>| {
+            row.iter()
+                .filter(|(id, _)| witnesses.contains(id))
+                .filter_map(|(_, cell)| match cell.value {
+                    CellValue::Known(v) => Some((cell.name.to_string(), v)),
+                    _ => None,
+                })
+                .collect()
+        };
+        let blocked_identities = identities
+            .into_iter()
+            .sorted_by_key(|identity| identity.id)
+            .map(|identity| {
+                // Lookups and permutations only need the values on their left side.
+                let expressions = match identity.kind {
+                    IdentityKind::Plookup | IdentityKind::Permutation => {
+                        identity.left.all_children()
+                    }
+                    IdentityKind::Polynomial | IdentityKind::Connect => identity.all_children(),
+                };
+                let unknown_references = expressions
+                    .filter_map(|e| match e {
+                        Expression::Reference(r) if witnesses.contains(&r.poly_id) => {
+                            let row = if r.next { next } else { current };
+                            (!row[&r.poly_id].value.is_known()).then(|| r.to_string())
+                        }
+                        _ => None,
+                    })
+                    .unique()
+                    .collect();
+                BlockedIdentity {
+                    identity_id: identity.id,
+                    source: identity.source.clone(),
+                    identity: identity.to_string(),
+                    unknown_references,
+                }
+            })
+            .collect();
+        FailureReport {
+            machine: machine.to_string(),
+            row,
+            known_values: known_values(current),
+            known_next_values: known_values(next),
+            blocked_identities,
+            errors: errors.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+impl<T: Display> Display for FailureReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let render_values = |values: &[(String, T)]| match values {
+            [] => "    (none)".to_string(),
+            values => values
+                .iter()
+                .map(|(name, value)| format!("    {name} = {value}"))
+                .join("\n"),
+        };
+        writeln!(
+            f,
+            "Witness generation failed in row {} of {}.",
+            self.row, self.machine
+        )?;
+        writeln!(f, "Known values in the current row:")?;
+        writeln!(f, "{}", render_values(&self.known_values))?;
+        writeln!(f, "Known values in the next row:")?;
+        writeln!(f, "{}", render_values(&self.known_next_values))?;
+        writeln!(f, "Identities that could not be solved:")?;
+        for identity in &self.blocked_identities {
+            writeln!(f, "{}", indent(&identity.to_string(), "    "))?;
+        }
+        write!(f, "Errors:")?;
+        for error in &self.errors {
+            write!(f, "\n{}", indent(error, "    "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for BlockedIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.identity)?;
+        if !self.unknown_references.is_empty() {
+            write!(
+                f,
+                "\n    unknown: {}",
+                self.unknown_references.iter().format(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+    use test_log::test;
+
+    use crate::constant_evaluator;
+    use crate::witgen::{unused_query_callback, WitnessGenerator};
+
+    #[test]
+    #[should_panic = "Witness generation failed in row 0 of Main Machine.
+Known values in the current row:
+    F.x = 0
+Known values in the next row:
+    (none)
+Identities that could not be solved:
+    input:5:13: ((1 - F.FIRST') * ((F.x' - F.x) - 1)) = 0;
+        unknown: F.x'
+    input:6:13: (F.x * F.y) = 1;
+        unknown: F.y
+Errors:
+    Error in identity: (F.x * F.y) = 1;"]
+    fn unsatisfiable_identity() {
+        let src = r#"namespace F(4);
+            col fixed FIRST = [1, 0, 0, 0];
+            col witness x, y;
+            FIRST * x = 0;
+            (1 - FIRST') * (x' - x - 1) = 0;
+            x * y = 1;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let fixed = constant_evaluator::generate(&analyzed);
+        WitnessGenerator::new(&analyzed, &fixed, &unused_query_callback())
+            .with_diagnostics(true)
+            .generate();
+    }
+
+    #[test]
+    #[should_panic = "Error: Witness generation failed in row 0 of Secondary machine 0: Add (BlockMachine).
+    Known values in the current row:
+        Add.A = 2
+        Add.B = 0
+        Add.C = 2
+    Known values in the next row:
+        (none)
+    Identities that could not be solved:
+        input:4:13: Add.C = ((2 * Add.A) + Add.B);
+    Errors:"]
+    fn unsatisfiable_identity_in_block_machine() {
+        let src = r#"namespace Add(4);
+            col witness A, B, C;
+            A + B = C;
+            C = 2 * A + B;
+        namespace Main(4);
+            col fixed a(i) { i + 2 };
+            col fixed b(i) { i * 3 };
+            col witness c;
+            col fixed CALL = [1, 0]*;
+            (1 - CALL) * c = 0;
+            CALL { a, b, c } in { Add.A, Add.B, Add.C };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let fixed = constant_evaluator::generate(&analyzed);
+        WitnessGenerator::new(&analyzed, &fixed, &unused_query_callback())
+            .with_diagnostics(true)
+            .generate();
+    }
+}
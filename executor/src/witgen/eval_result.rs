@@ -3,6 +3,7 @@ use std::fmt::{self, Debug};
 use powdr_ast::analyzed::AlgebraicReference;
 use powdr_number::FieldElement;
 
+use super::diagnostics::FailureReport;
 use super::range_constraints::RangeConstraint;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -172,6 +173,8 @@ pub enum EvalError<T: FieldElement> {
     ProverQueryError(String),
     Generic(String),
     Multiple(Vec<EvalError<T>>),
+    /// Witness generation got stuck in diagnostic mode.
+    FailureReport(Box<FailureReport<T>>),
 }
 
 impl<T: FieldElement> Debug for EvalError<T> {
@@ -234,6 +237,7 @@ impl<T: FieldElement> fmt::Display for EvalError<T> {
                 write!(f, "Error getting external information from the prover: {s}")
             }
             EvalError::Generic(s) => write!(f, "{s}"),
+            EvalError::FailureReport(report) => write!(f, "{report}"),
         }
     }
 }
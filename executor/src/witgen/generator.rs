@@ -19,7 +19,7 @@ use super::machines::{FixedLookup, Machine};
 use super::rows::{Row, RowFactory, RowIndex};
 use super::sequence_iterator::{DefaultSequenceIterator, ProcessingSequenceIterator};
 use super::vm_processor::VmProcessor;
-use super::{EvalResult, FixedData, MutableState, QueryCallback};
+use super::{EvalError, EvalResult, FixedData, MutableState, QueryCallback};
 
 struct ProcessResult<'a, T: FieldElement> {
     eval_value: EvalValue<&'a AlgebraicReference, T>,
@@ -59,18 +59,17 @@ impl<'a, T: FieldElement> Machine<'a, T> for Generator<'a, T> {
             log::trace!("  {r} = {l}");
         }
 
-        let first_row = self
-            .data
-            .last()
-            .cloned()
-            .unwrap_or_else(|| self.compute_partial_first_row(mutable_state));
+        let first_row = match self.data.last() {
+            Some(row) => row.clone(),
+            None => self.compute_partial_first_row(mutable_state)?,
+        };
 
         let outer_query = OuterQuery {
             left: args.to_vec(),
             right,
         };
         let ProcessResult { eval_value, block } =
-            self.process(first_row, 0, mutable_state, Some(outer_query), false);
+            self.process(first_row, 0, mutable_state, Some(outer_query), false)?;
 
         if eval_value.is_complete() {
             log::trace!("End processing VM '{}' (successfully)", self.name());
@@ -101,10 +100,28 @@ impl<'a, T: FieldElement> Machine<'a, T> for Generator<'a, T> {
         self.fill_remaining_rows(&mut mutable_state_no_machines);
         self.fix_first_row();
 
-        self.data
+        let taken = self.data.taken_rows();
+        let columns = self
+            .data
             .take_transposed()
-            .map(|(id, (values, _))| (self.fixed_data.column_name(&id).to_string(), values))
-            .collect()
+            .map(|(id, (values, _))| (self.fixed_data.column_name(&id).to_string(), values));
+        if taken.is_empty() {
+            return columns.collect();
+        }
+
+        // Some rows have already been written to the witness store,
+        // write the rows before and after them as well.
+        let (store, _) = self.fixed_data.witness_store.unwrap();
+        let (head, tail): (Vec<_>, Vec<_>) = columns
+            .map(|(name, mut values)| {
+                let tail = values.split_off(taken.start);
+                ((name.clone(), values), (name, tail))
+            })
+            .unzip();
+        let mut store = store.lock().unwrap();
+        store.write_chunk(0, &head);
+        store.write_chunk(taken.end as DegreeType, &tail);
+        HashMap::new()
     }
 }
 
@@ -135,12 +152,16 @@ impl<'a, T: FieldElement> Generator<'a, T> {
     }
 
     /// Runs the machine without any arguments from the first row.
-    pub fn run<'b, Q: QueryCallback<T>>(&mut self, mutable_state: &mut MutableState<'a, 'b, T, Q>) {
+    pub fn run<'b, Q: QueryCallback<T>>(
+        &mut self,
+        mutable_state: &mut MutableState<'a, 'b, T, Q>,
+    ) -> Result<(), EvalError<T>> {
         record_start(self.name());
         assert!(self.data.is_empty());
-        let first_row = self.compute_partial_first_row(mutable_state);
-        self.data = self.process(first_row, 0, mutable_state, None, true).block;
+        let first_row = self.compute_partial_first_row(mutable_state)?;
+        self.data = self.process(first_row, 0, mutable_state, None, true)?.block;
         record_end(self.name());
+        Ok(())
     }
 
     fn fill_remaining_rows<Q: QueryCallback<T>>(
@@ -151,13 +172,15 @@ impl<'a, T: FieldElement> Generator<'a, T> {
             assert!(self.latch.is_some());
 
             let first_row = self.data.pop().unwrap();
-            let ProcessResult { block, eval_value } = self.process(
-                first_row,
-                self.data.len() as DegreeType,
-                mutable_state,
-                None,
-                false,
-            );
+            let ProcessResult { block, eval_value } = self
+                .process(
+                    first_row,
+                    self.data.len() as DegreeType,
+                    mutable_state,
+                    None,
+                    false,
+                )
+                .unwrap_or_else(|e| panic!("Witness generation failed.\n{e}"));
             assert!(eval_value.is_complete());
 
             self.data.extend(block);
@@ -169,7 +192,7 @@ impl<'a, T: FieldElement> Generator<'a, T> {
     fn compute_partial_first_row<Q: QueryCallback<T>>(
         &self,
         mutable_state: &mut MutableState<'a, '_, T, Q>,
-    ) -> Row<'a, T> {
+    ) -> Result<Row<'a, T>, EvalError<T>> {
         // Use `BlockProcessor` + `DefaultSequenceIterator` using a "block size" of 0. Because `BlockProcessor`
         // expects `data` to include the row before and after the block, this means we'll run the
         // solver on exactly one row pair.
@@ -196,6 +219,7 @@ impl<'a, T: FieldElement> Generator<'a, T> {
             .filter_map(|identity| identity.contains_next_ref().then_some(*identity))
             .collect::<Vec<_>>();
         let mut processor = BlockProcessor::new(
+            self.name(),
             RowIndex::from_i64(-1, self.fixed_data.degree),
             data,
             mutable_state,
@@ -206,10 +230,10 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         let mut sequence_iterator = ProcessingSequenceIterator::Default(
             DefaultSequenceIterator::new(0, identities_with_next_reference.len(), None),
         );
-        processor.solve(&mut sequence_iterator).unwrap();
+        processor.solve(&mut sequence_iterator)?;
         let first_row = processor.finish().remove(1);
 
-        first_row
+        Ok(first_row)
     }
 
     fn process<Q: QueryCallback<T>>(
@@ -219,7 +243,7 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         mutable_state: &mut MutableState<'a, '_, T, Q>,
         outer_query: Option<OuterQuery<'a, T>>,
         is_main_run: bool,
-    ) -> ProcessResult<'a, T> {
+    ) -> Result<ProcessResult<'a, T>, EvalError<T>> {
         log::trace!(
             "Running main machine from row {row_offset} with the following initial values in the first row:\n{}", first_row.render_values(false, None)
         );
@@ -229,6 +253,7 @@ impl<'a, T: FieldElement> Generator<'a, T> {
             [first_row].into_iter(),
         );
         let mut processor = VmProcessor::new(
+            self.name().to_string(),
             RowIndex::from_degree(row_offset, self.fixed_data.degree),
             self.fixed_data,
             &self.identities,
@@ -240,9 +265,9 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         if let Some(outer_query) = outer_query {
             processor = processor.with_outer_query(outer_query);
         }
-        let eval_value = processor.run(is_main_run);
+        let eval_value = processor.run(is_main_run)?;
         let block = processor.finish();
-        ProcessResult { eval_value, block }
+        Ok(ProcessResult { eval_value, block })
     }
 
     /// At the end of the solving algorithm, we'll have computed the first row twice
@@ -179,7 +179,9 @@ fn propagate_constraints<T: FieldElement>(
             }
         }
         IdentityKind::Plookup | IdentityKind::Permutation | IdentityKind::Connect => {
-            if identity.left.selector.is_some() || identity.right.selector.is_some() {
+            if !is_unconditional(&identity.left.selector)
+                || !is_unconditional(&identity.right.selector)
+            {
                 return (known_constraints, false);
             }
             for (left, right) in identity
@@ -216,6 +218,16 @@ fn propagate_constraints<T: FieldElement>(
     (known_constraints, remove)
 }
 
+/// Returns true if the selector is absent or the constant one,
+/// e.g. after a link to a machine with constant latch was optimized.
+fn is_unconditional<T: FieldElement>(selector: &Option<Expression<T>>) -> bool {
+    match selector {
+        None => true,
+        Some(Expression::Number(n)) => n.is_one(),
+        Some(_) => false,
+    }
+}
+
 /// Tries to find "X * (1 - X) = 0"
 fn is_binary_constraint<T: FieldElement>(expr: &Expression<T>) -> Option<PolyID> {
     // TODO Write a proper pattern matching engine.
@@ -447,4 +459,39 @@ namespace Global(1024);
         );
         assert!(!removed);
     }
+
+    #[test]
+    fn test_constant_one_selectors() {
+        // Links to a machine with constant latch, like `std::range_check::RangeCheck`,
+        // result in lookups with selectors that are one after optimization.
+        let pil_source = r"
+namespace Global(1024);
+    let BYTE: col = |i| i & 0xff;
+    let X;
+    let Y;
+    1 { X } in 1 { BYTE };
+    Y { X } in { BYTE };
+";
+        let analyzed = powdr_pil_analyzer::analyze_string::<GoldilocksField>(pil_source);
+        let known_constraints: BTreeMap<_, _> =
+            vec![(constant_poly_id(0), RangeConstraint::from_max_bit(7))]
+                .into_iter()
+                .collect();
+        let full_span = vec![constant_poly_id(0)].into_iter().collect();
+        assert_eq!(analyzed.identities.len(), 2);
+        let (constraints, removed) = propagate_constraints(
+            known_constraints.clone(),
+            &analyzed.identities[0],
+            &full_span,
+        );
+        assert!(removed);
+        assert_eq!(
+            constraints.get(&witness_poly_id(0)),
+            Some(&RangeConstraint::from_max_bit(7))
+        );
+        let (constraints, removed) =
+            propagate_constraints(known_constraints, &analyzed.identities[1], &full_span);
+        assert!(!removed);
+        assert_eq!(constraints.get(&witness_poly_id(0)), None);
+    }
 }
@@ -235,7 +235,9 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
                     .ok_or(EvalError::Generic("Selector is not 1!".to_string()))
             })
             .unwrap_or(Ok(T::one()))?;
-        assert_eq!(selector_value, T::one());
+        if selector_value != T::one() {
+            return Err(EvalError::Generic("Selector is not 1!".to_string()));
+        }
 
         let mut updates = EvalValue::complete(vec![]);
 
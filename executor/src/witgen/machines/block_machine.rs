@@ -5,6 +5,7 @@ use super::{EvalResult, FixedData, FixedLookup};
 use crate::witgen::affine_expression::AffineExpression;
 
 use crate::witgen::block_processor::BlockProcessor;
+use crate::witgen::data_structures::column_sink::ColumnSink;
 use crate::witgen::data_structures::finalizable_data::FinalizableData;
 use crate::witgen::global_constraints::GlobalConstraints;
 use crate::witgen::identity_processor::IdentityProcessor;
@@ -38,6 +39,14 @@ impl<'a, T: FieldElement> ProcessResult<'a, T> {
         }
     }
 
+    /// The number of known cells in the new block, or zero if it is incomplete.
+    fn known_cells_count(&self) -> usize {
+        match self {
+            ProcessResult::Success(data, _) => data.known_cells_count(),
+            ProcessResult::Incomplete(_) => 0,
+        }
+    }
+
     fn is_success(&self) -> bool {
         match self {
             ProcessResult::Success(_, _) => true,
@@ -366,7 +375,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
             }
 
             // Run BlockProcessor (to potentially propagate selector values)
-            let mut processor = BlockProcessor::from_processor(processor, &self.identities);
+            let mut processor =
+                BlockProcessor::from_processor(&self.name, processor, &self.identities);
             let mut sequence_iterator = ProcessingSequenceIterator::Default(
                 DefaultSequenceIterator::new(self.block_size, self.identities.len(), None),
             );
@@ -380,65 +390,104 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
             }
         }
 
-        let mut data = self
-            .data
-            .take_transposed()
-            .map(|(id, (values, known_cells))| {
-                // Materialize column as Vec<Option<T>>
-                let mut values = values
-                    .into_iter()
-                    .zip(known_cells)
-                    .map(|(v, known)| known.then_some(v))
-                    .collect::<Vec<_>>();
-
-                // Remove the "last" block added to the beginning of self.data.
-                // It contains the values the first block wrote to it and otherwise unknown values.
-                let dummy_block = values.drain(0..self.block_size).collect::<Vec<_>>();
-
-                // For all constraints to be satisfied, unused cells have to be filled with valid values.
-                // We do this, we construct a default block, by repeating the first input to the block machine.
-                values.resize(self.fixed_data.degree as usize, None);
-
-                // Use the block as the default block. However, it needs to be merged with the dummy block,
-                // to handle blocks of non-rectangular shape.
-                // For example, let's say, the situation might look like this (block size = 3 in this example):
-                //  Row  Latch   C1  C2  C3
-                //  -3     0
-                //  -2     0
-                //  -1     1             X  <- This value belongs to the first block
-                //   0     0     X   X   X
-                //   1     0     X   X   X
-                //   2     1     X   X   X  <- This value belongs to the second block
-                //
-                // The following code constructs the default block as follows:
-                // - All values will come from rows 0-2, EXCEPT
-                // - In the last row, the value of C3 is whatever value was written to the dummy block
-                //
-                // Constructed like this, we can repeat the default block forever.
-                //
-                // TODO: Determine the row-extend per column
-                let first_block_values = values.iter().take(self.block_size);
-                let default_block = dummy_block
-                    .into_iter()
-                    .zip(first_block_values)
-                    .map(|(dummy_block, first_block)| {
-                        dummy_block.or(*first_block).unwrap_or_default()
-                    })
-                    .collect::<Vec<_>>();
-
-                let values = values
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, v)| v.unwrap_or(default_block[i % self.block_size]))
-                    .collect::<Vec<_>>();
-
-                (id, values)
+        // With a witness store, the rows are written to it in chunks. The first chunk needs to
+        // contain the "last" block added to the beginning of self.data and the first block.
+        let chunk_size = self
+            .fixed_data
+            .witness_store
+            .map_or(usize::MAX, |(_, chunk_size)| {
+                chunk_size.max(2 * self.block_size)
+            });
+        let mut chunks = self.data.take_transposed_chunks(chunk_size);
+        let first_chunk = chunks.next().unwrap();
+        let column_ids = first_chunk.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let mut output = ColumnSink::new(
+            column_ids
+                .iter()
+                .map(|id| self.fixed_data.column_name(id).to_string())
+                .collect(),
+            self.fixed_data.witness_store,
+        );
+
+        // For all constraints to be satisfied, unused cells have to be filled with valid values.
+        // We do this, we construct a default block, by repeating the first input to the block machine.
+        //
+        // We use the first block as the default block. However, it needs to be merged with the dummy block
+        // (the "last" block added to the beginning of self.data), to handle blocks of non-rectangular shape.
+        // The dummy block contains the values the first block wrote to it and otherwise unknown values.
+        // For example, let's say, the situation might look like this (block size = 3 in this example):
+        //  Row  Latch   C1  C2  C3
+        //  -3     0
+        //  -2     0
+        //  -1     1             X  <- This value belongs to the first block
+        //   0     0     X   X   X
+        //   1     0     X   X   X
+        //   2     1     X   X   X  <- This value belongs to the second block
+        //
+        // The following code constructs the default block as follows:
+        // - All values will come from rows 0-2, EXCEPT
+        // - In the last row, the value of C3 is whatever value was written to the dummy block
+        //
+        // Constructed like this, we can repeat the default block forever.
+        //
+        // TODO: Determine the row-extend per column
+        let default_blocks = first_chunk
+            .iter()
+            .map(|(_, (values, known_cells))| {
+                let value = |i: usize| known_cells.get(i).unwrap_or(false).then(|| values[i]);
+                (0..self.block_size)
+                    .map(|i| value(i).or(value(self.block_size + i)).unwrap_or_default())
+                    .collect::<Vec<_>>()
             })
-            .collect();
-        self.handle_last_row(&mut data);
-        data.into_iter()
-            .map(|(id, values)| (self.fixed_data.column_name(&id).to_string(), values))
-            .collect()
+            .collect::<Vec<_>>();
+
+        // The index of the next row, not counting the dummy block.
+        let mut row = 0;
+        let degree = self.fixed_data.degree as usize;
+        for (index, chunk) in iter::once(first_chunk).chain(chunks).enumerate() {
+            // Remove the dummy block.
+            let skip = if index == 0 { self.block_size } else { 0 };
+            let len = (chunk[0].1 .0.len() - skip).min(degree - row);
+            let mut columns = chunk
+                .into_iter()
+                .zip(&default_blocks)
+                .map(|((_, (values, known_cells)), default_block)| {
+                    values
+                        .into_iter()
+                        .zip(known_cells)
+                        .skip(skip)
+                        .take(len)
+                        .enumerate()
+                        .map(|(i, (v, known))| {
+                            if known {
+                                v
+                            } else {
+                                default_block[(row + i) % self.block_size]
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            self.handle_last_row(&column_ids, row, &mut columns);
+            output.extend(columns);
+            row += len;
+        }
+        // Fill up the remaining rows with the default block.
+        while row < degree {
+            let len = chunk_size.min(degree - row);
+            let mut columns = default_blocks
+                .iter()
+                .map(|default_block| {
+                    (row..row + len)
+                        .map(|i| default_block[i % self.block_size])
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            self.handle_last_row(&column_ids, row, &mut columns);
+            output.extend(columns);
+            row += len;
+        }
+        output.finish()
     }
 }
 
@@ -452,15 +501,20 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
     /// compiling a block machine from Powdr ASM and constrained as:
     /// _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - <Latch>));
     /// This function fixes this exception by setting _operation_id_no_change to 0.
-    fn handle_last_row(&self, data: &mut HashMap<PolyID, Vec<T>>) {
-        for (poly_id, col) in data.iter_mut() {
-            if self
-                .fixed_data
-                .column_name(poly_id)
-                .ends_with("_operation_id_no_change")
+    /// The columns are given by the rows starting at row `start`.
+    fn handle_last_row(&self, column_ids: &[PolyID], start: usize, columns: &mut [Vec<T>]) {
+        let Some(last_row) = (self.fixed_data.degree as usize - 1).checked_sub(start) else {
+            return;
+        };
+        for (poly_id, col) in column_ids.iter().zip(columns) {
+            if last_row < col.len()
+                && self
+                    .fixed_data
+                    .column_name(poly_id)
+                    .ends_with("_operation_id_no_change")
             {
                 log::trace!("Setting _operation_id_no_change to 0.");
-                col[self.fixed_data.degree as usize - 1] = T::zero();
+                col[last_row] = T::zero();
             }
         }
     }
@@ -543,8 +597,11 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
 
         let process_result = self.process(mutable_state, left, right, &mut sequence_iterator)?;
 
-        let process_result = if sequence_iterator.is_cached() && !process_result.is_success() {
-            log::debug!("The cached sequence did not complete the block machine. \
+        let process_result = if sequence_iterator.is_cached()
+            && (!process_result.is_success()
+                || sequence_iterator.deviated_from_cache(process_result.known_cells_count()))
+        {
+            log::debug!("The cached sequence did not complete the block machine or derived fewer values than expected. \
                          This can happen if the machine's execution steps depend on the input or constant values. \
                          We'll try again with the default sequence.");
             let mut sequence_iterator = self
@@ -561,11 +618,15 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                     "End processing block machine '{}' (successfully)",
                     self.name()
                 );
+                let known_cells = new_block.known_cells_count();
                 self.append_block(new_block)?;
 
                 // We solved the query, so report it to the cache.
-                self.processing_sequence_cache
-                    .report_processing_sequence(left, sequence_iterator);
+                self.processing_sequence_cache.report_processing_sequence(
+                    left,
+                    sequence_iterator,
+                    known_cells,
+                );
                 Ok(updates)
             }
             ProcessResult::Incomplete(updates) => {
@@ -595,6 +656,7 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             (0..(self.block_size + 2)).map(|i| self.row_factory.fresh_row(row_offset + i)),
         );
         let mut processor = BlockProcessor::new(
+            &self.name,
             row_offset,
             block,
             mutable_state,
@@ -1,11 +1,10 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::iter::once;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
-use itertools::Itertools;
 use num_traits::Zero;
 
 use super::{FixedLookup, Machine};
 use crate::witgen::affine_expression::AffineExpression;
+use crate::witgen::data_structures::column_sink::ColumnSink;
 use crate::witgen::global_constraints::GlobalConstraints;
 use crate::witgen::util::try_to_simple_poly;
 use crate::witgen::{EvalResult, FixedData, MutableState, QueryCallback};
@@ -67,6 +66,16 @@ pub struct DoubleSortedWitnesses<'a, T> {
     selector_ids: BTreeMap<u64, PolyID>,
 }
 
+/// A row of the machine, without the columns that depend on the next row.
+struct MemoryRow<T> {
+    addr: T,
+    step: T,
+    value: T,
+    is_normal_write: bool,
+    is_bootloader_write: bool,
+    selector_id: Option<PolyID>,
+}
+
 struct Operation<T> {
     pub is_normal_write: bool,
     pub is_bootloader_write: bool,
@@ -202,134 +211,138 @@ impl<'a, T: FieldElement> Machine<'a, T> for DoubleSortedWitnesses<'a, T> {
         _fixed_lookup: &'b mut FixedLookup<T>,
         _query_callback: &'b mut Q,
     ) -> HashMap<String, Vec<T>> {
-        let mut addr = vec![];
-        let mut step = vec![];
-        let mut value = vec![];
-        let mut is_normal_write = vec![];
-        let mut is_bootloader_write = vec![];
-        let mut diff = vec![];
-        let mut selectors = self
-            .selector_ids
-            .values()
-            .map(|id| (id, Vec::new()))
-            .collect::<BTreeMap<_, _>>();
-        let mut set_selector = |selector_id: Option<PolyID>| {
-            for (id, v) in selectors.iter_mut() {
-                v.push(if Some(**id) == selector_id {
-                    T::one()
-                } else {
-                    T::zero()
-                })
-            }
+        let selectors = self.selector_ids.values().collect::<BTreeSet<_>>();
+        let diff_columns = match self.diff_columns_base {
+            Some(_) => vec![
+                self.namespaced("m_diff_upper"),
+                self.namespaced("m_diff_lower"),
+            ],
+            None => vec![],
         };
-
-        for ((a, s), o) in std::mem::take(&mut self.trace) {
-            if let Some(prev_address) = addr.last() {
-                assert!(a >= *prev_address, "Expected addresses to be sorted");
-                if self.diff_columns_base.is_none()
-                    && (a - *prev_address).to_degree() >= self.degree
-                {
-                    log::error!("Jump in memory accesses between {prev_address:x} and {a:x} is larger than or equal to the degree {}! This will violate the constraints.", self.degree);
-                }
-
-                let current_diff = if a != *prev_address {
-                    a - *prev_address
-                } else {
-                    s - *step.last().unwrap()
-                };
-                assert!(current_diff > T::zero());
-                diff.push(current_diff.to_degree() - 1);
-            }
-
-            addr.push(a);
-            step.push(s);
-            value.push(o.value);
-
-            is_normal_write.push(o.is_normal_write.into());
-            is_bootloader_write.push(o.is_bootloader_write.into());
-            set_selector(Some(o.selector_id));
-        }
-        if addr.is_empty() {
-            // No memory access at all - fill a first row with something.
-            addr.push(-T::one());
-            step.push(0.into());
-            value.push(0.into());
-            is_normal_write.push(0.into());
-            is_bootloader_write.push(0.into());
-            set_selector(None);
-        }
-        while addr.len() < self.degree as usize {
-            addr.push(*addr.last().unwrap());
-            step.push(*step.last().unwrap() + T::from(1));
-            diff.push(0);
-            value.push(*value.last().unwrap());
-            is_normal_write.push(0.into());
-            is_bootloader_write.push(0.into());
-            set_selector(None);
-        }
-
-        // We have all diffs, except from the last to the first element, which is unconstrained.
-        assert_eq!(diff.len(), self.degree as usize - 1);
-        diff.push(0);
-
-        let last_row_change_value = match self.has_bootloader_write_column {
-            true => (&addr[0] != addr.last().unwrap()).into(),
-            // In the machine without the bootloader write column, m_change is constrained
-            // to be 1 in the last row.
-            false => 1.into(),
-        };
-
-        let change = addr
-            .iter()
-            .tuple_windows()
-            .map(|(a, a_next)| if a == a_next { 0.into() } else { 1.into() })
-            .chain(once(last_row_change_value))
-            .collect::<Vec<_>>();
-        assert_eq!(change.len(), addr.len());
-
-        let diff_columns = if let Some(diff_columns_base) = self.diff_columns_base {
-            let diff_upper = diff
-                .iter()
-                .map(|d| T::from(*d / diff_columns_base))
-                .collect::<Vec<_>>();
-            let diff_lower = diff
-                .iter()
-                .map(|d| T::from(*d % diff_columns_base))
-                .collect::<Vec<_>>();
-            vec![
-                (self.namespaced("m_diff_upper"), diff_upper),
-                (self.namespaced("m_diff_lower"), diff_lower),
-            ]
-        } else {
-            vec![]
+        let bootloader_columns = match self.has_bootloader_write_column {
+            true => vec![self.namespaced(BOOTLOADER_WRITE_COLUMN)],
+            false => vec![],
         };
-
-        let bootloader_columns = if self.has_bootloader_write_column {
-            vec![(
-                self.namespaced(BOOTLOADER_WRITE_COLUMN),
-                is_bootloader_write.clone(),
-            )]
-        } else {
-            vec![]
-        };
-
-        let selector_columns = selectors
-            .into_iter()
-            .map(|(id, v)| (self.fixed.column_name(id).to_string(), v))
-            .collect::<Vec<_>>();
-
-        [
-            (self.namespaced("m_value"), value),
-            (self.namespaced("m_addr"), addr),
-            (self.namespaced("m_step"), step),
-            (self.namespaced("m_change"), change),
-            (self.namespaced("m_is_write"), is_normal_write.clone()),
+        let names = [
+            self.namespaced("m_value"),
+            self.namespaced("m_addr"),
+            self.namespaced("m_step"),
+            self.namespaced("m_change"),
+            self.namespaced("m_is_write"),
         ]
         .into_iter()
         .chain(diff_columns)
         .chain(bootloader_columns)
-        .chain(selector_columns)
-        .collect()
+        .chain(
+            selectors
+                .iter()
+                .map(|id| self.fixed.column_name(id).to_string()),
+        )
+        .collect();
+        // The rows are written to the witness store (if any) as soon as they are complete,
+        // so that the columns never have to be kept in memory as a whole.
+        let mut output = ColumnSink::new(names, self.fixed.witness_store);
+
+        // The rows of the trace, sorted by address and step.
+        let mut rows = std::mem::take(&mut self.trace)
+            .into_iter()
+            .map(|((addr, step), o)| MemoryRow {
+                addr,
+                step,
+                value: o.value,
+                is_normal_write: o.is_normal_write,
+                is_bootloader_write: o.is_bootloader_write,
+                selector_id: Some(o.selector_id),
+            })
+            .peekable();
+        let mut row = rows.next().unwrap_or(
+            // No memory access at all - fill a first row with something.
+            MemoryRow {
+                addr: -T::one(),
+                step: 0.into(),
+                value: 0.into(),
+                is_normal_write: false,
+                is_bootloader_write: false,
+                selector_id: None,
+            },
+        );
+        let first_addr = row.addr;
+        let mut row_count = 1;
+        loop {
+            // The rows after the trace repeat the last address with increasing steps.
+            let next = rows.next().or_else(|| {
+                (row_count < self.degree).then(|| MemoryRow {
+                    step: row.step + T::from(1),
+                    is_normal_write: false,
+                    is_bootloader_write: false,
+                    selector_id: None,
+                    ..row
+                })
+            });
+            let (diff, change) = match &next {
+                Some(next) => {
+                    assert!(next.addr >= row.addr, "Expected addresses to be sorted");
+                    if self.diff_columns_base.is_none()
+                        && (next.addr - row.addr).to_degree() >= self.degree
+                    {
+                        log::error!("Jump in memory accesses between {:x} and {:x} is larger than or equal to the degree {}! This will violate the constraints.", row.addr, next.addr, self.degree);
+                    }
+                    let current_diff = if next.addr != row.addr {
+                        next.addr - row.addr
+                    } else {
+                        next.step - row.step
+                    };
+                    assert!(current_diff > T::zero());
+                    (current_diff.to_degree() - 1, T::from(next.addr != row.addr))
+                }
+                // The diff from the last to the first element is unconstrained.
+                None => {
+                    let last_row_change_value = match self.has_bootloader_write_column {
+                        true => (first_addr != row.addr).into(),
+                        // In the machine without the bootloader write column, m_change is constrained
+                        // to be 1 in the last row.
+                        false => 1.into(),
+                    };
+                    (0, last_row_change_value)
+                }
+            };
+
+            let diff_columns = self
+                .diff_columns_base
+                .map(|base| [T::from(diff / base), T::from(diff % base)]);
+            output.push_row(
+                [
+                    row.value,
+                    row.addr,
+                    row.step,
+                    change,
+                    row.is_normal_write.into(),
+                ]
+                .into_iter()
+                .chain(diff_columns.into_iter().flatten())
+                .chain(
+                    self.has_bootloader_write_column
+                        .then(|| row.is_bootloader_write.into()),
+                )
+                .chain(selectors.iter().map(|id| {
+                    if Some(**id) == row.selector_id {
+                        T::one()
+                    } else {
+                        T::zero()
+                    }
+                })),
+            );
+
+            match next {
+                Some(next) => {
+                    row = next;
+                    row_count += 1;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(row_count, self.degree);
+        output.finish()
     }
 }
 
@@ -109,7 +109,14 @@ impl<T: FieldElement> IndexedColumns<T> {
             .map(|id| fixed_data.fixed_cols[id].values)
             .collect::<Vec<_>>();
 
-        let index: BTreeMap<Vec<T>, IndexValue> = (0..fixed_data.degree as usize)
+        // The fixed columns might have a different degree than the calling machine.
+        let degree = input_column_values
+            .iter()
+            .chain(&output_column_values)
+            .map(|column| column.len())
+            .next()
+            .unwrap_or_default();
+        let index: BTreeMap<Vec<T>, IndexValue> = (0..degree)
             .fold(
                 (
                     BTreeMap::<Vec<T>, IndexValue>::default(),
@@ -167,6 +174,9 @@ impl<T: FieldElement> IndexedColumns<T> {
 pub struct FixedLookup<T: FieldElement> {
     global_constraints: GlobalConstraints<T>,
     indices: IndexedColumns<T>,
+    /// Caches, for fixed columns used as right-hand side selectors,
+    /// whether they are one on every row.
+    constant_one_columns: HashMap<PolyID, bool>,
 }
 
 impl<T: FieldElement> FixedLookup<T> {
@@ -174,6 +184,7 @@ impl<T: FieldElement> FixedLookup<T> {
         Self {
             global_constraints,
             indices: Default::default(),
+            constant_one_columns: Default::default(),
         }
     }
 
@@ -200,12 +211,18 @@ impl<T: FieldElement> FixedLookup<T> {
         right: &'b SelectedExpressions<Expression<T>>,
     ) -> Option<EvalResult<'b, T>> {
         // This is a matching machine if it is a plookup and the RHS is fully constant.
+        // A RHS selector is only allowed if it is one on every row, which is the case
+        // for lookups into fixed-table machines like `std::range_check::RangeCheck`.
         if kind != IdentityKind::Plookup
-            || right.selector.is_some()
             || right.expressions.iter().any(|e| e.contains_witness_ref())
         {
             return None;
         }
+        if let Some(selector) = &right.selector {
+            if !self.is_constant_one(fixed_data, selector) {
+                return None;
+            }
+        }
 
         // get the values of the fixed columns
         let mut right = right
@@ -219,6 +236,28 @@ impl<T: FieldElement> FixedLookup<T> {
         Some(self.process_plookup_internal(fixed_data, rows, left, right))
     }
 
+    /// Returns true if the expression is known to evaluate to one on every row,
+    /// i.e. it is the number one or a fixed column that only contains ones.
+    fn is_constant_one(&mut self, fixed_data: &FixedData<T>, expr: &Expression<T>) -> bool {
+        match expr {
+            Expression::Number(n) => n.is_one(),
+            Expression::Reference(poly)
+                if poly.poly_id.ptype == PolynomialType::Constant && !poly.next =>
+            {
+                *self
+                    .constant_one_columns
+                    .entry(poly.poly_id)
+                    .or_insert_with(|| {
+                        fixed_data.fixed_cols[&poly.poly_id]
+                            .values
+                            .iter()
+                            .all(|v| v.is_one())
+                    })
+            }
+            _ => false,
+        }
+    }
+
     fn process_plookup_internal<'b>(
         &mut self,
         fixed_data: &FixedData<T>,
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 use super::block_machine::BlockMachine;
 use super::double_sorted_witness_machine::DoubleSortedWitnesses;
@@ -13,7 +13,7 @@ use itertools::Itertools;
 use powdr_ast::analyzed::{AlgebraicExpression as Expression, Identity, IdentityKind, PolyID};
 use powdr_ast::parsed::visitor::ExpressionVisitable;
 use powdr_ast::parsed::SelectedExpressions;
-use powdr_number::FieldElement;
+use powdr_number::{DegreeType, FieldElement};
 
 pub struct ExtractionOutput<'a, T: FieldElement> {
     pub fixed_lookup: FixedLookup<T>,
@@ -25,8 +25,10 @@ pub struct ExtractionOutput<'a, T: FieldElement> {
 /// Finds machines in the witness columns and identities
 /// and returns a list of machines and the identities
 /// that are not "internal" to the machines.
+/// Each machine uses the entry of `fixed_by_degree` for the degree of its columns.
 pub fn split_out_machines<'a, T: FieldElement>(
     fixed: &'a FixedData<'a, T>,
+    fixed_by_degree: &'a BTreeMap<DegreeType, FixedData<'a, T>>,
     identities: Vec<&'a Identity<Expression<T>>>,
     global_range_constraints: &GlobalConstraints<T>,
 ) -> ExtractionOutput<'a, T> {
@@ -111,6 +113,7 @@ pub fn split_out_machines<'a, T: FieldElement>(
         let id = id_counter;
         id_counter += 1;
         let name_with_type = |t: &str| format!("Secondary machine {id}: {name} ({t})");
+        let fixed = &fixed_by_degree[&fixed.common_degree(&machine_witnesses)];
 
         if let Some(machine) = SortedWitnesses::try_new(
             name_with_type("SortedWitness"),
@@ -5,6 +5,7 @@ use itertools::Itertools;
 use super::super::affine_expression::AffineExpression;
 use super::{EvalResult, FixedData};
 use super::{FixedLookup, Machine};
+use crate::witgen::data_structures::column_sink::ColumnSink;
 use crate::witgen::{
     expression_evaluator::ExpressionEvaluator, fixed_evaluator::FixedEvaluator,
     symbolic_evaluator::SymbolicEvaluator,
@@ -179,28 +180,36 @@ impl<'a, T: FieldElement> Machine<'a, T> for SortedWitnesses<'a, T> {
         _fixed_lookup: &'b mut FixedLookup<T>,
         _query_callback: &'b mut Q,
     ) -> HashMap<String, Vec<T>> {
-        let mut result = HashMap::new();
+        let value_columns = self
+            .witness_positions
+            .iter()
+            .sorted_by_key(|(_, &i)| i)
+            .map(|(col, _)| self.fixed_data.column_name(col).to_string());
+        let names = std::iter::once(self.fixed_data.column_name(&self.key_col).to_string())
+            .chain(value_columns)
+            .collect();
+        // The rows are written to the witness store (if any) in chunks.
+        let mut output = ColumnSink::new(names, self.fixed_data.witness_store);
 
-        let (mut keys, mut values): (Vec<_>, Vec<_>) =
-            std::mem::take(&mut self.data).into_iter().unzip();
-
-        let mut last_key = keys.last().cloned().unwrap_or_default();
-        while keys.len() < self.fixed_data.degree as usize {
-            last_key += 1u64.into();
-            keys.push(last_key);
+        let mut row_count = 0;
+        let mut last_key = T::default();
+        for (key, values) in std::mem::take(&mut self.data) {
+            output.push_row(
+                std::iter::once(key).chain(values.into_iter().map(|v| v.unwrap_or_default())),
+            );
+            last_key = key;
+            row_count += 1;
         }
-        result.insert(self.fixed_data.column_name(&self.key_col).to_string(), keys);
-
-        for (col, &i) in &self.witness_positions {
-            let mut col_values = values
-                .iter_mut()
-                .map(|row| std::mem::take(&mut row[i]).unwrap_or_default())
-                .collect::<Vec<_>>();
-            col_values.resize(self.fixed_data.degree as usize, 0.into());
-            result.insert(self.fixed_data.column_name(col).to_string(), col_values);
+        while row_count < self.fixed_data.degree {
+            last_key += 1u64.into();
+            output.push_row(
+                std::iter::once(last_key)
+                    .chain(std::iter::repeat(T::zero()).take(self.witness_positions.len())),
+            );
+            row_count += 1;
         }
 
-        result
+        output.finish()
     }
 }
 
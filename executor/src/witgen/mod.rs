@@ -1,16 +1,22 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use itertools::Itertools;
 use powdr_ast::analyzed::{
     AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition, PolyID,
-    PolynomialType, SymbolKind, TypedExpression,
+    PolynomialType, Symbol, SymbolKind, TypedExpression,
 };
 use powdr_ast::parsed::visitor::ExpressionVisitable;
 use powdr_ast::parsed::{FunctionKind, LambdaExpression};
 use powdr_number::{DegreeType, FieldElement};
+use powdr_pil_analyzer::compiled_evaluator::{self, CompiledFunction};
+
+use crate::column_store::ColumnStore;
 
 use self::data_structures::column_map::{FixedColumnMap, WitnessColumnMap};
+use self::data_structures::column_sink::ColumnSink;
+pub use self::diagnostics::{BlockedIdentity, FailureReport};
 pub use self::eval_result::{
     Constraint, Constraints, EvalError, EvalResult, EvalStatus, EvalValue, IncompleteCause,
 };
@@ -24,6 +30,7 @@ use self::machines::{FixedLookup, Machine};
 mod affine_expression;
 mod block_processor;
 mod data_structures;
+mod diagnostics;
 mod eval_result;
 mod expression_evaluator;
 pub mod fixed_evaluator;
@@ -111,6 +118,7 @@ pub struct WitnessGenerator<'a, 'b, T: FieldElement> {
     external_witness_values: &'b [(String, Vec<T>)],
     stage: u8,
     challenges: BTreeMap<u64, T>,
+    diagnostics: bool,
 }
 
 impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
@@ -126,6 +134,7 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             external_witness_values: &[],
             stage: 0,
             challenges: BTreeMap::new(),
+            diagnostics: false,
         }
     }
 
@@ -147,16 +156,58 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
         }
     }
 
+    /// Enables the diagnostic mode: If witness generation gets stuck, a [FailureReport]
+    /// listing the known values, the identities that could not be solved and their
+    /// unknown cells is logged and included in the panic message.
+    pub fn with_diagnostics(self, diagnostics: bool) -> Self {
+        WitnessGenerator {
+            diagnostics,
+            ..self
+        }
+    }
+
     /// Generates the committed polynomial values
     /// @returns the values (in source order) and the degree of the polynomials.
     pub fn generate(self) -> Vec<(String, Vec<T>)> {
+        self.generate_with_store(None)
+    }
+
+    /// Generates the committed polynomial values and writes them to the given store instead
+    /// of returning them. The rows of the main machine are written in chunks of `chunk_size`
+    /// rows as soon as they are finalized, so that the whole trace of the main machine never
+    /// has to be kept in memory. The other machines write their columns in chunks of the
+    /// same size once the main machine has finished, one machine at a time.
+    /// Only the first stage can be generated this way, because witness generation for the
+    /// later stages needs the witness of the previous stages in memory.
+    pub fn generate_into(self, store: &Mutex<ColumnStore<T>>, chunk_size: usize) {
+        assert_eq!(
+            self.stage, 0,
+            "Only the witness of the first stage can be written to a store."
+        );
+        store
+            .lock()
+            .unwrap()
+            .declare_columns(self.witness_column_names());
+        let remaining_columns = self.generate_with_store(Some((store, chunk_size)));
+        assert!(remaining_columns.is_empty());
+    }
+
+    /// Generates the committed polynomial values. If a store is given, all columns are
+    /// written to the store instead and nothing is returned.
+    fn generate_with_store(
+        self,
+        witness_store: Option<(&Mutex<ColumnStore<T>>, usize)>,
+    ) -> Vec<(String, Vec<T>)> {
         record_start(OUTER_CODE_NAME);
+        let witness_column_names = self.witness_column_names();
         let fixed = FixedData::new(
             self.analyzed,
             self.fixed_col_values,
             self.external_witness_values,
             self.challenges,
-        );
+        )
+        .with_diagnostics(self.diagnostics)
+        .with_witness_store(witness_store);
         let identities = self
             .analyzed
             .identities_with_inlined_intermediate_polynomials()
@@ -185,6 +236,12 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             // These are already captured in the range constraints.
             retained_identities,
         ) = global_constraints::determine_global_constraints(&fixed, &identities);
+        // Machines run on the fixed data of the degree of their columns.
+        let fixed_by_degree = fixed
+            .degrees()
+            .into_iter()
+            .map(|degree| (degree, fixed.with_degree(degree)))
+            .collect::<BTreeMap<_, _>>();
         let ExtractionOutput {
             mut fixed_lookup,
             mut machines,
@@ -192,6 +249,7 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             base_witnesses,
         } = machines::machine_extractor::split_out_machines(
             &fixed,
+            &fixed_by_degree,
             retained_identities,
             &constraints,
         );
@@ -201,9 +259,10 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             machines: Machines::from(machines.iter_mut()),
             query_callback: &mut query_callback,
         };
+        let main_degree = fixed.common_degree(&base_witnesses);
         let mut generator = Generator::new(
             "Main Machine".to_string(),
-            &fixed,
+            &fixed_by_degree[&main_degree],
             &[], // No connecting identities
             base_identities,
             base_witnesses,
@@ -214,7 +273,9 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             None,
         );
 
-        generator.run(&mut mutable_state);
+        generator
+            .run(&mut mutable_state)
+            .unwrap_or_else(|e| panic!("Witness generation failed.\n{e}"));
 
         // Get columns from machines
         let main_columns = generator
@@ -223,35 +284,56 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             .machines
             .iter_mut()
             .flat_map(|m| {
-                m.take_witness_col_values(mutable_state.fixed_lookup, mutable_state.query_callback)
-                    .into_iter()
+                // Columns the machine did not write to the witness store itself are written
+                // right away, so that the columns of all machines are never in memory at once.
+                ColumnSink::write_all(
+                    m.take_witness_col_values(
+                        mutable_state.fixed_lookup,
+                        mutable_state.query_callback,
+                    ),
+                    witness_store,
+                )
             })
-            .chain(main_columns)
+            .chain(ColumnSink::write_all(main_columns, witness_store))
             .collect::<BTreeMap<_, _>>();
 
         record_end(OUTER_CODE_NAME);
         reset_and_print_profile_summary();
 
         // Order columns according to the order of declaration.
-        let witness_cols = self
-            .analyzed
-            .committed_polys_in_source_order()
+        let witness_cols = witness_column_names
             .into_iter()
-            .filter(|(symbol, _)| symbol.stage.unwrap_or_default() <= self.stage.into())
-            .flat_map(|(p, _)| p.array_elements())
-            .map(|(name, _id)| {
-                let column = columns.remove(&name).unwrap();
+            .filter_map(|name| {
+                // All columns have already been written to the store.
+                let column = match columns.remove(&name) {
+                    None if witness_store.is_some() => return None,
+                    column => column.unwrap(),
+                };
                 assert!(!column.is_empty());
-                (name, column)
+                Some((name, column))
             })
             .collect::<Vec<_>>();
 
+        if witness_store.is_some() {
+            return witness_cols;
+        }
         log::debug!("Publics:");
         for (name, value) in extract_publics(&witness_cols, self.analyzed) {
             log::debug!("  {name:>30}: {value}");
         }
         witness_cols
     }
+
+    /// @returns the names of the witness columns up to the current stage, in source order.
+    fn witness_column_names(&self) -> Vec<String> {
+        self.analyzed
+            .committed_polys_in_source_order()
+            .into_iter()
+            .filter(|(symbol, _)| symbol.stage.unwrap_or_default() <= self.stage.into())
+            .flat_map(|(p, _)| p.array_elements())
+            .map(|(name, _id)| name)
+            .collect()
+    }
 }
 
 pub fn extract_publics<T: FieldElement>(
@@ -274,13 +356,24 @@ pub fn extract_publics<T: FieldElement>(
 }
 
 /// Data that is fixed for witness generation.
+#[derive(Clone)]
 pub struct FixedData<'a, T> {
     analyzed: &'a Analyzed<T>,
+    /// The degree of the machine this data is used for.
+    /// Defaults to the maximum degree of all namespaces.
     degree: DegreeType,
+    /// The degrees of all columns, if declared in a namespace with a degree.
+    column_degrees: HashMap<PolyID, DegreeType>,
     fixed_cols: FixedColumnMap<FixedColumn<'a, T>>,
     witness_cols: WitnessColumnMap<WitnessColumn<'a, T>>,
     column_by_name: HashMap<String, PolyID>,
     challenges: BTreeMap<u64, T>,
+    /// Whether to report failures with a [FailureReport].
+    diagnostics: bool,
+    /// If set, the machines write their columns to this store in chunks of the given
+    /// number of rows instead of returning them. The main machine writes its rows
+    /// as soon as they are finalized.
+    witness_store: Option<(&'a Mutex<ColumnStore<T>>, usize)>,
 }
 
 impl<'a, T: FieldElement> FixedData<'a, T> {
@@ -295,6 +388,15 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
             .map(|(name, values)| (name.clone(), values))
             .collect::<BTreeMap<_, _>>();
 
+        let degree = analyzed.degree.unwrap();
+        let column_degrees = analyzed
+            .definitions
+            .values()
+            .filter(|(symbol, _)| matches!(symbol.kind, SymbolKind::Poly(_)))
+            .filter_map(|(symbol, _)| Some((symbol, symbol.degree?)))
+            .flat_map(|(symbol, degree)| symbol.array_elements().map(move |(_, id)| (id, degree)))
+            .collect::<HashMap<_, _>>();
+
         let witness_cols =
             WitnessColumnMap::from(analyzed.committed_polys_in_source_order().iter().flat_map(
                 |(poly, value)| {
@@ -302,17 +404,24 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
                         .map(|(name, poly_id)| {
                             let external_values = external_witness_values.remove(name.as_str());
                             if let Some(external_values) = &external_values {
-                                if external_values.len() != analyzed.degree() as usize {
+                                let degree = column_degrees.get(&poly_id).cloned().unwrap_or(degree);
+                                if external_values.len() != degree as usize {
                                     log::debug!(
                                         "External witness values for column {} were only partially provided \
                                          (length is {} but the degree is {})",
                                         name,
                                         external_values.len(),
-                                        analyzed.degree()
+                                        degree
                                     );
                                 }
                             }
-                            WitnessColumn::new(poly_id.id as usize, &name, value, external_values)
+                            WitnessColumn::new(
+                                poly_id.id as usize,
+                                &name,
+                                value,
+                                external_values,
+                                &analyzed.definitions,
+                            )
                         })
                         .collect::<Vec<_>>()
                 },
@@ -334,7 +443,8 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
             FixedColumnMap::from(fixed_col_values.iter().map(|(n, v)| FixedColumn::new(n, v)));
         FixedData {
             analyzed,
-            degree: analyzed.degree(),
+            degree,
+            column_degrees,
             fixed_cols,
             witness_cols,
             column_by_name: analyzed
@@ -344,9 +454,60 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
                 .map(|(name, (symbol, _))| (name.clone(), symbol.into()))
                 .collect(),
             challenges,
+            diagnostics: false,
+            witness_store: None,
         }
     }
 
+    pub fn with_diagnostics(self, diagnostics: bool) -> Self {
+        FixedData {
+            diagnostics,
+            ..self
+        }
+    }
+
+    pub fn with_witness_store(
+        self,
+        witness_store: Option<(&'a Mutex<ColumnStore<T>>, usize)>,
+    ) -> Self {
+        FixedData {
+            witness_store,
+            ..self
+        }
+    }
+
+    /// @returns a copy of the data for a machine of the given degree.
+    fn with_degree(&self, degree: DegreeType) -> Self {
+        FixedData {
+            degree,
+            ..self.clone()
+        }
+    }
+
+    /// @returns the set of all column degrees, including the default degree.
+    fn degrees(&self) -> BTreeSet<DegreeType> {
+        self.column_degrees
+            .values()
+            .cloned()
+            .chain(std::iter::once(self.degree))
+            .collect()
+    }
+
+    /// @returns the degree of the given columns, which have to be declared in
+    /// namespaces of the same degree. Columns without a degree have the default degree.
+    fn common_degree<'b>(&self, ids: impl IntoIterator<Item = &'b PolyID>) -> DegreeType {
+        let degrees = ids
+            .into_iter()
+            .map(|id| self.column_degrees.get(id).cloned().unwrap_or(self.degree))
+            .collect::<BTreeSet<_>>();
+        assert!(
+            degrees.len() <= 1,
+            "Expected all columns of a machine to have the same degree, but found degrees {}",
+            degrees.iter().format(", ")
+        );
+        degrees.into_iter().next().unwrap_or(self.degree)
+    }
+
     fn witness_map_with<V: Clone>(&self, initial_value: V) -> WitnessColumnMap<V> {
         WitnessColumnMap::new(initial_value, self.witness_cols.len())
     }
@@ -372,6 +533,7 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
     }
 }
 
+#[derive(Clone)]
 pub struct FixedColumn<'a, T> {
     name: String,
     values: &'a Vec<T>,
@@ -384,37 +546,43 @@ impl<'a, T> FixedColumn<'a, T> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WitnessColumn<'a, T> {
     /// A polynomial reference that points to this column in the "current" row
     /// (i.e., the "next" flag is set to false).
     /// This is needed in situations where we want to update a cell when the
     /// update does not come from an identity (which also has an AlgebraicReference).
     poly: AlgebraicReference,
-    /// The prover query expression, if any.
-    query: Option<&'a Expression>,
+    /// The prover query expression and its compiled form, if any.
+    query: Option<(&'a Expression, Arc<CompiledFunction<'a, T>>)>,
     /// A list of externally computed witness values, if any.
     /// The length of this list must be equal to the degree.
     external_values: Option<&'a Vec<T>>,
 }
 
-impl<'a, T> WitnessColumn<'a, T> {
+impl<'a, T: FieldElement> WitnessColumn<'a, T> {
     pub fn new(
         id: usize,
         name: &str,
         value: &'a Option<FunctionValueDefinition>,
         external_values: Option<&'a Vec<T>>,
+        definitions: &'a HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
     ) -> WitnessColumn<'a, T> {
         let query = if let Some(FunctionValueDefinition::Expression(TypedExpression {
             e:
-                query @ Expression::LambdaExpression(LambdaExpression {
-                    kind: FunctionKind::Query,
-                    ..
-                }),
+                query @ Expression::LambdaExpression(
+                    lambda @ LambdaExpression {
+                        kind: FunctionKind::Query,
+                        ..
+                    },
+                ),
             ..
         })) = value
         {
-            Some(query)
+            Some((
+                query,
+                Arc::new(compiled_evaluator::compile(lambda, definitions)),
+            ))
         } else {
             None
         };
@@ -433,3 +601,71 @@ impl<'a, T> WitnessColumn<'a, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+    use pretty_assertions::assert_eq;
+    use test_log::test;
+
+    use crate::constant_evaluator;
+
+    use super::*;
+
+    #[test]
+    fn generate_into_store() {
+        let src = r#"namespace F(64);
+            col fixed FIRST = [1] + [0]*;
+            col witness x, y;
+            (1 - FIRST') * (x' - x - 1) = 0;
+            FIRST' * x' = 0;
+            y = x * x + 3;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let fixed = constant_evaluator::generate(&analyzed);
+        let witness = WitnessGenerator::new(&analyzed, &fixed, &unused_query_callback()).generate();
+
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let store = Mutex::new(ColumnStore::new(&dir).unwrap());
+        WitnessGenerator::new(&analyzed, &fixed, &unused_query_callback()).generate_into(&store, 6);
+        let store = store.into_inner().unwrap();
+        assert_eq!(store.column_names(), vec!["F.x", "F.y"]);
+        let names = store.column_names();
+        assert_eq!(
+            store
+                .read_columns(names.iter().map(|name| name.as_str()))
+                .collect::<Vec<_>>(),
+            witness
+        );
+    }
+
+    #[test]
+    fn generate_into_store_with_block_machine() {
+        let src = r#"namespace Add(16);
+            col witness A, B, C;
+            A + B = C;
+        namespace Main(16);
+            col fixed a(i) { i + 13 };
+            col fixed b(i) { (i + 19) * 17 };
+            col witness c;
+            col fixed CALL = [1, 0]*;
+            (1 - CALL) * c = 0;
+            CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let fixed = constant_evaluator::generate(&analyzed);
+        let witness = WitnessGenerator::new(&analyzed, &fixed, &unused_query_callback()).generate();
+
+        let dir = mktemp::Temp::new_dir().unwrap();
+        let store = Mutex::new(ColumnStore::new(&dir).unwrap());
+        WitnessGenerator::new(&analyzed, &fixed, &unused_query_callback()).generate_into(&store, 5);
+        let store = store.into_inner().unwrap();
+        assert_eq!(
+            store
+                .read_columns(witness.iter().map(|(name, _)| name.as_str()))
+                .collect::<Vec<_>>(),
+            witness
+        );
+    }
+}
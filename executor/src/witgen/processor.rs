@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
 
 use powdr_ast::{
     analyzed::{AlgebraicExpression as Expression, AlgebraicReference, Identity, PolyID},
@@ -13,7 +14,8 @@ use super::{
     data_structures::{column_map::WitnessColumnMap, finalizable_data::FinalizableData},
     identity_processor::IdentityProcessor,
     rows::{CellValue, Row, RowIndex, RowPair, RowUpdater, UnknownStrategy},
-    Constraints, EvalError, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
+    Constraints, EvalError, EvalValue, FailureReport, FixedData, IncompleteCause, MutableState,
+    QueryCallback,
 };
 
 type Left<'a, T> = Vec<AffineExpression<&'a AlgebraicReference, T>>;
@@ -374,6 +376,14 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         self.data.finalize_range(range)
     }
 
+    pub fn taken_rows(&self) -> Range<usize> {
+        self.data.taken_rows()
+    }
+
+    pub fn take_finalized_rows(&mut self, range: Range<usize>) -> Vec<(PolyID, Vec<T>)> {
+        self.data.take_finalized_rows(range)
+    }
+
     pub fn row(&self, i: usize) -> &Row<'a, T> {
         &self.data[i]
     }
@@ -382,6 +392,33 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         self.outer_query.is_some()
     }
 
+    /// Returns true if failures should be reported as a [FailureReport].
+    pub fn diagnostics(&self) -> bool {
+        self.fixed_data.diagnostics
+    }
+
+    /// Creates a [FailureReport] for the row pair starting at `row_index`,
+    /// listing the given identities as the ones that could not be solved.
+    pub fn failure_report<'d>(
+        &self,
+        machine: &str,
+        row_index: usize,
+        identities: impl IntoIterator<Item = &'d Identity<Expression<T>>>,
+        errors: &[EvalError<T>],
+    ) -> FailureReport<T>
+    where
+        T: 'd,
+    {
+        FailureReport::new(
+            machine,
+            (self.row_offset + row_index).into(),
+            (&self.data[row_index], &self.data[row_index + 1]),
+            self.witness_cols,
+            identities,
+            errors,
+        )
+    }
+
     /// Sets the ith row, extending the data if necessary.
     pub fn set_row(&mut self, i: usize, row: Row<'a, T>) {
         if i < self.data.len() {
@@ -5,7 +5,10 @@ use powdr_ast::analyzed::{
 };
 use powdr_ast::parsed::types::Type;
 use powdr_number::{BigInt, FieldElement};
-use powdr_pil_analyzer::evaluator::{self, Definitions, EvalError, SymbolLookup, Value};
+use powdr_pil_analyzer::{
+    compiled_evaluator::CompiledFunction,
+    evaluator::{Definitions, EvalError, SymbolLookup, Value},
+};
 
 use super::{rows::RowPair, Constraint, EvalResult, EvalValue, FixedData, IncompleteCause};
 
@@ -28,9 +31,9 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
     pub fn process_query(&mut self, rows: &RowPair<T>, poly_id: &PolyID) -> EvalResult<'a, T> {
         let column = &self.fixed_data.witness_cols[poly_id];
 
-        if let Some(query) = column.query.as_ref() {
+        if let Some((query, compiled)) = column.query.as_ref() {
             if rows.get_value(&column.poly).is_none() {
-                return self.process_witness_query(query, &column.poly, rows);
+                return self.process_witness_query(query, compiled, &column.poly, rows);
             }
         }
         // Either no query or the value is already known.
@@ -40,15 +43,16 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
     fn process_witness_query(
         &mut self,
         query: &'a Expression,
+        compiled: &CompiledFunction<'a, T>,
         poly: &'a AlgebraicReference,
         rows: &RowPair<T>,
     ) -> EvalResult<'a, T> {
-        let query_str = match self.interpolate_query(query, rows) {
-            Ok(query) => query,
+        let query_str = match self.interpolate_query(compiled, rows) {
+            Ok(Some(query)) => query,
+            // The query function does not provide a value on this row.
+            Ok(None) => return Ok(EvalValue::complete(vec![])),
             Err(e) => {
                 return match e {
-                    // TODO this mechanism should be replaced by a proper Option datatype.
-                    EvalError::NoMatch() => Ok(EvalValue::complete(vec![])),
                     EvalError::DataNotAvailable => {
                         Ok(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable))
                     }
@@ -74,11 +78,13 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
         )
     }
 
+    /// Evaluates the query function on the current row and returns the
+    /// query string if it returned `Some(query)`.
     fn interpolate_query(
         &self,
-        query: &'a Expression,
+        query: &CompiledFunction<'a, T>,
         rows: &RowPair<T>,
-    ) -> Result<String, EvalError> {
+    ) -> Result<Option<String>, EvalError> {
         let arguments = vec![Arc::new(Value::Integer(BigInt::from(u64::from(
             rows.current_row_index,
         ))))];
@@ -86,19 +92,27 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
             fixed_data: self.fixed_data,
             rows,
         };
-        let fun = evaluator::evaluate(query, &mut symbols)?;
-        evaluator::evaluate_function_call(fun, arguments, &mut symbols).map(|v| v.to_string())
+        let result = query.call(arguments, &mut symbols)?;
+        match result.as_ref() {
+            Value::Enum("std::utils::Option", "None", None) => Ok(None),
+            Value::Enum("std::utils::Option", "Some", Some(fields)) if fields.len() == 1 => {
+                Ok(Some(fields[0].to_string()))
+            }
+            _ => Err(EvalError::TypeError(format!(
+                "Expected prover query function to return an option, but got {result}."
+            ))),
+        }
     }
 }
 
 #[derive(Clone)]
-struct Symbols<'a, T: FieldElement> {
+struct Symbols<'a, 'b, T: FieldElement> {
     fixed_data: &'a FixedData<'a, T>,
-    rows: &'a RowPair<'a, 'a, T>,
+    rows: &'b RowPair<'b, 'b, T>,
 }
 
-impl<'a, T: FieldElement> SymbolLookup<'a, T> for Symbols<'a, T> {
-    fn lookup<'b>(
+impl<'a, 'b, T: FieldElement> SymbolLookup<'a, T> for Symbols<'a, 'b, T> {
+    fn lookup(
         &mut self,
         name: &'a str,
         type_args: Option<Vec<Type>>,
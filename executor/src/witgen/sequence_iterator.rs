@@ -169,8 +169,9 @@ where
 pub enum ProcessingSequenceIterator {
     /// The default strategy
     Default(DefaultSequenceIterator),
-    /// The machine has been run successfully before and the sequence is cached.
-    Cached(<Vec<SequenceStep> as IntoIterator>::IntoIter),
+    /// The machine has been run successfully before and the sequence is cached,
+    /// together with the number of cells that were known after the recorded run.
+    Cached(<Vec<SequenceStep> as IntoIterator>::IntoIter, usize),
     /// The machine has been run before, but did not succeed. There is no point in trying again.
     Incomplete,
 }
@@ -179,14 +180,22 @@ impl ProcessingSequenceIterator {
     pub fn report_progress(&mut self, progress_in_last_step: bool) {
         match self {
             Self::Default(it) => it.report_progress(progress_in_last_step),
-            Self::Cached(_) => {} // Progress is ignored
+            Self::Cached(..) => {} // Progress is ignored
             Self::Incomplete => unreachable!(),
         }
     }
 
+    /// Returns true if a cached sequence was used, but the block has fewer known cells
+    /// than after the run the sequence was recorded for. This happens if the order in which
+    /// values can be derived depends on the inputs (e.g. on the operation ID), so the cached
+    /// sequence tries to derive some values before their dependencies are known.
+    pub fn deviated_from_cache(&self, known_cells: usize) -> bool {
+        matches!(self, Self::Cached(_, expected) if known_cells < *expected)
+    }
+
     pub fn has_steps(&self) -> bool {
         match self {
-            Self::Default(_) | Self::Cached(_) => true,
+            Self::Default(_) | Self::Cached(..) => true,
             Self::Incomplete => false,
         }
     }
@@ -194,7 +203,7 @@ impl ProcessingSequenceIterator {
     pub fn is_cached(&self) -> bool {
         match self {
             Self::Default(_) => false,
-            Self::Cached(_) | Self::Incomplete => true,
+            Self::Cached(..) | Self::Incomplete => true,
         }
     }
 }
@@ -205,7 +214,7 @@ impl Iterator for ProcessingSequenceIterator {
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             Self::Default(it) => it.next(),
-            Self::Cached(it) => it.next(),
+            Self::Cached(it, _) => it.next(),
             Self::Incomplete => unreachable!(),
         }
     }
@@ -213,7 +222,8 @@ impl Iterator for ProcessingSequenceIterator {
 
 enum CacheEntry {
     /// The machine has been run successfully before and the sequence is cached.
-    Complete(Vec<SequenceStep>),
+    /// Also stores the number of cells that were known after that run.
+    Complete(Vec<SequenceStep>, usize),
     /// The machine has been run before, but did not succeed. There is no point in trying again.
     Incomplete,
 }
@@ -244,9 +254,12 @@ impl ProcessingSequenceCache {
         T: FieldElement,
     {
         match self.cache.get(&left.into()) {
-            Some(CacheEntry::Complete(cached_sequence)) => {
+            Some(CacheEntry::Complete(cached_sequence, known_cells)) => {
                 log::trace!("Using cached sequence");
-                ProcessingSequenceIterator::Cached(cached_sequence.clone().into_iter())
+                ProcessingSequenceIterator::Cached(
+                    cached_sequence.clone().into_iter(),
+                    *known_cells,
+                )
             }
             Some(CacheEntry::Incomplete) => ProcessingSequenceIterator::Incomplete,
             None => {
@@ -279,6 +292,7 @@ impl ProcessingSequenceCache {
         &mut self,
         left: &[AffineExpression<K, T>],
         sequence_iterator: ProcessingSequenceIterator,
+        known_cells: usize,
     ) where
         K: Copy + Ord,
         T: FieldElement,
@@ -287,11 +301,14 @@ impl ProcessingSequenceCache {
             ProcessingSequenceIterator::Default(it) => {
                 assert!(self
                     .cache
-                    .insert(left.into(), CacheEntry::Complete(it.progress_steps))
+                    .insert(
+                        left.into(),
+                        CacheEntry::Complete(it.progress_steps, known_cells)
+                    )
                     .is_none());
             }
             ProcessingSequenceIterator::Incomplete => unreachable!(),
-            ProcessingSequenceIterator::Cached(_) => {} // Already cached, do nothing
+            ProcessingSequenceIterator::Cached(..) => {} // Already cached, do nothing
         }
     }
 }
@@ -7,8 +7,10 @@ use powdr_number::{DegreeType, FieldElement};
 use powdr_parser_util::lines::indent;
 use std::cmp::max;
 use std::collections::HashSet;
+use std::sync::Mutex;
 use std::time::Instant;
 
+use crate::column_store::ColumnStore;
 use crate::witgen::identity_processor::{self};
 use crate::witgen::IncompleteCause;
 
@@ -16,7 +18,9 @@ use super::data_structures::finalizable_data::FinalizableData;
 use super::processor::{OuterQuery, Processor};
 
 use super::rows::{Row, RowFactory, RowIndex, UnknownStrategy};
-use super::{Constraints, EvalError, EvalValue, FixedData, MutableState, QueryCallback};
+use super::{
+    Constraints, EvalError, EvalResult, EvalValue, FixedData, MutableState, QueryCallback,
+};
 
 /// Maximal period checked during loop detection.
 const MAX_PERIOD: usize = 4;
@@ -41,9 +45,19 @@ impl<'a, T: FieldElement> CompletableIdentities<'a, T> {
             .iter_mut()
             .map(|(identity, complete)| (*identity, complete))
     }
+
+    /// Yields the identities that are not complete yet.
+    fn incomplete(&self) -> impl Iterator<Item = &'a Identity<Expression<T>>> + '_ {
+        self.identities_with_complete
+            .iter()
+            .filter(|(_, complete)| !complete)
+            .map(|(identity, _)| *identity)
+    }
 }
 
 pub struct VmProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
+    /// The name of the machine being run, used in failure reports.
+    name: String,
     /// The global index of the first row of [VmProcessor::data].
     row_offset: DegreeType,
     /// The witness columns belonging to this machine
@@ -63,7 +77,9 @@ pub struct VmProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
 }
 
 impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T, Q> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        name: String,
         row_offset: RowIndex,
         fixed_data: &'a FixedData<'a, T>,
         identities: &[&'a Identity<Expression<T>>],
@@ -86,6 +102,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         );
 
         VmProcessor {
+            name,
             row_offset: row_offset.into(),
             witnesses: witnesses.clone(),
             fixed_data,
@@ -110,7 +127,8 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
 
     /// Starting out with a single row (at a given offset), iteratively append rows
     /// until we have exhausted the rows or the latch expression (if available) evaluates to 1.
-    pub fn run(&mut self, is_main_run: bool) -> EvalValue<&'a AlgebraicReference, T> {
+    /// In diagnostic mode, a row that cannot be solved results in an [EvalError::FailureReport].
+    pub fn run(&mut self, is_main_run: bool) -> EvalResult<'a, T> {
         assert!(self.processor.len() == 1);
 
         if is_main_run {
@@ -129,6 +147,19 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         } else {
             log::Level::Debug
         };
+        // The rows of the main machine are written to the witness store (if any)
+        // once they are finalized.
+        let witness_store = self.fixed_data.witness_store.filter(|_| is_main_run);
+        let finalize_frequency = match witness_store {
+            Some((_, chunk_size)) => {
+                assert!(
+                    chunk_size > MAX_PERIOD,
+                    "The chunk size needs to be larger than {MAX_PERIOD}."
+                );
+                chunk_size as DegreeType
+            }
+            None => 10000,
+        };
         let rows_left = self.fixed_data.degree - self.row_offset + 1;
         let mut finalize_start = 1;
         for row_index in 0..rows_left {
@@ -136,12 +167,15 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                 self.maybe_log_performance(row_index);
             }
 
-            if (row_index + 1) % 10000 == 0 {
+            if (row_index + 1) % finalize_frequency == 0 {
                 // Periodically make sure most rows are finalized.
                 // Row 0 and the last MAX_PERIOD rows might be needed later, so they are not finalized.
-                let finalize_end = row_index as usize - MAX_PERIOD;
+                let finalize_end = (row_index as usize - MAX_PERIOD).max(finalize_start);
                 self.processor.finalize_range(finalize_start..finalize_end);
                 finalize_start = finalize_end;
+                if let Some((store, _)) = witness_store {
+                    self.spill_rows(store, finalize_end);
+                }
             }
 
             if row_index >= rows_left - 2 {
@@ -162,7 +196,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             }
             if let Some(period) = looping_period {
                 let proposed_row = self.processor.row(row_index as usize - period).clone();
-                if !self.try_proposed_row(row_index, proposed_row) {
+                if !self.try_proposed_row(row_index, proposed_row)? {
                     log::log!(
                         loop_detection_log_level,
                         "Looping failed. Trying to generate regularly again. (Use RUST_LOG=debug to see whether this happens more often.) {row_index} {rows_left}"
@@ -178,26 +212,26 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             // add and compute some values for the next row as well.
             if looping_period.is_none() && row_index != rows_left - 1 {
                 self.ensure_has_next_row(row_index);
-                outer_assignments.extend(self.compute_row(row_index).into_iter());
+                outer_assignments.extend(self.compute_row(row_index)?.into_iter());
 
                 // Evaluate latch expression and return if it evaluates to 1.
                 if let Some(latch) = self.processor.latch_value(row_index as usize) {
                     if latch {
                         log::trace!("Machine returns!");
                         if self.processor.finished_outer_query() {
-                            return EvalValue::complete(outer_assignments);
+                            return Ok(EvalValue::complete(outer_assignments));
                         } else {
-                            return EvalValue::incomplete_with_constraints(
+                            return Ok(EvalValue::incomplete_with_constraints(
                                 outer_assignments,
                                 IncompleteCause::BlockMachineLookupIncomplete,
-                            );
+                            ));
                         }
                     }
                 } else if self.processor.has_outer_query() {
                     // If we have an outer query (and therefore a latch expression),
                     // its value should be known at this point.
                     // Probably, we don't have all the necessary inputs.
-                    return EvalValue::incomplete(IncompleteCause::UnknownLatch);
+                    return Ok(EvalValue::incomplete(IncompleteCause::UnknownLatch));
                 }
             };
         }
@@ -211,7 +245,27 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             self.progress_bar.finish();
         }
 
-        EvalValue::complete(outer_assignments)
+        Ok(EvalValue::complete(outer_assignments))
+    }
+
+    /// Writes the finalized rows before `end` that are still in memory to the store
+    /// and removes them from memory.
+    /// Row 0 is kept, because it is merged with the last row at the end.
+    fn spill_rows(&mut self, store: &Mutex<ColumnStore<T>>, end: usize) {
+        let start = max(self.processor.taken_rows().end, 1);
+        if start >= end {
+            return;
+        }
+        let columns = self
+            .processor
+            .take_finalized_rows(start..end)
+            .into_iter()
+            .map(|(id, values)| (self.fixed_data.column_name(&id).to_string(), values))
+            .collect::<Vec<_>>();
+        store
+            .lock()
+            .unwrap()
+            .write_chunk(start as DegreeType + self.row_offset, &columns);
     }
 
     /// Checks if the last rows are repeating and returns the period.
@@ -244,7 +298,10 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         }
     }
 
-    fn compute_row(&mut self, row_index: DegreeType) -> Constraints<&'a AlgebraicReference, T> {
+    fn compute_row(
+        &mut self,
+        row_index: DegreeType,
+    ) -> Result<Constraints<&'a AlgebraicReference, T>, EvalError<T>> {
         log::trace!(
             "===== Starting to process row: {}",
             row_index + self.row_offset
@@ -264,9 +321,20 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                     .into_iter()
                     .chain(self.loop_until_no_progress(row_index, &mut identities_with_next_ref)?)
                     .collect::<Vec<_>>())
-            })
-            .map_err(|e| self.report_failure_and_panic_unsatisfiable(row_index, e))
-            .unwrap();
+            });
+        let outer_assignments = match outer_assignments {
+            Ok(outer_assignments) => outer_assignments,
+            Err(e) => {
+                if self.processor.diagnostics() {
+                    return Err(self.failure_report(
+                        row_index,
+                        [&identities_without_next_ref, &identities_with_next_ref],
+                        &e,
+                    ));
+                }
+                self.report_failure_and_panic_unsatisfiable(row_index, e)
+            }
+        };
 
         // Check that the computed row is "final" by asserting that all unknown values can
         // be set to 0.
@@ -276,20 +344,29 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             log::trace!(
                 "  Checking that remaining identities hold when unknown values are set to 0"
             );
-            self.process_identities(
-                row_index,
-                &mut identities_without_next_ref,
-                UnknownStrategy::Zero,
-            )
-            .and_then(|_| {
-                self.process_identities(
+            let result = self
+                .process_identities(
                     row_index,
-                    &mut identities_with_next_ref,
+                    &mut identities_without_next_ref,
                     UnknownStrategy::Zero,
                 )
-            })
-            .map_err(|e| self.report_failure_and_panic_under_constrained(row_index, e))
-            .unwrap();
+                .and_then(|_| {
+                    self.process_identities(
+                        row_index,
+                        &mut identities_with_next_ref,
+                        UnknownStrategy::Zero,
+                    )
+                });
+            if let Err(e) = result {
+                if self.processor.diagnostics() {
+                    return Err(self.failure_report(
+                        row_index,
+                        [&identities_without_next_ref, &identities_with_next_ref],
+                        &e,
+                    ));
+                }
+                self.report_failure_and_panic_under_constrained(row_index, e)
+            }
         }
 
         log::trace!(
@@ -304,7 +381,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             )
         );
 
-        outer_assignments
+        Ok(outer_assignments)
     }
 
     /// Loops over all identities and queries, until no further progress is made.
@@ -397,6 +474,24 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         }
     }
 
+    /// Reports the incomplete identities together with their unknown cells
+    /// (used in diagnostic mode).
+    fn failure_report(
+        &self,
+        row_index: DegreeType,
+        identities: [&CompletableIdentities<'a, T>; 2],
+        failures: &[EvalError<T>],
+    ) -> EvalError<T> {
+        let report = self.processor.failure_report(
+            &self.name,
+            row_index as usize,
+            identities.into_iter().flat_map(|i| i.incomplete()),
+            failures,
+        );
+        log::error!("\n{report}\n");
+        EvalError::FailureReport(Box::new(report))
+    }
+
     fn report_failure_and_panic_unsatisfiable(
         &self,
         row_index: DegreeType,
@@ -477,7 +572,11 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
     /// Verifies the proposed values for the next row.
     /// TODO this is bad for machines because we might introduce rows in the machine that are then
     /// not used.
-    fn try_proposed_row(&mut self, row_index: DegreeType, proposed_row: Row<'a, T>) -> bool {
+    fn try_proposed_row(
+        &mut self,
+        row_index: DegreeType,
+        proposed_row: Row<'a, T>,
+    ) -> Result<bool, EvalError<T>> {
         let constraints_valid = self.identities_with_next_ref.iter().all(|i| {
             self.processor
                 .check_row_pair(row_index as usize, &proposed_row, i, true)
@@ -493,9 +592,9 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             // If it doesn't, we re-run compute_next_row on the previous row in order to
             // correctly forward-propagate values via next references.
             self.ensure_has_next_row(row_index - 1);
-            self.compute_row(row_index - 1);
+            self.compute_row(row_index - 1)?;
         }
-        constraints_valid
+        Ok(constraints_valid)
     }
 
     fn maybe_log_performance(&mut self, row_index: DegreeType) {
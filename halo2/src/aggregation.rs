@@ -63,6 +63,7 @@ const R_P: usize = 60;
 type Svk = KzgSuccinctVerifyingKey<G1Affine>;
 type BaseFieldEccChip = halo2_wrong_ecc::BaseFieldEccChip<G1Affine, LIMBS, BITS>;
 type Halo2Loader<'a> = loader::halo2::Halo2Loader<'a, G1Affine, BaseFieldEccChip>;
+type Halo2Scalar<'a> = loader::halo2::Scalar<'a, G1Affine, BaseFieldEccChip>;
 pub type PoseidonTranscript<L, S> =
     system::halo2::transcript::halo2::PoseidonTranscript<G1Affine, L, S, T, RATE, R_F, R_P>;
 
@@ -135,12 +136,18 @@ impl SnarkWitness {
     }
 }
 
+/// Verifies the snarks in-circuit and accumulates them.
+///
+/// Returns the accumulator and the loaded instances of all the snarks, in order.
 pub fn aggregate<'a>(
     svk: &Svk,
     loader: &Rc<Halo2Loader<'a>>,
     snarks: &[SnarkWitness],
     as_proof: Value<&'_ [u8]>,
-) -> KzgAccumulator<G1Affine, Rc<Halo2Loader<'a>>> {
+) -> (
+    KzgAccumulator<G1Affine, Rc<Halo2Loader<'a>>>,
+    Vec<Halo2Scalar<'a>>,
+) {
     let assign_instances = |instances: &[Vec<Value<Fr>>]| {
         instances
             .iter()
@@ -153,6 +160,7 @@ pub fn aggregate<'a>(
             .collect_vec()
     };
 
+    let mut all_instances = Vec::new();
     let accumulators = snarks
         .iter()
         .flat_map(|snark| {
@@ -163,7 +171,10 @@ pub fn aggregate<'a>(
             let proof =
                 PlonkSuccinctVerifier::read_proof(svk, &protocol, &instances, &mut transcript)
                     .unwrap();
-            PlonkSuccinctVerifier::verify(svk, &protocol, &instances, &proof).unwrap()
+            let accumulators =
+                PlonkSuccinctVerifier::verify(svk, &protocol, &instances, &proof).unwrap();
+            all_instances.extend(instances.into_iter().flatten());
+            accumulators
         })
         .collect_vec();
 
@@ -173,7 +184,7 @@ pub fn aggregate<'a>(
         As::verify(&Default::default(), &accumulators, &proof).unwrap()
     };
 
-    accumulator
+    (accumulator, all_instances)
 }
 
 #[derive(Clone)]
@@ -213,6 +224,11 @@ impl AggregationConfig {
     }
 }
 
+/// A circuit verifying and accumulating snarks.
+///
+/// Its instance column contains the limbs of the accumulator, followed by the
+/// instances of the aggregated snarks, which are copy-constrained to the
+/// instances used for their verification.
 #[derive(Clone)]
 pub struct AggregationCircuit {
     svk: Svk,
@@ -254,7 +270,14 @@ impl AggregationCircuit {
         let KzgAccumulator { lhs, rhs } = accumulator;
         let instances = [lhs.x, lhs.y, rhs.x, rhs.y]
             .map(fe_to_limbs::<_, _, LIMBS, BITS>)
-            .concat();
+            .into_iter()
+            .flatten()
+            .chain(
+                snarks
+                    .iter()
+                    .flat_map(|snark| snark.instances.iter().flatten().copied()),
+            )
+            .collect();
 
         Self {
             svk,
@@ -283,8 +306,13 @@ impl AggregationCircuit {
         (0..4 * LIMBS).map(|idx| (0, idx)).collect()
     }
 
-    pub fn num_instance() -> Vec<usize> {
-        vec![4 * LIMBS]
+    pub fn num_instance(&self) -> Vec<usize> {
+        let num_snark_instances = self
+            .snarks
+            .iter()
+            .flat_map(|snark| &snark.protocol.num_instance)
+            .sum::<usize>();
+        vec![4 * LIMBS + num_snark_instances]
     }
 
     pub fn instances(&self) -> Vec<Vec<Fr>> {
@@ -332,14 +360,15 @@ impl Circuit<Fr> for AggregationCircuit {
 
         range_chip.load_table(&mut layouter)?;
 
-        let accumulator_limbs = layouter.assign_region(
+        let public_cells = layouter.assign_region(
             || "",
             |region| {
                 let ctx = RegionCtx::new(region, 0);
 
                 let ecc_chip = config.ecc_chip();
                 let loader = Halo2Loader::new(ecc_chip, ctx);
-                let accumulator = aggregate(&self.svk, &loader, &self.snarks, self.as_proof());
+                let (accumulator, instances) =
+                    aggregate(&self.svk, &loader, &self.snarks, self.as_proof());
 
                 let accumulator_limbs = [accumulator.lhs, accumulator.rhs]
                     .iter()
@@ -352,12 +381,14 @@ impl Circuit<Fr> for AggregationCircuit {
                     .into_iter()
                     .flatten();
 
-                Ok(accumulator_limbs)
+                Ok(accumulator_limbs
+                    .chain(instances.iter().map(|instance| instance.assigned()))
+                    .collect_vec())
             },
         )?;
 
-        for (row, limb) in accumulator_limbs.enumerate() {
-            main_gate.expose_public(layouter.namespace(|| ""), limb, row)?;
+        for (row, cell) in public_cells.into_iter().enumerate() {
+            main_gate.expose_public(layouter.namespace(|| ""), cell, row)?;
         }
 
         Ok(())
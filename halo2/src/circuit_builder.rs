@@ -1,4 +1,8 @@
-use std::{cmp::max, collections::BTreeMap, iter};
+use std::{
+    cmp::max,
+    collections::{BTreeMap, HashMap},
+    iter,
+};
 
 use halo2_curves::ff::PrimeField;
 use halo2_proofs::{
@@ -9,7 +13,7 @@ use halo2_proofs::{
     },
     poly::Rotation,
 };
-use powdr_executor::witgen::WitgenCallback;
+use powdr_executor::{constraint_checker::connect_cell_labels, witgen::WitgenCallback};
 
 use powdr_ast::{
     analyzed::{AlgebraicBinaryOperator, AlgebraicExpression},
@@ -19,7 +23,7 @@ use powdr_ast::{
     analyzed::{Analyzed, IdentityKind},
     parsed::visitor::ExpressionVisitable,
 };
-use powdr_number::FieldElement;
+use powdr_number::{DegreeType, FieldElement};
 
 const ENABLE_NAME: &str = "__enable";
 const INSTANCE_NAME: &str = "__instance";
@@ -76,6 +80,8 @@ pub(crate) struct PowdrCircuit<'a, T> {
     analyzed: &'a Analyzed<T>,
     /// The value of the fixed columns
     fixed: &'a [(String, Vec<T>)],
+    /// The number of rows, shared by all columns
+    degree: DegreeType,
     /// The value of the witness columns, if set
     witness: Option<&'a [(String, Vec<T>)]>,
     /// Column name and index of the public cells
@@ -85,26 +91,35 @@ pub(crate) struct PowdrCircuit<'a, T> {
 }
 
 impl<'a, T: FieldElement> PowdrCircuit<'a, T> {
-    pub(crate) fn new(analyzed: &'a Analyzed<T>, fixed: &'a [(String, Vec<T>)]) -> Self {
-        let mut publics = analyzed
-            .public_declarations
-            .values()
-            .map(|public_declaration| {
+    pub(crate) fn new(
+        analyzed: &'a Analyzed<T>,
+        fixed: &'a [(String, Vec<T>)],
+    ) -> Result<Self, String> {
+        let degree = analyzed.unique_degree()?;
+        // Use the same order as `extract_publics`, so that the instance column
+        // matches the public values reported to the verifier.
+        let publics = analyzed
+            .public_declarations_in_source_order()
+            .into_iter()
+            .map(|(_, public_declaration)| {
                 let witness_name = public_declaration.referenced_poly_name();
                 let witness_offset = public_declaration.index as usize;
                 (witness_name, witness_offset)
             })
             .collect::<Vec<_>>();
-        // Sort, so that the order is deterministic
-        publics.sort();
 
-        Self {
+        Ok(Self {
             analyzed,
             fixed,
+            degree,
             witness: None,
             publics,
             witgen_callback: None,
-        }
+        })
+    }
+
+    pub(crate) fn degree(&self) -> DegreeType {
+        self.degree
     }
 
     pub(crate) fn with_witness(self, witness: &'a [(String, Vec<T>)]) -> Self {
@@ -121,6 +136,80 @@ impl<'a, T: FieldElement> PowdrCircuit<'a, T> {
         }
     }
 
+    /// Returns the pairs of witness cells `(column name, row)` that have to be
+    /// equal because of connect identities.
+    ///
+    /// The connection columns use the encoding of [connect_cell_labels].
+    fn copy_constraints(&self) -> Vec<((&'a str, usize), (&'a str, usize))> {
+        let fixed = self
+            .fixed
+            .iter()
+            .map(|(name, values)| (name.as_str(), values))
+            .collect::<BTreeMap<_, _>>();
+        let degree = self.degree;
+
+        self.analyzed
+            .identities
+            .iter()
+            .filter(|id| id.kind == IdentityKind::Connect)
+            .flat_map(|id| {
+                assert!(
+                    id.left.selector.is_none() && id.right.selector.is_none(),
+                    "Selectors are not supported in connect identities: {id}"
+                );
+                let columns = id
+                    .left
+                    .expressions
+                    .iter()
+                    .map(|expr| match expr {
+                        AlgebraicExpression::Reference(r) if r.is_witness() && !r.next => {
+                            r.name.as_str()
+                        }
+                        _ => panic!("Expected a witness column in connect identity, got: {expr}"),
+                    })
+                    .collect::<Vec<_>>();
+                let connections = id
+                    .right
+                    .expressions
+                    .iter()
+                    .map(|expr| match expr {
+                        AlgebraicExpression::Reference(r) if r.is_fixed() && !r.next => {
+                            fixed[r.name.as_str()]
+                        }
+                        _ => panic!("Expected a fixed column in connect identity, got: {expr}"),
+                    })
+                    .collect::<Vec<_>>();
+
+                let cells_by_label = connect_cell_labels::<T>(columns.len(), degree)
+                    .unwrap()
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(column, labels)| {
+                        labels
+                            .into_iter()
+                            .enumerate()
+                            .map(move |(row, label)| (label, (column, row)))
+                    })
+                    .collect::<HashMap<_, _>>();
+
+                (0..columns.len())
+                    .flat_map(|column| (0..degree as usize).map(move |row| (column, row)))
+                    .filter_map(|(column, row)| {
+                        let label = connections[column][row];
+                        let &(connected_column, connected_row) =
+                            cells_by_label.get(&label).unwrap_or_else(|| {
+                                panic!("Connection value {label} does not refer to any cell: {id}")
+                            });
+                        ((column, row) != (connected_column, connected_row)).then_some((
+                            (columns[column], row),
+                            (columns[connected_column], connected_row),
+                        ))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
     /// Computes the instance column from the witness
     pub(crate) fn instance_column<F: PrimeField<Repr = [u8; 32]>>(&self) -> Vec<F> {
         let witness = self
@@ -276,7 +365,9 @@ impl<'a, T: FieldElement, F: PrimeField<Repr = [u8; 32]>> Circuit<F> for PowdrCi
             match id.kind {
                 // Already handled above
                 IdentityKind::Polynomial => {}
-                IdentityKind::Connect => unimplemented!(),
+                // Turned into copy constraints in synthesize(), once the
+                // values of the connection (fixed) columns are known.
+                IdentityKind::Connect => {}
                 IdentityKind::Plookup => {
                     let name = id.to_string();
                     meta.lookup_any(&name, |meta| {
@@ -376,7 +467,7 @@ impl<'a, T: FieldElement, F: PrimeField<Repr = [u8; 32]>> Circuit<F> for PowdrCi
                         )?;
                     }
                 }
-                let degree = self.analyzed.degree() as usize;
+                let degree = self.degree as usize;
                 for i in 0..(2 * degree) {
                     let value = F::from((i < degree) as u64);
                     region.assign_fixed(
@@ -387,11 +478,20 @@ impl<'a, T: FieldElement, F: PrimeField<Repr = [u8; 32]>> Circuit<F> for PowdrCi
                     )?;
                 }
 
-                let publics = self
-                    .publics
+                // Several publics can refer to the same cell, so we map each cell
+                // to all the instance rows it is exposed at.
+                let mut publics = BTreeMap::<_, Vec<_>>::new();
+                for (i, p) in self.publics.iter().enumerate() {
+                    publics.entry(p).or_default().push(i);
+                }
+
+                // Collect the cells of witness columns used in connect identities,
+                // which are later copy-constrained to each other.
+                let copy_constraints = self.copy_constraints();
+                let mut connected_cells = copy_constraints
                     .iter()
-                    .enumerate()
-                    .map(|(i, p)| (p, i))
+                    .flat_map(|(a, b)| [a.0, b.0])
+                    .map(|name| (name, vec![]))
                     .collect::<BTreeMap<_, _>>();
 
                 // Set witness values
@@ -425,12 +525,25 @@ impl<'a, T: FieldElement, F: PrimeField<Repr = [u8; 32]>> Circuit<F> for PowdrCi
 
                         // Collect public cells, which are later copy-constrained to equal
                         // a cell in the instance column.
-                        if let Some(&instance_index) = publics.get(&(name.clone(), i)) {
-                            public_cells.push((instance_index, assigned_cell));
+                        if let Some(instance_indices) = publics.get(&(name.clone(), i)) {
+                            for &instance_index in instance_indices {
+                                public_cells.push((instance_index, assigned_cell.clone()));
+                            }
+                        }
+
+                        if let Some(cells) = connected_cells.get_mut(name.as_str()) {
+                            cells.push(assigned_cell.cell());
                         }
                     }
                 }
 
+                for ((column_a, row_a), (column_b, row_b)) in copy_constraints {
+                    region.constrain_equal(
+                        connected_cells[column_a][row_a],
+                        connected_cells[column_b][row_b],
+                    )?;
+                }
+
                 Ok(public_cells)
             },
         )?;
@@ -20,12 +20,12 @@ pub fn mock_prove<T: FieldElement>(
     // double the row count in order to make space for the cells introduced by the backend
     // TODO: use a precise count of the extra rows needed to avoid using so many rows
 
-    let circuit_row_count_log = usize::BITS - pil.degree().leading_zeros();
-    let expanded_row_count_log = circuit_row_count_log + 1;
-
-    let circuit = PowdrCircuit::new(pil, constants)
+    let circuit = PowdrCircuit::new(pil, constants)?
         .with_witness(witness)
         .with_witgen_callback(witgen_callback);
+    let circuit_row_count_log = usize::BITS - circuit.degree().leading_zeros();
+    let expanded_row_count_log = circuit_row_count_log + 1;
+
     let mock_prover = MockProver::<Fr>::run(
         expanded_row_count_log,
         &circuit,
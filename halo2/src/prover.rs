@@ -67,14 +67,17 @@ impl<'a, F: FieldElement> Halo2Prover<'a, F> {
     ) -> Result<Self, io::Error> {
         Self::assert_field_is_bn254();
 
+        let degree = analyzed
+            .unique_degree()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
         let params = setup
             .map(|mut setup| ParamsKZG::<Bn256>::read(&mut setup))
             .transpose()?
             .map(|mut params| {
-                params.downsize(degree_bits(analyzed.degree()));
+                params.downsize(degree_bits(degree));
                 params
             })
-            .unwrap_or_else(|| generate_setup(analyzed.degree()));
+            .unwrap_or_else(|| generate_setup(degree));
 
         Ok(Self {
             analyzed,
@@ -95,7 +98,7 @@ impl<'a, F: FieldElement> Halo2Prover<'a, F> {
     ) -> Result<Vec<u8>, String> {
         log::info!("Starting proof generation...");
 
-        let circuit = PowdrCircuit::new(self.analyzed, self.fixed)
+        let circuit = PowdrCircuit::new(self.analyzed, self.fixed)?
             .with_witgen_callback(witgen_callback)
             .with_witness(witness);
         let publics = vec![circuit.instance_column()];
@@ -146,14 +149,9 @@ impl<'a, F: FieldElement> Halo2Prover<'a, F> {
 
         log::info!("Generating circuit for app snark...");
 
-        let circuit_app = PowdrCircuit::new(self.analyzed, self.fixed).with_witness(witness);
+        let circuit_app = PowdrCircuit::new(self.analyzed, self.fixed)?.with_witness(witness);
         let publics = vec![circuit_app.instance_column()];
 
-        assert_eq!(publics.len(), 1);
-        if !publics[0].is_empty() {
-            unimplemented!("Public inputs are not supported yet");
-        }
-
         log::info!("Generating VK for app snark...");
         let vk_app = keygen_vk(&self.params, &circuit_app).unwrap();
 
@@ -161,7 +159,7 @@ impl<'a, F: FieldElement> Halo2Prover<'a, F> {
         let protocol_app = compile(
             &self.params,
             &vk_app,
-            Config::kzg().with_num_instance(vec![]),
+            Config::kzg().with_num_instance(vec![publics[0].len()]),
         );
         let empty_snark = aggregation::Snark::new_without_witness(protocol_app.clone());
         let agg_circuit =
@@ -175,13 +173,15 @@ impl<'a, F: FieldElement> Halo2Prover<'a, F> {
         let deployment_code = aggregation::gen_aggregation_evm_verifier(
             &self.params,
             pk_aggr.get_vk(),
-            aggregation::AggregationCircuit::num_instance(),
+            agg_circuit.num_instance(),
             aggregation::AggregationCircuit::accumulator_indices(),
         );
 
         log::info!("Generating aggregated proof...");
         let start = Instant::now();
-        let snark = aggregation::Snark::new(protocol_app, vec![], proof);
+        // The public values of the app snark are exposed by the aggregated
+        // proof, after the accumulator.
+        let snark = aggregation::Snark::new(protocol_app, publics, proof);
         let agg_circuit_with_proof = aggregation::AggregationCircuit::new(&self.params, [snark]);
         let proof = gen_proof::<_, _, EvmTranscript<G1Affine, _, _, _>>(
             &self.params,
@@ -196,7 +196,7 @@ impl<'a, F: FieldElement> Halo2Prover<'a, F> {
             &vk_aggr,
             &self.params,
             &proof,
-            &publics,
+            &agg_circuit_with_proof.instances(),
         ) {
             Ok(_) => {}
             Err(e) => {
@@ -223,7 +223,7 @@ impl<'a, F: FieldElement> Halo2Prover<'a, F> {
     }
 
     pub fn verification_key(&self) -> Result<VerifyingKey<G1Affine>, String> {
-        let circuit = PowdrCircuit::new(self.analyzed, self.fixed);
+        let circuit = PowdrCircuit::new(self.analyzed, self.fixed)?;
         keygen_vk(&self.params, &circuit).map_err(|e| e.to_string())
     }
 
@@ -0,0 +1,3 @@
+//! Native implementations of the hash functions of the powdr standard library.
+
+pub mod poseidon_gl;
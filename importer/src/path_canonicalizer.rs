@@ -3,20 +3,23 @@ use std::{
     collections::{BTreeMap, BTreeSet, HashSet},
     convert::Infallible,
     iter::once,
+    str::FromStr,
 };
 
 use powdr_ast::parsed::{
     asm::{
         ASMModule, ASMProgram, AbsoluteSymbolPath, Import, Instruction, InstructionBody,
-        LinkDeclaration, Machine, MachineStatement, Module, ModuleRef, ModuleStatement,
-        SymbolDefinition, SymbolValue, SymbolValueRef,
+        LinkDeclaration, Machine, MachineStatement, Module, ModuleRef, ModuleStatement, Part,
+        SymbolDefinition, SymbolPath, SymbolValue, SymbolValueRef,
     },
     folder::Folder,
-    types::{Type, TypeScheme},
+    types::{is_builtin_trait, Type, TypeBounds, TypeScheme},
     visitor::{Children, ExpressionVisitable},
-    ArrayLiteral, EnumDeclaration, EnumVariant, Expression, FunctionCall, IndexAccess,
-    LambdaExpression, LetStatementInsideBlock, MatchArm, PilStatement, StatementInsideBlock,
-    TypedExpression,
+    ArrayLiteral, EnumDeclaration, EnumVariant, Expression, FieldAccess, FunctionCall, IndexAccess,
+    LambdaExpression, LetStatementInsideBlock, MatchArm, NamedExpression,
+    NamespacedPolynomialReference, Pattern, PilStatement, StatementInsideBlock, StructDeclaration,
+    StructExpression, StructField, TraitDeclaration, TraitFunction, TraitImplementation,
+    TypeDeclaration, TypedExpression,
 };
 
 /// Changes all symbol references (symbol paths) from relative paths
@@ -79,11 +82,8 @@ impl<'a> Folder for Canonicalizer<'a> {
                             },
                             SymbolValue::Expression(mut exp) => {
                                 if let Some(type_scheme) = &mut exp.type_scheme {
-                                    type_scheme
-                                        .ty
-                                        .map_to_type_vars(&type_scheme.vars.vars().collect());
-                                    canonicalize_inside_type(
-                                        &mut type_scheme.ty,
+                                    canonicalize_inside_type_scheme(
+                                        type_scheme,
                                         &self.path,
                                         self.paths,
                                     );
@@ -91,28 +91,81 @@ impl<'a> Folder for Canonicalizer<'a> {
                                 canonicalize_inside_expression(&mut exp.e, &self.path, self.paths);
                                 Some(Ok(SymbolValue::Expression(exp)))
                             }
-                            SymbolValue::TypeDeclaration(mut enum_decl) => {
+                            SymbolValue::TypeDeclaration(TypeDeclaration::Enum(mut enum_decl)) => {
+                                canonicalize_inside_type_bounds(
+                                    &mut enum_decl.type_vars,
+                                    &self.path,
+                                    self.paths,
+                                );
+                                let type_vars = enum_decl.type_vars.vars().collect();
                                 for variant in &mut enum_decl.variants {
                                     if let Some(fields) = &mut variant.fields {
                                         for field in fields {
+                                            field.map_to_type_vars(&type_vars);
                                             canonicalize_inside_type(field, &self.path, self.paths);
                                         }
                                     }
                                 }
-                                Some(Ok(SymbolValue::TypeDeclaration(enum_decl)))
+                                Some(Ok(SymbolValue::TypeDeclaration(enum_decl.into())))
+                            }
+                            SymbolValue::TypeDeclaration(TypeDeclaration::Struct(
+                                mut struct_decl,
+                            )) => {
+                                for field in &mut struct_decl.fields {
+                                    canonicalize_inside_type(&mut field.ty, &self.path, self.paths);
+                                }
+                                Some(Ok(SymbolValue::TypeDeclaration(struct_decl.into())))
+                            }
+                            SymbolValue::TraitDeclaration(mut trait_decl) => {
+                                let type_vars = trait_decl.type_vars.iter().collect();
+                                for function in &mut trait_decl.functions {
+                                    function.ty.map_to_type_vars(&type_vars);
+                                    canonicalize_inside_type(
+                                        &mut function.ty,
+                                        &self.path,
+                                        self.paths,
+                                    );
+                                }
+                                Some(Ok(SymbolValue::TraitDeclaration(trait_decl)))
                             }
                         }
                         .map(|value| value.map(|value| SymbolDefinition { name, value }.into()))
                     }
+                    ModuleStatement::TraitImplementation(mut trait_impl) => {
+                        let trait_path = self.path.clone().join(trait_impl.name.clone());
+                        trait_impl.name = self.paths[&trait_path].relative_to(&Default::default());
+                        canonicalize_inside_type_bounds(
+                            &mut trait_impl.type_vars,
+                            &self.path,
+                            self.paths,
+                        );
+                        let type_vars = trait_impl.type_vars.vars().collect();
+                        for ty in &mut trait_impl.type_args {
+                            ty.map_to_type_vars(&type_vars);
+                            canonicalize_inside_type(ty, &self.path, self.paths);
+                        }
+                        for function in &mut trait_impl.functions {
+                            canonicalize_inside_expression(
+                                &mut function.body,
+                                &self.path,
+                                self.paths,
+                            );
+                        }
+                        Some(Ok(trait_impl.into()))
+                    }
                 })
                 .collect::<Result<_, _>>()?,
         })
     }
 
     fn fold_machine(&mut self, mut machine: Machine) -> Result<Machine, Self::Error> {
+        for param in &mut machine.params {
+            let p = self.path.clone().join(param.ty.clone());
+            param.ty = self.paths.get(&p).cloned().unwrap().into();
+        }
         for s in &mut machine.statements {
             match s {
-                MachineStatement::Submachine(_, path, _) => {
+                MachineStatement::Submachine(_, path, _, _) => {
                     let p = self.path.clone().join(path.clone());
                     *path = self.paths.get(&p).cloned().unwrap().into();
                 }
@@ -138,6 +191,33 @@ impl<'a> Folder for Canonicalizer<'a> {
                         canonicalize_inside_expression(e, &self.path, self.paths);
                     }
                 }
+                // canonicalize rhs input expressions for `instr` and `link` declarations
+                MachineStatement::LinkDeclaration(
+                    _,
+                    LinkDeclaration {
+                        to: callable_ref, ..
+                    },
+                )
+                | MachineStatement::InstructionDeclaration(
+                    _,
+                    _,
+                    Instruction {
+                        body: InstructionBody::CallablePlookup(callable_ref),
+                        ..
+                    },
+                )
+                | MachineStatement::InstructionDeclaration(
+                    _,
+                    _,
+                    Instruction {
+                        body: InstructionBody::CallablePermutation(callable_ref),
+                        ..
+                    },
+                ) => {
+                    for e in callable_ref.params.inputs.iter_mut() {
+                        canonicalize_inside_expression(e, &self.path, self.paths);
+                    }
+                }
                 _ => {}
             }
         }
@@ -167,6 +247,12 @@ fn free_inputs_in_expression<'a>(
             free_inputs_in_expression(function)
                 .chain(arguments.iter().flat_map(|e| free_inputs_in_expression(e))),
         ),
+        Expression::StructExpression(struct_expr) => Box::new(
+            struct_expr
+                .children()
+                .flat_map(|e| free_inputs_in_expression(e)),
+        ),
+        Expression::FieldAccess(FieldAccess { object, .. }) => free_inputs_in_expression(object),
         // These should really not appear in assembly statements.
         Expression::Tuple(_) => todo!(),
         Expression::LambdaExpression(_) => todo!(),
@@ -202,6 +288,14 @@ fn free_inputs_in_expression_mut<'a>(
                     .flat_map(|e| free_inputs_in_expression_mut(e)),
             ),
         ),
+        Expression::StructExpression(struct_expr) => Box::new(
+            struct_expr
+                .children_mut()
+                .flat_map(|e| free_inputs_in_expression_mut(e)),
+        ),
+        Expression::FieldAccess(FieldAccess { object, .. }) => {
+            free_inputs_in_expression_mut(object)
+        }
         // These should really not appear in assembly statements.
         Expression::Tuple(_) => todo!(),
         Expression::LambdaExpression(_) => todo!(),
@@ -218,18 +312,82 @@ fn canonicalize_inside_expression(
     path: &AbsoluteSymbolPath,
     paths: &'_ PathMap,
 ) {
-    e.pre_visit_expressions_mut(&mut |e| {
-        if let Expression::Reference(reference) = e {
-            // If resolving the reference fails, we assume it is a local variable that has been checked below.
+    e.pre_visit_expressions_mut(&mut |e| match e {
+        Expression::Reference(reference) => {
+            // If resolving the reference fails, we assume it is a local variable or a field access
+            // that have been checked below.
             if let Some(n) = paths.get(&path.clone().join(reference.path.clone())) {
                 *reference = n.relative_to(&Default::default()).into();
+            } else if let Some((object, field)) = try_to_field_access(reference) {
+                *e = Expression::FieldAccess(FieldAccess {
+                    object: Box::new(Expression::Reference(object)),
+                    field,
+                });
             } else {
                 assert!(reference.path.try_to_identifier().is_some());
             }
         }
+        Expression::StructExpression(StructExpression { name, .. }) => {
+            *name = paths[&path.clone().join(name.clone())].relative_to(&Default::default());
+        }
+        Expression::LambdaExpression(LambdaExpression { params, .. }) => {
+            for p in params {
+                canonicalize_inside_pattern(p, path, paths);
+            }
+        }
+        Expression::MatchExpression(_, arms) => {
+            for MatchArm { pattern, .. } in arms {
+                canonicalize_inside_pattern(pattern, path, paths);
+            }
+        }
+        Expression::BlockExpression(statements, _) => {
+            for statement in statements {
+                if let StatementInsideBlock::LetStatement(LetStatementInsideBlock {
+                    pattern, ..
+                }) = statement
+                {
+                    canonicalize_inside_pattern(pattern, path, paths);
+                }
+            }
+        }
+        _ => {}
     });
 }
 
+fn canonicalize_inside_pattern(
+    pattern: &mut Pattern,
+    path: &AbsoluteSymbolPath,
+    paths: &'_ PathMap,
+) {
+    if let Pattern::Struct(name, _) | Pattern::Enum(name, _) = pattern {
+        *name = paths[&path.clone().join(name.clone())].relative_to(&Default::default());
+    }
+    for p in pattern.children_mut() {
+        canonicalize_inside_pattern(p, path, paths);
+    }
+}
+
+/// The parser turns `a.b` into a reference to `b` in namespace `a`. If this does not resolve,
+/// it could also be an access to the field `b` of the local variable or symbol `a`.
+/// Returns the reference to `a` and the field name in that case.
+fn try_to_field_access(
+    reference: &NamespacedPolynomialReference,
+) -> Option<(NamespacedPolynomialReference, String)> {
+    if reference.type_args.is_some() {
+        return None;
+    }
+    let [object, field] = reference.path.parts().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    match (object, field) {
+        (Part::Named(object), Part::Named(field)) => Some((
+            SymbolPath::from_identifier(object.clone()).into(),
+            field.clone(),
+        )),
+        _ => None,
+    }
+}
+
 fn canonicalize_inside_type_scheme(
     type_scheme: &mut TypeScheme<Expression>,
     path: &AbsoluteSymbolPath,
@@ -239,6 +397,29 @@ fn canonicalize_inside_type_scheme(
         .ty
         .map_to_type_vars(&type_scheme.vars.vars().collect());
     canonicalize_inside_type(&mut type_scheme.ty, path, paths);
+    canonicalize_inside_type_bounds(&mut type_scheme.vars, path, paths);
+}
+
+/// Replaces the paths of user-defined traits in the bounds by absolute paths.
+/// Built-in traits are kept as they are.
+fn canonicalize_inside_type_bounds(
+    type_bounds: &mut TypeBounds,
+    path: &AbsoluteSymbolPath,
+    paths: &'_ PathMap,
+) {
+    for (_, bounds) in type_bounds.bounds_mut() {
+        *bounds = std::mem::take(bounds)
+            .into_iter()
+            .map(|bound| {
+                if is_builtin_trait(&bound) {
+                    bound
+                } else {
+                    let p = path.clone().join(SymbolPath::from_str(&bound).unwrap());
+                    paths[&p].relative_to(&Default::default()).to_string()
+                }
+            })
+            .collect();
+    }
 }
 
 fn canonicalize_inside_type(
@@ -333,10 +514,11 @@ fn check_path_internal<'a>(
             ),
             |(mut location, value, chain), member| {
                 match value {
-                    // machines, expressions and enum variants do not expose symbols
+                    // machines, expressions, enum variants and trait functions do not expose symbols
                     SymbolValueRef::Machine(_)
                     | SymbolValueRef::Expression(_)
-                    | SymbolValueRef::TypeConstructor(_) => {
+                    | SymbolValueRef::TypeConstructor(_)
+                    | SymbolValueRef::TraitFunction(_) => {
                         Err(format!("symbol not found in `{location}`: `{member}`"))
                     }
                     // modules expose symbols
@@ -370,8 +552,12 @@ fn check_path_internal<'a>(
                             chain,
                         )
                     }
+                    // structs do not expose symbols
+                    SymbolValueRef::TypeDeclaration(TypeDeclaration::Struct(_)) => {
+                        Err(format!("symbol not found in `{location}`: `{member}`"))
+                    }
                     // enums expose symbols
-                    SymbolValueRef::TypeDeclaration(enum_decl) => enum_decl
+                    SymbolValueRef::TypeDeclaration(TypeDeclaration::Enum(enum_decl)) => enum_decl
                         .variants
                         .iter()
                         .find(|variant| variant.name == member)
@@ -383,6 +569,17 @@ fn check_path_internal<'a>(
                                 chain,
                             )
                         }),
+                    // traits expose their functions
+                    SymbolValueRef::TraitDeclaration(trait_decl) => trait_decl
+                        .function_by_name(member)
+                        .ok_or_else(|| format!("symbol not found in `{location}`: `{member}`"))
+                        .map(|function| {
+                            (
+                                location.with_part(member),
+                                SymbolValueRef::TraitFunction(function),
+                                chain,
+                            )
+                        }),
                 }
             },
         )
@@ -463,11 +660,18 @@ fn check_module(
                 }
                 check_expression(&location, e, state, &HashSet::default())?
             }
-            SymbolValue::TypeDeclaration(enum_decl) => {
-                check_type_declaration(&location, enum_decl, state)?
+            SymbolValue::TypeDeclaration(type_decl) => {
+                check_type_declaration(&location, type_decl, state)?
+            }
+            SymbolValue::TraitDeclaration(trait_decl) => {
+                check_trait_declaration(&location, trait_decl, state)?
             }
         }
     }
+
+    for trait_impl in module.trait_implementations() {
+        check_trait_implementation(&location, trait_impl, state)?;
+    }
     Ok(())
 }
 
@@ -491,9 +695,12 @@ fn check_machine(
             return Err(format!("Duplicate name `{name}` in machine `{location}`"));
         }
     }
+    for param in &m.params {
+        check_path(module_location.clone().join(param.ty.clone()), state)?
+    }
     for statement in &m.statements {
         match statement {
-            MachineStatement::Submachine(_, path, _) => {
+            MachineStatement::Submachine(_, path, _, _) => {
                 check_path(module_location.clone().join(path.clone()), state)?
             }
             MachineStatement::FunctionDeclaration(_, _, _, statements) => statements
@@ -564,7 +771,30 @@ fn check_expression(
                     return Ok(());
                 }
             }
-            check_path(location.clone().join(reference.path.clone()), state)
+            let result = check_path(location.clone().join(reference.path.clone()), state);
+            match try_to_field_access(reference) {
+                // `a.b` might also be an access to the field `b` of the local variable
+                // or the value `a`.
+                Some((object, _)) if result.is_err() => {
+                    let is_value = match object.try_to_identifier() {
+                        Some(name) if local_variables.contains(name) => true,
+                        _ => matches!(
+                            check_path_internal(
+                                location.clone().join(object.path),
+                                state,
+                                Default::default()
+                            ),
+                            Ok((_, SymbolValueRef::Expression(_), _))
+                        ),
+                    };
+                    if is_value {
+                        Ok(())
+                    } else {
+                        result
+                    }
+                }
+                _ => result,
+            }
         }
         Expression::PublicReference(_) | Expression::Number(_, _) | Expression::String(_) => Ok(()),
         Expression::Tuple(items) | Expression::ArrayLiteral(ArrayLiteral { items }) => {
@@ -575,6 +805,9 @@ fn check_expression(
             params,
             body,
         }) => {
+            params
+                .iter()
+                .try_for_each(|p| check_pattern(location, p, state))?;
             // Add the local variables, ignore collisions.
             let mut local_variables = local_variables.clone();
             local_variables.extend(params.iter().flat_map(|p| p.variables().cloned()));
@@ -595,9 +828,19 @@ fn check_expression(
             check_expression(location, function, state, local_variables)?;
             check_expressions(location, arguments, state, local_variables)
         }
+        Expression::StructExpression(StructExpression { name, fields }) => {
+            check_path(location.clone().join(name.clone()), state)?;
+            fields.iter().try_for_each(|NamedExpression { body, .. }| {
+                check_expression(location, body, state, local_variables)
+            })
+        }
+        Expression::FieldAccess(FieldAccess { object, .. }) => {
+            check_expression(location, object, state, local_variables)
+        }
         Expression::MatchExpression(scrutinee, arms) => {
             check_expression(location, scrutinee, state, local_variables)?;
             arms.iter().try_for_each(|MatchArm { pattern, value }| {
+                check_pattern(location, pattern, state)?;
                 let mut local_variables = local_variables.clone();
                 local_variables.extend(pattern.variables().cloned());
                 check_expression(location, value, state, &local_variables)
@@ -623,6 +866,7 @@ fn check_expression(
                         if let Some(value) = value {
                             check_expression(location, value, state, &local_variables)?;
                         }
+                        check_pattern(location, pattern, state)?;
                         local_variables.extend(pattern.variables().cloned());
                     }
                     StatementInsideBlock::Expression(expr) => {
@@ -635,6 +879,20 @@ fn check_expression(
     }
 }
 
+/// Checks the paths of the struct types and enum variants used in a pattern.
+fn check_pattern(
+    location: &AbsoluteSymbolPath,
+    pattern: &Pattern,
+    state: &mut State<'_>,
+) -> Result<(), String> {
+    if let Pattern::Struct(name, _) | Pattern::Enum(name, _) = pattern {
+        check_path(location.clone().join(name.clone()), state)?;
+    }
+    pattern
+        .children()
+        .try_for_each(|p| check_pattern(location, p, state))
+}
+
 fn check_expressions(
     location: &AbsoluteSymbolPath,
     expressions: &[Expression],
@@ -648,12 +906,48 @@ fn check_expressions(
 
 fn check_type_declaration(
     location: &AbsoluteSymbolPath,
-    enum_decl: &EnumDeclaration<Expression>,
+    type_decl: &TypeDeclaration<Expression>,
     state: &mut State<'_>,
 ) -> Result<(), String> {
-    // If we add generic types, the type variables need to be added
-    // in a way similar to local variables in expressions.
+    match type_decl {
+        TypeDeclaration::Enum(enum_decl) => check_enum_declaration(location, enum_decl, state),
+        TypeDeclaration::Struct(struct_decl) => {
+            check_struct_declaration(location, struct_decl, state)
+        }
+    }
+}
+
+fn check_struct_declaration(
+    location: &AbsoluteSymbolPath,
+    struct_decl: &StructDeclaration<Expression>,
+    state: &mut State<'_>,
+) -> Result<(), String> {
+    struct_decl.fields.iter().try_fold(
+        BTreeSet::default(),
+        |mut acc, StructField { name, .. }| {
+            acc.insert(name.clone()).then_some(acc).ok_or(format!(
+                "Duplicate field `{name}` in struct `{}`",
+                location.with_part(&struct_decl.name)
+            ))
+        },
+    )?;
+
+    struct_decl.fields.iter().try_for_each(|field| {
+        check_type(
+            location,
+            &field.ty,
+            state,
+            &Default::default(),
+            &Default::default(),
+        )
+    })
+}
 
+fn check_enum_declaration(
+    location: &AbsoluteSymbolPath,
+    enum_decl: &EnumDeclaration<Expression>,
+    state: &mut State<'_>,
+) -> Result<(), String> {
     enum_decl.variants.iter().try_fold(
         BTreeSet::default(),
         |mut acc, EnumVariant { name, .. }| {
@@ -663,20 +957,54 @@ fn check_type_declaration(
         },
     )?;
 
+    check_type_bounds(location, &enum_decl.type_vars, state)?;
+    let type_vars = enum_decl.type_vars.vars().collect();
     enum_decl
         .variants
         .iter()
         .flat_map(|v| v.fields.iter())
         .flat_map(|v| v.iter())
-        .try_for_each(|ty| {
-            check_type(
-                location,
-                ty,
-                state,
-                &Default::default(),
-                &Default::default(),
-            )
-        })
+        .try_for_each(|ty| check_type(location, ty, state, &type_vars, &Default::default()))
+}
+
+fn check_trait_declaration(
+    location: &AbsoluteSymbolPath,
+    trait_decl: &TraitDeclaration<Expression>,
+    state: &mut State<'_>,
+) -> Result<(), String> {
+    trait_decl.functions.iter().try_fold(
+        BTreeSet::default(),
+        |mut acc, TraitFunction { name, .. }| {
+            acc.insert(name.clone()).then_some(acc).ok_or(format!(
+                "Duplicate function `{name}` in trait `{}`",
+                location.with_part(&trait_decl.name)
+            ))
+        },
+    )?;
+
+    let type_vars = trait_decl.type_vars.iter().collect();
+    trait_decl
+        .functions
+        .iter()
+        .try_for_each(|f| check_type(location, &f.ty, state, &type_vars, &Default::default()))
+}
+
+fn check_trait_implementation(
+    location: &AbsoluteSymbolPath,
+    trait_impl: &TraitImplementation<Expression, Expression>,
+    state: &mut State<'_>,
+) -> Result<(), String> {
+    check_path(location.clone().join(trait_impl.name.clone()), state)?;
+    check_type_bounds(location, &trait_impl.type_vars, state)?;
+    let type_vars = trait_impl.type_vars.vars().collect();
+    trait_impl
+        .type_args
+        .iter()
+        .try_for_each(|ty| check_type(location, ty, state, &type_vars, &Default::default()))?;
+    trait_impl
+        .functions
+        .iter()
+        .try_for_each(|f| check_expression(location, &f.body, state, &Default::default()))
 }
 
 fn check_type_scheme(
@@ -685,6 +1013,7 @@ fn check_type_scheme(
     state: &mut State<'_>,
     local_variables: &HashSet<String>,
 ) -> Result<(), String> {
+    check_type_bounds(location, &type_scheme.vars, state)?;
     let type_vars = type_scheme.vars.vars().collect::<HashSet<_>>();
     check_type(
         location,
@@ -695,6 +1024,24 @@ fn check_type_scheme(
     )
 }
 
+/// Checks the paths of user-defined traits used as bounds.
+fn check_type_bounds(
+    location: &AbsoluteSymbolPath,
+    type_bounds: &TypeBounds,
+    state: &mut State<'_>,
+) -> Result<(), String> {
+    type_bounds
+        .bounds()
+        .flat_map(|(_, bounds)| bounds)
+        .filter(|bound| !is_builtin_trait(bound))
+        .try_for_each(|bound| {
+            check_path(
+                location.clone().join(SymbolPath::from_str(bound).unwrap()),
+                state,
+            )
+        })
+}
+
 fn check_type(
     location: &AbsoluteSymbolPath,
     ty: &Type<Expression>,
@@ -848,4 +1195,27 @@ mod tests {
     fn import_after_usage() {
         expect("import_after_usage", Ok(()))
     }
+
+    #[test]
+    fn trait_paths() {
+        expect("trait_paths", Ok(()))
+    }
+
+    #[test]
+    fn struct_paths() {
+        expect("struct_paths", Ok(()))
+    }
+
+    #[test]
+    fn generic_enum_paths() {
+        expect("generic_enum_paths", Ok(()))
+    }
+
+    #[test]
+    fn trait_function_not_found() {
+        expect(
+            "trait_function_not_found",
+            Err("symbol not found in `::Hasher`: `hush`"),
+        )
+    }
 }
@@ -92,8 +92,14 @@ impl Folder for StdAdder {
                     SymbolValue::TypeDeclaration(ty) => {
                         self.fold_type_declaration(ty).map(From::from)
                     }
+                    SymbolValue::TraitDeclaration(trait_decl) => {
+                        self.fold_trait_declaration(trait_decl).map(From::from)
+                    }
                 }
                 .map(|value| ModuleStatement::SymbolDefinition(SymbolDefinition { value, ..d })),
+                ModuleStatement::TraitImplementation(trait_impl) => {
+                    self.fold_trait_implementation(trait_impl).map(From::from)
+                }
             })
             .collect::<Result<Vec<_>, _>>()?;
 
@@ -101,6 +107,7 @@ impl Folder for StdAdder {
         // (E.g. the main module)
         let has_std = statements.iter().any(|s| match s {
             ModuleStatement::SymbolDefinition(d) => d.name == "std",
+            ModuleStatement::TraitImplementation(_) => false,
         });
 
         if !has_std {
@@ -0,0 +1,10 @@
+mod submodule {
+    struct Point {
+        x: int,
+    }
+    enum Option<T> { None, Some(T) }
+    let origin: Option<Point> = Option::Some(Point { x: 0 });
+}
+use submodule::Option;
+let p: Option<submodule::Point> = submodule::origin;
+let f: Option<int> -> int = |o| match o { Option::Some(x) => x, Option::None => 0 };
@@ -0,0 +1,15 @@
+mod submodule {
+    struct Point {
+        x: int,
+    }
+    enum Option<T> {
+        None,
+        Some(T),
+    }
+    let origin: submodule::Option<submodule::Point> = submodule::Option::Some(submodule::Point { x: 0 });
+}
+let p: submodule::Option<submodule::Point> = submodule.origin;
+let f: submodule::Option<int> -> int = (|o| match o {
+    submodule::Option::Some(x) => x,
+    submodule::Option::None => 0,
+});
@@ -0,0 +1,11 @@
+mod submodule {
+    enum Tag { A, B }
+    struct Point {
+        x: int,
+        tag: Tag,
+    }
+}
+use submodule::Point;
+let origin: Point = Point { x: 0, tag: submodule::Tag::A };
+let f: Point -> int = |p| match p { Point { x, tag: _ } => x };
+let g: int = origin.x;
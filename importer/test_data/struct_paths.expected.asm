@@ -0,0 +1,15 @@
+mod submodule {
+    enum Tag {
+        A,
+        B,
+    }
+    struct Point {
+        x: int,
+        tag: submodule::Tag,
+    }
+}
+let origin: submodule::Point = submodule::Point { x: 0, tag: submodule::Tag::A };
+let f: submodule::Point -> int = (|p| match p {
+    submodule::Point { x: x, tag: _ } => x,
+});
+let g: int = origin.x;
@@ -0,0 +1,4 @@
+trait Hasher<T> {
+    hash: T -> int,
+}
+let f: int -> int = |x| Hasher::hush(x);
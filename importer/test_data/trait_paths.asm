@@ -0,0 +1,15 @@
+mod submodule {
+    enum Tag { A, B }
+    trait Hasher<T> {
+        hash: T -> Tag,
+    }
+}
+use submodule::Hasher;
+use submodule::Tag;
+impl Hasher<int> {
+    hash: |_| Tag::A,
+}
+impl<T: Hasher> Hasher<T[]> {
+    hash: |x| Hasher::hash(x[0]),
+}
+let<T: Hasher + Add> f: T -> Tag = |x| Hasher::hash(x + x);
@@ -0,0 +1,16 @@
+mod submodule {
+    enum Tag {
+        A,
+        B,
+    }
+    trait Hasher<T> {
+        hash: T -> submodule::Tag,
+    }
+}
+impl submodule::Hasher<int> {
+    hash: (|_| submodule::Tag::A),
+}
+impl<T: submodule::Hasher> submodule::Hasher<T[]> {
+    hash: (|x| submodule::Hasher::hash(x[0])),
+}
+let<T: Add + submodule::Hasher> f: T -> submodule::Tag = (|x| submodule::Hasher::hash((x + x)));
@@ -8,7 +8,7 @@ use powdr_ast::{
     parsed::{
         asm::{AbsoluteSymbolPath, SymbolPath},
         build::{index_access, namespaced_reference},
-        Expression, PILFile, PilStatement, SelectedExpressions, TypedExpression,
+        Expression, PILFile, PilStatement, SelectedExpressions, TypeDeclaration, TypedExpression,
     },
     SourceRef,
 };
@@ -19,7 +19,7 @@ const DEFAULT_DEGREE: u32 = 1024;
 const MAIN_OPERATION_NAME: &str = "main";
 
 /// a monolithic linker which outputs a single AIR
-/// It sets the degree of submachines to the degree of the main machine, and errors out if a submachine has an explicit degree which doesn't match the main one
+/// Each machine keeps its own degree. Submachines without an explicit degree get the degree of the main machine.
 pub fn link(graph: PILGraph) -> Result<PILFile, Vec<String>> {
     let main_machine = graph.main;
     let main_degree = graph
@@ -30,25 +30,16 @@ pub fn link(graph: PILGraph) -> Result<PILFile, Vec<String>> {
         .clone()
         .unwrap_or_else(|| DEFAULT_DEGREE.into());
 
-    let mut errors = vec![];
-
     let mut pil = process_definitions(graph.definitions);
 
     for (location, object) in graph.objects.into_iter() {
-        if let Some(degree) = object.degree {
-            if degree != main_degree {
-                errors.push(format!(
-                    "Machine {location} should have degree {main_degree}, found {}",
-                    degree
-                ))
-            }
-        }
+        let degree = object.degree.unwrap_or_else(|| main_degree.clone());
 
         // create a namespace for this object
         pil.push(PilStatement::Namespace(
             SourceRef::unknown(),
             SymbolPath::from_identifier(location.to_string()),
-            Some(main_degree.clone()),
+            Some(degree),
         ));
 
         pil.extend(object.pil);
@@ -82,11 +73,7 @@ pub fn link(graph: PILGraph) -> Result<PILFile, Vec<String>> {
         }
     }
 
-    if !errors.is_empty() {
-        Err(errors)
-    } else {
-        Ok(PILFile(pil))
-    }
+    Ok(PILFile(pil))
 }
 
 // Extract the utilities and sort them into namespaces where possible.
@@ -113,9 +100,18 @@ fn process_definitions(
                         Some(e),
                     )
                 }
-                TypeOrExpression::Type(enum_decl) => {
+                TypeOrExpression::Type(TypeDeclaration::Enum(enum_decl)) => {
                     PilStatement::EnumDeclaration(SourceRef::unknown(), enum_decl)
                 }
+                TypeOrExpression::Type(TypeDeclaration::Struct(struct_decl)) => {
+                    PilStatement::StructDeclaration(SourceRef::unknown(), struct_decl)
+                }
+                TypeOrExpression::TraitDeclaration(trait_decl) => {
+                    PilStatement::TraitDeclaration(SourceRef::unknown(), trait_decl)
+                }
+                TypeOrExpression::TraitImplementation(trait_impl) => {
+                    PilStatement::TraitImplementation(SourceRef::unknown(), trait_impl)
+                }
             };
 
             // If there is a namespace change, insert a namespace statement.
@@ -271,14 +267,22 @@ mod test {
             .into_iter()
             .collect(),
         };
+        // the degrees of all namespaces of a pil file `f` (if they are set)
+        let namespace_degrees = |f: PILFile| {
+            f.0.into_iter()
+                .filter_map(|s| match s {
+                    powdr_ast::parsed::PilStatement::Namespace(_, name, Some(e)) => {
+                        Some((name.to_string(), e))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
         // a test over a pil file `f` checking if all namespaces have degree `n` (if they are set)
         let all_namespaces_have_degree = |f: PILFile, n: u64| {
-            f.0.iter().all(|s| match s {
-                powdr_ast::parsed::PilStatement::Namespace(_, _, Some(e)) => {
-                    *e == Expression::Number(n.into(), None)
-                }
-                _ => true,
-            })
+            namespace_degrees(f)
+                .into_iter()
+                .all(|(_, e)| e == Expression::Number(n.into(), None))
         };
 
         let inferred: PILGraph = test_graph(Some(8), None);
@@ -292,10 +296,25 @@ mod test {
         ));
         let default_no_match: PILGraph = test_graph(None, Some(8));
         assert_eq!(
-            link(default_no_match),
-            Err(vec![
-                "Machine main_foo should have degree 1024, found 8".to_string()
-            ])
+            namespace_degrees(link(default_no_match).unwrap()),
+            vec![
+                ("main".to_string(), Expression::Number(1024u32.into(), None)),
+                (
+                    "main_foo".to_string(),
+                    Expression::Number(8u32.into(), None)
+                )
+            ]
+        );
+        let different_degrees: PILGraph = test_graph(Some(16), Some(8));
+        assert_eq!(
+            namespace_degrees(link(different_degrees).unwrap()),
+            vec![
+                ("main".to_string(), Expression::Number(16u32.into(), None)),
+                (
+                    "main_foo".to_string(),
+                    Expression::Number(8u32.into(), None)
+                )
+            ]
         );
     }
 
@@ -307,7 +326,7 @@ mod test {
     #[test]
     fn compile_empty_vm() {
         let expectation = r#"namespace main((4 + 4));
-    pol commit _operation_id(i) query std::prover::Query::Hint(2);
+    pol commit _operation_id(i) query std::prover::hint(2);
     pol constant _block_enforcer_last_step = [0]* + [1];
     let _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
     ((_operation_id_no_change * (_operation_id' - _operation_id)) = 0);
@@ -340,7 +359,7 @@ mod test {
     #[test]
     fn compile_different_signatures() {
         let expectation = r#"namespace main(16);
-    pol commit _operation_id(i) query std::prover::Query::Hint(4);
+    pol commit _operation_id(i) query std::prover::hint(4);
     pol constant _block_enforcer_last_step = [0]* + [1];
     let _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
     ((_operation_id_no_change * (_operation_id' - _operation_id)) = 0);
@@ -398,7 +417,7 @@ mod test {
     pol constant _linker_first_step = [1] + [0]*;
     ((_linker_first_step * (_operation_id - 2)) = 0);
 namespace main_sub(16);
-    pol commit _operation_id(i) query std::prover::Query::Hint(5);
+    pol commit _operation_id(i) query std::prover::hint(5);
     pol constant _block_enforcer_last_step = [0]* + [1];
     let _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
     ((_operation_id_no_change * (_operation_id' - _operation_id)) = 0);
@@ -448,7 +467,7 @@ namespace main_sub(16);
     (XIsZero = (1 - (X * XInv)));
     ((XIsZero * X) = 0);
     ((XIsZero * (1 - XIsZero)) = 0);
-    pol commit _operation_id(i) query std::prover::Query::Hint(10);
+    pol commit _operation_id(i) query std::prover::hint(10);
     pol constant _block_enforcer_last_step = [0]* + [1];
     let _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
     ((_operation_id_no_change * (_operation_id' - _operation_id)) = 0);
@@ -484,9 +503,10 @@ namespace main_sub(16);
     (pc' = ((1 - first_step') * pc_update));
     pol constant p_line = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10] + [10]*;
     pol commit X_free_value(__i) query match std::prover::eval(pc) {
-        2 => std::prover::Query::Input(1),
-        4 => std::prover::Query::Input(std::convert::int((std::prover::eval(CNT) + 1))),
-        7 => std::prover::Query::Input(0),
+        2 => std::utils::Option::Some(std::prover::Query::Input(1)),
+        4 => std::utils::Option::Some(std::prover::Query::Input(std::convert::int((std::prover::eval(CNT) + 1)))),
+        7 => std::utils::Option::Some(std::prover::Query::Input(0)),
+        _ => std::utils::Option::None,
     };
     pol constant p_X_const = [0]*;
     pol constant p_X_read_free = [0, 0, 1, 0, 1, 0, 0, 18446744069414584320, 0, 0, 0] + [0]*;
@@ -537,7 +557,7 @@ machine Machine {
 }
 "#;
         let expectation = r#"namespace main(1024);
-    pol commit _operation_id(i) query std::prover::Query::Hint(4);
+    pol commit _operation_id(i) query std::prover::hint(4);
     pol constant _block_enforcer_last_step = [0]* + [1];
     let _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
     ((_operation_id_no_change * (_operation_id' - _operation_id)) = 0);
@@ -624,7 +644,7 @@ machine Main {
 }
 ";
         let expected = r#"namespace main(1024);
-    pol commit _operation_id(i) query std::prover::Query::Hint(3);
+    pol commit _operation_id(i) query std::prover::hint(3);
     pol constant _block_enforcer_last_step = [0]* + [1];
     let _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
     ((_operation_id_no_change * (_operation_id' - _operation_id)) = 0);
@@ -677,7 +697,7 @@ namespace main_vm(1024);
     #[test]
     pub fn permutation_instructions() {
         let expected = r#"namespace main(65536);
-    pol commit _operation_id(i) query std::prover::Query::Hint(13);
+    pol commit _operation_id(i) query std::prover::hint(13);
     pol constant _block_enforcer_last_step = [0]* + [1];
     let _operation_id_no_change = ((1 - _block_enforcer_last_step) * (1 - instr_return));
     ((_operation_id_no_change * (_operation_id' - _operation_id)) = 0);
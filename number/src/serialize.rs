@@ -85,26 +85,24 @@ pub fn read_polys_csv_file<T: FieldElement>(file: impl Read) -> Vec<(String, Vec
         .collect()
 }
 
-fn ceil_div(num: usize, div: usize) -> usize {
+pub(crate) fn ceil_div(num: usize, div: usize) -> usize {
     (num + div - 1) / div
 }
 
+/// Writes the columns row by row. Row `i` contains the values of all columns that are
+/// longer than `i`, in the given order, so if all columns have the same length,
+/// each row contains one value of every column.
 pub fn write_polys_file<T: FieldElement>(file: &mut impl Write, polys: &[(String, Vec<T>)]) {
     let width = ceil_div(T::BITS as usize, 64) * 8;
 
-    if polys.is_empty() {
-        return;
-    }
-
-    // TODO maybe the witness should have a proper type that
-    // explicitly has a degree or length?
-    let degree = polys[0].1.len();
-    for (_, values) in polys {
-        assert_eq!(values.len(), degree);
-    }
+    let degree = polys
+        .iter()
+        .map(|(_, values)| values.len())
+        .max()
+        .unwrap_or_default();
 
     for i in 0..degree {
-        for (_name, constant) in polys {
+        for (_name, constant) in polys.iter().filter(|(_, values)| values.len() > i) {
             let bytes = constant[i].to_bytes_le();
             assert_eq!(bytes.len(), width);
             file.write_all(&bytes).unwrap();
@@ -112,33 +110,44 @@ pub fn write_polys_file<T: FieldElement>(file: &mut impl Write, polys: &[(String
     }
 }
 
+/// Reads the columns written by [write_polys_file], given their names and lengths.
+/// Columns of unknown length (`None`) are read until the end of the file.
+/// @returns the columns and the number of rows read, i.e. the length of the longest column.
 pub fn read_polys_file<T: FieldElement>(
     file: &mut impl Read,
-    columns: &[String],
+    columns: &[(String, Option<DegreeType>)],
 ) -> (Vec<(String, Vec<T>)>, DegreeType) {
     assert!(!columns.is_empty());
     let width = ceil_div(T::BITS as usize, 64) * 8;
 
-    let bytes_to_read = width * columns.len();
-
     let mut result: Vec<(_, Vec<T>)> = columns
         .iter()
-        .map(|name| (name.to_string(), vec![]))
+        .map(|(name, _)| (name.to_string(), vec![]))
         .collect();
     let mut degree = 0;
 
     loop {
-        let mut buf = vec![0u8; bytes_to_read];
+        // The columns that have a value in the current row.
+        let row_columns = columns
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, length))| length.map_or(true, |length| degree < length))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        if row_columns.is_empty() {
+            return (result, degree);
+        }
+        let mut buf = vec![0u8; width * row_columns.len()];
         match file.read_exact(&mut buf) {
             Ok(()) => {}
             Err(_) => return (result, degree),
         }
         degree += 1;
-        result
-            .iter_mut()
+        row_columns
+            .into_iter()
             .zip(buf.chunks(width))
-            .for_each(|((_, values), bytes)| {
-                values.push(T::from_bytes_le(bytes));
+            .for_each(|(index, bytes)| {
+                result[index].1.push(T::from_bytes_le(bytes));
             });
     }
 }
@@ -191,13 +200,37 @@ mod tests {
         write_polys_file(&mut buf, &polys);
         let (read_polys, read_degree) = read_polys_file::<Bn254Field>(
             &mut Cursor::new(buf),
-            &["a".to_string(), "b".to_string()],
+            &[("a".to_string(), None), ("b".to_string(), None)],
         );
 
         assert_eq!(read_polys, polys);
         assert_eq!(read_degree, degree);
     }
 
+    #[test]
+    fn write_read_different_lengths() {
+        let mut buf: Vec<u8> = vec![];
+
+        let polys = vec![
+            ("a".to_string(), (0..4).map(Bn254Field::from).collect()),
+            ("b".to_string(), (-16..0).map(Bn254Field::from).collect()),
+            ("c".to_string(), (0..8).map(Bn254Field::from).collect()),
+        ];
+
+        write_polys_file(&mut buf, &polys);
+        let (read_polys, read_degree) = read_polys_file::<Bn254Field>(
+            &mut Cursor::new(buf),
+            &[
+                ("a".to_string(), Some(4)),
+                ("b".to_string(), Some(16)),
+                ("c".to_string(), Some(8)),
+            ],
+        );
+
+        assert_eq!(read_polys, polys);
+        assert_eq!(read_degree, 16);
+    }
+
     #[test]
     fn write_read_csv() {
         let polys = test_polys()
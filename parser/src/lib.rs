@@ -262,7 +262,10 @@ mod test {
             | PilStatement::ConnectIdentity(s, _, _)
             | PilStatement::ConstantDefinition(s, _, _)
             | PilStatement::Expression(s, _)
-            | PilStatement::EnumDeclaration(s, _) => *s = SourceRef::unknown(),
+            | PilStatement::EnumDeclaration(s, _)
+            | PilStatement::StructDeclaration(s, _)
+            | PilStatement::TraitDeclaration(s, _)
+            | PilStatement::TraitImplementation(s, _) => *s = SourceRef::unknown(),
         }
     }
 
@@ -277,7 +280,8 @@ mod test {
             match stmt {
                 MachineStatement::Degree(s, _)
                 | MachineStatement::CallSelectors(s, _)
-                | MachineStatement::Submachine(s, _, _)
+                | MachineStatement::PcJump(s, _, _)
+                | MachineStatement::Submachine(s, _, _, _)
                 | MachineStatement::RegisterDeclaration(s, _, _)
                 | MachineStatement::OperationDeclaration(s, _, _, _)
                 | MachineStatement::LinkDeclaration(s, _) => {
@@ -311,7 +315,9 @@ mod test {
         }
 
         fn clear_module_stmt(stmt: &mut ModuleStatement) {
-            let ModuleStatement::SymbolDefinition(SymbolDefinition { value, .. }) = stmt;
+            let ModuleStatement::SymbolDefinition(SymbolDefinition { value, .. }) = stmt else {
+                return;
+            };
             match value {
                 SymbolValue::Machine(Machine { statements, .. }) => {
                     statements.iter_mut().for_each(clear_machine_stmt)
@@ -322,7 +328,8 @@ mod test {
                 SymbolValue::Module(Module::External(_))
                 | SymbolValue::Import(_)
                 | SymbolValue::Expression(_)
-                | SymbolValue::TypeDeclaration(_) => (),
+                | SymbolValue::TypeDeclaration(_)
+                | SymbolValue::TraitDeclaration(_) => (),
             }
         }
 
@@ -495,6 +502,51 @@ namespace N(2);
         assert_eq!(input.trim(), printed.trim());
     }
 
+    #[test]
+    fn generic_enums() {
+        let input = r#"
+namespace N(2);
+    enum Option<T> {
+        None,
+        Some(T),
+    }
+    enum Either<L, R> {
+        Left(L),
+        Right(R),
+    }
+    let x: Option<int> = Option.Some(7);
+    let unwrap_or: Option<int>, int -> int = (|o, d| match o {
+        Option::Some(v) => v,
+        Option::None => d,
+    });
+    let l: Either<int, Option<fe[]>> = Either.Left(1);
+"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
+        assert_eq!(input.trim(), printed.trim());
+    }
+
+    #[test]
+    fn trait_decls() {
+        let input = r#"
+namespace N(2);
+    trait Hasher<T> {
+        hash: T -> int,
+        combine: T, int -> int,
+    }
+    impl Hasher<int> {
+        hash: (|x| x),
+        combine: (|x, h| (x + h)),
+    }
+    impl<T: N::Hasher + Ord> Hasher<T[]> {
+        hash: (|x| 0),
+        combine: (|x, h| h),
+    }
+    let<T: Hasher> hash_twice: T -> int = (|x| (Hasher.hash(x) + Hasher.hash(x)));
+"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
+        assert_eq!(input.trim(), printed.trim());
+    }
+
     #[test]
     fn patterns() {
         let input = r#"
@@ -534,6 +586,43 @@ namespace N(2);
 namespace N(2);
     let<T: Ord> max: T, T -> T = (|a, b| if (a < b) { b } else { a });
     let seven = max::<int>(3, 7);
+"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
+        assert_eq!(expected.trim(), printed.trim());
+    }
+
+    #[test]
+    fn structs() {
+        let input = r#"
+namespace N(2);
+    struct Point {
+        x: int,
+        y: int,
+        f: (int -> int),
+    }
+    let p: Point = Point { x: 1, y: 2, f: (|i| i) };
+    let x_of = (|p| p.x);
+    let y_of = (|p| match p {
+        Point { x: _, y: y, f: _ } => y,
+    });
+    let y_coords = (|p| if (p.y > 0) { p.y } else { 0 });
+    let sum = (|p| (p.x + p.y).f);
+"#;
+        let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
+        assert_eq!(input.trim(), printed.trim());
+    }
+
+    #[test]
+    fn struct_pattern_shorthand() {
+        let input = r#"
+namespace N(2);
+    let y_of = (|p| match p { Point { x, y } => y });
+"#;
+        let expected = r#"
+namespace N(2);
+    let y_of = (|p| match p {
+        Point { x: x, y: y } => y,
+    });
 "#;
         let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
         assert_eq!(expected.trim(), printed.trim());
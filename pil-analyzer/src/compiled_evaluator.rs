@@ -0,0 +1,554 @@
+//! Compiles functions into trees of instructions so that they can be evaluated
+//! many times (for example once per row) without interpreting the expression tree
+//! on every call.
+//!
+//! References to symbols are resolved at compile time: calls to other non-generic
+//! functions are compiled as well and called directly, other symbols are evaluated
+//! once and stored as constants. Everything the compiler does not handle explicitly
+//! is delegated to the interpreter in [crate::evaluator], so compiled functions
+//! behave exactly like interpreted ones.
+//!
+//! The `compiled-evaluator-benchmark` group in `pipeline/benches/executor_benchmark.rs`
+//! compares both evaluators on functions that are called once per row.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+use powdr_ast::{
+    analyzed::{Expression, FunctionValueDefinition, Reference, Symbol, SymbolKind},
+    parsed::{
+        types::Type, BinaryOperator, FunctionCall, IfExpression, LambdaExpression,
+        LetStatementInsideBlock, MatchArm, Pattern, StatementInsideBlock, UnaryOperator,
+    },
+};
+use powdr_number::FieldElement;
+
+use crate::evaluator::{
+    evaluate_function_call,
+    internal::{self, evaluate_binary_operation, evaluate_index_access, evaluate_unary_operation},
+    Closure, Definitions, EvalError, SymbolLookup, Value, BUILTINS,
+};
+
+type SymbolDefinitions = HashMap<String, (Symbol, Option<FunctionValueDefinition>)>;
+
+/// Tries to compile an expression that evaluates to a non-generic function,
+/// i.e. a lambda expression or a reference to a symbol defined as a lambda expression.
+/// Returns None if the expression is of a different form.
+pub fn try_compile<'a, T: FieldElement>(
+    expr: &'a Expression,
+    definitions: &'a SymbolDefinitions,
+) -> Option<CompiledFunction<'a, T>> {
+    let lambda = match expr {
+        Expression::LambdaExpression(lambda) => lambda,
+        Expression::Reference(Reference::Poly(poly)) => {
+            function_definition(definitions, &poly.name)?
+        }
+        _ => return None,
+    };
+    Some(compile(lambda, definitions))
+}
+
+/// Compiles a lambda expression that does not capture any local variables.
+pub fn compile<'a, T: FieldElement>(
+    lambda: &'a LambdaExpression<Expression>,
+    definitions: &'a SymbolDefinitions,
+) -> CompiledFunction<'a, T> {
+    let mut compiler = Compiler {
+        definitions,
+        functions: vec![],
+        function_indices: Default::default(),
+    };
+    compiler.compile_function(lambda);
+    CompiledFunction {
+        functions: compiler.functions.into_iter().map(|f| f.unwrap()).collect(),
+    }
+}
+
+/// A compiled function together with all the functions it calls.
+pub struct CompiledFunction<'a, T> {
+    /// The function at index zero is the entry point.
+    functions: Vec<Function<'a, T>>,
+}
+
+impl<'a, T: FieldElement> CompiledFunction<'a, T> {
+    /// Calls the function with the given arguments. Symbols that were not resolved at
+    /// compile time are looked up in `symbols`, which is also used for the
+    /// `std::prover::eval` builtin.
+    pub fn call(
+        &self,
+        arguments: Vec<Arc<Value<'a, T>>>,
+        symbols: &mut impl SymbolLookup<'a, T>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        call(&self.functions, 0, arguments, symbols)
+    }
+}
+
+impl<'a, T> Debug for CompiledFunction<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CompiledFunction({})", self.functions[0].lambda)
+    }
+}
+
+struct Function<'a, T> {
+    lambda: &'a LambdaExpression<Expression>,
+    body: Code<'a, T>,
+}
+
+/// An expression with all symbol references resolved.
+enum Code<'a, T> {
+    /// A local variable, indexed in the same way as by the interpreter.
+    Local(usize),
+    Constant(Arc<Value<'a, T>>),
+    /// A symbol that has to be looked up at runtime.
+    Lookup(&'a str, Option<Vec<Type>>),
+    Tuple(Vec<Self>),
+    Array(Vec<Self>),
+    BinaryOperation(Box<Self>, BinaryOperator, Box<Self>),
+    UnaryOperation(UnaryOperator, Box<Self>),
+    Lambda(&'a LambdaExpression<Expression>),
+    /// An index access, together with the original expression for error reporting.
+    IndexAccess(Box<Self>, Box<Self>, &'a Expression),
+    /// A call of the compiled function with the given index.
+    Call(usize, Vec<Self>),
+    /// A call of a function that is only known at runtime.
+    DynamicCall(Box<Self>, Vec<Self>),
+    Match(Box<Self>, Vec<(&'a Pattern, Self)>),
+    If(Box<Self>, Box<Self>, Box<Self>),
+    /// A block of let statements followed by an expression.
+    Block(Vec<(&'a Pattern, Self)>, Box<Self>),
+    /// An expression that is evaluated by the interpreter.
+    Interpret(&'a Expression),
+}
+
+/// The state of a call to a compiled function.
+struct Frame<'r, 'a, T> {
+    functions: &'r [Function<'a, T>],
+    locals: Vec<Arc<Value<'a, T>>>,
+    symbols: &'r mut dyn SymbolLookup<'a, T>,
+}
+
+fn call<'a, T: FieldElement>(
+    functions: &[Function<'a, T>],
+    index: usize,
+    arguments: Vec<Arc<Value<'a, T>>>,
+    symbols: &mut dyn SymbolLookup<'a, T>,
+) -> Result<Arc<Value<'a, T>>, EvalError> {
+    let Function { lambda, body } = &functions[index];
+    if lambda.params.len() != arguments.len() {
+        Err(EvalError::TypeError(format!(
+            "Invalid function call: Supplied {} arguments to function that takes {} parameters.\nFunction: {lambda}",
+            arguments.len(),
+            lambda.params.len(),
+        )))?
+    }
+    let mut locals = Vec::with_capacity(arguments.len());
+    for (arg, pattern) in arguments.into_iter().zip(&lambda.params) {
+        if let Pattern::Variable(_) = pattern {
+            locals.push(arg);
+        } else {
+            locals.extend(
+                Value::try_match_pattern(&arg, pattern).unwrap_or_else(|| {
+                    panic!("Irrefutable pattern did not match: {pattern} = {arg}")
+                }),
+            );
+        }
+    }
+    evaluate(
+        body,
+        &mut Frame {
+            functions,
+            locals,
+            symbols,
+        },
+    )
+}
+
+fn evaluate<'a, T: FieldElement>(
+    code: &Code<'a, T>,
+    frame: &mut Frame<'_, 'a, T>,
+) -> Result<Arc<Value<'a, T>>, EvalError> {
+    Ok(match code {
+        Code::Local(i) => frame.locals[*i].clone(),
+        Code::Constant(value) => value.clone(),
+        Code::Lookup(name, type_args) => frame.symbols.lookup(name, type_args.clone())?,
+        Code::Tuple(items) => Value::Tuple(evaluate_all(items, frame)?).into(),
+        Code::Array(items) => Value::Array(evaluate_all(items, frame)?).into(),
+        Code::BinaryOperation(left, op, right) => {
+            let left = evaluate(left, frame)?;
+            let right = evaluate(right, frame)?;
+            evaluate_binary_operation(&left, *op, &right)?
+        }
+        Code::UnaryOperation(op, inner) => {
+            evaluate_unary_operation(*op, evaluate(inner, frame)?.as_ref())?
+        }
+        Code::Lambda(lambda) => Value::from(Closure {
+            lambda,
+            environment: frame.locals.clone(),
+            type_args: Default::default(),
+        })
+        .into(),
+        Code::IndexAccess(array, index, expr) => {
+            let array = evaluate(array, frame)?;
+            let index = evaluate(index, frame)?;
+            evaluate_index_access(&array, &index, expr)?
+        }
+        Code::Call(index, arguments) => {
+            let arguments = evaluate_all(arguments, frame)?;
+            call(frame.functions, *index, arguments, frame.symbols)?
+        }
+        Code::DynamicCall(function, arguments) => {
+            let function = evaluate(function, frame)?;
+            let arguments = evaluate_all(arguments, frame)?;
+            evaluate_function_call(function, arguments, &mut frame.symbols)?
+        }
+        Code::Match(scrutinee, arms) => {
+            let v = evaluate(scrutinee, frame)?;
+            let (vars, body) = arms
+                .iter()
+                .find_map(|(pattern, body)| {
+                    Value::try_match_pattern(&v, pattern).map(|vars| (vars, body))
+                })
+                .ok_or_else(EvalError::NoMatch)?;
+            let len = frame.locals.len();
+            frame.locals.extend(vars);
+            let result = evaluate(body, frame);
+            frame.locals.truncate(len);
+            result?
+        }
+        Code::If(condition, body, else_body) => match evaluate(condition, frame)?.as_ref() {
+            Value::Bool(true) => evaluate(body, frame)?,
+            Value::Bool(false) => evaluate(else_body, frame)?,
+            x => Err(EvalError::TypeError(format!(
+                "Expected boolean value but got {x}"
+            )))?,
+        },
+        Code::Block(lets, result) => {
+            let len = frame.locals.len();
+            for (pattern, value) in lets {
+                let value = evaluate(value, frame)?;
+                frame
+                    .locals
+                    .extend(
+                        Value::try_match_pattern(&value, pattern).unwrap_or_else(|| {
+                            panic!("Irrefutable pattern did not match: {pattern} = {value}")
+                        }),
+                    );
+            }
+            let result = evaluate(result, frame);
+            frame.locals.truncate(len);
+            result?
+        }
+        Code::Interpret(expr) => {
+            internal::evaluate(expr, &frame.locals, &Default::default(), &mut frame.symbols)?
+        }
+    })
+}
+
+fn evaluate_all<'a, T: FieldElement>(
+    code: &[Code<'a, T>],
+    frame: &mut Frame<'_, 'a, T>,
+) -> Result<Vec<Arc<Value<'a, T>>>, EvalError> {
+    code.iter().map(|c| evaluate(c, frame)).collect()
+}
+
+/// Returns the lambda expression a symbol is defined as, if it is a non-generic function.
+fn function_definition<'a>(
+    definitions: &'a SymbolDefinitions,
+    name: &str,
+) -> Option<&'a LambdaExpression<Expression>> {
+    match definitions.get(name)? {
+        (symbol, Some(FunctionValueDefinition::Expression(e)))
+            if !matches!(symbol.kind, SymbolKind::Poly(_))
+                && e.type_scheme.as_ref().map_or(true, |ts| ts.vars.is_empty()) =>
+        {
+            match &e.e {
+                Expression::LambdaExpression(lambda) => Some(lambda),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns true if the symbol can be evaluated without type arguments.
+fn is_non_generic(definitions: &SymbolDefinitions, name: &str) -> bool {
+    match definitions.get(name) {
+        Some((_, Some(FunctionValueDefinition::Expression(e)))) => {
+            e.type_scheme.as_ref().map_or(true, |ts| ts.vars.is_empty())
+        }
+        Some((symbol, _)) => matches!(symbol.kind, SymbolKind::Poly(_)),
+        None => false,
+    }
+}
+
+struct Compiler<'a, T> {
+    definitions: &'a SymbolDefinitions,
+    functions: Vec<Option<Function<'a, T>>>,
+    function_indices: HashMap<&'a str, usize>,
+}
+
+impl<'a, T: FieldElement> Compiler<'a, T> {
+    /// Compiles a function and returns its index.
+    fn compile_function(&mut self, lambda: &'a LambdaExpression<Expression>) -> usize {
+        let index = self.functions.len();
+        self.functions.push(None);
+        let body = self.compile(&lambda.body);
+        self.functions[index] = Some(Function { lambda, body });
+        index
+    }
+
+    /// Returns the index of the compiled function for the given symbol, compiling it
+    /// if needed.
+    fn function_index(&mut self, name: &'a str) -> Option<usize> {
+        if let Some(index) = self.function_indices.get(name) {
+            return Some(*index);
+        }
+        let lambda = function_definition(self.definitions, name)?;
+        // Register the index before compiling the body so that recursive calls find it.
+        self.function_indices.insert(name, self.functions.len());
+        Some(self.compile_function(lambda))
+    }
+
+    fn compile(&mut self, expr: &'a Expression) -> Code<'a, T> {
+        match expr {
+            Expression::Reference(Reference::LocalVar(i, _)) => Code::Local(*i as usize),
+            Expression::Reference(Reference::Poly(poly)) => {
+                if let Some((_, b)) = BUILTINS.iter().find(|(n, _)| n == &poly.name) {
+                    return Code::Constant(Value::BuiltinFunction(*b).into());
+                }
+                if is_non_generic(self.definitions, &poly.name) {
+                    // Symbols without type arguments always evaluate to the same value.
+                    let mut symbols = Definitions(self.definitions);
+                    if let Ok(value) = Definitions::lookup_with_symbols(
+                        self.definitions,
+                        &poly.name,
+                        None,
+                        &mut symbols,
+                    ) {
+                        return Code::Constant(value);
+                    }
+                }
+                Code::Lookup(&poly.name, poly.type_args.clone())
+            }
+            Expression::Number(_, ty) if !matches!(ty, Some(Type::TypeVar(_))) => {
+                match crate::evaluator::evaluate(expr, &mut Definitions(self.definitions)) {
+                    Ok(value) => Code::Constant(value),
+                    Err(_) => Code::Interpret(expr),
+                }
+            }
+            Expression::String(s) => Code::Constant(Value::String(s.clone()).into()),
+            Expression::Tuple(items) => Code::Tuple(self.compile_all(items)),
+            Expression::ArrayLiteral(array) => Code::Array(self.compile_all(&array.items)),
+            Expression::BinaryOperation(left, op, right) => {
+                Code::BinaryOperation(self.compile_boxed(left), *op, self.compile_boxed(right))
+            }
+            Expression::UnaryOperation(op, inner) => {
+                Code::UnaryOperation(*op, self.compile_boxed(inner))
+            }
+            Expression::LambdaExpression(lambda) => Code::Lambda(lambda),
+            Expression::IndexAccess(index_access) => Code::IndexAccess(
+                self.compile_boxed(&index_access.array),
+                self.compile_boxed(&index_access.index),
+                expr,
+            ),
+            Expression::FunctionCall(FunctionCall {
+                function,
+                arguments,
+            }) => {
+                let callee = match function.as_ref() {
+                    Expression::Reference(Reference::Poly(poly))
+                        if poly.type_args.as_ref().map_or(true, |args| args.is_empty()) =>
+                    {
+                        self.function_index(&poly.name)
+                    }
+                    _ => None,
+                };
+                let arguments = self.compile_all(arguments);
+                match callee {
+                    Some(index) => Code::Call(index, arguments),
+                    None => Code::DynamicCall(self.compile_boxed(function), arguments),
+                }
+            }
+            Expression::MatchExpression(scrutinee, arms) => Code::Match(
+                self.compile_boxed(scrutinee),
+                arms.iter()
+                    .map(|MatchArm { pattern, value }| (pattern, self.compile(value)))
+                    .collect(),
+            ),
+            Expression::IfExpression(IfExpression {
+                condition,
+                body,
+                else_body,
+            }) => Code::If(
+                self.compile_boxed(condition),
+                self.compile_boxed(body),
+                self.compile_boxed(else_body),
+            ),
+            Expression::BlockExpression(statements, result) => {
+                let Some(lets) = statements
+                    .iter()
+                    .map(|statement| match statement {
+                        StatementInsideBlock::LetStatement(LetStatementInsideBlock {
+                            pattern,
+                            value: Some(value),
+                        }) => Some((pattern, value)),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    // Witness columns and constraints are only valid in statement context.
+                    return Code::Interpret(expr);
+                };
+                Code::Block(
+                    lets.into_iter()
+                        .map(|(pattern, value)| (pattern, self.compile(value)))
+                        .collect(),
+                    self.compile_boxed(result),
+                )
+            }
+            Expression::Number(..)
+            | Expression::PublicReference(_)
+            | Expression::StructExpression(_)
+            | Expression::FieldAccess(_)
+            | Expression::FreeInput(_) => Code::Interpret(expr),
+        }
+    }
+
+    fn compile_boxed(&mut self, expr: &'a Expression) -> Box<Code<'a, T>> {
+        Box::new(self.compile(expr))
+    }
+
+    fn compile_all(&mut self, expressions: &'a [Expression]) -> Vec<Code<'a, T>> {
+        expressions.iter().map(|e| self.compile(e)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_ast::analyzed::TypedExpression;
+    use powdr_number::{BigInt, GoldilocksField};
+    use pretty_assertions::assert_eq;
+
+    use crate::{analyze_string, evaluator};
+
+    use super::*;
+
+    /// Calls the function `symbol` on each of the arguments, both compiled and interpreted,
+    /// checks that the results agree and returns them.
+    fn call_compiled_and_interpreted(input: &str, symbol: &str, args: &[u64]) -> Vec<String> {
+        let analyzed = analyze_string::<GoldilocksField>(input);
+        let Some(FunctionValueDefinition::Expression(TypedExpression { e, .. })) =
+            &analyzed.definitions[symbol].1
+        else {
+            panic!()
+        };
+        let compiled = try_compile::<GoldilocksField>(e, &analyzed.definitions).unwrap();
+        args.iter()
+            .map(|arg| {
+                let arg = || vec![Arc::new(Value::Integer(BigInt::from(*arg)))];
+                let mut symbols = Definitions(&analyzed.definitions);
+                let result = compiled.call(arg(), &mut symbols).map(|v| v.to_string());
+                let fun = evaluator::evaluate(e, &mut symbols).unwrap();
+                let expected = evaluator::evaluate_function_call(fun, arg(), &mut symbols)
+                    .map(|v| v.to_string());
+                match (result, expected) {
+                    (Ok(result), Ok(expected)) => {
+                        assert_eq!(result, expected);
+                        result
+                    }
+                    (Err(result), Err(expected)) => {
+                        assert_eq!(result.to_string(), expected.to_string());
+                        format!("error: {result}")
+                    }
+                    (result, expected) => panic!("{result:?} != {expected:?}"),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn arithmetic() {
+        let src = r#"
+            let N: int = 8;
+            let f: int -> fe = |i| if i < N / 2 { 7 * 7 } else { -1 };
+            let g: int -> int = |i| (i * i) % N + (i << 3) - (i | 1);
+        "#;
+        assert_eq!(
+            call_compiled_and_interpreted(src, "f", &[0, 3, 4]),
+            ["49", "49", "18446744069414584320"]
+        );
+        assert_eq!(
+            call_compiled_and_interpreted(src, "g", &[0, 3, 4]),
+            ["-1", "22", "27"]
+        );
+    }
+
+    #[test]
+    pub fn recursion() {
+        let src = r#"
+            let fib: int -> int = |i| match i {
+                0 => 0,
+                1 => 1,
+                _ => fib(i - 1) + fib(i - 2),
+            };
+        "#;
+        assert_eq!(
+            call_compiled_and_interpreted(src, "fib", &[0, 1, 10, 20]),
+            ["0", "1", "55", "6765"]
+        );
+    }
+
+    #[test]
+    pub fn locals_and_closures() {
+        let src = r#"
+            let g: int, int -> int = |a, b| a * 10 + b;
+            let f: int -> int[] = |i| {
+                let (x, y) = (i + 1, i + 2);
+                let h = |z| g(x, z) + y;
+                match [x, y] {
+                    [1, _] => [h(0)],
+                    [a, b] => [h(a), h(b), x, y],
+                    _ => [],
+                }
+            };
+        "#;
+        assert_eq!(
+            call_compiled_and_interpreted(src, "f", &[0, 5]),
+            ["[12]", "[73, 74, 6, 7]"]
+        );
+    }
+
+    #[test]
+    pub fn generic_functions() {
+        let src = r#"
+            namespace std::array(8);
+            let len = 123;
+            namespace F(8);
+            let<T> second: T[] -> T = |arr| arr[1];
+            let f: int -> int = |i| second([i, 2]) + std::array::len([i, i, i]);
+        "#;
+        assert_eq!(call_compiled_and_interpreted(src, "F.f", &[1]), ["5"]);
+    }
+
+    #[test]
+    pub fn errors() {
+        let src = r#"
+            namespace std::check(8);
+            let panic = 123;
+            namespace F(8);
+            let arr: int[] = [1, 2, 3];
+            let f: int -> int = |i| if i == 7 { std::check::panic("seven") } else { arr[i] };
+        "#;
+        assert_eq!(
+            call_compiled_and_interpreted(src, "F.f", &[1, 3, 7]),
+            [
+                "2",
+                "error: Out of bounds access: Index access out of bounds: Tried to access element 3 of array of size 3 in: F.arr[i].",
+                "error: Assertion failed: seven"
+            ]
+        );
+    }
+}
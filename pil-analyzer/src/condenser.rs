@@ -2,12 +2,13 @@
 //! i.e. it turns more complex expressions in identities to simpler expressions.
 
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     iter::{self, once},
     str::FromStr,
     sync::Arc,
 };
 
+use itertools::Itertools;
 use powdr_ast::{
     analyzed::{
         AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition,
@@ -18,6 +19,7 @@ use powdr_ast::{
         asm::{AbsoluteSymbolPath, SymbolPath},
         display::format_type_scheme_around_name,
         types::{ArrayType, Type},
+        visitor::AllChildren,
         SelectedExpressions,
     },
     SourceRef,
@@ -53,6 +55,9 @@ pub fn condense<T: FieldElement>(
                     AbsoluteSymbolPath::default().join(SymbolPath::from_str(name).unwrap());
                 namespace.pop();
                 condenser.set_namespace(namespace);
+                if let Some(degree) = definitions[name].0.degree {
+                    condenser.set_degree(degree);
+                }
             }
             let statement = match s {
                 StatementIdentifier::Identity(index) => {
@@ -142,6 +147,7 @@ pub fn condense<T: FieldElement>(
         // maybe move it into PublicDeclaration.
         reference.poly_id = Some(symbol.into());
     }
+    check_identity_degrees(&definitions, &intermediate_columns, &condensed_identities);
     Analyzed {
         degree,
         definitions,
@@ -152,6 +158,59 @@ pub fn condense<T: FieldElement>(
     }
 }
 
+/// Checks that all columns referenced in an identity have the same degree.
+/// The two sides of lookups and permutations are checked separately,
+/// since they can connect machines of different degrees.
+fn check_identity_degrees<T: FieldElement>(
+    definitions: &HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
+    intermediate_columns: &HashMap<String, (Symbol, Vec<AlgebraicExpression<T>>)>,
+    identities: &[Identity<AlgebraicExpression<T>>],
+) {
+    let degrees = definitions
+        .values()
+        .map(|(symbol, _)| symbol)
+        .chain(intermediate_columns.values().map(|(symbol, _)| symbol))
+        .filter(|symbol| matches!(symbol.kind, SymbolKind::Poly(_)))
+        .flat_map(|symbol| {
+            symbol
+                .array_elements()
+                .map(move |(_, poly_id)| (poly_id, symbol.degree))
+        })
+        .collect::<HashMap<_, _>>();
+    let referenced_degrees = |expressions: &SelectedExpressions<AlgebraicExpression<T>>| {
+        expressions
+            .all_children()
+            .filter_map(|e| match e {
+                AlgebraicExpression::Reference(r) => degrees[&r.poly_id],
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>()
+    };
+    for identity in identities {
+        let sides = match identity.kind {
+            IdentityKind::Plookup | IdentityKind::Permutation => {
+                vec![
+                    referenced_degrees(&identity.left),
+                    referenced_degrees(&identity.right),
+                ]
+            }
+            IdentityKind::Polynomial | IdentityKind::Connect => {
+                let mut degrees = referenced_degrees(&identity.left);
+                degrees.extend(referenced_degrees(&identity.right));
+                vec![degrees]
+            }
+        };
+        for degrees in sides {
+            if degrees.len() > 1 {
+                panic!(
+                    "Identity references columns of different degrees ({}): {identity}",
+                    degrees.iter().join(", ")
+                );
+            }
+        }
+    }
+}
+
 type SymbolCacheKey = (String, Option<Vec<Type>>);
 
 pub struct Condenser<'a, T> {
@@ -161,6 +220,8 @@ pub struct Condenser<'a, T> {
     symbol_values: BTreeMap<SymbolCacheKey, Arc<Value<'a, T>>>,
     /// Current namespace (for names of generated witnesses).
     namespace: AbsoluteSymbolPath,
+    /// Degree of the current namespace (for generated witnesses).
+    degree: Option<DegreeType>,
     next_witness_id: u64,
     /// The generated witness columns since the last extraction.
     new_witnesses: Vec<Symbol>,
@@ -220,6 +281,7 @@ impl<'a, T: FieldElement> Condenser<'a, T> {
             symbols,
             symbol_values: Default::default(),
             namespace: Default::default(),
+            degree: None,
             next_witness_id,
             new_witnesses: vec![],
             all_new_witness_names: HashSet::new(),
@@ -254,6 +316,10 @@ impl<'a, T: FieldElement> Condenser<'a, T> {
         self.namespace = namespace;
     }
 
+    pub fn set_degree(&mut self, degree: DegreeType) {
+        self.degree = Some(degree);
+    }
+
     /// Returns the witness columns generated since the last call to this function.
     pub fn extract_new_witness_columns(&mut self) -> Vec<Symbol> {
         std::mem::take(&mut self.new_witnesses)
@@ -353,6 +419,7 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T> for Condenser<'a, T> {
             stage: None,
             kind: SymbolKind::Poly(PolynomialType::Committed),
             length: None,
+            degree: self.degree,
         };
         self.next_witness_id += 1;
         self.all_new_witness_names.insert(name.clone());
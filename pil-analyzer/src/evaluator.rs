@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display},
     sync::Arc,
 };
@@ -11,14 +11,18 @@ use powdr_ast::{
         Symbol, SymbolKind, TypedExpression,
     },
     parsed::{
+        asm::SymbolPath,
         display::quote,
         types::{Type, TypeScheme},
-        BinaryOperator, FunctionCall, LambdaExpression, MatchArm, Pattern, UnaryOperator,
+        BinaryOperator, FieldAccess, FunctionCall, LambdaExpression, MatchArm, Pattern,
+        StructExpression, UnaryOperator,
     },
     SourceRef,
 };
 use powdr_number::{BigInt, BigUint, FieldElement, LargeInt};
 
+use crate::traits::find_implementation;
+
 /// Evaluates an expression given a hash map of definitions.
 pub fn evaluate_expression<'a, T: FieldElement>(
     expr: &'a Expression,
@@ -53,7 +57,9 @@ pub fn evaluate_function_call<'a, T: FieldElement>(
 ) -> Result<Arc<Value<'a, T>>, EvalError> {
     match function.as_ref() {
         Value::BuiltinFunction(b) => internal::evaluate_builtin_function(*b, arguments, symbols),
-        Value::TypeConstructor(name) => Ok(Value::Enum(name, Some(arguments)).into()),
+        Value::TypeConstructor(enum_name, name) => {
+            Ok(Value::Enum(enum_name, name, Some(arguments)).into())
+        }
         Value::Closure(Closure {
             lambda,
             environment,
@@ -167,8 +173,11 @@ pub enum Value<'a, T> {
     Tuple(Vec<Arc<Self>>),
     Array(Vec<Arc<Self>>),
     Closure(Closure<'a, T>),
-    TypeConstructor(&'a str),
-    Enum(&'a str, Option<Vec<Arc<Self>>>),
+    /// A constructor of an enum variant with fields: The absolute name of the enum and the variant name.
+    TypeConstructor(&'a str, &'a str),
+    /// A value of an enum: The absolute name of the enum, the variant name and the fields.
+    Enum(&'a str, &'a str, Option<Vec<Arc<Self>>>),
+    Struct(&'a SymbolPath, BTreeMap<&'a str, Arc<Self>>),
     BuiltinFunction(BuiltinFunction),
     Expression(AlgebraicExpression<T>),
     Identity(AlgebraicExpression<T>, AlgebraicExpression<T>),
@@ -245,8 +254,9 @@ impl<'a, T: FieldElement> Value<'a, T> {
                 )
             }
             Value::Closure(c) => c.type_formatted(),
-            Value::TypeConstructor(name) => format!("{name}_constructor"),
-            Value::Enum(name, _) => name.to_string(),
+            Value::TypeConstructor(_, name) => format!("{name}_constructor"),
+            Value::Enum(_, name, _) => name.to_string(),
+            Value::Struct(name, _) => name.to_string(),
             Value::BuiltinFunction(b) => format!("builtin_{b:?}"),
             Value::Expression(_) => "expr".to_string(),
             Value::Identity(_, _) => "constr".to_string(),
@@ -321,11 +331,47 @@ impl<'a, T: FieldElement> Value<'a, T> {
                     })
             }
             Pattern::Variable(_) => Some(vec![v.clone()]),
+            Pattern::Struct(_, fields) => {
+                let Value::Struct(_, values) = v.as_ref() else {
+                    panic!("Type error")
+                };
+                fields.iter().try_fold(vec![], |mut vars, (field, p)| {
+                    Value::try_match_pattern(&values[field.as_str()], p).map(|v| {
+                        vars.extend(v);
+                        vars
+                    })
+                })
+            }
+            Pattern::Enum(name, fields_pattern) => {
+                let Value::Enum(_, n, data) = v.as_ref() else {
+                    panic!("Type error")
+                };
+                // Type checking ensures that the value and the pattern
+                // belong to the same enum, so it suffices to compare the variant names.
+                if name.name() != n {
+                    return None;
+                }
+                match (data, fields_pattern) {
+                    (None, None) => Some(vec![]),
+                    (Some(data), Some(fields)) => {
+                        assert_eq!(data.len(), fields.len());
+                        data.iter()
+                            .zip(fields)
+                            .try_fold(vec![], |mut vars, (e, p)| {
+                                Value::try_match_pattern(e, p).map(|v| {
+                                    vars.extend(v);
+                                    vars
+                                })
+                            })
+                    }
+                    _ => panic!("Type error"),
+                }
+            }
         }
     }
 }
 
-const BUILTINS: [(&str, BuiltinFunction); 9] = [
+pub(crate) const BUILTINS: [(&str, BuiltinFunction); 9] = [
     ("std::array::len", BuiltinFunction::ArrayLen),
     ("std::check::panic", BuiltinFunction::Panic),
     ("std::convert::expr", BuiltinFunction::ToExpr),
@@ -371,14 +417,22 @@ impl<'a, T: Display> Display for Value<'a, T> {
             Value::Tuple(items) => write!(f, "({})", items.iter().format(", ")),
             Value::Array(elements) => write!(f, "[{}]", elements.iter().format(", ")),
             Value::Closure(closure) => write!(f, "{closure}"),
-            Value::TypeConstructor(name) => write!(f, "{name}_constructor"),
-            Value::Enum(name, data) => {
+            Value::TypeConstructor(_, name) => write!(f, "{name}_constructor"),
+            Value::Enum(_, name, data) => {
                 write!(f, "{name}")?;
                 if let Some(data) = data {
                     write!(f, "({})", data.iter().format(", "))?;
                 }
                 Ok(())
             }
+            Value::Struct(name, fields) => write!(
+                f,
+                "{name} {{ {} }}",
+                fields
+                    .iter()
+                    .map(|(field, value)| format!("{field}: {value}"))
+                    .format(", ")
+            ),
             Value::BuiltinFunction(b) => write!(f, "{b:?}"),
             Value::Expression(e) => write!(f, "{e}"),
             Value::Identity(left, right) => write!(f, "{left} = {right}"),
@@ -460,13 +514,38 @@ impl<'a> Definitions<'a> {
                     let type_args = type_arg_mapping(type_scheme, type_args);
                     evaluate_generic(value, &type_args, symbols)?
                 }
-                Some(FunctionValueDefinition::TypeConstructor(_type_name, variant)) => {
+                Some(FunctionValueDefinition::TypeConstructor(enum_decl, variant)) => {
                     if variant.fields.is_none() {
-                        Value::Enum(&variant.name, None).into()
+                        Value::Enum(&enum_decl.name, &variant.name, None).into()
                     } else {
-                        Value::TypeConstructor(&variant.name).into()
+                        Value::TypeConstructor(&enum_decl.name, &variant.name).into()
                     }
                 }
+                Some(FunctionValueDefinition::TraitFunction(trait_name, function)) => {
+                    let Some((_, Some(FunctionValueDefinition::TraitDeclaration(_, impls)))) =
+                        definitions.get(trait_name)
+                    else {
+                        return Err(EvalError::SymbolNotFound(format!(
+                            "Trait {trait_name} not found."
+                        )));
+                    };
+                    let ty = type_args
+                        .as_ref()
+                        .and_then(|args| args.first())
+                        .ok_or_else(|| {
+                            EvalError::TypeError(format!(
+                                "Missing type argument for trait function {name}."
+                            ))
+                        })?;
+                    let (trait_impl, type_args) =
+                        find_implementation(impls, ty).ok_or_else(|| {
+                            EvalError::TypeError(format!(
+                                "No implementation of trait {trait_name} found for type {ty}."
+                            ))
+                        })?;
+                    let body = &trait_impl.function_by_name(&function.name).unwrap().body;
+                    evaluate_generic(body, &type_args, symbols)?
+                }
                 _ => Err(EvalError::Unsupported(
                     "Cannot evaluate arrays and queries.".to_string(),
                 ))?,
@@ -533,7 +612,41 @@ pub trait SymbolLookup<'a, T> {
     }
 }
 
-mod internal {
+impl<'a, T, S: SymbolLookup<'a, T> + ?Sized> SymbolLookup<'a, T> for &mut S {
+    fn lookup(
+        &mut self,
+        name: &'a str,
+        type_args: Option<Vec<Type>>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        (**self).lookup(name, type_args)
+    }
+
+    fn lookup_public_reference(&self, name: &str) -> Result<Arc<Value<'a, T>>, EvalError> {
+        (**self).lookup_public_reference(name)
+    }
+
+    fn eval_expr(&self, expr: &AlgebraicExpression<T>) -> Result<Arc<Value<'a, T>>, EvalError> {
+        (**self).eval_expr(expr)
+    }
+
+    fn new_witness_column(
+        &mut self,
+        name: &str,
+        source: SourceRef,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        (**self).new_witness_column(name, source)
+    }
+
+    fn add_constraints(
+        &mut self,
+        constraints: Arc<Value<'a, T>>,
+        source: SourceRef,
+    ) -> Result<(), EvalError> {
+        (**self).add_constraints(constraints, source)
+    }
+}
+
+pub(crate) mod internal {
     use num_traits::Signed;
     use powdr_ast::{
         analyzed::{AlgebraicBinaryOperator, Challenge},
@@ -577,40 +690,8 @@ mod internal {
                 evaluate_binary_operation(&left, *op, &right)?
             }
             Expression::UnaryOperation(op, expr) => {
-                match (op, evaluate(expr, locals, type_args, symbols)?.as_ref()) {
-                    (UnaryOperator::Minus, Value::FieldElement(e)) => {
-                        Value::FieldElement(-*e).into()
-                    }
-                    (UnaryOperator::LogicalNot, Value::Bool(b)) => Value::Bool(!b).into(),
-                    (UnaryOperator::Minus, Value::Integer(n)) => Value::Integer(-n).into(),
-                    (UnaryOperator::Next, Value::Expression(e)) => {
-                        let AlgebraicExpression::Reference(reference) = e else {
-                            return Err(EvalError::TypeError(format!(
-                                "Expected column for \"'\" operator, but got: {e}"
-                            )));
-                        };
-
-                        if reference.next {
-                            return Err(EvalError::TypeError(format!(
-                                "Double application of \"'\" on: {reference}"
-                            )));
-                        }
-                        Value::from(AlgebraicExpression::Reference(AlgebraicReference {
-                            next: true,
-                            ..reference.clone()
-                        }))
-                        .into()
-                    }
-                    (op, Value::Expression(e)) => Value::from(AlgebraicExpression::UnaryOperation(
-                        (*op).try_into().unwrap(),
-                        e.clone().into(),
-                    ))
-                    .into(),
-                    (_, inner) => Err(EvalError::TypeError(format!(
-                        "Operator {op} not supported on types: {inner}: {}",
-                        inner.type_formatted()
-                    )))?,
-                }
+                let inner = evaluate(expr, locals, type_args, symbols)?;
+                evaluate_unary_operation(*op, &inner)?
             }
             Expression::LambdaExpression(lambda) => {
                 // TODO only copy the part of the environment that is actually referenced?
@@ -622,29 +703,9 @@ mod internal {
                 .into()
             }
             Expression::IndexAccess(index_access) => {
-                match evaluate(&index_access.array, locals, type_args, symbols)?.as_ref() {
-                    Value::Array(elements) => {
-                        match evaluate(&index_access.index, locals, type_args,symbols)?.as_ref() {
-                            Value::Integer(index)
-                                if index.is_negative()
-                                    || *index >= (elements.len() as u64).into() =>
-                            {
-                                Err(EvalError::OutOfBounds(format!(
-                                    "Index access out of bounds: Tried to access element {index} of array of size {} in: {expr}.",
-                                    elements.len()
-                                )))?
-                            }
-                            Value::Integer(index) => {
-                                elements[usize::try_from(index).unwrap()].clone()
-                            }
-                            index => Err(EvalError::TypeError(format!(
-                                    "Expected integer for array index access but got {index}: {}",
-                                    index.type_formatted()
-                            )))?,
-                        }
-                    }
-                    e => Err(EvalError::TypeError(format!("Expected array, but got {e}")))?,
-                }
+                let array = evaluate(&index_access.array, locals, type_args, symbols)?;
+                let index = evaluate(&index_access.index, locals, type_args, symbols)?;
+                evaluate_index_access(&array, &index, expr)?
             }
             Expression::FunctionCall(FunctionCall {
                 function,
@@ -657,6 +718,33 @@ mod internal {
                     .collect::<Result<Vec<_>, _>>()?;
                 evaluate_function_call(function, arguments, symbols)?
             }
+            Expression::StructExpression(StructExpression { name, fields }) => Value::Struct(
+                name,
+                fields
+                    .iter()
+                    .map(|field| {
+                        Ok((
+                            field.name.as_str(),
+                            evaluate(&field.body, locals, type_args, symbols)?,
+                        ))
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+            .into(),
+            Expression::FieldAccess(FieldAccess { object, field }) => {
+                match evaluate(object, locals, type_args, symbols)?.as_ref() {
+                    Value::Struct(name, values) => values
+                        .get(field.as_str())
+                        .ok_or_else(|| {
+                            EvalError::TypeError(format!("Struct {name} has no field {field}."))
+                        })?
+                        .clone(),
+                    v => Err(EvalError::TypeError(format!(
+                        "Expected struct for access to field {field}, but got {v}: {}",
+                        v.type_formatted()
+                    )))?,
+                }
+            }
             Expression::MatchExpression(scrutinee, arms) => {
                 let v = evaluate(scrutinee, locals, type_args, symbols)?;
                 let (vars, body) = arms
@@ -780,7 +868,72 @@ mod internal {
         })
     }
 
-    fn evaluate_binary_operation<'a, T: FieldElement>(
+    pub fn evaluate_unary_operation<'a, T: FieldElement>(
+        op: UnaryOperator,
+        inner: &Value<'a, T>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        Ok(match (op, inner) {
+            (UnaryOperator::Minus, Value::FieldElement(e)) => Value::FieldElement(-*e).into(),
+            (UnaryOperator::LogicalNot, Value::Bool(b)) => Value::Bool(!b).into(),
+            (UnaryOperator::Minus, Value::Integer(n)) => Value::Integer(-n).into(),
+            (UnaryOperator::Next, Value::Expression(e)) => {
+                let AlgebraicExpression::Reference(reference) = e else {
+                    return Err(EvalError::TypeError(format!(
+                        "Expected column for \"'\" operator, but got: {e}"
+                    )));
+                };
+
+                if reference.next {
+                    return Err(EvalError::TypeError(format!(
+                        "Double application of \"'\" on: {reference}"
+                    )));
+                }
+                Value::from(AlgebraicExpression::Reference(AlgebraicReference {
+                    next: true,
+                    ..reference.clone()
+                }))
+                .into()
+            }
+            (op, Value::Expression(e)) => Value::from(AlgebraicExpression::UnaryOperation(
+                op.try_into().unwrap(),
+                e.clone().into(),
+            ))
+            .into(),
+            (_, inner) => Err(EvalError::TypeError(format!(
+                "Operator {op} not supported on types: {inner}: {}",
+                inner.type_formatted()
+            )))?,
+        })
+    }
+
+    /// Evaluates `array[index]`, where `expr` is the index access expression
+    /// (only used for error reporting).
+    pub fn evaluate_index_access<'a, T: FieldElement>(
+        array: &Value<'a, T>,
+        index: &Value<'a, T>,
+        expr: &Expression,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        match array {
+            Value::Array(elements) => match index {
+                Value::Integer(index)
+                    if index.is_negative() || *index >= (elements.len() as u64).into() =>
+                {
+                    Err(EvalError::OutOfBounds(format!(
+                        "Index access out of bounds: Tried to access element {index} of array of size {} in: {expr}.",
+                        elements.len()
+                    )))
+                }
+                Value::Integer(index) => Ok(elements[usize::try_from(index).unwrap()].clone()),
+                index => Err(EvalError::TypeError(format!(
+                    "Expected integer for array index access but got {index}: {}",
+                    index.type_formatted()
+                ))),
+            },
+            e => Err(EvalError::TypeError(format!("Expected array, but got {e}"))),
+        }
+    }
+
+    pub fn evaluate_binary_operation<'a, T: FieldElement>(
         left: &Value<'a, T>,
         op: BinaryOperator,
         right: &Value<'a, T>,
@@ -1062,7 +1215,7 @@ mod test {
     #[test]
     pub fn capturing() {
         let src = r#"namespace Main(16);
-            let f: int, (int -> int) -> (int -> int) = |n, g| match n { 99 => |i| n, 1 => g };
+            let f: int, (int -> int) -> (int -> int) = |n, g| match n { 99 => |i| n, _ => g };
             let result = f(1, f(99, |x| x + 3000))(0);
         "#;
         // If the lambda function returned by the expression f(99, ...) does not
@@ -1221,7 +1374,8 @@ mod test {
                 ((_, 2), [y, z]) => 3 + y + z,
                 ((x, 3), _) => x,
                 ((x, -1), _) => x,
-                (t, [_, r]) => r
+                (t, [_, r]) => r,
+                _ => 0
             };
             let res = [
                 f(((1, 9), [20, 4])),
@@ -1322,4 +1476,58 @@ mod test {
             "[1, 3, 4, 7]".to_string()
         );
     }
+
+    #[test]
+    pub fn trait_implementations() {
+        let src = r#"
+            trait Size<T> {
+                size: T -> int,
+            }
+            impl Size<int> {
+                size: |_| 1,
+            }
+            impl<T: Size> Size<T[]> {
+                size: |arr| Size::size(arr[0]) + 10,
+            }
+            let i: int = 3;
+            let a: int[][] = [[7]];
+            let x: int[] = [Size::size(i), Size::size(a)];
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "x"), "[1, 21]".to_string());
+    }
+
+    #[test]
+    pub fn enum_patterns() {
+        let src = r#"
+            enum Option<T> {
+                None,
+                Some(T)
+            }
+            let unwrap_or: Option<int>, int -> int = |o, d| match o {
+                Option::Some(v) => v,
+                Option::None => d,
+            };
+            let x: int[] = [unwrap_or(Option::Some(7), 2), unwrap_or(Option::None, 2)];
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "x"), "[7, 2]".to_string());
+    }
+
+    #[test]
+    pub fn structs() {
+        let src = r#"
+            struct Point {
+                x: int,
+                y: int,
+            }
+            let p: Point = Point { y: 2, x: 1 };
+            let swap: Point -> Point = |Point { x, y }| Point { x: y, y: x };
+            let q: Point = swap(p);
+            let x: int[] = [q.x, q.y, match q { Point { x: 2, y } => y, _ => 0 }];
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "x"), "[2, 1, 1]".to_string());
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "q"),
+            "Point { x: 2, y: 1 }".to_string()
+        );
+    }
 }
@@ -1,12 +1,18 @@
 use core::panic;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
+use itertools::Itertools;
 use powdr_ast::{
     analyzed::{Expression, PolynomialReference, Reference, RepeatedArray},
     parsed::{
-        self, ArrayExpression, ArrayLiteral, IfExpression, LambdaExpression,
-        LetStatementInsideBlock, MatchArm, NamespacedPolynomialReference, Pattern,
-        SelectedExpressions, StatementInsideBlock,
+        self,
+        asm::{Part, SymbolPath},
+        ArrayExpression, ArrayLiteral, FieldAccess, IfExpression, LambdaExpression,
+        LetStatementInsideBlock, MatchArm, NamedExpression, NamespacedPolynomialReference, Pattern,
+        SelectedExpressions, StatementInsideBlock, StructExpression,
     },
 };
 use powdr_number::DegreeType;
@@ -82,7 +88,13 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
     pub fn process_expression(&mut self, expr: parsed::Expression) -> Expression {
         use parsed::Expression as PExpression;
         match expr {
-            PExpression::Reference(poly) => Expression::Reference(self.process_reference(poly)),
+            PExpression::Reference(reference) => match self.try_to_field_access(&reference) {
+                Some((object, field)) => Expression::FieldAccess(FieldAccess {
+                    object: Box::new(Expression::Reference(self.process_reference(object))),
+                    field,
+                }),
+                None => Expression::Reference(self.process_reference(reference)),
+            },
             PExpression::PublicReference(name) => Expression::PublicReference(name),
             PExpression::Number(n, t) => Expression::Number(n, t),
             PExpression::String(value) => Expression::String(value),
@@ -93,8 +105,12 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
                 })
             }
             PExpression::LambdaExpression(LambdaExpression { kind, params, body }) => {
-                let body = Box::new(self.process_function(&params, *body));
-                Expression::LambdaExpression(LambdaExpression { kind, params, body })
+                let (params, body) = self.process_function(params, *body);
+                Expression::LambdaExpression(LambdaExpression {
+                    kind,
+                    params,
+                    body: Box::new(body),
+                })
             }
             PExpression::BinaryOperation(left, op, right) => Expression::BinaryOperation(
                 Box::new(self.process_expression(*left)),
@@ -114,12 +130,30 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
                 function: Box::new(self.process_expression(*c.function)),
                 arguments: self.process_expressions(c.arguments),
             }),
+            PExpression::StructExpression(StructExpression { name, fields }) => {
+                Expression::StructExpression(StructExpression {
+                    name: self.process_struct_name(&name),
+                    fields: fields
+                        .into_iter()
+                        .map(|NamedExpression { name, body }| NamedExpression {
+                            name,
+                            body: Box::new(self.process_expression(*body)),
+                        })
+                        .collect(),
+                })
+            }
+            PExpression::FieldAccess(FieldAccess { object, field }) => {
+                Expression::FieldAccess(FieldAccess {
+                    object: Box::new(self.process_expression(*object)),
+                    field,
+                })
+            }
             PExpression::MatchExpression(scrutinee, arms) => Expression::MatchExpression(
                 Box::new(self.process_expression(*scrutinee)),
                 arms.into_iter()
                     .map(|MatchArm { pattern, value }| {
                         let vars = self.save_local_variables();
-                        self.process_pattern(&pattern);
+                        let pattern = self.process_pattern(pattern);
                         let value = self.process_expression(value);
                         self.reset_local_variables(vars);
                         MatchArm { pattern, value }
@@ -142,27 +176,77 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
         }
     }
 
-    /// Processes a pattern, registering all variables bound in there.
-    fn process_pattern(&mut self, pattern: &Pattern) {
+    /// Processes a pattern, registering all variables bound in there
+    /// and resolving the names of struct types and enum variants.
+    fn process_pattern(&mut self, pattern: Pattern) -> Pattern {
         match pattern {
-            Pattern::CatchAll | Pattern::Ellipsis | Pattern::Number(_) | Pattern::String(_) => {}
-            Pattern::Tuple(items) | Pattern::Array(items) => {
-                if matches!(pattern, Pattern::Array(_)) {
-                    // If there is more than one Pattern::Ellipsis in items, it is an error
-                    if items.iter().filter(|p| *p == &Pattern::Ellipsis).count() > 1 {
-                        panic!("Only one \"..\"-item allowed in array pattern");
-                    }
+            Pattern::CatchAll | Pattern::Ellipsis | Pattern::Number(_) | Pattern::String(_) => {
+                pattern
+            }
+            Pattern::Tuple(items) => Pattern::Tuple(self.process_patterns(items)),
+            Pattern::Array(items) => {
+                // If there is more than one Pattern::Ellipsis in items, it is an error
+                if items.iter().filter(|p| *p == &Pattern::Ellipsis).count() > 1 {
+                    panic!("Only one \"..\"-item allowed in array pattern");
                 }
-                items.iter().for_each(|p| self.process_pattern(p));
+                Pattern::Array(self.process_patterns(items))
             }
-            Pattern::Variable(name) => {
+            Pattern::Variable(ref name) => {
                 let id = self.local_variable_counter;
                 if self.local_variables.insert(name.clone(), id).is_some() {
                     panic!("Variable already defined: {name}");
                 }
                 self.local_variable_counter += 1;
+                pattern
             }
+            Pattern::Struct(name, fields) => Pattern::Struct(
+                self.process_struct_name(&name),
+                fields
+                    .into_iter()
+                    .map(|(field, pattern)| (field, self.process_pattern(pattern)))
+                    .collect(),
+            ),
+            Pattern::Enum(name, fields) => Pattern::Enum(
+                SymbolPath::from_str(&self.driver.resolve_value_ref(&name)).unwrap(),
+                fields.map(|fields| self.process_patterns(fields)),
+            ),
+        }
+    }
+
+    fn process_patterns(&mut self, patterns: Vec<Pattern>) -> Vec<Pattern> {
+        patterns
+            .into_iter()
+            .map(|p| self.process_pattern(p))
+            .collect()
+    }
+
+    fn process_struct_name(&self, name: &SymbolPath) -> SymbolPath {
+        SymbolPath::from_str(&self.driver.resolve_type_ref(name)).unwrap()
+    }
+
+    /// The parser turns `a.b` into a reference to `b` in namespace `a`.
+    /// If `a` is a local variable or if there is no symbol `a.b` but
+    /// there is a symbol `a`, this is an access to the field `b` of `a` instead.
+    /// In that case, returns the reference to `a` and the field name.
+    fn try_to_field_access(
+        &self,
+        reference: &NamespacedPolynomialReference,
+    ) -> Option<(NamespacedPolynomialReference, String)> {
+        if reference.type_args.is_some() {
+            return None;
         }
+        let (Part::Named(object), Part::Named(field)) = reference.path.parts().collect_tuple()?
+        else {
+            return None;
+        };
+        let object_path = SymbolPath::from_identifier(object.clone());
+        (self.local_variables.contains_key(object)
+            || (self
+                .driver
+                .try_resolve_ref(&reference.path, false)
+                .is_none()
+                && self.driver.try_resolve_ref(&object_path, false).is_some()))
+        .then(|| (object_path.into(), field.clone()))
     }
 
     fn process_reference(&mut self, reference: NamespacedPolynomialReference) -> Reference {
@@ -177,21 +261,24 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
 
     pub fn process_function(
         &mut self,
-        params: &[Pattern],
+        params: Vec<Pattern>,
         expression: ::powdr_ast::parsed::Expression,
-    ) -> Expression {
+    ) -> (Vec<Pattern>, Expression) {
         let previous_local_vars = self.save_local_variables();
 
-        for param in params {
-            if !param.is_irrefutable() {
-                panic!("Function parameters must be irrefutable, but {param} is refutable.");
-            }
-            self.process_pattern(param);
-        }
+        let params = params
+            .into_iter()
+            .map(|param| {
+                if !param.is_irrefutable() {
+                    panic!("Function parameters must be irrefutable, but {param} is refutable.");
+                }
+                self.process_pattern(param)
+            })
+            .collect();
         let processed_value = self.process_expression(expression);
 
         self.reset_local_variables(previous_local_vars);
-        processed_value
+        (params, processed_value)
     }
 
     fn process_block_expression(
@@ -212,7 +299,7 @@ impl<'a, D: AnalysisDriver> ExpressionProcessor<'a, D> {
                         panic!("Let statement requires an irrefutable pattern, but {pattern} is refutable.");
                     }
                     let value = value.map(|v| self.process_expression(v));
-                    self.process_pattern(&pattern);
+                    let pattern = self.process_pattern(pattern);
                     StatementInsideBlock::LetStatement(LetStatementInsideBlock { pattern, value })
                 }
                 StatementInsideBlock::Expression(expr) => {
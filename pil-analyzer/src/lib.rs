@@ -1,12 +1,15 @@
 #![deny(clippy::print_stdout)]
 
 mod call_graph;
+pub mod compiled_evaluator;
 mod condenser;
 pub mod evaluator;
 pub mod expression_processor;
+mod match_checker;
 mod pil_analyzer;
 mod side_effect_checker;
 mod statement_processor;
+mod traits;
 mod type_builtins;
 mod type_inference;
 mod type_processor;
@@ -38,6 +41,11 @@ pub trait AnalysisDriver: Clone + Copy {
     /// Turns a reference to a name with an optional namespace into an absolute name.
     /// If `is_type` is true, expects references to type names, otherwise
     /// only references to value names.
-    fn resolve_ref(&self, path: &SymbolPath, is_type: bool) -> String;
+    fn resolve_ref(&self, path: &SymbolPath, is_type: bool) -> String {
+        self.try_resolve_ref(path, is_type)
+            .unwrap_or_else(|| panic!("Symbol not found: {}", path.to_dotted_string()))
+    }
+    /// Same as `resolve_ref`, but returns `None` if the symbol is not found.
+    fn try_resolve_ref(&self, path: &SymbolPath, is_type: bool) -> Option<String>;
     fn definitions(&self) -> &HashMap<String, (Symbol, Option<FunctionValueDefinition>)>;
 }
@@ -0,0 +1,407 @@
+use std::collections::{BTreeSet, HashMap};
+use std::iter::once;
+use std::ops::ControlFlow;
+
+use itertools::Itertools;
+use powdr_ast::{
+    analyzed::{Expression, FunctionValueDefinition, Symbol},
+    parsed::{asm::SymbolPath, visitor::ExpressionVisitable, MatchArm, Pattern},
+};
+
+/// Checks all match expressions inside `e`.
+/// Returns an error if a match expression is not exhaustive, i.e. if there
+/// is a value that is not matched by any of its arms.
+/// Otherwise, returns a list of warnings about arms that can never be reached
+/// because all values they match are already matched by previous arms.
+pub fn check(
+    definitions: &HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
+    e: &Expression,
+) -> Result<Vec<String>, String> {
+    let checker = MatchChecker { definitions };
+    let mut warnings = vec![];
+    match e.pre_visit_expressions_return(&mut |e| match e {
+        Expression::MatchExpression(_, arms) => match checker.check_match(arms) {
+            Ok(unreachable) => {
+                warnings.extend(unreachable.into_iter().map(|pattern| {
+                    format!("Unreachable pattern {pattern} in match expression:\n{e}")
+                }));
+                ControlFlow::Continue(())
+            }
+            Err(missing) => ControlFlow::Break(format!(
+                "Match expression is not exhaustive, pattern {missing} is not covered:\n{e}"
+            )),
+        },
+        _ => ControlFlow::Continue(()),
+    }) {
+        ControlFlow::Continue(()) => Ok(warnings),
+        ControlFlow::Break(err) => Err(err),
+    }
+}
+
+/// A constructor of values as it appears in patterns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Constructor {
+    /// A tuple with the given number of items.
+    Tuple(usize),
+    /// A struct, where only the given fields are matched.
+    Struct(SymbolPath, Vec<String>),
+    /// An enum variant and the number of its fields (if it has fields).
+    Variant(SymbolPath, Option<usize>),
+    /// A number or string literal.
+    Literal(Pattern),
+    /// An array of exactly the given length.
+    Array(usize),
+    /// An array of at least the given length.
+    ArrayAtLeast(usize),
+}
+
+impl Constructor {
+    /// The number of sub-patterns of the constructor.
+    fn arity(&self) -> usize {
+        match self {
+            Constructor::Tuple(n) | Constructor::Array(n) | Constructor::ArrayAtLeast(n) => *n,
+            Constructor::Struct(_, fields) => fields.len(),
+            Constructor::Variant(_, fields) => fields.unwrap_or_default(),
+            Constructor::Literal(_) => 0,
+        }
+    }
+
+    /// Creates a pattern from this constructor and its sub-patterns.
+    fn to_pattern(&self, items: Vec<Pattern>) -> Pattern {
+        match self {
+            Constructor::Tuple(_) => Pattern::Tuple(items),
+            Constructor::Struct(name, fields) => {
+                Pattern::Struct(name.clone(), fields.iter().cloned().zip(items).collect())
+            }
+            Constructor::Variant(name, fields) => {
+                Pattern::Enum(name.clone(), fields.map(|_| items))
+            }
+            Constructor::Literal(p) => p.clone(),
+            Constructor::Array(_) => Pattern::Array(items),
+            Constructor::ArrayAtLeast(_) => {
+                Pattern::Array(items.into_iter().chain(once(Pattern::Ellipsis)).collect())
+            }
+        }
+    }
+}
+
+/// Implements the "usefulness" algorithm from
+/// "Warnings for pattern matching" by Luc Maranget.
+/// Patterns are checked without type information, since type checking
+/// already ensured that all patterns in a match expression have the same type.
+struct MatchChecker<'a> {
+    definitions: &'a HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
+}
+
+impl<'a> MatchChecker<'a> {
+    /// Returns the patterns of all unreachable arms or a pattern that is not
+    /// covered by any arm.
+    fn check_match(&self, arms: &[MatchArm<Expression>]) -> Result<Vec<Pattern>, Pattern> {
+        let mut rows: Vec<Vec<Pattern>> = vec![];
+        let mut unreachable = vec![];
+        for MatchArm { pattern, .. } in arms {
+            let row = vec![pattern.clone()];
+            if self.find_witness(&rows, &row).is_none() {
+                unreachable.push(pattern.clone());
+            }
+            rows.push(row);
+        }
+        match self.find_witness(&rows, &[Pattern::CatchAll]) {
+            Some(mut witness) => Err(witness.remove(0)),
+            None => Ok(unreachable),
+        }
+    }
+
+    /// Returns a list of patterns that is matched by `row` but not by any of the `rows`,
+    /// or None if every value matched by `row` is also matched by one of the `rows`.
+    fn find_witness(&self, rows: &[Vec<Pattern>], row: &[Pattern]) -> Option<Vec<Pattern>> {
+        let Some((head, tail)) = row.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        let column = rows.iter().map(|r| &r[0]).chain(once(head)).collect_vec();
+        let constructors = if is_wildcard(head) {
+            self.all_constructors(&column)
+        } else {
+            Some(self.constructors(head, &column))
+        };
+        match constructors {
+            Some(constructors) => constructors
+                .into_iter()
+                .find_map(|c| self.find_witness_for_constructor(&c, rows, row)),
+            None => {
+                // The constructors in the column do not cover all values,
+                // so we only need to look at the rows that start with a wildcard.
+                let rows = rows
+                    .iter()
+                    .filter(|r| is_wildcard(&r[0]))
+                    .map(|r| r[1..].to_vec())
+                    .collect_vec();
+                let witness = self.find_witness(&rows, tail)?;
+                Some(once(Pattern::CatchAll).chain(witness).collect())
+            }
+        }
+    }
+
+    /// Like `find_witness` but only considers values created by the constructor `c`.
+    fn find_witness_for_constructor(
+        &self,
+        c: &Constructor,
+        rows: &[Vec<Pattern>],
+        row: &[Pattern],
+    ) -> Option<Vec<Pattern>> {
+        let specialize = |r: &[Pattern]| -> Option<Vec<Pattern>> {
+            Some(
+                specialize(c, &r[0])?
+                    .into_iter()
+                    .chain(r[1..].iter().cloned())
+                    .collect(),
+            )
+        };
+        let rows = rows.iter().filter_map(|r| specialize(r)).collect_vec();
+        let mut witness = self.find_witness(&rows, &specialize(row)?)?;
+        let rest = witness.split_off(c.arity());
+        Some(once(c.to_pattern(witness)).chain(rest).collect())
+    }
+
+    /// Returns the constructors of all values matched by the non-wildcard pattern `p`.
+    fn constructors(&self, p: &Pattern, column: &[&Pattern]) -> Vec<Constructor> {
+        match p {
+            Pattern::Number(_) | Pattern::String(_) => vec![Constructor::Literal(p.clone())],
+            Pattern::Tuple(items) => vec![Constructor::Tuple(items.len())],
+            Pattern::Struct(name, _) => {
+                vec![Constructor::Struct(name.clone(), struct_fields(column))]
+            }
+            Pattern::Enum(name, fields) => vec![Constructor::Variant(
+                name.clone(),
+                fields.as_ref().map(|f| f.len()),
+            )],
+            Pattern::Array(items) => {
+                let limit = array_length_limit(column);
+                match split_at_ellipsis(items) {
+                    Some((prefix, suffix)) => (prefix.len() + suffix.len()..limit)
+                        .map(Constructor::Array)
+                        .chain(once(Constructor::ArrayAtLeast(limit)))
+                        .collect(),
+                    None => vec![Constructor::Array(items.len())],
+                }
+            }
+            Pattern::CatchAll | Pattern::Variable(_) | Pattern::Ellipsis => unreachable!(),
+        }
+    }
+
+    /// Returns all constructors of the type of the patterns in the column
+    /// or None if the column only contains wildcards or the type has
+    /// infinitely many constructors.
+    fn all_constructors(&self, column: &[&Pattern]) -> Option<Vec<Constructor>> {
+        let p = column.iter().find(|p| !is_wildcard(p))?;
+        match p {
+            Pattern::Number(_) | Pattern::String(_) => None,
+            Pattern::Enum(name, _) => {
+                let Some((_, Some(FunctionValueDefinition::TypeConstructor(enum_decl, _)))) =
+                    self.definitions.get(&name.to_dotted_string())
+                else {
+                    panic!("Expected enum variant: {name}")
+                };
+                Some(
+                    enum_decl
+                        .variants
+                        .iter()
+                        .map(|v| {
+                            let mut variant = name.clone();
+                            *variant.try_last_part_mut().unwrap() = v.name.clone();
+                            Constructor::Variant(variant, v.fields.as_ref().map(|f| f.len()))
+                        })
+                        .collect(),
+                )
+            }
+            Pattern::Tuple(_) | Pattern::Struct(_, _) => Some(self.constructors(p, column)),
+            Pattern::Array(_) => {
+                let limit = array_length_limit(column);
+                Some(
+                    (0..limit)
+                        .map(Constructor::Array)
+                        .chain(once(Constructor::ArrayAtLeast(limit)))
+                        .collect(),
+                )
+            }
+            Pattern::CatchAll | Pattern::Variable(_) | Pattern::Ellipsis => unreachable!(),
+        }
+    }
+}
+
+/// Returns the sub-patterns of `p` if `p` matches values created by
+/// the constructor `c` and None otherwise.
+fn specialize(c: &Constructor, p: &Pattern) -> Option<Vec<Pattern>> {
+    if is_wildcard(p) {
+        return Some(vec![Pattern::CatchAll; c.arity()]);
+    }
+    match (c, p) {
+        (Constructor::Tuple(_), Pattern::Tuple(items)) => Some(items.clone()),
+        (Constructor::Struct(_, names), Pattern::Struct(_, fields)) => Some(
+            names
+                .iter()
+                .map(|name| {
+                    fields
+                        .iter()
+                        .find(|(field, _)| field == name)
+                        .map(|(_, p)| p.clone())
+                        .unwrap_or(Pattern::CatchAll)
+                })
+                .collect(),
+        ),
+        (Constructor::Variant(variant, _), Pattern::Enum(name, fields)) => {
+            (variant == name).then(|| fields.clone().unwrap_or_default())
+        }
+        (Constructor::Literal(literal), p) => (literal == p).then(Vec::new),
+        (Constructor::Array(len) | Constructor::ArrayAtLeast(len), Pattern::Array(items)) => {
+            match split_at_ellipsis(items) {
+                Some((prefix, suffix)) => {
+                    let fill = len.checked_sub(prefix.len() + suffix.len())?;
+                    Some(
+                        prefix
+                            .iter()
+                            .cloned()
+                            .chain(vec![Pattern::CatchAll; fill])
+                            .chain(suffix.iter().cloned())
+                            .collect(),
+                    )
+                }
+                None => (matches!(c, Constructor::Array(_)) && items.len() == *len)
+                    .then(|| items.clone()),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_wildcard(p: &Pattern) -> bool {
+    matches!(p, Pattern::CatchAll | Pattern::Variable(_))
+}
+
+/// Returns the items before and after the ellipsis if the array pattern contains one.
+fn split_at_ellipsis(items: &[Pattern]) -> Option<(&[Pattern], &[Pattern])> {
+    let index = items.iter().position(|p| p == &Pattern::Ellipsis)?;
+    Some((&items[..index], &items[index + 1..]))
+}
+
+/// Returns a length such that all arrays of at least this length are
+/// matched by the same patterns in the column.
+fn array_length_limit(column: &[&Pattern]) -> usize {
+    column
+        .iter()
+        .filter_map(|p| match p {
+            Pattern::Array(items) => Some(match split_at_ellipsis(items) {
+                Some((prefix, suffix)) => prefix.len() + suffix.len(),
+                None => items.len() + 1,
+            }),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// Returns the names of all fields matched by the struct patterns in the column.
+fn struct_fields(column: &[&Pattern]) -> Vec<String> {
+    column
+        .iter()
+        .filter_map(|p| match p {
+            Pattern::Struct(_, fields) => Some(fields.iter().map(|(name, _)| name.clone())),
+            _ => None,
+        })
+        .flatten()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_ast::analyzed::TypedExpression;
+    use powdr_number::GoldilocksField;
+
+    use crate::analyze_string;
+
+    use super::*;
+
+    fn unreachable_arms(src: &str, name: &str) -> Vec<String> {
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let Some(FunctionValueDefinition::Expression(TypedExpression { e, .. })) =
+            &analyzed.definitions[name].1
+        else {
+            panic!()
+        };
+        check(&analyzed.definitions, e)
+            .unwrap()
+            .into_iter()
+            .map(|w| w.lines().next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn no_unreachable_arms() {
+        let src = r#"
+            let f: int, int[] -> int = |i, a| match (i, a) {
+                (0, [x]) => x,
+                (0, _) => 0,
+                (_, [.., x]) => x,
+                _ => 1,
+            };
+        "#;
+        assert!(unreachable_arms(src, "f").is_empty());
+    }
+
+    #[test]
+    fn unreachable_literal() {
+        let src = r#"
+            let f: int -> int = |i| match i {
+                0 => 1,
+                x => x,
+                1 => 2,
+            };
+        "#;
+        assert_eq!(
+            unreachable_arms(src, "f"),
+            vec!["Unreachable pattern 1 in match expression:"]
+        );
+    }
+
+    #[test]
+    fn unreachable_enum_variants() {
+        let src = r#"
+            enum E { A(int), B }
+            let f: E -> int = |e| match e {
+                E::A(0) => 0,
+                E::B => 1,
+                E::A(_) => 2,
+                E::A(3) => 3,
+                E::B => 4,
+            };
+        "#;
+        assert_eq!(
+            unreachable_arms(src, "f"),
+            vec![
+                "Unreachable pattern E::A(3) in match expression:",
+                "Unreachable pattern E::B in match expression:"
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_arrays() {
+        let src = r#"
+            let f: int[] -> int = |a| match a {
+                [] => 0,
+                [x, ..] => x,
+                [_, y] => y,
+                _ => 1,
+            };
+        "#;
+        assert_eq!(
+            unreachable_arms(src, "f"),
+            vec![
+                "Unreachable pattern [_, y] in match expression:",
+                "Unreachable pattern _ in match expression:"
+            ]
+        );
+    }
+}
@@ -1,13 +1,20 @@
-use std::collections::{HashMap, HashSet};
+use std::cmp::max;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
+use std::fmt::Display;
 use std::fs;
 use std::iter::once;
 use std::path::{Path, PathBuf};
 
+use itertools::Itertools;
 use powdr_ast::parsed::asm::{AbsoluteSymbolPath, SymbolPath};
-use powdr_ast::parsed::types::Type;
+use powdr_ast::parsed::types::{Type, TypeScheme};
 use powdr_ast::parsed::visitor::Children;
-use powdr_ast::parsed::{self, FunctionKind, LambdaExpression, PILFile, PilStatement};
+use powdr_ast::parsed::{
+    self, FunctionKind, LambdaExpression, PILFile, PilStatement, TraitDeclaration,
+    TraitImplementation, TypeDeclaration,
+};
+use powdr_ast::SourceRef;
 use powdr_number::{DegreeType, FieldElement, GoldilocksField};
 
 use powdr_ast::analyzed::{
@@ -17,7 +24,7 @@ use powdr_ast::analyzed::{
 use powdr_parser::parse_type;
 
 use crate::type_inference::{infer_types, ExpectedType};
-use crate::{side_effect_checker, AnalysisDriver};
+use crate::{match_checker, side_effect_checker, AnalysisDriver};
 
 use crate::statement_processor::{Counters, PILItem, StatementProcessor};
 use crate::{condenser, evaluator, expression_processor::ExpressionProcessor};
@@ -45,6 +52,7 @@ fn analyze<T: FieldElement>(files: Vec<PILFile>) -> Analyzed<T> {
     analyzer.process(files);
     analyzer.side_effect_check();
     analyzer.type_check();
+    analyzer.match_check();
     analyzer.condense::<T>()
 }
 
@@ -53,10 +61,17 @@ struct PILAnalyzer {
     /// The set of all known symbols. If the flag is true, the symbol is a type name.
     known_symbols: HashMap<String, bool>,
     current_namespace: AbsoluteSymbolPath,
+    /// The degree of the current namespace.
+    /// Namespaces without a degree inherit the degree of the previous namespace.
     polynomial_degree: Option<DegreeType>,
+    /// The maximum degree of all namespaces.
+    max_degree: Option<DegreeType>,
     definitions: HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
     public_declarations: HashMap<String, PublicDeclaration>,
     identities: Vec<Identity<Expression>>,
+    /// Trait implementations, they are attached to the trait declarations
+    /// after all statements have been processed.
+    trait_implementations: Vec<(SourceRef, TraitImplementation<Expression>)>,
     /// The order in which definitions and identities
     /// appear in the source.
     source_order: Vec<StatementIdentifier>,
@@ -128,6 +143,53 @@ impl PILAnalyzer {
                 self.handle_statement(statement);
             }
         }
+
+        self.attach_trait_implementations();
+    }
+
+    /// Checks that the trait implementations match their trait declarations and
+    /// stores them with the declarations.
+    fn attach_trait_implementations(&mut self) {
+        for (source, trait_impl) in std::mem::take(&mut self.trait_implementations) {
+            let trait_name = trait_impl.name.to_dotted_string();
+            let Some((_, Some(FunctionValueDefinition::TraitDeclaration(trait_decl, impls)))) =
+                self.definitions.get_mut(&trait_name)
+            else {
+                panic!("{source}: Expected {trait_name} to be a trait.");
+            };
+            if trait_impl.type_args.len() != trait_decl.type_vars.len() {
+                panic!(
+                    "{source}: Trait {trait_name} expects {} type argument(s), but the implementation provides {}.",
+                    trait_decl.type_vars.len(),
+                    trait_impl.type_args.len()
+                );
+            }
+            if let Some(Type::TypeVar(v)) = trait_impl.type_args.first() {
+                panic!(
+                    "{source}: Implementations of {trait_name} for an unconstrained type variable {v} are not supported."
+                );
+            }
+            let declared = trait_decl
+                .functions
+                .iter()
+                .map(|f| &f.name)
+                .collect::<BTreeSet<_>>();
+            let implemented = trait_impl
+                .functions
+                .iter()
+                .map(|f| &f.name)
+                .collect::<BTreeSet<_>>();
+            if let Some(name) = implemented.iter().find(|n| !declared.contains(*n)) {
+                panic!("{source}: Function {name} is not a member of trait {trait_name}.");
+            }
+            if let Some(name) = declared.iter().find(|n| !implemented.contains(*n)) {
+                panic!("{source}: Missing function {name} in implementation of {trait_name}.");
+            }
+            if implemented.len() != trait_impl.functions.len() {
+                panic!("{source}: Duplicate function in implementation of {trait_name}.");
+            }
+            impls.push(trait_impl);
+        }
     }
 
     /// Check that query and constr functions are used in the correct contexts.
@@ -148,6 +210,8 @@ impl PILAnalyzer {
                             FunctionKind::Constr
                         }
                     }
+                    // Functions of trait implementations have to be pure.
+                    FunctionValueDefinition::TraitDeclaration(_, _) => FunctionKind::Pure,
                     _ => FunctionKind::Constr,
                 },
                 // Default is constr.
@@ -172,11 +236,25 @@ impl PILAnalyzer {
     }
 
     pub fn type_check(&mut self) {
-        let query_type: Type = parse_type("int -> std::prover::Query").unwrap().into();
+        let query_type: Type = parse_type("int -> std::utils::Option<std::prover::Query>")
+            .unwrap()
+            .into();
         let mut expressions = vec![];
+        let mut trait_impls = HashMap::new();
+        let structs = self
+            .definitions
+            .iter()
+            .filter_map(|(name, (_, value))| match value {
+                Some(FunctionValueDefinition::TypeDeclaration(TypeDeclaration::Struct(
+                    struct_decl,
+                ))) => Some((name.clone(), struct_decl.clone())),
+                _ => None,
+            })
+            .collect();
         // Collect all definitions with their types and expressions.
-        // We filter out enum type declarations (the constructor functions have been added
-        // by the statement processor already).
+        // We filter out type declarations (the constructor functions of enums have been added
+        // by the statement processor already, struct declarations are passed separately).
+        // Trait declarations are replaced by the functions of their implementations.
         // For Arrays, we also collect the inner expressions and expect them to be field elements.
         let definitions = self
             .definitions
@@ -186,6 +264,19 @@ impl PILAnalyzer {
             })
             .flat_map(|(name, (symbol, value))| {
                 let (type_scheme, expr) = match (symbol.kind, value) {
+                    (_, Some(FunctionValueDefinition::TraitDeclaration(trait_decl, impls))) => {
+                        trait_impls.insert(
+                            name.clone(),
+                            impls
+                                .iter()
+                                .map(|i| TypeScheme {
+                                    vars: i.type_vars.clone(),
+                                    ty: i.type_args[0].clone(),
+                                })
+                                .collect(),
+                        );
+                        return trait_implementation_functions(name, trait_decl, impls);
+                    }
                     (SymbolKind::Poly(PolynomialType::Committed), Some(value)) => {
                         // Witness column, move its value (query function) into the expressions to be checked separately.
                         let type_scheme = type_from_definition(symbol, &None);
@@ -222,7 +313,7 @@ impl PILAnalyzer {
                         (type_scheme, None)
                     }
                 };
-                Some((name.clone(), (type_scheme, expr)))
+                vec![(name.clone(), (type_scheme, expr))]
             })
             .collect();
         // Collect all expressions in identities.
@@ -245,12 +336,18 @@ impl PILAnalyzer {
                 }
             }
         }
-        let inferred_types = infer_types(definitions, &mut expressions, &statement_type)
-            .map_err(|e| {
-                eprintln!("\nError during type inference:\n{e}");
-                e
-            })
-            .unwrap();
+        let inferred_types = infer_types(
+            definitions,
+            &mut expressions,
+            &statement_type,
+            trait_impls,
+            structs,
+        )
+        .map_err(|e| {
+            eprintln!("\nError during type inference:\n{e}");
+            e
+        })
+        .unwrap();
         // Store the inferred types.
         for (name, ty) in inferred_types {
             let Some(FunctionValueDefinition::Expression(TypedExpression {
@@ -264,9 +361,31 @@ impl PILAnalyzer {
         }
     }
 
+    /// Check that all match expressions are exhaustive and warn about unreachable match arms.
+    pub fn match_check(&self) {
+        let check = |source: &SourceRef, name: &dyn Display, e: &Expression| {
+            let warnings = match_checker::check(&self.definitions, e).unwrap_or_else(|err| {
+                panic!("{source}: Error checking match expressions of {name}: {err}")
+            });
+            for warning in warnings {
+                log::warn!("{source}: {warning}");
+            }
+        };
+        for (name, (symbol, value)) in &self.definitions {
+            for e in value.iter().flat_map(|v| v.children()) {
+                check(&symbol.source, name, e);
+            }
+        }
+        for id in &self.identities {
+            for e in id.children() {
+                check(&id.source, &format!("identity {id}"), e);
+            }
+        }
+    }
+
     pub fn condense<T: FieldElement>(self) -> Analyzed<T> {
         condenser::condense::<T>(
-            self.polynomial_degree,
+            self.max_degree,
             self.definitions,
             self.public_declarations,
             &self.identities,
@@ -341,6 +460,9 @@ impl PILAnalyzer {
                             self.source_order.push(StatementIdentifier::Identity(index));
                             self.identities.push(identity)
                         }
+                        PILItem::TraitImplementation(source, trait_impl) => {
+                            self.trait_implementations.push((source, trait_impl))
+                        }
                     }
                 }
             }
@@ -360,14 +482,8 @@ impl PILAnalyzer {
                     .unwrap(),
             )
             .unwrap();
-            if let Some(degree) = self.polynomial_degree {
-                assert_eq!(
-                    degree, namespace_degree,
-                    "all namespaces must have the same degree"
-                );
-            } else {
-                self.polynomial_degree = Some(namespace_degree);
-            }
+            self.polynomial_degree = Some(namespace_degree);
+            self.max_degree = max(self.max_degree, Some(namespace_degree));
         }
         self.current_namespace = AbsoluteSymbolPath::default().join(name);
     }
@@ -377,6 +493,46 @@ impl PILAnalyzer {
     }
 }
 
+/// A named definition to be type-checked, with its declared type scheme, if any.
+type DefinitionToCheck<'a> = (String, (Option<TypeScheme>, Option<&'a mut Expression>));
+
+/// Returns the functions of the trait implementations as definitions to be type-checked,
+/// together with their type schemes derived from the trait declaration.
+fn trait_implementation_functions<'a>(
+    trait_name: &str,
+    trait_decl: &TraitDeclaration,
+    impls: &'a mut [TraitImplementation<Expression>],
+) -> Vec<DefinitionToCheck<'a>> {
+    let trait_var = &trait_decl.type_vars[0];
+    impls
+        .iter_mut()
+        .flat_map(|trait_impl| {
+            let substitution = [(trait_var.clone(), trait_impl.type_args[0].clone())].into();
+            let impl_name = format!(
+                "impl {trait_name}<{}>",
+                trait_impl.type_args.iter().format(", ")
+            );
+            let type_vars = trait_impl.type_vars.clone();
+            trait_impl.functions.iter_mut().map(move |function| {
+                let ty = trait_decl
+                    .function_by_name(&function.name)
+                    .unwrap()
+                    .ty
+                    .clone()
+                    .substitute_type_vars_to(&substitution);
+                let type_scheme = TypeScheme {
+                    vars: type_vars.clone(),
+                    ty,
+                };
+                (
+                    format!("{impl_name}::{}", function.name),
+                    (Some(type_scheme), Some(function.body.as_mut())),
+                )
+            })
+        })
+        .collect()
+}
+
 #[derive(Clone, Copy)]
 struct Driver<'a>(&'a PILAnalyzer);
 
@@ -394,25 +550,21 @@ impl<'a> AnalysisDriver for Driver<'a> {
             })
     }
 
-    fn resolve_ref(&self, path: &SymbolPath, is_type: bool) -> String {
+    fn try_resolve_ref(&self, path: &SymbolPath, is_type: bool) -> Option<String> {
         // Try to resolve the name starting at the current namespace and then
         // go up level by level until the root.
 
-        self.0
-            .current_namespace
-            .iter_to_root()
-            .find_map(|prefix| {
-                let path = prefix.join(path.clone()).to_dotted_string();
-                self.0.known_symbols.get(&path).map(|t| {
-                    if *t && !is_type {
-                        panic!("Expected value but got type: {path}");
-                    } else if !t && is_type {
-                        panic!("Expected type but got value: {path}");
-                    }
-                    path
-                })
+        self.0.current_namespace.iter_to_root().find_map(|prefix| {
+            let path = prefix.join(path.clone()).to_dotted_string();
+            self.0.known_symbols.get(&path).map(|t| {
+                if *t && !is_type {
+                    panic!("Expected value but got type: {path}");
+                } else if !t && is_type {
+                    panic!("Expected type but got value: {path}");
+                }
+                path
             })
-            .unwrap_or_else(|| panic!("Symbol not found: {}", path.to_dotted_string()))
+        })
     }
 
     fn definitions(&self) -> &HashMap<String, (Symbol, Option<FunctionValueDefinition>)> {
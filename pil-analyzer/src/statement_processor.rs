@@ -1,14 +1,18 @@
 use std::collections::{BTreeMap, HashSet};
 use std::iter;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use itertools::Itertools;
 
 use powdr_ast::analyzed::TypedExpression;
 use powdr_ast::parsed::{
     self,
+    asm::SymbolPath,
     types::{ArrayType, Type, TypeScheme},
-    EnumDeclaration, EnumVariant, FunctionDefinition, PilStatement, PolynomialName,
-    SelectedExpressions,
+    EnumDeclaration, EnumVariant, FunctionDefinition, NamedExpression, PilStatement,
+    PolynomialName, SelectedExpressions, StructDeclaration, StructField, TraitDeclaration,
+    TraitFunction, TraitImplementation, TypeDeclaration,
 };
 use powdr_ast::parsed::{FunctionKind, LambdaExpression};
 use powdr_ast::SourceRef;
@@ -28,6 +32,7 @@ pub enum PILItem {
     Definition(Symbol, Option<FunctionValueDefinition>),
     PublicDeclaration(PublicDeclaration),
     Identity(Identity<Expression>),
+    TraitImplementation(SourceRef, TraitImplementation<Expression>),
 }
 
 pub struct Counters {
@@ -175,9 +180,26 @@ where
                     None,
                     None,
                     Some(FunctionDefinition::TypeDeclaration(
-                        enum_declaration.clone(),
+                        enum_declaration.clone().into(),
                     )),
                 ),
+            PilStatement::StructDeclaration(source, struct_declaration) => self
+                .handle_symbol_definition(
+                    source,
+                    struct_declaration.name.clone(),
+                    SymbolKind::Other(),
+                    None,
+                    None,
+                    Some(FunctionDefinition::TypeDeclaration(
+                        struct_declaration.clone().into(),
+                    )),
+                ),
+            PilStatement::TraitDeclaration(source, trait_declaration) => {
+                self.handle_trait_declaration(source, trait_declaration)
+            }
+            PilStatement::TraitImplementation(source, trait_impl) => {
+                self.handle_trait_implementation(source, trait_impl)
+            }
             _ => self.handle_identity_statement(statement),
         }
     }
@@ -236,6 +258,9 @@ where
                         .format(", ")
                 );
             };
+            let vars = self
+                .type_processor(&Default::default())
+                .process_bounds(vars);
             TypeScheme { vars, ty }
         });
 
@@ -422,13 +447,32 @@ where
             absolute_name: absolute_name.clone(),
             kind: symbol_kind,
             length,
+            degree: match symbol_kind {
+                SymbolKind::Poly(_) => self.degree,
+                _ => None,
+            },
         };
 
-        if let Some(FunctionDefinition::TypeDeclaration(enum_decl)) = value {
+        if let Some(FunctionDefinition::TypeDeclaration(TypeDeclaration::Struct(struct_decl))) =
+            value
+        {
+            assert_eq!(symbol_kind, SymbolKind::Other());
+            let struct_decl = self.process_struct_declaration(struct_decl);
+            return vec![PILItem::Definition(
+                symbol,
+                Some(FunctionValueDefinition::TypeDeclaration(struct_decl.into())),
+            )];
+        }
+
+        if let Some(FunctionDefinition::TypeDeclaration(TypeDeclaration::Enum(enum_decl))) = value {
             // For enums, we add PILItems both for the enum itself and also for all
             // its type constructors.
             assert_eq!(symbol_kind, SymbolKind::Other());
             let enum_decl = self.process_enum_declaration(enum_decl);
+            let shared_enum_decl = Arc::new(EnumDeclaration {
+                name: absolute_name.clone(),
+                ..enum_decl.clone()
+            });
             let var_items = enum_decl.variants.iter().map(|variant| {
                 let var_symbol = Symbol {
                     id: self.counters.dispense_symbol_id(SymbolKind::Other(), None),
@@ -440,16 +484,19 @@ where
                     stage: None,
                     kind: SymbolKind::Other(),
                     length: None,
+                    degree: None,
                 };
                 let value = FunctionValueDefinition::TypeConstructor(
-                    absolute_name.clone(),
+                    shared_enum_decl.clone(),
                     variant.clone(),
                 );
                 PILItem::Definition(var_symbol, Some(value))
             });
             return iter::once(PILItem::Definition(
                 symbol,
-                Some(FunctionValueDefinition::TypeDeclaration(enum_decl.clone())),
+                Some(FunctionValueDefinition::TypeDeclaration(
+                    enum_decl.clone().into(),
+                )),
             ))
             .chain(var_items)
             .collect();
@@ -491,11 +538,151 @@ where
                 assert!(type_scheme.is_none() || type_scheme == Some(Type::Col.into()));
                 FunctionValueDefinition::Array(expression)
             }
-            FunctionDefinition::TypeDeclaration(_enum_declaration) => unreachable!(),
+            FunctionDefinition::TypeDeclaration(_type_declaration) => unreachable!(),
         });
         vec![PILItem::Definition(symbol, value)]
     }
 
+    fn handle_trait_declaration(
+        &mut self,
+        source: SourceRef,
+        trait_decl: TraitDeclaration<parsed::Expression>,
+    ) -> Vec<PILItem> {
+        let name = trait_decl.name.clone();
+        if trait_decl.type_vars.len() != 1 {
+            panic!(
+                "Trait {name} has to have exactly one type variable, but it has {}.",
+                trait_decl.type_vars.len()
+            );
+        }
+        if let Some(duplicate) = trait_decl
+            .functions
+            .iter()
+            .map(|f| &f.name)
+            .duplicates()
+            .next()
+        {
+            panic!("Duplicate function {duplicate} in trait {name}.");
+        }
+        let absolute_name = self.driver.resolve_decl(&name);
+        let type_vars = trait_decl.type_vars.iter().collect::<HashSet<_>>();
+        let functions = trait_decl
+            .functions
+            .into_iter()
+            .map(|TraitFunction { name: fn_name, ty }| {
+                let ty = self.type_processor(&type_vars).process_type(ty);
+                if ty.contained_type_vars().next().is_none() {
+                    panic!(
+                        "The type of function {fn_name} in trait {name} does not depend on the type variable of the trait: {ty}"
+                    );
+                }
+                TraitFunction { name: fn_name, ty }
+            })
+            .collect::<Vec<_>>();
+        let trait_decl = TraitDeclaration {
+            name: name.clone(),
+            type_vars: trait_decl.type_vars,
+            functions,
+        };
+
+        let symbol = Symbol {
+            id: self.counters.dispense_symbol_id(SymbolKind::Other(), None),
+            source: source.clone(),
+            absolute_name: absolute_name.clone(),
+            stage: None,
+            kind: SymbolKind::Other(),
+            length: None,
+            degree: None,
+        };
+        let function_items = trait_decl
+            .functions
+            .iter()
+            .map(|function| {
+                let symbol = Symbol {
+                    id: self.counters.dispense_symbol_id(SymbolKind::Other(), None),
+                    source: source.clone(),
+                    absolute_name: self
+                        .driver
+                        .resolve_namespaced_decl(&[&name, &function.name])
+                        .to_dotted_string(),
+                    stage: None,
+                    kind: SymbolKind::Other(),
+                    length: None,
+                    degree: None,
+                };
+                let value =
+                    FunctionValueDefinition::TraitFunction(absolute_name.clone(), function.clone());
+                PILItem::Definition(symbol, Some(value))
+            })
+            .collect::<Vec<_>>();
+        // The implementations are attached to the declaration once all statements are processed.
+        iter::once(PILItem::Definition(
+            symbol,
+            Some(FunctionValueDefinition::TraitDeclaration(
+                trait_decl,
+                vec![],
+            )),
+        ))
+        .chain(function_items)
+        .collect()
+    }
+
+    fn handle_trait_implementation(
+        &mut self,
+        source: SourceRef,
+        trait_impl: TraitImplementation<parsed::Expression, parsed::Expression>,
+    ) -> Vec<PILItem> {
+        let trait_name = self.driver.resolve_type_ref(&trait_impl.name);
+        let duplicates = trait_impl.type_vars.vars().duplicates().collect::<Vec<_>>();
+        if !duplicates.is_empty() {
+            panic!(
+                "Duplicate type variables in implementation of {trait_name}:\n{}",
+                duplicates.iter().format(", ")
+            );
+        }
+        let declared_type_vars = trait_impl.type_vars.vars().collect::<HashSet<_>>();
+        let type_args = trait_impl
+            .type_args
+            .into_iter()
+            .map(|ty| self.type_processor(&declared_type_vars).process_type(ty))
+            .collect::<Vec<_>>();
+        let contained_type_vars = type_args
+            .iter()
+            .flat_map(|ty| ty.contained_type_vars())
+            .collect::<HashSet<_>>();
+        if contained_type_vars != declared_type_vars {
+            panic!(
+                "Unused type variable(s) in implementation of {trait_name}: {}",
+                declared_type_vars
+                    .difference(&contained_type_vars)
+                    .format(", ")
+            );
+        }
+        let functions = trait_impl
+            .functions
+            .into_iter()
+            .map(|NamedExpression { name, body }| NamedExpression {
+                name,
+                body: Box::new(
+                    self.expression_processor(&declared_type_vars)
+                        .process_expression(*body),
+                ),
+            })
+            .collect();
+        let type_vars = self
+            .type_processor(&Default::default())
+            .process_bounds(trait_impl.type_vars.clone());
+        vec![PILItem::TraitImplementation(
+            source,
+            TraitImplementation {
+                name: SymbolPath::from_str(&trait_name).unwrap(),
+                type_vars,
+                type_args,
+                functions,
+            },
+        )]
+    }
+
     fn handle_public_declaration(
         &mut self,
         source: SourceRef,
@@ -544,24 +731,68 @@ where
         &self,
         enum_decl: EnumDeclaration<parsed::Expression>,
     ) -> EnumDeclaration {
+        let duplicates = enum_decl.type_vars.vars().duplicates().collect::<Vec<_>>();
+        if !duplicates.is_empty() {
+            panic!(
+                "Duplicate type variables in declaration of enum {}:\n{}",
+                enum_decl.name,
+                duplicates.iter().format(", ")
+            );
+        }
+        let type_vars = enum_decl.type_vars.vars().collect::<HashSet<_>>();
+        let variants = enum_decl
+            .variants
+            .into_iter()
+            .map(|v| self.process_enum_variant(v, &type_vars))
+            .collect();
         EnumDeclaration {
+            type_vars: self
+                .type_processor(&type_vars)
+                .process_bounds(enum_decl.type_vars.clone()),
             name: enum_decl.name,
-            variants: enum_decl
-                .variants
-                .into_iter()
-                .map(|v| self.process_enum_variant(v))
-                .collect(),
+            variants,
         }
     }
 
-    fn process_enum_variant(&self, enum_variant: EnumVariant<parsed::Expression>) -> EnumVariant {
+    fn process_enum_variant(
+        &self,
+        enum_variant: EnumVariant<parsed::Expression>,
+        type_vars: &HashSet<&String>,
+    ) -> EnumVariant {
         EnumVariant {
             name: enum_variant.name,
             fields: enum_variant.fields.map(|f| {
                 f.into_iter()
-                    .map(|ty| self.type_processor(&Default::default()).process_type(ty))
+                    .map(|ty| self.type_processor(type_vars).process_type(ty))
                     .collect()
             }),
         }
     }
+
+    fn process_struct_declaration(
+        &self,
+        struct_decl: StructDeclaration<parsed::Expression>,
+    ) -> StructDeclaration {
+        let name = struct_decl.name;
+        if let Some(duplicate) = struct_decl
+            .fields
+            .iter()
+            .map(|f| &f.name)
+            .duplicates()
+            .next()
+        {
+            panic!("Duplicate field {duplicate} in struct {name}.");
+        }
+        StructDeclaration {
+            name,
+            fields: struct_decl
+                .fields
+                .into_iter()
+                .map(|StructField { name, ty }| StructField {
+                    name,
+                    ty: self.type_processor(&Default::default()).process_type(ty),
+                })
+                .collect(),
+        }
+    }
 }
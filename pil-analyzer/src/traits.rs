@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use powdr_ast::parsed::{
+    types::{ArrayType, FunctionType, TupleType, Type, TypeScheme},
+    TraitImplementation,
+};
+
+use crate::type_unifier::Unifier;
+
+/// The result of matching a type against the type of a trait implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImplMatch {
+    /// The type is an instance of the implementation's type.
+    Yes,
+    /// The type can never be an instance of the implementation's type.
+    No,
+    /// The type still contains type variables and it depends on their
+    /// substitutions if it matches.
+    Undecided,
+}
+
+impl ImplMatch {
+    fn and(self, other: ImplMatch) -> ImplMatch {
+        match (self, other) {
+            (ImplMatch::No, _) | (_, ImplMatch::No) => ImplMatch::No,
+            (ImplMatch::Undecided, _) | (_, ImplMatch::Undecided) => ImplMatch::Undecided,
+            (ImplMatch::Yes, ImplMatch::Yes) => ImplMatch::Yes,
+        }
+    }
+}
+
+/// Matches `ty` against `pattern`, the type of a trait implementation.
+/// Only the type variables in `pattern` are assigned (in `bindings`),
+/// type variables in `ty` are treated as unknown types.
+pub fn match_type(pattern: &Type, ty: &Type, bindings: &mut HashMap<String, Type>) -> ImplMatch {
+    match (pattern, ty) {
+        (Type::TypeVar(v), ty) => match bindings.get(v) {
+            Some(bound) if bound == ty => ImplMatch::Yes,
+            Some(bound) if bound.is_concrete_type() && ty.is_concrete_type() => ImplMatch::No,
+            Some(_) => ImplMatch::Undecided,
+            None => {
+                bindings.insert(v.clone(), ty.clone());
+                ImplMatch::Yes
+            }
+        },
+        (_, Type::TypeVar(_) | Type::Bottom) => ImplMatch::Undecided,
+        (
+            Type::Array(ArrayType { base, length }),
+            Type::Array(ArrayType {
+                base: ty_base,
+                length: ty_length,
+            }),
+        ) => {
+            if length.is_some() && length != ty_length {
+                ImplMatch::No
+            } else {
+                match_type(base, ty_base, bindings)
+            }
+        }
+        (Type::Tuple(TupleType { items }), Type::Tuple(TupleType { items: ty_items })) => {
+            match_types(items, ty_items, bindings)
+        }
+        (
+            Type::Function(FunctionType { params, value }),
+            Type::Function(FunctionType {
+                params: ty_params,
+                value: ty_value,
+            }),
+        ) => match_types(params, ty_params, bindings).and(match_type(value, ty_value, bindings)),
+        (Type::NamedType(name, Some(args)), Type::NamedType(ty_name, Some(ty_args)))
+            if name == ty_name =>
+        {
+            match_types(args, ty_args, bindings)
+        }
+        (pattern, ty) if pattern == ty => ImplMatch::Yes,
+        _ => ImplMatch::No,
+    }
+}
+
+fn match_types(
+    patterns: &[Type],
+    types: &[Type],
+    bindings: &mut HashMap<String, Type>,
+) -> ImplMatch {
+    if patterns.len() != types.len() {
+        return ImplMatch::No;
+    }
+    patterns
+        .iter()
+        .zip(types)
+        .fold(ImplMatch::Yes, |acc, (p, t)| {
+            acc.and(match_type(p, t, bindings))
+        })
+}
+
+/// Returns true if there is a type that is an instance of both type schemes.
+pub fn overlapping(a: &TypeScheme, b: &TypeScheme) -> bool {
+    let rename = |scheme: &TypeScheme, suffix: &str| {
+        let substitutions = scheme
+            .vars
+            .vars()
+            .map(|v| (v.clone(), Type::TypeVar(format!("{v}_{suffix}"))))
+            .collect();
+        scheme.ty.clone().substitute_type_vars_to(&substitutions)
+    };
+    Unifier::default()
+        .unify_types(rename(a, "a"), rename(b, "b"))
+        .is_ok()
+}
+
+/// Finds the implementation of a trait for a concrete type and returns it
+/// together with the values of its type variables.
+pub fn find_implementation<'a, Expr>(
+    impls: &'a [TraitImplementation<Expr>],
+    ty: &Type,
+) -> Option<(&'a TraitImplementation<Expr>, HashMap<String, Type>)> {
+    impls.iter().find_map(|trait_impl| {
+        let mut bindings = HashMap::new();
+        (match_type(&trait_impl.type_args[0], ty, &mut bindings) == ImplMatch::Yes)
+            .then_some((trait_impl, bindings))
+    })
+}
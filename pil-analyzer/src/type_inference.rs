@@ -1,19 +1,27 @@
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+};
 
 use itertools::Itertools;
 use powdr_ast::{
     analyzed::{Expression, PolynomialReference, Reference},
     parsed::{
+        asm::SymbolPath,
         display::format_type_scheme_around_name,
-        types::{ArrayType, FunctionType, TupleType, Type, TypeBounds, TypeScheme},
+        types::{
+            is_builtin_trait, ArrayType, FunctionType, TupleType, Type, TypeBounds, TypeScheme,
+        },
         visitor::ExpressionVisitable,
-        ArrayLiteral, FunctionCall, IndexAccess, LambdaExpression, LetStatementInsideBlock,
-        MatchArm, Pattern, StatementInsideBlock,
+        ArrayLiteral, FieldAccess, FunctionCall, IndexAccess, LambdaExpression,
+        LetStatementInsideBlock, MatchArm, Pattern, StatementInsideBlock, StructDeclaration,
+        StructExpression,
     },
 };
 
 use crate::{
     call_graph::sort_called_first,
+    traits::overlapping,
     type_builtins::{
         binary_operator_scheme, builtin_schemes, type_for_reference, unary_operator_scheme,
     },
@@ -24,13 +32,74 @@ use crate::{
 /// expressions (from identities and arrays) where the expected type is given.
 /// The parameter `statement_type` is the expected type for expressions at statement level.
 /// Sets the generic arguments for references and the literal types in all expressions.
+/// The parameter `trait_impls` contains, for each user-defined trait, the type schemes
+/// of its implementations.
+/// The parameter `structs` contains the declarations of all struct types by their absolute name.
 /// Returns the types for symbols without explicit type.
 pub fn infer_types(
     definitions: HashMap<String, (Option<TypeScheme>, Option<&mut Expression>)>,
     expressions: &mut [(&mut Expression, ExpectedType)],
     statement_type: &ExpectedType,
+    trait_impls: HashMap<String, Vec<TypeScheme>>,
+    structs: HashMap<String, StructDeclaration>,
 ) -> Result<Vec<(String, Type)>, String> {
-    TypeChecker::new(statement_type).infer_types(definitions, expressions)
+    check_trait_implementations(&trait_impls)?;
+    check_bounds(&definitions, &trait_impls)?;
+    TypeChecker::new(statement_type, trait_impls, structs).infer_types(definitions, expressions)
+}
+
+/// Checks that no two implementations of the same trait overlap.
+fn check_trait_implementations(
+    trait_impls: &HashMap<String, Vec<TypeScheme>>,
+) -> Result<(), String> {
+    for (trait_name, impls) in trait_impls {
+        for (a, b) in impls.iter().tuple_combinations() {
+            if overlapping(a, b) {
+                return Err(format!(
+                    "Overlapping implementations of trait {trait_name} for types {} and {}.",
+                    a.ty, b.ty
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that all trait bounds in declared type schemes are either built-in traits
+/// or user-defined traits.
+fn check_bounds<T>(
+    definitions: &HashMap<String, (Option<TypeScheme>, T)>,
+    trait_impls: &HashMap<String, Vec<TypeScheme>>,
+) -> Result<(), String> {
+    definitions
+        .iter()
+        .filter_map(|(name, (type_scheme, _))| Some((name, type_scheme.as_ref()?)))
+        .chain(
+            trait_impls
+                .iter()
+                .flat_map(|(name, impls)| impls.iter().map(move |i| (name, i))),
+        )
+        .flat_map(|(name, type_scheme)| {
+            type_scheme
+                .vars
+                .bounds()
+                .flat_map(move |(_, bounds)| bounds.iter().map(move |b| (name, b)))
+        })
+        .try_for_each(|(name, bound)| {
+            if is_builtin_trait(bound) || trait_impls.contains_key(bound) {
+                Ok(())
+            } else {
+                Err(format!("Unknown trait {bound} used in bound of {name}."))
+            }
+        })
+}
+
+/// Returns the type of the field `field` of the struct.
+fn field_type<'a>(struct_decl: &'a StructDeclaration, field: &str) -> Result<&'a Type, String> {
+    struct_decl
+        .field_by_name(field)
+        .map(|f| &f.ty)
+        .ok_or_else(|| format!("Struct {} has no field {field}.", struct_decl.name))
 }
 
 /// A type to expect and a flag that says if arrays of that type are also fine.
@@ -49,6 +118,14 @@ impl From<Type> for ExpectedType {
     }
 }
 
+/// An access to a field of an object whose type was not yet known
+/// when the access was processed.
+struct PendingFieldAccess {
+    object_type: Type,
+    field: String,
+    result: Type,
+}
+
 struct TypeChecker<'a> {
     /// The expected type for expressions at statement level in block expressions.
     statement_type: &'a ExpectedType,
@@ -60,18 +137,28 @@ struct TypeChecker<'a> {
     /// Current mapping of declared type vars to type. Reset before checking each definition.
     declared_type_vars: HashMap<String, Type>,
     unifier: Unifier,
+    /// Declarations of all struct types by their absolute name.
+    structs: HashMap<String, StructDeclaration>,
+    /// Field accesses that still need to be resolved.
+    pending_field_accesses: Vec<PendingFieldAccess>,
     /// Last used type variable index.
     last_type_var: usize,
 }
 
 impl<'a> TypeChecker<'a> {
-    pub fn new(statement_type: &'a ExpectedType) -> Self {
+    pub fn new(
+        statement_type: &'a ExpectedType,
+        trait_impls: HashMap<String, Vec<TypeScheme>>,
+        structs: HashMap<String, StructDeclaration>,
+    ) -> Self {
         Self {
             statement_type,
             local_var_types: Default::default(),
             declared_types: Default::default(),
             declared_type_vars: Default::default(),
-            unifier: Default::default(),
+            unifier: Unifier::new(trait_impls),
+            structs,
+            pending_field_accesses: Default::default(),
             last_type_var: Default::default(),
         }
     }
@@ -147,7 +234,8 @@ impl<'a> TypeChecker<'a> {
                 self.infer_type_of_expression(value).map(|ty| {
                     inferred_types.insert(name.to_string(), ty);
                 })
-            };
+            }
+            .and_then(|_| self.resolve_field_accesses());
             if let Err(e) = result {
                 return Err(format!(
                     "Error type checking the symbol {name} = {value}:\n{e}",
@@ -157,9 +245,17 @@ impl<'a> TypeChecker<'a> {
         self.declared_type_vars.clear();
 
         self.check_expressions(expressions)?;
+        self.resolve_field_accesses()?;
 
         // From this point on, the substitutions are fixed.
 
+        if let Some((ty, bound)) = self.unifier.pending_bounds().first() {
+            return Err(format!(
+                "Could not determine the implementation of trait {bound} to use for type {}.",
+                self.type_into_substituted(ty.clone())
+            ));
+        }
+
         // Now we check for all symbols that are not declared as a type scheme that they
         // can resolve to a concrete type.
         for (name, declared_type) in &self.declared_types {
@@ -578,6 +674,40 @@ impl<'a> TypeChecker<'a> {
                     format!("calling function {function}")
                 })?
             }
+            Expression::StructExpression(StructExpression { name, fields }) => {
+                let struct_decl = self.struct_declaration(name)?.clone();
+                if let Some(duplicate) = fields.iter().map(|f| &f.name).duplicates().next() {
+                    return Err(format!(
+                        "Field {duplicate} specified more than once for struct {name}."
+                    ));
+                }
+                if let Some(missing) = struct_decl
+                    .fields
+                    .iter()
+                    .find(|f| !fields.iter().any(|field| field.name == f.name))
+                {
+                    return Err(format!(
+                        "Missing field {} in expression of struct {name}.",
+                        missing.name
+                    ));
+                }
+                for field in fields {
+                    let ty = field_type(&struct_decl, &field.name)?.clone();
+                    self.expect_type(&ty, &mut field.body)?;
+                }
+                Type::NamedType(name.clone(), None)
+            }
+            Expression::FieldAccess(FieldAccess { object, field }) => {
+                let object_type = self.infer_type_of_expression(object)?;
+                let result = self.new_type_var();
+                self.pending_field_accesses.push(PendingFieldAccess {
+                    object_type,
+                    field: field.clone(),
+                    result: result.clone(),
+                });
+                self.resolve_field_accesses_by_type()?;
+                result
+            }
             Expression::FreeInput(_) => todo!(),
             Expression::MatchExpression(scrutinee, arms) => {
                 let scrutinee_type = self.infer_type_of_expression(scrutinee)?;
@@ -741,9 +871,127 @@ impl<'a> TypeChecker<'a> {
                 self.local_var_types.push(ty.clone());
                 ty
             }
+            Pattern::Struct(name, fields) => {
+                let struct_decl = self.struct_declaration(name)?.clone();
+                if let Some(duplicate) = fields.iter().map(|(f, _)| f).duplicates().next() {
+                    return Err(format!(
+                        "Field {duplicate} specified more than once in pattern for struct {name}."
+                    ));
+                }
+                for (field, pattern) in fields {
+                    let ty = field_type(&struct_decl, field)?;
+                    self.expect_type_of_pattern(ty, pattern)?;
+                }
+                Type::NamedType(name.clone(), None)
+            }
+            Pattern::Enum(name, fields) => {
+                let (ty, _) =
+                    self.instantiate_scheme(self.declared_types[&name.to_dotted_string()].clone());
+                match (ty, fields) {
+                    (ty @ Type::NamedType(_, _), None) => ty,
+                    (Type::Function(FunctionType { params, value }), Some(fields))
+                        if matches!(value.as_ref(), Type::NamedType(_, _)) =>
+                    {
+                        if params.len() != fields.len() {
+                            return Err(format!(
+                                "Enum variant {name} has {} field(s), but the pattern has {}.",
+                                params.len(),
+                                fields.len()
+                            ));
+                        }
+                        for (ty, pattern) in params.iter().zip(fields) {
+                            self.expect_type_of_pattern(ty, pattern)?;
+                        }
+                        *value
+                    }
+                    (Type::Function(FunctionType { value, .. }), None)
+                        if matches!(value.as_ref(), Type::NamedType(_, _)) =>
+                    {
+                        return Err(format!(
+                            "Enum variant {name} has fields, but the pattern does not list them."
+                        ))
+                    }
+                    (ty, _) => {
+                        return Err(format!(
+                            "Expected enum variant for pattern {pattern}, but {name} has type {ty}."
+                        ))
+                    }
+                }
+            }
         })
     }
 
+    fn struct_declaration(&self, name: &SymbolPath) -> Result<&StructDeclaration, String> {
+        self.structs
+            .get(&name.to_dotted_string())
+            .ok_or_else(|| format!("{name} is not a struct."))
+    }
+
+    /// Resolves all pending field accesses whose object type is known by now.
+    fn resolve_field_accesses_by_type(&mut self) -> Result<(), String> {
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for access in std::mem::take(&mut self.pending_field_accesses) {
+                let object_type = self.type_into_substituted(access.object_type.clone());
+                match object_type {
+                    Type::TypeVar(_) => self.pending_field_accesses.push(access),
+                    Type::NamedType(name, _) => {
+                        let field_type =
+                            field_type(self.struct_declaration(&name)?, &access.field)?.clone();
+                        self.unifier
+                            .unify_types(field_type, access.result)
+                            .map_err(|err| {
+                                format!(
+                                    "Error checking access to field {} of struct {name}:\n{err}",
+                                    access.field
+                                )
+                            })?;
+                        progress = true;
+                    }
+                    ty => {
+                        return Err(format!(
+                            "Expected struct for access to field {}, but got {ty}.",
+                            access.field
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves all pending field accesses. If the type of the object is still unknown,
+    /// it is set to the unique struct that has a field of that name.
+    fn resolve_field_accesses(&mut self) -> Result<(), String> {
+        self.resolve_field_accesses_by_type()?;
+        while let Some(access) = self.pending_field_accesses.first() {
+            let candidates = self
+                .structs
+                .iter()
+                .filter(|(_, s)| s.field_by_name(&access.field).is_some())
+                .map(|(name, _)| name)
+                .sorted()
+                .collect::<Vec<_>>();
+            let [name] = candidates[..] else {
+                return Err(format!(
+                    "Could not determine the struct type for access to field {}.\n{}",
+                    access.field,
+                    if candidates.is_empty() {
+                        "No struct has a field of that name.".to_string()
+                    } else {
+                        format!("Candidates are: {}", candidates.iter().format(", "))
+                    }
+                ));
+            };
+            let struct_type = Type::NamedType(SymbolPath::from_str(name).unwrap(), None);
+            self.unifier
+                .unify_types(access.object_type.clone(), struct_type)?;
+            self.resolve_field_accesses_by_type()?;
+        }
+        Ok(())
+    }
+
     /// Returns, for each name declared with a type scheme, a mapping from
     /// the type variables used by the type checker to those used in the declaration.
     fn verify_type_schemes(
@@ -1,6 +1,11 @@
 use std::{collections::HashSet, str::FromStr};
 
-use powdr_ast::parsed::{asm::SymbolPath, types::Type, visitor::Children, Expression};
+use powdr_ast::parsed::{
+    asm::SymbolPath,
+    types::{Type, TypeBounds},
+    visitor::Children,
+    Expression,
+};
 
 use crate::{evaluator::EvalError, untyped_evaluator, AnalysisDriver};
 
@@ -30,6 +35,22 @@ impl<'a, D: AnalysisDriver> TypeProcessor<'a, D> {
         ty
     }
 
+    /// Resolves the trait bounds: Bounds that refer to user-defined traits are turned
+    /// into absolute names, all other bounds are built-in traits and are kept as they are.
+    pub fn process_bounds(&self, mut bounds: TypeBounds) -> TypeBounds {
+        for (_, bounds) in bounds.bounds_mut() {
+            *bounds = std::mem::take(bounds)
+                .into_iter()
+                .map(|b| {
+                    self.driver
+                        .try_resolve_ref(&SymbolPath::from_str(&b).unwrap(), true)
+                        .unwrap_or(b)
+                })
+                .collect();
+        }
+        bounds
+    }
+
     /// Turns a Type<Expression> to a Type<u64> by evaluating the array length expressions.
     fn evaluate_array_lengths(&self, mut t: Type<Expression>) -> Result<Type, EvalError> {
         // Replace all expressions by number literals.
@@ -1,8 +1,14 @@
 use std::collections::{HashMap, HashSet};
 
-use powdr_ast::parsed::{types::Type, visitor::Children};
+use powdr_ast::parsed::{
+    types::{Type, TypeScheme},
+    visitor::Children,
+};
 
-use crate::type_builtins::elementary_type_bounds;
+use crate::{
+    traits::{match_type, ImplMatch},
+    type_builtins::elementary_type_bounds,
+};
 
 // TODO Optimization ideas:
 // the substitutions are applied a lot.
@@ -19,9 +25,21 @@ pub struct Unifier {
     type_var_bounds: HashMap<String, HashSet<String>>,
     /// Substitutions for type variables
     substitutions: HashMap<String, Type>,
+    /// For each user-defined trait, the type schemes of its implementations.
+    trait_impls: HashMap<String, Vec<TypeScheme>>,
+    /// Trait bounds on types that might match more than one implementation.
+    /// They are checked again whenever a substitution is added.
+    pending_bounds: Vec<(Type, String)>,
 }
 
 impl Unifier {
+    pub fn new(trait_impls: HashMap<String, Vec<TypeScheme>>) -> Self {
+        Self {
+            trait_impls,
+            ..Default::default()
+        }
+    }
+
     pub fn substitutions(&self) -> &HashMap<String, Type> {
         &self.substitutions
     }
@@ -33,6 +51,11 @@ impl Unifier {
             .unwrap_or_default()
     }
 
+    /// Returns the trait bounds that could not be resolved to a unique implementation.
+    pub fn pending_bounds(&self) -> &[(Type, String)] {
+        &self.pending_bounds
+    }
+
     pub fn ensure_bound(&mut self, ty: &Type, bound: String) -> Result<(), String> {
         let ty = (if let Type::TypeVar(n) = ty {
             self.substitutions.get(n)
@@ -43,9 +66,10 @@ impl Unifier {
 
         if let Type::TypeVar(n) = ty {
             self.add_type_var_bound(n.clone(), bound);
-        } else if let Type::NamedType(n) = ty {
-            // TODO Change this as soon as we support user-implemented traits.
-            return Err(format!("Type {n} does not satisfy trait {bound}."));
+        } else if self.trait_impls.contains_key(&bound) {
+            return self.ensure_trait_implemented(ty.clone(), bound);
+        } else if let Type::NamedType(_, _) = ty {
+            return Err(format!("Type {ty} does not satisfy trait {bound}."));
         } else if bound == "ToString" && matches!(ty, Type::Array(_) | Type::Tuple(_)) {
             // TODO Change this to a proper trait impl later.
             for c in ty.clone().children().collect::<Vec<_>>() {
@@ -103,11 +127,57 @@ impl Unifier {
                     .zip(t2.items)
                     .try_for_each(|(i1, i2)| self.unify_types(i1, i2))
             }
+            (Type::NamedType(n1, Some(args1)), Type::NamedType(n2, Some(args2)))
+                if n1 == n2 && args1.len() == args2.len() =>
+            {
+                args1
+                    .into_iter()
+                    .zip(args2)
+                    .try_for_each(|(a1, a2)| self.unify_types(a1, a2))
+            }
 
             (ty1, ty2) => Err(format!("Cannot unify types {ty1} and {ty2}")),
         }
     }
 
+    /// Checks that there is an implementation of the user-defined trait for the type
+    /// and that the type satisfies the bounds of the implementation.
+    /// If the type contains type variables such that there might be multiple
+    /// matching implementations, the check is deferred.
+    fn ensure_trait_implemented(&mut self, mut ty: Type, trait_name: String) -> Result<(), String> {
+        ty.substitute_type_vars(&self.substitutions);
+        let mut undecided = false;
+        let mut required_bounds = None;
+        for impl_scheme in &self.trait_impls[&trait_name] {
+            let mut bindings = HashMap::new();
+            match match_type(&impl_scheme.ty, &ty, &mut bindings) {
+                ImplMatch::Yes => {
+                    required_bounds = Some(
+                        impl_scheme
+                            .vars
+                            .bounds()
+                            .flat_map(|(var, bounds)| {
+                                bounds.iter().map(|b| (bindings[var].clone(), b.clone()))
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                }
+                ImplMatch::Undecided => undecided = true,
+                ImplMatch::No => {}
+            }
+        }
+        match (required_bounds, undecided) {
+            (Some(required_bounds), false) => required_bounds
+                .into_iter()
+                .try_for_each(|(ty, bound)| self.ensure_bound(&ty, bound)),
+            (None, false) => Err(format!("Type {ty} does not satisfy trait {trait_name}.")),
+            (_, true) => {
+                self.pending_bounds.push((ty, trait_name));
+                Ok(())
+            }
+        }
+    }
+
     fn add_type_var_bound(&mut self, type_var: String, bound: String) {
         self.type_var_bounds
             .entry(type_var)
@@ -130,7 +200,15 @@ impl Unifier {
         self.substitutions
             .values_mut()
             .for_each(|t| t.substitute_type_vars(&subs));
-        self.substitutions.insert(type_var, ty);
-        Ok(())
+        self.substitutions.insert(type_var.clone(), ty);
+
+        // Re-check the pending bounds that are affected by the new substitution.
+        let (affected, unaffected) = std::mem::take(&mut self.pending_bounds)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(ty, _)| ty.contains_type_var(&type_var));
+        self.pending_bounds = unaffected;
+        affected
+            .into_iter()
+            .try_for_each(|(ty, bound)| self.ensure_bound(&ty, bound))
     }
 }
@@ -0,0 +1,183 @@
+use powdr_number::GoldilocksField;
+use powdr_pil_analyzer::analyze_string;
+use test_log::test;
+
+#[test]
+fn exhaustive_enum() {
+    let input = r#"
+    enum E { A, B(int), C(int, int) }
+    let f: E -> int = |e| match e {
+        E::A => 0,
+        E::B(x) => x,
+        E::C(x, _) => x,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "input:3:5: Error checking match expressions of f: Match expression is not exhaustive, pattern E::C(_, _) is not covered"]
+fn missing_enum_variant() {
+    let input = r#"
+    enum E { A, B(int), C(int, int) }
+    let f: E -> int = |e| match e {
+        E::A => 0,
+        E::B(x) => x,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "pattern E::B(E::A) is not covered"]
+fn nested_enum() {
+    let input = r#"
+    enum E { A, B(E) }
+    let f: E -> int = |e| match e {
+        E::A => 0,
+        E::B(E::B(_)) => 1,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "pattern _ is not covered"]
+fn missing_literal_catch_all() {
+    let input = r#"
+    let f: int -> int = |i| match i {
+        0 => 1,
+        1 => 2,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "pattern (_, _) is not covered"]
+fn tuple_with_literal() {
+    let input = r#"
+    let f: int, int -> int = |i, j| match (i, j) {
+        (1, 2) => 1,
+        (_, 3) => 2,
+        (0, _) => 3,
+        (x, 2) => x,
+        (2, _) => 4,
+    };
+    let g: int -> int = |i| match (i, i) {
+        (0, _) => 1,
+        (_, _) => 3,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+fn exhaustive_arrays() {
+    let input = r#"
+    let f: int[] -> int = |a| match a {
+        [] => 0,
+        [x] => x,
+        [x, .., y] => x + y,
+    };
+    let g: int[] -> int = |a| match a {
+        [.., 1] => 0,
+        [_, ..] => 1,
+        [] => 2,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "pattern [_, _, ..] is not covered"]
+fn missing_array_length() {
+    let input = r#"
+    let f: int[] -> int = |a| match a {
+        [] => 0,
+        [x] => x,
+        [x, 2, ..] => x,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "pattern [] is not covered"]
+fn missing_empty_array() {
+    let input = r#"
+    let f: int[] -> int = |a| match a {
+        [x, ..] => x,
+        [.., x] => x,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "pattern (_, _) is not covered"]
+fn missing_string() {
+    let input = r#"
+    let f: string -> int = |s| match s {
+        "a" => 1,
+        _ => 2,
+    };
+    let g: string, int -> int = |s, i| match (s, i) {
+        ("a", _) => 1,
+        (_, 0) => 2,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "Error checking match expressions of N.x: Match expression is not exhaustive"]
+fn nested_match_in_constraint() {
+    let input = r#"
+    namespace N(16);
+        let x: int -> int = |i| match i {
+            0 => match i { 1 => 2 },
+            _ => 3,
+        };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "Error checking match expressions of identity"]
+fn match_in_identity() {
+    let input = r#"
+    namespace N(16);
+        col witness w;
+        let k: int = 3;
+        match k { 0 => w, 1 => w' } = 0;
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+fn exhaustive_struct() {
+    let input = r#"
+    enum E { A, B }
+    struct S { e: E, x: int }
+    let f: S -> int = |s| match s {
+        S { e: E::A, x } => x,
+        S { e: E::B } => 0,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "pattern S { e: E::B, x: _ } is not covered"]
+fn missing_struct_field_value() {
+    let input = r#"
+    enum E { A, B }
+    struct S { e: E, x: int }
+    let f: S -> int = |s| match s {
+        S { e: E::A, x } => x,
+        S { x: 0 } => 0,
+    };
+    "#;
+    analyze_string::<GoldilocksField>(input);
+}
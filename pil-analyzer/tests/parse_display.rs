@@ -16,6 +16,11 @@ namespace std::prover(65536);
     enum Query {
         Input(int),
     }
+namespace std::utils(65536);
+    enum Option<T> {
+        None,
+        Some(T),
+    }
 namespace std::convert(65536);
     let int = [];
 namespace T(65536);
@@ -48,9 +53,10 @@ namespace T(65536);
     T.X = ((((T.read_X_A * T.A) + (T.read_X_CNT * T.CNT)) + T.X_const) + (T.X_read_free * T.X_free_value));
     T.A' = (((T.first_step' * 0) + (T.reg_write_X_A * T.X)) + ((1 - (T.first_step' + T.reg_write_X_A)) * T.A));
     col witness X_free_value(__i) query match std::prover::eval(T.pc) {
-        0 => std::prover::Query::Input(1),
-        3 => std::prover::Query::Input(std::convert::int::<fe>((std::prover::eval(T.CNT) + 1))),
-        7 => std::prover::Query::Input(0),
+        0 => std::utils::Option::Some::<std::prover::Query>(std::prover::Query::Input(1)),
+        3 => std::utils::Option::Some::<std::prover::Query>(std::prover::Query::Input(std::convert::int::<fe>((std::prover::eval(T.CNT) + 1)))),
+        7 => std::utils::Option::Some::<std::prover::Query>(std::prover::Query::Input(0)),
+        _ => std::utils::Option::None::<std::prover::Query>,
     };
     col fixed p_X_const = [0, 0, 0, 0, 0, 0, 0, 0, 0] + [0]*;
     col fixed p_X_read_free = [1, 0, 0, 1, 0, 0, 0, -1, 0] + [0]*;
@@ -101,6 +107,38 @@ fn intermediate_nested() {
     assert_eq!(formatted, expected);
 }
 
+#[test]
+fn different_degrees() {
+    let input = r#"namespace Byte(256);
+    col fixed BYTE(i) { i & 0xff };
+namespace N(65536);
+    col witness x;
+    { x } in { Byte.BYTE };
+"#;
+    let expected = r#"namespace Byte(256);
+    col fixed BYTE(i) { (i & 255) };
+namespace N(65536);
+    col witness x;
+    { N.x } in { Byte.BYTE };
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.degree, Some(65536));
+    assert_eq!(analyzed.degrees(), [256, 65536].into());
+    assert_eq!(analyzed.to_string(), expected);
+}
+
+#[test]
+#[should_panic = "Identity references columns of different degrees (256, 65536): N.x = Byte.BYTE;"]
+fn different_degrees_in_polynomial_identity() {
+    let input = r#"namespace Byte(256);
+    col fixed BYTE(i) { i & 0xff };
+namespace N(65536);
+    col witness x;
+    x = Byte.BYTE;
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
 #[test]
 fn let_definitions() {
     let input = r#"constant %r = 65536;
@@ -713,3 +751,49 @@ namespace T(8);
     assert_eq!(analyzed.degree, Some(8));
     assert_eq!(expected, analyzed.to_string());
 }
+
+#[test]
+fn traits() {
+    let input = "    trait Size<T> {
+        size: T -> int,
+    }
+    impl Size<int> {
+        size: (|_| 1),
+    }
+    impl<T: Size> Size<T[]> {
+        size: (|arr| (Size.size(arr[0]) + 1)),
+    }
+    let<T: Size> twice: T -> int = (|x| (Size.size(x) * 2));
+";
+    let expected = "    trait Size<T> {
+        size: T -> int,
+    }
+    impl Size<int> {
+        size: (|_| 1),
+    }
+    impl<T: Size> Size<T[]> {
+        size: (|arr| (Size.size::<T>(arr[0]) + 1)),
+    }
+    let<T: Size> twice: T -> int = (|x| (Size.size::<T>(x) * 2));
+";
+    assert_eq!(
+        expected,
+        analyze_string::<GoldilocksField>(input).to_string()
+    );
+}
+
+#[test]
+fn structs() {
+    let input = "    struct Point {
+        x: int,
+        y: int,
+    }
+    let origin: Point = Point { x: 0, y: 0 };
+    let f: Point -> int = (|p| (p.x + p.y));
+    let g: Point -> int = (|p| match p {
+        Point { x: 0, y: y } => y,
+        Point { x: x, y: _ } => x,
+    });
+";
+    assert_eq!(input, analyze_string::<GoldilocksField>(input).to_string());
+}
@@ -327,7 +327,7 @@ fn enum_is_not_constr() {
 }
 
 #[test]
-#[should_panic = "Expected type: int -> std::prover::Query"]
+#[should_panic = "Expected type: int -> std::utils::Option<std::prover::Query>"]
 fn query_with_wrong_type() {
     let input = "col witness w(i) query i;";
     type_check(input, &[]);
@@ -417,3 +417,210 @@ fn type_from_pattern() {
     ";
     type_check(input, &[("f", "", "(int, int[]) -> int")]);
 }
+
+#[test]
+fn trait_function_call() {
+    let input = "
+        trait Default<T> {
+            default: -> T,
+        }
+        impl Default<int> {
+            default: || 0,
+        }
+        impl Default<(int, string)> {
+            default: || (1, \"\"),
+        }
+        let x: int = Default::default() + 7;
+        let y: (int, string) = Default::default();
+    ";
+    type_check(input, &[("x", "", "int"), ("y", "", "(int, string)")]);
+}
+
+#[test]
+fn trait_generic_implementation() {
+    let input = "
+        trait Size<T> {
+            size: T -> int,
+        }
+        impl Size<int> {
+            size: |_| 1,
+        }
+        impl<T: Size> Size<T[]> {
+            size: |arr| Size::size(arr[0]) + 1,
+        }
+        let<T: Size> twice: T -> int = |x| Size::size(x) * 2;
+        let a: int[][] = [[1, 2], [3]];
+        let x = twice(a);
+    ";
+    type_check(input, &[("twice", "T: Size", "T -> int"), ("x", "", "int")]);
+}
+
+#[test]
+#[should_panic = "Type string does not satisfy trait Size"]
+fn trait_missing_implementation() {
+    let input = "
+        trait Size<T> {
+            size: T -> int,
+        }
+        impl Size<int> {
+            size: |_| 1,
+        }
+        let x = Size::size(\"abc\");
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Type string does not satisfy trait Size"]
+fn trait_generic_implementation_missing_bound() {
+    let input = "
+        trait Size<T> {
+            size: T -> int,
+        }
+        impl<T: Size> Size<T[]> {
+            size: |arr| 1,
+        }
+        let x = Size::size([\"abc\"]);
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Overlapping implementations of trait Size"]
+fn trait_overlapping_implementations() {
+    let input = "
+        trait Size<T> {
+            size: T -> int,
+        }
+        impl<T> Size<T[]> {
+            size: |_| 1,
+        }
+        impl Size<int[]> {
+            size: |_| 2,
+        }
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Error type checking the symbol impl Size<int>::size"]
+fn trait_implementation_wrong_type() {
+    let input = "
+        trait Size<T> {
+            size: T -> int,
+        }
+        impl Size<int> {
+            size: |_| \"a\",
+        }
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+fn struct_type_check() {
+    let input = "
+        struct Point {
+            x: int,
+            y: int,
+        }
+        let p: Point = Point { y: 2, x: 1 };
+        let sum = |q| q.x + q.y;
+        let x = sum(p);
+        let y = match p { Point { x, y: _ } => x };
+    ";
+    type_check(
+        input,
+        &[
+            ("p", "", "Point"),
+            ("sum", "", "Point -> int"),
+            ("x", "", "int"),
+            ("y", "", "int"),
+        ],
+    );
+}
+
+#[test]
+#[should_panic = "Missing field y in expression of struct Point."]
+fn struct_missing_field() {
+    let input = "
+        struct Point {
+            x: int,
+            y: int,
+        }
+        let p = Point { x: 1 };
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Struct Point has no field z."]
+fn struct_unknown_field() {
+    let input = "
+        struct Point {
+            x: int,
+            y: int,
+        }
+        let p: Point = Point { x: 1, y: 2 };
+        let z = p.z;
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+fn generic_enum() {
+    let input = "
+        enum Option<T> {
+            None,
+            Some(T),
+        }
+        let<T> unwrap_or: Option<T>, T -> T = |o, default| match o {
+            Option::None => default,
+            Option::Some(x) => x,
+        };
+        let x = unwrap_or(Option::Some(\"a\"), \"b\");
+        let y: Option<string> = Option::None;
+        let z = match Option::Some((\"x\", \"a\")) {
+            Option::Some((_, s)) => s,
+            _ => \"\",
+        };
+    ";
+    type_check(
+        input,
+        &[
+            ("unwrap_or", "T", "Option<T>, T -> T"),
+            ("x", "", "string"),
+            ("y", "", "Option<string>"),
+            ("z", "", "string"),
+        ],
+    );
+}
+
+#[test]
+#[should_panic = "Cannot unify types string and int"]
+fn generic_enum_wrong_type_arg() {
+    let input = "
+        enum Option<T> {
+            None,
+            Some(T),
+        }
+        let x: Option<int> = Option::Some(\"a\");
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Enum variant Result::Ok has 1 field(s), but the pattern has 2."]
+fn enum_pattern_wrong_field_count() {
+    let input = "
+        enum Result<T, E> {
+            Ok(T),
+            Err(E),
+        }
+        let r: Result<int, string> = Result::Ok(1);
+        let x: int = match r {
+            Result::Ok(a, b) => a,
+            _ => 0,
+        };
+    ";
+    type_check(input, &[]);
+}
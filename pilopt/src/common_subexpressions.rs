@@ -0,0 +1,304 @@
+//! Extracts common sub-expressions of identities into intermediate columns.
+//!
+//! Operations that occur more than once in the identities (for example the products of
+//! instruction flags and instruction constraints in virtual machines) are replaced by
+//! references to new intermediate columns. Larger expressions are extracted first and
+//! occurrences inside an extracted expression only count once, so that a sub-expression is
+//! only extracted on its own if it also occurs somewhere else.
+//!
+//! Only operations that reference a column and have another operation as an operand are
+//! extracted, because replacing an operation on two leaves does not make the identities smaller.
+//! Witness generation and most backends inline intermediate columns again, so this mainly
+//! reduces the size of exported constraints.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use powdr_ast::analyzed::{
+    AlgebraicExpression, AlgebraicReference, Analyzed, PolyID, PolynomialType, StatementIdentifier,
+    Symbol, SymbolKind,
+};
+use powdr_ast::parsed::visitor::{AllChildren, Children};
+use powdr_ast::SourceRef;
+use powdr_number::{DegreeType, FieldElement};
+
+use crate::logup::{column_degrees, column_stages, latest_stage, namespace_and_degree};
+
+/// Replaces all operations that occur more than once in the identities by references to
+/// intermediate columns, see the module documentation.
+/// @returns the number of intermediate columns that were introduced.
+pub fn extract_common_subexpressions<T: FieldElement>(pil_file: &mut Analyzed<T>) -> usize {
+    let extracted = common_subexpressions(pil_file);
+    if extracted.is_empty() {
+        return 0;
+    }
+
+    let column_degrees = column_degrees(pil_file);
+    let column_stages = column_stages(pil_file);
+    let mut extractor = Extractor {
+        pil_file,
+        extracted,
+        column_degrees,
+        column_stages,
+        columns: Default::default(),
+        new_columns: vec![],
+    };
+    let mut identities = std::mem::take(&mut extractor.pil_file.identities);
+    for identity in &mut identities {
+        let source = identity.source.clone();
+        for e in identity.children_mut() {
+            extractor.replace(e, &source);
+        }
+    }
+    let Extractor {
+        pil_file,
+        new_columns,
+        ..
+    } = extractor;
+    pil_file.identities = identities;
+
+    let count = new_columns.len();
+    for (symbol, definition) in new_columns {
+        let name = symbol.absolute_name.clone();
+        pil_file
+            .intermediate_columns
+            .insert(name.clone(), (symbol, vec![definition]));
+        pil_file
+            .source_order
+            .push(StatementIdentifier::Definition(name));
+    }
+    log::debug!("Extracted {count} common sub-expressions into intermediate columns.");
+    count
+}
+
+/// @returns the expressions that should be extracted into intermediate columns.
+fn common_subexpressions<T: FieldElement>(
+    pil_file: &Analyzed<T>,
+) -> BTreeSet<AlgebraicExpression<T>> {
+    let mut counts = BTreeMap::new();
+    for e in pil_file.identities.iter().flat_map(|i| i.children()) {
+        for e in e.all_children().filter(|e| is_candidate(e)) {
+            *counts.entry(e).or_insert(0usize) += 1;
+        }
+    }
+    let mut candidates = counts
+        .iter()
+        .filter(|(_, count)| **count > 1)
+        .map(|(e, _)| *e)
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|e| Reverse(e.all_children().count()));
+
+    let mut extracted = BTreeSet::new();
+    for e in candidates {
+        let count = counts[e];
+        if count > 1 {
+            // All but one of the occurrences of the sub-expressions of `e` are replaced
+            // together with `e`.
+            for child in operands(e).flat_map(|child| child.all_children()) {
+                if let Some(child_count) = counts.get_mut(child) {
+                    *child_count -= count - 1;
+                }
+            }
+            extracted.insert(e.clone());
+        }
+    }
+    extracted
+}
+
+struct Extractor<'a, T> {
+    pil_file: &'a mut Analyzed<T>,
+    extracted: BTreeSet<AlgebraicExpression<T>>,
+    column_degrees: HashMap<PolyID, Option<DegreeType>>,
+    column_stages: HashMap<PolyID, u32>,
+    /// The intermediate columns introduced so far, by the expression they are equal to.
+    columns: BTreeMap<AlgebraicExpression<T>, AlgebraicReference>,
+    new_columns: Vec<(Symbol, AlgebraicExpression<T>)>,
+}
+
+impl<'a, T: FieldElement> Extractor<'a, T> {
+    /// Replaces all extracted expressions in `e` by references to intermediate columns.
+    fn replace(&mut self, e: &mut AlgebraicExpression<T>, source: &SourceRef) {
+        if self.extracted.contains(e) {
+            let reference = self.column_for(e, source);
+            *e = AlgebraicExpression::Reference(reference);
+        } else {
+            self.replace_in_operands(e, source);
+        }
+    }
+
+    fn replace_in_operands(&mut self, e: &mut AlgebraicExpression<T>, source: &SourceRef) {
+        match e {
+            AlgebraicExpression::BinaryOperation(left, _, right) => {
+                self.replace(left, source);
+                self.replace(right, source);
+            }
+            AlgebraicExpression::UnaryOperation(_, inner) => self.replace(inner, source),
+            _ => {}
+        }
+    }
+
+    /// @returns a reference to an intermediate column defined as `e`,
+    /// creating it if it does not exist yet.
+    fn column_for(&mut self, e: &AlgebraicExpression<T>, source: &SourceRef) -> AlgebraicReference {
+        if let Some(reference) = self.columns.get(e) {
+            return reference.clone();
+        }
+        let (namespace, degree) = namespace_and_degree(e, &self.column_degrees)
+            .expect("Only expressions that reference a column are extracted.");
+        // The column can only be evaluated once all columns and challenges it references are known.
+        let stage = latest_stage(e, &self.column_stages);
+        let absolute_name = (0..)
+            .map(|i| format!("{namespace}.common_subexpression_{i}"))
+            .find(|name| {
+                !self.pil_file.definitions.contains_key(name)
+                    && !self.pil_file.intermediate_columns.contains_key(name)
+                    && !self
+                        .new_columns
+                        .iter()
+                        .any(|(symbol, _)| &symbol.absolute_name == name)
+            })
+            .unwrap();
+        let id = (self.pil_file.intermediate_count() + self.new_columns.len()) as u64;
+        let symbol = Symbol {
+            id,
+            source: source.clone(),
+            absolute_name: absolute_name.clone(),
+            stage: (stage > 0).then_some(stage),
+            kind: SymbolKind::Poly(PolynomialType::Intermediate),
+            length: None,
+            degree,
+        };
+        let reference = AlgebraicReference {
+            name: absolute_name,
+            poly_id: PolyID {
+                id,
+                ptype: PolynomialType::Intermediate,
+            },
+            next: false,
+        };
+        self.columns.insert(e.clone(), reference.clone());
+
+        // Reserve the position of the column before extracting from its definition,
+        // so that the IDs are in source order.
+        let index = self.new_columns.len();
+        self.new_columns.push((symbol, e.clone()));
+        let mut definition = e.clone();
+        self.replace_in_operands(&mut definition, source);
+        self.new_columns[index].1 = definition;
+        reference
+    }
+}
+
+/// Returns true if `e` is an operation on another operation that references a column.
+fn is_candidate<T>(e: &AlgebraicExpression<T>) -> bool {
+    matches!(e, AlgebraicExpression::BinaryOperation(..))
+        && operands(e).any(|operand| {
+            matches!(
+                operand,
+                AlgebraicExpression::BinaryOperation(..) | AlgebraicExpression::UnaryOperation(..)
+            )
+        })
+        && e.all_children()
+            .any(|e| matches!(e, AlgebraicExpression::Reference(_)))
+}
+
+fn operands<T>(e: &AlgebraicExpression<T>) -> impl Iterator<Item = &AlgebraicExpression<T>> {
+    match e {
+        AlgebraicExpression::BinaryOperation(left, _, right) => vec![left.as_ref(), right.as_ref()],
+        AlgebraicExpression::UnaryOperation(_, inner) => vec![inner.as_ref()],
+        _ => vec![],
+    }
+    .into_iter()
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn extract_instruction_constraints() {
+        let input = r#"namespace N(8);
+        col witness instr_add, instr_sub, A, B, X;
+        instr_add * (X - (A + B)) + instr_sub * (X - (A - B)) = 0;
+        instr_add * (X - (A + B)) = A;
+        (X - (A + B)) * B = 1;
+        (X - (A - B)) * B = 0;
+        { A + B * 2 } in { X };
+        { (A + B * 2) * 3 } in { X };
+    "#;
+        let expectation = r#"namespace N(8);
+    col witness instr_add;
+    col witness instr_sub;
+    col witness A;
+    col witness B;
+    col witness X;
+    (N.common_subexpression_0 + (N.instr_sub * N.common_subexpression_2)) = 0;
+    N.common_subexpression_0 = N.A;
+    (N.common_subexpression_1 * N.B) = 1;
+    (N.common_subexpression_2 * N.B) = 0;
+    { N.common_subexpression_3 } in { N.X };
+    { (N.common_subexpression_3 * 3) } in { N.X };
+    col common_subexpression_0 = (N.instr_add * N.common_subexpression_1);
+    col common_subexpression_1 = (N.X - (N.A + N.B));
+    col common_subexpression_2 = (N.X - (N.A - N.B));
+    col common_subexpression_3 = (N.A + (N.B * 2));
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(extract_common_subexpressions(&mut pil), 4);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn only_nested_occurrences() {
+        let input = r#"namespace N(8);
+        col witness x, y, z;
+        x * (y + z * z) = 0;
+        x * (y + z * z) = y;
+    "#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(extract_common_subexpressions(&mut pil), 1);
+        assert_eq!(
+            pil.intermediate_polys_in_source_order()[0].1[0].to_string(),
+            "(N.x * (N.y + (N.z * N.z)))"
+        );
+        assert_eq!(pil.intermediate_polys_in_source_order()[0].0.stage, None);
+    }
+
+    #[test]
+    fn challenge_dependent_subexpressions() {
+        let input = r#"namespace std::prover(8);
+        let challenge = [];
+    namespace N(8);
+        col witness a, b;
+        col witness stage(1) z;
+        let alpha: expr = std::prover::challenge(0, 1);
+        z' * (alpha - a * b) = z;
+        (z - 1) * (alpha - a * b) = 0;
+        (a + b * b) * a = 0;
+        (a + b * b) * b = 0;
+        (z + b * b) * a = 0;
+        (z + b * b) * b = 0;
+    "#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(extract_common_subexpressions(&mut pil), 3);
+        let stages = pil
+            .intermediate_polys_in_source_order()
+            .into_iter()
+            .filter(|(symbol, _)| symbol.absolute_name != "N.alpha")
+            .map(|(symbol, definition)| (definition[0].to_string(), symbol.stage))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            vec![
+                ("(N.alpha - (N.a * N.b))".to_string(), Some(1)),
+                ("(N.a + (N.b * N.b))".to_string(), None),
+                ("(N.z + (N.b * N.b))".to_string(), Some(1)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,560 @@
+//! Reduces the degree of polynomial identities by introducing intermediate witness columns.
+//!
+//! If a product in a polynomial identity has a degree above the bound, it (or one of its
+//! factors) is replaced by a new witness column `w`, which is constrained by the new identity
+//! `w = factor`. Powers are expanded into products first. Equal factors share the same column.
+//!
+//! Witness generation for the new columns works without hints: Each column is determined by
+//! its defining identity as soon as the values in the factor are known (or one of the factors
+//! of the factor is zero), which is also what was needed to evaluate the original product.
+//! Expressions that reference the next row are never replaced by columns, since their values
+//! are not always known when the current row is processed.
+//! Since witness generation solves one identity at a time, it can still fail if the original
+//! identity was used to solve for a column inside a factor that is now replaced.
+//!
+//! Identities that cannot be reduced this way are reported as an error. This is the case for
+//! products of expressions that reference the next row and for references to intermediate
+//! columns of too large degree, which are not inlined.
+//! Only polynomial identities are reduced: The selectors and expressions of lookups and
+//! permutations are kept as they are, since witness generation and the detection of
+//! specialized machines rely on their shape. Their degree is not checked against the bound.
+//! Note that machines whose witness is generated by a specialized machine implementation
+//! (like the memory machines) are only recognized as long as their identities are not rewritten.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, Analyzed, IdentityKind,
+    PolyID, PolynomialType, SymbolKind,
+};
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_ast::SourceRef;
+use powdr_number::{DegreeType, FieldElement, LargeInt};
+
+use crate::logup::{add_column, column_degrees, namespace_and_degree};
+
+/// Rewrites all polynomial identities of degree larger than `max_degree` such that their
+/// degree is at most `max_degree`, see the module documentation.
+/// Lookups and permutations are not rewritten.
+/// @returns the number of witness columns that were introduced, or an error listing the
+/// identities whose degree could not be reduced to `max_degree`.
+pub fn reduce_degree<T: FieldElement>(
+    pil_file: &mut Analyzed<T>,
+    max_degree: usize,
+) -> Result<usize, String> {
+    if max_degree < 2 {
+        return Err(format!(
+            "Cannot reduce the degree of identities to {max_degree}, the bound has to be at least 2."
+        ));
+    }
+
+    let mut reducer = DegreeReducer::new(pil_file, max_degree);
+    let mut errors = vec![];
+    let column_degrees = column_degrees(pil_file);
+    for index in 0..pil_file.identities.len() {
+        let identity = &pil_file.identities[index];
+        if identity.kind != IdentityKind::Polynomial
+            || reducer.degree(identity.expression_for_poly_id()) <= max_degree
+        {
+            continue;
+        }
+        let (namespace, degree) = namespace_and_degree(&identity.left, &column_degrees)
+            .expect("An identity of positive degree has to reference a column.");
+        let degree = degree.or(pil_file.degree).ok_or_else(|| {
+            format!("Cannot reduce the degree of identity without degree: {identity}")
+        })?;
+        let source = identity.source.clone();
+        let expression = identity.expression_for_poly_id().clone();
+
+        let reduced = reducer.reduce(
+            expression,
+            max_degree,
+            &mut Namespace {
+                pil_file,
+                name: &namespace,
+                degree,
+                source: &source,
+            },
+        );
+        let reduced_degree = reducer.degree(&reduced);
+        pil_file.identities[index].left.selector = Some(reduced);
+        if reduced_degree > max_degree {
+            errors.push(format!(
+                "Could only reduce the degree of identity to {reduced_degree}: {}",
+                pil_file.identities[index]
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(format!(
+            "Cannot reduce the degree of identities to {max_degree}:\n{}",
+            errors.join("\n")
+        ));
+    }
+
+    log::debug!(
+        "Introduced {} witness columns to reduce the degree of identities to {max_degree}.",
+        reducer.columns.len()
+    );
+    Ok(reducer.columns.len())
+}
+
+/// The context in which new columns and their defining identities are created.
+struct Namespace<'a, T> {
+    pil_file: &'a mut Analyzed<T>,
+    name: &'a str,
+    degree: DegreeType,
+    source: &'a SourceRef,
+}
+
+struct DegreeReducer<T> {
+    max_degree: usize,
+    /// The degrees of all intermediate columns.
+    intermediate_degrees: HashMap<PolyID, usize>,
+    /// The intermediate columns that (directly or indirectly) reference the next row.
+    intermediates_with_next: HashSet<PolyID>,
+    /// The stages of all witness columns in a stage larger than zero.
+    stages: HashMap<PolyID, u32>,
+    /// The columns introduced so far, by the expression they are equal to.
+    columns: BTreeMap<AlgebraicExpression<T>, AlgebraicReference>,
+}
+
+impl<T: FieldElement> DegreeReducer<T> {
+    fn new(pil_file: &Analyzed<T>, max_degree: usize) -> Self {
+        let stages = pil_file
+            .definitions
+            .values()
+            .map(|(symbol, _)| symbol)
+            .filter(|symbol| symbol.kind == SymbolKind::Poly(PolynomialType::Committed))
+            .filter_map(|symbol| Some((symbol, symbol.stage?)))
+            .flat_map(|(symbol, stage)| {
+                symbol
+                    .array_elements()
+                    .map(move |(_, poly_id)| (poly_id, stage))
+            })
+            .collect();
+        let definitions = pil_file
+            .intermediate_columns
+            .values()
+            .flat_map(|(symbol, expressions)| {
+                symbol
+                    .array_elements()
+                    .map(|(_, poly_id)| poly_id)
+                    .zip(expressions)
+            })
+            .collect::<HashMap<_, _>>();
+        let mut intermediate_degrees = HashMap::new();
+        let mut intermediates_with_next = HashSet::new();
+        for poly_id in definitions.keys() {
+            analyze_intermediate(
+                *poly_id,
+                &definitions,
+                &mut intermediate_degrees,
+                &mut intermediates_with_next,
+            );
+        }
+        Self {
+            max_degree,
+            intermediate_degrees,
+            intermediates_with_next,
+            stages,
+            columns: Default::default(),
+        }
+    }
+
+    fn degree(&self, e: &AlgebraicExpression<T>) -> usize {
+        expression_degree(e, &|poly_id| self.intermediate_degrees[poly_id])
+    }
+
+    /// @returns an expression equivalent to `e` of degree at most `bound` (which is at least one),
+    /// if that is possible without replacing expressions that reference the next row or
+    /// intermediate columns of too large degree by new columns.
+    fn reduce(
+        &mut self,
+        e: AlgebraicExpression<T>,
+        bound: usize,
+        namespace: &mut Namespace<T>,
+    ) -> AlgebraicExpression<T> {
+        if self.degree(&e) <= bound {
+            return e;
+        }
+        match e {
+            e @ AlgebraicExpression::BinaryOperation(_, AlgebraicBinaryOperator::Mul, _) => {
+                let factors = product_factors(e)
+                    .into_iter()
+                    .map(|factor| self.reduce(factor, self.max_degree, namespace))
+                    .collect();
+                self.reduce_product(factors, bound, namespace)
+            }
+            AlgebraicExpression::BinaryOperation(base, AlgebraicBinaryOperator::Pow, exponent) => {
+                let AlgebraicExpression::Number(exponent) = exponent.as_ref() else {
+                    unreachable!("Exponent has to be a number.")
+                };
+                let exponent = exponent.to_integer().try_into_u64().unwrap();
+                self.reduce_power(*base, exponent, bound, namespace)
+            }
+            AlgebraicExpression::BinaryOperation(left, op, right) => {
+                AlgebraicExpression::BinaryOperation(
+                    Box::new(self.reduce(*left, bound, namespace)),
+                    op,
+                    Box::new(self.reduce(*right, bound, namespace)),
+                )
+            }
+            AlgebraicExpression::UnaryOperation(op, inner) => AlgebraicExpression::UnaryOperation(
+                op,
+                Box::new(self.reduce(*inner, bound, namespace)),
+            ),
+            e if self.can_be_column(&e) => self.column_for(e, namespace),
+            e => e,
+        }
+    }
+
+    /// @returns the product of `factors` (each of degree at most `max_degree`) with degree
+    /// at most `bound`, if possible. Pairs of factors are replaced by columns first, so that
+    /// factors that cannot be replaced (because they reference the next row) do not prevent
+    /// the others from being combined.
+    fn reduce_product(
+        &mut self,
+        mut factors: Vec<AlgebraicExpression<T>>,
+        bound: usize,
+        namespace: &mut Namespace<T>,
+    ) -> AlgebraicExpression<T> {
+        while self.product_degree(&factors) > bound {
+            if let Some((i, j)) = self.cheapest_pair(&factors) {
+                let right = factors.remove(j);
+                factors[i] = self.column_for(factors[i].clone() * right, namespace);
+            } else if let Some(i) = (0..factors.len())
+                .filter(|&i| self.degree(&factors[i]) > 1 && self.can_be_column(&factors[i]))
+                .max_by_key(|&i| (self.degree(&factors[i]), std::cmp::Reverse(i)))
+            {
+                factors[i] = self.column_for(factors[i].clone(), namespace);
+            } else {
+                break;
+            }
+        }
+        // The remaining factors cannot be replaced, but maybe their sub-expressions can.
+        for i in 0..factors.len() {
+            let excess = self.product_degree(&factors).saturating_sub(bound);
+            let degree = self.degree(&factors[i]);
+            if excess > 0 && degree > 1 {
+                factors[i] = self.reduce(
+                    factors[i].clone(),
+                    degree.saturating_sub(excess).max(1),
+                    namespace,
+                );
+            }
+        }
+        factors
+            .into_iter()
+            .reduce(|left, right| left * right)
+            .unwrap()
+    }
+
+    /// Reduces `base**exponent` by squaring, so that equal factors share columns.
+    fn reduce_power(
+        &mut self,
+        base: AlgebraicExpression<T>,
+        exponent: u64,
+        bound: usize,
+        namespace: &mut Namespace<T>,
+    ) -> AlgebraicExpression<T> {
+        if exponent == 1 {
+            return self.reduce(base, bound, namespace);
+        }
+        let root = self.reduce_power(base.clone(), exponent / 2, self.max_degree, namespace);
+        let mut factors = vec![root.clone(), root];
+        if exponent % 2 == 1 {
+            factors.push(self.reduce(base, self.max_degree, namespace));
+        }
+        self.reduce_product(factors, bound, namespace)
+    }
+
+    fn product_degree(&self, factors: &[AlgebraicExpression<T>]) -> usize {
+        factors.iter().map(|factor| self.degree(factor)).sum()
+    }
+
+    /// @returns the indices of the two factors of smallest total degree whose product
+    /// can be replaced by a column, such that the degree is actually reduced.
+    fn cheapest_pair(&self, factors: &[AlgebraicExpression<T>]) -> Option<(usize, usize)> {
+        (0..factors.len())
+            .flat_map(|i| (i + 1..factors.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| {
+                let (left, right) = (self.degree(&factors[i]), self.degree(&factors[j]));
+                left > 0
+                    && right > 0
+                    && left + right <= self.max_degree
+                    && self.can_be_column(&factors[i])
+                    && self.can_be_column(&factors[j])
+            })
+            .min_by_key(|&(i, j)| self.degree(&factors[i]) + self.degree(&factors[j]))
+    }
+
+    /// Returns true if `e` can be replaced by a new column: Its defining identity must not
+    /// exceed the degree bound and it must not reference the next row. The latter is needed
+    /// for witness generation, because the value of the next row might only be known
+    /// later (for example in the next block of a block machine) or not at all, if the
+    /// original identity did not require it on the current row.
+    fn can_be_column(&self, e: &AlgebraicExpression<T>) -> bool {
+        self.degree(e) <= self.max_degree
+            && !e.all_children().any(|e| match e {
+                AlgebraicExpression::Reference(reference) => {
+                    reference.next || self.intermediates_with_next.contains(&reference.poly_id)
+                }
+                _ => false,
+            })
+    }
+
+    /// @returns a reference to a witness column that is constrained to be equal to `e`,
+    /// creating it if it does not exist yet.
+    fn column_for(
+        &mut self,
+        e: AlgebraicExpression<T>,
+        namespace: &mut Namespace<T>,
+    ) -> AlgebraicExpression<T> {
+        if let Some(reference) = self.columns.get(&e) {
+            return AlgebraicExpression::Reference(reference.clone());
+        }
+        let pil_file = &mut *namespace.pil_file;
+        let name = (0..)
+            .map(|i| format!("{}.degree_reduction_{i}", namespace.name))
+            .find(|name| !pil_file.definitions.contains_key(name))
+            .unwrap();
+        // The column can only be computed once all columns and challenges in `e` are known.
+        let stage = e
+            .all_children()
+            .filter_map(|e| match e {
+                AlgebraicExpression::Reference(reference) => {
+                    self.stages.get(&reference.poly_id).copied()
+                }
+                AlgebraicExpression::Challenge(challenge) => Some(challenge.stage + 1),
+                _ => None,
+            })
+            .max();
+        let reference = add_column(
+            pil_file,
+            name,
+            PolynomialType::Committed,
+            stage,
+            namespace.degree,
+            namespace.source,
+        );
+        if let Some(stage) = stage {
+            self.stages.insert(reference.poly_id, stage);
+        }
+        pil_file.append_polynomial_identity(
+            AlgebraicExpression::Reference(reference.clone()) - e.clone(),
+            namespace.source.clone(),
+        );
+        self.columns.insert(e, reference.clone());
+        AlgebraicExpression::Reference(reference)
+    }
+}
+
+/// @returns the factors of a (nested) product.
+fn product_factors<T>(e: AlgebraicExpression<T>) -> Vec<AlgebraicExpression<T>> {
+    match e {
+        AlgebraicExpression::BinaryOperation(left, AlgebraicBinaryOperator::Mul, right) => {
+            let mut factors = product_factors(*left);
+            factors.extend(product_factors(*right));
+            factors
+        }
+        e => vec![e],
+    }
+}
+
+/// Computes the degree of the intermediate column and whether it references the next row,
+/// after doing so for all intermediate columns it references.
+fn analyze_intermediate<T: FieldElement>(
+    poly_id: PolyID,
+    definitions: &HashMap<PolyID, &AlgebraicExpression<T>>,
+    degrees: &mut HashMap<PolyID, usize>,
+    with_next: &mut HashSet<PolyID>,
+) {
+    if degrees.contains_key(&poly_id) {
+        return;
+    }
+    let mut references_next = false;
+    for e in definitions[&poly_id].all_children() {
+        if let AlgebraicExpression::Reference(reference) = e {
+            if reference.poly_id.ptype == PolynomialType::Intermediate {
+                analyze_intermediate(reference.poly_id, definitions, degrees, with_next);
+                references_next |= with_next.contains(&reference.poly_id);
+            }
+            references_next |= reference.next;
+        }
+    }
+    let degree = expression_degree(definitions[&poly_id], &|id| degrees[id]);
+    degrees.insert(poly_id, degree);
+    if references_next {
+        with_next.insert(poly_id);
+    }
+}
+
+/// @returns the degree of the expression, where columns have degree one, apart from
+/// intermediate columns, whose degree is returned by `intermediate_degree`.
+fn expression_degree<T: FieldElement>(
+    e: &AlgebraicExpression<T>,
+    intermediate_degree: &impl Fn(&PolyID) -> usize,
+) -> usize {
+    let degree = |e| expression_degree(e, intermediate_degree);
+    match e {
+        AlgebraicExpression::Reference(reference) => match reference.poly_id.ptype {
+            PolynomialType::Intermediate => intermediate_degree(&reference.poly_id),
+            _ => 1,
+        },
+        AlgebraicExpression::PublicReference(_)
+        | AlgebraicExpression::Challenge(_)
+        | AlgebraicExpression::Number(_) => 0,
+        AlgebraicExpression::BinaryOperation(left, op, right) => match op {
+            AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                degree(left).max(degree(right))
+            }
+            AlgebraicBinaryOperator::Mul => degree(left) + degree(right),
+            AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                AlgebraicExpression::Number(exponent) => {
+                    degree(left) * exponent.to_integer().try_into_u64().unwrap() as usize
+                }
+                _ => unreachable!("Exponent has to be a number."),
+            },
+        },
+        AlgebraicExpression::UnaryOperation(_, inner) => degree(inner),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn reduce_product() {
+        let input = r#"namespace N(8);
+        col witness a, b, c, d, e;
+        a * b * c * d = e;
+        (a * b * c) * e = 0;
+    "#;
+        let expectation = r#"namespace N(8);
+    col witness a;
+    col witness b;
+    col witness c;
+    col witness d;
+    col witness e;
+    ((N.degree_reduction_0 * N.c) * N.d) = N.e;
+    ((N.degree_reduction_0 * N.c) * N.e) = 0;
+    col witness degree_reduction_0;
+    N.degree_reduction_0 = (N.a * N.b);
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(reduce_degree(&mut pil, 3).unwrap(), 1);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn reduce_power() {
+        let input = r#"namespace N(8);
+        col witness x, y;
+        y = x**5 + 1;
+        y' = x * y;
+    "#;
+        let expectation = r#"namespace N(8);
+    col witness x;
+    col witness y;
+    N.y = ((N.degree_reduction_1 * N.degree_reduction_0) + 1);
+    N.y' = (N.x * N.y);
+    col witness degree_reduction_0;
+    N.degree_reduction_0 = (N.x * N.x);
+    col witness degree_reduction_1;
+    N.degree_reduction_1 = (N.degree_reduction_0 * N.x);
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(reduce_degree(&mut pil, 2).unwrap(), 2);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn next_reference() {
+        let input = r#"namespace N(8);
+        col witness x, y, z;
+        (x' - x) * y * z = 0;
+    "#;
+        let expectation = r#"namespace N(8);
+    col witness x;
+    col witness y;
+    col witness z;
+    ((N.x' - N.x) * N.degree_reduction_0) = 0;
+    col witness degree_reduction_0;
+    N.degree_reduction_0 = (N.y * N.z);
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(reduce_degree(&mut pil, 2).unwrap(), 1);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn later_stage() {
+        let input = r#"namespace std::prover(8);
+        let challenge = [];
+    namespace N(8);
+        col witness x;
+        col witness stage(1) acc;
+        acc' = acc * x * x + std::prover::challenge(0, 1);
+    "#;
+        let expectation = r#"namespace std::prover(8);
+    let challenge = [];
+namespace N(8);
+    col witness x;
+    col witness stage(1) acc;
+    N.acc' = ((N.degree_reduction_0 * N.x) + std::prover::challenge(0, 1));
+    col witness stage(1) degree_reduction_0;
+    N.degree_reduction_0 = (N.acc * N.x);
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(reduce_degree(&mut pil, 2).unwrap(), 1);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn irreducible() {
+        let input = r#"namespace N(8);
+        col witness x, y;
+        (x' - x) * (y' - y) * x = 0;
+    "#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(
+            reduce_degree(&mut pil, 2),
+            Err("Cannot reduce the degree of identities to 2:\n\
+                Could only reduce the degree of identity to 3: \
+                (((N.x' - N.x) * (N.y' - N.y)) * N.x) = 0;"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn lookups_are_kept() {
+        let input = r#"namespace N(8);
+        col witness x, y, z;
+        col fixed SEL = [0, 1]*;
+        SEL * x { x * y * z } in { y };
+    "#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        let expectation = pil.to_string();
+        assert_eq!(reduce_degree(&mut pil, 2).unwrap(), 0);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn bound_too_small() {
+        let mut pil = analyze_string::<GoldilocksField>("namespace N(8); col witness x; x = x;");
+        assert_eq!(
+            reduce_degree(&mut pil, 1),
+            Err(
+                "Cannot reduce the degree of identities to 1, the bound has to be at least 2."
+                    .to_string()
+            )
+        );
+    }
+}
@@ -1,18 +1,25 @@
 //! PIL-based optimizer
 #![deny(clippy::print_stdout)]
 
+pub mod common_subexpressions;
+pub mod degree_reduction;
+pub mod logup;
+
 use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::iter::once;
+use std::iter::{empty, once};
 
 use powdr_ast::analyzed::{
     AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator,
     Analyzed, Expression, FunctionValueDefinition, IdentityKind, PolyID, PolynomialReference,
     Reference, SymbolKind, TypedExpression,
 };
-use powdr_ast::parsed::types::Type;
+use powdr_ast::parsed::types::{is_builtin_trait, Type, TypeBounds};
 use powdr_ast::parsed::visitor::{AllChildren, Children, ExpressionVisitable};
-use powdr_ast::parsed::EnumDeclaration;
+use powdr_ast::parsed::{
+    EnumDeclaration, LambdaExpression, LetStatementInsideBlock, Pattern, StatementInsideBlock,
+    StructDeclaration, StructExpression, TraitDeclaration, TypeDeclaration,
+};
 use powdr_number::{BigUint, FieldElement};
 
 pub fn optimize<T: FieldElement>(mut pil_file: Analyzed<T>) -> Analyzed<T> {
@@ -86,23 +93,51 @@ trait ReferencedSymbols {
 impl ReferencedSymbols for FunctionValueDefinition {
     fn symbols(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
         match self {
-            FunctionValueDefinition::TypeDeclaration(EnumDeclaration { name: _, variants }) => {
-                Box::new(
+            FunctionValueDefinition::TypeDeclaration(TypeDeclaration::Enum(EnumDeclaration {
+                name: _,
+                type_vars,
+                variants,
+            })) => Box::new(
+                type_vars.symbols().chain(
                     variants
                         .iter()
                         .flat_map(|v| &v.fields)
                         .flat_map(|t| t.iter())
                         .flat_map(|t| t.symbols()),
-                )
-            }
-            FunctionValueDefinition::TypeConstructor(type_name, _) => {
+                ),
+            ),
+            FunctionValueDefinition::TypeDeclaration(TypeDeclaration::Struct(
+                StructDeclaration { name: _, fields },
+            )) => Box::new(fields.iter().flat_map(|f| f.ty.symbols())),
+            FunctionValueDefinition::TypeConstructor(enum_decl, _) => {
                 // This the type constructor of an enum variant, it references the enum itself.
-                Box::new(once(type_name.into()))
+                Box::new(once(enum_decl.name.as_str().into()))
+            }
+            FunctionValueDefinition::TraitDeclaration(
+                TraitDeclaration { functions, .. },
+                implementations,
+            ) => Box::new(functions.iter().flat_map(|f| f.ty.symbols()).chain(
+                implementations.iter().flat_map(|i| {
+                    i.type_vars
+                        .symbols()
+                        .chain(i.type_args.iter().flat_map(|t| t.symbols()))
+                        .chain(i.functions.iter().flat_map(|f| f.body.symbols()))
+                }),
+            )),
+            FunctionValueDefinition::TraitFunction(trait_name, _) => {
+                // A trait function references the trait and thus all its implementations.
+                Box::new(once(trait_name.into()))
             }
             FunctionValueDefinition::Expression(TypedExpression {
                 type_scheme: Some(type_scheme),
                 e,
-            }) => Box::new(type_scheme.ty.symbols().chain(e.symbols())),
+            }) => Box::new(
+                type_scheme
+                    .vars
+                    .symbols()
+                    .chain(type_scheme.ty.symbols())
+                    .chain(e.symbols()),
+            ),
             _ => Box::new(self.children().flat_map(|e| e.symbols())),
         }
     }
@@ -112,21 +147,67 @@ impl ReferencedSymbols for Expression {
     fn symbols(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
         Box::new(
             self.all_children()
-                .flat_map(|e| match e {
-                    Expression::Reference(Reference::Poly(PolynomialReference {
-                        name,
-                        type_args,
-                        poly_id: _,
-                    })) => Some(
-                        type_args
-                            .iter()
-                            .flat_map(|t| t.iter())
-                            .flat_map(|t| t.symbols())
-                            .chain(once(name.into())),
-                    ),
-                    _ => None,
-                })
-                .flatten(),
+                .flat_map(|e| -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+                    match e {
+                        Expression::Reference(Reference::Poly(PolynomialReference {
+                            name,
+                            type_args,
+                            poly_id: _,
+                        })) => Box::new(
+                            type_args
+                                .iter()
+                                .flat_map(|t| t.iter())
+                                .flat_map(|t| t.symbols())
+                                .chain(once(name.into())),
+                        ),
+                        Expression::StructExpression(StructExpression { name, .. }) => {
+                            Box::new(once(name.to_dotted_string().into()))
+                        }
+                        Expression::LambdaExpression(LambdaExpression { params, .. }) => {
+                            Box::new(params.iter().flat_map(|p| p.symbols()))
+                        }
+                        Expression::MatchExpression(_, arms) => {
+                            Box::new(arms.iter().flat_map(|arm| arm.pattern.symbols()))
+                        }
+                        Expression::BlockExpression(statements, _) => {
+                            Box::new(statements.iter().flat_map(|s| match s {
+                                StatementInsideBlock::LetStatement(LetStatementInsideBlock {
+                                    pattern,
+                                    ..
+                                }) => pattern.symbols(),
+                                StatementInsideBlock::Expression(_) => Box::new(empty()),
+                            }))
+                        }
+                        _ => Box::new(empty()),
+                    }
+                }),
+        )
+    }
+}
+
+impl ReferencedSymbols for Pattern {
+    fn symbols(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+        let name = match self {
+            Pattern::Struct(name, _) | Pattern::Enum(name, _) => {
+                Some(name.to_dotted_string().into())
+            }
+            _ => None,
+        };
+        Box::new(
+            name.into_iter()
+                .chain(self.children().flat_map(|p| p.symbols())),
+        )
+    }
+}
+
+impl ReferencedSymbols for TypeBounds {
+    fn symbols(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+        // Built-in traits do not have a definition.
+        Box::new(
+            self.bounds()
+                .flat_map(|(_, bounds)| bounds)
+                .filter(|b| !is_builtin_trait(b))
+                .map(|b| b.into()),
         )
     }
 }
@@ -228,7 +309,9 @@ fn constant_value(function: &FunctionValueDefinition) -> Option<BigUint> {
         }
         FunctionValueDefinition::Expression(_)
         | FunctionValueDefinition::TypeDeclaration(_)
-        | FunctionValueDefinition::TypeConstructor(_, _) => None,
+        | FunctionValueDefinition::TypeConstructor(_, _)
+        | FunctionValueDefinition::TraitDeclaration(_, _)
+        | FunctionValueDefinition::TraitFunction(_, _) => None,
     }
 }
 
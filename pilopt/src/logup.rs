@@ -0,0 +1,459 @@
+//! Rewrites plookup and permutation identities into polynomial identities of a second stage,
+//! implementing the LogUp argument.
+//!
+//! For a lookup `sel_l { l_1, ..., l_k } in sel_r { r_1, ..., r_k }`, the tuples are
+//! compressed to `f = l_1 * alpha^(k-1) + ... + l_k` and `t = r_1 * alpha^(k-1) + ... + r_k`
+//! using a challenge `alpha`. A stage-0 witness column `m` holds how often each row of the
+//! right side is looked up and a stage-1 witness column `acc` accumulates
+//! `sel_l / (beta - f) - m * sel_r / (beta - t)` over all rows, using a second challenge `beta`:
+//!
+//! ```text
+//! first * acc = 0;
+//! (acc' - acc) * (beta - f) * (beta - t) = sel_l * (beta - t) - m * sel_r * (beta - f);
+//! ```
+//!
+//! Since the constraint wraps around from the last row to the first, the sum over all rows
+//! has to be zero, which is the case (with high probability) if and only if every selected
+//! tuple on the left side occurs on the right side.
+//!
+//! The values of the multiplicity columns are not determined by the rewritten identities,
+//! they have to be computed from the original lookups.
+//!
+//! A permutation `sel_l { l_1, ..., l_k } is sel_r { r_1, ..., r_k }` is rewritten in the
+//! same way, but with all multiplicities equal to one, so that no multiplicity column is needed.
+//!
+//! The challenges are drawn after the latest stage of the columns (and challenges) referenced
+//! by the identity, and the accumulator is a column of the following stage. Since the
+//! multiplicities are only computed for stage 0, lookups over columns of later stages
+//! are rejected.
+
+use std::collections::{BTreeMap, HashMap};
+
+use powdr_ast::analyzed::{
+    AlgebraicExpression, AlgebraicReference, Analyzed, Challenge, Expression,
+    FunctionValueDefinition, IdentityKind, PolyID, PolynomialType, RepeatedArray,
+    StatementIdentifier, Symbol, SymbolKind,
+};
+use powdr_ast::parsed::types::Type;
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_ast::parsed::SelectedExpressions;
+use powdr_ast::SourceRef;
+use powdr_number::{DegreeType, FieldElement};
+
+/// A plookup identity that has been replaced by LogUp constraints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogUpLookup {
+    /// The ID of the (removed) plookup identity.
+    pub identity_id: u64,
+    /// The name of the stage-0 witness column that holds, for each row of the right side,
+    /// how often it is looked up.
+    pub multiplicity_column: String,
+}
+
+/// Replaces all plookup and permutation identities by LogUp constraints, see the module
+/// documentation. Both sides of each lookup need to reference columns of the same degree.
+/// @returns the rewritten lookups (but not the permutations) in source order.
+pub fn lookups_to_logup<T: FieldElement>(
+    pil_file: &mut Analyzed<T>,
+) -> Result<Vec<LogUpLookup>, String> {
+    let lookups = pil_file
+        .identities
+        .iter()
+        .enumerate()
+        .filter(|(_, identity)| {
+            matches!(
+                identity.kind,
+                IdentityKind::Plookup | IdentityKind::Permutation
+            )
+        })
+        .map(|(index, identity)| (index, identity.clone()))
+        .collect::<Vec<_>>();
+    if lookups.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let column_degrees = column_degrees(pil_file);
+    let first_challenge_id = pil_file
+        .identities
+        .iter()
+        .flat_map(|identity| identity.all_children())
+        .chain(
+            pil_file
+                .intermediate_columns
+                .values()
+                .flat_map(|(_, expressions)| expressions.iter().flat_map(|e| e.all_children())),
+        )
+        .filter_map(|e| match e {
+            AlgebraicExpression::Challenge(challenge) => Some(challenge.id + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default();
+    let column_stages = column_stages(pil_file);
+    let mut next_challenge_id = first_challenge_id;
+    let mut challenges_by_stage = BTreeMap::new();
+    let mut first_columns = BTreeMap::new();
+    let mut rewritten = vec![];
+    for (_, identity) in &lookups {
+        let (left_namespace, left_degree) = namespace_and_degree(&identity.left, &column_degrees)
+            .ok_or_else(|| {
+            format!("Cannot rewrite lookup whose left side references no column: {identity}")
+        })?;
+        let (right_namespace, right_degree) =
+            namespace_and_degree(&identity.right, &column_degrees).ok_or_else(|| {
+                format!("Cannot rewrite lookup whose right side references no column: {identity}")
+            })?;
+        let degree = match (left_degree, right_degree) {
+            (Some(left), Some(right)) if left != right => {
+                return Err(format!(
+                    "Cannot rewrite lookup between columns of different degrees ({left} and {right}): {identity}"
+                ))
+            }
+            (degree, None) | (None, degree) => degree.or(pil_file.degree),
+            (degree, _) => degree,
+        }
+        .ok_or_else(|| format!("Cannot rewrite lookup without degree: {identity}"))?;
+
+        // The challenges have to be drawn after all columns of the lookup are committed.
+        let stage = latest_stage(identity, &column_stages);
+        if stage > 0 && identity.kind == IdentityKind::Plookup {
+            return Err(format!(
+                "Cannot rewrite lookup that references columns of stage {stage}: {identity}"
+            ));
+        }
+        let [alpha, beta] = challenges_by_stage
+            .entry(stage)
+            .or_insert_with(|| {
+                [0, 1].map(|_| {
+                    next_challenge_id += 1;
+                    AlgebraicExpression::Challenge(Challenge {
+                        id: next_challenge_id - 1,
+                        stage,
+                    })
+                })
+            })
+            .clone();
+
+        let first = first_columns
+            .entry(left_namespace.clone())
+            .or_insert_with(|| {
+                add_column(
+                    pil_file,
+                    format!("{left_namespace}.logup_first"),
+                    PolynomialType::Constant,
+                    None,
+                    degree,
+                    &identity.source,
+                )
+            })
+            .clone();
+        let multiplicity = if identity.kind == IdentityKind::Plookup {
+            let multiplicity_column =
+                format!("{right_namespace}.logup_multiplicity_{}", identity.id);
+            let multiplicity = add_column(
+                pil_file,
+                multiplicity_column.clone(),
+                PolynomialType::Committed,
+                None,
+                degree,
+                &identity.source,
+            );
+            rewritten.push(LogUpLookup {
+                identity_id: identity.id,
+                multiplicity_column,
+            });
+            AlgebraicExpression::Reference(multiplicity)
+        } else {
+            T::one().into()
+        };
+        let accumulator = add_column(
+            pil_file,
+            format!("{left_namespace}.logup_acc_{}", identity.id),
+            PolynomialType::Committed,
+            Some(stage + 1),
+            degree,
+            &identity.source,
+        );
+
+        let selected = |side: &SelectedExpressions<AlgebraicExpression<T>>,
+                        e: AlgebraicExpression<T>| match &side.selector {
+            Some(selector) => selector.clone() * e,
+            None => e,
+        };
+        let compress = |side: &SelectedExpressions<AlgebraicExpression<T>>| {
+            side.expressions
+                .iter()
+                .cloned()
+                .reduce(|acc, e| acc * alpha.clone() + e)
+                .unwrap_or_else(|| T::zero().into())
+        };
+        let left_denominator = beta.clone() - compress(&identity.left);
+        let right_denominator = beta.clone() - compress(&identity.right);
+        let accumulator_next = AlgebraicExpression::Reference(AlgebraicReference {
+            next: true,
+            ..accumulator.clone()
+        });
+        let [first, accumulator] = [first, accumulator].map(AlgebraicExpression::Reference);
+
+        pil_file.append_polynomial_identity(first * accumulator.clone(), identity.source.clone());
+        pil_file.append_polynomial_identity(
+            (accumulator_next - accumulator) * left_denominator.clone() * right_denominator.clone()
+                - (selected(&identity.left, right_denominator)
+                    - selected(&identity.right, multiplicity) * left_denominator),
+            identity.source.clone(),
+        );
+    }
+
+    pil_file.remove_identities(&lookups.iter().map(|(index, _)| *index).collect());
+    Ok(rewritten)
+}
+
+/// @returns the degree of each column, if it has one.
+pub(crate) fn column_degrees<T>(pil_file: &Analyzed<T>) -> HashMap<PolyID, Option<DegreeType>> {
+    pil_file
+        .definitions
+        .values()
+        .map(|(symbol, _)| symbol)
+        .filter(|symbol| matches!(symbol.kind, SymbolKind::Poly(_)))
+        .chain(
+            pil_file
+                .intermediate_columns
+                .values()
+                .map(|(symbol, _)| symbol),
+        )
+        .flat_map(|symbol| {
+            symbol
+                .array_elements()
+                .map(|(_, poly_id)| (poly_id, symbol.degree))
+        })
+        .collect()
+}
+
+/// @returns the stage of each witness column and of each intermediate column, which is
+/// the latest stage of the columns and challenges in its definition.
+pub(crate) fn column_stages<T>(pil_file: &Analyzed<T>) -> HashMap<PolyID, u32> {
+    let mut stages = pil_file
+        .definitions
+        .values()
+        .map(|(symbol, _)| symbol)
+        .filter(|symbol| symbol.kind == SymbolKind::Poly(PolynomialType::Committed))
+        .flat_map(|symbol| {
+            symbol
+                .array_elements()
+                .map(|(_, poly_id)| (poly_id, symbol.stage.unwrap_or_default()))
+        })
+        .collect::<HashMap<_, _>>();
+    for (symbol, definitions) in pil_file.intermediate_polys_in_source_order() {
+        for ((_, poly_id), definition) in symbol.array_elements().zip(definitions) {
+            let stage = latest_stage(definition, &stages);
+            stages.insert(poly_id, stage);
+        }
+    }
+    stages
+}
+
+/// @returns the latest stage of the witness columns and challenges referenced by the given
+/// expressions, where a challenge belongs to the stage after the one it is drawn after.
+pub(crate) fn latest_stage<T>(
+    expressions: &impl AllChildren<AlgebraicExpression<T>>,
+    column_stages: &HashMap<PolyID, u32>,
+) -> u32 {
+    expressions
+        .all_children()
+        .filter_map(|e| match e {
+            AlgebraicExpression::Reference(reference) => {
+                column_stages.get(&reference.poly_id).copied()
+            }
+            AlgebraicExpression::Challenge(challenge) => Some(challenge.stage + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// @returns the namespace and degree of the first column referenced by the given
+/// expressions (for example one side of a lookup).
+pub(crate) fn namespace_and_degree<T>(
+    expressions: &impl AllChildren<AlgebraicExpression<T>>,
+    column_degrees: &HashMap<PolyID, Option<DegreeType>>,
+) -> Option<(String, Option<DegreeType>)> {
+    expressions.all_children().find_map(|e| match e {
+        AlgebraicExpression::Reference(reference) => {
+            let namespace = reference
+                .name
+                .rfind('.')
+                .map(|index| reference.name[..index].to_string())
+                .unwrap_or_default();
+            Some((namespace, column_degrees[&reference.poly_id]))
+        }
+        _ => None,
+    })
+}
+
+/// Declares a new column at the end of the source order and returns a reference to it.
+/// Fixed columns are defined as `[1] + [0]*`.
+pub(crate) fn add_column<T>(
+    pil_file: &mut Analyzed<T>,
+    absolute_name: String,
+    ptype: PolynomialType,
+    stage: Option<u32>,
+    degree: DegreeType,
+    source: &SourceRef,
+) -> AlgebraicReference {
+    assert!(
+        !pil_file.definitions.contains_key(&absolute_name),
+        "Symbol {absolute_name} is already defined."
+    );
+    let id = match ptype {
+        PolynomialType::Committed => pil_file.commitment_count(),
+        PolynomialType::Constant => pil_file.constant_count(),
+        PolynomialType::Intermediate => unreachable!(),
+    } as u64;
+    let value = (ptype == PolynomialType::Constant).then(|| {
+        let number = |n: u32| Expression::Number(n.into(), Some(Type::Fe));
+        FunctionValueDefinition::Array(vec![
+            RepeatedArray::new(vec![number(1)], 1),
+            RepeatedArray::new(vec![number(0)], degree - 1),
+        ])
+    });
+    let symbol = Symbol {
+        id,
+        source: source.clone(),
+        absolute_name: absolute_name.clone(),
+        stage,
+        kind: SymbolKind::Poly(ptype),
+        length: None,
+        degree: Some(degree),
+    };
+    pil_file
+        .definitions
+        .insert(absolute_name.clone(), (symbol, value));
+    pil_file
+        .source_order
+        .push(StatementIdentifier::Definition(absolute_name.clone()));
+    AlgebraicReference {
+        name: absolute_name,
+        poly_id: PolyID { id, ptype },
+        next: false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use super::*;
+
+    #[test]
+    fn rewrite_lookup() {
+        let input = r#"namespace N(8);
+        col fixed BYTE(i) { i & 0xff };
+        col fixed sel = [1, 0]*;
+        col witness x, y;
+        sel { x, y } in { BYTE, BYTE };
+    "#;
+        let expectation = r#"namespace N(8);
+    col fixed BYTE(i) { (i & 255) };
+    col fixed sel = [1, 0]*;
+    col witness x;
+    col witness y;
+    col fixed logup_first = [1] + [0]*;
+    col witness logup_multiplicity_0;
+    col witness stage(1) logup_acc_0;
+    (N.logup_first * N.logup_acc_0) = 0;
+    (((N.logup_acc_0' - N.logup_acc_0) * (std::prover::challenge(0, 1) - ((N.x * std::prover::challenge(0, 0)) + N.y))) * (std::prover::challenge(0, 1) - ((N.BYTE * std::prover::challenge(0, 0)) + N.BYTE))) = ((N.sel * (std::prover::challenge(0, 1) - ((N.BYTE * std::prover::challenge(0, 0)) + N.BYTE))) - (N.logup_multiplicity_0 * (std::prover::challenge(0, 1) - ((N.x * std::prover::challenge(0, 0)) + N.y))));
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        let rewritten = lookups_to_logup(&mut pil).unwrap();
+        assert_eq!(
+            rewritten,
+            vec![LogUpLookup {
+                identity_id: 0,
+                multiplicity_column: "N.logup_multiplicity_0".to_string()
+            }]
+        );
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn rewrite_permutation() {
+        let input = r#"namespace N(4);
+        col fixed sel = [1, 0]*;
+        col witness x, y;
+        sel { x } is { y };
+    "#;
+        let expectation = r#"namespace N(4);
+    col fixed sel = [1, 0]*;
+    col witness x;
+    col witness y;
+    col fixed logup_first = [1] + [0]*;
+    col witness stage(1) logup_acc_0;
+    (N.logup_first * N.logup_acc_0) = 0;
+    (((N.logup_acc_0' - N.logup_acc_0) * (std::prover::challenge(0, 1) - N.x)) * (std::prover::challenge(0, 1) - N.y)) = ((N.sel * (std::prover::challenge(0, 1) - N.y)) - (1 * (std::prover::challenge(0, 1) - N.x)));
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(lookups_to_logup(&mut pil).unwrap(), vec![]);
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn rewrite_permutation_of_later_stage() {
+        let input = r#"namespace N(4);
+        col fixed sel = [1, 0]*;
+        col witness x;
+        col witness stage(1) y, z;
+        x in sel;
+        sel { y } is { z };
+    "#;
+        let expectation = r#"namespace N(4);
+    col fixed sel = [1, 0]*;
+    col witness x;
+    col witness stage(1) y;
+    col witness stage(1) z;
+    col fixed logup_first = [1] + [0]*;
+    col witness logup_multiplicity_0;
+    col witness stage(1) logup_acc_0;
+    (N.logup_first * N.logup_acc_0) = 0;
+    (((N.logup_acc_0' - N.logup_acc_0) * (std::prover::challenge(0, 1) - N.x)) * (std::prover::challenge(0, 1) - N.sel)) = ((std::prover::challenge(0, 1) - N.sel) - (N.logup_multiplicity_0 * (std::prover::challenge(0, 1) - N.x)));
+    col witness stage(2) logup_acc_1;
+    (N.logup_first * N.logup_acc_1) = 0;
+    (((N.logup_acc_1' - N.logup_acc_1) * (std::prover::challenge(1, 3) - N.y)) * (std::prover::challenge(1, 3) - N.z)) = ((N.sel * (std::prover::challenge(1, 3) - N.z)) - (1 * (std::prover::challenge(1, 3) - N.y)));
+"#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        lookups_to_logup(&mut pil).unwrap();
+        assert_eq!(pil.to_string(), expectation);
+    }
+
+    #[test]
+    fn lookup_of_later_stage() {
+        let input = r#"namespace N(4);
+        col fixed sel = [1, 0]*;
+        col witness stage(1) x;
+        x in sel;
+    "#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(
+            lookups_to_logup(&mut pil),
+            Err(
+                "Cannot rewrite lookup that references columns of stage 1: { N.x } in { N.sel };"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn different_degrees() {
+        let input = r#"namespace N(8);
+        col witness x;
+        x in T.BYTE;
+    namespace T(16);
+        col fixed BYTE(i) { i & 0xff };
+    "#;
+        let mut pil = analyze_string::<GoldilocksField>(input);
+        assert_eq!(
+            lookups_to_logup(&mut pil),
+            Err("Cannot rewrite lookup between columns of different degrees (8 and 16): { N.x } in { T.BYTE };".to_string())
+        );
+    }
+}
@@ -1,6 +1,12 @@
+use std::sync::Arc;
+
 use ::powdr_pipeline::{inputs_to_query_callback, Pipeline};
-use powdr_ast::analyzed::Analyzed;
+use powdr_ast::analyzed::{Analyzed, FunctionValueDefinition};
 use powdr_number::{BigInt, FieldElement, GoldilocksField};
+use powdr_pil_analyzer::{
+    compiled_evaluator,
+    evaluator::{self, Definitions, Value},
+};
 
 use powdr_pipeline::test_util::{evaluate_integer_function, std_analyzed};
 use powdr_riscv::{
@@ -152,5 +158,71 @@ fn evaluator_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, evaluator_benchmark, executor_benchmark);
+/// Compares the compiled evaluator to the interpreter on functions that are
+/// called once per row, as it is done for fixed columns and prover functions.
+fn compiled_evaluator_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compiled-evaluator-benchmark");
+
+    let analyzed: Analyzed<GoldilocksField> = {
+        // airgen needs a main machine.
+        let code = "
+            let clock: int -> fe = |i| if i % 32 == 0 { 1 } else { 0 };
+            let byte_sum: int -> int = |i| match i {
+                0 => 0,
+                _ => (i & 0xff) + byte_sum(i >> 8),
+            };
+            let sqrt: int -> int = |x| sqrt_rec(x, x);
+            let sqrt_rec: int, int -> int = |y, x|
+                if y * y <= x && (y + 1) * (y + 1) > x {
+                    y
+                } else {
+                    sqrt_rec((y + x / y) / 2, x)
+                };
+            let rows: int -> int = |i| sqrt(i * 112655675) + byte_sum(i * 879882356);
+            machine Main { }
+        "
+        .to_string();
+        let mut pipeline = Pipeline::default().from_asm_string(code, None);
+        pipeline.compute_analyzed_pil().unwrap().clone()
+    };
+
+    for name in ["clock", "byte_sum", "rows"] {
+        let Some(FunctionValueDefinition::Expression(function)) = &analyzed.definitions[name].1
+        else {
+            panic!("Expected a function definition for {name}.")
+        };
+        let arguments = |row: u64| vec![Arc::new(Value::<T>::Integer(BigInt::from(row)))];
+
+        group.bench_function(format!("{name}_interpreted"), |b| {
+            b.iter(|| {
+                let mut symbols = Definitions(&analyzed.definitions);
+                let fun = evaluator::evaluate(&function.e, &mut symbols).unwrap();
+                for row in 0..1024 {
+                    evaluator::evaluate_function_call(fun.clone(), arguments(row), &mut symbols)
+                        .unwrap();
+                }
+            })
+        });
+
+        group.bench_function(format!("{name}_compiled"), |b| {
+            b.iter(|| {
+                let mut symbols = Definitions(&analyzed.definitions);
+                let compiled =
+                    compiled_evaluator::try_compile(&function.e, &analyzed.definitions).unwrap();
+                for row in 0..1024 {
+                    compiled.call(arguments(row), &mut symbols).unwrap();
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    evaluator_benchmark,
+    compiled_evaluator_benchmark,
+    executor_benchmark
+);
 criterion_main!(benches);
@@ -89,9 +89,6 @@ pub fn serde_data_to_query_callback<T: FieldElement, S: serde::Serialize + Send
 
 pub fn inputs_to_query_callback<T: FieldElement>(inputs: Vec<T>) -> impl QueryCallback<T> {
     move |query: &str| -> Result<Option<T>, String> {
-        // TODO In the future, when match statements need to be exhaustive,
-        // this function should answer None by Ok(None).
-
         let (id, data) = parse_query(query)?;
         match id {
             "Input" => {
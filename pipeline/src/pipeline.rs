@@ -1,12 +1,12 @@
 use std::{
     borrow::Borrow,
-    fmt::Display,
+    fmt::{Display, Write},
     fs,
     io::{self, BufReader, BufWriter},
     marker::Send,
     path::{Path, PathBuf},
     rc::Rc,
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::Instant,
 };
 
@@ -17,22 +17,31 @@ use powdr_ast::{
     object::PILGraph,
     parsed::{asm::ASMProgram, PILFile},
 };
-use powdr_backend::{BackendType, Proof};
+use powdr_backend::{Backend, BackendType, Proof};
 use powdr_executor::{
+    column_store::ColumnStore,
     constant_evaluator,
+    constraint_checker::ConstraintChecker,
     witgen::{
-        chain_callbacks, unused_query_callback, QueryCallback, WitgenCallback, WitnessGenerator,
+        chain_callbacks, extract_publics, unused_query_callback, QueryCallback, WitgenCallback,
+        WitnessGenerator,
     },
 };
 use powdr_number::{write_polys_csv_file, write_polys_file, CsvRenderMode, FieldElement};
+use powdr_pilopt::logup::LogUpLookup;
 use powdr_schemas::SerializedAnalyzed;
 
 use crate::{
     inputs_to_query_callback, serde_data_to_query_callback,
-    util::{try_read_poly_set, write_or_panic, FixedPolySet, WitnessPolySet},
+    util::{
+        fixed_cols_cache_key, try_read_cached_fixed_cols, try_read_poly_set,
+        write_cached_fixed_cols, write_or_panic, FixedPolySet, WitnessPolySet,
+        FIXED_COLS_CACHE_DIR,
+    },
 };
 
 type Columns<T> = Vec<(String, Vec<T>)>;
+type PilWithFixedCols<T> = (Rc<Analyzed<T>>, Rc<Columns<T>>);
 
 #[derive(Default, Clone)]
 pub struct Artifacts<T: FieldElement> {
@@ -67,8 +76,16 @@ pub struct Artifacts<T: FieldElement> {
     fixed_cols: Option<Rc<Columns<T>>>,
     /// Generated witnesses.
     witness: Option<Rc<Columns<T>>>,
+    /// Generated witnesses, spilled to disk.
+    witness_store: Option<Rc<ColumnStore<T>>>,
+    /// The optimized .pil file with all lookups rewritten into LogUp constraints,
+    /// its fixed columns and the rewritten lookups.
+    logup_pil: Option<(PilWithFixedCols<T>, Vec<LogUpLookup>)>,
     /// The proof (if successful).
     proof: Option<Proof>,
+    /// The verifier program of the backend, used for proof aggregation, and the pipeline
+    /// that compiles it, up to its fixed columns.
+    verifier_pipeline: Option<(String, Box<Pipeline<T>>)>,
 }
 
 /// Helper trait to make it prettier to get an `Option<&mut dyn io::Read>`` from
@@ -83,6 +100,18 @@ impl<R: io::Read> AsIoRead for Option<R> {
     }
 }
 
+/// Where fixed columns are cached, see [Pipeline::with_fixed_cols_cache].
+#[derive(Default, Clone)]
+enum FixedColsCache {
+    /// In [FIXED_COLS_CACHE_DIR] in the output directory, if there is one.
+    #[default]
+    OutputDir,
+    /// In the given directory.
+    Dir(PathBuf),
+    /// Fixed columns are not cached.
+    Disabled,
+}
+
 /// Optional Arguments for various stages of the pipeline.
 #[derive(Default, Clone)]
 struct Arguments<T: FieldElement> {
@@ -96,12 +125,24 @@ struct Arguments<T: FieldElement> {
     csv_render_mode: CsvRenderMode,
     /// Whether to export the witness as a CSV file.
     export_witness_csv: bool,
+    /// Whether to report witness generation failures in diagnostic mode.
+    witgen_diagnostics: bool,
+    /// Whether to rewrite lookups into LogUp constraints before proving.
+    logup: bool,
+    /// If set, the degree of all polynomial identities is reduced to at most this bound.
+    max_constraint_degree: Option<usize>,
+    /// Whether to extract common sub-expressions of identities into intermediate columns.
+    common_subexpression_elimination: bool,
+    /// If set, the witness is spilled to disk in chunks of this number of rows.
+    witness_chunk_size: Option<usize>,
     /// The optional setup file to use for proving.
     setup_file: Option<PathBuf>,
     /// The optional verification key file to use for proving.
     vkey_file: Option<PathBuf>,
     /// The optional existing proof file to use for aggregation.
     existing_proof_file: Option<PathBuf>,
+    /// Where fixed columns are cached.
+    fixed_cols_cache: FixedColsCache,
 }
 
 #[derive(Clone)]
@@ -228,6 +269,63 @@ impl<T: FieldElement> Pipeline<T> {
         self
     }
 
+    /// Enables the diagnostic mode of witness generation, which reports the identities
+    /// that could not be solved and their unknown cells if witness generation fails.
+    pub fn with_witgen_diagnostics(mut self, witgen_diagnostics: bool) -> Self {
+        self.arguments.witgen_diagnostics = witgen_diagnostics;
+        self
+    }
+
+    /// Rewrites all lookups into LogUp constraints of a second stage before proving,
+    /// so that they can be proven by backends that only support polynomial identities.
+    pub fn with_logup(mut self, logup: bool) -> Self {
+        self.arguments.logup = logup;
+        self
+    }
+
+    /// Reduces the degree of all polynomial identities to at most `max_degree` by introducing
+    /// intermediate witness columns, for backends that only support low-degree constraints.
+    /// Computing the optimized PIL fails if an identity cannot be reduced. Lookups and
+    /// permutations are not reduced.
+    pub fn with_max_constraint_degree(mut self, max_degree: Option<usize>) -> Self {
+        self.arguments.max_constraint_degree = max_degree;
+        self
+    }
+
+    /// Extracts sub-expressions that occur in several identities into intermediate columns,
+    /// so that they are only evaluated once per row.
+    pub fn with_common_subexpression_elimination(
+        mut self,
+        common_subexpression_elimination: bool,
+    ) -> Self {
+        self.arguments.common_subexpression_elimination = common_subexpression_elimination;
+        self
+    }
+
+    /// Generates the witness for proving in chunks of `chunk_size` rows that are written
+    /// to the output directory as soon as they are finalized, instead of keeping the whole
+    /// trace in memory. Requires an output directory.
+    /// Cannot be combined with LogUp or with PIL files that have later stages.
+    /// The eSTARK and FRI STARK backends read the spilled columns one at a time, the other
+    /// backends read the whole witness back into memory.
+    pub fn with_witness_spilling(mut self, chunk_size: Option<usize>) -> Self {
+        self.arguments.witness_chunk_size = chunk_size;
+        self
+    }
+
+    /// Caches the fixed columns in the given directory, keyed by a hash of the optimized
+    /// PIL file (including its degree) and the field. If the directory already contains
+    /// fixed columns for the same PIL file, they are read instead of being re-evaluated.
+    /// By default, they are cached in [FIXED_COLS_CACHE_DIR] in the output directory,
+    /// if there is one. If `None`, fixed columns are not cached.
+    pub fn with_fixed_cols_cache(mut self, directory: Option<PathBuf>) -> Self {
+        self.arguments.fixed_cols_cache = match directory {
+            Some(directory) => FixedColsCache::Dir(directory),
+            None => FixedColsCache::Disabled,
+        };
+        self
+    }
+
     pub fn add_query_callback(mut self, query_callback: Arc<dyn QueryCallback<T>>) -> Self {
         let query_callback = match self.arguments.query_callback {
             Some(old_callback) => Arc::new(chain_callbacks(old_callback, query_callback)),
@@ -517,8 +615,8 @@ impl<T: FieldElement> Pipeline<T> {
         Ok(())
     }
 
-    fn maybe_write_proof(&self, proof: &Proof) -> Result<(), Vec<String>> {
-        let fname = if self.arguments.existing_proof_file.is_some() {
+    fn maybe_write_proof(&self, proof: &Proof, aggregated: bool) -> Result<(), Vec<String>> {
+        let fname = if aggregated {
             "proof_aggr.bin"
         } else {
             "proof.bin"
@@ -530,6 +628,25 @@ impl<T: FieldElement> Pipeline<T> {
         Ok(())
     }
 
+    /// Writes the public values next to the proof, one `name,value` line per
+    /// public declaration, in source order.
+    fn maybe_write_publics(&self, publics: &[(String, T)]) -> Result<(), Vec<String>> {
+        if publics.is_empty() {
+            return Ok(());
+        }
+        if let Some(path) = self.path_if_should_write(|name| format!("{name}_publics.csv"))? {
+            let contents = publics
+                .iter()
+                .fold(String::new(), |mut contents, (name, value)| {
+                    writeln!(contents, "{name},{}", value.to_integer()).unwrap();
+                    contents
+                });
+            fs::write(path, contents).unwrap();
+        }
+
+        Ok(())
+    }
+
     // ===== Compute and retrieve artifacts =====
 
     pub fn asm_file_path(&self) -> Result<&PathBuf, Vec<String>> {
@@ -768,7 +885,18 @@ impl<T: FieldElement> Pipeline<T> {
         let analyzed_pil = self.artifact.analyzed_pil.take().unwrap();
 
         self.log("Optimizing pil...");
-        let optimized = powdr_pilopt::optimize(analyzed_pil);
+        let mut optimized = powdr_pilopt::optimize(analyzed_pil);
+        if let Some(max_degree) = self.arguments.max_constraint_degree {
+            self.log(&format!("Reducing constraint degree to {max_degree}..."));
+            powdr_pilopt::degree_reduction::reduce_degree(&mut optimized, max_degree)
+                .map_err(|e| vec![e])?;
+        }
+        if self.arguments.common_subexpression_elimination {
+            // This has to happen after the degree reduction, which does not look into
+            // intermediate columns.
+            self.log("Extracting common sub-expressions...");
+            powdr_pilopt::common_subexpressions::extract_common_subexpressions(&mut optimized);
+        }
         self.maybe_write_pil(&optimized, "_opt")?;
         self.maybe_write_pil_object(&optimized, "_opt")?;
 
@@ -791,7 +919,8 @@ impl<T: FieldElement> Pipeline<T> {
         let pil = self.compute_optimized_pil()?;
 
         let start = Instant::now();
-        let fixed_cols = constant_evaluator::generate(&pil);
+        let fixed_cols =
+            self.read_cached_or_generate_fixed_cols(&pil, || constant_evaluator::generate(&pil))?;
         self.maybe_write_constants(&fixed_cols)?;
         self.log(&format!("Took {}", start.elapsed().as_secs_f32()));
 
@@ -800,6 +929,30 @@ impl<T: FieldElement> Pipeline<T> {
         Ok(self.artifact.fixed_cols.as_ref().unwrap().clone())
     }
 
+    /// Reads the fixed columns of `pil` from the fixed column cache, if enabled,
+    /// or generates them and writes them to the cache.
+    fn read_cached_or_generate_fixed_cols(
+        &self,
+        pil: &Analyzed<T>,
+        generate: impl FnOnce() -> Columns<T>,
+    ) -> Result<Columns<T>, Vec<String>> {
+        let cache_key = self
+            .fixed_cols_cache_dir()
+            .map(|dir| (dir, fixed_cols_cache_key(pil)));
+        let cached = cache_key
+            .as_ref()
+            .and_then(|(dir, key)| try_read_cached_fixed_cols(pil, dir, key));
+        if let Some(fixed_cols) = cached {
+            self.log("Read fixed columns from the cache.");
+            return Ok(fixed_cols);
+        }
+        let fixed_cols = generate();
+        if let Some((dir, key)) = &cache_key {
+            write_cached_fixed_cols(dir, key, &fixed_cols).map_err(|e| vec![e])?;
+        }
+        Ok(fixed_cols)
+    }
+
     pub fn fixed_cols(&self) -> Result<Rc<Columns<T>>, Vec<String>> {
         Ok(self.artifact.fixed_cols.as_ref().unwrap().clone())
     }
@@ -818,13 +971,15 @@ impl<T: FieldElement> Pipeline<T> {
 
         let start = Instant::now();
         let external_witness_values = std::mem::take(&mut self.arguments.external_witness_values);
+        // The query callback is kept for the witness generation of later stages.
         let query_callback = self
             .arguments
             .query_callback
-            .take()
+            .clone()
             .unwrap_or_else(|| Arc::new(unused_query_callback()));
         let witness = WitnessGenerator::new(&pil, &fixed_cols, query_callback.borrow())
             .with_external_witness_values(&external_witness_values)
+            .with_diagnostics(self.arguments.witgen_diagnostics)
             .generate();
 
         self.log(&format!("Took {}", start.elapsed().as_secs_f32()));
@@ -840,7 +995,132 @@ impl<T: FieldElement> Pipeline<T> {
         Ok(self.artifact.witness.as_ref().unwrap().clone())
     }
 
+    /// Generates the witness into a [ColumnStore] in the `{name}_witness` subdirectory of
+    /// the output directory, spilling the rows of the main machine and of the secondary
+    /// machines in chunks of the size set by [Pipeline::with_witness_spilling].
+    pub fn compute_witness_store(&mut self) -> Result<Rc<ColumnStore<T>>, Vec<String>> {
+        if let Some(ref store) = self.artifact.witness_store {
+            return Ok(store.clone());
+        }
+
+        let chunk_size = self
+            .arguments
+            .witness_chunk_size
+            .ok_or_else(|| vec!["Witness spilling is not enabled.".to_string()])?;
+        // The multiplicities of LogUp and the later stages are computed from the witness
+        // in memory.
+        if self.arguments.logup {
+            return Err(vec![
+                "Witness spilling cannot be combined with LogUp.".to_string()
+            ]);
+        }
+        let dir = self
+            .output_dir()
+            .ok_or_else(|| vec!["Witness spilling requires an output directory.".to_string()])?
+            .join(format!("{}_witness", self.name()));
+
+        self.log(&format!(
+            "Deducing witness columns, writing chunks of {chunk_size} rows to {}...",
+            dir.display()
+        ));
+
+        let pil = self.compute_optimized_pil()?;
+        if pil
+            .committed_polys_in_source_order()
+            .iter()
+            .any(|(symbol, _)| symbol.stage.unwrap_or_default() > 0)
+        {
+            return Err(vec![
+                "Witness spilling cannot be combined with witness generation for later stages."
+                    .to_string(),
+            ]);
+        }
+        let fixed_cols = self.compute_fixed_cols()?;
+
+        let start = Instant::now();
+        let store = Mutex::new(ColumnStore::new(&dir).map_err(|e| vec![e.to_string()])?);
+        let external_witness_values = std::mem::take(&mut self.arguments.external_witness_values);
+        let query_callback = self
+            .arguments
+            .query_callback
+            .take()
+            .unwrap_or_else(|| Arc::new(unused_query_callback()));
+        WitnessGenerator::new(&pil, &fixed_cols, query_callback.borrow())
+            .with_external_witness_values(&external_witness_values)
+            .with_diagnostics(self.arguments.witgen_diagnostics)
+            .generate_into(&store, chunk_size);
+
+        self.log(&format!("Took {}", start.elapsed().as_secs_f32()));
+
+        self.artifact.witness_store = Some(Rc::new(store.into_inner().unwrap()));
+
+        Ok(self.artifact.witness_store.as_ref().unwrap().clone())
+    }
+
+    /// @returns the PIL file and fixed columns used for proving: The optimized PIL file or,
+    /// if enabled, the optimized PIL file with all lookups rewritten into LogUp constraints.
+    pub fn compute_backend_pil(&mut self) -> Result<PilWithFixedCols<T>, Vec<String>> {
+        let pil = self.compute_optimized_pil()?;
+        let fixed_cols = self.compute_fixed_cols()?;
+        if !self.arguments.logup {
+            return Ok((pil, fixed_cols));
+        }
+
+        if self.artifact.logup_pil.is_none() {
+            self.log("Rewriting lookups into LogUp constraints...");
+            let mut logup_pil = (*pil).clone();
+            let lookups =
+                powdr_pilopt::logup::lookups_to_logup(&mut logup_pil).map_err(|e| vec![e])?;
+            self.maybe_write_pil(&logup_pil, "_logup")?;
+            // The rewrite only adds fixed columns, so only those have to be evaluated.
+            let logup_fixed_cols = self.read_cached_or_generate_fixed_cols(&logup_pil, || {
+                constant_evaluator::generate_with_known(&logup_pil, &fixed_cols)
+            })?;
+            self.artifact.logup_pil =
+                Some(((Rc::new(logup_pil), Rc::new(logup_fixed_cols)), lookups));
+        }
+        Ok(self.artifact.logup_pil.as_ref().unwrap().0.clone())
+    }
+
+    /// @returns the witness used for proving: The generated witness, extended by the
+    /// multiplicity columns of the rewritten lookups if LogUp is enabled.
+    pub fn compute_backend_witness(&mut self) -> Result<Rc<Columns<T>>, Vec<String>> {
+        let witness = self.compute_witness()?;
+        self.compute_backend_pil()?;
+        let Some((_, lookups)) = &self.artifact.logup_pil else {
+            return Ok(witness);
+        };
+
+        let pil = self.optimized_pil()?;
+        let fixed_cols = self.fixed_cols()?;
+        let checker = ConstraintChecker::new(&pil, &fixed_cols, &witness);
+        let multiplicities = lookups
+            .iter()
+            .map(|lookup| {
+                Ok((
+                    lookup.multiplicity_column.clone(),
+                    checker.lookup_multiplicities(lookup.identity_id)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| vec![e])?;
+        Ok(Rc::new(
+            witness.iter().cloned().chain(multiplicities).collect(),
+        ))
+    }
+
     pub fn witgen_callback(&mut self) -> Result<WitgenCallback<T>, Vec<String>> {
+        let (pil, fixed_cols) = self.compute_backend_pil()?;
+        Ok(WitgenCallback::new(
+            pil,
+            fixed_cols,
+            self.arguments.query_callback.as_ref().cloned(),
+        ))
+    }
+
+    /// @returns a callback that computes the witness columns of the later stages of the
+    /// optimized PIL, i.e. without the columns added by the LogUp rewrite.
+    pub fn optimized_witgen_callback(&mut self) -> Result<WitgenCallback<T>, Vec<String>> {
         Ok(WitgenCallback::new(
             self.compute_optimized_pil()?,
             self.compute_fixed_cols()?,
@@ -853,9 +1133,13 @@ impl<T: FieldElement> Pipeline<T> {
             return Ok(self.artifact.proof.as_ref().unwrap());
         }
 
-        let pil = self.compute_optimized_pil()?;
-        let fixed_cols = self.compute_fixed_cols()?;
-        let witness = self.compute_witness()?;
+        let (pil, fixed_cols) = self.compute_backend_pil()?;
+        // With witness spilling, the backend reads the witness from the column store.
+        let (witness, witness_store) = if self.arguments.witness_chunk_size.is_some() {
+            (None, Some(self.compute_witness_store()?))
+        } else {
+            (Some(self.compute_backend_witness()?), None)
+        };
         let witgen_callback = self.witgen_callback()?;
 
         let backend = self
@@ -878,6 +1162,26 @@ impl<T: FieldElement> Pipeline<T> {
             .as_ref()
             .map(|path| BufReader::new(fs::File::open(path).unwrap()));
 
+        // Reads the existing proof file, if set.
+        let existing_proof = self
+            .arguments
+            .existing_proof_file
+            .as_ref()
+            .map(|path| fs::read(path).unwrap());
+
+        let publics = match &witness_store {
+            // Only the rows of the public values are read from the store.
+            Some(store) => pil
+                .public_declarations_in_source_order()
+                .into_iter()
+                .map(|(name, public_declaration)| {
+                    let column = store.column_reader(&public_declaration.referenced_poly_name());
+                    (name.clone(), column.get(public_declaration.index as usize))
+                })
+                .collect(),
+            None => extract_publics(witness.as_ref().unwrap(), &pil),
+        };
+
         /* Create the backend */
         let backend = factory
             .create(
@@ -887,16 +1191,32 @@ impl<T: FieldElement> Pipeline<T> {
                 setup.as_io_read(),
                 vkey.as_io_read(),
             )
-            .unwrap();
-
-        // Reads the existing proof file, if set.
-        let existing_proof = self
-            .arguments
-            .existing_proof_file
-            .as_ref()
-            .map(|path| fs::read(path).unwrap());
+            .map_err(|e| vec![e.to_string()])?;
+
+        // Backends with a verifier program aggregate the existing proof by proving the
+        // program, the others aggregate it in `Backend::prove`.
+        if let Some(existing_proof) = &existing_proof {
+            let values = publics.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+            let proofs = [(existing_proof.clone(), values.clone())];
+            if let Some((program, inputs)) =
+                verifier_program(backend.as_ref(), &[values], Some(&proofs))?
+            {
+                drop(backend);
+                let proof = self.prove_verifier_program(program, inputs)?;
+                self.maybe_write_proof(&proof, true)?;
+                self.maybe_write_publics(&publics)?;
+
+                self.artifact.proof = Some(proof);
+
+                return Ok(self.artifact.proof.as_ref().unwrap());
+            }
+        }
 
-        let proof = match backend.prove(&witness, existing_proof, witgen_callback) {
+        let result = match witness_store {
+            Some(store) => backend.prove_from_store(&store, existing_proof, witgen_callback),
+            None => backend.prove(&witness.unwrap(), existing_proof, witgen_callback),
+        };
+        let proof = match result {
             Ok(proof) => proof,
             Err(powdr_backend::Error::BackendError(e)) => {
                 return Err(vec![e.to_string()]);
@@ -906,13 +1226,129 @@ impl<T: FieldElement> Pipeline<T> {
 
         drop(backend);
 
-        self.maybe_write_proof(&proof)?;
+        self.maybe_write_proof(&proof, self.arguments.existing_proof_file.is_some())?;
+        self.maybe_write_publics(&publics)?;
+
+        self.artifact.proof = Some(proof);
+
+        Ok(self.artifact.proof.as_ref().unwrap())
+    }
+
+    /// Aggregates the given proofs of the PIL file of this pipeline, each together with its
+    /// public values, by proving the verifier program of the backend for them,
+    /// see [powdr_backend::Backend::verifier_program].
+    pub fn compute_aggregated_proof(
+        &mut self,
+        proofs: &[(Proof, Vec<T>)],
+    ) -> Result<&Proof, Vec<String>> {
+        if self.artifact.proof.is_some() {
+            return Ok(self.artifact.proof.as_ref().unwrap());
+        }
+
+        self.log("Proving the verifier program...");
+        let proof = self.aggregation_pipeline(proofs)?.compute_proof()?.clone();
+        self.maybe_write_proof(&proof, true)?;
 
         self.artifact.proof = Some(proof);
 
         Ok(self.artifact.proof.as_ref().unwrap())
     }
 
+    /// @returns the pipeline of the verifier program of the backend for the given proofs of
+    /// the PIL file of this pipeline, each together with its public values, with the prover
+    /// inputs of the program set. Its proof is the aggregated proof of
+    /// [Pipeline::compute_aggregated_proof].
+    pub fn aggregation_pipeline(
+        &mut self,
+        proofs: &[(Proof, Vec<T>)],
+    ) -> Result<Pipeline<T>, Vec<String>> {
+        let publics = proofs
+            .iter()
+            .map(|(_, publics)| publics.clone())
+            .collect::<Vec<_>>();
+        let (program, inputs) = self
+            .compute_verifier_program(&publics, Some(proofs))?
+            .ok_or_else(|| {
+                vec!["The backend does not provide a verifier program for aggregation.".to_string()]
+            })?;
+        Ok(self.verifier_pipeline(program)?.with_prover_inputs(inputs))
+    }
+
+    /// Creates the backend for the PIL file of this pipeline and generates its verifier
+    /// program, see [verifier_program].
+    fn compute_verifier_program(
+        &mut self,
+        publics: &[Vec<T>],
+        proofs: Option<&[(Proof, Vec<T>)]>,
+    ) -> Result<Option<(String, Vec<T>)>, Vec<String>> {
+        let backend = self
+            .arguments
+            .backend
+            .expect("backend must be set before aggregating proofs!");
+        let factory = backend.factory::<T>();
+
+        let mut setup = self
+            .arguments
+            .setup_file
+            .as_ref()
+            .map(|path| BufReader::new(fs::File::open(path).unwrap()));
+        let mut vkey = self
+            .arguments
+            .vkey_file
+            .as_ref()
+            .map(|path| BufReader::new(fs::File::open(path).unwrap()));
+
+        let (pil, fixed_cols) = self.compute_backend_pil()?;
+
+        let backend = factory
+            .create(
+                pil.borrow(),
+                &fixed_cols[..],
+                self.output_dir(),
+                setup.as_io_read(),
+                vkey.as_io_read(),
+            )
+            .map_err(|e| vec![e.to_string()])?;
+
+        verifier_program(backend.as_ref(), publics, proofs)
+    }
+
+    /// Proves the verifier program with the given prover inputs.
+    fn prove_verifier_program(
+        &mut self,
+        program: String,
+        inputs: Vec<T>,
+    ) -> Result<Proof, Vec<String>> {
+        self.log("Proving the verifier program...");
+        let mut pipeline = self.verifier_pipeline(program)?.with_prover_inputs(inputs);
+        Ok(pipeline.compute_proof()?.clone())
+    }
+
+    /// @returns a pipeline for the given verifier program, with the program compiled and its
+    /// fixed columns evaluated. Since the program only depends on the verification key and
+    /// the public values, the pipeline of the last program is kept and reused.
+    fn verifier_pipeline(&mut self, program: String) -> Result<Pipeline<T>, Vec<String>> {
+        if let Some((cached_program, pipeline)) = &self.artifact.verifier_pipeline {
+            if *cached_program == program {
+                return Ok((**pipeline).clone());
+            }
+        }
+
+        let mut pipeline = Pipeline::default()
+            .with_name(format!("{}_verifier", self.name()))
+            .from_asm_string(program.clone(), None)
+            .with_logup(self.arguments.logup)
+            .with_max_constraint_degree(self.arguments.max_constraint_degree)
+            .with_common_subexpression_elimination(self.arguments.common_subexpression_elimination)
+            .with_fixed_cols_cache(self.fixed_cols_cache_dir())
+            .with_backend(self.arguments.backend.unwrap());
+        pipeline.log_level = self.log_level;
+        pipeline.compute_backend_pil()?;
+
+        self.artifact.verifier_pipeline = Some((program, Box::new(pipeline.clone())));
+        Ok(pipeline)
+    }
+
     pub fn proof(&self) -> Result<&Proof, Vec<String>> {
         Ok(self.artifact.proof.as_ref().unwrap())
     }
@@ -921,6 +1357,18 @@ impl<T: FieldElement> Pipeline<T> {
         self.output_dir.as_ref().map(|p| p.as_ref())
     }
 
+    /// @returns the directory in which fixed columns are cached, if any.
+    pub fn fixed_cols_cache_dir(&self) -> Option<PathBuf> {
+        match &self.arguments.fixed_cols_cache {
+            FixedColsCache::OutputDir => self
+                .output_dir
+                .as_ref()
+                .map(|dir| dir.join(FIXED_COLS_CACHE_DIR)),
+            FixedColsCache::Dir(dir) => Some(dir.clone()),
+            FixedColsCache::Disabled => None,
+        }
+    }
+
     pub fn name(&self) -> &str {
         self.name.as_ref().unwrap()
     }
@@ -945,8 +1393,7 @@ impl<T: FieldElement> Pipeline<T> {
             .as_ref()
             .map(|path| BufReader::new(fs::File::open(path).unwrap()));
 
-        let pil = self.compute_optimized_pil()?;
-        let fixed_cols = self.compute_fixed_cols()?;
+        let (pil, fixed_cols) = self.compute_backend_pil()?;
 
         let backend = factory
             .create(
@@ -968,6 +1415,39 @@ impl<T: FieldElement> Pipeline<T> {
     }
 
     pub fn verify(&mut self, proof: &[u8], instances: &[Vec<T>]) -> Result<(), Vec<String>> {
+        let mut vkey_file = if let Some(ref path) = self.arguments.vkey_file {
+            BufReader::new(fs::File::open(path).unwrap())
+        } else {
+            panic!("Verification key should have been provided for verification")
+        };
+
+        self.verify_with_vkey(proof, instances, Some(&mut vkey_file))
+    }
+
+    /// Verifies a proof created by [Pipeline::compute_aggregated_proof] or by
+    /// [Pipeline::compute_proof] with an existing proof file, given the public values
+    /// of the aggregated proofs.
+    pub fn verify_aggregated(
+        &mut self,
+        proof: &[u8],
+        publics: &[Vec<T>],
+    ) -> Result<(), Vec<String>> {
+        let Some((program, _)) = self.compute_verifier_program(publics, None)? else {
+            // The backend verifies the proofs it aggregated itself.
+            return self.verify(proof, publics);
+        };
+
+        // The verification key of the program is derived from the program itself.
+        self.verifier_pipeline(program)?
+            .verify_with_vkey(proof, &[], None)
+    }
+
+    fn verify_with_vkey(
+        &mut self,
+        proof: &[u8],
+        instances: &[Vec<T>],
+        vkey: Option<&mut dyn io::Read>,
+    ) -> Result<(), Vec<String>> {
         let backend = self
             .arguments
             .backend
@@ -980,14 +1460,7 @@ impl<T: FieldElement> Pipeline<T> {
             .as_ref()
             .map(|path| BufReader::new(fs::File::open(path).unwrap()));
 
-        let mut vkey_file = if let Some(ref path) = self.arguments.vkey_file {
-            BufReader::new(fs::File::open(path).unwrap())
-        } else {
-            panic!("Verification key should have been provided for verification")
-        };
-
-        let pil = self.compute_optimized_pil()?;
-        let fixed_cols = self.compute_fixed_cols()?;
+        let (pil, fixed_cols) = self.compute_backend_pil()?;
 
         let backend = factory
             .create(
@@ -997,7 +1470,7 @@ impl<T: FieldElement> Pipeline<T> {
                 setup_file
                     .as_mut()
                     .map(|file| file as &mut dyn std::io::Read),
-                Some(&mut vkey_file),
+                vkey,
             )
             .unwrap();
 
@@ -1008,3 +1481,26 @@ impl<T: FieldElement> Pipeline<T> {
         }
     }
 }
+
+/// Generates the verifier program of the backend for proofs with the given public values
+/// and, if the proofs are given, verifies them and generates the prover inputs of the
+/// program. Returns None if the backend does not provide a verifier program.
+fn verifier_program<T: FieldElement>(
+    backend: &dyn Backend<'_, T>,
+    publics: &[Vec<T>],
+    proofs: Option<&[(Proof, Vec<T>)]>,
+) -> Result<Option<(String, Vec<T>)>, Vec<String>> {
+    let result = backend.verifier_program(publics).and_then(|program| {
+        let inputs = match proofs {
+            Some(proofs) => backend.verifier_inputs(proofs)?,
+            None => vec![],
+        };
+        Ok((program, inputs))
+    });
+    match result {
+        Ok(program) => Ok(Some(program)),
+        Err(powdr_backend::Error::NoAggregationAvailable) => Ok(None),
+        Err(powdr_backend::Error::BackendError(e)) => Err(vec![e]),
+        Err(e) => Err(vec![e.to_string()]),
+    }
+}
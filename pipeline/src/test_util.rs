@@ -1,5 +1,4 @@
 use powdr_ast::analyzed::Analyzed;
-use powdr_backend::BackendType;
 use powdr_executor::witgen::extract_publics;
 use powdr_number::{BigInt, Bn254Field, FieldElement, GoldilocksField};
 use powdr_pil_analyzer::evaluator::{self, SymbolLookup};
@@ -50,17 +49,10 @@ pub fn verify_asm_string<S: serde::Serialize + Send + Sync + 'static>(
     verify_pipeline(pipeline).unwrap();
 }
 
-pub fn verify_pipeline(pipeline: Pipeline<GoldilocksField>) -> Result<(), String> {
-    let mut pipeline = pipeline.with_backend(BackendType::PilStarkCli);
-
-    let tmp_dir = mktemp::Temp::new_dir().unwrap();
-    if pipeline.output_dir().is_none() {
-        pipeline = pipeline.with_tmp_output(&tmp_dir);
-    }
-
-    pipeline.compute_proof().unwrap();
-
-    verify(pipeline.output_dir().unwrap(), pipeline.name(), None)
+/// Computes the witness and checks it against all constraints using the native
+/// constraint checker.
+pub fn verify_pipeline(mut pipeline: Pipeline<GoldilocksField>) -> Result<(), String> {
+    verify(&mut pipeline)
 }
 
 pub fn gen_estark_proof(file_name: &str, inputs: Vec<GoldilocksField>) {
@@ -97,6 +89,83 @@ pub fn gen_estark_proof(file_name: &str, inputs: Vec<GoldilocksField>) {
     pipeline.verify(&proof, &[publics]).unwrap();
 }
 
+pub fn gen_fri_stark_proof(file_name: &str, inputs: Vec<GoldilocksField>) {
+    gen_fri_stark_proof_with_pipeline(Pipeline::default(), file_name, inputs)
+}
+
+/// Like [gen_fri_stark_proof], but rewrites all lookups into LogUp constraints first.
+pub fn gen_fri_stark_proof_with_logup(file_name: &str, inputs: Vec<GoldilocksField>) {
+    gen_fri_stark_proof_with_pipeline(Pipeline::default().with_logup(true), file_name, inputs)
+}
+
+fn gen_fri_stark_proof_with_pipeline(
+    pipeline: Pipeline<GoldilocksField>,
+    file_name: &str,
+    inputs: Vec<GoldilocksField>,
+) {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = pipeline
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file(file_name))
+        .with_prover_inputs(inputs)
+        .with_backend(powdr_backend::BackendType::FriStark);
+
+    let proof: Vec<u8> = pipeline.compute_proof().unwrap().clone();
+    let pil = pipeline.compute_optimized_pil().unwrap();
+
+    // Verify the proof with an exported verification key
+    let vkey_file_path = tmp_dir.as_path().join("verification_key.bin");
+    let vkey_file = BufWriter::new(File::create(&vkey_file_path).unwrap());
+    write_or_panic(vkey_file, |writer| {
+        pipeline.export_verification_key(writer).unwrap()
+    });
+    let mut pipeline = pipeline.with_vkey_file(Some(vkey_file_path));
+
+    let publics: Vec<GoldilocksField> = extract_publics(&pipeline.witness().unwrap(), &pil)
+        .iter()
+        .map(|(_name, v)| *v)
+        .collect();
+
+    pipeline.verify(&proof, &[publics]).unwrap();
+}
+
+/// Proves the given file with the FRI STARK and checks the witness of the program
+/// verifying that proof against its constraints.
+pub fn test_fri_stark_aggregation(file_name: &str, inputs: Vec<GoldilocksField>) {
+    use std::env;
+
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file(file_name))
+        .with_prover_inputs(inputs)
+        .with_backend(powdr_backend::BackendType::FriStark);
+
+    // Prove on a clone, so that the pipeline can still compute the aggregated proof.
+    let proof: Vec<u8> = pipeline.clone().compute_proof().unwrap().clone();
+    let pil = pipeline.compute_optimized_pil().unwrap();
+    let publics: Vec<GoldilocksField> = extract_publics(&pipeline.compute_witness().unwrap(), &pil)
+        .iter()
+        .map(|(_name, v)| *v)
+        .collect();
+    let proofs = [(proof, publics.clone())];
+
+    // Checking the witness of the verifier program is much faster than proving it.
+    verify_pipeline(pipeline.aggregation_pipeline(&proofs).unwrap()).unwrap();
+
+    // Proving the verifier program needs a lot of memory and time.
+    // Therefore, we only do it in the nightly tests.
+    let is_nightly_test = env::var("IS_NIGHTLY_TEST")
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    if is_nightly_test {
+        let aggregated_proof = pipeline.compute_aggregated_proof(&proofs).unwrap().clone();
+        pipeline
+            .verify_aggregated(&aggregated_proof, &[publics])
+            .unwrap();
+    }
+}
+
 #[cfg(feature = "halo2")]
 pub fn test_halo2(file_name: &str, inputs: Vec<Bn254Field>) {
     use std::env;
@@ -143,7 +212,7 @@ pub fn gen_halo2_proof(file_name: &str, inputs: Vec<Bn254Field>) {
     write_or_panic(setup_file, |writer| {
         powdr_backend::BackendType::Halo2
             .factory::<Bn254Field>()
-            .generate_setup(pil.degree(), writer)
+            .generate_setup(pil.unique_degree().unwrap(), writer)
             .unwrap()
     });
     let mut pipeline = pipeline.with_setup_file(Some(setup_file_path));
@@ -218,17 +287,15 @@ fn convert_witness<T: FieldElement>(witness: &[(String, Vec<u64>)]) -> Vec<(Stri
         .collect()
 }
 
-pub fn assert_proofs_fail_for_invalid_witnesses_pilcom(
+pub fn assert_proofs_fail_for_invalid_witnesses_constraint_checker(
     file_name: &str,
     witness: &[(String, Vec<u64>)],
 ) {
-    let tmp_dir = mktemp::Temp::new_dir().unwrap();
     let pipeline = Pipeline::<GoldilocksField>::default()
-        .with_tmp_output(&tmp_dir)
         .from_file(resolve_test_file(file_name))
         .set_witness(convert_witness(witness));
 
-    assert!(verify_pipeline(pipeline.clone()).is_err());
+    assert!(verify_pipeline(pipeline).is_err());
 }
 
 pub fn assert_proofs_fail_for_invalid_witnesses_estark(
@@ -276,7 +343,7 @@ pub fn assert_proofs_fail_for_invalid_witnesses_bn254(
 }
 
 pub fn assert_proofs_fail_for_invalid_witnesses(file_name: &str, witness: &[(String, Vec<u64>)]) {
-    assert_proofs_fail_for_invalid_witnesses_pilcom(file_name, witness);
+    assert_proofs_fail_for_invalid_witnesses_constraint_checker(file_name, witness);
     assert_proofs_fail_for_invalid_witnesses_estark(file_name, witness);
     #[cfg(feature = "halo2")]
     assert_proofs_fail_for_invalid_witnesses_halo2(file_name, witness);
@@ -1,8 +1,10 @@
 use powdr_ast::analyzed::{Analyzed, FunctionValueDefinition, Symbol};
-use powdr_number::{read_polys_file, DegreeType, FieldElement};
+use powdr_number::{read_polys_file, write_polys_file, DegreeType, FieldElement, LargeInt};
+use sha3::{Digest, Sha3_256};
 use std::{
-    fs::File,
-    io::{self, BufReader},
+    fmt::Write,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
     path::Path,
 };
 
@@ -41,21 +43,86 @@ pub fn try_read_poly_set<P: PolySet, T: FieldElement>(
     dir: &Path,
     name: &str,
 ) -> Option<(Vec<(String, Vec<T>)>, DegreeType)> {
-    let column_names: Vec<String> = P::get_polys(pil)
+    let columns: Vec<(String, Option<DegreeType>)> = P::get_polys(pil)
         .iter()
-        .flat_map(|(poly, _)| poly.array_elements())
-        .map(|(name, _id)| name)
+        .flat_map(|(poly, _)| poly.array_elements().map(|(name, _id)| (name, poly.degree)))
         .collect();
 
-    (!column_names.is_empty()).then(|| {
+    (!columns.is_empty()).then(|| {
         let fname = format!("{name}_{}", P::FILE_NAME);
         read_polys_file(
             &mut BufReader::new(File::open(dir.join(fname)).unwrap()),
-            &column_names,
+            &columns,
         )
     })
 }
 
+/// The directory in the output directory in which fixed columns are cached by default.
+pub const FIXED_COLS_CACHE_DIR: &str = "fixed_cols_cache";
+
+/// Returns the key under which the fixed columns of `pil` are cached: A hash of the field,
+/// the degree and the PIL file, which contains the definitions of all fixed columns.
+pub fn fixed_cols_cache_key<T: FieldElement>(pil: &Analyzed<T>) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(T::modulus().to_arbitrary_integer().to_string());
+    for degree in pil.degrees() {
+        hasher.update(degree.to_le_bytes());
+    }
+    hasher.update(pil.to_string());
+    hasher.finalize().iter().fold(String::new(), |mut key, b| {
+        write!(key, "{b:02x}").unwrap();
+        key
+    })
+}
+
+/// Reads the fixed columns cached under `key` in `dir`, if they exist.
+pub fn try_read_cached_fixed_cols<T: FieldElement>(
+    pil: &Analyzed<T>,
+    dir: &Path,
+    key: &str,
+) -> Option<Vec<(String, Vec<T>)>> {
+    if !dir
+        .join(format!("{key}_{}", FixedPolySet::FILE_NAME))
+        .exists()
+    {
+        return None;
+    }
+    try_read_poly_set::<FixedPolySet, T>(pil, dir, key)
+        // Each column has to have the degree of its namespace, otherwise the file is incomplete.
+        .filter(|(fixed, _)| {
+            let degrees = FixedPolySet::get_polys(pil)
+                .into_iter()
+                .flat_map(|(poly, _)| poly.array_elements().map(|_| poly.degree.or(pil.degree)));
+            fixed
+                .iter()
+                .zip(degrees)
+                .all(|((_, values), degree)| Some(values.len() as DegreeType) == degree)
+        })
+        .map(|(fixed, _)| fixed)
+}
+
+/// Writes the fixed columns to the cache in `dir` under `key`.
+/// The file is written under a temporary name first, so that concurrent runs never
+/// read a partially written file.
+pub fn write_cached_fixed_cols<T: FieldElement>(
+    dir: &Path,
+    key: &str,
+    fixed: &[(String, Vec<T>)],
+) -> Result<(), String> {
+    if fixed.is_empty() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+    let path = dir.join(format!("{key}_{}", FixedPolySet::FILE_NAME));
+    let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    let file = File::create(&tmp_path)
+        .map_err(|e| format!("Could not create {}: {e}", tmp_path.display()))?;
+    write_or_panic(BufWriter::new(file), |writer| {
+        write_polys_file(writer, fixed)
+    });
+    fs::rename(&tmp_path, &path).map_err(|e| format!("Could not write {}: {e}", path.display()))
+}
+
 /// Calls a function with the given writer, flushes it, and panics on error.
 pub fn write_or_panic<W, F, T>(mut writer: W, f: F) -> T
 where
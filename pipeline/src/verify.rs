@@ -1,47 +1,77 @@
-use std::{path::Path, process::Command};
-
-pub fn verify(temp_dir: &Path, name: &str, constants_name: Option<&str>) -> Result<(), String> {
-    let pilcom = std::env::var("PILCOM")
-        .expect("Please set the PILCOM environment variable to the path to the pilcom repository.");
-
-    let constants_name = constants_name.unwrap_or(name);
-
-    let constants_file = format!(
-        "{}/{constants_name}_constants.bin",
-        temp_dir.to_str().unwrap()
-    );
-    let commits_file = format!("{}/{name}_commits.bin", temp_dir.to_str().unwrap());
-    let constraints_file = format!("{}/constraints.json", temp_dir.to_str().unwrap());
-
-    let verifier_output = Command::new("node")
-        .args([
-            "--max-old-space-size=8000".to_string(), // 8GB of memory
-            format!("{pilcom}/src/main_pilverifier.js"),
-            commits_file,
-            "-j".to_string(),
-            constraints_file,
-            "-c".to_string(),
-            constants_file,
-        ])
-        .output()
-        .expect("failed to run pil verifier");
-
-    let output = String::from_utf8_lossy(&verifier_output.stdout);
-    let result = if !verifier_output.status.success() {
-        Err("Pil verifier run was unsuccessful.".to_string())
-    } else if !output.trim().ends_with("PIL OK!!") {
-        Err("Verified did not say 'PIL OK' for {name}.".to_string())
-    } else {
-        Ok(())
-    };
-
-    if result.is_err() {
-        log::error!(
-            "Pil verifier run was unsuccessful.\nStdout: {}\nStderr: {}\n",
-            output,
-            String::from_utf8_lossy(&verifier_output.stderr)
-        );
+use std::collections::{BTreeMap, BTreeSet};
+
+use powdr_ast::analyzed::{AlgebraicExpression, Analyzed};
+use powdr_ast::parsed::visitor::AllChildren;
+use powdr_executor::constraint_checker::{CheckError, ConstraintChecker};
+use powdr_number::FieldElement;
+use sha3::{Digest, Sha3_256};
+
+use crate::Pipeline;
+
+/// Checks that the witness computed by the pipeline satisfies all constraints
+/// of the optimized PIL, using the native constraint checker.
+/// If the PIL uses challenges, the witness columns of the later stages are generated
+/// with challenges derived from a hash of the witness of the previous stages.
+pub fn verify<T: FieldElement>(pipeline: &mut Pipeline<T>) -> Result<(), String> {
+    let pil = pipeline.compute_optimized_pil().map_err(|e| e.join("\n"))?;
+    let fixed_cols = pipeline.compute_fixed_cols().map_err(|e| e.join("\n"))?;
+    let mut witness = (*pipeline.compute_witness().map_err(|e| e.join("\n"))?).clone();
+
+    let mut challenges = BTreeMap::new();
+    for (stage, ids) in challenges_by_stage(&pil) {
+        let seed = hash_columns(&witness);
+        challenges.extend(ids.into_iter().map(|id| (id, derive_challenge(&seed, id))));
+        witness = pipeline
+            .optimized_witgen_callback()
+            .map_err(|e| e.join("\n"))?
+            .next_stage_witness(&witness, challenges.clone(), stage + 1);
+    }
+
+    ConstraintChecker::new(&pil, &fixed_cols, &witness)
+        .with_challenges(challenges)
+        .check()
+        .map_err(|e| {
+            if let CheckError::Violations(violations) = &e {
+                for violation in violations {
+                    log::error!("{violation}");
+                }
+            }
+            format!("Constraint check failed for {}: {e}", pipeline.name())
+        })
+}
+
+/// @returns the IDs of the challenges referenced by the identities, grouped by the
+/// stage after which they are drawn.
+fn challenges_by_stage<T: FieldElement>(pil: &Analyzed<T>) -> BTreeMap<u8, BTreeSet<u64>> {
+    let mut challenges: BTreeMap<u8, BTreeSet<u64>> = BTreeMap::new();
+    for identity in pil.identities_with_inlined_intermediate_polynomials() {
+        for expr in identity.all_children() {
+            if let AlgebraicExpression::Challenge(challenge) = expr {
+                challenges
+                    .entry(challenge.stage as u8)
+                    .or_default()
+                    .insert(challenge.id);
+            }
+        }
+    }
+    challenges
+}
+
+fn hash_columns<T: FieldElement>(columns: &[(String, Vec<T>)]) -> Vec<u8> {
+    let mut hasher = Sha3_256::new();
+    for (name, values) in columns {
+        hasher.update(name.as_bytes());
+        for value in values {
+            hasher.update(value.to_bytes_le());
+        }
     }
+    hasher.finalize().to_vec()
+}
 
-    result
+fn derive_challenge<T: FieldElement>(seed: &[u8], id: u64) -> T {
+    let digest = Sha3_256::new()
+        .chain_update(seed)
+        .chain_update(id.to_le_bytes())
+        .finalize();
+    T::from(u64::from_le_bytes(digest[..8].try_into().unwrap()))
 }
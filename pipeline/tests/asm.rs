@@ -1,7 +1,11 @@
 use powdr_number::{Bn254Field, FieldElement, GoldilocksField};
 use powdr_pipeline::{
-    test_util::{gen_estark_proof, resolve_test_file, test_halo2, verify_test_file},
+    test_util::{
+        gen_estark_proof, gen_fri_stark_proof, resolve_test_file, test_halo2, verify_pipeline,
+        verify_test_file,
+    },
     util::{try_read_poly_set, FixedPolySet, WitnessPolySet},
+    verify::verify,
     Pipeline,
 };
 use test_log::test;
@@ -110,6 +114,12 @@ fn empty_vm() {
     gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn block_machine_cache_deviation() {
+    let f = "asm/block_machine_cache_deviation.asm";
+    verify_asm(f, Default::default());
+}
+
 #[test]
 fn vm_to_block_unique_interface() {
     let f = "asm/vm_to_block_unique_interface.asm";
@@ -122,6 +132,46 @@ fn vm_to_block_unique_interface() {
     //gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn different_degrees() {
+    let f = "asm/different_degrees.asm";
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let pipeline = || {
+        Pipeline::<GoldilocksField>::default()
+            .with_tmp_output(&tmp_dir)
+            .from_file(resolve_test_file(f))
+            .with_prover_inputs(vec![])
+    };
+    let mut pipeline1 = pipeline();
+    let witness = pipeline1.compute_witness().unwrap();
+    let column_length = |name: &str| witness.iter().find(|(n, _)| n == name).unwrap().1.len();
+    assert_eq!(column_length("main.pc"), 32);
+    assert_eq!(column_length("main_arith.x"), 8);
+    assert_eq!(column_length("main_binary.x"), 16);
+    verify(&mut pipeline1).unwrap();
+
+    // The columns are written to the output directory with their lengths.
+    let name = pipeline1.name().to_string();
+    let pil = pipeline1.compute_optimized_pil().unwrap();
+    let fixed = pipeline1.compute_fixed_cols().unwrap();
+    let (read_fixed, degree) =
+        try_read_poly_set::<FixedPolySet, _>(&pil, tmp_dir.as_path(), &name).unwrap();
+    assert_eq!(degree, 32);
+    assert_eq!(read_fixed, *fixed);
+    let (read_witness, degree) =
+        try_read_poly_set::<WitnessPolySet, _>(&pil, tmp_dir.as_path(), &name).unwrap();
+    assert_eq!(degree, 32);
+    assert_eq!(read_witness, *witness);
+
+    // The fixed columns are read from the cache in the output directory.
+    let cache_dir = pipeline1.fixed_cols_cache_dir().unwrap();
+    assert_eq!(std::fs::read_dir(cache_dir).unwrap().count(), 1);
+    assert_eq!(pipeline().compute_fixed_cols().unwrap(), fixed);
+
+    // The machines are connected by lookups between namespaces of different degrees.
+    gen_fri_stark_proof(f, vec![]);
+}
+
 #[test]
 fn vm_to_block_to_block() {
     let f = "asm/vm_to_block_to_block.asm";
@@ -130,6 +180,14 @@ fn vm_to_block_to_block() {
     test_halo2(f, slice_to_vec(&i));
 }
 
+#[test]
+fn machine_parameters() {
+    let f = "asm/machine_parameters.asm";
+    let i = [];
+    verify_asm(f, slice_to_vec(&i));
+    test_halo2(f, slice_to_vec(&i));
+}
+
 #[test]
 fn block_to_block() {
     let f = "asm/block_to_block.asm";
@@ -139,6 +197,23 @@ fn block_to_block() {
     gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn block_machine_first_row_latch() {
+    let f = "asm/block_machine_first_row_latch.asm";
+    let i = [];
+    verify_asm(f, slice_to_vec(&i));
+    test_halo2(f, slice_to_vec(&i));
+    gen_estark_proof(f, slice_to_vec(&i));
+}
+
+#[test]
+fn block_machine_memory_write() {
+    let f = "asm/block_machine_memory_write.asm";
+    let i = [];
+    verify_asm(f, slice_to_vec(&i));
+    gen_estark_proof(f, slice_to_vec(&i));
+}
+
 #[test]
 fn vm_instr_param_mapping() {
     let f = "asm/vm_instr_param_mapping.asm";
@@ -184,6 +259,65 @@ fn vm_to_vm_to_block() {
     gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn vm_to_vm_to_block_reduced_degree() {
+    let f = "asm/vm_to_vm_to_block.asm";
+    let pipeline = Pipeline::default()
+        .from_file(resolve_test_file(f))
+        .with_max_constraint_degree(Some(2));
+    verify_pipeline(pipeline).unwrap();
+}
+
+/// Checks that the witness spilled to disk in chunks of 16 rows matches the witness
+/// generated in memory and proves it with the given backend.
+fn assert_witness_spilling(
+    file_name: &str,
+    inputs: Vec<GoldilocksField>,
+    backend: powdr_backend::BackendType,
+) {
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file(file_name))
+        .with_prover_inputs(inputs.clone())
+        .with_witness_spilling(Some(16));
+    let store = pipeline.compute_witness_store().unwrap();
+
+    let witness = Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file(file_name))
+        .with_prover_inputs(inputs)
+        .compute_witness()
+        .unwrap();
+    assert_eq!(
+        store
+            .read_columns(witness.iter().map(|(name, _)| name.as_str()))
+            .collect::<Vec<_>>(),
+        *witness
+    );
+
+    pipeline.with_backend(backend).compute_proof().unwrap();
+}
+
+#[test]
+fn vm_to_vm_to_block_witness_spilling() {
+    let f = "asm/vm_to_vm_to_block.asm";
+    assert_witness_spilling(f, vec![], powdr_backend::BackendType::EStark);
+    assert_witness_spilling(f, vec![], powdr_backend::BackendType::FriStark);
+}
+
+#[test]
+fn mem_read_write_witness_spilling() {
+    let f = "asm/mem_read_write.asm";
+    assert_witness_spilling(f, vec![], powdr_backend::BackendType::FriStark);
+}
+
+#[test]
+fn palindrome_witness_spilling() {
+    let f = "asm/palindrome.asm";
+    let i = [7, 1, 7, 3, 9, 3, 7, 1];
+    assert_witness_spilling(f, slice_to_vec(&i), powdr_backend::BackendType::FriStark);
+}
+
 #[test]
 fn vm_to_block_array() {
     let f = "asm/vm_to_block_array.asm";
@@ -226,6 +360,15 @@ fn test_mem_read_write_with_bootloader() {
     gen_estark_proof(f, Default::default());
 }
 
+#[test]
+fn mem_read_write_with_bootloader_common_subexpressions() {
+    let f = "asm/mem_read_write_with_bootloader.asm";
+    let pipeline = Pipeline::default()
+        .from_file(resolve_test_file(f))
+        .with_common_subexpression_elimination(true);
+    verify_pipeline(pipeline).unwrap();
+}
+
 #[test]
 fn test_mem_read_write_large_diffs() {
     let f = "asm/mem_read_write_large_diffs.asm";
@@ -370,6 +513,14 @@ fn enum_in_asm() {
     gen_estark_proof(f, Default::default());
 }
 
+#[test]
+fn trait_in_asm() {
+    let f = "asm/trait_in_asm.asm";
+    verify_asm(f, Default::default());
+    test_halo2(f, Default::default());
+    gen_estark_proof(f, Default::default());
+}
+
 #[test]
 fn permutation_simple() {
     let f = "asm/permutations/simple.asm";
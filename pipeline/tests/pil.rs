@@ -3,10 +3,12 @@ use powdr_number::Bn254Field;
 use powdr_number::GoldilocksField;
 use powdr_pipeline::{
     test_util::{
-        assert_proofs_fail_for_invalid_witnesses, assert_proofs_fail_for_invalid_witnesses_estark,
-        assert_proofs_fail_for_invalid_witnesses_halo2,
-        assert_proofs_fail_for_invalid_witnesses_pilcom, gen_estark_proof, resolve_test_file,
-        test_halo2, verify_test_file,
+        assert_proofs_fail_for_invalid_witnesses,
+        assert_proofs_fail_for_invalid_witnesses_constraint_checker,
+        assert_proofs_fail_for_invalid_witnesses_estark,
+        assert_proofs_fail_for_invalid_witnesses_halo2, gen_estark_proof, gen_fri_stark_proof,
+        gen_fri_stark_proof_with_logup, resolve_test_file, test_fri_stark_aggregation, test_halo2,
+        verify_test_file,
     },
     Pipeline,
 };
@@ -44,12 +46,45 @@ fn test_lookup_with_selector() {
     // Invalid witness: 0 is not in the set {2, 4}
     let witness = vec![("main.w".to_string(), vec![0, 42, 4, 17])];
     assert_proofs_fail_for_invalid_witnesses_halo2(f, &witness);
-    assert_proofs_fail_for_invalid_witnesses_pilcom(f, &witness);
+    assert_proofs_fail_for_invalid_witnesses_constraint_checker(f, &witness);
     // Unfortunately, eStark panics in this case. That's why the test is marked
     // as should_panic, with the error message that would be coming from eStark...
     assert_proofs_fail_for_invalid_witnesses_estark(f, &witness);
 }
 
+#[cfg(feature = "halo2")]
+#[test]
+fn test_connect() {
+    let f = "pil/connect.pil";
+    let witness = |a: [u64; 4], b: [u64; 4]| {
+        vec![
+            (
+                "main.a".to_string(),
+                a.into_iter().map(Bn254Field::from).collect(),
+            ),
+            (
+                "main.b".to_string(),
+                b.into_iter().map(Bn254Field::from).collect(),
+            ),
+        ]
+    };
+
+    // Valid witness: a[0] = b[2] and a[3] = b[1]
+    Pipeline::default()
+        .from_file(resolve_test_file(f))
+        .set_witness(witness([5, 1, 2, 6], [0, 6, 5, 3]))
+        .with_backend(powdr_backend::BackendType::Halo2Mock)
+        .compute_proof()
+        .unwrap();
+
+    // Invalid witness: a[3] != b[1]
+    let witness = vec![
+        ("main.a".to_string(), vec![5, 1, 2, 6]),
+        ("main.b".to_string(), vec![0, 7, 5, 3]),
+    ];
+    assert_proofs_fail_for_invalid_witnesses_halo2(f, &witness);
+}
+
 #[test]
 #[should_panic = "assertion failed: check_val._eq(&F::one())"]
 fn test_permutation_with_selector() {
@@ -71,7 +106,7 @@ fn test_permutation_with_selector() {
     // Invalid witness: 0 is not in the set {2, 4}
     let witness = vec![("main.w".to_string(), vec![0, 42, 4, 17])];
     assert_proofs_fail_for_invalid_witnesses_halo2(f, &witness);
-    assert_proofs_fail_for_invalid_witnesses_pilcom(f, &witness);
+    assert_proofs_fail_for_invalid_witnesses_constraint_checker(f, &witness);
     // Unfortunately, eStark panics in this case. That's why the test is marked
     // as should_panic, with the error message that would be coming from eStark...
     assert_proofs_fail_for_invalid_witnesses_estark(f, &witness);
@@ -83,12 +118,39 @@ fn test_fibonacci() {
     verify_pil(f, Default::default());
     test_halo2(f, Default::default());
     gen_estark_proof(f, Default::default());
+    gen_fri_stark_proof(f, Default::default());
+}
+
+#[test]
+fn test_fibonacci_aggregation() {
+    test_fri_stark_aggregation("pil/fibonacci.pil", Default::default());
 }
 
 #[test]
 fn test_permutation_via_challenges() {
     let f = "pil/permutation_via_challenges.pil";
+    verify_test_file(f, Default::default(), vec![]).unwrap();
     test_halo2(f, Default::default());
+    gen_estark_proof(f, Default::default());
+    gen_fri_stark_proof(f, Default::default());
+}
+
+#[test]
+fn witness_spilling_rejects_later_stages() {
+    let f = "pil/permutation_via_challenges.pil";
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let result = Pipeline::<GoldilocksField>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file(f))
+        .with_witness_spilling(Some(16))
+        .compute_witness_store();
+    assert_eq!(
+        result.err().unwrap(),
+        vec![
+            "Witness spilling cannot be combined with witness generation for later stages."
+                .to_string()
+        ]
+    );
 }
 
 #[test]
@@ -137,6 +199,17 @@ fn test_external_witgen_fails_if_none_provided() {
     verify_pil(f, Default::default());
 }
 
+#[test]
+#[should_panic = "main.b = (main.a + 1);\n        unknown: main.b, main.a"]
+fn test_external_witgen_diagnostics_if_none_provided() {
+    let f = "pil/external_witgen.pil";
+    Pipeline::<GoldilocksField>::default()
+        .from_file(resolve_test_file(f))
+        .with_witgen_diagnostics(true)
+        .compute_witness()
+        .unwrap();
+}
+
 #[test]
 fn test_external_witgen_a_provided() {
     let f = "pil/external_witgen.pil";
@@ -193,7 +266,8 @@ fn test_witness_lookup() {
         .collect::<Vec<_>>();
     verify_pil(f, inputs.clone());
     // halo2 fails with "gates must contain at least one constraint"
-    gen_estark_proof(f, inputs);
+    gen_estark_proof(f, inputs.clone());
+    gen_fri_stark_proof_with_logup(f, inputs);
 }
 
 #[test]
@@ -249,6 +323,7 @@ fn test_single_line_blocks() {
     let f = "pil/single_line_blocks.pil";
     verify_pil(f, Default::default());
     gen_estark_proof(f, Default::default());
+    gen_fri_stark_proof_with_logup(f, Default::default());
 }
 
 #[test]
@@ -256,6 +331,7 @@ fn test_two_block_machine_functions() {
     let f = "pil/two_block_machine_functions.pil";
     verify_pil(f, Default::default());
     gen_estark_proof(f, Default::default());
+    gen_fri_stark_proof_with_logup(f, Default::default());
 }
 
 #[test]
@@ -330,3 +406,123 @@ mod book {
 
     include!(concat!(env!("OUT_DIR"), "/pil_book_tests.rs"));
 }
+
+#[test]
+fn test_publics() {
+    let f = "pil/publics.pil";
+    verify_pil(f, Default::default());
+    test_halo2(f, Default::default());
+    gen_estark_proof(f, Default::default());
+}
+
+#[cfg(feature = "halo2")]
+#[test]
+fn test_publics_are_exported() {
+    let f = "pil/publics.pil";
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    Pipeline::<Bn254Field>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file(f))
+        .with_backend(powdr_backend::BackendType::Halo2Mock)
+        .compute_proof()
+        .unwrap();
+
+    let publics = std::fs::read_to_string(tmp_dir.as_path().join("publics_publics.csv")).unwrap();
+    assert_eq!(publics, "out,5\nfirst,1\nout_again,5\n");
+}
+
+#[test]
+fn fixed_cols_cache() {
+    let f = "pil/fixed_columns.pil";
+    let cache_dir = mktemp::Temp::new_dir().unwrap();
+    let pipeline = || {
+        Pipeline::<GoldilocksField>::default()
+            .from_file(resolve_test_file(f))
+            .with_fixed_cols_cache(Some(cache_dir.to_path_buf()))
+    };
+    let fixed = pipeline().compute_fixed_cols().unwrap();
+    let cached_files = std::fs::read_dir(&cache_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(cached_files.len(), 1);
+
+    // Modify the cached columns to check that they are actually read from the cache.
+    let mut modified = (*fixed).clone();
+    modified[0].1[0] += GoldilocksField::from(1);
+    let mut file = std::fs::File::create(&cached_files[0]).unwrap();
+    powdr_number::write_polys_file(&mut file, &modified);
+    assert_eq!(*pipeline().compute_fixed_cols().unwrap(), modified);
+
+    // A different field does not use the cached columns.
+    Pipeline::<powdr_number::Bn254Field>::default()
+        .from_file(resolve_test_file(f))
+        .with_fixed_cols_cache(Some(cache_dir.to_path_buf()))
+        .compute_fixed_cols()
+        .unwrap();
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 2);
+}
+
+#[test]
+fn fixed_cols_cache_with_logup() {
+    let f = "pil/block_lookup_or.pil";
+    let cache_dir = mktemp::Temp::new_dir().unwrap();
+    let pipeline = || {
+        Pipeline::<GoldilocksField>::default()
+            .from_file(resolve_test_file(f))
+            .with_logup(true)
+            .with_fixed_cols_cache(Some(cache_dir.to_path_buf()))
+    };
+    let cached_files = || {
+        std::fs::read_dir(&cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>()
+    };
+    let mut first = pipeline();
+    let fixed = first.compute_fixed_cols().unwrap();
+    let base_files = cached_files();
+    assert_eq!(base_files.len(), 1);
+    // The fixed columns of the PIL rewritten by LogUp are cached separately.
+    let (_, logup_fixed) = first.compute_backend_pil().unwrap();
+    assert!(logup_fixed.len() > fixed.len());
+    let logup_files = cached_files()
+        .into_iter()
+        .filter(|file| !base_files.contains(file))
+        .collect::<Vec<_>>();
+    assert_eq!(logup_files.len(), 1);
+
+    // Modify the cached columns to check that they are actually read from the cache.
+    let mut modified = (*logup_fixed).clone();
+    modified.last_mut().unwrap().1[0] += GoldilocksField::from(1);
+    let mut file = std::fs::File::create(&logup_files[0]).unwrap();
+    powdr_number::write_polys_file(&mut file, &modified);
+    let (_, cached_logup_fixed) = pipeline().compute_backend_pil().unwrap();
+    assert_eq!(*cached_logup_fixed, modified);
+}
+
+#[test]
+fn fixed_cols_cache_in_output_dir() {
+    let f = "pil/fixed_columns.pil";
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file(f));
+    pipeline.compute_fixed_cols().unwrap();
+    let cache_dir = pipeline.fixed_cols_cache_dir().unwrap();
+    assert!(cache_dir.starts_with(tmp_dir.as_path()));
+    assert_eq!(std::fs::read_dir(cache_dir).unwrap().count(), 1);
+
+    // Caching can be disabled.
+    let tmp_dir = mktemp::Temp::new_dir().unwrap();
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .with_tmp_output(&tmp_dir)
+        .from_file(resolve_test_file(f))
+        .with_fixed_cols_cache(None);
+    pipeline.compute_fixed_cols().unwrap();
+    assert_eq!(pipeline.fixed_cols_cache_dir(), None);
+    assert!(!tmp_dir
+        .as_path()
+        .join(powdr_pipeline::util::FIXED_COLS_CACHE_DIR)
+        .exists());
+}
@@ -24,6 +24,20 @@ fn poseidon_gl_test() {
     gen_estark_proof(f, Default::default());
 }
 
+#[test]
+fn keccakf_test() {
+    let f = "std/keccakf_test.asm";
+    verify_test_file(f, Default::default(), vec![]).unwrap();
+    gen_estark_proof(f, Default::default());
+}
+
+#[test]
+fn sha256_test() {
+    let f = "std/sha256_test.asm";
+    verify_test_file(f, Default::default(), vec![]).unwrap();
+    gen_estark_proof(f, Default::default());
+}
+
 #[test]
 fn split_bn254_test() {
     let f = "std/split_bn254_test.asm";
@@ -76,6 +90,20 @@ fn shift_test() {
     test_halo2(f, Default::default());
 }
 
+#[test]
+fn divrem_test() {
+    let f = "std/divrem_test.asm";
+    verify_test_file(f, Default::default(), vec![]).unwrap();
+    test_halo2(f, Default::default());
+}
+
+#[test]
+fn range_check_test() {
+    let f = "std/range_check_test.asm";
+    verify_test_file(f, Default::default(), vec![]).unwrap();
+    test_halo2(f, Default::default());
+}
+
 #[test]
 fn ff_reduce_mod_7() {
     let test_inputs = vec![
@@ -0,0 +1,672 @@
+//! An interactive step debugger for powdr-asm programs, built on top of the
+//! executor's [`TraceBuilder`].
+//!
+//! The debugger stops before executing a statement of the main function. It
+//! supports breakpoints on labels and PCs, stepping over single powdr
+//! statements or over whole RISC-V instructions (using the `.debug insn`
+//! directives emitted by the RISC-V compiler), and inspection of registers and
+//! memory.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead, Write},
+};
+
+use powdr_ast::{
+    asm_analysis::{AnalysisASMFile, FunctionStatement, Machine},
+    parsed::asm::DebugDirective,
+};
+use powdr_number::FieldElement;
+
+use crate::{
+    get_main_machine, preprocess_main_function, Callback, Elem, ExecMode, ExecutionTrace, Executor,
+    MemoryState, PreprocessedMain, TraceBuilder,
+};
+
+/// A preprocessed program, ready to be debugged.
+pub struct DebugProgram<'a, F: FieldElement> {
+    machine: &'a Machine,
+    main: PreprocessedMain<'a, F>,
+    /// The labels pointing to each PC.
+    labels_by_pc: BTreeMap<u32, Vec<&'a str>>,
+    /// Whether the program carries the original RISC-V instructions.
+    has_original_instructions: bool,
+    /// The debug information in effect at each statement, i.e. the one of
+    /// the closest debug directives before it.
+    debug_info_by_line: Vec<DebugInfo<'a>>,
+}
+
+/// The directory, file, line and column of the original source code.
+type FileLocation<'a> = (&'a str, &'a str, usize, usize);
+
+#[derive(Clone, Copy, Default)]
+struct DebugInfo<'a> {
+    original_instruction: Option<&'a str>,
+    file_location: Option<FileLocation<'a>>,
+}
+
+impl<'a, F: FieldElement> DebugProgram<'a, F> {
+    pub fn new(program: &'a AnalysisASMFile) -> Self {
+        let machine = get_main_machine(program);
+        let main = preprocess_main_function(machine);
+
+        let mut labels_by_pc: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+        for (label, pc) in &main.label_map {
+            labels_by_pc.entry(pc.u()).or_default().push(label);
+        }
+        labels_by_pc.values_mut().for_each(|labels| labels.sort());
+
+        let has_original_instructions = main.statements.iter().any(|s| {
+            matches!(
+                s,
+                FunctionStatement::DebugDirective(d)
+                    if matches!(d.directive, DebugDirective::OriginalInstruction(_))
+            )
+        });
+
+        let mut current = DebugInfo::default();
+        let debug_info_by_line = main
+            .statements
+            .iter()
+            .map(|s| {
+                let info = current;
+                if let FunctionStatement::DebugDirective(d) = s {
+                    match &d.directive {
+                        DebugDirective::OriginalInstruction(insn) => {
+                            current.original_instruction = Some(insn.as_str());
+                        }
+                        DebugDirective::Loc(file, line, column) => {
+                            let (dir, file) = main.debug_files[file - 1];
+                            current.file_location = Some((dir, file, *line, *column));
+                        }
+                        DebugDirective::File(..) => unreachable!(),
+                    }
+                }
+                info
+            })
+            .collect();
+
+        Self {
+            machine,
+            main,
+            labels_by_pc,
+            has_original_instructions,
+            debug_info_by_line,
+        }
+    }
+
+    /// Returns the PC of the given label, if it exists.
+    pub fn label_pc(&self, label: &str) -> Option<u32> {
+        self.main.label_map.get(label).map(|pc| pc.u())
+    }
+
+    /// Resolves a breakpoint target, given as a label or as a PC.
+    pub fn resolve_pc(&self, target: &str) -> Option<u32> {
+        self.label_pc(target).or_else(|| {
+            parse_u32(target).filter(|pc| (*pc as usize) < self.main.batch_to_line_map.len() - 1)
+        })
+    }
+
+    /// Returns the PC of the batch the statement at `line` belongs to.
+    fn pc_of_line(&self, line: u32) -> u32 {
+        // The last element of the map is a sentinel past the last statement.
+        let map = &self.main.batch_to_line_map[..self.main.batch_to_line_map.len() - 1];
+        (map.partition_point(|&l| l <= line) - 1) as u32
+    }
+
+    /// Returns true if `line` is the first non-debug statement of its batch.
+    fn is_batch_start(&self, line: u32) -> bool {
+        let batch_start = self.main.batch_to_line_map[self.pc_of_line(line) as usize];
+        self.main.statements[batch_start as usize..line as usize]
+            .iter()
+            .all(|s| matches!(s, FunctionStatement::DebugDirective(_)))
+    }
+
+    /// Maps the statement at `line` back to its source.
+    pub fn source_location(&self, line: u32) -> SourceLocation<'a> {
+        let pc = self.pc_of_line(line);
+        let label = self
+            .labels_by_pc
+            .range(..=pc)
+            .next_back()
+            .map(|(label_pc, labels)| (labels[0], pc - label_pc));
+
+        let DebugInfo {
+            original_instruction,
+            file_location,
+        } = self.debug_info_by_line[line as usize];
+
+        SourceLocation {
+            pc,
+            label,
+            original_instruction,
+            file_location,
+            statement: self.main.statements[line as usize],
+        }
+    }
+}
+
+/// The location in the source of a statement of the main function.
+pub struct SourceLocation<'a> {
+    /// The powdr PC of the statement.
+    pub pc: u32,
+    /// The closest label at or before the PC, and the offset of the PC from it.
+    pub label: Option<(&'a str, u32)>,
+    /// The RISC-V instruction the statement was compiled from.
+    pub original_instruction: Option<&'a str>,
+    /// The directory, file, line and column of the original source code.
+    pub file_location: Option<FileLocation<'a>>,
+    /// The powdr-asm statement itself.
+    pub statement: &'a FunctionStatement,
+}
+
+impl<'a> Display for SourceLocation<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {}", self.pc)?;
+        match self.label {
+            Some((label, 0)) => write!(f, " <{label}>")?,
+            Some((label, offset)) => write!(f, " <{label}+{offset}>")?,
+            None => (),
+        }
+        if let Some((dir, file, line, column)) = self.file_location {
+            write!(f, " at {dir}/{file}:{line}:{column}")?;
+        }
+        writeln!(f)?;
+        if let Some(insn) = self.original_instruction {
+            writeln!(f, "  riscv: {insn}")?;
+        }
+        write!(f, "  powdr: {}", self.statement)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested number of steps was executed.
+    Step,
+    /// A breakpoint on the given PC was hit.
+    Breakpoint(u32),
+    /// The execution has finished.
+    Finished,
+}
+
+pub struct Debugger<'a, 'b, F: FieldElement> {
+    program: &'b DebugProgram<'a, F>,
+    executor: Executor<'a, 'b, F>,
+    /// The next statement to be executed, or None if the execution finished.
+    next_line: Option<u32>,
+    breakpoints: BTreeSet<u32>,
+}
+
+impl<'a, 'b, F: FieldElement> Debugger<'a, 'b, F> {
+    /// Creates a debugger stopped before the first statement of the program.
+    ///
+    /// Fails in the same situations as [`TraceBuilder::new`].
+    pub fn new(
+        program: &'b DebugProgram<'a, F>,
+        initial_memory: MemoryState,
+        inputs: &'b Callback<'b, F>,
+        bootloader_inputs: &'b [Elem<F>],
+        max_steps_to_execute: usize,
+    ) -> Result<Self, Box<(ExecutionTrace<F>, MemoryState)>> {
+        let proc = TraceBuilder::new(
+            program.machine,
+            initial_memory,
+            &program.main.batch_to_line_map,
+            max_steps_to_execute,
+            ExecMode::Fast,
+        )?;
+
+        let mut debugger = Self {
+            program,
+            executor: Executor {
+                proc,
+                label_map: program.main.label_map.clone(),
+                inputs,
+                bootloader_inputs,
+                scratch_mem: HashMap::new(),
+                _stdout: io::stdout(),
+            },
+            next_line: Some(0),
+            breakpoints: BTreeSet::new(),
+        };
+        debugger.skip_debug_directives();
+
+        Ok(debugger)
+    }
+
+    /// Adds a breakpoint on the given PC.
+    pub fn add_breakpoint(&mut self, pc: u32) {
+        self.breakpoints.insert(pc);
+    }
+
+    /// Removes a breakpoint, returns false if there was none on the given PC.
+    pub fn remove_breakpoint(&mut self, pc: u32) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u32> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_line.is_none()
+    }
+
+    /// The source location of the next statement to be executed.
+    pub fn location(&self) -> Option<SourceLocation<'a>> {
+        self.next_line
+            .map(|line| self.program.source_location(line))
+    }
+
+    /// Returns the current value of a register.
+    pub fn get_reg(&self, name: &str) -> Option<Elem<F>> {
+        self.executor.proc.try_get_reg(name)
+    }
+
+    /// Returns the current values of all registers.
+    pub fn registers(&self) -> Vec<(&str, Elem<F>)> {
+        self.executor
+            .proc
+            .reg_names()
+            .into_iter()
+            .map(|name| (name, self.executor.proc.get_reg(name)))
+            .collect()
+    }
+
+    /// Returns the memory word at the given address.
+    pub fn get_mem(&self, addr: u32) -> u32 {
+        self.executor.proc.peek_mem(addr)
+    }
+
+    /// The number of rows of the execution trace so far.
+    pub fn rows(&self) -> usize {
+        self.executor.proc.len()
+    }
+
+    /// Executes a single powdr-asm statement.
+    pub fn step(&mut self) -> StopReason {
+        if self.next_line.is_none() {
+            return StopReason::Finished;
+        }
+        self.exec_next();
+        self.stop_reason().unwrap_or(StopReason::Step)
+    }
+
+    /// Executes all the statements compiled from the current RISC-V
+    /// instruction.
+    ///
+    /// Behaves like [`Debugger::step`] if the program does not carry the
+    /// original instructions.
+    pub fn step_instruction(&mut self) -> StopReason {
+        if !self.program.has_original_instructions {
+            return self.step();
+        }
+        loop {
+            if self.next_line.is_none() {
+                return StopReason::Finished;
+            }
+            let new_instruction = self.exec_next();
+            if let Some(reason) = self.stop_reason() {
+                return reason;
+            }
+            if new_instruction {
+                return StopReason::Step;
+            }
+        }
+    }
+
+    /// Runs until a breakpoint is hit or the execution finishes.
+    pub fn cont(&mut self) -> StopReason {
+        loop {
+            if self.next_line.is_none() {
+                return StopReason::Finished;
+            }
+            self.exec_next();
+            if let Some(reason) = self.stop_reason() {
+                return reason;
+            }
+        }
+    }
+
+    /// Runs the rest of the program, ignoring breakpoints, and returns the
+    /// final state.
+    pub fn finish(mut self) -> (ExecutionTrace<F>, MemoryState) {
+        while self.next_line.is_some() {
+            self.exec_next();
+        }
+        self.executor.proc.finish()
+    }
+
+    /// Executes the next statement and the debug directives following it.
+    ///
+    /// Returns true if a new RISC-V instruction starts at the next statement.
+    fn exec_next(&mut self) -> bool {
+        let line = self.next_line.unwrap();
+        let stm = self.program.main.statements[line as usize];
+        self.next_line = if self
+            .executor
+            .exec_statement(stm, &self.program.main.debug_files)
+        {
+            self.executor.proc.advance()
+        } else {
+            None
+        };
+        self.skip_debug_directives()
+    }
+
+    /// Executes debug directives until the next actual statement.
+    ///
+    /// Returns true if an original instruction directive was executed.
+    fn skip_debug_directives(&mut self) -> bool {
+        let mut new_instruction = false;
+        while let Some(line) = self.next_line {
+            let stm = self.program.main.statements[line as usize];
+            let FunctionStatement::DebugDirective(d) = stm else {
+                break;
+            };
+            new_instruction |= matches!(d.directive, DebugDirective::OriginalInstruction(_));
+            self.executor
+                .exec_statement(stm, &self.program.main.debug_files);
+            self.next_line = self.executor.proc.advance();
+        }
+        new_instruction
+    }
+
+    /// Checks whether the execution must stop before the next statement.
+    fn stop_reason(&self) -> Option<StopReason> {
+        let Some(line) = self.next_line else {
+            return Some(StopReason::Finished);
+        };
+        let pc = self.program.pc_of_line(line);
+        (self.breakpoints.contains(&pc) && self.program.is_batch_start(line))
+            .then_some(StopReason::Breakpoint(pc))
+    }
+
+    /// Runs an interactive debugging session, reading commands from `input`
+    /// until it is closed or `quit` is entered.
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        self.print_location(&mut output)?;
+        write!(output, "(debug) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let Some(command) = words.next() else {
+                write!(output, "(debug) ")?;
+                output.flush()?;
+                continue;
+            };
+            let args = words.collect::<Vec<_>>();
+            match (command, &args[..]) {
+                ("q" | "quit", []) => return Ok(()),
+                ("h" | "help", []) => writeln!(output, "{HELP}")?,
+                ("s" | "step", [] | [_]) | ("si" | "stepi", [] | [_]) => {
+                    match args.first().map(|n| n.parse::<usize>()).unwrap_or(Ok(1)) {
+                        Ok(count) => {
+                            let instruction = command.starts_with("si");
+                            let mut reason = StopReason::Step;
+                            for _ in 0..count {
+                                reason = if instruction {
+                                    self.step_instruction()
+                                } else {
+                                    self.step()
+                                };
+                                if reason != StopReason::Step {
+                                    break;
+                                }
+                            }
+                            self.print_stop(reason, &mut output)?;
+                        }
+                        Err(_) => writeln!(output, "Invalid step count: {}", args[0])?,
+                    }
+                }
+                ("c" | "continue", []) => {
+                    let reason = self.cont();
+                    self.print_stop(reason, &mut output)?;
+                }
+                ("b" | "break", [target]) => match self.program.resolve_pc(target) {
+                    Some(pc) => {
+                        self.add_breakpoint(pc);
+                        writeln!(output, "Breakpoint at pc {pc}")?;
+                    }
+                    None => writeln!(output, "Unknown label or PC: {target}")?,
+                },
+                ("d" | "delete", [target]) => match self.program.resolve_pc(target) {
+                    Some(pc) if self.remove_breakpoint(pc) => {
+                        writeln!(output, "Deleted breakpoint at pc {pc}")?
+                    }
+                    _ => writeln!(output, "No breakpoint at {target}")?,
+                },
+                ("breakpoints", []) => {
+                    for pc in self.breakpoints() {
+                        let location = self
+                            .program
+                            .source_location(self.program.main.batch_to_line_map[pc as usize]);
+                        match location.label {
+                            Some((label, 0)) => writeln!(output, "pc {pc} <{label}>")?,
+                            Some((label, offset)) => {
+                                writeln!(output, "pc {pc} <{label}+{offset}>")?
+                            }
+                            None => writeln!(output, "pc {pc}")?,
+                        }
+                    }
+                }
+                ("w" | "where", []) => self.print_location(&mut output)?,
+                ("r" | "regs", []) => {
+                    for (name, value) in self.registers() {
+                        writeln!(output, "{name} = {value}")?;
+                    }
+                }
+                ("r" | "regs", names) => {
+                    for name in names {
+                        match self.get_reg(name) {
+                            Some(value) => writeln!(output, "{name} = {value}")?,
+                            None => writeln!(output, "Unknown register: {name}")?,
+                        }
+                    }
+                }
+                ("m" | "mem", [addr] | [addr, _]) => {
+                    let count = args.get(1).map(|c| parse_u32(c)).unwrap_or(Some(1));
+                    match (parse_u32(addr), count) {
+                        (Some(addr), Some(count)) => {
+                            // Memory is word-addressed, align the start address.
+                            let addr = addr & !3;
+                            for i in 0..count {
+                                let addr = addr.wrapping_add(4 * i);
+                                writeln!(output, "0x{addr:08x}: 0x{:08x}", self.get_mem(addr))?;
+                            }
+                        }
+                        _ => writeln!(output, "Invalid address or count")?,
+                    }
+                }
+                ("rows", []) => writeln!(output, "{}", self.rows())?,
+                _ => writeln!(output, "Unknown command: {line} (try `help`)")?,
+            }
+            write!(output, "(debug) ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn print_stop(&self, reason: StopReason, output: &mut impl Write) -> io::Result<()> {
+        if let StopReason::Breakpoint(pc) = reason {
+            writeln!(output, "Breakpoint at pc {pc}")?;
+        }
+        self.print_location(output)
+    }
+
+    fn print_location(&self, output: &mut impl Write) -> io::Result<()> {
+        match self.location() {
+            Some(location) => writeln!(output, "{location}"),
+            None => writeln!(
+                output,
+                "Execution finished after {} rows.",
+                self.executor.proc.len()
+            ),
+        }
+    }
+}
+
+const HELP: &str = "Commands:
+  s, step [n]         execute n powdr-asm statements (default 1)
+  si, stepi [n]       execute n RISC-V instructions (default 1)
+  c, continue         run until a breakpoint is hit or the program ends
+  b, break <target>   set a breakpoint on a label or a PC
+  d, delete <target>  remove the breakpoint on a label or a PC
+  breakpoints         list the breakpoints
+  w, where            show the current location
+  r, regs [names..]   show all registers, or the given ones
+  m, mem <addr> [n]   show n memory words starting at addr (default 1)
+  rows                show the number of rows executed so far
+  q, quit             leave the debugger";
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+fn parse_u32(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use powdr_number::GoldilocksField;
+
+    use super::*;
+
+    const PROGRAM: &str = r#"
+machine Main {
+    degree 32;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg x0;
+    reg x1;
+    reg x2;
+
+    col witness XIsZero;
+    XIsZero * (1 - XIsZero) = 0;
+
+    instr branch_if_nonzero X, l: label { pc' = (1 - XIsZero) * l + XIsZero * (pc + 1) }
+    instr mstore X, Y {}
+
+    function main {
+        x1 <=X= 3;
+        start:
+        x1 <=X= x1 - 1;
+        x2 <=X= x2 + 2;
+        branch_if_nonzero x1, start;
+        mstore 16, x2;
+        return;
+    }
+}
+"#;
+
+    fn analyze(source: &str) -> AnalysisASMFile {
+        let parsed = powdr_parser::parse_asm(None, source).unwrap();
+        let resolved = powdr_importer::load_dependencies_and_resolve(None, parsed).unwrap();
+        powdr_analysis::analyze(resolved).unwrap()
+    }
+
+    #[test]
+    fn breakpoints_and_steps() {
+        let analyzed = analyze(PROGRAM);
+        let program = DebugProgram::<GoldilocksField>::new(&analyzed);
+        let inputs = |_: &str| Ok(None);
+        let mut debugger = Debugger::new(&program, MemoryState::new(), &inputs, &[], usize::MAX)
+            .unwrap_or_else(|_| panic!("empty program"));
+
+        let start = program.label_pc("start").unwrap();
+        debugger.add_breakpoint(start);
+
+        for expected in [3, 2, 1] {
+            assert_eq!(debugger.cont(), StopReason::Breakpoint(start));
+            assert_eq!(debugger.get_reg("x1"), Some(expected.into()));
+            let location = debugger.location().unwrap();
+            assert_eq!(location.pc, start);
+            assert_eq!(location.label, Some(("start", 0)));
+        }
+
+        // Step over `x1 <=X= x1 - 1;` and `x2 <=X= x2 + 2;`.
+        assert_eq!(debugger.step(), StopReason::Step);
+        assert_eq!(debugger.step(), StopReason::Step);
+        assert_eq!(debugger.get_reg("x1"), Some(0.into()));
+        assert_eq!(debugger.get_reg("x2"), Some(6.into()));
+        assert_eq!(debugger.location().unwrap().label, Some(("start", 2)));
+
+        assert!(debugger.remove_breakpoint(start));
+        assert_eq!(debugger.cont(), StopReason::Finished);
+        assert!(debugger.is_finished());
+        assert_eq!(debugger.get_mem(16), 6);
+    }
+
+    #[test]
+    fn source_locations() {
+        let analyzed = analyze(
+            r#"
+machine Main {
+    degree 8;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg x1;
+
+    function main {
+        .debug file 1 "dir" "main.rs";
+        .debug loc 1 10 5;
+        .debug insn "addi x1, x1, 1";
+        x1 <=X= x1 + 1;
+        .debug insn "addi x1, x1, 2";
+        x1 <=X= x1 + 2;
+        return;
+    }
+}
+"#,
+        );
+        let program = DebugProgram::<GoldilocksField>::new(&analyzed);
+
+        let locations = (0..program.main.statements.len() as u32)
+            .filter(|&line| {
+                !matches!(
+                    program.main.statements[line as usize],
+                    FunctionStatement::DebugDirective(_)
+                )
+            })
+            .map(|line| {
+                let location = program.source_location(line);
+                (location.original_instruction, location.file_location)
+            })
+            .collect::<Vec<_>>();
+        let file_location = Some(("dir", "main.rs", 10, 5));
+        assert_eq!(
+            locations,
+            [
+                (Some("addi x1, x1, 1"), file_location),
+                (Some("addi x1, x1, 2"), file_location),
+                (Some("addi x1, x1, 2"), file_location),
+            ]
+        );
+    }
+
+    #[test]
+    fn repl() {
+        let analyzed = analyze(PROGRAM);
+        let program = DebugProgram::<GoldilocksField>::new(&analyzed);
+        let inputs = |_: &str| Ok(None);
+        let mut debugger = Debugger::new(&program, MemoryState::new(), &inputs, &[], usize::MAX)
+            .unwrap_or_else(|_| panic!("empty program"));
+
+        let commands = "break start\ncontinue\ncontinue\nregs x1 x3\ndelete start\nc\nmem 0x10 2\n";
+        let mut output = Vec::new();
+        debugger.repl(commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("x1 = 2\n"));
+        assert!(output.contains("Unknown register: x3\n"));
+        assert!(output.contains("Deleted breakpoint at pc 3\n"));
+        assert!(output.contains("Execution finished"));
+        assert!(output.contains("0x00000010: 0x00000006\n0x00000014: 0x00000000\n"));
+    }
+}
@@ -0,0 +1,107 @@
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// The rotation offsets of the rho step, indexed by x + 5 * y.
+const ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Naive implementation of the Keccak-f[1600] permutation, where lane (x, y) is `state[x + 5 * y]`.
+/// Follows the specification at https://keccak.team/keccak_specs_summary.html
+/// It's also equivalent to std::hash::keccakf::KeccakF from the Powdr standard library.
+pub fn keccakf(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // theta
+        let c: [u64; 5] = std::array::from_fn(|x| (0..5).fold(0, |acc, y| acc ^ state[x + 5 * y]));
+        for (i, lane) in state.iter_mut().enumerate() {
+            let x = i % 5;
+            *lane ^= c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+        }
+
+        // rho and pi
+        let mut b = [0; 25];
+        for (i, lane) in state.iter().enumerate() {
+            let (x, y) = (i % 5, i / 5);
+            b[y + 5 * ((2 * x + 3 * y) % 5)] = lane.rotate_left(ROTATIONS[i]);
+        }
+
+        // chi
+        for (i, lane) in state.iter_mut().enumerate() {
+            let (x, y) = (i % 5, i / 5);
+            *lane = b[i] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+        }
+
+        // iota
+        state[0] ^= round_constant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keccakf() {
+        // See test vectors at:
+        // https://github.com/XKCP/XKCP/blob/master/tests/TestVectors/KeccakF-1600-IntermediateValues.txt
+        let mut state = [0; 25];
+        keccakf(&mut state);
+        assert_eq!(
+            state,
+            [
+                0xf1258f7940e1dde7,
+                0x84d5ccf933c0478a,
+                0xd598261ea65aa9ee,
+                0xbd1547306f80494d,
+                0x8b284e056253d057,
+                0xff97a42d7f8e6fd4,
+                0x90fee5a0a44647c4,
+                0x8c5bda0cd6192e76,
+                0xad30a6f71b19059c,
+                0x30935ab7d08ffc64,
+                0xeb5aa93f2317d635,
+                0xa9a6e6260d712103,
+                0x81a57c16dbcf555f,
+                0x43b831cd0347c826,
+                0x01f22f1a11a5569f,
+                0x05e5635a21d9ae61,
+                0x64befef28cc970f2,
+                0x613670957bc46611,
+                0xb87c5a554fd00ecb,
+                0x8c3ee88a1ccf32c8,
+                0x940c7922ae3a2614,
+                0x1841f924a2c509e4,
+                0x16f53526e70465c2,
+                0x75f644e97f30a13b,
+                0xeaf1ff7b5ceca249,
+            ]
+        );
+
+        keccakf(&mut state);
+        assert_eq!(state[0], 0x2d5c954df96ecb3c);
+    }
+}
@@ -24,10 +24,13 @@ use powdr_ast::{
     },
     parsed::{asm::DebugDirective, Expression, FunctionCall},
 };
+use powdr_hash::poseidon_gl;
 use powdr_number::{FieldElement, LargeInt};
 use powdr_riscv_syscalls::SYSCALL_REGISTERS;
 
-pub mod poseidon_gl;
+pub mod debugger;
+pub mod keccakf;
+pub mod sha256;
 
 /// Initial value of the PC.
 ///
@@ -351,6 +354,31 @@ mod builder {
             self.get_reg_idx(self.trace.reg_map[idx])
         }
 
+        /// get current value of register, or None if there is no such register
+        pub(crate) fn try_get_reg(&self, idx: &str) -> Option<Elem<F>> {
+            self.trace
+                .reg_map
+                .get(idx)
+                .map(|&idx| self.get_reg_idx(idx))
+        }
+
+        /// names of all registers, in register bank order
+        pub(crate) fn reg_names(&self) -> Vec<&str> {
+            let mut names = self
+                .trace
+                .reg_map
+                .iter()
+                .map(|(name, idx)| (*idx, name.as_str()))
+                .collect::<Vec<_>>();
+            names.sort();
+            names.into_iter().map(|(_, name)| name).collect()
+        }
+
+        /// get current length of the execution trace
+        pub(crate) fn len(&self) -> usize {
+            self.trace.len
+        }
+
         /// get current value of register by register index instead of name
         fn get_reg_idx(&self, idx: u16) -> Elem<F> {
             self.regs[idx as usize]
@@ -439,6 +467,11 @@ mod builder {
             *self.mem.get(&addr).unwrap_or(&0)
         }
 
+        /// Reads memory without recording the access in the trace.
+        pub(crate) fn peek_mem(&self, addr: u32) -> u32 {
+            *self.mem.get(&addr).unwrap_or(&0)
+        }
+
         pub fn finish(self) -> (ExecutionTrace<F>, MemoryState) {
             (self.trace, self.mem)
         }
@@ -488,6 +521,27 @@ pub fn get_main_machine(program: &AnalysisASMFile) -> &Machine {
     panic!();
 }
 
+/// Returns the PC of the `return` statement of the main function, which is the last
+/// instruction of a terminating execution.
+pub fn get_exit_pc(program: &AnalysisASMFile) -> u64 {
+    let CallableSymbol::Function(main_function) = &get_main_machine(program).callable.0["main"]
+    else {
+        panic!("main function missing")
+    };
+    let batch_idx = main_function
+        .body
+        .statements
+        .iter_batches()
+        .position(|batch| {
+            batch
+                .statements
+                .iter()
+                .any(|s| matches!(s, FunctionStatement::Return(_)))
+        })
+        .expect("main function has no return statement");
+    (batch_idx + PC_INITIAL_VAL) as u64
+}
+
 struct PreprocessedMain<'a, T: FieldElement> {
     statements: Vec<&'a FunctionStatement>,
     label_map: HashMap<&'a str, Elem<T>>,
@@ -562,10 +616,49 @@ struct Executor<'a, 'b, F: FieldElement> {
     label_map: HashMap<&'a str, Elem<F>>,
     inputs: &'b Callback<'b, F>,
     bootloader_inputs: &'b [Elem<F>],
+    /// The scratch memory through which syscalls pass data to submachines.
+    /// It is not part of the execution trace.
+    scratch_mem: HashMap<u32, u32>,
     _stdout: io::Stdout,
 }
 
 impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
+    /// Executes a single statement of the main function.
+    ///
+    /// Returns false if the statement ends the execution.
+    fn exec_statement(&mut self, stm: &FunctionStatement, debug_files: &[(&str, &str)]) -> bool {
+        match stm {
+            FunctionStatement::Assignment(a) => {
+                let results = self.eval_expression(a.rhs.as_ref());
+                assert_eq!(a.lhs_with_reg.len(), results.len());
+                for ((dest, _), val) in a.lhs_with_reg.iter().zip(results) {
+                    self.proc.set_reg(dest, val);
+                }
+            }
+            FunctionStatement::Instruction(i) => {
+                self.exec_instruction(&i.instruction, &i.inputs);
+            }
+            FunctionStatement::Return(_) => return false,
+            FunctionStatement::DebugDirective(dd) => {
+                match &dd.directive {
+                    DebugDirective::Loc(file, line, column) => {
+                        let (dir, file) = debug_files[file - 1];
+                        log::trace!("Executed {dir}/{file}:{line}:{column}");
+                    }
+                    DebugDirective::OriginalInstruction(insn) => {
+                        log::trace!("  {insn}");
+                    }
+                    DebugDirective::File(_, _, _) => unreachable!(),
+                };
+            }
+            FunctionStatement::Label(_) => {
+                unreachable!()
+            }
+        };
+
+        true
+    }
+
     fn exec_instruction(&mut self, name: &str, args: &[Expression]) -> Vec<Elem<F>> {
         let args = args
             .iter()
@@ -612,6 +705,7 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                 let bootloader_input_idx = args[0].bin() as usize;
                 let addr = self.bootloader_inputs[bootloader_input_idx];
                 self.proc.set_pc(addr);
+                self.proc.set_reg("bootloader_done", 1);
 
                 Vec::new()
             }
@@ -705,6 +799,22 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
 
                 vec![div.into(), rem.into()]
             }
+            "divrem" => {
+                let y = args[0].u() as i32;
+                let x = args[1].u() as i32;
+                let div;
+                let rem;
+                if x != 0 {
+                    // The only overflow (-2**31 / -1) results in -2**31 and 0.
+                    div = y.wrapping_div(x);
+                    rem = y.wrapping_rem(x);
+                } else {
+                    div = -1;
+                    rem = y;
+                }
+
+                vec![(div as u32).into(), (rem as u32).into()]
+            }
             "mul" => {
                 let r = args[0].u() as u64 * args[1].u() as u64;
                 let lo = r as u32;
@@ -739,6 +849,54 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                 });
                 vec![]
             }
+            "scratch_store" => {
+                let addr = args[0].u();
+                self.scratch_mem.insert(addr, args[1].u());
+
+                vec![]
+            }
+            "scratch_begin" => {
+                self.proc.set_reg("scratch_busy", 1);
+
+                vec![]
+            }
+            "scratch_end" => {
+                self.proc.set_reg("scratch_busy", 0);
+
+                vec![]
+            }
+            "scratch_load" => {
+                let addr = args[0].u();
+                let val = *self.scratch_mem.get(&addr).unwrap_or(&0);
+
+                vec![val.into()]
+            }
+            "keccakf" => {
+                let addr = args[0].u();
+                let word = |i: u32| *self.scratch_mem.get(&(addr + 4 * i)).unwrap_or(&0) as u64;
+                let mut state: [u64; 25] =
+                    std::array::from_fn(|i| word(2 * i as u32) | (word(2 * i as u32 + 1) << 32));
+                keccakf::keccakf(&mut state);
+                state.iter().enumerate().for_each(|(i, lane)| {
+                    let i = i as u32;
+                    self.scratch_mem.insert(addr + 8 * i, *lane as u32);
+                    self.scratch_mem
+                        .insert(addr + 8 * i + 4, (lane >> 32) as u32);
+                });
+                vec![]
+            }
+            "sha256" => {
+                let state_addr = args[0].u();
+                let block_addr = args[1].u();
+                let word = |addr: u32| *self.scratch_mem.get(&addr).unwrap_or(&0);
+                let mut state: [u32; 8] = std::array::from_fn(|i| word(state_addr + 4 * i as u32));
+                let block: [u32; 16] = std::array::from_fn(|i| word(block_addr + 4 * i as u32));
+                sha256::sha256_compress(&mut state, &block);
+                state.iter().enumerate().for_each(|(i, word)| {
+                    self.scratch_mem.insert(state_addr + 4 * i as u32, *word);
+                });
+                vec![]
+            }
             instr => {
                 panic!("unknown instruction: {instr}");
             }
@@ -887,6 +1045,9 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
             Expression::IfExpression(_) => panic!(),
             Expression::BlockExpression(_, _) => panic!(),
             Expression::IndexAccess(_) => todo!(),
+            Expression::StructExpression(_) | Expression::FieldAccess(_) => {
+                panic!("Structs are not supported in instruction arguments: {expression}")
+            }
         }
     }
 }
@@ -923,6 +1084,7 @@ pub fn execute_ast<T: FieldElement>(
         label_map,
         inputs,
         bootloader_inputs,
+        scratch_mem: HashMap::new(),
         _stdout: io::stdout(),
     };
 
@@ -932,34 +1094,9 @@ pub fn execute_ast<T: FieldElement>(
 
         log::trace!("l {curr_pc}: {stm}",);
 
-        match stm {
-            FunctionStatement::Assignment(a) => {
-                let results = e.eval_expression(a.rhs.as_ref());
-                assert_eq!(a.lhs_with_reg.len(), results.len());
-                for ((dest, _), val) in a.lhs_with_reg.iter().zip(results) {
-                    e.proc.set_reg(dest, val);
-                }
-            }
-            FunctionStatement::Instruction(i) => {
-                e.exec_instruction(&i.instruction, &i.inputs);
-            }
-            FunctionStatement::Return(_) => break,
-            FunctionStatement::DebugDirective(dd) => {
-                match &dd.directive {
-                    DebugDirective::Loc(file, line, column) => {
-                        let (dir, file) = debug_files[file - 1];
-                        log::trace!("Executed {dir}/{file}:{line}:{column}");
-                    }
-                    DebugDirective::OriginalInstruction(insn) => {
-                        log::trace!("  {insn}");
-                    }
-                    DebugDirective::File(_, _, _) => unreachable!(),
-                };
-            }
-            FunctionStatement::Label(_) => {
-                unreachable!()
-            }
-        };
+        if !e.exec_statement(stm, &debug_files) {
+            break;
+        }
 
         curr_pc = match e.proc.advance() {
             Some(pc) => pc,
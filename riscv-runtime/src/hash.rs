@@ -27,3 +27,21 @@ pub fn poseidon_gl_unsafe(mut data: [u64; 12]) -> [u64; 4] {
 
     [data[0], data[1], data[2], data[3]]
 }
+
+/// Calls the low level Keccak-f[1600] PIL machine, where lane (x, y) of the state is
+/// `state[x + 5 * y]`, and returns the permuted state.
+pub fn keccakf(mut state: [u64; 25]) -> [u64; 25] {
+    unsafe {
+        asm!("ecall", in("a0") &mut state as *mut [u64; 25], in("t0") u32::from(Syscall::KeccakF));
+    }
+
+    state
+}
+
+/// Calls the low level SHA-256 PIL machine, which compresses the block of 16 big-endian words
+/// into the state of 8 words.
+pub fn sha256_compress(state: &mut [u32; 8], block: &[u32; 16]) {
+    unsafe {
+        asm!("ecall", in("a0") state as *mut [u32; 8], in("a1") block as *const [u32; 16], in("t0") u32::from(Syscall::Sha256));
+    }
+}
@@ -22,6 +22,8 @@ pub enum Syscall {
     DataIdentifier = 1,
     PrintChar = 2,
     PoseidonGL = 3,
+    KeccakF = 4,
+    Sha256 = 5,
 }
 
 impl core::fmt::Display for Syscall {
@@ -31,6 +33,8 @@ impl core::fmt::Display for Syscall {
             Syscall::DataIdentifier => write!(f, "data_identifier"),
             Syscall::PrintChar => write!(f, "print_char"),
             Syscall::PoseidonGL => write!(f, "poseidon_gl"),
+            Syscall::KeccakF => write!(f, "keccakf"),
+            Syscall::Sha256 => write!(f, "sha256"),
         }
     }
 }
@@ -44,6 +48,8 @@ impl core::str::FromStr for Syscall {
             "data_identifier" => Ok(Syscall::DataIdentifier),
             "print_char" => Ok(Syscall::PrintChar),
             "poseidon_gl" => Ok(Syscall::PoseidonGL),
+            "keccakf" => Ok(Syscall::KeccakF),
+            "sha256" => Ok(Syscall::Sha256),
             _ => Err(()),
         }
     }
@@ -64,6 +70,8 @@ impl TryFrom<u32> for Syscall {
             1 => Ok(Syscall::DataIdentifier),
             2 => Ok(Syscall::PrintChar),
             3 => Ok(Syscall::PoseidonGL),
+            4 => Ok(Syscall::KeccakF),
+            5 => Ok(Syscall::Sha256),
             _ => Err(()),
         }
     }
@@ -69,12 +69,12 @@ impl Architecture for RiscvArchitecture {
     fn instruction_ends_control_flow(instr: &str) -> bool {
         match instr {
             "li" | "lui" | "la" | "mv" | "add" | "addi" | "sub" | "neg" | "mul" | "mulh"
-            | "mulhu" | "mulhsu" | "divu" | "remu" | "xor" | "xori" | "and" | "andi" | "or"
-            | "ori" | "not" | "slli" | "sll" | "srli" | "srl" | "srai" | "seqz" | "snez"
-            | "slt" | "slti" | "sltu" | "sltiu" | "sgtz" | "beq" | "beqz" | "bgeu" | "bltu"
-            | "blt" | "bge" | "bltz" | "blez" | "bgtz" | "bgez" | "bne" | "bnez" | "jal"
-            | "jalr" | "call" | "ecall" | "ebreak" | "lw" | "lb" | "lbu" | "lh" | "lhu" | "sw"
-            | "sh" | "sb" | "nop" | "fence" | "fence.i" | "amoadd.w" | "amoadd.w.aq"
+            | "mulhu" | "mulhsu" | "div" | "divu" | "rem" | "remu" | "xor" | "xori" | "and"
+            | "andi" | "or" | "ori" | "not" | "slli" | "sll" | "srli" | "srl" | "srai" | "seqz"
+            | "snez" | "slt" | "slti" | "sltu" | "sltiu" | "sgtz" | "beq" | "beqz" | "bgeu"
+            | "bltu" | "blt" | "bge" | "bltz" | "blez" | "bgtz" | "bgez" | "bne" | "bnez"
+            | "jal" | "jalr" | "call" | "ecall" | "ebreak" | "lw" | "lb" | "lbu" | "lh" | "lhu"
+            | "sw" | "sh" | "sb" | "nop" | "fence" | "fence.i" | "amoadd.w" | "amoadd.w.aq"
             | "amoadd.w.rl" | "amoadd.w.aqrl" | "lr.w" | "lr.w.aq" | "lr.w.rl" | "lr.w.aqrl"
             | "sc.w" | "sc.w.aq" | "sc.w.rl" | "sc.w.aqrl" => false,
             "j" | "jr" | "tail" | "ret" | "unimp" => true,
@@ -444,6 +444,19 @@ fn preamble<T: FieldElement>(degree: u64, runtime: &Runtime, with_bootloader: bo
         "".to_string()
     };
 
+    // `scratch_busy` is set while a syscall passes data through the scratch memory.
+    // The scratch memory is not part of the state of a chunk, so the prover cannot
+    // jump to the shutdown routine while it is set.
+    let scratch_memory_preamble = if runtime.has_submachine("scratch_memory") {
+        let mut preamble = "\n    reg scratch_busy;\n".to_string();
+        if with_bootloader {
+            preamble.push_str("    jump_to_shutdown_routine * scratch_busy = 0;\n");
+        }
+        preamble
+    } else {
+        "".to_string()
+    };
+
     for machine in ["binary", "shift"] {
         assert!(
             runtime.has_submachine(machine),
@@ -473,6 +486,7 @@ fn preamble<T: FieldElement>(degree: u64, runtime: &Runtime, with_bootloader: bo
             .collect::<Vec<_>>()
             .concat()
         + &bootloader_preamble_if_included
+        + &scratch_memory_preamble
         + &memory(with_bootloader)
         + r#"
     // ============== Constraint on x0 =======================
@@ -580,38 +594,6 @@ fn preamble<T: FieldElement>(degree: u64, runtime: &Runtime, with_bootloader: bo
     { Y_b6 } in { bytes };
     { Y_b7 } in { bytes };
     { Y_b8 } in { bytes };
-
-    col witness REM_b1;
-    col witness REM_b2;
-    col witness REM_b3;
-    col witness REM_b4;
-    { REM_b1 } in { bytes };
-    { REM_b2 } in { bytes };
-    { REM_b3 } in { bytes };
-    { REM_b4 } in { bytes };
-
-    // implements Z = Y / X and W = Y % X.
-    instr divremu Y, X -> Z, W {
-        // main division algorithm:
-        // Y is the known dividend
-        // X is the known divisor
-        // Z is the unknown quotient
-        // W is the unknown remainder
-        // if X is zero, remainder is set to dividend, as per RISC-V specification:
-        X * Z + W = Y,
-
-        // remainder >= 0:
-        W = REM_b1 + REM_b2 * 0x100 + REM_b3 * 0x10000 + REM_b4 * 0x1000000,
-
-        // remainder < divisor, conditioned to X not being 0:
-        (1 - XIsZero) * (X - W - 1 - Y_b5 - Y_b6 * 0x100 - Y_b7 * 0x10000 - Y_b8 * 0x1000000) = 0,
-
-        // in case X is zero, we set quotient according to RISC-V specification
-        XIsZero * (Z - 0xffffffff) = 0,
-
-        // quotient is 32 bits:
-        Z = X_b1 + X_b2 * 0x100 + X_b3 * 0x10000 + X_b4 * 0x1000000
-    }
 "# + mul_instruction
 }
 
@@ -1062,6 +1044,14 @@ fn process_instruction(instr: &str, args: &[Argument]) -> Vec<String> {
             let (rd, r1, r2) = rrr(args);
             only_if_no_write_to_zero(format!("tmp1, {rd} <== divremu({r1}, {r2});"), rd)
         }
+        "div" => {
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero(format!("{rd}, tmp1 <== divrem({r1}, {r2});"), rd)
+        }
+        "rem" => {
+            let (rd, r1, r2) = rrr(args);
+            only_if_no_write_to_zero(format!("tmp1, {rd} <== divrem({r1}, {r2});"), rd)
+        }
 
         // bitwise
         "xor" => {
@@ -6,7 +6,7 @@ use powdr_ast::{
 };
 use powdr_number::FieldElement;
 use powdr_pipeline::Pipeline;
-use powdr_riscv_executor::{get_main_machine, Elem, ExecutionTrace, MemoryState};
+use powdr_riscv_executor::{get_exit_pc, get_main_machine, Elem, ExecutionTrace, MemoryState};
 
 pub mod bootloader;
 mod memory_merkle_tree;
@@ -16,8 +16,8 @@ use memory_merkle_tree::MerkleTree;
 
 use crate::continuations::bootloader::{
     default_register_values, shutdown_routine_upper_bound, BOOTLOADER_INPUTS_PER_PAGE,
-    BOOTLOADER_SPECIFIC_INSTRUCTION_NAMES, DEFAULT_PC, MEMORY_HASH_START_INDEX, PAGE_INPUTS_OFFSET,
-    WORDS_PER_PAGE,
+    BOOTLOADER_SPECIFIC_INSTRUCTION_NAMES, DEFAULT_PC, MEMORY_HASH_START_INDEX,
+    NON_STATE_REGISTER_NAMES, PAGE_INPUTS_OFFSET, WORDS_PER_PAGE,
 };
 
 fn transposed_trace<F: FieldElement>(trace: &ExecutionTrace<F>) -> HashMap<String, Vec<Elem<F>>> {
@@ -46,6 +46,122 @@ fn render_hash<F: FieldElement>(hash: &[Elem<F>]) -> String {
         .join("")
 }
 
+/// The public outputs of a chunk proof, in the order in which they are declared in
+/// `bootloader::bootloader_preamble`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkPublics<F> {
+    /// The register values (including the PC) at the start of the chunk.
+    pub initial_registers: Vec<F>,
+    /// The register values (including the PC) at the end of the chunk.
+    pub final_registers: Vec<F>,
+    /// The memory Merkle root at the start of the chunk.
+    pub initial_memory_hash: [F; 4],
+    /// The memory Merkle root at the end of the chunk.
+    pub final_memory_hash: [F; 4],
+}
+
+impl<F: FieldElement> ChunkPublics<F> {
+    /// Splits the public values of a chunk proof into their components.
+    pub fn from_values(values: &[F]) -> Result<Self, String> {
+        let expected_len = MEMORY_HASH_START_INDEX + 8;
+        if values.len() != expected_len {
+            return Err(format!(
+                "Expected {expected_len} public values for a chunk, but got {}.",
+                values.len()
+            ));
+        }
+        let hash = |start: usize| values[start..start + 4].try_into().unwrap();
+        Ok(Self {
+            initial_registers: values[..REGISTER_NAMES.len()].to_vec(),
+            final_registers: values[REGISTER_NAMES.len()..MEMORY_HASH_START_INDEX].to_vec(),
+            initial_memory_hash: hash(MEMORY_HASH_START_INDEX),
+            final_memory_hash: hash(MEMORY_HASH_START_INDEX + 4),
+        })
+    }
+}
+
+/// Computes the root of the memory Merkle tree at the start of the execution.
+pub fn initial_memory_hash<F: FieldElement>(program: &AnalysisASMFile) -> [F; 4] {
+    let mut merkle_tree = MerkleTree::<F>::new();
+    merkle_tree.update(load_initial_memory(program).into_iter());
+    *merkle_tree.root_hash()
+}
+
+/// Checks that the public outputs of the chunk proofs (in order) link up to a single
+/// execution of `program`:
+/// - The first chunk starts with the default register values and the initial memory of the program.
+/// - Every other chunk starts with the final register values (including the PC) and the final
+///   memory root of the previous chunk.
+/// - The last chunk ends at the `return` statement of the main function, i.e. the execution
+///   has terminated.
+///
+/// Together with the verification of each chunk proof, this proves that the chunks are
+/// consecutive segments of the same, complete execution.
+///
+/// The final registers of a chunk are those of the row in which the prover jumps to the
+/// shutdown routine. The instruction in that row is executed in both chunks: once at the
+/// end of the chunk, where its update of the PC is replaced by the jump, and once at the
+/// start of the next chunk, which resumes from the same registers. Executing it twice is
+/// harmless, because it computes the same values both times (a store writes the same value
+/// to the same address again).
+pub fn verify_chunk_publics<F: FieldElement>(
+    program: &AnalysisASMFile,
+    chunks: &[ChunkPublics<F>],
+) -> Result<(), String> {
+    let first = chunks.first().ok_or("No chunks provided.")?;
+
+    let default_registers = default_register_values::<F>()
+        .into_iter()
+        .map(|e| e.into_fe())
+        .collect::<Vec<_>>();
+    check_registers(&default_registers, &first.initial_registers)
+        .map_err(|e| format!("The first chunk does not start with the default registers: {e}"))?;
+    if first.initial_memory_hash != initial_memory_hash(program) {
+        return Err(
+            "The first chunk does not start with the initial memory of the program.".to_string(),
+        );
+    }
+
+    for (i, (previous, next)) in chunks.iter().zip(chunks.iter().skip(1)).enumerate() {
+        check_registers(&previous.final_registers, &next.initial_registers).map_err(|e| {
+            format!(
+                "Chunk {} does not start with the final registers of chunk {i}: {e}",
+                i + 1
+            )
+        })?;
+        if previous.final_memory_hash != next.initial_memory_hash {
+            return Err(format!(
+                "Chunk {} does not start with the final memory root of chunk {i}.",
+                i + 1
+            ));
+        }
+    }
+
+    let last = chunks.last().unwrap();
+    let exit_pc = F::from(get_exit_pc(program));
+    if last.final_registers[PC_INDEX] != exit_pc {
+        return Err(format!(
+            "The last chunk ends at PC {}, but the execution terminates at PC {exit_pc}.",
+            last.final_registers[PC_INDEX]
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_registers<F: FieldElement>(expected: &[F], actual: &[F]) -> Result<(), String> {
+    match REGISTER_NAMES
+        .iter()
+        .zip(expected.iter().zip(actual))
+        .find(|(_, (expected, actual))| expected != actual)
+    {
+        Some((name, (expected, actual))) => Err(format!(
+            "register {name} is {actual}, but should be {expected}"
+        )),
+        None => Ok(()),
+    }
+}
+
 /// Calls the provided `pipeline_callback` for each chunk of the execution.
 ///
 /// # Arguments
@@ -68,7 +184,13 @@ where
     pipeline.compute_fixed_cols().unwrap();
 
     // we can assume optimized_pil has been computed
-    let length = pipeline.compute_optimized_pil().unwrap().degree();
+    // The external witness columns belong to the main machine, so they have its degree,
+    // which can differ from the degrees of the other machines.
+    let pil = pipeline.compute_optimized_pil().unwrap();
+    let length = pil.definitions["main.jump_to_shutdown_routine"]
+        .0
+        .degree
+        .unwrap();
 
     bootloader_inputs
         .into_iter()
@@ -126,6 +248,7 @@ fn sanity_check(program: &AnalysisASMFile) {
             ((r.ty == RegisterTy::Pc || r.ty == RegisterTy::Write) && r.name != "x0")
                 .then_some(format!("main.{}", r.name))
         })
+        .filter(|name| !NON_STATE_REGISTER_NAMES.contains(&name.as_str()))
         .collect::<BTreeSet<_>>();
     let expected_registers = REGISTER_NAMES
         .iter()
@@ -194,7 +317,7 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
     let mut merkle_tree = MerkleTree::<F>::new();
     merkle_tree.update(initial_memory.iter().map(|(k, v)| (*k, *v)));
 
-    // TODO: commit to the merkle_tree root in the verifier.
+    // The verifier recomputes this root in `verify_chunk_publics`.
 
     log::info!("Executing powdr-asm...");
     let (full_trace, memory_accesses) = {
@@ -296,7 +419,7 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
         log::info!("Bootloader inputs length: {}", bootloader_inputs.len());
 
         log::info!("Simulating chunk execution...");
-        let (chunk_trace, memory_snapshot_update) = {
+        let simulate_chunk = |num_rows: usize| {
             let (trace, memory_snapshot_update) = powdr_riscv_executor::execute_ast::<F>(
                 &program,
                 MemoryState::new(),
@@ -307,6 +430,19 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
             );
             (transposed_trace(&trace), memory_snapshot_update)
         };
+        let (mut chunk_trace, mut memory_snapshot_update) = simulate_chunk(num_rows);
+
+        // The chunk cannot end inside a syscall that passes data through the scratch
+        // memory (see `jump_to_shutdown_routine * scratch_busy = 0`), so in that case
+        // we end it in the last row before the syscall.
+        let mut chunk_rows = num_rows;
+        if let Some(scratch_busy) = chunk_trace.get("main.scratch_busy") {
+            if scratch_busy.len() == num_rows && scratch_busy.last().unwrap().bin() != 0 {
+                chunk_rows = scratch_busy.iter().rposition(|v| v.bin() == 0).unwrap() + 1;
+                log::info!("Ending the chunk after {chunk_rows} rows, before a syscall.");
+                (chunk_trace, memory_snapshot_update) = simulate_chunk(chunk_rows);
+            }
+        }
         let mut memory_updates_by_page =
             merkle_tree.organize_updates_by_page(memory_snapshot_update.into_iter());
         for (i, &page_index) in accessed_pages.iter().enumerate() {
@@ -417,13 +553,13 @@ pub fn rust_continuations_dry_run<F: FieldElement>(
             }
         }
 
-        if chunk_trace["main.pc"].len() < num_rows {
+        if chunk_trace["main.pc"].len() < chunk_rows {
             log::info!("Done!");
             break;
         }
 
         // Minus one, because the last row will have to be repeated in the next chunk.
-        let new_rows = num_rows - start - 1;
+        let new_rows = chunk_rows - start - 1;
         proven_trace += new_rows;
         log::info!("Proved {} rows.", new_rows);
 
@@ -29,10 +29,9 @@ pub const PAGE_INPUTS_OFFSET: usize = NUM_PAGES_INDEX + 1;
 pub fn shutdown_routine_upper_bound(num_pages: usize) -> usize {
     // Regardless of the number of pages, we have to:
     // - Jump to the start of the routine
-    // - Assert all register values are correct (except the PC)
     // - Start the page loop
     // - Jump to shutdown sink
-    let constant_overhead = 6 + REGISTER_NAMES.len() - 1;
+    let constant_overhead = 6;
 
     // For each page, we have to:
     // - Start the page loop (14 instructions)
@@ -60,22 +59,61 @@ pub fn bootloader_preamble() -> String {
 
     let tmp_bootloader_value;
 
+    // Set to 1 once the bootloader has finished, i.e. after the PC has been set to the
+    // bootloader input. Not part of the state passed between chunks.
+    reg bootloader_done;
+
     // Sets the PC to the bootloader input at the provided index if it is nonzero
     instr jump_to_bootloader_input X {
         // TODO: Putting {X, pc'} on the left-hand side should work, but this leads to a wrong PC update rule.
         {X, tmp_bootloader_value} in {BOOTLOADER_INPUT_ADDRESS, bootloader_input_value},
-        pc' = tmp_bootloader_value
+        pc' = tmp_bootloader_value,
+        bootloader_done' = 1
     }
 
     // ============== Shutdown routine constraints =======================
     // Insert a `jump_to_shutdown_routine` witness column, which will let the prover indicate that
     // the normal PC update rule should be bypassed and instead set to the start of the shutdown routine.
-    // Nothing of this is enforced yet, and the flag will be ignored.
     let jump_to_shutdown_routine;
     jump_to_shutdown_routine * (1 - jump_to_shutdown_routine) = 0;
+"#
+    .to_string();
+
+    preamble.push_str(&format!(
+        r#"
+    pc_jump jump_to_shutdown_routine, {SHUTDOWN_START};
+
+    // The prover can only jump to the shutdown routine after the bootloader has finished
+    // (in particular, after it has asserted the final Merkle root). On the first row, the
+    // registers are not constrained yet (`bootloader_done` is only reset in the next row).
+    pol constant bootloader_first_row = [1] + [0]*;
+    bootloader_first_row * jump_to_shutdown_routine = 0;
+    jump_to_shutdown_routine * (1 - bootloader_done) = 0;
+
+    // The jump has to happen, i.e. the execution has to end in the shutdown sink.
+    pol constant bootloader_last_row = [0]* + [1];
+    bootloader_last_row * (pc - {SHUTDOWN_SINK}) = 0;
+
+    // In the row where the prover jumps to the shutdown routine, the registers (including the PC)
+    // need to be equal to the claimed final register values.
+    // The instruction in that row is still executed, but will be executed again at the beginning
+    // of the next chunk, starting from the same state.
+"#
+    ));
+
+    for (i, reg) in REGISTER_NAMES.iter().enumerate() {
+        let reg = reg.strip_prefix("main.").unwrap();
+        preamble.push_str(&format!(
+            "    jump_to_shutdown_routine {{ {}, {reg} }} in {{ BOOTLOADER_INPUT_ADDRESS, bootloader_input_value }};\n",
+            i + REGISTER_NAMES.len()
+        ));
+    }
 
+    preamble.push_str(
+        r#"
     // Expose initial register values as public outputs
-"#.to_string();
+"#,
+    );
 
     for (i, reg) in REGISTER_NAMES.iter().enumerate() {
         let reg = reg.strip_prefix("main.").unwrap();
@@ -135,8 +173,9 @@ static P11: &str = SYSCALL_REGISTERS[11];
 /// The bootloader: An assembly program that can be executed at the beginning a RISC-V execution.
 /// It lets the prover provide arbitrary memory pages and writes them to memory, as well as values for
 /// the registers (including the PC, which is set last).
-/// This can be used to implement continuations. Note that this is completely non-sound. Progress to
-/// make it sound is tracked in https://github.com/powdr-labs/powdr/issues/814.
+/// This can be used to implement continuations. Chunks are linked via the initial and final
+/// register values and memory Merkle roots, which are exposed as public outputs, see
+/// `continuations::verify_chunk_publics`.
 /// Bootloader inputs are in the format:
 /// - First 49 values: Values of x1-x31, tmp1-tmp4, lr_sc_reservation, P0-P11, and the PC
 /// - Second 49 values: The same values, but after this chunk's execution
@@ -381,17 +420,15 @@ jump_to_bootloader_input {PC_INDEX};
 "#
     ));
 
-    bootloader.push_str(
+    bootloader.push_str(&format!(
         r#"
 // START OF SHUTDOWN ROUTINE
 //
-// This code is currently never executed in practice!
-//
-// The shutdown routine is responsible for:
-// - Validating that the final register values are equal to those in the bootloader inputs
-//   (which are exposed as public outputs)
-// - Validating that the final page hashes are equal to the claimed values provided in the
-//   bootloader inputs (which have previously been used to update the Merkle tree)
+// The prover jumps here via the `jump_to_shutdown_routine` flag. The final register values
+// (including the PC) are already validated in the row of the jump (see the constraints in the
+// preamble), so the shutdown routine is only responsible for validating that the final page
+// hashes are equal to the claimed values provided in the bootloader inputs (which have
+// previously been used to update the Merkle tree).
 //
 // During the execution of the shutdown routine, registers are used as follows:
 // - x1: Number of pages (constant throughout the execution)
@@ -401,26 +438,6 @@ jump_to_bootloader_input {PC_INDEX};
 
 shutdown_start:
 
-// Assert final register values are as claimed
-// Note that we cannot assert that the final PC is correct, because it will already
-// have changed at this point. This will need to be done by whatever mechanism is used
-// to jump to the shutdown routine.
-"#,
-    );
-
-    // Go over all registers except the PC
-    let register_iter = REGISTER_NAMES.iter().take(REGISTER_NAMES.len() - 1);
-
-    for (i, reg) in register_iter.enumerate() {
-        let reg = reg.strip_prefix("main.").unwrap();
-        bootloader.push_str(&format!(
-            "assert_bootloader_input {}, {reg};\n",
-            i + REGISTER_NAMES.len()
-        ));
-    }
-
-    bootloader.push_str(&format!(
-        r#"
 // Number of pages
 x1 <== load_bootloader_input({NUM_PAGES_INDEX});
 x1 <== wrap(x1);
@@ -542,6 +559,9 @@ pub const REGISTER_NAMES: [&str; 37] = [
     "main.pc",
 ];
 
+/// The registers of the main machine that are not part of the state passed between chunks.
+pub const NON_STATE_REGISTER_NAMES: [&str; 2] = ["main.bootloader_done", "main.scratch_busy"];
+
 /// Index of the PC in the bootloader input.
 pub const PC_INDEX: usize = REGISTER_NAMES.len() - 1;
 
@@ -557,6 +577,9 @@ pub const DEFAULT_PC: u64 = 3;
 /// Analogous to the `DEFAULT_PC`, this well-known PC jumps to the shutdown routine.
 pub const SHUTDOWN_START: u64 = 4;
 
+/// The PC of the infinite loop at the end of the shutdown routine, which every chunk has to reach.
+pub const SHUTDOWN_SINK: u64 = 5;
+
 /// Helper struct to construct the bootloader inputs, placing each element in
 /// its correct position.
 struct InputCreator<'a, F, Pages>
@@ -7,8 +7,8 @@ use super::bootloader::{
     BYTES_PER_WORD, N_LEAVES_LOG, WORDS_PER_PAGE as WORDS_PER_PAGE_BOOTLOADER,
 };
 
+use powdr_hash::poseidon_gl::poseidon_gl;
 use powdr_number::{FieldElement, GoldilocksField};
-use powdr_riscv_executor::poseidon_gl::poseidon_gl;
 
 const N_LEVELS_DEFAULT: usize = N_LEAVES_LOG + 1;
 
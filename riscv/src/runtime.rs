@@ -37,6 +37,8 @@ struct SubMachine {
     alias: Option<String>,
     /// Instance declaration name,
     instance_name: String,
+    /// Instances passed to the parameters of the submachine
+    args: Vec<String>,
     /// Instruction declarations
     instructions: Vec<MachineStatement>,
     /// TODO: only needed because of witgen requiring that each machine be called at least once
@@ -55,7 +57,11 @@ impl SubMachine {
 
     fn declaration(&self) -> String {
         let ty = self.alias.as_deref().unwrap_or(self.path.name());
-        format!("{} {};", ty, self.instance_name)
+        if self.args.is_empty() {
+            format!("{} {};", ty, self.instance_name)
+        } else {
+            format!("{} {}({});", ty, self.instance_name, self.args.join(", "))
+        }
     }
 }
 
@@ -80,6 +86,10 @@ impl Runtime {
 
         // Base submachines
         // TODO: can/should the memory machine be part of the runtime also?
+        // The range check machine only consists of fixed lookups,
+        // so it does not need an init call.
+        r.add_submachine::<&str, _, _>("std::range_check::RangeCheck", None, "range", [], []);
+
         r.add_submachine(
             "std::binary::Binary",
             None,
@@ -111,6 +121,22 @@ impl Runtime {
             ["x10, x11 <== split_gl(x10);", "x10 <=X= 0;", "x11 <=X= 0;"],
         );
 
+        r.add_submachine_with_args(
+            "std::divrem::DivRem",
+            None,
+            "divrem",
+            &["range"],
+            [
+                "instr divremu Y, X -> Z, W = divrem.divremu;",
+                "instr divrem Y, X -> Z, W = divrem.divrem;",
+            ],
+            [
+                "x10, x11 <== divremu(x10, x10);",
+                "x10 <=X= 0;",
+                "x11 <=X= 0;",
+            ],
+        );
+
         // Base syscalls
         r.add_syscall(
             Syscall::Input,
@@ -206,6 +232,115 @@ impl Runtime {
         self
     }
 
+    /// Adds a scratch memory machine through which syscalls pass memory
+    /// contents to submachines by pointer, since the memory of the main
+    /// machine cannot be accessed by other machines.
+    ///
+    /// The scratch memory is not part of the state of a chunk, so it can only
+    /// hold data within a single syscall. Such a syscall has to be enclosed
+    /// in `scratch_begin` and `scratch_end`, which set the `scratch_busy`
+    /// register that prevents a chunk from ending inside it.
+    fn with_scratch_memory(mut self) -> Self {
+        if self.has_submachine("scratch_memory") {
+            return self;
+        }
+        // The submachines using it write at step `2 * STEP + 1`, so the main
+        // machine only uses even steps.
+        self.add_submachine_with_args(
+            "std::memory::Memory",
+            None,
+            "scratch_memory",
+            &["range"],
+            [
+                "instr scratch_load Y -> X ~ scratch_memory.mload Y, 2 * STEP -> X;",
+                "instr scratch_store Y, Z ~ scratch_memory.mstore Y, 2 * STEP, Z;",
+                "instr scratch_begin { scratch_busy' = 1 }",
+                "instr scratch_end { scratch_busy' = 0 }",
+            ],
+            // no init call, the memory is called by the submachines using it
+            [],
+        );
+        self
+    }
+
+    pub fn with_keccak(self) -> Self {
+        let mut r = self.with_scratch_memory();
+        r.add_submachine_with_args(
+            "std::hash::keccakf::KeccakF",
+            None,
+            "keccakf",
+            &["scratch_memory"],
+            ["instr keccakf Y ~ keccakf.keccakf Y, 2 * STEP;"],
+            // init call
+            ["keccakf 0;"],
+        );
+
+        // The keccakf syscall has a single argument passed on x10, the
+        // memory address of the state of 25 64-bit lanes. Since the memory
+        // offset is chosen by LLVM, we assume it is properly aligned.
+        // The state is copied to the same address in the scratch memory,
+        // permuted there and copied back.
+        let implementation = std::iter::once("scratch_begin;".to_string())
+            .chain((0..50).flat_map(|i| {
+                [
+                    format!("tmp1, tmp2 <== mload({} + x10);", i * 4),
+                    format!("scratch_store {} + x10, tmp1;", i * 4),
+                ]
+            }))
+            .chain(std::iter::once("keccakf x10;".to_string()))
+            .chain((0..50).flat_map(|i| {
+                [
+                    format!("tmp1 <== scratch_load({} + x10);", i * 4),
+                    format!("mstore {} + x10, tmp1;", i * 4),
+                ]
+            }))
+            .chain(std::iter::once("scratch_end;".to_string()));
+
+        r.add_syscall(Syscall::KeccakF, implementation);
+        r
+    }
+
+    pub fn with_sha256(self) -> Self {
+        let mut r = self.with_scratch_memory();
+        r.add_submachine_with_args(
+            "std::hash::sha256::Sha256",
+            None,
+            "sha256",
+            &["scratch_memory", "range"],
+            ["instr sha256 Y, Z ~ sha256.sha256 Y, Z, 2 * STEP;"],
+            // init call
+            ["sha256 0, 32;"],
+        );
+
+        // The sha256 syscall has two arguments: x10 is the memory address
+        // of the state of 8 words, which is overwritten by the new state,
+        // and x11 is the memory address of the block of 16 words.
+        // Both are copied to the same addresses in the scratch memory and
+        // the new state is copied back.
+        let copy_to_scratch = |reg: &'static str, words: usize| {
+            (0..words).flat_map(move |i| {
+                [
+                    format!("tmp1, tmp2 <== mload({} + {reg});", i * 4),
+                    format!("scratch_store {} + {reg}, tmp1;", i * 4),
+                ]
+            })
+        };
+        let implementation = std::iter::once("scratch_begin;".to_string())
+            .chain(copy_to_scratch("x10", 8))
+            .chain(copy_to_scratch("x11", 16))
+            .chain(std::iter::once("sha256 x10, x11;".to_string()))
+            .chain((0..8).flat_map(|i| {
+                [
+                    format!("tmp1 <== scratch_load({} + x10);", i * 4),
+                    format!("mstore {} + x10, tmp1;", i * 4),
+                ]
+            }))
+            .chain(std::iter::once("scratch_end;".to_string()));
+
+        r.add_syscall(Syscall::Sha256, implementation);
+        r
+    }
+
     pub fn add_submachine<S: AsRef<str>, I1: IntoIterator<Item = S>, I2: IntoIterator<Item = S>>(
         &mut self,
         path: &str,
@@ -213,11 +348,30 @@ impl Runtime {
         instance_name: &str,
         instructions: I1,
         init_call: I2,
+    ) {
+        self.add_submachine_with_args(path, alias, instance_name, &[], instructions, init_call)
+    }
+
+    /// Like `add_submachine`, but passes the instances `args` (which have to be
+    /// submachines of the runtime as well) to the parameters of the submachine.
+    pub fn add_submachine_with_args<
+        S: AsRef<str>,
+        I1: IntoIterator<Item = S>,
+        I2: IntoIterator<Item = S>,
+    >(
+        &mut self,
+        path: &str,
+        alias: Option<&str>,
+        instance_name: &str,
+        args: &[&str],
+        instructions: I1,
+        init_call: I2,
     ) {
         let subm = SubMachine {
             path: str::parse(path).expect("invalid submachine path"),
             alias: alias.map(|s| s.to_string()),
             instance_name: instance_name.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
             instructions: instructions
                 .into_iter()
                 .map(|s| parse_instruction_declaration(s.as_ref()))
@@ -366,6 +520,8 @@ impl TryFrom<&[&str]> for Runtime {
             }
             match *name {
                 "poseidon_gl" => runtime = runtime.with_poseidon(),
+                "keccakf" => runtime = runtime.with_keccak(),
+                "sha256" => runtime = runtime.with_sha256(),
                 _ => return Err(format!("Invalid co-processor specified: {name}")),
             }
         }
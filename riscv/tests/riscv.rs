@@ -2,21 +2,23 @@ mod common;
 
 use common::verify_riscv_asm_string;
 use mktemp::Temp;
-use powdr_backend::BackendType;
+use powdr_ast::asm_analysis::AnalysisASMFile;
 use powdr_number::{FieldElement, GoldilocksField};
-use powdr_pipeline::{verify::verify, Pipeline};
+use powdr_pipeline::{inputs_to_query_callback, verify::verify, Pipeline};
 use std::path::PathBuf;
 use test_log::test;
 
 use powdr_riscv::{
-    continuations::{rust_continuations, rust_continuations_dry_run},
+    continuations::{
+        bootloader::MEMORY_HASH_START_INDEX, rust_continuations, rust_continuations_dry_run,
+        verify_chunk_publics, ChunkPublics,
+    },
     Runtime,
 };
 
 /// Compiles and runs a rust program with continuations, runs the full
-/// witness generation & verifies it using Pilcom.
-pub fn test_continuations(case: &str) {
-    let runtime = Runtime::base().with_poseidon();
+/// witness generation & checks it using the native constraint checker.
+pub fn test_continuations(case: &str, runtime: Runtime) {
     let temp_dir = Temp::new_dir().unwrap();
     let riscv_asm = powdr_riscv::compile_rust_crate_to_riscv_asm(
         &format!("tests/riscv_data/{case}/Cargo.toml"),
@@ -31,12 +33,8 @@ pub fn test_continuations(case: &str) {
         .from_asm_string(powdr_asm.clone(), Some(PathBuf::from(&case)))
         .with_prover_inputs(Default::default())
         .with_output(tmp_dir.to_path_buf(), false);
-    let pipeline_callback = |pipeline: Pipeline<GoldilocksField>| -> Result<(), ()> {
-        // Can't use `verify_pipeline`, because the pipeline was renamed in the middle of after
-        // computing the constants file.
-        let mut pipeline = pipeline.with_backend(BackendType::PilStarkCli);
-        pipeline.compute_proof().unwrap();
-        verify(pipeline.output_dir().unwrap(), pipeline.name(), Some(case)).unwrap();
+    let pipeline_callback = |mut pipeline: Pipeline<GoldilocksField>| -> Result<(), ()> {
+        verify(&mut pipeline).unwrap();
         Ok(())
     };
     let bootloader_inputs = rust_continuations_dry_run(&mut pipeline);
@@ -64,6 +62,34 @@ fn test_poseidon_gl() {
     verify_riscv_crate(case, Default::default(), &Runtime::base().with_poseidon());
 }
 
+#[test]
+#[ignore = "Too slow"]
+fn test_keccakf() {
+    let case = "keccakf_via_coprocessor";
+    verify_riscv_crate(case, Default::default(), &Runtime::base().with_keccak());
+}
+
+#[test]
+fn test_keccakf_executor() {
+    // Only runs the executor, which is fast enough to not be ignored.
+    let case = "keccakf_via_coprocessor";
+    execute_riscv_crate(case, &Runtime::base().with_keccak());
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_sha256() {
+    let case = "sha256_via_coprocessor";
+    verify_riscv_crate(case, Default::default(), &Runtime::base().with_sha256());
+}
+
+#[test]
+fn test_sha256_executor() {
+    // Only runs the executor, which is fast enough to not be ignored.
+    let case = "sha256_via_coprocessor";
+    execute_riscv_crate(case, &Runtime::base().with_sha256());
+}
+
 #[test]
 #[ignore = "Too slow"]
 fn test_sum() {
@@ -201,13 +227,12 @@ fn test_print() {
     verify_riscv_crate(case, Default::default(), &Runtime::base());
 }
 
-#[test]
-fn test_many_chunks_dry() {
-    // Compiles and runs the many_chunks example with continuations, just computing
-    // and validating the bootloader inputs.
-    // Doesn't do a full witness generation, verification, or proving.
-    let case = "many_chunks";
-    let runtime = Runtime::base().with_poseidon();
+/// Compiles and runs a rust program with continuations, just computing
+/// the bootloader inputs. Returns the program and the public outputs of each chunk.
+fn continuations_dry_run(
+    case: &str,
+    runtime: Runtime,
+) -> (AnalysisASMFile, Vec<ChunkPublics<GoldilocksField>>) {
     let temp_dir = Temp::new_dir().unwrap();
     let riscv_asm = powdr_riscv::compile_rust_crate_to_riscv_asm(
         &format!("tests/riscv_data/{case}/Cargo.toml"),
@@ -218,19 +243,70 @@ fn test_many_chunks_dry() {
     let mut pipeline = Pipeline::default()
         .from_asm_string(powdr_asm, Some(PathBuf::from(case)))
         .with_prover_inputs(Default::default());
-    rust_continuations_dry_run::<GoldilocksField>(&mut pipeline);
+    let bootloader_inputs = rust_continuations_dry_run::<GoldilocksField>(&mut pipeline);
+
+    // The public outputs of each chunk are the first bootloader inputs.
+    let chunks = bootloader_inputs
+        .iter()
+        .map(|(inputs, _)| ChunkPublics::from_values(&inputs[..MEMORY_HASH_START_INDEX + 8]))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let program = pipeline.compute_analyzed_asm().unwrap().clone();
+    (program, chunks)
+}
+
+#[test]
+fn test_many_chunks_dry() {
+    // Compiles and runs the many_chunks example with continuations, just computing
+    // and validating the bootloader inputs.
+    // Doesn't do a full witness generation, verification, or proving.
+    let (program, mut chunks) =
+        continuations_dry_run("many_chunks", Runtime::base().with_poseidon());
+    assert!(chunks.len() > 1);
+    verify_chunk_publics(&program, &chunks).unwrap();
+
+    // Leaving out the last chunk breaks the chain, because the execution has not terminated.
+    assert!(verify_chunk_publics(&program, &chunks[..chunks.len() - 1])
+        .unwrap_err()
+        .starts_with("The last chunk ends at PC"));
+
+    // Resuming at a different PC breaks the chain.
+    let pc = chunks[1].initial_registers.last_mut().unwrap();
+    *pc += GoldilocksField::from(1);
+    assert!(verify_chunk_publics(&program, &chunks)
+        .unwrap_err()
+        .starts_with("Chunk 1 does not start with the final registers of chunk 0"));
 }
 
 #[test]
 #[ignore = "Too slow"]
 fn test_many_chunks() {
-    test_continuations("many_chunks")
+    test_continuations("many_chunks", Runtime::base().with_poseidon())
+}
+
+#[test]
+fn test_keccakf_many_chunks_dry() {
+    // Most rows are inside the keccakf syscall, whose data is passed through the
+    // scratch memory, so the chunks have to end before the syscalls.
+    let runtime = Runtime::base().with_poseidon().with_keccak();
+    let (program, chunks) = continuations_dry_run("keccakf_many_chunks", runtime);
+    assert!(chunks.len() > 1);
+    verify_chunk_publics(&program, &chunks).unwrap();
+}
+
+#[test]
+#[ignore = "Too slow"]
+fn test_keccakf_many_chunks() {
+    test_continuations(
+        "keccakf_many_chunks",
+        Runtime::base().with_poseidon().with_keccak(),
+    )
 }
 
 #[test]
 #[ignore = "Too slow"]
 fn test_many_chunks_memory() {
-    test_continuations("many_chunks_memory")
+    test_continuations("many_chunks_memory", Runtime::base().with_poseidon())
 }
 
 fn verify_riscv_crate(case: &str, inputs: Vec<GoldilocksField>, runtime: &Runtime) {
@@ -238,6 +314,18 @@ fn verify_riscv_crate(case: &str, inputs: Vec<GoldilocksField>, runtime: &Runtim
     verify_riscv_asm_string::<()>(&format!("{case}.asm"), &powdr_asm, inputs, None);
 }
 
+/// Runs the RISC-V executor on the crate, without witness generation or proving.
+fn execute_riscv_crate(case: &str, runtime: &Runtime) {
+    let powdr_asm = compile_riscv_crate::<GoldilocksField>(case, runtime);
+    powdr_riscv_executor::execute::<GoldilocksField>(
+        &powdr_asm,
+        Default::default(),
+        &inputs_to_query_callback(vec![]),
+        &[],
+        powdr_riscv_executor::ExecMode::Fast,
+    );
+}
+
 fn verify_riscv_crate_with_data<S: serde::Serialize + Send + Sync + 'static>(
     case: &str,
     inputs: Vec<GoldilocksField>,
@@ -0,0 +1,74 @@
+#![no_std]
+
+use powdr_riscv_runtime::hash::keccakf;
+
+#[no_mangle]
+fn main() {
+    // See test vectors at:
+    // https://github.com/XKCP/XKCP/blob/master/tests/TestVectors/KeccakF-1600-IntermediateValues.txt
+    let state = keccakf([0; 25]);
+    assert_eq!(
+        state,
+        [
+            0xf1258f7940e1dde7,
+            0x84d5ccf933c0478a,
+            0xd598261ea65aa9ee,
+            0xbd1547306f80494d,
+            0x8b284e056253d057,
+            0xff97a42d7f8e6fd4,
+            0x90fee5a0a44647c4,
+            0x8c5bda0cd6192e76,
+            0xad30a6f71b19059c,
+            0x30935ab7d08ffc64,
+            0xeb5aa93f2317d635,
+            0xa9a6e6260d712103,
+            0x81a57c16dbcf555f,
+            0x43b831cd0347c826,
+            0x1f22f1a11a5569f,
+            0x5e5635a21d9ae61,
+            0x64befef28cc970f2,
+            0x613670957bc46611,
+            0xb87c5a554fd00ecb,
+            0x8c3ee88a1ccf32c8,
+            0x940c7922ae3a2614,
+            0x1841f924a2c509e4,
+            0x16f53526e70465c2,
+            0x75f644e97f30a13b,
+            0xeaf1ff7b5ceca249,
+        ]
+    );
+    let state = keccakf(state);
+    assert_eq!(state[0], 0x2d5c954df96ecb3c);
+
+    let state = keccakf(core::array::from_fn(|i| i as u64 * 0x0101010101010101));
+    assert_eq!(
+        state,
+        [
+            0x9228104e8e6aadae,
+            0xcfab7e1a0fde91c4,
+            0x2d0b412547799456,
+            0x68e01354fcab18d7,
+            0xcb2a452f0a2e76bb,
+            0x14cf4f051aebe17a,
+            0xffff4672254e2eff,
+            0x6042d21ff1e240fe,
+            0x3f78769cf1886a69,
+            0xf2e8a62ba1048b61,
+            0x7b0ad6372677db21,
+            0x17d5fd006bf1feb6,
+            0x158c3084cc7d47f6,
+            0x35ccc1aab02dd9ef,
+            0xfe3f4d09a9ff6d3f,
+            0xa7c0e43f0c99e52e,
+            0xa7fa0b4c8329a845,
+            0xbe39502800acf9dc,
+            0x56172170b551473,
+            0x1a7c4c2f8826fc9b,
+            0x79ffe34ef1dc2f60,
+            0xb54a26257f4ee911,
+            0xc5737d24f3bed743,
+            0xc045876047a9c2ff,
+            0xde39cf1e73cfd3b8,
+        ]
+    );
+}
@@ -0,0 +1,46 @@
+#![no_std]
+
+use powdr_riscv_runtime::hash::sha256_compress;
+
+/// The initial hash value of SHA-256.
+const IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Computes SHA-256 of a message, using the SHA-256 coprocessor for each block.
+fn sha256(message: &[u8]) -> [u32; 8] {
+    let mut padded = [0u8; 128];
+    padded[..message.len()].copy_from_slice(message);
+    padded[message.len()] = 0x80;
+    let len = if message.len() + 9 <= 64 { 64 } else { 128 };
+    padded[len - 8..len].copy_from_slice(&(message.len() as u64 * 8).to_be_bytes());
+
+    let mut state = IV;
+    for chunk in padded[..len].chunks(64) {
+        let block = core::array::from_fn(|i| {
+            u32::from_be_bytes(chunk[i * 4..i * 4 + 4].try_into().unwrap())
+        });
+        sha256_compress(&mut state, &block);
+    }
+    state
+}
+
+#[no_mangle]
+fn main() {
+    // See test vectors at:
+    // https://www.di-mgt.com.au/sha_testvectors.html
+    assert_eq!(
+        sha256(b"abc"),
+        [
+            0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+            0xf20015ad
+        ]
+    );
+    assert_eq!(
+        sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        [
+            0x248d6a61, 0xd20638b8, 0xe5c02693, 0x0c3e6039, 0xa33ce459, 0x64ff2167, 0xf6ecedd4,
+            0x19db06c1
+        ]
+    );
+}
@@ -8,11 +8,12 @@ use std::convert::int;
 use std::convert::fe;
 use std::convert::expr;
 use std::prover::eval;
-use std::prover::Query;
+use std::prover::hint;
+use std::range_check::RangeCheck;
 
 // Arithmetic machine, ported mainly from Polygon: https://github.com/0xPolygonHermez/zkevm-proverjs/blob/main/pil/arith.pil
 // Currently only supports "Equation 0", i.e., 256-Bit addition and multiplication.
-machine Arith(CLK32_31, operation_id){
+machine Arith(range: RangeCheck)(CLK32_31, operation_id){
     
     // The operation ID will be bit-decomposed to yield selEq[], controlling which equations are activated.
     col witness operation_id;
@@ -33,9 +34,6 @@ machine Arith(CLK32_31, operation_id){
     // Allow this machine to be connected via a permutation
     call_selectors sel;
 
-    let BYTE: col = |i| i & 0xff;
-    let BYTE2: col = |i| i & 0xffff;
-
     let secp_modulus = 0xfffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f;
 
     let inverse: int -> int = |x| ff::inverse(x, secp_modulus);
@@ -90,18 +88,21 @@ machine Arith(CLK32_31, operation_id){
         "affine_256" => 0,
         "ec_add" => 1,
         "ec_double" => 1,
+        _ => panic("Unknown operation")
     };
 
     let s_hint = query || match get_operation() {
         "affine_256" => 0,
         "ec_add" => s_for_eq1(x1_int(), y1_int(), x2_int(), y2_int()),
         "ec_double" => s_for_eq2(x1_int(), y1_int()),
+        _ => panic("Unknown operation")
     };
 
     let q0_hint = query || match get_operation() {
         "affine_256" => 0,
         "ec_add" => compute_q0_for_eq1(x1_int(), y1_int(), x2_int(), y2_int(), s_int()),
         "ec_double" => compute_q0_for_eq2(x1_int(), y1_int(), s_int()),
+        _ => panic("Unknown operation")
     };
 
     let q1_hint = query || if is_ec_operation() == 1 {
@@ -125,79 +126,79 @@ machine Arith(CLK32_31, operation_id){
         0
     };
 
-    col witness s_0(i) query Query::Hint(fe(select_limb(s_hint(), 0)));
-    col witness s_1(i) query Query::Hint(fe(select_limb(s_hint(), 1)));
-    col witness s_2(i) query Query::Hint(fe(select_limb(s_hint(), 2)));
-    col witness s_3(i) query Query::Hint(fe(select_limb(s_hint(), 3)));
-    col witness s_4(i) query Query::Hint(fe(select_limb(s_hint(), 4)));
-    col witness s_5(i) query Query::Hint(fe(select_limb(s_hint(), 5)));
-    col witness s_6(i) query Query::Hint(fe(select_limb(s_hint(), 6)));
-    col witness s_7(i) query Query::Hint(fe(select_limb(s_hint(), 7)));
-    col witness s_8(i) query Query::Hint(fe(select_limb(s_hint(), 8)));
-    col witness s_9(i) query Query::Hint(fe(select_limb(s_hint(), 9)));
-    col witness s_10(i) query Query::Hint(fe(select_limb(s_hint(), 10)));
-    col witness s_11(i) query Query::Hint(fe(select_limb(s_hint(), 11)));
-    col witness s_12(i) query Query::Hint(fe(select_limb(s_hint(), 12)));
-    col witness s_13(i) query Query::Hint(fe(select_limb(s_hint(), 13)));
-    col witness s_14(i) query Query::Hint(fe(select_limb(s_hint(), 14)));
-    col witness s_15(i) query Query::Hint(fe(select_limb(s_hint(), 15)));
+    col witness s_0(i) query hint(fe(select_limb(s_hint(), 0)));
+    col witness s_1(i) query hint(fe(select_limb(s_hint(), 1)));
+    col witness s_2(i) query hint(fe(select_limb(s_hint(), 2)));
+    col witness s_3(i) query hint(fe(select_limb(s_hint(), 3)));
+    col witness s_4(i) query hint(fe(select_limb(s_hint(), 4)));
+    col witness s_5(i) query hint(fe(select_limb(s_hint(), 5)));
+    col witness s_6(i) query hint(fe(select_limb(s_hint(), 6)));
+    col witness s_7(i) query hint(fe(select_limb(s_hint(), 7)));
+    col witness s_8(i) query hint(fe(select_limb(s_hint(), 8)));
+    col witness s_9(i) query hint(fe(select_limb(s_hint(), 9)));
+    col witness s_10(i) query hint(fe(select_limb(s_hint(), 10)));
+    col witness s_11(i) query hint(fe(select_limb(s_hint(), 11)));
+    col witness s_12(i) query hint(fe(select_limb(s_hint(), 12)));
+    col witness s_13(i) query hint(fe(select_limb(s_hint(), 13)));
+    col witness s_14(i) query hint(fe(select_limb(s_hint(), 14)));
+    col witness s_15(i) query hint(fe(select_limb(s_hint(), 15)));
 
     let s = [s_0, s_1, s_2, s_3, s_4, s_5, s_6, s_7, s_8, s_9, s_10, s_11, s_12, s_13, s_14, s_15];
 
-    col witness q0_0(i) query Query::Hint(fe(select_limb(q0_hint(), 0)));
-    col witness q0_1(i) query Query::Hint(fe(select_limb(q0_hint(), 1)));
-    col witness q0_2(i) query Query::Hint(fe(select_limb(q0_hint(), 2)));
-    col witness q0_3(i) query Query::Hint(fe(select_limb(q0_hint(), 3)));
-    col witness q0_4(i) query Query::Hint(fe(select_limb(q0_hint(), 4)));
-    col witness q0_5(i) query Query::Hint(fe(select_limb(q0_hint(), 5)));
-    col witness q0_6(i) query Query::Hint(fe(select_limb(q0_hint(), 6)));
-    col witness q0_7(i) query Query::Hint(fe(select_limb(q0_hint(), 7)));
-    col witness q0_8(i) query Query::Hint(fe(select_limb(q0_hint(), 8)));
-    col witness q0_9(i) query Query::Hint(fe(select_limb(q0_hint(), 9)));
-    col witness q0_10(i) query Query::Hint(fe(select_limb(q0_hint(), 10)));
-    col witness q0_11(i) query Query::Hint(fe(select_limb(q0_hint(), 11)));
-    col witness q0_12(i) query Query::Hint(fe(select_limb(q0_hint(), 12)));
-    col witness q0_13(i) query Query::Hint(fe(select_limb(q0_hint(), 13)));
-    col witness q0_14(i) query Query::Hint(fe(select_limb(q0_hint(), 14)));
-    col witness q0_15(i) query Query::Hint(fe(select_limb(q0_hint(), 15)));
+    col witness q0_0(i) query hint(fe(select_limb(q0_hint(), 0)));
+    col witness q0_1(i) query hint(fe(select_limb(q0_hint(), 1)));
+    col witness q0_2(i) query hint(fe(select_limb(q0_hint(), 2)));
+    col witness q0_3(i) query hint(fe(select_limb(q0_hint(), 3)));
+    col witness q0_4(i) query hint(fe(select_limb(q0_hint(), 4)));
+    col witness q0_5(i) query hint(fe(select_limb(q0_hint(), 5)));
+    col witness q0_6(i) query hint(fe(select_limb(q0_hint(), 6)));
+    col witness q0_7(i) query hint(fe(select_limb(q0_hint(), 7)));
+    col witness q0_8(i) query hint(fe(select_limb(q0_hint(), 8)));
+    col witness q0_9(i) query hint(fe(select_limb(q0_hint(), 9)));
+    col witness q0_10(i) query hint(fe(select_limb(q0_hint(), 10)));
+    col witness q0_11(i) query hint(fe(select_limb(q0_hint(), 11)));
+    col witness q0_12(i) query hint(fe(select_limb(q0_hint(), 12)));
+    col witness q0_13(i) query hint(fe(select_limb(q0_hint(), 13)));
+    col witness q0_14(i) query hint(fe(select_limb(q0_hint(), 14)));
+    col witness q0_15(i) query hint(fe(select_limb(q0_hint(), 15)));
 
     let q0 = [q0_0, q0_1, q0_2, q0_3, q0_4, q0_5, q0_6, q0_7, q0_8, q0_9, q0_10, q0_11, q0_12, q0_13, q0_14, q0_15];
 
-    col witness q1_0(i) query Query::Hint(fe(select_limb(q1_hint(), 0)));
-    col witness q1_1(i) query Query::Hint(fe(select_limb(q1_hint(), 1)));
-    col witness q1_2(i) query Query::Hint(fe(select_limb(q1_hint(), 2)));
-    col witness q1_3(i) query Query::Hint(fe(select_limb(q1_hint(), 3)));
-    col witness q1_4(i) query Query::Hint(fe(select_limb(q1_hint(), 4)));
-    col witness q1_5(i) query Query::Hint(fe(select_limb(q1_hint(), 5)));
-    col witness q1_6(i) query Query::Hint(fe(select_limb(q1_hint(), 6)));
-    col witness q1_7(i) query Query::Hint(fe(select_limb(q1_hint(), 7)));
-    col witness q1_8(i) query Query::Hint(fe(select_limb(q1_hint(), 8)));
-    col witness q1_9(i) query Query::Hint(fe(select_limb(q1_hint(), 9)));
-    col witness q1_10(i) query Query::Hint(fe(select_limb(q1_hint(), 10)));
-    col witness q1_11(i) query Query::Hint(fe(select_limb(q1_hint(), 11)));
-    col witness q1_12(i) query Query::Hint(fe(select_limb(q1_hint(), 12)));
-    col witness q1_13(i) query Query::Hint(fe(select_limb(q1_hint(), 13)));
-    col witness q1_14(i) query Query::Hint(fe(select_limb(q1_hint(), 14)));
-    col witness q1_15(i) query Query::Hint(fe(select_limb(q1_hint(), 15)));
+    col witness q1_0(i) query hint(fe(select_limb(q1_hint(), 0)));
+    col witness q1_1(i) query hint(fe(select_limb(q1_hint(), 1)));
+    col witness q1_2(i) query hint(fe(select_limb(q1_hint(), 2)));
+    col witness q1_3(i) query hint(fe(select_limb(q1_hint(), 3)));
+    col witness q1_4(i) query hint(fe(select_limb(q1_hint(), 4)));
+    col witness q1_5(i) query hint(fe(select_limb(q1_hint(), 5)));
+    col witness q1_6(i) query hint(fe(select_limb(q1_hint(), 6)));
+    col witness q1_7(i) query hint(fe(select_limb(q1_hint(), 7)));
+    col witness q1_8(i) query hint(fe(select_limb(q1_hint(), 8)));
+    col witness q1_9(i) query hint(fe(select_limb(q1_hint(), 9)));
+    col witness q1_10(i) query hint(fe(select_limb(q1_hint(), 10)));
+    col witness q1_11(i) query hint(fe(select_limb(q1_hint(), 11)));
+    col witness q1_12(i) query hint(fe(select_limb(q1_hint(), 12)));
+    col witness q1_13(i) query hint(fe(select_limb(q1_hint(), 13)));
+    col witness q1_14(i) query hint(fe(select_limb(q1_hint(), 14)));
+    col witness q1_15(i) query hint(fe(select_limb(q1_hint(), 15)));
 
     let q1 = [q1_0, q1_1, q1_2, q1_3, q1_4, q1_5, q1_6, q1_7, q1_8, q1_9, q1_10, q1_11, q1_12, q1_13, q1_14, q1_15];
 
-    col witness q2_0(i) query Query::Hint(fe(select_limb(q2_hint(), 0)));
-    col witness q2_1(i) query Query::Hint(fe(select_limb(q2_hint(), 1)));
-    col witness q2_2(i) query Query::Hint(fe(select_limb(q2_hint(), 2)));
-    col witness q2_3(i) query Query::Hint(fe(select_limb(q2_hint(), 3)));
-    col witness q2_4(i) query Query::Hint(fe(select_limb(q2_hint(), 4)));
-    col witness q2_5(i) query Query::Hint(fe(select_limb(q2_hint(), 5)));
-    col witness q2_6(i) query Query::Hint(fe(select_limb(q2_hint(), 6)));
-    col witness q2_7(i) query Query::Hint(fe(select_limb(q2_hint(), 7)));
-    col witness q2_8(i) query Query::Hint(fe(select_limb(q2_hint(), 8)));
-    col witness q2_9(i) query Query::Hint(fe(select_limb(q2_hint(), 9)));
-    col witness q2_10(i) query Query::Hint(fe(select_limb(q2_hint(), 10)));
-    col witness q2_11(i) query Query::Hint(fe(select_limb(q2_hint(), 11)));
-    col witness q2_12(i) query Query::Hint(fe(select_limb(q2_hint(), 12)));
-    col witness q2_13(i) query Query::Hint(fe(select_limb(q2_hint(), 13)));
-    col witness q2_14(i) query Query::Hint(fe(select_limb(q2_hint(), 14)));
-    col witness q2_15(i) query Query::Hint(fe(select_limb(q2_hint(), 15)));
+    col witness q2_0(i) query hint(fe(select_limb(q2_hint(), 0)));
+    col witness q2_1(i) query hint(fe(select_limb(q2_hint(), 1)));
+    col witness q2_2(i) query hint(fe(select_limb(q2_hint(), 2)));
+    col witness q2_3(i) query hint(fe(select_limb(q2_hint(), 3)));
+    col witness q2_4(i) query hint(fe(select_limb(q2_hint(), 4)));
+    col witness q2_5(i) query hint(fe(select_limb(q2_hint(), 5)));
+    col witness q2_6(i) query hint(fe(select_limb(q2_hint(), 6)));
+    col witness q2_7(i) query hint(fe(select_limb(q2_hint(), 7)));
+    col witness q2_8(i) query hint(fe(select_limb(q2_hint(), 8)));
+    col witness q2_9(i) query hint(fe(select_limb(q2_hint(), 9)));
+    col witness q2_10(i) query hint(fe(select_limb(q2_hint(), 10)));
+    col witness q2_11(i) query hint(fe(select_limb(q2_hint(), 11)));
+    col witness q2_12(i) query hint(fe(select_limb(q2_hint(), 12)));
+    col witness q2_13(i) query hint(fe(select_limb(q2_hint(), 13)));
+    col witness q2_14(i) query hint(fe(select_limb(q2_hint(), 14)));
+    col witness q2_15(i) query hint(fe(select_limb(q2_hint(), 15)));
 
     let q2 = [q2_0, q2_1, q2_2, q2_3, q2_4, q2_5, q2_6, q2_7, q2_8, q2_9, q2_10, q2_11, q2_12, q2_13, q2_14, q2_15];
 
@@ -240,12 +241,12 @@ machine Arith(CLK32_31, operation_id){
     *
     *****/
 
-    sum(16, |i| x1[i] * CLK32[i]) + sum(16, |i| y1[i] * CLK32[16 + i]) in BYTE2;
-    sum(16, |i| x2[i] * CLK32[i]) + sum(16, |i| y2[i] * CLK32[16 + i]) in BYTE2;
-    sum(16, |i| x3[i] * CLK32[i]) + sum(16, |i| y3[i] * CLK32[16 + i]) in BYTE2;
+    link 1 => range.check_16 sum(16, |i| x1[i] * CLK32[i]) + sum(16, |i| y1[i] * CLK32[16 + i]);
+    link 1 => range.check_16 sum(16, |i| x2[i] * CLK32[i]) + sum(16, |i| y2[i] * CLK32[16 + i]);
+    link 1 => range.check_16 sum(16, |i| x3[i] * CLK32[i]) + sum(16, |i| y3[i] * CLK32[16 + i]);
     // Note that for q0-q2, we only range-constrain the first 15 limbs here
-    sum(16, |i| s[i] * CLK32[i]) + sum(15, |i| q0[i] * CLK32[16 + i]) in BYTE2;
-    sum(15, |i| q1[i] * CLK32[i]) + sum(15, |i| q2[i] * CLK32[16 + i]) in BYTE2;
+    link 1 => range.check_16 sum(16, |i| s[i] * CLK32[i]) + sum(15, |i| q0[i] * CLK32[16 + i]);
+    link 1 => range.check_16 sum(15, |i| q1[i] * CLK32[i]) + sum(15, |i| q2[i] * CLK32[16 + i]);
 
     // The most significant limbs of q0-q2 are constrained to be 32 bits
     // In Polygon's version they are 19 bits, but that requires increasing the minimum degree
@@ -255,7 +256,7 @@ machine Arith(CLK32_31, operation_id){
     // limbs of the prime, so the result is within 48 bits, still far from overflowing the
     // Goldilocks field.
     pol witness q0_15_high, q0_15_low, q1_15_high, q1_15_low, q2_15_high, q2_15_low;
-    q0_15_high * CLK32[0] + q0_15_low * CLK32[1] + q1_15_high * CLK32[2] + q1_15_low * CLK32[3] + q2_15_high * CLK32[4] + q2_15_low * CLK32[5] in BYTE2;
+    link 1 => range.check_16 q0_15_high * CLK32[0] + q0_15_low * CLK32[1] + q1_15_high * CLK32[2] + q1_15_low * CLK32[3] + q2_15_high * CLK32[4] + q2_15_low * CLK32[5];
 
     fixed_inside_32_block(q0_15_high);
     fixed_inside_32_block(q0_15_low);
@@ -373,12 +374,12 @@ machine Arith(CLK32_31, operation_id){
     // while still preventing overflows: The 32-bit carry gets added to 32 48-Bit values, which can't overflow
     // the Goldilocks field.
     pol witness carry_low[3], carry_high[3];
-    { carry_low[0] } in { BYTE2 };
-    { carry_low[1] } in { BYTE2 };
-    { carry_low[2] } in { BYTE2 };
-    { carry_high[0] } in { BYTE2 };
-    { carry_high[1] } in { BYTE2 };
-    { carry_high[2] } in { BYTE2 };
+    link 1 => range.check_16 carry_low[0];
+    link 1 => range.check_16 carry_low[1];
+    link 1 => range.check_16 carry_low[2];
+    link 1 => range.check_16 carry_high[0];
+    link 1 => range.check_16 carry_high[1];
+    link 1 => range.check_16 carry_high[2];
 
     // Carries can be any integer in the range [-2**31, 2**31 - 1)
     let carry: expr[3] = array::new(3, |i| carry_high[i] * 2**16 + carry_low[i] - 2 ** 31);
@@ -1,4 +1,5 @@
 use std::convert::int;
+use std::check::panic;
 use std::utils::cross_product;
 use std::utils::unchanged_until;
 
@@ -34,6 +35,7 @@ machine Binary(latch, operation_id) {
             0 => a(i) & b(i),
             1 => a(i) | b(i),
             2 => a(i) ^ b(i),
+            _ => panic("Unknown operation")
         }
     };
 
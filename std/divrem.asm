@@ -0,0 +1,136 @@
+use std::convert::int;
+use std::convert::fe;
+use std::field::modulus;
+use std::math::ff::inverse;
+use std::prover::eval;
+use std::prover::hint;
+use std::range_check::RangeCheck;
+use std::utils::force_bool;
+
+// Division with remainder of 32-bit words, following the semantics of the
+// RISC-V M extension. Signed values are represented in two's complement.
+// Each operation takes a single row and every row is a valid division,
+// so the machine is meant to be connected via lookups.
+machine DivRem(range: RangeCheck)(latch, operation_id) {
+
+    // lower bound degree is 65536
+
+    // Unsigned division: A = B * Q + R with R < B.
+    // If B is zero, Q is 0xffffffff and R is A.
+    operation divremu<0> A, B -> Q, R;
+
+    // Signed division, rounding towards zero: |A| = |B| * |Q| + |R| with |R| < |B|,
+    // where the remainder has the sign of A.
+    // If B is zero, Q is -1 and R is A.
+    // On overflow (A = -2**31, B = -1), Q is -2**31 and R is 0.
+    operation divrem<1> A, B -> Q, R;
+
+    col fixed latch = [1]*;
+    col witness operation_id;
+    force_bool(operation_id);
+
+    // Hints for the values that cannot be derived from the inputs by the solver.
+    let is_negative: int -> int = query |x| if int(eval(operation_id)) == 1 && x >= 0x80000000 { 1 } else { 0 };
+    let abs: int -> int = query |x| if is_negative(x) == 1 { 0x100000000 - x } else { x };
+    let negate: int -> int = |x| (0x100000000 - x) % 0x100000000;
+    let a_int = query || int(eval(A));
+    let b_int = query || int(eval(B));
+    let q_abs_hint = query || if b_int() == 0 {
+        // Results in Q = 0xffffffff, independent of the sign of the quotient.
+        if is_negative(a_int()) == 1 { 1 } else { 0xffffffff }
+    } else {
+        abs(a_int()) / abs(b_int())
+    };
+    let r_abs_hint = query || if b_int() == 0 { abs(a_int()) } else { abs(a_int()) % abs(b_int()) };
+    let q_hint = query || if is_negative(a_int()) + is_negative(b_int()) == 1 { negate(q_abs_hint()) } else { q_abs_hint() };
+    let r_hint = query || if is_negative(a_int()) == 1 { negate(r_abs_hint()) } else { r_abs_hint() };
+    let d_hint = query || if b_int() == 0 { 0 } else { abs(b_int()) - r_abs_hint() - 1 };
+    let b_inv_hint = query || if b_int() == 0 { 0 } else { inverse(b_int(), modulus()) };
+    let b_is_zero_hint: -> int = query || if b_int() == 0 { 1 } else { 0 };
+    // The negation of a non-zero value wraps around 2**32.
+    let q_wrap_hint: -> int = query || if is_negative(a_int()) + is_negative(b_int()) == 1 && q_abs_hint() != 0 { 1 } else { 0 };
+    let r_wrap_hint: -> int = query || if is_negative(a_int()) == 1 && r_abs_hint() != 0 { 1 } else { 0 };
+    let low: int -> fe = |x| fe(x & 0xffff);
+    let high: int -> fe = |x| fe(x >> 16);
+
+    col witness A, B, Q, R;
+
+    // 1. Decompose the inputs into 16-bit limbs and extract their most significant bits.
+    col witness A_low, B_low;
+    col witness A_high(i) query hint(high(a_int()));
+    col witness B_high(i) query hint(high(b_int()));
+    col witness A_msb, B_msb;
+    A = A_low + A_high * 0x10000;
+    B = B_low + B_high * 0x10000;
+    link 1 => range.check_16 A_low;
+    link 1 => range.check_16 B_low;
+    link 1 => range.msb_16 A_high -> A_msb;
+    link 1 => range.msb_16 B_high -> B_msb;
+
+    // 2. Compute the absolute values of the inputs.
+    // The inputs are only negative for the signed operation.
+    col witness sign_a, sign_b;
+    sign_a = operation_id * A_msb;
+    sign_b = operation_id * B_msb;
+    col witness A_abs, B_abs;
+    A_abs = A + sign_a * (0x100000000 - 2 * A);
+    B_abs = B + sign_b * (0x100000000 - 2 * B);
+
+    // 3. Divide the absolute values.
+    col witness Q_abs_low(i) query hint(low(q_abs_hint()));
+    col witness Q_abs_high(i) query hint(high(q_abs_hint()));
+    col witness R_abs_low(i) query hint(low(r_abs_hint()));
+    col witness R_abs_high(i) query hint(high(r_abs_hint()));
+    link 1 => range.check_16 Q_abs_low;
+    link 1 => range.check_16 Q_abs_high;
+    link 1 => range.check_16 R_abs_low;
+    link 1 => range.check_16 R_abs_high;
+    col witness Q_abs, R_abs;
+    Q_abs = Q_abs_low + Q_abs_high * 0x10000;
+    R_abs = R_abs_low + R_abs_high * 0x10000;
+
+    // This cannot overflow, because all values are less than 2**32 and R_abs < B_abs.
+    A_abs = B_abs * Q_abs + R_abs;
+
+    // The remainder is less than the divisor, unless the divisor is zero.
+    col witness B_is_zero(i) query hint(fe(b_is_zero_hint()));
+    col witness B_inv(i) query hint(fe(b_inv_hint()));
+    B_is_zero = 1 - B * B_inv;
+    B_is_zero * B = 0;
+    col witness D_low(i) query hint(low(d_hint()));
+    col witness D_high(i) query hint(high(d_hint()));
+    link 1 => range.check_16 D_low;
+    link 1 => range.check_16 D_high;
+    (1 - B_is_zero) * (B_abs - R_abs - 1 - D_low - D_high * 0x10000) = 0;
+
+    // If the divisor is zero, the quotient is 0xffffffff.
+    B_is_zero * (Q - 0xffffffff) = 0;
+
+    // 4. Apply the signs to the results.
+    // The quotient is negative if exactly one of the inputs is negative,
+    // the remainder has the sign of A.
+    // Negation is modulo 2**32, i.e. Q + Q_abs is either 0 or 2**32.
+    col witness Q_low(i) query hint(low(q_hint()));
+    col witness Q_high(i) query hint(high(q_hint()));
+    col witness R_low(i) query hint(low(r_hint()));
+    col witness R_high(i) query hint(high(r_hint()));
+    link 1 => range.check_16 Q_low;
+    link 1 => range.check_16 Q_high;
+    link 1 => range.check_16 R_low;
+    link 1 => range.check_16 R_high;
+    Q = Q_low + Q_high * 0x10000;
+    R = R_low + R_high * 0x10000;
+
+    col witness sign_q;
+    sign_q = sign_a + sign_b - 2 * sign_a * sign_b;
+    col witness Q_wrap(i) query hint(fe(q_wrap_hint()));
+    col witness R_wrap(i) query hint(fe(r_wrap_hint()));
+    force_bool(Q_wrap);
+    force_bool(R_wrap);
+    (1 - sign_q) * (Q - Q_abs) = 0;
+    sign_q * (Q + Q_abs - Q_wrap * 0x100000000) = 0;
+    (1 - sign_q) * Q_wrap = 0;
+    (1 - sign_a) * (R - R_abs) = 0;
+    sign_a * (R + R_abs - R_wrap * 0x100000000) = 0;
+    (1 - sign_a) * R_wrap = 0;
+}
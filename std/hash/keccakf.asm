@@ -0,0 +1,146 @@
+use std::array;
+use std::convert::expr;
+use std::utils::force_bool;
+use std::utils::unchanged_until;
+use std::memory::Memory;
+
+// Implements the Keccak-f[1600] permutation.
+// The state is read from and written back to the memory machine `mem`.
+machine KeccakF(mem: Memory)(FIRSTBLOCK, operation_id) {
+
+    // Applies the Keccak-f[1600] permutation to the state of 25 lanes of 64 bits
+    // stored in `mem` at address `addr`. The state is read at step `step` and
+    // the permuted state is written back to the same address at step `step + 1`.
+    // Each lane is split into two 32-bit words, the less significant word first,
+    // i.e. lane x + 5 * y is the word at addr + 4 * (2 * (x + 5 * y)) plus
+    // 2**32 times the word at addr + 4 * (2 * (x + 5 * y) + 1).
+    // This is the layout of a `[u64; 25]` state in little-endian memory.
+    operation keccakf<0> addr, step ->;
+
+    // Allow this machine to be connected via a permutation
+    call_selectors sel;
+
+    col witness operation_id;
+
+    // Follows the specification at https://keccak.team/keccak_specs_summary.html
+    // Each row computes one round, the bits of the state are stored in separate columns.
+
+    let NUM_ROUNDS: int = 24;
+    let ROWS_PER_PERMUTATION = NUM_ROUNDS + 1;
+
+    pol constant FIRSTBLOCK(i) { if i % ROWS_PER_PERMUTATION == 0 { 1 } else { 0 } };
+    pol constant LASTBLOCK(i) { if i % ROWS_PER_PERMUTATION == NUM_ROUNDS { 1 } else { 0 } };
+    // Like LASTBLOCK, but also 1 in the last row of the table
+    // Specified this way because we can't access the degree in the match statement
+    pol constant LAST = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]* + [1];
+
+    // The round constants of the iota step.
+    let RC: int[] = [0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000, 0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009, 0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a, 0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003, 0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a, 0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008];
+    let rc_bit: int, int -> int = |i, z| if i % ROWS_PER_PERMUTATION < NUM_ROUNDS { (RC[i % ROWS_PER_PERMUTATION] >> z) & 1 } else { 0 };
+    // Only these bits are set in any of the round constants.
+    pol constant RC_0(i) { rc_bit(i, 0) };
+    pol constant RC_1(i) { rc_bit(i, 1) };
+    pol constant RC_3(i) { rc_bit(i, 3) };
+    pol constant RC_7(i) { rc_bit(i, 7) };
+    pol constant RC_15(i) { rc_bit(i, 15) };
+    pol constant RC_31(i) { rc_bit(i, 31) };
+    pol constant RC_63(i) { rc_bit(i, 63) };
+    let rc: int -> expr = |z| match z {
+        0 => RC_0,
+        1 => RC_1,
+        3 => RC_3,
+        7 => RC_7,
+        15 => RC_15,
+        31 => RC_31,
+        63 => RC_63,
+        _ => 0
+    };
+
+    // The rotation offsets of the rho step, indexed by x + 5 * y.
+    let ROTATION: int[] = [0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14];
+
+    // The index of bit z of lane (x, y) in a state.
+    let bit_index: int, int, int -> int = |x, y, z| (x + 5 * y) * 64 + z;
+
+    let xor: expr, expr -> expr = |a, b| a + b - 2 * a * b;
+
+    // Creates one constraint for each bit of the first `lanes` lanes.
+    // The arrays are created lane by lane to limit the recursion depth of the evaluation.
+    let for_each_bit_of: int, (int, int, int -> constr) -> constr[] = |lanes, f| array::fold(
+        array::new(lanes, |lane| array::new(64, |z| f(lane % 5, lane / 5, z))),
+        [],
+        |acc, constraints| acc + constraints
+    );
+    let for_each_bit: (int, int, int -> constr) -> constr[] = |f| for_each_bit_of(25, f);
+
+    col witness addr;
+    col witness step;
+    pol commit input[50];
+    pol commit output[50];
+
+    // The memory accesses are enabled in all rows of a block that has been 'used'
+    // by a call into this machine.
+    let used = array::sum(sel);
+    force_bool(used);
+    array::map(sel, |s| unchanged_until(s, LAST));
+
+    // The inputs of the operation and the input state are constant in the block.
+    unchanged_until(addr, LAST);
+    unchanged_until(step, LAST);
+    array::map(input, |c| unchanged_until(c, LAST));
+
+    // Scratch memory layout: The state occupies the 50 words (200 bytes) starting at `addr`.
+    // Row r of a block reads the words 2 * r and 2 * r + 1 at `step` and writes the
+    // permuted words back to the same addresses at `step + 1`, so the 25 rows of a block
+    // access all 50 words with two loads and two stores each.
+    pol constant WORD_PAIR_OFFSET(i) { (i % ROWS_PER_PERMUTATION) * 8 };
+    let CLK25: col[25] = array::new(ROWS_PER_PERMUTATION, |r| |i| if i % ROWS_PER_PERMUTATION == r { 1 } else { 0 });
+    // Word 2 * r + k of the given words in row r of the block.
+    let word_of_row: expr[], int -> expr = |words, k| array::sum(array::new(ROWS_PER_PERMUTATION, |r| CLK25[r] * words[2 * r + k]));
+
+    // Read the input state from memory.
+    link used ~> mem.mload addr + WORD_PAIR_OFFSET, step -> word_of_row(input, 0);
+    link used ~> mem.mload addr + WORD_PAIR_OFFSET + 4, step -> word_of_row(input, 1);
+
+    // Write the output state back to memory.
+    // The output is constant in the block, so each row can write its words.
+    link used ~> mem.mstore addr + WORD_PAIR_OFFSET, step + 1, word_of_row(output, 0);
+    link used ~> mem.mstore addr + WORD_PAIR_OFFSET + 4, step + 1, word_of_row(output, 1);
+
+    // The state at the beginning of the round.
+    pol commit a[1600];
+    for_each_bit(|x, y, z| force_bool(a[bit_index(x, y, z)]));
+
+    // The 32-bit word w of a state, for w in 0..50.
+    let word: expr[], int -> expr = |state, w| array::sum(array::new(32, |k| state[w * 32 + k] * expr(1 << k)));
+
+    // The input is the state in the first row.
+    array::new(50, |w| FIRSTBLOCK * (input[w] - word(a, w)) = 0);
+
+    // theta: The parity c of each column of 5 bits.
+    // The sum of the bits is decomposed as c + 2 * c_carry_lo + 4 * c_carry_hi.
+    pol commit c[320];
+    pol commit c_carry_lo[320];
+    pol commit c_carry_hi[320];
+    for_each_bit_of(5, |x, _, z| force_bool(c[bit_index(x, 0, z)]));
+    for_each_bit_of(5, |x, _, z| force_bool(c_carry_lo[bit_index(x, 0, z)]));
+    for_each_bit_of(5, |x, _, z| force_bool(c_carry_hi[bit_index(x, 0, z)]));
+    for_each_bit_of(5, |x, _, z| array::sum(array::new(5, |y| a[bit_index(x, y, z)])) = c[bit_index(x, 0, z)] + 2 * c_carry_lo[bit_index(x, 0, z)] + 4 * c_carry_hi[bit_index(x, 0, z)]);
+    let d: int, int -> expr = |x, z| xor(c[bit_index((x + 4) % 5, 0, z)], c[bit_index((x + 1) % 5, 0, (z + 63) % 64)]);
+
+    // theta, rho and pi: b[y, 2x + 3y] = rot(a[x, y] ^ d[x], ROTATION[x, y])
+    pol commit b[1600];
+    for_each_bit(|x, y, z| b[bit_index(y, (2 * x + 3 * y) % 5, (z + ROTATION[x + 5 * y]) % 64)] = xor(a[bit_index(x, y, z)], d(x, z)));
+
+    // chi and iota: The state of the next round is a[x, y] = b[x, y] ^ (~b[x + 1, y] & b[x + 2, y]),
+    // where the round constant is added to lane (0, 0).
+    let chi: int, int, int -> expr = |x, y, z| xor(b[bit_index(x, y, z)], (1 - b[bit_index((x + 1) % 5, y, z)]) * b[bit_index((x + 2) % 5, y, z)]);
+    let iota: int, int, int -> expr = |x, y, z| if x + 5 * y == 0 { xor(chi(x, y, z), rc(z)) } else { chi(x, y, z) };
+    for_each_bit(|x, y, z| (a[bit_index(x, y, z)]' - iota(x, y, z)) * (1 - LAST) = 0);
+
+    // In the last row, the output is the state.
+    array::new(50, |w| LASTBLOCK * (output[w] - word(a, w)) = 0);
+
+    // The output should stay constant in the block
+    array::map(output, |c| unchanged_until(c, LAST));
+}
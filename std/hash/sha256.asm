@@ -0,0 +1,207 @@
+use std::array;
+use std::convert::expr;
+use std::utils::force_bool;
+use std::utils::unchanged_until;
+use std::memory::Memory;
+use std::range_check::RangeCheck;
+
+// Implements the SHA-256 compression function.
+// The state and the block are read from `mem` and the new state is written back to it.
+machine Sha256(mem: Memory, range: RangeCheck)(FIRSTBLOCK, operation_id) {
+
+    // Compresses the block of 16 32-bit words stored in `mem` at address `block_addr`
+    // into the state of 8 32-bit words stored at address `state_addr`. The state and
+    // the block are read at step `step` and the new state is written back to
+    // `state_addr` at step `step + 1`. The words are the big-endian words of the
+    // message and the state, i.e. the first byte of a message block is the most
+    // significant byte of the first word.
+    operation sha256<0> state_addr, block_addr, step ->;
+
+    // Allow this machine to be connected via a permutation
+    call_selectors sel;
+
+    col witness operation_id;
+
+    // Follows the specification in FIPS 180-4: https://doi.org/10.6028/NIST.FIPS.180-4
+    // Row t of a block computes round t, the last row contains the final working variables.
+
+    let NUM_ROUNDS: int = 64;
+    let ROWS_PER_BLOCK = NUM_ROUNDS + 1;
+
+    pol constant FIRSTBLOCK(i) { if i % ROWS_PER_BLOCK == 0 { 1 } else { 0 } };
+    pol constant LASTBLOCK(i) { if i % ROWS_PER_BLOCK == NUM_ROUNDS { 1 } else { 0 } };
+    // Like LASTBLOCK, but also 1 in the last row of the table
+    // Specified this way because we can't access the degree in the match statement
+    pol constant LAST = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]* + [1];
+
+    // The round constants.
+    let K: int[] = [0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2];
+    pol constant ROUND_CONSTANT(i) { if i % ROWS_PER_BLOCK < NUM_ROUNDS { K[i % ROWS_PER_BLOCK] } else { 0 } };
+
+    let xor: expr, expr -> expr = |a, b| a + b - 2 * a * b;
+    let xor3: expr, expr, expr -> expr = |a, b, c| xor(xor(a, b), c);
+
+    // Operations on the bits of a word, least significant bit first.
+    let word_of: (int -> expr) -> expr = |bit| array::sum(array::new(32, |k| bit(k) * expr(1 << k)));
+    let word: expr[] -> expr = |bits| word_of(|k| bits[k]);
+    let rotr: expr[], int -> (int -> expr) = |bits, n| |k| bits[(k + n) % 32];
+    let shr: expr[], int -> (int -> expr) = |bits, n| |k| if k + n < 32 { bits[k + n] } else { 0 };
+    let xor3_word: (int -> expr), (int -> expr), (int -> expr) -> expr = |x, y, z| word_of(|k| xor3(x(k), y(k), z(k)));
+
+    let Sigma0: expr[] -> expr = |x| xor3_word(rotr(x, 2), rotr(x, 13), rotr(x, 22));
+    let Sigma1: expr[] -> expr = |x| xor3_word(rotr(x, 6), rotr(x, 11), rotr(x, 25));
+    let sigma0: expr[] -> expr = |x| xor3_word(rotr(x, 7), rotr(x, 18), shr(x, 3));
+    let sigma1: expr[] -> expr = |x| xor3_word(rotr(x, 17), rotr(x, 19), shr(x, 10));
+    // The two terms of Ch are never 1 at the same time, so their xor is their sum.
+    let Ch: expr[], expr[], expr[] -> expr = |x, y, z| word_of(|k| x[k] * y[k] + (1 - x[k]) * z[k]);
+    let Maj: expr[], expr[], expr[] -> expr = |x, y, z| word_of(|k| x[k] * y[k] + x[k] * z[k] + y[k] * z[k] - 2 * x[k] * y[k] * z[k]);
+
+    col witness state_addr;
+    col witness block_addr;
+    col witness step;
+
+    // The input state, constant in the block.
+    pol commit h_in[8];
+    array::map(h_in, |x| unchanged_until(x, LAST));
+
+    // The message schedule: In round t, w[i] is W_(t + i).
+    pol commit w[16];
+
+    // The memory accesses are only enabled in the first row of a block that
+    // has been 'used' by a call into this machine.
+    let used = array::sum(sel);
+    force_bool(used);
+    let is_call = FIRSTBLOCK * used;
+
+    // Read the input state and the block from memory.
+    link is_call ~> mem.mload state_addr, step -> h_in[0];
+    link is_call ~> mem.mload state_addr + 4, step -> h_in[1];
+    link is_call ~> mem.mload state_addr + 8, step -> h_in[2];
+    link is_call ~> mem.mload state_addr + 12, step -> h_in[3];
+    link is_call ~> mem.mload state_addr + 16, step -> h_in[4];
+    link is_call ~> mem.mload state_addr + 20, step -> h_in[5];
+    link is_call ~> mem.mload state_addr + 24, step -> h_in[6];
+    link is_call ~> mem.mload state_addr + 28, step -> h_in[7];
+    link is_call ~> mem.mload block_addr, step -> w[0];
+    link is_call ~> mem.mload block_addr + 4, step -> w[1];
+    link is_call ~> mem.mload block_addr + 8, step -> w[2];
+    link is_call ~> mem.mload block_addr + 12, step -> w[3];
+    link is_call ~> mem.mload block_addr + 16, step -> w[4];
+    link is_call ~> mem.mload block_addr + 20, step -> w[5];
+    link is_call ~> mem.mload block_addr + 24, step -> w[6];
+    link is_call ~> mem.mload block_addr + 28, step -> w[7];
+    link is_call ~> mem.mload block_addr + 32, step -> w[8];
+    link is_call ~> mem.mload block_addr + 36, step -> w[9];
+    link is_call ~> mem.mload block_addr + 40, step -> w[10];
+    link is_call ~> mem.mload block_addr + 44, step -> w[11];
+    link is_call ~> mem.mload block_addr + 48, step -> w[12];
+    link is_call ~> mem.mload block_addr + 52, step -> w[13];
+    link is_call ~> mem.mload block_addr + 56, step -> w[14];
+    link is_call ~> mem.mload block_addr + 60, step -> w[15];
+    array::new(15, |i| (w[i]' - w[i + 1]) * (1 - LAST) = 0);
+    // The bits of the words of the message schedule needed to compute W_(t + 16).
+    pol commit w1_bits[32];
+    pol commit w14_bits[32];
+    array::map(w1_bits, force_bool);
+    array::map(w14_bits, force_bool);
+    w[1] = word(w1_bits);
+    w[14] = word(w14_bits);
+    // W_(t + 16) = sigma1(W_(t + 14)) + W_(t + 9) + sigma0(W_(t + 1)) + W_t modulo 2**32
+    pol commit w16_bits[32];
+    pol commit w16_carry[2];
+    array::map(w16_bits, force_bool);
+    array::map(w16_carry, force_bool);
+    word(w16_bits) + w16_carry[0] * 2**32 + w16_carry[1] * 2**33 = sigma1(w14_bits) + w[9] + sigma0(w1_bits) + w[0];
+    (w[15]' - word(w16_bits)) * (1 - LAST) = 0;
+
+    // The working variables. The bits are needed for a, b, c, e, f and g, the words for d and h.
+    pol commit a[32];
+    pol commit b[32];
+    pol commit c[32];
+    pol commit d;
+    pol commit e[32];
+    pol commit f[32];
+    pol commit g[32];
+    pol commit h;
+    array::map(a, force_bool);
+    array::map(b, force_bool);
+    array::map(c, force_bool);
+    array::map(e, force_bool);
+    array::map(f, force_bool);
+    array::map(g, force_bool);
+
+    // Initialize the working variables with the input state.
+    FIRSTBLOCK * (word(a) - h_in[0]) = 0;
+    FIRSTBLOCK * (word(b) - h_in[1]) = 0;
+    FIRSTBLOCK * (word(c) - h_in[2]) = 0;
+    FIRSTBLOCK * (d - h_in[3]) = 0;
+    FIRSTBLOCK * (word(e) - h_in[4]) = 0;
+    FIRSTBLOCK * (word(f) - h_in[5]) = 0;
+    FIRSTBLOCK * (word(g) - h_in[6]) = 0;
+    FIRSTBLOCK * (h - h_in[7]) = 0;
+
+    // The round function.
+    let T1 = h + Sigma1(e) + Ch(e, f, g) + ROUND_CONSTANT + w[0];
+    let T2 = Sigma0(a) + Maj(a, b, c);
+    pol commit a_carry[3];
+    pol commit e_carry[3];
+    array::map(a_carry, force_bool);
+    array::map(e_carry, force_bool);
+    let next_bits: expr[] -> expr[] = |bits| array::map(bits, |bit| bit');
+    (1 - LAST) * (word(next_bits(a)) + a_carry[0] * 2**32 + a_carry[1] * 2**33 + a_carry[2] * 2**34 - (T1 + T2)) = 0;
+    array::zip(b, a, |b, a| (1 - LAST) * (b' - a) = 0);
+    array::zip(c, b, |c, b| (1 - LAST) * (c' - b) = 0);
+    (1 - LAST) * (d' - word(c)) = 0;
+    (1 - LAST) * (word(next_bits(e)) + e_carry[0] * 2**32 + e_carry[1] * 2**33 + e_carry[2] * 2**34 - (d + T1)) = 0;
+    array::zip(f, e, |f, e| (1 - LAST) * (f' - e) = 0);
+    array::zip(g, f, |g, f| (1 - LAST) * (g' - f) = 0);
+    (1 - LAST) * (h' - word(g)) = 0;
+
+    // In the last row, the output is the sum of the input state and the working variables modulo 2**32.
+    pol commit output[8];
+    pol commit output_bits[256];
+    pol commit output_carry[8];
+    array::fold(array::new(8, |i| array::new(32, |k| force_bool(output_bits[i * 32 + k]))), [], |acc, constraints| acc + constraints);
+    array::map(output_carry, force_bool);
+    let output_word: int -> expr = |i| word_of(|k| output_bits[i * 32 + k]);
+    let working_variables = [word(a), word(b), word(c), d, word(e), word(f), word(g), h];
+    array::new(8, |i| LASTBLOCK * (output[i] - output_word(i)) = 0);
+    array::new(8, |i| LASTBLOCK * (output_word(i) + output_carry[i] * 2**32 - (h_in[i] + working_variables[i])) = 0);
+
+    // The output should stay constant in the block
+    array::map(output, |o| unchanged_until(o, LAST));
+
+    // Write the new state back to memory.
+    // The output is constant in the block, so it is already known in the first row.
+    link is_call ~> mem.mstore state_addr, step + 1, output[0];
+    link is_call ~> mem.mstore state_addr + 4, step + 1, output[1];
+    link is_call ~> mem.mstore state_addr + 8, step + 1, output[2];
+    link is_call ~> mem.mstore state_addr + 12, step + 1, output[3];
+    link is_call ~> mem.mstore state_addr + 16, step + 1, output[4];
+    link is_call ~> mem.mstore state_addr + 20, step + 1, output[5];
+    link is_call ~> mem.mstore state_addr + 24, step + 1, output[6];
+    link is_call ~> mem.mstore state_addr + 28, step + 1, output[7];
+
+    // The inputs need to be 32-bit words. In the first row, this follows from the bit
+    // decompositions for all words except W_0 = w[0] and the words h_in[3] and h_in[7]
+    // of the state, which initialize d and h. These are range checked through their bytes.
+    let word_of_bytes: expr[] -> expr = |bytes| bytes[0] + bytes[1] * 2**8 + bytes[2] * 2**16 + bytes[3] * 2**24;
+    pol commit w0_bytes[4];
+    pol commit h_in3_bytes[4];
+    pol commit h_in7_bytes[4];
+    w[0] = word_of_bytes(w0_bytes);
+    h_in[3] = word_of_bytes(h_in3_bytes);
+    h_in[7] = word_of_bytes(h_in7_bytes);
+    link 1 => range.check_8 w0_bytes[0];
+    link 1 => range.check_8 w0_bytes[1];
+    link 1 => range.check_8 w0_bytes[2];
+    link 1 => range.check_8 w0_bytes[3];
+    link 1 => range.check_8 h_in3_bytes[0];
+    link 1 => range.check_8 h_in3_bytes[1];
+    link 1 => range.check_8 h_in3_bytes[2];
+    link 1 => range.check_8 h_in3_bytes[3];
+    link 1 => range.check_8 h_in7_bytes[0];
+    link 1 => range.check_8 h_in7_bytes[1];
+    link 1 => range.check_8 h_in7_bytes[2];
+    link 1 => range.check_8 h_in7_bytes[3];
+}
@@ -2,10 +2,11 @@ use std::convert::int;
 use std::utils::cross_product;
 use std::utils::unchanged_until;
 use std::array;
+use std::range_check::RangeCheck;
 
 // A read/write memory, similar to that of Polygon:
 // https://github.com/0xPolygonHermez/zkevm-proverjs/blob/main/pil/mem.pil
-machine Memory(LATCH, m_is_write) {
+machine Memory(range: RangeCheck)(LATCH, m_is_write) {
 
     // lower bound degree is 65536
 
@@ -53,10 +54,9 @@ machine Memory(LATCH, m_is_write) {
     col fixed FIRST = [1] + [0]*;
     let LAST = FIRST';
     col fixed STEP(i) { i };
-    col fixed BIT16(i) { i & 0xffff };
 
-    {m_diff_lower} in {BIT16};
-    {m_diff_upper} in {BIT16};
+    link 1 => range.check_16 m_diff_lower;
+    link 1 => range.check_16 m_diff_upper;
 
     std::utils::force_bool(m_change);
 
@@ -4,11 +4,13 @@ mod binary;
 mod check;
 mod convert;
 mod debug;
+mod divrem;
 mod field;
 mod hash;
 mod math;
 mod memory;
 mod prover;
+mod range_check;
 mod shift;
 mod split;
 mod utils;
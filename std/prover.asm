@@ -3,7 +3,8 @@
 /// valid in query functions.
 let eval: expr -> fe = [];
 
-/// The return type of a prover query function.
+/// The queries a prover query function can return.
+/// The return type of a prover query function is `std::utils::Option<Query>`.
 enum Query {
     /// Query a prover input element by index.
     Input(int),
@@ -15,6 +16,10 @@ enum Query {
     DataIdentifier(int, int)
 }
 
+/// Returns the result of a prover query function that provides `v` as the
+/// value of the witness column on the current row.
+let hint: fe -> std::utils::Option<Query> = |v| std::utils::Option::Some(Query::Hint(v));
+
 /// Constructs a challenge object.
 /// The arguments are the proof stage and the id of the challenge, in this order.
 let challenge: int, int -> expr = [];
@@ -0,0 +1,25 @@
+// A machine providing range checks and bit decompositions via lookups into fixed tables.
+// Other machines take an instance as a parameter (e.g. `machine Arith(range: RangeCheck)(...)`)
+// and link to it, for example `link 1 => range.check_16 x;`. This way, a single instance
+// and its fixed tables are shared by all machines of a program.
+// Its degree needs to be at least 65536 for the 16-bit tables to be complete.
+machine RangeCheck(latch, operation_id) {
+    // All operations are lookups into fixed tables on every row,
+    // so they share the same (constant) operation id.
+
+    // Checks that the value is in the range [0, 2**8).
+    operation check_8<0> BYTE;
+
+    // Checks that the value is in the range [0, 2**16).
+    operation check_16<0> BYTE2;
+
+    // Checks that the value is in the range [0, 2**16) and returns its most significant bit.
+    operation msb_16<0> BYTE2 -> BYTE2_MSB;
+
+    col fixed latch = [1]*;
+    col fixed operation_id = [0]*;
+
+    col fixed BYTE(i) { i & 0xff };
+    col fixed BYTE2(i) { i & 0xffff };
+    col fixed BYTE2_MSB(i) { (i & 0xffff) >> 15 };
+}
@@ -1,6 +1,7 @@
 use std::utils::unchanged_until;
 use std::utils::cross_product;
 use std::convert::int;
+use std::check::panic;
 
 machine Shift(latch, operation_id) {
     // lower bound degree is 262144
@@ -32,6 +33,7 @@ machine Shift(latch, operation_id) {
         match op(i) {
             0 => a(i) << (b(i) + (row(i) * 8)),
             1 => (a(i) << (row(i) * 8)) >> b(i),
+            _ => panic("Unknown operation")
         } & 0xffffffff
     };
 
@@ -1,5 +1,5 @@
 use std::utils::cross_product;
-use std::prover::Query;
+use std::prover::hint;
 
 // Splits an arbitrary field element into 8 u32s (in little endian order), on the BN254 field.
 machine SplitBN254(RESET, _) {
@@ -20,7 +20,7 @@ machine SplitBN254(RESET, _) {
     // A hint is provided because automatic witness generation does not
     // understand step 3 to figure out that the byte decomposition is unique.
     let select_byte: fe, int -> fe = |input, byte| std::convert::fe((std::convert::int(input) >> (byte * 8)) & 0xff);
-    col witness bytes(i) query Query::Hint(select_byte(std::prover::eval(in_acc'), (i + 1) % 32));
+    col witness bytes(i) query hint(select_byte(std::prover::eval(in_acc'), (i + 1) % 32));
     // Puts the bytes together to form the input
     col witness in_acc;
     // Factors to multiply the bytes by
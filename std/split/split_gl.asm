@@ -1,5 +1,5 @@
 use std::utils::cross_product;
-use std::prover::Query;
+use std::prover::hint;
 
 // Splits an arbitrary field element into two u32s, on the Goldilocks field.
 machine SplitGL(RESET, _) {
@@ -20,7 +20,7 @@ machine SplitGL(RESET, _) {
     // A hint is provided because automatic witness generation does not
     // understand step 3 to figure out that the byte decomposition is unique.
     let select_byte: fe, int -> fe = |input, byte| std::convert::fe((std::convert::int(input) >> (byte * 8)) & 0xff);
-    col witness bytes(i) query Query::Hint(select_byte(std::prover::eval(in_acc'), (i + 1) % 8));
+    col witness bytes(i) query hint(select_byte(std::prover::eval(in_acc'), (i + 1) % 8));
     // Puts the bytes together to form the input
     col witness in_acc;
     // Factors to multiply the bytes by
@@ -62,4 +62,29 @@ let cross_product_internal: int, int, int[] -> (int -> int)[] = |cycle_len, pos,
     } else {
         [|i| (i / cycle_len) % sizes[pos]] +
             cross_product_internal(cycle_len * sizes[pos], pos + 1, sizes)
-    };
\ No newline at end of file
+    };
+
+/// Standard Option type: A value that is either `Some(x)` or `None`.
+enum Option<T> {
+    None,
+    Some(T)
+}
+
+/// Returns the value inside `o` if it is `Some(_)` and the result of
+/// calling `f` otherwise.
+let<T> unwrap_or_else: Option<T>, (-> T) -> T = |o, f| match o {
+    Option::None => f(),
+    Option::Some(x) => x,
+};
+
+/// Standard Result type: Either a value `Ok(x)` or an error `Err(e)`.
+enum Result<T, E> {
+    Ok(T),
+    Err(E)
+}
+
+/// Turns a result into an option, discarding the error.
+let<T, E> ok: Result<T, E> -> Option<T> = |r| match r {
+    Result::Ok(x) => Option::Some(x),
+    Result::Err(_) => Option::None,
+};
@@ -0,0 +1,50 @@
+// The processing sequence of the block machine is cached after the first call.
+// In the first call (`id`), `neg` can be derived from the operation id right away,
+// in the later calls (`negate`) only after the lookup derived `a_neg`.
+// Since the output does not depend on `neg`, the cached sequence completes the block
+// without deriving `neg`, so witness generation has to retry with the default sequence.
+machine Negate(latch, operation_id) {
+
+    operation id<0> a -> b;
+
+    operation negate<1> a -> b;
+
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness a;
+    col witness b;
+    col witness a_neg;
+    col witness neg;
+    col fixed X(i) { i };
+    col fixed X_NEG(i) { -std::convert::fe(i) };
+    neg = operation_id * a_neg;
+    { a, a_neg } in { X, X_NEG };
+    b = (1 - operation_id) * a + operation_id * a_neg;
+}
+
+machine Main {
+
+    degree 32;
+
+    Negate negate;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg A;
+
+    instr id X -> Y = negate.id;
+    instr negate X -> Y = negate.negate;
+    instr assert_eq X, Y { X = Y }
+    instr assert_negated X, Y { X + Y = 0 }
+
+    function main {
+        A <== id(3);
+        assert_eq A, 3;
+        A <== negate(3);
+        assert_negated A, 3;
+        A <== negate(4);
+        assert_negated A, 4;
+        return;
+    }
+}
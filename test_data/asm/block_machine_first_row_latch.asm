@@ -0,0 +1,38 @@
+// A block machine whose latch is in the first row of a block.
+// The square is only computed in the second row.
+machine Square(latch, operation_id) {
+
+    degree 8;
+
+    operation assert_square<0> x, y ->;
+
+    col witness operation_id;
+    col fixed latch = [1, 0]*;
+    col witness x;
+    col witness y;
+
+    latch * (x' - x) = 0;
+    latch * (y' - y) = 0;
+    (1 - latch) * (y - x * x) = 0;
+}
+
+machine Main {
+
+    degree 8;
+
+    Square square;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+
+    instr assert_square X, Y -> = square.assert_square;
+
+    function main {
+        // The operation has no outputs, so all inputs of the second call are known
+        // and the machine first checks if the last block already answers it.
+        assert_square 2, 4;
+        assert_square 3, 9;
+        return;
+    }
+}
@@ -0,0 +1,68 @@
+use std::array;
+use std::memory::Memory;
+use std::range_check::RangeCheck;
+
+// A block machine that reads a word from memory and writes back its square.
+// The square is only computed in the second row of a block, so the write is
+// only issued after the first row has been processed.
+machine Square(mem: Memory)(latch, operation_id) {
+
+    // Reads the word at `addr` at step `step` and writes its square to
+    // `addr + 4` at step `step + 1`.
+    operation square<0> addr, step ->;
+
+    call_selectors sel;
+
+    col witness operation_id;
+    col fixed latch = [1, 0]*;
+    col witness addr;
+    col witness step;
+    col witness x;
+    col witness y;
+
+    // The memory accesses are only enabled in blocks that are used.
+    let is_call = latch * array::sum(sel);
+    link is_call ~> mem.mload addr, step -> x;
+    link is_call ~> mem.mstore addr + 4, step + 1, y;
+
+    latch * (x' - x) = 0;
+    latch * (y' - y) = 0;
+    (1 - latch) * (y - x * x) = 0;
+}
+
+machine Main {
+    degree 256;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg A;
+
+    col fixed STEP(i) { i };
+    RangeCheck range;
+    Memory memory(range);
+    Square square(memory);
+
+    // The square machine writes at step 2 * STEP + 1, so all memory operations use even steps.
+    instr mload X -> Y ~ memory.mload X, 2 * STEP -> Y;
+    instr mstore X, Y -> ~ memory.mstore X, 2 * STEP, Y ->;
+    instr square X ~ square.square X, 2 * STEP;
+
+    instr assert_eq X, Y {
+        X = Y
+    }
+
+    function main {
+        mstore 0, 3;
+        square 0;
+        A <== mload(4);
+        assert_eq A, 9;
+
+        mstore 8, 5;
+        square 8;
+        A <== mload(12);
+        assert_eq A, 25;
+
+        return;
+    }
+}
@@ -0,0 +1,57 @@
+machine Arith(latch, operation_id) {
+
+    degree 8;
+
+    operation add<0> x, y -> z;
+
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+    col witness z;
+    z = x + y;
+}
+
+machine Binary(latch, operation_id) {
+
+    degree 16;
+
+    operation and<0> x, y -> z;
+
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+    col witness z;
+    col fixed P_X = [0, 0, 1, 1]*;
+    col fixed P_Y = [0, 1, 0, 1]*;
+    col fixed P_Z = [0, 0, 0, 1]*;
+    { x, y, z } in { P_X, P_Y, P_Z };
+}
+
+machine Main {
+
+    degree 32;
+
+    Arith arith;
+    Binary binary;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    instr add X, Y -> Z = arith.add;
+    instr and X, Y -> Z = binary.and;
+    instr assert_eq X, Y { X = Y }
+
+    function main {
+        A <== add(2, 1);
+        assert_eq A, 3;
+        A <== and(1, 1);
+        A <== and(A, 0);
+        assert_eq A, 0;
+        return;
+    }
+}
@@ -0,0 +1,61 @@
+machine Inc(latch, operation_id) {
+
+    degree 8;
+
+    operation inc<0> x -> y;
+
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+    y = x + 1;
+}
+
+// Receives the instance of `Inc` from its parent instead of declaring its own.
+machine Assert2(inc: Inc)(latch, operation_id) {
+
+    degree 8;
+
+    operation assert2<0> x ->;
+
+    // Increment x by calling into the shared inc machine
+    link 1 => inc.inc x -> y;
+
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+
+    y = 3;
+}
+
+machine Main {
+
+    degree 8;
+
+    Inc inc;
+    Assert2 assert2(inc);
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg A;
+
+    instr inc X -> Y = inc.inc;
+    instr assert2 X -> = assert2.assert2;
+
+    instr assert_eq X, Y {
+        X = Y
+    }
+
+    instr loop {
+        pc' = pc
+    }
+
+    function main {
+        A <== inc(4);
+        assert_eq A, 5;
+        assert2(2);
+        loop;
+    }
+}
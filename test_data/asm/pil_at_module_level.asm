@@ -31,7 +31,7 @@ mod R {
 
         let C: int -> fe = |i| match i % 2 {
             0 => x,
-            1 => y,
+            _ => y,
         };
         // Use some weird type just for the sake of it.
         // We cannot call generic functions here.
@@ -0,0 +1,23 @@
+mod types {
+    trait Weight<T> {
+        weight: T -> int,
+    }
+    impl Weight<int> {
+        weight: |x| x,
+    }
+    impl<T: Weight> Weight<T[]> {
+        weight: |arr| std::utils::fold(std::array::len(arr), |i| Weight::weight(arr[i]), 0, |acc, w| acc + w),
+    }
+}
+
+mod utils {
+    use super::types::Weight;
+    let<T: Weight> double_weight: T -> int = |x| 2 * Weight::weight(x);
+}
+
+machine Empty {
+    let w: int[] = [1, 2, 3];
+    col fixed C(i) { i * utils::double_weight(w) };
+    col witness x;
+    x = x * x;
+}
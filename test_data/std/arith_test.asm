@@ -1,4 +1,5 @@
 use std::arith::Arith;
+use std::range_check::RangeCheck;
 
 machine Main{
     degree 65536;
@@ -70,7 +71,8 @@ machine Main{
     reg t_1_6;
     reg t_1_7;
 
-    Arith arith;
+    RangeCheck range;
+    Arith arith(range);
 
     instr affine_256 A0, A1, A2, A3, A4, A5, A6, A7, B0, B1, B2, B3, B4, B5, B6, B7, C0, C1, C2, C3, C4, C5, C6, C7 -> D0, D1, D2, D3, D4, D5, D6, D7, E0, E1, E2, E3, E4, E5, E6, E7 ~ arith.affine_256;
     instr ec_add A0, A1, A2, A3, A4, A5, A6, A7, B0, B1, B2, B3, B4, B5, B6, B7, C0, C1, C2, C3, C4, C5, C6, C7, D0, D1, D2, D3, D4, D5, D6, D7 -> E0, E1, E2, E3, E4, E5, E6, E7, F0, F1, F2, F3, F4, F5, F6, F7 ~ arith.ec_add;
@@ -0,0 +1,85 @@
+use std::divrem::DivRem;
+use std::range_check::RangeCheck;
+
+machine Main {
+    reg pc[@pc];
+    reg X0[<=];
+    reg X1[<=];
+    reg X2[<=];
+    reg X3[<=];
+    reg A;
+    reg B;
+
+    degree 65536;
+
+    RangeCheck range;
+    DivRem divrem(range);
+
+    instr divremu X0, X1 -> X2, X3 = divrem.divremu;
+    instr divrem X0, X1 -> X2, X3 = divrem.divrem;
+
+    instr assert_eq X0, X1 {
+        X0 = X1
+    }
+
+    function main {
+
+        // Unsigned
+        A, B <== divremu(17, 5);
+        assert_eq A, 3;
+        assert_eq B, 2;
+        A, B <== divremu(0xffffffff, 0x10);
+        assert_eq A, 0x0fffffff;
+        assert_eq B, 0xf;
+        A, B <== divremu(0x80000000, 0xffffffff);
+        assert_eq A, 0;
+        assert_eq B, 0x80000000;
+        A, B <== divremu(0x12345678, 0x12345678);
+        assert_eq A, 1;
+        assert_eq B, 0;
+
+        // Unsigned division by zero
+        A, B <== divremu(0x12345678, 0);
+        assert_eq A, 0xffffffff;
+        assert_eq B, 0x12345678;
+
+        // Signed: -17 / 5 = -3, remainder -2
+        A, B <== divrem(0xffffffef, 5);
+        assert_eq A, 0xfffffffd;
+        assert_eq B, 0xfffffffe;
+        // 17 / -5 = -3, remainder 2
+        A, B <== divrem(17, 0xfffffffb);
+        assert_eq A, 0xfffffffd;
+        assert_eq B, 2;
+        // -17 / -5 = 3, remainder -2
+        A, B <== divrem(0xffffffef, 0xfffffffb);
+        assert_eq A, 3;
+        assert_eq B, 0xfffffffe;
+        // -4 / 5 = 0, remainder -4
+        A, B <== divrem(0xfffffffc, 5);
+        assert_eq A, 0;
+        assert_eq B, 0xfffffffc;
+        // 4 / -5 = 0, remainder 4
+        A, B <== divrem(4, 0xfffffffb);
+        assert_eq A, 0;
+        assert_eq B, 4;
+
+        // Signed division by zero
+        A, B <== divrem(0xffffffef, 0);
+        assert_eq A, 0xffffffff;
+        assert_eq B, 0xffffffef;
+        A, B <== divrem(17, 0);
+        assert_eq A, 0xffffffff;
+        assert_eq B, 17;
+
+        // Signed overflow
+        A, B <== divrem(0x80000000, 0xffffffff);
+        assert_eq A, 0x80000000;
+        assert_eq B, 0;
+        A, B <== divrem(0x80000000, 1);
+        assert_eq A, 0x80000000;
+        assert_eq B, 0;
+
+        return;
+    }
+}
@@ -0,0 +1,392 @@
+use std::hash::keccakf::KeccakF;
+use std::memory::Memory;
+use std::range_check::RangeCheck;
+
+machine Main {
+    degree 512;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg A;
+
+    col fixed STEP(i) { i };
+    RangeCheck range;
+    Memory memory(range);
+    KeccakF keccakf(memory);
+
+    // The permutation reads the state at step 2 * STEP and writes it at
+    // step 2 * STEP + 1, so all memory operations use even steps.
+    instr mload X -> Y ~ memory.mload X, 2 * STEP -> Y;
+    instr mstore X, Y -> ~ memory.mstore X, 2 * STEP, Y ->;
+    instr keccakf X ~ keccakf.keccakf X, 2 * STEP;
+
+    instr assert_eq X, Y {
+        X = Y
+    }
+
+    function main {
+
+        // Permutation of the zero state (uninitialized memory), see
+        // https://github.com/XKCP/XKCP/blob/master/tests/TestVectors/KeccakF-1600-IntermediateValues.txt
+        keccakf 0;
+        A <== mload(0);
+        assert_eq A, 0x40e1dde7;
+        A <== mload(4);
+        assert_eq A, 0xf1258f79;
+        A <== mload(8);
+        assert_eq A, 0x33c0478a;
+        A <== mload(12);
+        assert_eq A, 0x84d5ccf9;
+        A <== mload(16);
+        assert_eq A, 0xa65aa9ee;
+        A <== mload(20);
+        assert_eq A, 0xd598261e;
+        A <== mload(24);
+        assert_eq A, 0x6f80494d;
+        A <== mload(28);
+        assert_eq A, 0xbd154730;
+        A <== mload(32);
+        assert_eq A, 0x6253d057;
+        A <== mload(36);
+        assert_eq A, 0x8b284e05;
+        A <== mload(40);
+        assert_eq A, 0x7f8e6fd4;
+        A <== mload(44);
+        assert_eq A, 0xff97a42d;
+        A <== mload(48);
+        assert_eq A, 0xa44647c4;
+        A <== mload(52);
+        assert_eq A, 0x90fee5a0;
+        A <== mload(56);
+        assert_eq A, 0xd6192e76;
+        A <== mload(60);
+        assert_eq A, 0x8c5bda0c;
+        A <== mload(64);
+        assert_eq A, 0x1b19059c;
+        A <== mload(68);
+        assert_eq A, 0xad30a6f7;
+        A <== mload(72);
+        assert_eq A, 0xd08ffc64;
+        A <== mload(76);
+        assert_eq A, 0x30935ab7;
+        A <== mload(80);
+        assert_eq A, 0x2317d635;
+        A <== mload(84);
+        assert_eq A, 0xeb5aa93f;
+        A <== mload(88);
+        assert_eq A, 0xd712103;
+        A <== mload(92);
+        assert_eq A, 0xa9a6e626;
+        A <== mload(96);
+        assert_eq A, 0xdbcf555f;
+        A <== mload(100);
+        assert_eq A, 0x81a57c16;
+        A <== mload(104);
+        assert_eq A, 0x347c826;
+        A <== mload(108);
+        assert_eq A, 0x43b831cd;
+        A <== mload(112);
+        assert_eq A, 0x11a5569f;
+        A <== mload(116);
+        assert_eq A, 0x1f22f1a;
+        A <== mload(120);
+        assert_eq A, 0x21d9ae61;
+        A <== mload(124);
+        assert_eq A, 0x5e5635a;
+        A <== mload(128);
+        assert_eq A, 0x8cc970f2;
+        A <== mload(132);
+        assert_eq A, 0x64befef2;
+        A <== mload(136);
+        assert_eq A, 0x7bc46611;
+        A <== mload(140);
+        assert_eq A, 0x61367095;
+        A <== mload(144);
+        assert_eq A, 0x4fd00ecb;
+        A <== mload(148);
+        assert_eq A, 0xb87c5a55;
+        A <== mload(152);
+        assert_eq A, 0x1ccf32c8;
+        A <== mload(156);
+        assert_eq A, 0x8c3ee88a;
+        A <== mload(160);
+        assert_eq A, 0xae3a2614;
+        A <== mload(164);
+        assert_eq A, 0x940c7922;
+        A <== mload(168);
+        assert_eq A, 0xa2c509e4;
+        A <== mload(172);
+        assert_eq A, 0x1841f924;
+        A <== mload(176);
+        assert_eq A, 0xe70465c2;
+        A <== mload(180);
+        assert_eq A, 0x16f53526;
+        A <== mload(184);
+        assert_eq A, 0x7f30a13b;
+        A <== mload(188);
+        assert_eq A, 0x75f644e9;
+        A <== mload(192);
+        assert_eq A, 0x5ceca249;
+        A <== mload(196);
+        assert_eq A, 0xeaf1ff7b;
+
+        // Applying the permutation again yields the second test vector.
+        keccakf 0;
+        A <== mload(0);
+        assert_eq A, 0xf96ecb3c;
+        A <== mload(4);
+        assert_eq A, 0x2d5c954d;
+        A <== mload(8);
+        assert_eq A, 0x7057b56d;
+        A <== mload(12);
+        assert_eq A, 0x6a332cd0;
+        A <== mload(16);
+        assert_eq A, 0x70d76b6c;
+        A <== mload(20);
+        assert_eq A, 0x93d8d12;
+        A <== mload(24);
+        assert_eq A, 0x5569d094;
+        A <== mload(28);
+        assert_eq A, 0x8a20d9b2;
+        A <== mload(32);
+        assert_eq A, 0xe5e7f156;
+        A <== mload(36);
+        assert_eq A, 0x4f9c4f99;
+        A <== mload(40);
+        assert_eq A, 0xda65fb38;
+        A <== mload(44);
+        assert_eq A, 0xf957b9a2;
+        A <== mload(48);
+        assert_eq A, 0x1275af0d;
+        A <== mload(52);
+        assert_eq A, 0x85773dae;
+        A <== mload(56);
+        assert_eq A, 0xc3d810f7;
+        A <== mload(60);
+        assert_eq A, 0xfaf4f247;
+        A <== mload(64);
+        assert_eq A, 0xf79a8759;
+        A <== mload(68);
+        assert_eq A, 0x1f1b9ee6;
+        A <== mload(72);
+        assert_eq A, 0xee98b425;
+        A <== mload(76);
+        assert_eq A, 0xe4fecc0f;
+        A <== mload(80);
+        assert_eq A, 0xb9ce68a1;
+        A <== mload(84);
+        assert_eq A, 0x68ce61b6;
+        A <== mload(88);
+        assert_eq A, 0xba8f974f;
+        A <== mload(92);
+        assert_eq A, 0xdeea66c4;
+        A <== mload(96);
+        assert_eq A, 0x6eafb1f5;
+        A <== mload(100);
+        assert_eq A, 0x33c43d83;
+        A <== mload(104);
+        assert_eq A, 0x2719dbd9;
+        A <== mload(108);
+        assert_eq A, 0xe0065404;
+        A <== mload(112);
+        assert_eq A, 0x9831265;
+        A <== mload(116);
+        assert_eq A, 0x7cf8a9f0;
+        A <== mload(120);
+        assert_eq A, 0xbf174743;
+        A <== mload(124);
+        assert_eq A, 0xfd5449a6;
+        A <== mload(128);
+        assert_eq A, 0xd8994b40;
+        A <== mload(132);
+        assert_eq A, 0x97ddad33;
+        A <== mload(136);
+        assert_eq A, 0x5d0be774;
+        A <== mload(140);
+        assert_eq A, 0x48ead5fc;
+        A <== mload(144);
+        assert_eq A, 0x55b7b03c;
+        A <== mload(148);
+        assert_eq A, 0xe3b8c8ee;
+        A <== mload(152);
+        assert_eq A, 0x649e42e9;
+        A <== mload(156);
+        assert_eq A, 0x91a0226e;
+        A <== mload(160);
+        assert_eq A, 0xe7badd7b;
+        A <== mload(164);
+        assert_eq A, 0x900e3129;
+        A <== mload(168);
+        assert_eq A, 0xfaa3cce8;
+        A <== mload(172);
+        assert_eq A, 0x202a9ec5;
+        A <== mload(176);
+        assert_eq A, 0x4e1c3db6;
+        A <== mload(180);
+        assert_eq A, 0x5b340246;
+        A <== mload(184);
+        assert_eq A, 0xa44c1059;
+        A <== mload(188);
+        assert_eq A, 0x609f4e62;
+        A <== mload(192);
+        assert_eq A, 0x6a8fbf5c;
+        A <== mload(196);
+        assert_eq A, 0x20d06cd2;
+
+        // Lane i is 0x0101010101010101 * i.
+        mstore 0, 0x0;
+        mstore 4, 0x0;
+        mstore 8, 0x1010101;
+        mstore 12, 0x1010101;
+        mstore 16, 0x2020202;
+        mstore 20, 0x2020202;
+        mstore 24, 0x3030303;
+        mstore 28, 0x3030303;
+        mstore 32, 0x4040404;
+        mstore 36, 0x4040404;
+        mstore 40, 0x5050505;
+        mstore 44, 0x5050505;
+        mstore 48, 0x6060606;
+        mstore 52, 0x6060606;
+        mstore 56, 0x7070707;
+        mstore 60, 0x7070707;
+        mstore 64, 0x8080808;
+        mstore 68, 0x8080808;
+        mstore 72, 0x9090909;
+        mstore 76, 0x9090909;
+        mstore 80, 0xa0a0a0a;
+        mstore 84, 0xa0a0a0a;
+        mstore 88, 0xb0b0b0b;
+        mstore 92, 0xb0b0b0b;
+        mstore 96, 0xc0c0c0c;
+        mstore 100, 0xc0c0c0c;
+        mstore 104, 0xd0d0d0d;
+        mstore 108, 0xd0d0d0d;
+        mstore 112, 0xe0e0e0e;
+        mstore 116, 0xe0e0e0e;
+        mstore 120, 0xf0f0f0f;
+        mstore 124, 0xf0f0f0f;
+        mstore 128, 0x10101010;
+        mstore 132, 0x10101010;
+        mstore 136, 0x11111111;
+        mstore 140, 0x11111111;
+        mstore 144, 0x12121212;
+        mstore 148, 0x12121212;
+        mstore 152, 0x13131313;
+        mstore 156, 0x13131313;
+        mstore 160, 0x14141414;
+        mstore 164, 0x14141414;
+        mstore 168, 0x15151515;
+        mstore 172, 0x15151515;
+        mstore 176, 0x16161616;
+        mstore 180, 0x16161616;
+        mstore 184, 0x17171717;
+        mstore 188, 0x17171717;
+        mstore 192, 0x18181818;
+        mstore 196, 0x18181818;
+        keccakf 0;
+        A <== mload(0);
+        assert_eq A, 0x8e6aadae;
+        A <== mload(4);
+        assert_eq A, 0x9228104e;
+        A <== mload(8);
+        assert_eq A, 0xfde91c4;
+        A <== mload(12);
+        assert_eq A, 0xcfab7e1a;
+        A <== mload(16);
+        assert_eq A, 0x47799456;
+        A <== mload(20);
+        assert_eq A, 0x2d0b4125;
+        A <== mload(24);
+        assert_eq A, 0xfcab18d7;
+        A <== mload(28);
+        assert_eq A, 0x68e01354;
+        A <== mload(32);
+        assert_eq A, 0xa2e76bb;
+        A <== mload(36);
+        assert_eq A, 0xcb2a452f;
+        A <== mload(40);
+        assert_eq A, 0x1aebe17a;
+        A <== mload(44);
+        assert_eq A, 0x14cf4f05;
+        A <== mload(48);
+        assert_eq A, 0x254e2eff;
+        A <== mload(52);
+        assert_eq A, 0xffff4672;
+        A <== mload(56);
+        assert_eq A, 0xf1e240fe;
+        A <== mload(60);
+        assert_eq A, 0x6042d21f;
+        A <== mload(64);
+        assert_eq A, 0xf1886a69;
+        A <== mload(68);
+        assert_eq A, 0x3f78769c;
+        A <== mload(72);
+        assert_eq A, 0xa1048b61;
+        A <== mload(76);
+        assert_eq A, 0xf2e8a62b;
+        A <== mload(80);
+        assert_eq A, 0x2677db21;
+        A <== mload(84);
+        assert_eq A, 0x7b0ad637;
+        A <== mload(88);
+        assert_eq A, 0x6bf1feb6;
+        A <== mload(92);
+        assert_eq A, 0x17d5fd00;
+        A <== mload(96);
+        assert_eq A, 0xcc7d47f6;
+        A <== mload(100);
+        assert_eq A, 0x158c3084;
+        A <== mload(104);
+        assert_eq A, 0xb02dd9ef;
+        A <== mload(108);
+        assert_eq A, 0x35ccc1aa;
+        A <== mload(112);
+        assert_eq A, 0xa9ff6d3f;
+        A <== mload(116);
+        assert_eq A, 0xfe3f4d09;
+        A <== mload(120);
+        assert_eq A, 0xc99e52e;
+        A <== mload(124);
+        assert_eq A, 0xa7c0e43f;
+        A <== mload(128);
+        assert_eq A, 0x8329a845;
+        A <== mload(132);
+        assert_eq A, 0xa7fa0b4c;
+        A <== mload(136);
+        assert_eq A, 0xacf9dc;
+        A <== mload(140);
+        assert_eq A, 0xbe395028;
+        A <== mload(144);
+        assert_eq A, 0xb551473;
+        A <== mload(148);
+        assert_eq A, 0x5617217;
+        A <== mload(152);
+        assert_eq A, 0x8826fc9b;
+        A <== mload(156);
+        assert_eq A, 0x1a7c4c2f;
+        A <== mload(160);
+        assert_eq A, 0xf1dc2f60;
+        A <== mload(164);
+        assert_eq A, 0x79ffe34e;
+        A <== mload(168);
+        assert_eq A, 0x7f4ee911;
+        A <== mload(172);
+        assert_eq A, 0xb54a2625;
+        A <== mload(176);
+        assert_eq A, 0xf3bed743;
+        A <== mload(180);
+        assert_eq A, 0xc5737d24;
+        A <== mload(184);
+        assert_eq A, 0x47a9c2ff;
+        A <== mload(188);
+        assert_eq A, 0xc0458760;
+        A <== mload(192);
+        assert_eq A, 0x73cfd3b8;
+        A <== mload(196);
+        assert_eq A, 0xde39cf1e;
+
+        return;
+    }
+}
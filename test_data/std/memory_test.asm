@@ -1,4 +1,5 @@
 use std::memory::Memory;
+use std::range_check::RangeCheck;
 
 machine Main {
     reg pc[@pc];
@@ -9,7 +10,8 @@ machine Main {
     degree 65536;
 
     col fixed STEP(i) { i };
-    Memory memory;
+    RangeCheck range;
+    Memory memory(range);
 
     instr mload X -> Y ~ memory.mload X, STEP -> Y;
     instr mstore X, Y -> ~ memory.mstore X, STEP, Y ->;
@@ -0,0 +1,41 @@
+use std::range_check::RangeCheck;
+
+machine Main {
+    reg pc[@pc];
+    reg X0[<=];
+    reg X1[<=];
+    reg A;
+
+    degree 65536;
+
+    RangeCheck range;
+
+    instr check_8 X0 = range.check_8;
+    instr check_16 X0 = range.check_16;
+    instr msb_16 X0 -> X1 = range.msb_16;
+
+    instr assert_eq X0, X1 {
+        X0 = X1
+    }
+
+    function main {
+        check_8 0;
+        check_8 0x7f;
+        check_8 0xff;
+
+        check_16 0;
+        check_16 0x100;
+        check_16 0xffff;
+
+        A <== msb_16(0);
+        assert_eq A, 0;
+        A <== msb_16(0x7fff);
+        assert_eq A, 0;
+        A <== msb_16(0x8000);
+        assert_eq A, 1;
+        A <== msb_16(0xffff);
+        assert_eq A, 1;
+
+        return;
+    }
+}
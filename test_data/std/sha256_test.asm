@@ -0,0 +1,137 @@
+use std::hash::sha256::Sha256;
+use std::memory::Memory;
+use std::range_check::RangeCheck;
+
+machine Main {
+    degree 256;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg A;
+
+    col fixed STEP(i) { i };
+    RangeCheck range;
+    Memory memory(range);
+    Sha256 sha256(memory, range);
+
+    // The compression reads at step 2 * STEP and writes at step 2 * STEP + 1,
+    // so all memory operations use even steps.
+    instr mload X -> Y ~ memory.mload X, 2 * STEP -> Y;
+    instr mstore X, Y -> ~ memory.mstore X, 2 * STEP, Y ->;
+    // The state is stored at address X and the block at address Y.
+    instr sha256 X, Y ~ sha256.sha256 X, Y, 2 * STEP;
+
+    instr assert_eq X, Y {
+        X = Y
+    }
+
+    function main {
+        // The initial hash value.
+        mstore 0, 0x6a09e667;
+        mstore 4, 0xbb67ae85;
+        mstore 8, 0x3c6ef372;
+        mstore 12, 0xa54ff53a;
+        mstore 16, 0x510e527f;
+        mstore 20, 0x9b05688c;
+        mstore 24, 0x1f83d9ab;
+        mstore 28, 0x5be0cd19;
+
+        // SHA-256("abc"), a single block.
+        mstore 64, 0x61626380;
+        mstore 68, 0x00000000;
+        mstore 72, 0x00000000;
+        mstore 76, 0x00000000;
+        mstore 80, 0x00000000;
+        mstore 84, 0x00000000;
+        mstore 88, 0x00000000;
+        mstore 92, 0x00000000;
+        mstore 96, 0x00000000;
+        mstore 100, 0x00000000;
+        mstore 104, 0x00000000;
+        mstore 108, 0x00000000;
+        mstore 112, 0x00000000;
+        mstore 116, 0x00000000;
+        mstore 120, 0x00000000;
+        mstore 124, 0x00000018;
+        sha256 0, 64;
+        A <== mload(0);
+        assert_eq A, 0xba7816bf;
+        A <== mload(4);
+        assert_eq A, 0x8f01cfea;
+        A <== mload(8);
+        assert_eq A, 0x414140de;
+        A <== mload(12);
+        assert_eq A, 0x5dae2223;
+        A <== mload(16);
+        assert_eq A, 0xb00361a3;
+        A <== mload(20);
+        assert_eq A, 0x96177a9c;
+        A <== mload(24);
+        assert_eq A, 0xb410ff61;
+        A <== mload(28);
+        assert_eq A, 0xf20015ad;
+
+        // SHA-256("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"), two blocks.
+        mstore 0, 0x6a09e667;
+        mstore 4, 0xbb67ae85;
+        mstore 8, 0x3c6ef372;
+        mstore 12, 0xa54ff53a;
+        mstore 16, 0x510e527f;
+        mstore 20, 0x9b05688c;
+        mstore 24, 0x1f83d9ab;
+        mstore 28, 0x5be0cd19;
+        mstore 64, 0x61626364;
+        mstore 68, 0x62636465;
+        mstore 72, 0x63646566;
+        mstore 76, 0x64656667;
+        mstore 80, 0x65666768;
+        mstore 84, 0x66676869;
+        mstore 88, 0x6768696a;
+        mstore 92, 0x68696a6b;
+        mstore 96, 0x696a6b6c;
+        mstore 100, 0x6a6b6c6d;
+        mstore 104, 0x6b6c6d6e;
+        mstore 108, 0x6c6d6e6f;
+        mstore 112, 0x6d6e6f70;
+        mstore 116, 0x6e6f7071;
+        mstore 120, 0x80000000;
+        mstore 124, 0x00000000;
+        sha256 0, 64;
+        mstore 64, 0x00000000;
+        mstore 68, 0x00000000;
+        mstore 72, 0x00000000;
+        mstore 76, 0x00000000;
+        mstore 80, 0x00000000;
+        mstore 84, 0x00000000;
+        mstore 88, 0x00000000;
+        mstore 92, 0x00000000;
+        mstore 96, 0x00000000;
+        mstore 100, 0x00000000;
+        mstore 104, 0x00000000;
+        mstore 108, 0x00000000;
+        mstore 112, 0x00000000;
+        mstore 116, 0x00000000;
+        mstore 120, 0x00000000;
+        mstore 124, 0x000001c0;
+        sha256 0, 64;
+        A <== mload(0);
+        assert_eq A, 0x248d6a61;
+        A <== mload(4);
+        assert_eq A, 0xd20638b8;
+        A <== mload(8);
+        assert_eq A, 0xe5c02693;
+        A <== mload(12);
+        assert_eq A, 0x0c3e6039;
+        A <== mload(16);
+        assert_eq A, 0xa33ce459;
+        A <== mload(20);
+        assert_eq A, 0x64ff2167;
+        A <== mload(24);
+        assert_eq A, 0xf6ecedd4;
+        A <== mload(28);
+        assert_eq A, 0x19db06c1;
+
+        return;
+    }
+}